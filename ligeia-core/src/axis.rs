@@ -0,0 +1,66 @@
+//! A vertical value axis for plotting signals as numbers: fitting it to the values shown, and
+//! labelled ticks at round numbers along it.
+
+use crate::format::{self, Separators};
+
+/// How much of the span between the smallest and largest values is left empty above and below
+/// them, so the plot doesn't touch the edges of its track.
+const PADDING: f64 = 0.05;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tick {
+    pub value: f64,
+    pub label: String,
+}
+
+/// The range an axis covers to show values from `min` to `max`, as from
+/// [`value_range`](crate::stats::value_range).
+///
+/// A single value is put in the middle of a range one wide.
+pub fn auto_range((min, max): (f64, f64)) -> (f64, f64) {
+    let span = max - min;
+    if span <= 0.0 {
+        return (min - 0.5, max + 0.5);
+    }
+    (min - span * PADDING, max + span * PADDING)
+}
+
+/// The step between ticks: the smallest 1, 2 or 5 times a power of ten that makes no more than
+/// `max_ticks` intervals over `span`.
+fn step(span: f64, max_ticks: usize) -> f64 {
+    let rough = span / max_ticks.max(1) as f64;
+    let magnitude = 10f64.powf(rough.log10().floor());
+    [1.0, 2.0, 5.0, 10.0]
+        .iter()
+        .map(|multiple| multiple * magnitude)
+        .find(|&step| step >= rough)
+        .unwrap_or(10.0 * magnitude)
+}
+
+/// Ticks at round numbers from `min` to `max`, with no more than `max_ticks` intervals between
+/// them. Labels have as many decimal places as the step between ticks needs.
+pub fn ticks(range: (f64, f64), max_ticks: usize) -> Vec<Tick> {
+    ticks_with(range, max_ticks, Separators::default())
+}
+
+/// Like [`ticks`], with labels written with `separators`.
+pub fn ticks_with((min, max): (f64, f64), max_ticks: usize, separators: Separators) -> Vec<Tick> {
+    let span = max - min;
+    if !(span > 0.0 && span.is_finite()) {
+        return vec![];
+    }
+
+    let step = step(span, max_ticks);
+    let decimals = (-step.log10().floor()).max(0.0) as usize;
+    let first = (min / step).ceil() as i64;
+    let last = (max / step).floor() as i64;
+    (first..=last)
+        .map(|index| {
+            let value = index as f64 * step;
+            Tick {
+                value,
+                label: format::format_decimal(value, decimals, separators),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,135 @@
+//! Unsigned integers of any width, for buses too wide to fit in a `u64`.
+
+use std::{cmp::Ordering, fmt};
+
+/// An unsigned integer, stored as 64-bit limbs with the least significant first.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct BigUint {
+    /// Never has trailing zero limbs, so that equal numbers have equal limbs.
+    limbs: Vec<u64>,
+}
+
+impl BigUint {
+    pub fn from_limbs(mut limbs: Vec<u64>) -> Self {
+        while limbs.last() == Some(&0) {
+            limbs.pop();
+        }
+        Self { limbs }
+    }
+
+    /// The limbs, least significant first, without any leading zero limbs.
+    pub fn limbs(&self) -> &[u64] {
+        &self.limbs
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    /// The number of bits needed to hold the number, which is 0 for zero.
+    pub fn bits(&self) -> u64 {
+        match self.limbs.last() {
+            Some(last) => self.limbs.len() as u64 * 64 - last.leading_zeros() as u64,
+            None => 0,
+        }
+    }
+
+    /// Bit `index`, counting from the least significant.
+    pub fn bit(&self, index: u64) -> bool {
+        match self.limbs.get((index / 64) as usize) {
+            Some(limb) => limb >> (index % 64) & 1 == 1,
+            None => false,
+        }
+    }
+
+    /// The number, if it fits in a `u64`.
+    pub fn to_u64(&self) -> Option<u64> {
+        match self.limbs[..] {
+            [] => Some(0),
+            [limb] => Some(limb),
+            _ => None,
+        }
+    }
+
+    /// Parse digits in `radix`, which must be from 2 to 36, without any sign or prefix.
+    pub fn from_str_radix(text: &str, radix: u32) -> Option<Self> {
+        if text.is_empty() {
+            return None;
+        }
+
+        let mut number = Self::default();
+        for c in text.chars() {
+            number.mul_add(radix as u64, c.to_digit(radix)? as u64);
+        }
+        Some(number)
+    }
+
+    /// The digits of the number in `radix`, which must be from 2 to 36, using lowercase letters.
+    pub fn to_str_radix(&self, radix: u32) -> String {
+        assert!((2..=36).contains(&radix), "radix out of range");
+
+        let mut number = self.clone();
+        let mut digits = vec![];
+        loop {
+            let digit = number.div_rem(radix as u64);
+            digits.push(char::from_digit(digit as u32, radix).unwrap());
+            if number.is_zero() {
+                return digits.into_iter().rev().collect();
+            }
+        }
+    }
+
+    /// `self = self * mul + add`
+    fn mul_add(&mut self, mul: u64, add: u64) {
+        let mut carry = add as u128;
+        for limb in &mut self.limbs {
+            let value = *limb as u128 * mul as u128 + carry;
+            *limb = value as u64;
+            carry = value >> 64;
+        }
+        if carry != 0 {
+            self.limbs.push(carry as u64);
+        }
+    }
+
+    /// Divide in place, returning the remainder.
+    fn div_rem(&mut self, divisor: u64) -> u64 {
+        let mut remainder = 0u128;
+        for limb in self.limbs.iter_mut().rev() {
+            let value = remainder << 64 | *limb as u128;
+            *limb = (value / divisor as u128) as u64;
+            remainder = value % divisor as u128;
+        }
+        if self.limbs.last() == Some(&0) {
+            self.limbs.pop();
+        }
+        remainder as u64
+    }
+}
+
+impl fmt::Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad_integral(true, "", &self.to_str_radix(10))
+    }
+}
+
+impl From<u64> for BigUint {
+    fn from(value: u64) -> Self {
+        Self::from_limbs(vec![value])
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.limbs
+            .len()
+            .cmp(&other.limbs.len())
+            .then_with(|| self.limbs.iter().rev().cmp(other.limbs.iter().rev()))
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
@@ -0,0 +1,147 @@
+//! Telling whether a cache of a processed waveform is safe to use.
+//!
+//! A cache that was being written when the viewer crashed, or that was made from an older
+//! version of the source file, must never be read back. Each cache gets a manifest next to it,
+//! which is only written once the cache is complete and synced, and which records enough about
+//! the source file to notice when it's changed.
+//!
+//! Writing a cache goes [`begin`], then writing and syncing the cache file, then [`finish`].
+//! Before reading one, check it with [`is_valid`], and reparse the source if it isn't.
+
+use std::{
+    fs::{self, File},
+    hash::Hasher,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use twox_hash::XxHash64;
+
+const MAGIC: &[u8; 4] = b"LGCM";
+const VERSION: u32 = 1;
+/// Ends every manifest, so one that was cut off is never mistaken for a complete one.
+const COMPLETE: &[u8; 4] = b"DONE";
+/// The magic, version, source fingerprint, cache length and completion marker.
+const MANIFEST_LEN: usize = 52;
+
+/// How much of each end of the source file is hashed.
+const HASHED_LEN: u64 = 1024 * 1024;
+
+/// Enough about a source file to tell if it's changed, without reading all of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fingerprint {
+    pub len: u64,
+    /// Nanoseconds since the Unix epoch, where the platform has modification times.
+    pub modified: u128,
+    /// The xxHash64 of the first and last MiB of the file.
+    pub hash: u64,
+}
+
+impl Fingerprint {
+    pub fn of(path: &Path) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        let metadata = file.metadata()?;
+        let len = metadata.len();
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |duration| duration.as_nanos());
+
+        let mut hasher = XxHash64::with_seed(0);
+        let mut buffer = vec![];
+        (&mut file).take(HASHED_LEN).read_to_end(&mut buffer)?;
+        hasher.write(&buffer);
+
+        if len > HASHED_LEN {
+            buffer.clear();
+            file.seek(SeekFrom::Start(
+                len.saturating_sub(HASHED_LEN).max(HASHED_LEN),
+            ))?;
+            file.take(HASHED_LEN).read_to_end(&mut buffer)?;
+            hasher.write(&buffer);
+        }
+
+        Ok(Self {
+            len,
+            modified,
+            hash: hasher.finish(),
+        })
+    }
+}
+
+/// The manifest that goes along with `cache`.
+pub fn manifest_path(cache: &Path) -> PathBuf {
+    let mut name = cache.as_os_str().to_owned();
+    name.push(".manifest");
+    PathBuf::from(name)
+}
+
+/// Start writing a cache, invalidating whatever was there before.
+pub fn begin(cache: &Path) -> io::Result<File> {
+    match fs::remove_file(manifest_path(cache)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    File::create(cache)
+}
+
+/// Mark a cache as complete, once all of it has been written to `file`.
+///
+/// The manifest is written beside it and then renamed into place, so it's either all there or
+/// not there at all.
+pub fn finish(cache: &Path, file: File, source: &Fingerprint) -> io::Result<()> {
+    file.sync_all()?;
+    let cache_len = file.metadata()?.len();
+    drop(file);
+
+    let mut manifest = Vec::with_capacity(MANIFEST_LEN);
+    manifest.extend_from_slice(MAGIC);
+    manifest.extend_from_slice(&VERSION.to_le_bytes());
+    manifest.extend_from_slice(&source.len.to_le_bytes());
+    manifest.extend_from_slice(&source.modified.to_le_bytes());
+    manifest.extend_from_slice(&source.hash.to_le_bytes());
+    manifest.extend_from_slice(&cache_len.to_le_bytes());
+    manifest.extend_from_slice(COMPLETE);
+
+    let path = manifest_path(cache);
+    let mut partial = path.clone().into_os_string();
+    partial.push(".partial");
+    let partial = PathBuf::from(partial);
+
+    let mut writer = File::create(&partial)?;
+    writer.write_all(&manifest)?;
+    writer.sync_all()?;
+    drop(writer);
+
+    fs::rename(&partial, &path)
+}
+
+/// Whether `cache` was completely written, from the file at `source` as it is now.
+pub fn is_valid(cache: &Path, source: &Path) -> bool {
+    let check = || -> io::Result<bool> {
+        let manifest = fs::read(manifest_path(cache))?;
+        if manifest.len() != MANIFEST_LEN || &manifest[..4] != MAGIC || &manifest[48..] != COMPLETE
+        {
+            return Ok(false);
+        }
+
+        let u64_at = |at: usize| u64::from_le_bytes(manifest[at..at + 8].try_into().unwrap());
+        if u32::from_le_bytes(manifest[4..8].try_into().unwrap()) != VERSION {
+            return Ok(false);
+        }
+
+        let recorded = Fingerprint {
+            len: u64_at(8),
+            modified: u128::from_le_bytes(manifest[16..32].try_into().unwrap()),
+            hash: u64_at(32),
+        };
+        let cache_len = u64_at(40);
+
+        Ok(fs::metadata(cache)?.len() == cache_len && Fingerprint::of(source)? == recorded)
+    };
+
+    // Anything that can't be read is as good as missing.
+    check().unwrap_or(false)
+}
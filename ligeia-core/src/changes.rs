@@ -0,0 +1,323 @@
+//! Iterating over a storage's changes as typed logic values, for callers that would rather pull
+//! changes than be called with raw bytes, and shouldn't have to know how each type is packed.
+
+use std::{iter::Peekable, marker::PhantomData};
+
+use crate::{
+    logic,
+    meta::{StorageType, Timesteps},
+    read_packed, read_varint,
+    scratch::Scratch,
+    CommittedBlocks, Error, BLOCK_HEADER_SIZE,
+};
+
+/// A kind of logic that storages hold, and how its values are packed.
+pub trait Logic {
+    /// How the logic is described in errors, like "four-logic".
+    const NAME: &'static str;
+
+    /// Whether storages of this type hold this kind of logic.
+    fn holds(ty: StorageType) -> bool;
+
+    /// How many bytes `width` values take up.
+    fn packed_len(width: usize) -> usize;
+
+    /// The value of a single bit.
+    fn bit(packed: &[u8], index: usize) -> u8;
+
+    /// Replace `out` with the value of each of the first `count` bits.
+    fn unpack(packed: &[u8], count: usize, out: &mut Vec<u8>);
+
+    /// Replace `out` with `values`, one per byte, packed.
+    fn pack(values: &[u8], out: &mut Vec<u8>);
+}
+
+/// `0` and `1`, eight to a byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Two {}
+
+/// `0`, `1`, `x` and `z` as 0 to 3, four to a byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Four {}
+
+/// [`Nine`](logic::Nine) values, a byte each.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Nine {}
+
+impl Logic for Two {
+    const NAME: &'static str = "two-logic";
+
+    fn holds(ty: StorageType) -> bool {
+        matches!(ty, StorageType::TwoLogic)
+    }
+
+    fn packed_len(width: usize) -> usize {
+        (width + 7) / 8
+    }
+
+    fn bit(packed: &[u8], index: usize) -> u8 {
+        (packed[index / 8] >> (index % 8)) & 1
+    }
+
+    fn unpack(packed: &[u8], count: usize, out: &mut Vec<u8>) {
+        logic::unpack_two(packed, count, out);
+    }
+
+    fn pack(values: &[u8], out: &mut Vec<u8>) {
+        logic::pack_two(values, out);
+    }
+}
+
+impl Logic for Four {
+    const NAME: &'static str = "four-logic";
+
+    fn holds(ty: StorageType) -> bool {
+        matches!(ty, StorageType::FourLogic)
+    }
+
+    fn packed_len(width: usize) -> usize {
+        (width + 3) / 4
+    }
+
+    fn bit(packed: &[u8], index: usize) -> u8 {
+        (packed[index / 4] >> ((index % 4) * 2)) & 0b11
+    }
+
+    fn unpack(packed: &[u8], count: usize, out: &mut Vec<u8>) {
+        logic::unpack_four(packed, count, out);
+    }
+
+    fn pack(values: &[u8], out: &mut Vec<u8>) {
+        logic::pack_four(values, out);
+    }
+}
+
+impl Logic for Nine {
+    const NAME: &'static str = "nine-logic";
+
+    fn holds(ty: StorageType) -> bool {
+        matches!(ty, StorageType::NineLogic)
+    }
+
+    fn packed_len(width: usize) -> usize {
+        width
+    }
+
+    fn bit(packed: &[u8], index: usize) -> u8 {
+        packed[index]
+    }
+
+    fn unpack(packed: &[u8], count: usize, out: &mut Vec<u8>) {
+        out.clear();
+        out.extend_from_slice(&packed[..count]);
+    }
+
+    fn pack(values: &[u8], out: &mut Vec<u8>) {
+        out.clear();
+        out.extend_from_slice(values);
+    }
+}
+
+/// A value of `width` bits of some kind of logic, packed the way storages of it are.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogicSlice<L> {
+    packed: Vec<u8>,
+    width: u32,
+    logic: PhantomData<L>,
+}
+
+impl<L: Logic> LogicSlice<L> {
+    /// # Panics
+    /// If `packed` is too short to hold `width` values.
+    pub fn new(packed: Vec<u8>, width: u32) -> Self {
+        assert!(
+            packed.len() >= L::packed_len(width as usize),
+            "{} bytes can't hold {} bits of {}",
+            packed.len(),
+            width,
+            L::NAME
+        );
+        Self {
+            packed,
+            width,
+            logic: PhantomData,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn packed(&self) -> &[u8] {
+        &self.packed
+    }
+
+    /// The value of bit `index`, counting from the first.
+    ///
+    /// # Panics
+    /// If `index` is past the end.
+    pub fn bit(&self, index: u32) -> u8 {
+        assert!(index < self.width, "bit {} of {}", index, self.width);
+        L::bit(&self.packed, index as usize)
+    }
+
+    /// The value of every bit, one per byte, first bit first.
+    pub fn unpack(&self) -> Vec<u8> {
+        let mut values = Vec::with_capacity(self.width as usize);
+        L::unpack(&self.packed, self.width as usize, &mut values);
+        values
+    }
+}
+
+/// The changes to a storage, in time order, from
+/// [`Processed::iter_changes`](crate::Processed::iter_changes).
+///
+/// Blocks are read one at a time as the iterator reaches them. If one can't be read, the error
+/// is the last item.
+pub struct Changes<'a, L> {
+    blocks: &'a CommittedBlocks,
+    file: &'a Scratch,
+    width: u32,
+    /// The next block to read.
+    next_block: usize,
+    block: Vec<u8>,
+    /// Where the next change starts in `block`.
+    position: usize,
+    timestamp: u64,
+    logic: PhantomData<L>,
+}
+
+impl<'a, L: Logic> Changes<'a, L> {
+    pub(crate) fn new(blocks: &'a CommittedBlocks, file: &'a Scratch, width: u32) -> Self {
+        Self {
+            blocks,
+            file,
+            width,
+            next_block: 0,
+            block: vec![],
+            position: 0,
+            timestamp: 0,
+            logic: PhantomData,
+        }
+    }
+
+    fn read_next_block(&mut self) -> Result<(), Error> {
+        let block = self.blocks.block_offsets[self.next_block];
+        self.next_block += 1;
+
+        self.block.resize(block.len, 0);
+        self.file.read_exact_at(&mut self.block, block.offset)?;
+        self.timestamp = u64::from_le_bytes(self.block[..BLOCK_HEADER_SIZE].try_into().unwrap());
+        self.position = BLOCK_HEADER_SIZE;
+        Ok(())
+    }
+}
+
+impl<L: Logic> Iterator for Changes<'_, L> {
+    type Item = Result<(Timesteps, LogicSlice<L>), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.position >= self.block.len() {
+            if self.next_block == self.blocks.block_offsets.len() {
+                return None;
+            }
+            if let Err(e) = self.read_next_block() {
+                // There's no telling where the next readable change would be.
+                self.next_block = self.blocks.block_offsets.len();
+                self.block.clear();
+                return Some(Err(e));
+            }
+        }
+
+        let rest = &self.block[self.position..];
+        let packed = match self.blocks.packed_bits {
+            Some(bits) => {
+                let (delta, value, len) = read_packed(rest, bits);
+                self.timestamp = self.timestamp.wrapping_add(delta);
+                self.position += len;
+                vec![value]
+            }
+            None => {
+                let (delta, len) = read_varint(rest);
+                self.timestamp = self.timestamp.wrapping_add(delta);
+                let start = self.position + len;
+                self.position = start + self.blocks.bytes as usize;
+                self.block[start..self.position].to_vec()
+            }
+        };
+        Some(Ok((
+            Timesteps(self.timestamp),
+            LogicSlice {
+                packed,
+                width: self.width,
+                logic: PhantomData,
+            },
+        )))
+    }
+}
+
+/// The changes to a value made of several storages of `L` logic, one after another, from
+/// [`Processed::iter_combined_changes`](crate::Processed::iter_combined_changes).
+///
+/// Whenever any of the storages changes, this gives the whole value, with the bits of the first
+/// storage first. Several changes at the same time are given once, with the last value of each
+/// storage. Nothing is given until every storage has a value.
+pub struct Combined<'a, L: Logic> {
+    parts: Vec<Peekable<Changes<'a, L>>>,
+    values: Vec<Option<LogicSlice<L>>>,
+    unpacked: Vec<u8>,
+    part: Vec<u8>,
+}
+
+impl<'a, L: Logic> Combined<'a, L> {
+    pub(crate) fn new(parts: Vec<Changes<'a, L>>) -> Self {
+        Self {
+            values: parts.iter().map(|_| None).collect(),
+            parts: parts.into_iter().map(Iterator::peekable).collect(),
+            unpacked: vec![],
+            part: vec![],
+        }
+    }
+}
+
+impl<L: Logic> Iterator for Combined<'_, L> {
+    type Item = Result<(Timesteps, LogicSlice<L>), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let mut time = None;
+            for part in &mut self.parts {
+                match part.peek() {
+                    Some(Ok((next, _))) => {
+                        time = Some(time.map_or(*next, |time: Timesteps| time.min(*next)))
+                    }
+                    Some(Err(_)) => return part.next(),
+                    None => {}
+                }
+            }
+            let time = time?;
+
+            for (part, value) in self.parts.iter_mut().zip(&mut self.values) {
+                while let Some(Ok((_, slice))) =
+                    part.next_if(|change| matches!(change, Ok((next, _)) if *next == time))
+                {
+                    *value = Some(slice);
+                }
+            }
+
+            if self.values.iter().any(Option::is_none) {
+                continue;
+            }
+            self.unpacked.clear();
+            let mut width = 0;
+            for value in self.values.iter().flatten() {
+                L::unpack(value.packed(), value.width() as usize, &mut self.part);
+                self.unpacked.extend_from_slice(&self.part);
+                width += value.width();
+            }
+            let mut packed = vec![];
+            L::pack(&self.unpacked, &mut packed);
+            return Some(Ok((time, LogicSlice::new(packed, width))));
+        }
+    }
+}
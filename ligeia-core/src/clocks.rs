@@ -0,0 +1,233 @@
+//! Finding clocks, and the clock domain that each signal probably belongs to.
+//!
+//! A clock is a one-bit storage that toggles between 0 and 1 with a steady period. A signal is
+//! in a clock's domain when its changes line up with that clock's rising edges, landing on or
+//! shortly after them.
+
+use std::ops::Range;
+
+use crate::{
+    meta::{StorageId, StorageType, Timesteps},
+    Error, Processed,
+};
+
+/// Clocks need at least this many rising edges to be told apart from other signals.
+const MIN_EDGES: usize = 4;
+
+/// The fraction of a clock's periods that must match its typical period.
+const MIN_REGULARITY: f64 = 0.9;
+
+/// The fraction of a signal's changes that must line up with a clock for it to be in that
+/// clock's domain.
+const MIN_ALIGNMENT: f64 = 0.9;
+
+#[derive(Debug, Clone)]
+pub struct Clock {
+    pub storage: StorageId,
+    /// The typical time between rising edges.
+    pub period: Timesteps,
+    pub rising_edges: Vec<Timesteps>,
+}
+
+impl Clock {
+    /// How long after a rising edge a change still counts as lining up with it. This leaves room
+    /// for clock-to-output delays in gate-level simulations.
+    fn tolerance(&self) -> u64 {
+        self.period.0 / 8
+    }
+
+    /// The fraction of `changes` that land on or shortly after a rising edge.
+    ///
+    /// Changes before the first rising edge, like initial values, aren't counted either way.
+    /// Returns `None` if there aren't any others.
+    pub fn alignment(&self, changes: &[Timesteps]) -> Option<f64> {
+        let first = *self.rising_edges.first()?;
+        let mut counted = 0;
+        let mut aligned = 0;
+
+        for &time in changes.iter().filter(|&&time| time >= first) {
+            counted += 1;
+            let edge = match self.rising_edges.binary_search(&time) {
+                Ok(_) => time,
+                Err(next) => self.rising_edges[next - 1],
+            };
+            if time.0 - edge.0 <= self.tolerance() {
+                aligned += 1;
+            }
+        }
+
+        match counted {
+            0 => None,
+            _ => Some(aligned as f64 / counted as f64),
+        }
+    }
+}
+
+/// The level of a one-bit value, or `None` for `x` and `z`.
+fn level(ty: StorageType, data: &[u8]) -> Option<bool> {
+    match (ty, data[0] & 0b11) {
+        (StorageType::TwoLogic, value) => Some(value & 1 == 1),
+        (StorageType::FourLogic, 0) => Some(false),
+        (StorageType::FourLogic, 1) => Some(true),
+        _ => None,
+    }
+}
+
+/// The type of a storage that has edges, or an error if it isn't one bit.
+fn one_bit(processed: &Processed, id: StorageId) -> Result<StorageType, Error> {
+    let storage = processed.storage(id)?;
+    match storage.ty {
+        StorageType::TwoLogic | StorageType::FourLogic if storage.width == 1 => Ok(storage.ty),
+        _ => Err(Error::NotOneBit(id)),
+    }
+}
+
+/// The levels of a one-bit storage at each of its changes, with `None` for `x` and `z`, for
+/// sampling on its edges with [`sample_on_edges`](crate::resample::sample_on_edges).
+pub fn levels(
+    processed: &mut Processed,
+    id: StorageId,
+) -> Result<Vec<(Timesteps, Option<bool>)>, Error> {
+    let ty = one_bit(processed, id)?;
+    let mut levels = vec![];
+    processed.load_storage(id, |time, data| levels.push((time, level(ty, data))))?;
+    Ok(levels)
+}
+
+/// Like [`levels`], but only the level in effect at the start of `range` and the changes within
+/// it, like with [`Processed::changes_in_range_with_initial`].
+pub fn levels_in_range(
+    processed: &mut Processed,
+    id: StorageId,
+    range: Range<Timesteps>,
+) -> Result<Vec<(Timesteps, Option<bool>)>, Error> {
+    let ty = one_bit(processed, id)?;
+    let mut levels = vec![];
+    processed.changes_in_range_with_initial(id, range, |time, data| {
+        levels.push((time, level(ty, data)))
+    })?;
+    Ok(levels)
+}
+
+/// Find the rising edges of a storage, if it looks like a clock.
+fn as_clock(changes: &[(Timesteps, Option<bool>)]) -> Option<(Timesteps, Vec<Timesteps>)> {
+    let mut rising_edges = vec![];
+    let mut previous = None;
+
+    for &(time, level) in changes {
+        match (previous, level) {
+            // Clocks only ever toggle, once they've started.
+            (Some(_), None) => return None,
+            (Some(previous), Some(level)) if previous == level => return None,
+            (Some(false), Some(true)) => rising_edges.push(time),
+            _ => {}
+        }
+        if level.is_some() {
+            previous = level;
+        }
+    }
+
+    if rising_edges.len() < MIN_EDGES {
+        return None;
+    }
+
+    let mut periods: Vec<u64> = rising_edges.windows(2).map(|w| w[1].0 - w[0].0).collect();
+    periods.sort_unstable();
+    let period = periods[periods.len() / 2];
+    if period == 0 {
+        return None;
+    }
+
+    // Allow a little jitter, for clocks with periods that don't divide the timescale evenly.
+    let jitter = (period / 100).max(1);
+    let regular = periods
+        .iter()
+        .filter(|&&p| p.abs_diff(period) <= jitter)
+        .count();
+    if (regular as f64) < periods.len() as f64 * MIN_REGULARITY {
+        return None;
+    }
+
+    Some((Timesteps(period), rising_edges))
+}
+
+/// Find the storages that look like clocks.
+///
+/// This loads every one-bit storage, which can take a while for lazily loaded waveforms.
+pub fn detect_clocks(processed: &mut Processed) -> Result<Vec<Clock>, Error> {
+    let ids = processed.storage_ids();
+
+    let mut clocks = vec![];
+    let mut changes = vec![];
+    for id in ids {
+        let storage = processed.storage(id)?;
+        let ty = storage.ty;
+        if storage.width != 1
+            || !matches!(ty, StorageType::TwoLogic | StorageType::FourLogic)
+            || processed.change_count(id)? < 2 * MIN_EDGES as u64
+        {
+            continue;
+        }
+
+        changes.clear();
+        processed.load_storage(id, |time, data| changes.push((time, level(ty, data))))?;
+
+        if let Some((period, rising_edges)) = as_clock(&changes) {
+            clocks.push(Clock {
+                storage: id,
+                period,
+                rising_edges,
+            });
+        }
+    }
+
+    Ok(clocks)
+}
+
+/// The clock domain of a storage.
+#[derive(Debug, Clone, Copy)]
+pub struct Domain {
+    /// The index of the clock in the list given to [`clock_domains`].
+    pub clock: usize,
+    /// The fraction of the storage's changes that lined up with the clock.
+    pub alignment: f64,
+}
+
+/// Work out which of `clocks` each of `ids` is most likely driven by.
+///
+/// Clocks are in their own domain. When a signal lines up with several clocks equally well, as
+/// with a clock and another derived from it, the slowest is picked, since it's the most specific.
+pub fn clock_domains(
+    processed: &mut Processed,
+    clocks: &[Clock],
+    ids: &[StorageId],
+) -> Result<Vec<Option<Domain>>, Error> {
+    let mut changes = vec![];
+    ids.iter()
+        .map(|&id| {
+            if let Some(clock) = clocks.iter().position(|clock| clock.storage == id) {
+                return Ok(Some(Domain {
+                    clock,
+                    alignment: 1.0,
+                }));
+            }
+
+            changes.clear();
+            processed.load_storage(id, |time, _| changes.push(time))?;
+
+            let best = clocks
+                .iter()
+                .enumerate()
+                .filter_map(|(index, clock)| Some((index, clock.alignment(&changes)?)))
+                .filter(|&(_, alignment)| alignment >= MIN_ALIGNMENT)
+                .max_by(|&(a, a_alignment), &(b, b_alignment)| {
+                    a_alignment
+                        .partial_cmp(&b_alignment)
+                        .unwrap()
+                        .then(clocks[a].period.cmp(&clocks[b].period))
+                });
+
+            Ok(best.map(|(clock, alignment)| Domain { clock, alignment }))
+        })
+        .collect()
+}
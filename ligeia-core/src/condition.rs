@@ -0,0 +1,557 @@
+//! Finding when a condition over several variables holds, like `req && !ack` or
+//! `state == "FETCH" && error`.
+//!
+//! Conditions are made of variable names, numbers (`42`, `0x2a`, `0b101010`), quoted strings for
+//! comparing against string variables, `==`, `!=`, `!`, `&&`, `||` and parentheses. Variables can
+//! be named by their full hierarchical path, or by any shorter suffix of it that's unique.
+//!
+//! Values with `x` or `z` bits are unknown, and unknowns carry through the way they do in
+//! Verilog. A condition only holds where it's known to be true, so `unknown(bus)` is there to ask
+//! whether a value has any unknown bits, or hasn't been set yet.
+//!
+//! Conditions about one variable in particular, like highlighting rules for a trace, can be
+//! parsed with [`Condition::parse_for`], which has `value` stand for it.
+
+use std::ops::Range;
+
+use crate::{
+    bignum::BigUint,
+    logic,
+    meta::{StorageId, StorageType, Timesteps, VarKind},
+    Error, Processed,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConditionError {
+    #[error("unexpected `{0}` at {1}")]
+    Unexpected(String, usize),
+    #[error("the condition ends early")]
+    UnexpectedEnd,
+    #[error("there's no variable named `{0}`")]
+    UnknownVariable(String),
+    #[error("`{0}` could be any of several variables")]
+    AmbiguousVariable(String),
+    #[error("`{0}` can't be used in conditions yet")]
+    Unsupported(String),
+    #[error("{0}")]
+    Load(#[from] Error),
+}
+
+/// A span of time over which a condition holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interval {
+    pub start: Timesteps,
+    /// The time it stops holding, if it does.
+    pub end: Option<Timesteps>,
+}
+
+#[derive(Debug)]
+enum Expr {
+    /// An index into `Condition::storages`.
+    Var(usize),
+    /// Unpacked bits, least significant first.
+    Number(Vec<u8>),
+    Text(String),
+    Not(Box<Expr>),
+    /// Whether a value is unknown, which is always known.
+    IsUnknown(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Eq(Box<Expr>, Box<Expr>),
+    Ne(Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(Vec<u8>),
+    Text(String),
+    Not,
+    And,
+    Or,
+    Eq,
+    Ne,
+    Open,
+    Close,
+}
+
+fn is_ident(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '.' | '$' | '[' | ']')
+}
+
+fn parse_number(text: &str) -> Option<Vec<u8>> {
+    let text = text.replace('_', "");
+    let value = match text.get(..2) {
+        Some("0x") | Some("0X") => BigUint::from_str_radix(&text[2..], 16),
+        Some("0b") | Some("0B") => BigUint::from_str_radix(&text[2..], 2),
+        _ => BigUint::from_str_radix(&text, 10),
+    }?;
+
+    let bits = value.bits().max(1);
+    Some((0..bits).map(|i| value.bit(i) as u8).collect())
+}
+
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, ConditionError> {
+    let mut tokens = vec![];
+    let mut chars = source.char_indices().peekable();
+
+    while let Some((at, c)) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '!' | '=' | '&' | '|' => {
+                let next = chars.peek().map(|&(_, next)| next);
+                let token = match (c, next) {
+                    ('!', Some('=')) => Token::Ne,
+                    ('!', _) => Token::Not,
+                    ('=', Some('=')) => Token::Eq,
+                    ('&', Some('&')) => Token::And,
+                    ('|', Some('|')) => Token::Or,
+                    _ => return Err(ConditionError::Unexpected(c.to_string(), at)),
+                };
+                if token != Token::Not {
+                    chars.next();
+                }
+                token
+            }
+            '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, c)) => text.push(c),
+                        None => return Err(ConditionError::UnexpectedEnd),
+                    }
+                }
+                Token::Text(text)
+            }
+            c if is_ident(c) => {
+                let mut end = at + c.len_utf8();
+                while let Some(&(next_at, next)) = chars.peek() {
+                    if !is_ident(next) {
+                        break;
+                    }
+                    end = next_at + next.len_utf8();
+                    chars.next();
+                }
+
+                let word = &source[at..end];
+                match c.is_ascii_digit() {
+                    true => Token::Number(
+                        parse_number(word)
+                            .ok_or_else(|| ConditionError::Unexpected(word.to_string(), at))?,
+                    ),
+                    false => Token::Ident(word.to_string()),
+                }
+            }
+            c => return Err(ConditionError::Unexpected(c.to_string(), at)),
+        };
+        tokens.push((at, token));
+    }
+
+    Ok(tokens)
+}
+
+/// A recursive descent parser, with `||` binding loosest and `!` tightest.
+struct Parser<'a> {
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+    processed: &'a Processed,
+    /// The path of the variable `value` names, if anything.
+    subject: Option<&'a str>,
+    storages: Vec<(StorageId, StorageType, u32)>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(_, token)| token)
+    }
+
+    fn eat(&mut self, token: Token) -> bool {
+        let matched = self.peek() == Some(&token);
+        if matched {
+            self.next += 1;
+        }
+        matched
+    }
+
+    fn or(&mut self) -> Result<Expr, ConditionError> {
+        let mut expr = self.and()?;
+        while self.eat(Token::Or) {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, ConditionError> {
+        let mut expr = self.comparison()?;
+        while self.eat(Token::And) {
+            expr = Expr::And(Box::new(expr), Box::new(self.comparison()?));
+        }
+        Ok(expr)
+    }
+
+    fn comparison(&mut self) -> Result<Expr, ConditionError> {
+        let left = self.unary()?;
+        if self.eat(Token::Eq) {
+            Ok(Expr::Eq(Box::new(left), Box::new(self.unary()?)))
+        } else if self.eat(Token::Ne) {
+            Ok(Expr::Ne(Box::new(left), Box::new(self.unary()?)))
+        } else {
+            Ok(left)
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, ConditionError> {
+        match self.eat(Token::Not) {
+            true => Ok(Expr::Not(Box::new(self.unary()?))),
+            false => self.primary(),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, ConditionError> {
+        let (at, token) = self
+            .tokens
+            .get(self.next)
+            .cloned()
+            .ok_or(ConditionError::UnexpectedEnd)?;
+        self.next += 1;
+
+        match token {
+            Token::Ident(name) if name == "unknown" && self.peek() == Some(&Token::Open) => {
+                Ok(Expr::IsUnknown(Box::new(self.primary()?)))
+            }
+            Token::Ident(name) => match self.subject {
+                Some(subject) if name == "value" => self.var(subject),
+                _ => self.var(&name),
+            },
+            Token::Number(bits) => Ok(Expr::Number(bits)),
+            Token::Text(text) => Ok(Expr::Text(text)),
+            Token::Open => {
+                let expr = self.or()?;
+                match self.eat(Token::Close) {
+                    true => Ok(expr),
+                    false => match self.tokens.get(self.next) {
+                        Some((at, token)) => Err(unexpected(token, *at)),
+                        None => Err(ConditionError::UnexpectedEnd),
+                    },
+                }
+            }
+            token => Err(unexpected(&token, at)),
+        }
+    }
+
+    fn var(&mut self, name: &str) -> Result<Expr, ConditionError> {
+        let suffix = format!(".{}", name);
+        let mut matches = self.processed.vars().iter().filter_map(|var| {
+            let path = self.processed.var_path(var);
+            (path == name || path.ends_with(&suffix)).then_some((path, var))
+        });
+
+        let (path, var) = match (matches.next(), matches.next()) {
+            (Some(found), None) => found,
+            (Some(_), Some(_)) => {
+                // A full path wins over other variables that it happens to be a suffix of.
+                match self
+                    .processed
+                    .vars()
+                    .iter()
+                    .find(|var| self.processed.var_path(var) == name)
+                {
+                    Some(var) => (name.to_string(), var),
+                    None => return Err(ConditionError::AmbiguousVariable(name.to_string())),
+                }
+            }
+            (None, _) => return Err(ConditionError::UnknownVariable(name.to_string())),
+        };
+
+        let id = match &var.kind {
+            VarKind::Integer { storages, .. } if storages.len() == 1 => storages[0],
+            VarKind::Enum { storage, .. } | VarKind::Utf8 { storage } => *storage,
+            _ => return Err(ConditionError::Unsupported(path)),
+        };
+
+        let index = match self.storages.iter().position(|&(known, ..)| known == id) {
+            Some(index) => index,
+            None => {
+                let storage = self.processed.storage(id)?;
+                self.storages.push((id, storage.ty, storage.width));
+                self.storages.len() - 1
+            }
+        };
+        Ok(Expr::Var(index))
+    }
+}
+
+fn unexpected(token: &Token, at: usize) -> ConditionError {
+    let text = match token {
+        Token::Ident(name) => name.clone(),
+        Token::Number(_) => "number".to_string(),
+        Token::Text(text) => format!("\"{}\"", text),
+        Token::Not => "!".to_string(),
+        Token::And => "&&".to_string(),
+        Token::Or => "||".to_string(),
+        Token::Eq => "==".to_string(),
+        Token::Ne => "!=".to_string(),
+        Token::Open => "(".to_string(),
+        Token::Close => ")".to_string(),
+    };
+    ConditionError::Unexpected(text, at)
+}
+
+/// The value of an expression at some point in time.
+enum Value<'a> {
+    /// Unpacked values, least significant first, with 2 for `x` and 3 for `z`.
+    Bits(Vec<u8>),
+    Text(&'a str),
+    Unknown,
+}
+
+impl Value<'_> {
+    fn from_bool(value: Option<bool>) -> Self {
+        match value {
+            Some(value) => Value::Bits(vec![value as u8]),
+            None => Value::Unknown,
+        }
+    }
+
+    /// Like Verilog, anything with a 1 bit is true, and anything else with an unknown bit is
+    /// unknown.
+    fn truth(&self) -> Option<bool> {
+        match self {
+            Value::Bits(bits) if bits.contains(&1) => Some(true),
+            Value::Bits(bits) if bits.iter().all(|&bit| bit == 0) => Some(false),
+            Value::Text(text) => Some(!text.is_empty()),
+            _ => None,
+        }
+    }
+
+    fn equals(&self, other: &Self) -> Option<bool> {
+        match (self, other) {
+            (Value::Bits(a), Value::Bits(b)) => {
+                if a.iter().chain(b).any(|&bit| bit > 1) {
+                    return None;
+                }
+                let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+                Some(
+                    long[..short.len()] == short[..]
+                        && long[short.len()..].iter().all(|&bit| bit == 0),
+                )
+            }
+            (Value::Text(a), Value::Text(b)) => Some(a == b),
+            (Value::Unknown, _) | (_, Value::Unknown) => None,
+            _ => Some(false),
+        }
+    }
+}
+
+/// A parsed condition, ready to be searched for.
+#[derive(Debug)]
+pub struct Condition {
+    expr: Expr,
+    /// Each storage the condition refers to, with its type and width.
+    storages: Vec<(StorageId, StorageType, u32)>,
+}
+
+impl Condition {
+    pub fn parse(source: &str, processed: &Processed) -> Result<Self, ConditionError> {
+        Self::parse_with(source, processed, None)
+    }
+
+    /// Parse a condition about the variable at `path`, which `value` stands for, like
+    /// `value == 0xdead` or `unknown(value)`.
+    pub fn parse_for(
+        source: &str,
+        processed: &Processed,
+        path: &str,
+    ) -> Result<Self, ConditionError> {
+        Self::parse_with(source, processed, Some(path))
+    }
+
+    fn parse_with(
+        source: &str,
+        processed: &Processed,
+        subject: Option<&str>,
+    ) -> Result<Self, ConditionError> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            next: 0,
+            processed,
+            subject,
+            storages: vec![],
+        };
+
+        let expr = parser.or()?;
+        if let Some((at, token)) = parser.tokens.get(parser.next) {
+            return Err(unexpected(token, *at));
+        }
+
+        Ok(Self {
+            expr,
+            storages: parser.storages,
+        })
+    }
+
+    fn eval<'a>(
+        &'a self,
+        expr: &'a Expr,
+        processed: &'a Processed,
+        current: &[Option<&[u8]>],
+    ) -> Value<'a> {
+        match expr {
+            Expr::Var(index) => {
+                let (_, ty, width) = self.storages[*index];
+                let data = match current[*index] {
+                    Some(data) => data,
+                    None => return Value::Unknown,
+                };
+
+                let mut bits = vec![];
+                match ty {
+                    StorageType::TwoLogic => logic::unpack_two(data, width as usize, &mut bits),
+                    StorageType::FourLogic => logic::unpack_four(data, width as usize, &mut bits),
+                    // Anything stronger or weaker than a plain 0 or 1 counts as unknown.
+                    StorageType::NineLogic => {
+                        bits.extend(data[..width as usize].iter().map(|&value| {
+                            if value <= 1 {
+                                value
+                            } else {
+                                2
+                            }
+                        }))
+                    }
+                    StorageType::Utf8 => return Value::Text(processed.string(data)),
+                    // Events don't have a value to compare.
+                    StorageType::Event => return Value::Unknown,
+                }
+                Value::Bits(bits)
+            }
+            Expr::Number(bits) => Value::Bits(bits.clone()),
+            Expr::Text(text) => Value::Text(text),
+            Expr::Not(expr) => Value::from_bool(
+                self.eval(expr, processed, current)
+                    .truth()
+                    .map(|value| !value),
+            ),
+            Expr::IsUnknown(expr) => {
+                let unknown = match self.eval(expr, processed, current) {
+                    Value::Bits(bits) => bits.iter().any(|&bit| bit > 1),
+                    Value::Text(_) => false,
+                    Value::Unknown => true,
+                };
+                Value::from_bool(Some(unknown))
+            }
+            Expr::And(a, b) => {
+                let a = self.eval(a, processed, current).truth();
+                let b = self.eval(b, processed, current).truth();
+                Value::from_bool(match (a, b) {
+                    (Some(false), _) | (_, Some(false)) => Some(false),
+                    (Some(true), Some(true)) => Some(true),
+                    _ => None,
+                })
+            }
+            Expr::Or(a, b) => {
+                let a = self.eval(a, processed, current).truth();
+                let b = self.eval(b, processed, current).truth();
+                Value::from_bool(match (a, b) {
+                    (Some(true), _) | (_, Some(true)) => Some(true),
+                    (Some(false), Some(false)) => Some(false),
+                    _ => None,
+                })
+            }
+            Expr::Eq(a, b) => Value::from_bool(
+                self.eval(a, processed, current)
+                    .equals(&self.eval(b, processed, current)),
+            ),
+            Expr::Ne(a, b) => Value::from_bool(
+                self.eval(a, processed, current)
+                    .equals(&self.eval(b, processed, current))
+                    .map(|equal| !equal),
+            ),
+        }
+    }
+
+    /// Find every span of time over which the condition holds, in order.
+    pub fn intervals(&self, processed: &mut Processed) -> Result<Vec<Interval>, ConditionError> {
+        let mut changes = Vec::with_capacity(self.storages.len());
+        for &(id, ..) in &self.storages {
+            let mut storage_changes = vec![];
+            processed.load_storage(id, |time, data| storage_changes.push((time, data.to_vec())))?;
+            changes.push(storage_changes);
+        }
+        Ok(self.intervals_of(processed, &changes, None))
+    }
+
+    /// Like [`Condition::intervals`], but only looking at `range`, like the part of a waveform
+    /// that's on screen. Only the blocks that overlap it are read.
+    ///
+    /// An interval that started before the range starts at the start of it, and one still
+    /// holding at the end of it has no end.
+    pub fn intervals_in(
+        &self,
+        processed: &mut Processed,
+        range: Range<Timesteps>,
+    ) -> Result<Vec<Interval>, ConditionError> {
+        let mut changes = Vec::with_capacity(self.storages.len());
+        for &(id, ..) in &self.storages {
+            let mut storage_changes = vec![];
+            processed.changes_in_range_with_initial(id, range.clone(), |time, data| {
+                storage_changes.push((time.max(range.start), data.to_vec()))
+            })?;
+            changes.push(storage_changes);
+        }
+        Ok(self.intervals_of(processed, &changes, Some(range.start)))
+    }
+
+    /// The spans over which the condition holds, given each storage's changes in time order.
+    /// With a `from`, evaluation starts there even if nothing changes then.
+    fn intervals_of(
+        &self,
+        processed: &Processed,
+        changes: &[Vec<(Timesteps, Vec<u8>)>],
+        from: Option<Timesteps>,
+    ) -> Vec<Interval> {
+        let mut times: Vec<Timesteps> = changes.iter().flatten().map(|&(time, _)| time).collect();
+        times.extend(from);
+        times.sort_unstable();
+        times.dedup();
+
+        let mut next = vec![0; changes.len()];
+        let mut current: Vec<Option<&[u8]>> = vec![None; changes.len()];
+        let mut intervals = vec![];
+        let mut start = None;
+
+        for time in times {
+            // Catch every storage up to its latest change at or before `time`.
+            for (i, storage_changes) in changes.iter().enumerate() {
+                while let Some((change_time, data)) = storage_changes.get(next[i]) {
+                    if *change_time > time {
+                        break;
+                    }
+                    current[i] = Some(data);
+                    next[i] += 1;
+                }
+            }
+
+            let holds = self.eval(&self.expr, processed, &current).truth() == Some(true);
+            match (holds, start) {
+                (true, None) => start = Some(time),
+                (false, Some(started)) => {
+                    intervals.push(Interval {
+                        start: started,
+                        end: Some(time),
+                    });
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(start) = start {
+            intervals.push(Interval { start, end: None });
+        }
+
+        intervals
+    }
+}
@@ -0,0 +1,107 @@
+//! Tidying up names the way simulators write them, for showing to people.
+//!
+//! Verilog escaped identifiers like `\foo.bar ` keep their backslash and the space that ends
+//! them, and some simulators write generate loop iterations as `genblk1(3)` rather than
+//! `genblk1[3]`. Demangled names are only for display: searches and lookups by path still go by
+//! the names as they were in the file.
+//!
+//! Since a demangled name can have a `.` in it, display paths escape `.` and `\` inside names
+//! with a `\`, the same as [globs](crate::search) do, and [`split_path`] splits them back up.
+
+use std::borrow::Cow;
+
+/// Which rules are applied to names. All of them are by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Demangling {
+    /// `\foo.bar ` becomes `foo.bar`.
+    pub escaped: bool,
+    /// `genblk1(3)` becomes `genblk1[3]`, as do escaped generate names like `\genblk1[3] `.
+    pub generate_indices: bool,
+}
+
+impl Default for Demangling {
+    fn default() -> Self {
+        Self {
+            escaped: true,
+            generate_indices: true,
+        }
+    }
+}
+
+impl Demangling {
+    /// Leave every name as it is.
+    pub const NONE: Self = Self {
+        escaped: false,
+        generate_indices: false,
+    };
+
+    pub fn apply<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        let mut name = Cow::Borrowed(name);
+        if self.escaped {
+            if let Some(escaped) = name.strip_prefix('\\') {
+                name = Cow::Owned(escaped.trim_end().to_string());
+            }
+        }
+        if self.generate_indices {
+            if let Some(indexed) = bracket_indices(&name) {
+                name = Cow::Owned(indexed);
+            }
+        }
+        name
+    }
+}
+
+/// Turn `(3)` indices at the end of a name into `[3]`, if there are any.
+fn bracket_indices(name: &str) -> Option<String> {
+    let mut rest = name;
+    let mut indices = vec![];
+    while let Some(open) = rest.strip_suffix(')').and_then(|inner| inner.rfind('(')) {
+        let index = &rest[open + 1..rest.len() - 1];
+        if index.is_empty() || !index.bytes().all(|b| b.is_ascii_digit()) {
+            break;
+        }
+        indices.push(index);
+        rest = &rest[..open];
+    }
+    if indices.is_empty() || rest.is_empty() {
+        return None;
+    }
+
+    let mut bracketed = rest.to_string();
+    for index in indices.iter().rev() {
+        bracketed.push('[');
+        bracketed.push_str(index);
+        bracketed.push(']');
+    }
+    Some(bracketed)
+}
+
+/// Escape `.` and `\` in a name, so it can be joined into a path with `.`.
+pub fn escape(name: &str) -> Cow<'_, str> {
+    if !name.contains(&['.', '\\'][..]) {
+        return Cow::Borrowed(name);
+    }
+    let mut escaped = String::with_capacity(name.len() + 2);
+    for c in name.chars() {
+        if c == '.' || c == '\\' {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    Cow::Owned(escaped)
+}
+
+/// Split a path on each `.` that isn't escaped, unescaping the names in it.
+pub fn split_path(path: &str) -> Vec<String> {
+    let mut names = vec![String::new()];
+    let mut chars = path.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            // A trailing `\` has nothing to escape, so it's kept as it is.
+            '\\' => names.last_mut().unwrap().push(chars.next().unwrap_or('\\')),
+            '.' => names.push(String::new()),
+            c => names.last_mut().unwrap().push(c),
+        }
+    }
+    names
+}
@@ -0,0 +1,255 @@
+//! Comparing storages, possibly from different waveforms, to find when their values differ.
+//!
+//! Storages are compared at every time either of them changes, so one that changes more often
+//! than the other, like with a glitch or a value that's written again unchanged, is only
+//! different while its value actually is.
+//!
+//! Events don't have values, so they're compared by when they happen instead: they differ at
+//! every timestep one of them happens at and the other doesn't.
+
+use crate::{
+    logic,
+    meta::{StorageId, StorageType, Timesteps},
+    timescale::Rescale,
+    Error, Processed,
+};
+
+/// A span of time during which two storages had different values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mismatch {
+    pub start: Timesteps,
+    /// When the values were the same again, or `None` if they never were.
+    pub end: Option<Timesteps>,
+}
+
+/// Where two storages differ, in timesteps of the first.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiffReport {
+    /// In time order, and never touching.
+    pub mismatches: Vec<Mismatch>,
+    /// How many changes to the second storage landed on the same timestep of the first as the
+    /// change before them, when the second waveform's timescale is finer. Only the last change
+    /// at each timestep is compared, so any others could differ without it being noticed.
+    pub collapsed: u64,
+}
+
+impl DiffReport {
+    pub fn is_match(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+
+    /// The earliest time the storages differ.
+    pub fn first_divergence(&self) -> Option<Timesteps> {
+        self.mismatches.first().map(|mismatch| mismatch.start)
+    }
+}
+
+/// How values are compared, so that the same value stored differently still matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    /// Unpacked to a byte per value. Two-logic and four-logic values unpack the same way, and
+    /// nine-logic values are already a byte each.
+    Logic(u32),
+    Text,
+    Event,
+}
+
+fn kind(processed: &Processed, id: StorageId) -> Result<(StorageType, Kind), Error> {
+    let storage = processed.storage(id)?;
+    let kind = match storage.ty {
+        StorageType::Utf8 => Kind::Text,
+        StorageType::Event => Kind::Event,
+        _ => Kind::Logic(storage.width),
+    };
+    Ok((storage.ty, kind))
+}
+
+/// Every change to a storage, with its value in a form that can be compared across storage
+/// types and waveforms, and its time converted by `rescale`.
+fn load(
+    processed: &mut Processed,
+    id: StorageId,
+    (ty, kind): (StorageType, Kind),
+    rescale: &mut Rescale,
+) -> Result<Vec<(Timesteps, Vec<u8>)>, Error> {
+    let mut texts = vec![];
+    let mut changes = vec![];
+    rescale.restart();
+    processed.load_storage(id, |time, data| {
+        let time = rescale.convert(time);
+        let mut value = vec![];
+        match (ty, kind) {
+            (StorageType::TwoLogic, Kind::Logic(width)) => {
+                logic::unpack_two(data, width as usize, &mut value)
+            }
+            (StorageType::FourLogic, Kind::Logic(width)) => {
+                logic::unpack_four(data, width as usize, &mut value)
+            }
+            (StorageType::NineLogic, Kind::Logic(width)) => {
+                value.extend_from_slice(&data[..width as usize])
+            }
+            (StorageType::Event, _) => {}
+            // Strings are looked up once everything's loaded, since the table can't be borrowed
+            // while the storage is loading.
+            _ => texts.push(u32::from_le_bytes(data[..4].try_into().unwrap())),
+        }
+        changes.push((time, value));
+    })?;
+
+    if kind == Kind::Text {
+        for ((_, value), index) in changes.iter_mut().zip(texts) {
+            value.extend_from_slice(processed.string(&index.to_le_bytes()).as_bytes());
+        }
+    }
+    Ok(changes)
+}
+
+/// Find where storage `id_a` of `a` and storage `id_b` of `b` have different values.
+///
+/// The waveforms can have different timescales. Times in `b` are converted to timesteps of `a`,
+/// rounding down, and the report is in timesteps of `a`. Changes of `b` that collapse onto the
+/// same timestep are counted in [`DiffReport::collapsed`]. Before a storage's first change, it has
+/// no value, which differs from any value the other has. Both storages are loaded in full.
+///
+/// The storages have to hold the same kind of values: strings, or logic values of the same
+/// width. To compare storages in the same waveform, use [`diff_within`].
+pub fn diff_storages(
+    a: &mut Processed,
+    id_a: StorageId,
+    b: &mut Processed,
+    id_b: StorageId,
+) -> Result<DiffReport, Error> {
+    let kind_a = kind(a, id_a)?;
+    let kind_b = kind(b, id_b)?;
+    if kind_a.1 != kind_b.1 {
+        return Err(Error::Incomparable(id_a, id_b));
+    }
+
+    let changes_a = load(a, id_a, kind_a, &mut Rescale::new(1, 1))?;
+    let mut rescale = Rescale::new(b.femtoseconds_per_timestep(), a.femtoseconds_per_timestep());
+    let changes_b = load(b, id_b, kind_b, &mut rescale)?;
+
+    let mut report = match kind_a.1 {
+        Kind::Event => diff_occurrences(&changes_a, &changes_b),
+        _ => diff_changes(&changes_a, &changes_b),
+    };
+    report.collapsed = rescale.collapsed();
+    Ok(report)
+}
+
+/// Find where two storages of the same waveform have different values, like with
+/// [`diff_storages`].
+pub fn diff_within(
+    processed: &mut Processed,
+    id_a: StorageId,
+    id_b: StorageId,
+) -> Result<DiffReport, Error> {
+    let kind_a = kind(processed, id_a)?;
+    let kind_b = kind(processed, id_b)?;
+    if kind_a.1 != kind_b.1 {
+        return Err(Error::Incomparable(id_a, id_b));
+    }
+
+    let changes_a = load(processed, id_a, kind_a, &mut Rescale::new(1, 1))?;
+    let changes_b = load(processed, id_b, kind_b, &mut Rescale::new(1, 1))?;
+    Ok(match kind_a.1 {
+        Kind::Event => diff_occurrences(&changes_a, &changes_b),
+        _ => diff_changes(&changes_a, &changes_b),
+    })
+}
+
+/// Find the timesteps at which only one of two events, in time order, happens.
+///
+/// Each is a mismatch of its own, a timestep long, unless it's right after another.
+pub fn diff_occurrences<V>(a: &[(Timesteps, V)], b: &[(Timesteps, V)]) -> DiffReport {
+    let mut a: Vec<Timesteps> = a.iter().map(|&(time, _)| time).collect();
+    let mut b: Vec<Timesteps> = b.iter().map(|&(time, _)| time).collect();
+    a.dedup();
+    b.dedup();
+
+    let mut mismatches: Vec<Mismatch> = vec![];
+    let (mut next_a, mut next_b) = (0, 0);
+    while next_a < a.len() || next_b < b.len() {
+        let time = match (a.get(next_a), b.get(next_b)) {
+            (Some(&time_a), Some(&time_b)) if time_a == time_b => {
+                next_a += 1;
+                next_b += 1;
+                continue;
+            }
+            (Some(&time_a), Some(&time_b)) if time_a < time_b => {
+                next_a += 1;
+                time_a
+            }
+            (Some(&time), None) => {
+                next_a += 1;
+                time
+            }
+            (_, Some(&time)) => {
+                next_b += 1;
+                time
+            }
+            (None, None) => unreachable!(),
+        };
+
+        let end = Timesteps(time.0 + 1);
+        match mismatches.last_mut() {
+            Some(last) if last.end == Some(time) => last.end = Some(end),
+            _ => mismatches.push(Mismatch {
+                start: time,
+                end: Some(end),
+            }),
+        }
+    }
+
+    DiffReport {
+        mismatches,
+        collapsed: 0,
+    }
+}
+
+/// Find where two sequences of changes, in time order, have different values.
+pub fn diff_changes<V: PartialEq>(a: &[(Timesteps, V)], b: &[(Timesteps, V)]) -> DiffReport {
+    let mut mismatches = vec![];
+    let (mut next_a, mut next_b) = (0, 0);
+    let (mut value_a, mut value_b) = (None, None);
+    let mut mismatch_start = None;
+
+    while next_a < a.len() || next_b < b.len() {
+        let time = match (a.get(next_a), b.get(next_b)) {
+            (Some(&(time_a, _)), Some(&(time_b, _))) => time_a.min(time_b),
+            (Some(&(time, _)), None) | (None, Some(&(time, _))) => time,
+            (None, None) => unreachable!(),
+        };
+
+        // Only the last of several changes at the same time counts.
+        while let Some((_, value)) = a.get(next_a).filter(|&&(t, _)| t == time) {
+            value_a = Some(value);
+            next_a += 1;
+        }
+        while let Some((_, value)) = b.get(next_b).filter(|&&(t, _)| t == time) {
+            value_b = Some(value);
+            next_b += 1;
+        }
+
+        match (value_a != value_b, mismatch_start) {
+            (true, None) => mismatch_start = Some(time),
+            (false, Some(start)) => {
+                mismatches.push(Mismatch {
+                    start,
+                    end: Some(time),
+                });
+                mismatch_start = None;
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(start) = mismatch_start {
+        mismatches.push(Mismatch { start, end: None });
+    }
+
+    DiffReport {
+        mismatches,
+        collapsed: 0,
+    }
+}
@@ -0,0 +1,159 @@
+//! A deterministic, human-diffable text dump of a waveform database, for comparing what a
+//! loader produced against golden files in tests.
+//!
+//! The dump lists the scope tree, with each scope's child scopes in id order and its variables in
+//! declaration order, then every storage in id order along with its first and last few changes.
+//! Values are written most significant bit first, one character per bit.
+
+use std::{collections::VecDeque, io::Write};
+
+use crate::{
+    logic,
+    meta::{self, ScopeId, StorageType, Timesteps, VarKind},
+    Error, Processed,
+};
+
+/// Write a dump of `processed`, with up to `changes` changes from each end of every storage.
+pub fn dump<W: Write>(
+    processed: &mut Processed,
+    mut writer: W,
+    changes: usize,
+) -> Result<(), Error> {
+    let (first, last) = processed.time_bounds();
+    writeln!(
+        writer,
+        "femtoseconds per timestep: {}",
+        processed.femtoseconds_per_timestep()
+    )?;
+    writeln!(writer, "time bounds: {}..{}", first.0, last.0)?;
+
+    writeln!(writer, "\nscopes:")?;
+    dump_scope(processed, &mut writer, ScopeId::ROOT, 0)?;
+
+    writeln!(writer, "\nstorages:")?;
+    for id in processed.storage_ids() {
+        let storage = processed.storage(id)?;
+        let (ty, width, start) = (storage.ty, storage.width, storage.start);
+        let count = processed.change_count(id)?;
+        writeln!(
+            writer,
+            "{}: {:?}, {} bits from {}, {} changes",
+            id.0, ty, width, start, count
+        )?;
+
+        let mut head: Vec<(Timesteps, Vec<u8>)> = vec![];
+        let mut tail: VecDeque<(Timesteps, Vec<u8>)> = VecDeque::new();
+        let mut skipped = 0u64;
+        processed.load_storage(id, |timestamp, data| {
+            if head.len() < changes {
+                head.push((timestamp, data.to_vec()));
+            } else {
+                tail.push_back((timestamp, data.to_vec()));
+                if tail.len() > changes {
+                    tail.pop_front();
+                    skipped += 1;
+                }
+            }
+        })?;
+
+        for (timestamp, data) in head {
+            writeln!(
+                writer,
+                "  {} {}",
+                timestamp.0,
+                format(processed, ty, width, &data)
+            )?;
+        }
+        if skipped > 0 {
+            writeln!(writer, "  ... {} more", skipped)?;
+        }
+        for (timestamp, data) in tail {
+            writeln!(
+                writer,
+                "  {} {}",
+                timestamp.0,
+                format(processed, ty, width, &data)
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+fn dump_scope<W: Write>(
+    processed: &Processed,
+    writer: &mut W,
+    id: ScopeId,
+    depth: usize,
+) -> Result<(), Error> {
+    let indent = "  ".repeat(depth);
+    let (scopes, vars) = processed.within_scope(id)?;
+
+    for var in vars {
+        write!(writer, "{}{}: ", indent, processed.name(var.name))?;
+        dump_var(writer, var)?;
+        if let Some(source) = &var.source {
+            write!(writer, ", at {}:{}", source.file, source.line)?;
+        }
+        writeln!(writer)?;
+    }
+    for scope in scopes {
+        writeln!(writer, "{}{}", indent, processed.name(scope.name))?;
+        dump_scope(processed, writer, scope.id, depth + 1)?;
+    }
+
+    Ok(())
+}
+
+fn dump_var<W: Write>(writer: &mut W, var: &meta::Var) -> Result<(), Error> {
+    match &var.kind {
+        VarKind::None => write!(writer, "no value")?,
+        VarKind::Integer {
+            storages,
+            msb_index,
+            lsb_index,
+            signedness,
+        } => {
+            let storages: Vec<_> = storages.iter().map(|id| id.0.to_string()).collect();
+            write!(
+                writer,
+                "integer [{}:{}] {:?}, storages {}",
+                msb_index,
+                lsb_index,
+                signedness,
+                storages.join(" ")
+            )?;
+        }
+        VarKind::Enum { storage, values } => {
+            write!(writer, "enum, storage {}", storage.0)?;
+            for value in values {
+                let bits: String = value
+                    .value
+                    .iter()
+                    .map(|&bit| if bit { '1' } else { '0' })
+                    .collect();
+                write!(writer, ", {}={}", value.name, bits)?;
+            }
+        }
+        VarKind::Utf8 { storage } => write!(writer, "string, storage {}", storage.0)?,
+        VarKind::Event { storage } => write!(writer, "event, storage {}", storage.0)?,
+    }
+    Ok(())
+}
+
+fn format(processed: &Processed, ty: StorageType, width: u32, data: &[u8]) -> String {
+    let mut values = vec![];
+    match ty {
+        StorageType::TwoLogic => logic::unpack_two(data, width as usize, &mut values),
+        StorageType::FourLogic => logic::unpack_four(data, width as usize, &mut values),
+        StorageType::NineLogic => return logic::format_nine(&data[..width as usize]),
+        StorageType::Utf8 => return format!("{:?}", processed.string(data)),
+        StorageType::Event => return "event".to_string(),
+    }
+
+    values
+        .into_iter()
+        .rev()
+        .map(|value| ['0', '1', 'x', 'z'][value as usize])
+        .collect()
+}
@@ -0,0 +1,258 @@
+//! Showing logic values as numbers, in whatever radix, and at any width, and showing
+//! measurements and times with the separators of the reader's locale.
+
+use std::{env, str::FromStr};
+
+use crate::{bignum::BigUint, timescale::TimeUnit};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Radix {
+    Binary,
+    Octal,
+    Decimal,
+    Hexadecimal,
+}
+
+impl Radix {
+    fn bits_per_digit(self) -> Option<usize> {
+        match self {
+            Radix::Binary => Some(1),
+            Radix::Octal => Some(3),
+            Radix::Decimal => None,
+            Radix::Hexadecimal => Some(4),
+        }
+    }
+
+    fn prefix(self) -> &'static str {
+        match self {
+            Radix::Binary => "0b",
+            Radix::Octal => "0o",
+            Radix::Decimal => "",
+            Radix::Hexadecimal => "0x",
+        }
+    }
+}
+
+impl FromStr for Radix {
+    type Err = String;
+
+    /// Parses `bin`, `oct`, `dec` or `hex`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bin" => Ok(Radix::Binary),
+            "oct" => Ok(Radix::Octal),
+            "dec" => Ok(Radix::Decimal),
+            "hex" => Ok(Radix::Hexadecimal),
+            _ => Err(format!("unknown radix `{}`", s)),
+        }
+    }
+}
+
+/// How a number is written out, beyond its radix. The default is just the digits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// Separate every this many digits with `_`, counting from the least significant, like
+    /// `dead_beef` with 4.
+    pub group: Option<usize>,
+    /// Start with `0b`, `0o` or `0x`. Decimal numbers don't have one.
+    pub prefix: bool,
+    /// Leave out leading zero digits, keeping at least one.
+    pub trim_leading_zeros: bool,
+    /// What goes between groups of digits, `_` if `None`.
+    pub separator: Option<char>,
+}
+
+/// The characters a locale writes numbers with, like `1,234.5` in English or `1.234,5` in
+/// German. The default is a `.` and no grouping, as Rust formats numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Separators {
+    pub decimal: char,
+    /// Put between every three digits of whole numbers, if anything.
+    pub grouping: Option<char>,
+}
+
+impl Default for Separators {
+    fn default() -> Self {
+        Self {
+            decimal: '.',
+            grouping: None,
+        }
+    }
+}
+
+impl Separators {
+    /// The separators of a POSIX locale name, like `de_DE.UTF-8`, `fr_CA` or `en`.
+    ///
+    /// Only the language and territory are looked at. `C`, `POSIX` and languages that aren't
+    /// known get the default.
+    pub fn from_locale(locale: &str) -> Self {
+        let name = locale.split(&['.', '@'][..]).next().unwrap_or("");
+        let mut parts = name.split(&['_', '-'][..]);
+        let language = parts.next().unwrap_or("");
+        let territory = parts.next().unwrap_or("");
+
+        let (decimal, grouping) = match (language, territory) {
+            (_, "CH") | (_, "LI") => ('.', '\''),
+            ("en" | "ja" | "zh" | "ko" | "he" | "th" | "hi" | "ga" | "mt", _) => ('.', ','),
+            ("de" | "es" | "it" | "nl" | "pt" | "da" | "id" | "tr" | "el" | "ro" | "hr", _)
+            | ("sl" | "sr", _) => (',', '.'),
+            ("fr" | "ru" | "pl" | "sv" | "fi" | "nb" | "nn" | "no" | "cs" | "sk" | "uk", _)
+            | ("hu" | "bg" | "et" | "lv" | "lt", _) => (',', '\u{a0}'),
+            _ => return Self::default(),
+        };
+        Self {
+            decimal,
+            grouping: Some(grouping),
+        }
+    }
+
+    /// The separators of the locale numbers are formatted in, from the first of `LC_ALL`,
+    /// `LC_NUMERIC` and `LANG` that's set.
+    pub fn from_env() -> Self {
+        ["LC_ALL", "LC_NUMERIC", "LANG"]
+            .iter()
+            .filter_map(|name| env::var(name).ok())
+            .find(|locale| !locale.is_empty())
+            .map_or_else(Self::default, |locale| Self::from_locale(&locale))
+    }
+
+    /// Write out whole number digits, grouped in threes.
+    fn group(self, digits: &str) -> String {
+        let grouping = match self.grouping {
+            Some(grouping) => grouping,
+            None => return digits.to_string(),
+        };
+        let mut grouped = String::with_capacity(digits.len() * 2);
+        for (i, digit) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i) % 3 == 0 {
+                grouped.push(grouping);
+            }
+            grouped.push(digit);
+        }
+        grouped
+    }
+}
+
+/// Format unpacked values, as from [`logic::unpack_four`](crate::logic::unpack_four), as a
+/// number. The first value is the least significant bit.
+///
+/// In binary, octal and hexadecimal, every digit is shown, even leading zeros. A digit is `z` if
+/// all of its bits are `z`, and `x` if any of them are `x`, `z` or anything above that. In
+/// decimal, that goes for the whole number instead.
+pub fn format_values(values: &[u8], radix: Radix) -> String {
+    format_values_with(values, radix, FormatOptions::default())
+}
+
+/// Like [`format_values`], written out as `options` say.
+pub fn format_values_with(values: &[u8], radix: Radix, options: FormatOptions) -> String {
+    let mut digits = digits(values, radix);
+    if options.trim_leading_zeros {
+        let zeros = digits.iter().take_while(|&&digit| digit == '0').count();
+        digits.drain(..zeros.min(digits.len().saturating_sub(1)));
+    }
+
+    let mut formatted = String::new();
+    if options.prefix {
+        formatted.push_str(radix.prefix());
+    }
+    for (i, &digit) in digits.iter().enumerate() {
+        let remaining = digits.len() - i;
+        if matches!(options.group, Some(group) if group > 0 && i > 0 && remaining % group == 0) {
+            formatted.push(options.separator.unwrap_or('_'));
+        }
+        formatted.push(digit);
+    }
+    formatted
+}
+
+/// Format a measurement with `decimals` decimal places, like `1,234.50`, with `separators`.
+pub fn format_decimal(value: f64, decimals: usize, separators: Separators) -> String {
+    if !value.is_finite() {
+        return value.to_string();
+    }
+
+    let formatted = format!("{:.*}", decimals, value.abs());
+    let (whole, fraction) = match formatted.split_once('.') {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (formatted.as_str(), None),
+    };
+
+    let mut out = String::new();
+    // Values that round to zero don't get a sign.
+    if value < 0.0 && formatted.bytes().any(|b| matches!(b, b'1'..=b'9')) {
+        out.push('-');
+    }
+    out.push_str(&separators.group(whole));
+    if let Some(fraction) = fraction {
+        out.push(separators.decimal);
+        out.push_str(fraction);
+    }
+    out
+}
+
+/// Format a time in the largest unit it's at least one of, with as many decimal places as it
+/// takes to be exact, like `1.5 ns` or `20 us`.
+pub fn format_time(femtoseconds: u128, separators: Separators) -> String {
+    let unit = TimeUnit::ALL
+        .into_iter()
+        .find(|unit| unit.femtoseconds() <= femtoseconds)
+        .unwrap_or(TimeUnit::Femtoseconds);
+    let per_unit = unit.femtoseconds();
+
+    let mut out = separators.group(&(femtoseconds / per_unit).to_string());
+    let places = per_unit.to_string().len() - 1;
+    let fraction = format!("{:0places$}", femtoseconds % per_unit, places = places);
+    let fraction = fraction.trim_end_matches('0');
+    if !fraction.is_empty() {
+        out.push(separators.decimal);
+        out.push_str(fraction);
+    }
+    out.push(' ');
+    out.push_str(unit.symbol());
+    out
+}
+
+/// The digits of a number, most significant first.
+fn digits(values: &[u8], radix: Radix) -> Vec<char> {
+    let digit_char = |chunk: &[u8], digit: u64| {
+        if chunk.iter().all(|&value| value == 3) {
+            'z'
+        } else if chunk.iter().any(|&value| value > 1) {
+            'x'
+        } else {
+            std::char::from_digit(digit as u32, 16).unwrap()
+        }
+    };
+
+    let bits = match radix.bits_per_digit() {
+        Some(bits) => bits,
+        None => {
+            return match values.iter().any(|&value| value > 1) {
+                true => vec![digit_char(values, 0)],
+                false => from_bits(values).to_str_radix(10).chars().collect(),
+            };
+        }
+    };
+
+    let mut digits: Vec<char> = values
+        .chunks(bits)
+        .map(|chunk| {
+            let digit = chunk
+                .iter()
+                .enumerate()
+                .fold(0, |digit, (i, &value)| digit | ((value & 1) as u64) << i);
+            digit_char(chunk, digit)
+        })
+        .collect();
+    digits.reverse();
+    digits
+}
+
+/// Read known bits as a number, with the first as the least significant.
+fn from_bits(values: &[u8]) -> BigUint {
+    let mut limbs = vec![0; (values.len() + 63) / 64];
+    for (i, &value) in values.iter().enumerate() {
+        limbs[i / 64] |= ((value & 1) as u64) << (i % 64);
+    }
+    BigUint::from_limbs(limbs)
+}
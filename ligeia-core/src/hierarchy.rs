@@ -0,0 +1,372 @@
+//! Looking up what's directly within a scope a page at a time, so a browser over hundreds of
+//! thousands of scopes only has to build the parts that are expanded, and sorting and filtering
+//! the variables in a scope for it.
+//!
+//! Families of siblings that only differ by an index at the end of their names, like the words
+//! of a memory `mem[0]`, `mem[1]`, ..., or the iterations of a generate loop, are listed as a
+//! single [`Array`] in their place. An array is a pseudo-scope: it has an id of its own that
+//! doesn't belong to any real scope, and what's within it is its elements, in index order.
+
+use std::{ops::Range, str::FromStr};
+
+use fnv::FnvHashMap;
+
+use crate::{
+    meta::{self, ScopeId, Timesteps},
+    names::{natural_cmp, NameTable},
+};
+
+/// Something directly within a scope.
+#[derive(Debug, Clone, Copy)]
+pub enum Child<'a> {
+    Scope(&'a meta::Scope),
+    Array(&'a Array),
+    Var(&'a meta::Var),
+}
+
+/// Scopes or variables that are alike but for an index, grouped together.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Array {
+    /// The id its elements are listed under, which no real scope has.
+    pub id: ScopeId,
+    pub parent: ScopeId,
+    /// The name of its elements without their indices, e.g. `mem`.
+    pub stem: String,
+    /// Whether its elements are scopes, rather than variables.
+    pub scopes: bool,
+    /// `[` or `(`, whichever the elements' indices are in.
+    open: char,
+    /// The index of each element, in ascending order.
+    indices: Vec<i64>,
+}
+
+impl Array {
+    /// The lowest and highest indices. Not every index in between has to have an element.
+    pub fn range(&self) -> (i64, i64) {
+        (self.indices[0], self.indices[self.indices.len() - 1])
+    }
+
+    /// How many elements there are.
+    pub fn elements(&self) -> usize {
+        self.indices.len()
+    }
+
+    /// The name to show it by, like `mem[0:65535]`.
+    pub fn name(&self) -> String {
+        let (first, last) = self.range();
+        let close = if self.open == '[' { ']' } else { ')' };
+        format!("{}{}{}:{}{}", self.stem, self.open, first, last, close)
+    }
+
+    /// Where the element with `index` is among the array's children, if there is one.
+    pub(crate) fn position(&self, index: i64) -> Option<usize> {
+        self.indices.binary_search(&index).ok()
+    }
+}
+
+/// Arrays are only made of at least this many siblings.
+pub const MIN_ARRAY_LEN: usize = 2;
+
+/// How many scopes and variables are directly within a scope.
+///
+/// [Arrays](Array) count as scopes, in place of their elements.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ChildCounts {
+    pub scopes: usize,
+    pub vars: usize,
+}
+
+impl ChildCounts {
+    pub fn total(&self) -> usize {
+        self.scopes + self.vars
+    }
+}
+
+/// What's directly within a scope: child scopes in [natural order](crate::names::natural_cmp) by
+/// name, then variables as indices into [`Processed::vars`](crate::Processed::vars), in
+/// declaration order. Within an array, they're its elements in index order.
+#[derive(Debug, Default)]
+pub(crate) struct Children {
+    pub scopes: Vec<ScopeId>,
+    pub vars: Vec<u32>,
+    /// The arrays among them, if there are any.
+    pub grouped: Option<Grouped>,
+}
+
+/// The children of a scope as they're browsed, with arrays in place of their elements.
+#[derive(Debug, Default)]
+pub(crate) struct Grouped {
+    pub scopes: Vec<ScopeId>,
+    pub arrays: Vec<ScopeId>,
+    pub vars: Vec<u32>,
+}
+
+impl Children {
+    pub fn counts(&self) -> ChildCounts {
+        match &self.grouped {
+            Some(grouped) => ChildCounts {
+                scopes: grouped.scopes.len() + grouped.arrays.len(),
+                vars: grouped.vars.len(),
+            },
+            None => ChildCounts {
+                scopes: self.scopes.len(),
+                vars: self.vars.len(),
+            },
+        }
+    }
+}
+
+/// Index the children of every scope that has any, along with the arrays among them.
+pub(crate) fn index(
+    scopes: &FnvHashMap<ScopeId, meta::Scope>,
+    vars: &[meta::Var],
+    names: &NameTable,
+) -> (FnvHashMap<ScopeId, Children>, FnvHashMap<ScopeId, Array>) {
+    let mut children: FnvHashMap<ScopeId, Children> = FnvHashMap::default();
+    for scope in scopes.values() {
+        children
+            .entry(scope.parent)
+            .or_default()
+            .scopes
+            .push(scope.id);
+    }
+    for (i, var) in vars.iter().enumerate() {
+        children
+            .entry(var.scope_id)
+            .or_default()
+            .vars
+            .push(i as u32);
+    }
+
+    // Scopes with the same name, if there are any, are kept in id order.
+    for children in children.values_mut() {
+        children.scopes.sort_unstable_by(|a, b| {
+            let name = |id| names.get(scopes[id].name);
+            natural_cmp(name(a), name(b)).then(a.cmp(b))
+        });
+    }
+
+    // Arrays get the ids after the last real scope's.
+    let mut next_id = scopes.keys().map(|id| id.0).max().unwrap_or(0) + 1;
+    let mut arrays = FnvHashMap::default();
+    let mut elements = vec![];
+    for (&parent, children) in &mut children {
+        let scope_families = families(&children.scopes, |id| names.get(scopes[id].name));
+        let var_families = families(&children.vars, |&i| names.get(vars[i as usize].name));
+        if scope_families.is_empty() && var_families.is_empty() {
+            continue;
+        }
+
+        let mut grouped = Grouped::default();
+        let mut add = |family: Family, scopes: bool| {
+            let id = ScopeId(next_id);
+            next_id += 1;
+            grouped.arrays.push(id);
+            arrays.insert(
+                id,
+                Array {
+                    id,
+                    parent,
+                    stem: family.stem.to_string(),
+                    scopes,
+                    open: family.open,
+                    indices: family.members.iter().map(|&(index, _)| index).collect(),
+                },
+            );
+            id
+        };
+        let mut grouped_scopes = vec![false; children.scopes.len()];
+        for family in scope_families {
+            let members: Vec<_> = family.members.iter().map(|&(_, i)| i).collect();
+            let id = add(family, true);
+            for &i in &members {
+                grouped_scopes[i] = true;
+            }
+            let scopes = members.iter().map(|&i| children.scopes[i]).collect();
+            elements.push((
+                id,
+                Children {
+                    scopes,
+                    ..Children::default()
+                },
+            ));
+        }
+        let mut grouped_vars = vec![false; children.vars.len()];
+        for family in var_families {
+            let members: Vec<_> = family.members.iter().map(|&(_, i)| i).collect();
+            let id = add(family, false);
+            for &i in &members {
+                grouped_vars[i] = true;
+            }
+            let vars = members.iter().map(|&i| children.vars[i]).collect();
+            elements.push((
+                id,
+                Children {
+                    vars,
+                    ..Children::default()
+                },
+            ));
+        }
+
+        grouped.scopes = children
+            .scopes
+            .iter()
+            .zip(&grouped_scopes)
+            .filter(|&(_, &grouped)| !grouped)
+            .map(|(&id, _)| id)
+            .collect();
+        grouped.vars = children
+            .vars
+            .iter()
+            .zip(&grouped_vars)
+            .filter(|&(_, &grouped)| !grouped)
+            .map(|(&i, _)| i)
+            .collect();
+        grouped
+            .arrays
+            .sort_unstable_by(|a, b| natural_cmp(&arrays[a].stem, &arrays[b].stem).then(a.cmp(b)));
+        children.grouped = Some(grouped);
+    }
+    children.extend(elements);
+
+    (children, arrays)
+}
+
+/// Siblings named alike but for an index.
+struct Family<'a> {
+    stem: &'a str,
+    open: char,
+    /// The index of each member, and where it is among the siblings, in index order.
+    members: Vec<(i64, usize)>,
+}
+
+/// Find the families of at least [`MIN_ARRAY_LEN`] among `siblings`, in the order their first
+/// members are.
+fn families<'a, T>(siblings: &[T], name: impl Fn(&T) -> &'a str) -> Vec<Family<'a>> {
+    let mut families: Vec<Family> = vec![];
+    let mut by_stem: FnvHashMap<(&str, char), usize> = FnvHashMap::default();
+    for (i, sibling) in siblings.iter().enumerate() {
+        let (stem, open, index) = match split_index(name(sibling)) {
+            Some(split) => split,
+            None => continue,
+        };
+        let family = *by_stem.entry((stem, open)).or_insert_with(|| {
+            families.push(Family {
+                stem,
+                open,
+                members: vec![],
+            });
+            families.len() - 1
+        });
+        families[family].members.push((index, i));
+    }
+
+    families.retain(|family| family.members.len() >= MIN_ARRAY_LEN);
+    for family in &mut families {
+        family.members.sort_by_key(|&(index, _)| index);
+    }
+    families
+}
+
+/// Split a name like `mem[3]` or `genblk1(3)` into its stem, the bracket its index is in, and
+/// its index.
+fn split_index(name: &str) -> Option<(&str, char, i64)> {
+    let (open, close) = match name.as_bytes().last()? {
+        b']' => ('[', ']'),
+        b')' => ('(', ')'),
+        _ => return None,
+    };
+    let start = name.rfind(open)?;
+    let index = name[start + 1..].strip_suffix(close)?.parse().ok()?;
+    Some((&name[..start], open, index))
+}
+
+/// The part of an instance's name that's shared by the other instances like it, without any
+/// index or number at the end, so `core0`, `core1` and `core[2]` are all instances of `core`.
+pub(crate) fn instance_stem(name: &str) -> &str {
+    let mut stem = name;
+    loop {
+        let trimmed = match stem.as_bytes().last() {
+            Some(b']') => stem.rfind('[').map(|open| &stem[..open]),
+            Some(b')') => stem.rfind('(').map(|open| &stem[..open]),
+            _ => None,
+        };
+        match trimmed {
+            Some(trimmed) => stem = trimmed,
+            None => break,
+        }
+    }
+    stem.trim_end_matches(|c: char| c.is_ascii_digit())
+}
+
+/// How to order the variables within a scope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VarOrder {
+    /// The order the loader declared them in.
+    Declaration,
+    /// By name, in [natural order](crate::names::natural_cmp).
+    Name,
+    /// Widest first.
+    Width,
+    /// Most changes first.
+    Activity,
+}
+
+impl FromStr for VarOrder {
+    type Err = String;
+
+    /// Parses `declaration`, `name`, `width` or `activity`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "declaration" => Ok(VarOrder::Declaration),
+            "name" => Ok(VarOrder::Name),
+            "width" => Ok(VarOrder::Width),
+            "activity" => Ok(VarOrder::Activity),
+            _ => Err(format!("unknown variable order `{}`", s)),
+        }
+    }
+}
+
+/// Which variables to keep, by width.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WidthFilter {
+    Any,
+    /// Only single bits.
+    Bits,
+    /// Only variables more than a bit wide.
+    Buses,
+}
+
+impl FromStr for WidthFilter {
+    type Err = String;
+
+    /// Parses `any`, `bits` or `buses`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "any" => Ok(WidthFilter::Any),
+            "bits" => Ok(WidthFilter::Bits),
+            "buses" => Ok(WidthFilter::Buses),
+            _ => Err(format!("unknown width filter `{}`", s)),
+        }
+    }
+}
+
+/// Which of the variables within a scope to list, and in what order, for
+/// [`Processed::vars_within`](crate::Processed::vars_within).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VarQuery {
+    pub order: VarOrder,
+    pub width: WidthFilter,
+    /// Only keep variables that change within this range, like the one on screen.
+    pub changed_in: Option<Range<Timesteps>>,
+}
+
+impl Default for VarQuery {
+    fn default() -> Self {
+        Self {
+            order: VarOrder::Declaration,
+            width: WidthFilter::Any,
+            changed_in: None,
+        }
+    }
+}
@@ -1,46 +1,448 @@
-use fnv::FnvHashMap;
+use crate::{
+    demangle::Demangling,
+    hierarchy::{Child, ChildCounts, Children, VarOrder, VarQuery, WidthFilter},
+    meta::{ScopeId, StorageId, Timesteps},
+    names::{NameId, NameTable},
+    scratch::Scratch,
+    time_index::TimeIndex,
+};
+use fnv::{FnvHashMap, FnvHashSet};
+use rayon::prelude::*;
 use std::{
-    fs::File,
-    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    borrow::Cow,
+    fmt,
+    io::{self, BufWriter, Write},
     mem,
+    ops::Range,
+    path::{Path, PathBuf},
 };
-use tempfile::tempfile;
-
-use crate::meta::{ScopeId, StorageId, Timesteps};
 
+pub mod axis;
+pub mod bignum;
+pub mod cache;
+pub mod changes;
+pub mod clocks;
+pub mod condition;
+pub mod demangle;
+pub mod diff;
+pub mod dump;
+pub mod format;
+pub mod hierarchy;
+pub mod logic;
 pub mod meta;
+pub mod names;
+mod pread;
+pub mod resample;
+pub mod saif;
+pub mod schema;
+pub mod scratch;
+pub mod search;
+pub mod slice;
+pub mod states;
+pub mod stats;
+pub mod summary;
+mod time_index;
+pub mod timescale;
 
 pub struct Value<'a> {
     pub storage_id: StorageId,
     /// This is expected to be in the format used within SVCB `VALUE_CHANGE` blocks.
+    ///
+    /// Data shorter than the storage is zero-extended, and anything past the end of the storage
+    /// is ignored, unless the ingestor is [strict](IngestorOptions::with_strict).
+    ///
+    /// For [`Utf8`](meta::StorageType::Utf8) storages, this is the text itself, which is
+    /// interned into the string table instead.
     pub data: &'a [u8],
 }
 
+/// How well string changes were deduplicated by the string table.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct StringStats {
+    /// The number of changes to string storages.
+    pub changes: u64,
+    /// The number of distinct strings among them.
+    pub distinct: usize,
+    /// The bytes of text across every change, as if each were stored separately.
+    pub text_bytes: u64,
+    /// The bytes actually stored: the table's text, plus an index for every change.
+    pub stored_bytes: u64,
+}
+
+impl StringStats {
+    /// How many times smaller the stored strings are than their text. With no strings at all,
+    /// this is 1.
+    pub fn dedup_ratio(&self) -> f64 {
+        match self.stored_bytes {
+            0 => 1.0,
+            stored => self.text_bytes as f64 / stored as f64,
+        }
+    }
+}
+
+/// The text of every string change, each stored once, along with how much that saved.
+#[derive(Default)]
+struct Strings {
+    table: NameTable,
+    changes: u64,
+    text_bytes: u64,
+}
+
+impl Strings {
+    /// Intern the text of a change, returning the data to store for it in its place.
+    ///
+    /// Text that isn't valid UTF-8 is stored lossily.
+    fn intern(&mut self, text: &[u8]) -> [u8; 4] {
+        let id = self.table.intern(&String::from_utf8_lossy(text));
+        self.changes += 1;
+        self.text_bytes += text.len() as u64;
+        id.0.to_le_bytes()
+    }
+
+    /// Take back the counting of a change that [`Strings::intern`] was given, once it turns out
+    /// not to be stored.
+    fn uncount(&mut self, text: &[u8]) {
+        self.changes -= 1;
+        self.text_bytes -= text.len() as u64;
+    }
+
+    fn stats(&self) -> StringStats {
+        StringStats {
+            changes: self.changes,
+            distinct: self.table.len(),
+            text_bytes: self.text_bytes,
+            stored_bytes: self.table.text_len() as u64 + self.changes * 4,
+        }
+    }
+}
+
+/// How much memory a waveform takes up once it's loaded, and how much of its scratch files.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// Bytes of the in-memory index of where each storage's blocks are.
+    pub block_index: usize,
+    /// Bytes of scope and variable names.
+    pub names: usize,
+    /// Bytes of the string table.
+    pub strings: usize,
+    /// Bytes of the time-major index, if there is one.
+    pub time_index: usize,
+    /// Bytes of changes written to scratch files.
+    pub scratch: u64,
+    /// How many bytes of `scratch` are still in memory, under
+    /// [`IngestorOptions::with_memory_limit`].
+    pub scratch_in_memory: u64,
+}
+
+/// A value along with the span of time over which it holds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StableValue {
+    /// The time of the change that produced this value.
+    pub start: Timesteps,
+    /// The time of the next change, if there is one.
+    pub end: Option<Timesteps>,
+    pub data: Vec<u8>,
+}
+
+/// Supplies the changes of storages on demand, for loaders that don't ingest everything up front.
+pub trait LazySource: Send {
+    /// Call `f` with every change of the storage, in time order.
+    ///
+    /// As with [`Value::data`], changes to [`Utf8`](meta::StorageType::Utf8) storages are their
+    /// text.
+    fn load(&mut self, id: StorageId, f: &mut dyn FnMut(Timesteps, &[u8])) -> Result<(), Error>;
+
+    /// The number of changes `load` would produce for the storage.
+    fn change_count(&self, id: StorageId) -> u64;
+
+    /// What's been worked around while loading so far, which is only handed over once. See
+    /// [`Processed::warnings`].
+    fn take_warnings(&mut self) -> Vec<Warning> {
+        vec![]
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("an i/o error occured")]
     Io(#[from] io::Error),
+    #[error("failed to load storage on demand: {0}")]
+    Lazy(String),
+    #[error("there's no storage with id {0:?}")]
+    UnknownStorage(StorageId),
+    #[error("there's no scope with id {0:?}")]
+    UnknownScope(ScopeId),
+    #[error("{0}")]
+    Timescale(#[from] timescale::TimescaleError),
+    #[error("storages {0:?} and {1:?} hold different kinds of values, so they can't be compared")]
+    Incomparable(StorageId, StorageId),
+    #[error("couldn't use scratch directory `{0}`")]
+    ScratchDir(String, #[source] io::Error),
+    #[error("scratch directory `{0}` ran out of space; set LIGEIA_SCRATCH_DIR to use another")]
+    ScratchFull(String),
+    #[error("a change to storage {0:?} is {1} bytes, but it only holds {2}")]
+    ValueTooLong(StorageId, usize, u32),
+    #[error("storage {0:?} isn't a single bit, so it has no edges to sample on")]
+    NotOneBit(StorageId),
+    #[error("{}", warnings_message(.0))]
+    Warnings(Vec<Warning>),
+}
+
+fn warnings_message(warnings: &[Warning]) -> String {
+    let mut message = format!("warnings are errors, and {}", warnings[0]);
+    if warnings.len() > 1 {
+        message += &format!(" (and {} more)", warnings.len() - 1);
+    }
+    message
+}
+
+fn storage_bytes(storage: &meta::Storage) -> u32 {
+    match storage.ty {
+        meta::StorageType::TwoLogic => (storage.width + 7) / 8, // 8 bits per byte
+        meta::StorageType::FourLogic => (storage.width + 3) / 4, // 4 qits per byte
+        meta::StorageType::NineLogic => storage.width,          // 1 nit per byte
+        meta::StorageType::Utf8 => 4,                           // 1 string table index
+        meta::StorageType::Event => 0,                          // just the time
+    }
+}
+
+/// How many bits a change's value takes up, if that's few enough for it to share a byte with the
+/// time since the change before it. See [`write_packed`].
+fn packed_bits(storage: &meta::Storage) -> Option<u32> {
+    let bits = match storage.ty {
+        meta::StorageType::TwoLogic => storage.width,
+        meta::StorageType::FourLogic => storage.width * 2,
+        _ => return None,
+    };
+    (1..8).contains(&bits).then_some(bits)
+}
+
+/// The bits of the last byte of a value that are within the storage's width.
+fn last_byte_mask(storage: &meta::Storage) -> u8 {
+    let bits = match storage.ty {
+        meta::StorageType::TwoLogic => storage.width % 8,
+        meta::StorageType::FourLogic => storage.width * 2 % 8,
+        _ => 0,
+    };
+    match bits {
+        0 => 0xff,
+        bits => (1 << bits) - 1,
+    }
+}
+
+fn scope_path<'a>(
+    scopes: &FnvHashMap<ScopeId, meta::Scope>,
+    names: &'a NameTable,
+    mut id: ScopeId,
+) -> Vec<&'a str> {
+    let mut path = vec![];
+    while let Some(scope) = scopes.get(&id) {
+        path.push(names.get(scope.name));
+        id = scope.parent;
+    }
+    path.reverse();
+    path
+}
+
+/// Where a block was written, along with enough about it to find a change without reading it.
+#[derive(Debug, Copy, Clone)]
+struct BlockOffset {
+    offset: u64,
+    len: usize,
+    /// The timestamp of the first change in the block.
+    start: Timesteps,
+    /// The timestamp of the last change in the block.
+    end: Timesteps,
+    /// How many changes to the storage came before this block.
+    first_change: u64,
+    /// The values in the block, for storages that are summarized.
+    summary: Option<summary::Summary>,
 }
 
+/// Changes to a single storage, buffered and written out in blocks.
+///
+/// Each block starts with the absolute timestamp of its first change. Every change is then stored
+/// as a LEB128 varint of the time since the previous change, followed by exactly `bytes` bytes of
+/// value, with any bits past the storage's width zeroed. Values narrower than a byte are packed
+/// in with the time instead, by [`write_packed`].
 struct Block {
     bytes: u32,
+    packed_bits: Option<u32>,
+    last_byte_mask: u8,
+    /// Whether changes are text to intern into the string table, rather than the data itself.
+    interned: bool,
+    /// Always big enough for the header and at least one change.
     block_size: usize,
     data: Box<[u8]>,
     offset: usize,
-    // (Block offset, block size)
-    block_offsets: Vec<(u64, usize)>,
+    block_offsets: Vec<BlockOffset>,
+    changes: u64,
+    /// The block currently being filled starts at this time and change.
+    block_start: Timesteps,
+    block_first_change: u64,
+    previous: Timesteps,
+    summarizer: Option<summary::Summarizer>,
+    /// Whether changes that repeat the last value are dropped.
+    dedup: bool,
+    /// Where the last change's value starts in `data`. It stays there after a flush, until the
+    /// next change is pushed.
+    last_at: usize,
+    /// The last change's value, if it was packed in with its time.
+    last_packed: u8,
+}
+
+const BLOCK_HEADER_SIZE: usize = mem::size_of::<Timesteps>();
+/// A `u64` takes up to ten bytes as a LEB128 varint.
+const MAX_VARINT_SIZE: usize = 10;
+
+fn write_varint(buffer: &mut [u8], mut value: u64) -> usize {
+    let mut len = 0;
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            buffer[len] = byte;
+            return len + 1;
+        }
+        buffer[len] = byte | 0x80;
+        len += 1;
+    }
+}
+
+fn read_varint(buffer: &[u8]) -> (u64, usize) {
+    let mut value = 0;
+    for (i, &byte) in buffer.iter().enumerate() {
+        value |= ((byte & 0x7f) as u64) << (7 * i);
+        if byte & 0x80 == 0 {
+            return (value, i + 1);
+        }
+    }
+    unreachable!("truncated varint")
+}
+
+/// Write a change whose value is only `bits` bits, fewer than 8, along with the time since the
+/// change before it.
+///
+/// The first byte has a continuation bit on top, like a varint, then the value, then as many of
+/// the low bits of the time as fit. Whatever's left of the time follows as a varint. Most changes
+/// to single-bit signals come soon after the last, so they only take a byte.
+fn write_packed(buffer: &mut [u8], delta: u64, value: u8, bits: u32) -> usize {
+    let low_bits = 7 - bits;
+    let first = value << low_bits | (delta & ((1 << low_bits) - 1)) as u8;
+    match delta >> low_bits {
+        0 => {
+            buffer[0] = first;
+            1
+        }
+        high => {
+            buffer[0] = first | 0x80;
+            1 + write_varint(&mut buffer[1..], high)
+        }
+    }
+}
+
+/// Read a change written by [`write_packed`], returning the time since the change before it,
+/// its value, and how many bytes it took up.
+fn read_packed(buffer: &[u8], bits: u32) -> (u64, u8, usize) {
+    let low_bits = 7 - bits;
+    let first = buffer[0];
+    let value = (first & 0x7f) >> low_bits;
+    let low = (first & ((1 << low_bits) - 1)) as u64;
+    if first & 0x80 == 0 {
+        return (low, value, 1);
+    }
+    let (high, len) = read_varint(&buffer[1..]);
+    (high << low_bits | low, value, 1 + len)
+}
+
+/// Call `f` with each change in a block that was written by `Block::flush`.
+fn decode_block<F>(block: &[u8], bytes: usize, packed_bits: Option<u32>, mut f: F)
+where
+    F: FnMut(Timesteps, &[u8]),
+{
+    let (header, mut rest) = block.split_at(BLOCK_HEADER_SIZE);
+    let mut timestamp = u64::from_le_bytes(header.try_into().unwrap());
+
+    while !rest.is_empty() {
+        if let Some(bits) = packed_bits {
+            let (delta, value, len) = read_packed(rest, bits);
+            timestamp = timestamp.wrapping_add(delta);
+            f(Timesteps(timestamp), &[value]);
+            rest = &rest[len..];
+            continue;
+        }
+
+        let (delta, len) = read_varint(rest);
+        // Wrapping, so timestamps that go backwards still round-trip.
+        timestamp = timestamp.wrapping_add(delta);
+        let (data, next) = rest[len..].split_at(bytes);
+        f(Timesteps(timestamp), data);
+        rest = next;
+    }
 }
 
 impl Block {
-    pub fn new(bytes: u32) -> Self {
-        let block_size = (10 * 1024).max(bytes as usize + mem::size_of::<Timesteps>());
+    /// Blocks are at least `block_size` bytes, and more if that isn't enough for a single change.
+    pub fn new(storage: &meta::Storage, dedup: bool, block_size: usize) -> Self {
+        let bytes = storage_bytes(storage);
+        let block_size = block_size.max(BLOCK_HEADER_SIZE + Self::max_entry_size(bytes));
         Self {
             bytes,
+            packed_bits: packed_bits(storage),
+            last_byte_mask: last_byte_mask(storage),
+            interned: matches!(storage.ty, meta::StorageType::Utf8),
             block_size,
             data: vec![0; block_size].into_boxed_slice(),
             offset: 0,
             block_offsets: vec![],
+            changes: 0,
+            block_start: Timesteps(0),
+            block_first_change: 0,
+            previous: Timesteps(0),
+            summarizer: summary::Summarizer::new(storage),
+            // Every change to an event is an occurrence of it, even with nothing to compare.
+            dedup: dedup && bytes != 0,
+            last_at: 0,
+            last_packed: 0,
+        }
+    }
+
+    /// Whether `data` is the same value as the last change, once it's extended or cut to the
+    /// storage's width, and repeats are being dropped.
+    fn is_repeat(&self, data: &[u8]) -> bool {
+        let last = match self.last_value() {
+            Some((_, last)) if self.dedup => last,
+            _ => return false,
+        };
+        last.iter().enumerate().all(|(i, &byte)| {
+            let mut value = data.get(i).copied().unwrap_or(0);
+            if i == last.len() - 1 {
+                value &= self.last_byte_mask;
+            }
+            value == byte
+        })
+    }
+
+    /// Whether `data` is no longer than a value of the storage, as strict ingestion requires.
+    fn fits(&self, data: &[u8]) -> bool {
+        // Events have no value to fit, and text is interned whatever its length.
+        self.bytes == 0 || self.interned || data.len() <= self.bytes as usize
+    }
+
+    /// The time and value of the last change, as it was stored.
+    fn last_value(&self) -> Option<(Timesteps, &[u8])> {
+        if self.changes == 0 {
+            return None;
         }
+        let value = match self.packed_bits {
+            Some(_) => std::slice::from_ref(&self.last_packed),
+            None => &self.data[self.last_at..][..self.bytes as usize],
+        };
+        Some((self.previous, value))
+    }
+
+    fn max_entry_size(bytes: u32) -> usize {
+        MAX_VARINT_SIZE + bytes as usize
     }
 
     #[cold]
@@ -48,8 +450,19 @@ impl Block {
     where
         W: Write,
     {
+        if self.offset == 0 {
+            return Ok(());
+        }
+
         writer.write_all(&self.data[..self.offset])?;
-        self.block_offsets.push((*writer_offset, self.offset));
+        self.block_offsets.push(BlockOffset {
+            offset: *writer_offset,
+            len: self.offset,
+            start: self.block_start,
+            end: self.previous,
+            first_change: self.block_first_change,
+            summary: self.summarizer.as_mut().and_then(summary::Summarizer::take),
+        });
         *writer_offset += self.offset as u64;
         self.offset = 0;
 
@@ -66,19 +479,50 @@ impl Block {
     where
         W: Write,
     {
-        if mem::size_of::<Timesteps>() + data.len() > self.offset + data.len() {
+        let bytes = self.bytes as usize;
+        if self.offset + Self::max_entry_size(self.bytes) > self.block_size {
             self.flush(writer, writer_offset)?;
         }
 
-        self.data[self.offset..][..mem::size_of::<Timesteps>()]
-            .copy_from_slice(&timestamp.0.to_le_bytes());
-        self.offset += mem::size_of::<Timesteps>();
+        if self.offset == 0 {
+            self.data[..BLOCK_HEADER_SIZE].copy_from_slice(&timestamp.0.to_le_bytes());
+            self.offset = BLOCK_HEADER_SIZE;
+            self.block_start = timestamp;
+            self.block_first_change = self.changes;
+            self.previous = timestamp;
+        }
+
+        let delta = timestamp.0.wrapping_sub(self.previous.0);
+        self.previous = timestamp;
+
+        let packed;
+        let value: &[u8] = match self.packed_bits {
+            Some(bits) => {
+                packed = [data.first().map_or(0, |&byte| byte & self.last_byte_mask)];
+                self.offset += write_packed(&mut self.data[self.offset..], delta, packed[0], bits);
+                self.last_packed = packed[0];
+                &packed
+            }
+            None => {
+                self.offset += write_varint(&mut self.data[self.offset..], delta);
+                let data = &data[..data.len().min(bytes)];
+                let value = &mut self.data[self.offset..][..bytes];
+                let (actual_data, remaining) = value.split_at_mut(data.len());
+                actual_data.copy_from_slice(data);
+                remaining.fill(0);
+                if let Some(last) = value.last_mut() {
+                    *last &= self.last_byte_mask;
+                }
+                self.last_at = self.offset;
+                self.offset += bytes;
+                &self.data[self.last_at..self.offset]
+            }
+        };
+        if let Some(summarizer) = &mut self.summarizer {
+            summarizer.push(timestamp, value);
+        }
 
-        let (actual_data, remaining) =
-            self.data[self.offset..][..self.bytes as usize].split_at_mut(data.len());
-        actual_data.copy_from_slice(data);
-        remaining.fill(0);
-        self.offset += self.bytes as usize;
+        self.changes += 1;
 
         Ok(())
     }
@@ -92,142 +536,732 @@ impl Block {
         W: Write,
     {
         self.flush(writer, writer_offset)?;
+        // Nothing more is added once a storage is committed, so the room left over from growing
+        // the list is wasted.
+        self.block_offsets.shrink_to_fit();
         Ok(CommittedBlocks {
             bytes: self.bytes,
-            block_size: self.block_size,
+            packed_bits: self.packed_bits,
             block_offsets: self.block_offsets,
+            changes: self.changes,
         })
     }
 }
 
 struct CommittedBlocks {
     bytes: u32,
-    block_size: usize,
-    block_offsets: Vec<(u64, usize)>,
+    packed_bits: Option<u32>,
+    block_offsets: Vec<BlockOffset>,
+    changes: u64,
 }
 
 impl CommittedBlocks {
-    pub fn read_blocks<R, F>(&self, mut reader: R, mut f: F) -> Result<(), io::Error>
+    pub fn read_blocks<F>(&self, file: &Scratch, mut f: F) -> Result<(), io::Error>
     where
-        R: Read + Seek,
         F: FnMut(Timesteps, &[u8]),
     {
-        let mut buffer = vec![0; self.block_size];
+        for index in 0..self.block_offsets.len() {
+            self.read_block(file, index, &mut f)?;
+        }
 
-        for &(offset, block_size) in &self.block_offsets {
-            reader.seek(SeekFrom::Start(offset))?;
-            reader.read_exact(&mut buffer[..block_size])?;
+        Ok(())
+    }
 
-            for sub_offset in
-                (0..block_size).step_by(self.bytes as usize + mem::size_of::<Timesteps>())
-            {
-                let timestamp = Timesteps(u64::from_le_bytes(
-                    buffer[sub_offset..sub_offset + mem::size_of::<Timesteps>()]
-                        .try_into()
-                        .unwrap(),
-                ));
-                let data = &buffer[sub_offset + mem::size_of::<Timesteps>()..];
-                f(timestamp, data);
+    fn read_block<F>(&self, file: &Scratch, index: usize, f: F) -> Result<(), io::Error>
+    where
+        F: FnMut(Timesteps, &[u8]),
+    {
+        let block = self.block_offsets[index];
+        pread::with_buffer(block.len, |buffer| {
+            file.read_exact_at(buffer, block.offset)?;
+            decode_block(buffer, self.bytes as usize, self.packed_bits, f);
+            Ok(())
+        })
+    }
+
+    /// The index of the block that holds the last change at or before `time`, if there is one.
+    fn block_at(&self, time: Timesteps) -> Option<usize> {
+        self.block_offsets
+            .partition_point(|block| block.start <= time)
+            .checked_sub(1)
+    }
+
+    /// Read a single change, given its position among all the changes to this storage.
+    pub fn read_entry(
+        &self,
+        file: &Scratch,
+        ordinal: u64,
+    ) -> Result<(Timesteps, Vec<u8>), io::Error> {
+        let index = self
+            .block_offsets
+            .partition_point(|block| block.first_change <= ordinal)
+            - 1;
+        let mut skip = ordinal - self.block_offsets[index].first_change;
+
+        let mut entry = None;
+        self.read_block(file, index, |timestamp, data| {
+            if skip == 0 {
+                entry = Some((timestamp, data.to_vec()));
             }
+            skip = skip.wrapping_sub(1);
+        })?;
+
+        Ok(entry.expect("change ordinal out of range"))
+    }
+}
+
+/// Storages are spread over several temporary files, so they can be committed in parallel.
+fn partition_of(id: StorageId, partitions: usize) -> usize {
+    id.0 as usize % partitions
+}
+
+type CommittedPartition = (Scratch, Vec<(StorageId, CommittedBlocks)>);
+
+struct Partition {
+    writer: BufWriter<Scratch>,
+    writer_offset: u64,
+    blocks: FnvHashMap<StorageId, Block>,
+}
+
+impl Partition {
+    fn new(memory_limit: u64, scratch_dir: &Path) -> Result<Self, Error> {
+        Ok(Self {
+            writer: BufWriter::new(Scratch::new(memory_limit, scratch_dir)?),
+            writer_offset: 0,
+            blocks: FnvHashMap::default(),
+        })
+    }
+
+    /// Storages in `priority` are committed first, in that order, and then the rest from the most
+    /// changes to the fewest, since the busiest ones are usually the first to be looked at.
+    fn commit(
+        self,
+        keep_empty: bool,
+        priority: &FnvHashMap<StorageId, usize>,
+    ) -> Result<CommittedPartition, io::Error> {
+        let mut writer = self.writer;
+        let mut writer_offset = self.writer_offset;
+
+        // Ties go by id, so the file is laid out the same way every time.
+        let mut blocks: Vec<_> = self.blocks.into_iter().collect();
+        blocks.sort_unstable_by_key(|(id, block)| {
+            (
+                priority.get(id).copied().unwrap_or(usize::MAX),
+                std::cmp::Reverse(block.changes),
+                *id,
+            )
+        });
+
+        let blocks = blocks
+            .into_iter()
+            .filter(|(_, block)| keep_empty || block.changes != 0)
+            .map(|(id, block)| Ok((id, block.commit(&mut writer, &mut writer_offset)?)))
+            .collect::<Result<_, io::Error>>()?;
+
+        let file = writer.into_inner().map_err(|e| e.into_error())?;
+        Ok((file, blocks))
+    }
+}
+
+/// How an [`Ingestor`] stores what it's given, built up from the defaults like
+/// `IngestorOptions::new().with_timescale(1000).with_dedup()`.
+///
+/// Loaders take these too, and replace the timescale with the one from the file.
+#[derive(Debug, Clone)]
+pub struct IngestorOptions {
+    femtoseconds_per_timestep: u128,
+    block_size: usize,
+    memory_limit: u64,
+    scratch_dir: Option<PathBuf>,
+    dedup: bool,
+    time_index: bool,
+    strict: bool,
+    ignored: Vec<search::Glob>,
+    crowded_scope_vars: usize,
+    warnings_as_errors: bool,
+    demangling: Demangling,
+}
+
+impl Default for IngestorOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IngestorOptions {
+    /// Blocks of changes aim for this many bytes unless told otherwise.
+    pub const DEFAULT_BLOCK_SIZE: usize = 10 * 1024;
+
+    /// Scopes with more variables than this are [crowded](Ingestor::crowded_scopes) unless told
+    /// otherwise.
+    pub const DEFAULT_CROWDED_SCOPE_VARS: usize = 10_000;
+
+    /// Femtosecond timesteps, and everything else off.
+    pub fn new() -> Self {
+        Self {
+            femtoseconds_per_timestep: 1,
+            block_size: Self::DEFAULT_BLOCK_SIZE,
+            memory_limit: 0,
+            scratch_dir: None,
+            dedup: false,
+            time_index: false,
+            strict: false,
+            ignored: vec![],
+            crowded_scope_vars: Self::DEFAULT_CROWDED_SCOPE_VARS,
+            warnings_as_errors: false,
+            demangling: Demangling::default(),
         }
+    }
 
-        Ok(())
+    pub fn with_timescale(mut self, femtoseconds_per_timestep: u128) -> Self {
+        self.femtoseconds_per_timestep = femtoseconds_per_timestep;
+        self
+    }
+
+    /// Write changes in blocks of about this many bytes. Bigger blocks make for a smaller index
+    /// of them, but more to read to find any one change.
+    pub fn with_block_size(mut self, bytes: usize) -> Self {
+        self.block_size = bytes;
+        self
+    }
+
+    /// Keep changes in memory until there are more than this many bytes of them, rather than
+    /// writing them to a scratch file from the start. It's split evenly between the partitions
+    /// that are written in parallel, and each one spills to its own file once it's full.
+    ///
+    /// The default of 0 always uses scratch files.
+    pub fn with_memory_limit(mut self, bytes: u64) -> Self {
+        self.memory_limit = bytes;
+        self
+    }
+
+    /// Put scratch files in `dir`, rather than the [default](scratch::dir).
+    pub fn with_scratch_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.scratch_dir = Some(dir.into());
+        self
+    }
+
+    /// Drop changes that have the same value as the change before them to the same storage, as
+    /// some simulators write them. See [`Processed::repeats_dropped`].
+    ///
+    /// Changes to events are always kept. Until a lazily loaded storage is loaded, its
+    /// [change count](Processed::change_count) still includes its repeats.
+    pub fn with_dedup(mut self) -> Self {
+        self.dedup = true;
+        self
+    }
+
+    /// Also build a time-major index of which storages change at each timestep, for
+    /// [`Processed::snapshot`] and [`Processed::changed_at`].
+    ///
+    /// This costs 16 bytes of memory per change.
+    pub fn with_time_index(mut self) -> Self {
+        self.time_index = true;
+        self
+    }
+
+    /// Reject values that don't fit their storages, instead of cutting them down to size.
+    /// Loaders that would otherwise repair what they read, and warn about it, fail instead.
+    pub fn with_strict(mut self) -> Self {
+        self.strict = true;
+        self
+    }
+
+    /// Skip the variables whose full hierarchical names match `glob`, such as the words of a
+    /// memory with `top.ram.mem*`. Calling this again skips those that match either.
+    ///
+    /// Skipped variables are left out of the hierarchy, and the changes to their storages are
+    /// dropped as they're ingested, unless another variable that isn't skipped shares them. See
+    /// [`Processed::ignored_vars`].
+    pub fn with_ignored(mut self, glob: search::Glob) -> Self {
+        self.ignored.push(glob);
+        self
+    }
+
+    /// Count scopes with more than `vars` variables directly in them as
+    /// [crowded](Ingestor::crowded_scopes).
+    pub fn with_crowded_scope_vars(mut self, vars: usize) -> Self {
+        self.crowded_scope_vars = vars;
+        self
+    }
+
+    /// Fail with [`Error::Warnings`] instead of finishing if there's anything to
+    /// [warn](Processed::warnings) about, or instead of loading a lazily loaded storage that
+    /// has any, for checking in CI that waveforms load cleanly.
+    pub fn with_warnings_as_errors(mut self) -> Self {
+        self.warnings_as_errors = true;
+        self
+    }
+
+    /// Change which rules tidy up scope and variable names for display. See
+    /// [`Processed::display_name`].
+    pub fn with_demangling(mut self, demangling: Demangling) -> Self {
+        self.demangling = demangling;
+        self
+    }
+
+    /// The directory scratch files go in.
+    pub fn scratch_dir(&self) -> PathBuf {
+        self.scratch_dir.clone().unwrap_or_else(scratch::dir)
+    }
+
+    /// How many bytes of changes stay in memory before any are written to scratch files.
+    pub fn memory_limit(&self) -> u64 {
+        self.memory_limit
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.strict
     }
 }
 
 pub struct Ingestor {
     femtoseconds_per_timestep: u128,
+    names: NameTable,
+    strings: Strings,
     scopes: FnvHashMap<ScopeId, meta::Scope>,
     vars: Vec<meta::Var>,
     storages: FnvHashMap<StorageId, meta::Storage>,
     current_timestep: Timesteps,
-    writer: BufWriter<File>,
-    writer_offset: u64,
-    blocks: FnvHashMap<StorageId, Block>,
+    first_timestep: Option<Timesteps>,
+    last_timestep: Timesteps,
+    partitions: Vec<Partition>,
+    time_index: Option<TimeIndex>,
+    demangling: Demangling,
+    /// Storages to commit before any others, and their place in line.
+    priority: FnvHashMap<StorageId, usize>,
+    dedup: bool,
+    repeats: u64,
+    block_size: usize,
+    scratch_dir: PathBuf,
+    strict: bool,
+    ignored: Vec<search::Glob>,
+    /// Storages that only skipped variables have, whose changes are dropped.
+    ignored_storages: FnvHashSet<StorageId>,
+    /// Storages that a variable that wasn't skipped has, which are kept even if skipped
+    /// variables share them. Only filled in if there's anything to skip.
+    kept_storages: FnvHashSet<StorageId>,
+    ignored_vars: u64,
+    crowded_scope_vars: usize,
+    warnings: Vec<Warning>,
+    warnings_as_errors: bool,
+}
+
+/// A scope with more variables directly in it than is sensible to load, usually because it's a
+/// memory dumped a word at a time. See [`Ingestor::crowded_scopes`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrowdedScope {
+    /// The full hierarchical name of the scope, e.g. `top.ram`.
+    pub path: String,
+    pub vars: usize,
+}
+
+impl CrowdedScope {
+    /// A glob that [skips](IngestorOptions::with_ignored) every variable in the scope.
+    pub fn ignore_pattern(&self) -> String {
+        format!("{}.*", search::escape(&self.path))
+    }
+}
+
+impl fmt::Display for CrowdedScope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "scope `{}` has {} variables; they can be skipped by ignoring `{}`",
+            self.path,
+            self.vars,
+            self.ignore_pattern()
+        )
+    }
+}
+
+/// Something a loader worked around instead of failing on. These are kept with the waveform, so
+/// that they can be shown once it's loaded. See [`Processed::warnings`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Warning {
+    Crowded(CrowdedScope),
+    /// A change to the variable with this code in the file had more values than its width, so
+    /// it was cut down to size. Only the first one of each variable is warned about.
+    Truncated {
+        code: String,
+        dropped: usize,
+        width: usize,
+    },
+    /// A variable of a kind that isn't supported, which was left out.
+    Unsupported {
+        path: String,
+        kind: String,
+    },
+    /// Changes that were dropped for repeating the value before them, as
+    /// [`IngestorOptions::with_dedup`] asked for.
+    RepeatsDropped(u64),
+    /// The scratch directory looked to have less room than loading might need, going by the
+    /// size of the file. See [`scratch::check_space`].
+    ScratchSpace {
+        dir: String,
+        needed: u64,
+        available: u64,
+    },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Warning::Crowded(scope) => write!(f, "{}", scope),
+            Warning::Truncated {
+                code,
+                dropped,
+                width,
+            } => write!(
+                f,
+                "a value of `{}` has {} more values than its declared width of {}, so it was \
+                 truncated",
+                code, dropped, width
+            ),
+            Warning::Unsupported { path, kind } => {
+                write!(
+                    f,
+                    "`{}` is a {}, which isn't supported, so it was left out",
+                    path, kind
+                )
+            }
+            Warning::RepeatsDropped(repeats) => write!(
+                f,
+                "{} change{} repeated the value before, so {} dropped",
+                repeats,
+                if *repeats == 1 { "" } else { "s" },
+                if *repeats == 1 { "it was" } else { "they were" }
+            ),
+            Warning::ScratchSpace {
+                dir,
+                needed,
+                available,
+            } => write!(
+                f,
+                "scratch directory `{}` has {} bytes free, but loading may need about {}; set \
+                 LIGEIA_SCRATCH_DIR to use another",
+                dir, available, needed
+            ),
+        }
+    }
 }
 
 impl Ingestor {
-    pub fn new(femtoseconds_per_timestep: u128) -> Result<Self, Error> {
-        let writer = BufWriter::new(tempfile()?);
+    pub fn new(options: IngestorOptions) -> Result<Self, Error> {
+        let femtoseconds_per_timestep =
+            timescale::validate_femtoseconds(options.femtoseconds_per_timestep)?;
+        let scratch_dir = options.scratch_dir();
+        let count = rayon::current_num_threads().max(1);
+        let partitions = (0..count)
+            .map(|_| Partition::new(options.memory_limit / count as u64, &scratch_dir))
+            .collect::<Result<_, _>>()?;
 
         Ok(Self {
             femtoseconds_per_timestep,
+            names: NameTable::new(),
+            strings: Strings::default(),
             scopes: FnvHashMap::default(),
             vars: vec![],
             storages: FnvHashMap::default(),
             current_timestep: Timesteps(0),
-            writer,
-            writer_offset: 0,
-            blocks: FnvHashMap::default(),
+            first_timestep: None,
+            last_timestep: Timesteps(0),
+            partitions,
+            time_index: options.time_index.then(TimeIndex::default),
+            demangling: options.demangling,
+            priority: FnvHashMap::default(),
+            dedup: options.dedup,
+            repeats: 0,
+            block_size: options.block_size,
+            scratch_dir,
+            strict: options.strict,
+            ignored: options.ignored,
+            ignored_storages: FnvHashSet::default(),
+            kept_storages: FnvHashSet::default(),
+            ignored_vars: 0,
+            crowded_scope_vars: options.crowded_scope_vars,
+            warnings: vec![],
+            warnings_as_errors: options.warnings_as_errors,
         })
     }
 
+    /// Commit these storages before any others when finishing, in the order they're given, such
+    /// as the ones a viewer is about to show. Calling this again puts more after them.
+    pub fn prioritize<I>(&mut self, ids: I)
+    where
+        I: IntoIterator<Item = StorageId>,
+    {
+        for id in ids {
+            let next = self.priority.len();
+            self.priority.entry(id).or_insert(next);
+        }
+    }
+
+    /// Get the id for a scope or variable name.
+    pub fn intern(&mut self, name: &str) -> NameId {
+        self.names.intern(name)
+    }
+
     pub fn ingest_scope(&mut self, scope: meta::Scope) {
         self.scopes.insert(scope.id, scope);
     }
 
+    /// Add a variable, unless it's [ignored](IngestorOptions::with_ignored). Its scope has to
+    /// have been ingested already for that to work.
     pub fn ingest_var(&mut self, var: meta::Var) {
-        self.vars.push(var);
+        if self.ignored.is_empty() {
+            self.vars.push(var);
+            return;
+        }
+
+        let mut path = scope_path(&self.scopes, &self.names, var.scope_id);
+        path.push(self.names.get(var.name));
+        let path = path.join(".");
+        let storages = Processed::var_storages(&var);
+        if self.ignored.iter().any(|glob| glob.matches(&path)) {
+            for &id in storages {
+                if !self.kept_storages.contains(&id) {
+                    self.ignored_storages.insert(id);
+                }
+            }
+            self.ignored_vars += 1;
+        } else {
+            for &id in storages {
+                self.ignored_storages.remove(&id);
+                self.kept_storages.insert(id);
+            }
+            self.vars.push(var);
+        }
     }
 
     pub fn ingest_storage(&mut self, storage: meta::Storage) {
         assert_eq!(storage.start, 0, "for now, storage.start must be 0");
 
         let id = storage.id;
-        let bytes = match storage.ty {
-            meta::StorageType::TwoLogic => (storage.width + 7) / 8, // 8 bits per byte
-            meta::StorageType::FourLogic => (storage.width + 3) / 4, // 4 qits per byte
-            meta::StorageType::NineLogic => storage.width,          // 1 nit per byte
-        };
+        let block = Block::new(&storage, self.dedup, self.block_size);
 
         self.storages.insert(id, storage);
-        self.blocks.insert(id, Block::new(bytes));
+        let partition = partition_of(id, self.partitions.len());
+        self.partitions[partition].blocks.insert(id, block);
+    }
+
+    /// The scopes ingested so far with more variables directly in them than
+    /// [`IngestorOptions::with_crowded_scope_vars`] allows, not counting ignored ones, so that a
+    /// loader can warn about them once it's read the hierarchy.
+    pub fn crowded_scopes(&self) -> Vec<CrowdedScope> {
+        let mut counts: FnvHashMap<ScopeId, usize> = FnvHashMap::default();
+        for var in &self.vars {
+            *counts.entry(var.scope_id).or_default() += 1;
+        }
+
+        let mut crowded: Vec<_> = counts
+            .into_iter()
+            .filter(|&(_, vars)| vars > self.crowded_scope_vars)
+            .map(|(id, vars)| CrowdedScope {
+                path: scope_path(&self.scopes, &self.names, id).join("."),
+                vars,
+            })
+            .collect();
+        crowded.sort_by(|a, b| a.path.cmp(&b.path));
+        crowded
+    }
+
+    /// Note something the loader worked around, to go with the waveform once it's finished.
+    pub fn warn(&mut self, warning: Warning) {
+        self.warnings.push(warning);
     }
 
     pub fn ingest_timestep(&mut self, new: Timesteps) {
         self.current_timestep = new;
+        self.first_timestep.get_or_insert(new);
+        self.last_timestep = self.last_timestep.max(new);
+    }
+
+    /// The time and value of the last change ingested for a storage, without waiting for it to
+    /// be written out.
+    ///
+    /// The value is as it's stored: zero-extended or cut to the storage's width. Values of
+    /// [`Utf8`](meta::StorageType::Utf8) storages are their text, as they were ingested.
+    pub fn last_value(&self, id: StorageId) -> Result<Option<(Timesteps, &[u8])>, Error> {
+        let partition = partition_of(id, self.partitions.len());
+        let block = self.partitions[partition]
+            .blocks
+            .get(&id)
+            .ok_or(Error::UnknownStorage(id))?;
+        Ok(block
+            .last_value()
+            .map(|(time, value)| match block.interned {
+                true => {
+                    let index = u32::from_le_bytes(value.try_into().unwrap());
+                    (time, self.strings.table.get(NameId(index)).as_bytes())
+                }
+                false => (time, value),
+            }))
     }
 
     pub fn ingest_value(&mut self, value: Value) -> Result<(), Error> {
-        self.blocks.get_mut(&value.storage_id).unwrap().push(
-            &mut self.writer,
-            &mut self.writer_offset,
-            self.current_timestep,
-            value.data,
-        )?;
+        if self.ignored_storages.contains(&value.storage_id) {
+            return Ok(());
+        }
+        let partition = partition_of(value.storage_id, self.partitions.len());
+        let partition = &mut self.partitions[partition];
+        let block = partition
+            .blocks
+            .get_mut(&value.storage_id)
+            .ok_or(Error::UnknownStorage(value.storage_id))?;
+        if self.strict && !block.fits(value.data) {
+            return Err(Error::ValueTooLong(
+                value.storage_id,
+                value.data.len(),
+                block.bytes,
+            ));
+        }
+        let index;
+        let data = match block.interned {
+            true => {
+                index = self.strings.intern(value.data);
+                &index[..]
+            }
+            false => value.data,
+        };
+        if block.is_repeat(data) {
+            if block.interned {
+                self.strings.uncount(value.data);
+            }
+            self.repeats += 1;
+            return Ok(());
+        }
+        block
+            .push(
+                &mut partition.writer,
+                &mut partition.writer_offset,
+                self.current_timestep,
+                data,
+            )
+            .map_err(|e| scratch::write_error(&self.scratch_dir, e))?;
+
+        if let Some(index) = &mut self.time_index {
+            index.record(self.current_timestep, value.storage_id, block.changes - 1);
+        }
 
         Ok(())
     }
 
     pub fn finish(self) -> Result<Processed, Error> {
-        let mut writer = self.writer;
-        let mut writer_offset = self.writer_offset;
+        self.finish_with(None)
+    }
 
-        let blocks = self
-            .blocks
-            .into_iter()
-            .map(|(id, block)| Ok((id, block.commit(&mut writer, &mut writer_offset)?)))
-            .collect::<Result<_, io::Error>>()?;
+    /// Finish ingestion, leaving any storage that wasn't given values to be loaded from `source`
+    /// the first time it's accessed.
+    pub fn finish_lazy(self, source: Box<dyn LazySource>) -> Result<Processed, Error> {
+        self.finish_with(Some(source))
+    }
+
+    fn finish_with(mut self, lazy: Option<Box<dyn LazySource>>) -> Result<Processed, Error> {
+        if self.repeats != 0 {
+            self.warnings.push(Warning::RepeatsDropped(self.repeats));
+        }
+        if self.warnings_as_errors && !self.warnings.is_empty() {
+            return Err(Error::Warnings(self.warnings));
+        }
+
+        for id in &self.ignored_storages {
+            self.storages.remove(id);
+            let partition = partition_of(*id, self.partitions.len());
+            self.partitions[partition].blocks.remove(id);
+        }
+
+        let keep_empty = lazy.is_none();
+        let committed = self
+            .partitions
+            .into_par_iter()
+            .map(|partition| partition.commit(keep_empty, &self.priority))
+            .collect::<Result<Vec<_>, io::Error>>()
+            .map_err(|e| scratch::write_error(&self.scratch_dir, e))?;
+
+        let mut files = Vec::with_capacity(committed.len());
+        let mut blocks = FnvHashMap::default();
+        for (file, partition_blocks) in committed {
+            files.push(file);
+            blocks.extend(partition_blocks);
+        }
+
+        let (children, arrays) = hierarchy::index(&self.scopes, &self.vars, &self.names);
 
-        Ok(Processed {
+        // Everything has been ingested, so give back what's left over from growing as it was.
+        self.names.shrink_to_fit();
+        self.strings.table.shrink_to_fit();
+        self.vars.shrink_to_fit();
+        if let Some(index) = &mut self.time_index {
+            index.shrink_to_fit();
+        }
+
+        let mut processed = Processed {
             femtoseconds_per_timestep: self.femtoseconds_per_timestep,
+            time_bounds: (
+                self.first_timestep.unwrap_or(Timesteps(0)),
+                self.last_timestep,
+            ),
+            names: self.names,
+            strings: self.strings,
             scopes: self.scopes,
             vars: self.vars,
+            children,
+            arrays,
             storages: self.storages,
-            reader: BufReader::new(writer.into_inner().unwrap()),
+            files,
             blocks,
-        })
+            // Lazily loaded storages never go through `ingest_value`, so the index wouldn't
+            // cover them.
+            time_index: self.time_index.filter(|_| lazy.is_none()),
+            lazy,
+            display_names: FnvHashMap::default(),
+            dedup: self.dedup,
+            repeats: self.repeats,
+            ignored_vars: self.ignored_vars,
+            block_size: self.block_size,
+            scratch_dir: self.scratch_dir,
+            strict: self.strict,
+            warnings: self.warnings,
+            warnings_as_errors: self.warnings_as_errors,
+            refused: FnvHashMap::default(),
+        };
+        processed.set_demangling(self.demangling);
+        Ok(processed)
     }
 }
 
 pub struct Processed {
     femtoseconds_per_timestep: u128,
+    time_bounds: (Timesteps, Timesteps),
+    names: NameTable,
+    strings: Strings,
     scopes: FnvHashMap<ScopeId, meta::Scope>,
     vars: Vec<meta::Var>,
+    children: FnvHashMap<ScopeId, Children>,
+    arrays: FnvHashMap<ScopeId, hierarchy::Array>,
     storages: FnvHashMap<StorageId, meta::Storage>,
 
-    reader: BufReader<File>,
+    /// One per partition. These are only ever read positionally, so their ends are free for
+    /// appending lazily loaded storages.
+    files: Vec<Scratch>,
     blocks: FnvHashMap<StorageId, CommittedBlocks>,
+    lazy: Option<Box<dyn LazySource>>,
+    time_index: Option<TimeIndex>,
+    /// The demangled names of scopes and variables, where they differ from their names.
+    display_names: FnvHashMap<NameId, NameId>,
+    /// Whether lazily loaded storages drop repeated values too.
+    dedup: bool,
+    repeats: u64,
+    ignored_vars: u64,
+    /// How lazily loaded storages are stored, as they were given to the ingestor.
+    block_size: usize,
+    scratch_dir: PathBuf,
+    strict: bool,
+    warnings: Vec<Warning>,
+    warnings_as_errors: bool,
+    /// Lazily loaded storages that failed for having warnings, with warnings as errors.
+    refused: FnvHashMap<StorageId, Vec<Warning>>,
 }
 
 impl Processed {
@@ -235,23 +1269,918 @@ impl Processed {
         self.femtoseconds_per_timestep
     }
 
-    /// Temporary for testing
+    /// The first and last timesteps present in the waveform.
+    pub fn time_bounds(&self) -> (Timesteps, Timesteps) {
+        self.time_bounds
+    }
+
+    /// The number of value changes recorded for a storage.
+    pub fn change_count(&self, id: StorageId) -> Result<u64, Error> {
+        self.check_storage(id)?;
+        Ok(match (self.blocks.get(&id), &self.lazy) {
+            (None, Some(lazy)) => lazy.change_count(id),
+            _ => self.blocks[&id].changes,
+        })
+    }
+
+    /// How many times a storage changes in each of `buckets` equal spans of the waveform's time
+    /// bounds, for telling at a glance whether, and when, it's active.
+    ///
+    /// This only looks at where each block of changes starts and ends, not at the changes, so
+    /// the changes in the middle of each block are taken to be spread evenly through it.
+    /// Storages loaded on demand are loaded first.
+    pub fn change_density(&mut self, id: StorageId, buckets: usize) -> Result<Vec<u64>, Error> {
+        self.ensure_loaded(id)?;
+        let blocks = &self.blocks[&id].block_offsets;
+        let (first, last) = self.time_bounds;
+        let span = (last.0 - first.0) as u128 + 1;
+
+        // Roughly how many changes come before `time`.
+        let changes_before = |time: u128| -> u64 {
+            let index = match blocks
+                .partition_point(|block| (block.start.0 as u128) < time)
+                .checked_sub(1)
+            {
+                Some(index) => index,
+                None => return 0,
+            };
+            let block = &blocks[index];
+            let changes = match blocks.get(index + 1) {
+                Some(next) => next.first_change - block.first_change,
+                None => self.blocks[&id].changes - block.first_change,
+            };
+            // The first and last changes are where the block says, and only the rest are spread
+            // out between them, so signals that rarely change are counted exactly.
+            let (start, end) = (block.start.0 as u128, block.end.0 as u128);
+            let within = match time > end {
+                true => changes,
+                false => {
+                    let between = changes.saturating_sub(2) as u128;
+                    1 + (between * (time - start - 1) / (end - start)) as u64
+                }
+            };
+            block.first_change + within
+        };
+
+        let boundary = |bucket: usize| first.0 as u128 + span * bucket as u128 / buckets as u128;
+        Ok((0..buckets)
+            .map(|bucket| changes_before(boundary(bucket + 1)) - changes_before(boundary(bucket)))
+            .collect())
+    }
+
+    pub fn storage(&self, id: StorageId) -> Result<&meta::Storage, Error> {
+        self.storages.get(&id).ok_or(Error::UnknownStorage(id))
+    }
+
+    fn check_storage(&self, id: StorageId) -> Result<(), Error> {
+        self.storage(id).map(|_| ())
+    }
+
+    /// Every storage, in ascending id order, so that anything derived from the list is the same
+    /// from run to run.
     pub fn storage_ids(&self) -> Vec<StorageId> {
-        self.storages.keys().copied().collect()
+        let mut ids: Vec<_> = self.storages.keys().copied().collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    pub fn name(&self, id: NameId) -> &str {
+        self.names.get(id)
+    }
+
+    pub fn names(&self) -> &NameTable {
+        &self.names
+    }
+
+    /// A scope or variable name, tidied up for display by the rules given to
+    /// [`IngestorOptions::with_demangling`] or [`Processed::set_demangling`].
+    pub fn display_name(&self, id: NameId) -> &str {
+        self.names
+            .get(self.display_names.get(&id).copied().unwrap_or(id))
+    }
+
+    /// Change which rules tidy up names for display.
+    pub fn set_demangling(&mut self, demangling: Demangling) {
+        let ids: Vec<_> = self
+            .scopes
+            .values()
+            .map(|scope| scope.name)
+            .chain(self.vars.iter().map(|var| var.name))
+            .collect();
+
+        self.display_names.clear();
+        for id in ids {
+            if let Cow::Owned(display) = demangling.apply(self.names.get(id)) {
+                let display = self.names.intern(&display);
+                self.display_names.insert(id, display);
+            }
+        }
+    }
+
+    /// The text of a change to a [`Utf8`](meta::StorageType::Utf8) storage, given the data that
+    /// [`Processed::load_storage`] or [`Processed::value_at`] produced for it.
+    ///
+    /// # Panics
+    /// If `data` isn't an index into the string table.
+    pub fn string(&self, data: &[u8]) -> &str {
+        let index = u32::from_le_bytes(data[..4].try_into().unwrap());
+        self.strings.table.get(NameId(index))
+    }
+
+    /// How much deduplicating string changes has saved so far. Storages that are loaded lazily
+    /// only count once they've been loaded.
+    pub fn string_stats(&self) -> StringStats {
+        self.strings.stats()
+    }
+
+    /// How many changes were dropped for repeating the value before them, if
+    /// [`IngestorOptions::with_dedup`] was used. Storages that are loaded lazily only count once
+    /// they've been loaded.
+    pub fn repeats_dropped(&self) -> u64 {
+        self.repeats
+    }
+
+    /// How many variables were left out for matching [`IngestorOptions::with_ignored`].
+    pub fn ignored_vars(&self) -> u64 {
+        self.ignored_vars
+    }
+
+    /// What the loader worked around, in the order it came across it. Lazily loaded storages
+    /// add theirs once they've been loaded, except for repeats, which are only counted as of
+    /// when ingestion finished.
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// Add a warning from outside the loader, like the one from [`scratch::check_space`]. With
+    /// warnings as errors, this fails with it instead.
+    pub fn warn(&mut self, warning: Warning) -> Result<(), Error> {
+        if self.warnings_as_errors {
+            return Err(Error::Warnings(vec![warning]));
+        }
+        self.warnings.push(warning);
+        Ok(())
+    }
+
+    /// How much memory and scratch space the waveform takes up, including lazily loaded
+    /// storages that have been loaded so far.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let blocks = self.blocks.values();
+        MemoryUsage {
+            block_index: blocks
+                .clone()
+                .map(|blocks| blocks.block_offsets.capacity() * mem::size_of::<BlockOffset>())
+                .sum(),
+            names: self.names.heap_bytes(),
+            strings: self.strings.table.heap_bytes(),
+            time_index: self.time_index.as_ref().map_or(0, TimeIndex::heap_bytes),
+            scratch: blocks
+                .flat_map(|blocks| &blocks.block_offsets)
+                .map(|block| block.len as u64)
+                .sum(),
+            scratch_in_memory: self.files.iter().map(Scratch::memory_len).sum(),
+        }
+    }
+
+    /// Write everything about the waveform but its changes in `version` of the
+    /// [metadata encoding](schema), for [`schema::Metadata::read_from`] to read back.
+    ///
+    /// Storages that are loaded lazily only have their blocks written once they've been loaded.
+    pub fn write_metadata<W: Write>(
+        &self,
+        writer: W,
+        version: u32,
+    ) -> Result<(), schema::SchemaError> {
+        let mut encoder = schema::Encoder::new(writer, version)?;
+        encoder.u128(self.femtoseconds_per_timestep)?;
+        encoder.encode(&self.time_bounds.0)?;
+        encoder.encode(&self.time_bounds.1)?;
+        self.names.write_to(encoder.writer())?;
+        self.strings.table.write_to(encoder.writer())?;
+
+        let mut scopes: Vec<_> = self.scopes.values().collect();
+        scopes.sort_unstable_by_key(|scope| scope.id);
+        encoder.list(scopes.into_iter())?;
+        encoder.encode(&self.vars[..])?;
+
+        let mut storages: Vec<_> = self.storages.values().collect();
+        storages.sort_unstable_by_key(|storage| storage.id);
+        encoder.list(storages.into_iter())?;
+
+        let mut blocks: Vec<_> = self
+            .blocks
+            .iter()
+            .map(|(&storage, blocks)| schema::BlockIndex {
+                storage,
+                bytes: blocks.bytes,
+                changes: blocks.changes,
+                blocks: blocks
+                    .block_offsets
+                    .iter()
+                    .map(|block| schema::BlockEntry {
+                        offset: block.offset,
+                        len: block.len as u64,
+                        start: block.start,
+                        first_change: block.first_change,
+                        summary: block.summary,
+                    })
+                    .collect(),
+            })
+            .collect();
+        blocks.sort_unstable_by_key(|index| index.storage);
+        encoder.encode(&blocks[..])?;
+        Ok(())
+    }
+
+    /// Every variable, in the order the loader declared them.
+    pub fn vars(&self) -> &[meta::Var] {
+        &self.vars
+    }
+
+    /// The scopes and variables directly within a scope. Scopes are in ascending id order, and
+    /// variables in the order they were declared.
+    ///
+    /// Unlike [`children_of`](Self::children_of), the elements of arrays are listed along with
+    /// everything else. Within an array, they're its elements.
+    pub fn within_scope(&self, id: ScopeId) -> Result<(Vec<&meta::Scope>, Vec<&meta::Var>), Error> {
+        let children = match self.children(id)? {
+            Some(children) => children,
+            None => return Ok((vec![], vec![])),
+        };
+
+        let scopes = children.scopes.iter().map(|id| &self.scopes[id]).collect();
+        let vars = children
+            .vars
+            .iter()
+            .map(|&i| &self.vars[i as usize])
+            .collect();
+        Ok((scopes, vars))
+    }
+
+    /// How many scopes and variables are directly within a scope, without listing them, as
+    /// [`children_of`](Self::children_of) lists them.
+    pub fn child_counts(&self, id: ScopeId) -> Result<ChildCounts, Error> {
+        Ok(self
+            .children(id)?
+            .map_or_else(ChildCounts::default, Children::counts))
+    }
+
+    /// Up to `limit` of the scopes and variables directly within a scope, skipping the first
+    /// `offset` of them.
+    ///
+    /// Children are in the same order as [`within_scope`](Self::within_scope) gives them, with
+    /// the scopes first, so paging through them all yields each once. [Arrays](hierarchy::Array)
+    /// come between the scopes and variables, by name, in place of their elements; pass an
+    /// array's id to page through its elements.
+    pub fn children_of(
+        &self,
+        id: ScopeId,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<Child<'_>>, Error> {
+        let children = match self.children(id)? {
+            Some(children) => children,
+            None => return Ok(vec![]),
+        };
+
+        // Slicing, rather than skipping, keeps deep pages as cheap as the first.
+        let end = offset.saturating_add(limit);
+        // The part of the page that falls in a list of `len` children, with `before` more
+        // ahead of it.
+        let page = |len: usize, before: usize| {
+            offset.saturating_sub(before).min(len)..end.saturating_sub(before).min(len)
+        };
+        let (scopes, arrays, vars) = match &children.grouped {
+            Some(grouped) => (&grouped.scopes, &grouped.arrays[..], &grouped.vars),
+            None => (&children.scopes, &[][..], &children.vars),
+        };
+        let before_vars = scopes.len() + arrays.len();
+        let scopes = &scopes[page(scopes.len(), 0)];
+        let arrays = &arrays[page(arrays.len(), before_vars - arrays.len())];
+        let vars = &vars[page(vars.len(), before_vars)];
+
+        let scopes = scopes.iter().map(|id| Child::Scope(&self.scopes[id]));
+        let arrays = arrays.iter().map(|id| Child::Array(&self.arrays[id]));
+        let vars = vars.iter().map(|&i| Child::Var(&self.vars[i as usize]));
+        Ok(scopes.chain(arrays).chain(vars).collect())
+    }
+
+    /// The array with pseudo-scope `id`, if it is one.
+    pub fn array(&self, id: ScopeId) -> Option<&hierarchy::Array> {
+        self.arrays.get(&id)
+    }
+
+    /// The element of an array with `index`, if it has one, without paging through them to find
+    /// it.
+    pub fn array_element(&self, id: ScopeId, index: i64) -> Result<Option<Child<'_>>, Error> {
+        let array = self.arrays.get(&id).ok_or(Error::UnknownScope(id))?;
+        let position = match array.position(index) {
+            Some(position) => position,
+            None => return Ok(None),
+        };
+        let children = &self.children[&id];
+        Ok(Some(match array.scopes {
+            true => Child::Scope(&self.scopes[&children.scopes[position]]),
+            false => Child::Var(&self.vars[children.vars[position] as usize]),
+        }))
+    }
+
+    /// The storages holding a variable's value.
+    pub fn var_storages(var: &meta::Var) -> &[StorageId] {
+        match &var.kind {
+            meta::VarKind::Integer { storages, .. } => storages,
+            meta::VarKind::Enum { storage, .. }
+            | meta::VarKind::Utf8 { storage }
+            | meta::VarKind::Event { storage } => std::slice::from_ref(storage),
+            meta::VarKind::None => &[],
+        }
+    }
+
+    /// How many bits wide a variable is, or `None` for strings, events and variables without a
+    /// value.
+    pub fn var_width(&self, var: &meta::Var) -> Result<Option<u32>, Error> {
+        if matches!(
+            var.kind,
+            meta::VarKind::Utf8 { .. } | meta::VarKind::Event { .. } | meta::VarKind::None
+        ) {
+            return Ok(None);
+        }
+        Self::var_storages(var)
+            .iter()
+            .map(|&id| Ok(self.storage(id)?.width))
+            .sum::<Result<u32, Error>>()
+            .map(Some)
+    }
+
+    /// The variables directly within a scope that pass `query`'s filters, in its order.
+    ///
+    /// Ties keep their declaration order. Filtering by `changed_in` reads the blocks of each
+    /// storage that overlap the range, loading lazily loaded storages as it goes.
+    pub fn vars_within(&mut self, id: ScopeId, query: &VarQuery) -> Result<Vec<&meta::Var>, Error> {
+        let indices = match self.children(id)? {
+            Some(children) => children.vars.clone(),
+            None => return Ok(vec![]),
+        };
+
+        let mut kept = vec![];
+        for i in indices {
+            let var = &self.vars[i as usize];
+            let width = self.var_width(var)?;
+            let keep = match query.width {
+                WidthFilter::Any => true,
+                WidthFilter::Bits => width == Some(1),
+                WidthFilter::Buses => matches!(width, Some(width) if width > 1),
+            };
+            if keep {
+                kept.push((i, width));
+            }
+        }
+
+        if let Some(range) = &query.changed_in {
+            let mut changed = Vec::with_capacity(kept.len());
+            for (i, width) in kept {
+                let storages = Self::var_storages(&self.vars[i as usize]).to_vec();
+                let mut any = false;
+                for storage in storages {
+                    self.changes_in_range_with_initial(storage, range.clone(), |timestamp, _| {
+                        // Only the value in effect before the range can start earlier.
+                        any |= timestamp >= range.start;
+                    })?;
+                }
+                if any {
+                    changed.push((i, width));
+                }
+            }
+            kept = changed;
+        }
+
+        match query.order {
+            VarOrder::Declaration => {}
+            VarOrder::Name => {
+                let name = |i: u32| self.names.get(self.vars[i as usize].name);
+                kept.sort_by(|&(a, _), &(b, _)| names::natural_cmp(name(a), name(b)))
+            }
+            VarOrder::Width => kept.sort_by_key(|&(_, width)| std::cmp::Reverse(width)),
+            VarOrder::Activity => {
+                let mut activity = Vec::with_capacity(kept.len());
+                for &(i, _) in &kept {
+                    let storages = Self::var_storages(&self.vars[i as usize]);
+                    activity.push(
+                        storages
+                            .iter()
+                            .map(|&id| self.change_count(id))
+                            .sum::<Result<u64, Error>>()?,
+                    );
+                }
+                let mut ordered: Vec<_> = kept.into_iter().zip(activity).collect();
+                ordered.sort_by_key(|&(_, activity)| std::cmp::Reverse(activity));
+                kept = ordered.into_iter().map(|(kept, _)| kept).collect();
+            }
+        }
+
+        Ok(kept
+            .into_iter()
+            .map(|(i, _)| &self.vars[i as usize])
+            .collect())
     }
 
-    pub fn within_scope(&self, id: ScopeId) -> (Vec<&meta::Scope>, Vec<&meta::Var>) {
-        let scopes = self.scopes.values().filter(|s| s.parent == id).collect();
-        let vars = self.vars.iter().filter(|v| v.scope_id == id).collect();
+    /// The children of a scope, or `None` if it has none.
+    fn children(&self, id: ScopeId) -> Result<Option<&Children>, Error> {
+        if id != ScopeId::ROOT && !self.scopes.contains_key(&id) && !self.arrays.contains_key(&id) {
+            return Err(Error::UnknownScope(id));
+        }
+        Ok(self.children.get(&id))
+    }
+
+    /// The names of a scope and all of its ancestors, outermost first.
+    pub fn scope_path(&self, id: ScopeId) -> Vec<&str> {
+        scope_path(&self.scopes, &self.names, id)
+    }
+
+    /// The full hierarchical name of a variable, e.g. `top.cpu.pc`.
+    pub fn var_path(&self, var: &meta::Var) -> String {
+        let mut path = self.scope_path(var.scope_id);
+        path.push(self.names.get(var.name));
+        path.join(".")
+    }
+
+    /// The full hierarchical name of a variable with its names demangled for display, and any
+    /// `.` or `\` in them escaped. See [`demangle::split_path`].
+    pub fn display_path(&self, var: &meta::Var) -> String {
+        let mut path = vec![];
+        let mut id = var.scope_id;
+        while let Some(scope) = self.scopes.get(&id) {
+            path.push(demangle::escape(self.display_name(scope.name)));
+            id = scope.parent;
+        }
+        path.reverse();
+        path.push(demangle::escape(self.display_name(var.name)));
+        path.join(".")
+    }
+
+    /// The variables whose full hierarchical names match `glob`, in declaration order.
+    pub fn find_vars(&self, glob: &search::Glob) -> Vec<&meta::Var> {
+        self.vars
+            .iter()
+            .filter(|var| glob.matches(&self.var_path(var)))
+            .collect()
+    }
+
+    /// The same variable in each instance like one of the scopes it's in, such as
+    /// `top.core1.alu.result` and `top.core2.alu.result` for `top.core0.alu.result`.
+    ///
+    /// Instances are the scopes alongside the one at index `instance` in the variable's
+    /// [`scope_path`](Processed::scope_path) with the same name apart from an index or number at
+    /// the end. Without an `instance`, it's the innermost scope the variable is in that has such
+    /// an instance with the variable in it.
+    ///
+    /// Returns the index of the instance along with the variable from each one that has it, the
+    /// given one included, in the order the hierarchy lists them. Returns `None` if `instance` is
+    /// past the end of the scope path, or there's no instance to use.
+    pub fn across_instances(
+        &self,
+        var: &meta::Var,
+        instance: Option<usize>,
+    ) -> Option<(usize, Vec<&meta::Var>)> {
+        // The scopes the variable is in, innermost first, each with the names of the scopes
+        // within it that lead to the variable.
+        let mut levels = vec![];
+        let mut relative = vec![];
+        let mut id = var.scope_id;
+        while let Some(scope) = self.scopes.get(&id) {
+            levels.push((scope, relative.clone()));
+            relative.insert(0, scope.name);
+            id = scope.parent;
+        }
+
+        let across = |(scope, relative): &(&meta::Scope, Vec<NameId>)| {
+            let stem = hierarchy::instance_stem(self.names.get(scope.name));
+            let siblings = match self.children.get(&scope.parent) {
+                Some(children) => &children.scopes,
+                None => return vec![],
+            };
+            siblings
+                .iter()
+                .filter(|&id| {
+                    hierarchy::instance_stem(self.names.get(self.scopes[id].name)) == stem
+                })
+                .filter_map(|&id| self.resolve(id, relative, var.name))
+                .collect::<Vec<_>>()
+        };
+
+        let depth = levels.len();
+        match instance {
+            Some(index) if index < depth => Some((index, across(&levels[depth - 1 - index]))),
+            Some(_) => None,
+            None => levels.iter().enumerate().find_map(|(i, level)| {
+                let vars = across(level);
+                (vars.len() > 1).then(|| (depth - 1 - i, vars))
+            }),
+        }
+    }
+
+    /// Follow scope names down from `id` to a variable named `name`.
+    fn resolve(&self, mut id: ScopeId, scopes: &[NameId], name: NameId) -> Option<&meta::Var> {
+        for &scope in scopes {
+            id = *self
+                .children
+                .get(&id)?
+                .scopes
+                .iter()
+                .find(|child| self.scopes[child].name == scope)?;
+        }
+        self.children
+            .get(&id)?
+            .vars
+            .iter()
+            .map(|&i| &self.vars[i as usize])
+            .find(|var| var.name == name)
+    }
+
+    /// Associate variables with their source locations, looked up by hierarchical name.
+    ///
+    /// This is how source correlation from outside the waveform (like a sidecar file) is attached.
+    pub fn attach_sources<F>(&mut self, mut lookup: F)
+    where
+        F: FnMut(&str) -> Option<meta::SourceLocation>,
+    {
+        let sources: Vec<_> = self
+            .vars
+            .iter()
+            .map(|var| lookup(&self.var_path(var)))
+            .collect();
+
+        for (var, source) in self.vars.iter_mut().zip(sources) {
+            if source.is_some() {
+                var.source = source;
+            }
+        }
+    }
+
+    /// Find where the variable with the given hierarchical name is declared, if known.
+    pub fn source_of(&self, path: &str) -> Option<&meta::SourceLocation> {
+        self.vars
+            .iter()
+            .find(|var| var.source.is_some() && self.var_path(var) == path)
+            .and_then(|var| var.source.as_ref())
+    }
+
+    /// Find the value of a storage in effect at `time`, or `None` if it hasn't changed yet.
+    pub fn value_at(
+        &mut self,
+        id: StorageId,
+        time: Timesteps,
+    ) -> Result<Option<StableValue>, Error> {
+        self.ensure_loaded(id)?;
+        let blocks = &self.blocks[&id];
+        let file = &self.files[partition_of(id, self.files.len())];
+        let bytes = blocks.bytes as usize;
+        let mut found: Option<StableValue> = None;
+
+        // Only the block the value starts in needs reading. If the value lasts past the end of
+        // it, the next block's start is where it ends.
+        let index = match blocks.block_at(time) {
+            Some(index) => index,
+            None => return Ok(None),
+        };
+
+        blocks.read_block(file, index, |timestamp, data| {
+            if timestamp <= time {
+                let value = found.get_or_insert_with(|| StableValue {
+                    start: timestamp,
+                    end: None,
+                    data: Vec::with_capacity(bytes),
+                });
+                value.start = timestamp;
+                value.data.clear();
+                value.data.extend_from_slice(data);
+            } else if let Some(value) = &mut found {
+                value.end.get_or_insert(timestamp);
+            }
+        })?;
+
+        if let Some(value) = &mut found {
+            if value.end.is_none() {
+                value.end = blocks.block_offsets.get(index + 1).map(|block| block.start);
+            }
+        }
+
+        Ok(found)
+    }
+
+    /// The storages that changed at exactly `time`, in the order they were ingested, if a time
+    /// index was built.
+    pub fn changed_at(&self, time: Timesteps) -> Option<Vec<StorageId>> {
+        self.time_index
+            .as_ref()
+            .map(|index| index.changed_at(time).collect())
+    }
+
+    /// Find the values of several storages in effect at `time`, as from [`Processed::value_at`].
+    ///
+    /// With a time index, this only reads the changes that are actually needed.
+    pub fn snapshot(
+        &mut self,
+        ids: &[StorageId],
+        time: Timesteps,
+    ) -> Result<Vec<Option<StableValue>>, Error> {
+        let index = match &self.time_index {
+            Some(index) => index,
+            None => return ids.iter().map(|&id| self.value_at(id, time)).collect(),
+        };
+
+        for &id in ids {
+            self.check_storage(id)?;
+        }
+
+        let ordinals = index.last_changes(ids, time);
+        ids.iter()
+            .zip(ordinals)
+            .map(|(&id, ordinal)| {
+                let ordinal = match ordinal {
+                    Some(ordinal) => ordinal,
+                    None => return Ok(None),
+                };
+
+                let blocks = &self.blocks[&id];
+                let file = &self.files[partition_of(id, self.files.len())];
+
+                let (start, data) = blocks.read_entry(file, ordinal)?;
+                let end = if ordinal + 1 < blocks.changes {
+                    Some(blocks.read_entry(file, ordinal + 1)?.0)
+                } else {
+                    None
+                };
+
+                Ok(Some(StableValue { start, end, data }))
+            })
+            .collect()
+    }
+
+    /// Every storage, from the most changes to the fewest, which is roughly the order they're
+    /// worth [preloading](Processed::preload) in when nothing better is known.
+    pub fn hot_storages(&self) -> Vec<StorageId> {
+        let mut ids = self.storage_ids();
+        let changes = |id| {
+            self.change_count(id)
+                .expect("storage ids are all known storages")
+        };
+        ids.sort_by_cached_key(|&id| (std::cmp::Reverse(changes(id)), id));
+        ids
+    }
 
-        (scopes, vars)
+    /// Load storages that are loaded lazily ahead of time, in the order they're given, so that
+    /// asking for their changes later doesn't wait on the source. Storages that are already
+    /// loaded are skipped.
+    ///
+    /// Viewers can put the signals that were asked for first, and then follow with
+    /// [`Processed::hot_storages`].
+    pub fn preload<I>(&mut self, ids: I) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = StorageId>,
+    {
+        for id in ids {
+            self.ensure_loaded(id)?;
+        }
+        Ok(())
     }
 
     pub fn load_storage<F>(&mut self, id: StorageId, f: F) -> Result<(), Error>
     where
         F: FnMut(Timesteps, &[u8]),
     {
-        self.blocks[&id].read_blocks(&mut self.reader, f)?;
+        self.ensure_loaded(id)?;
+        let file = &self.files[partition_of(id, self.files.len())];
+        self.blocks[&id].read_blocks(file, f)?;
+        Ok(())
+    }
+
+    /// The changes to a storage of `L` logic, in time order, with the same value data as
+    /// [`Processed::load_storage`].
+    ///
+    /// # Panics
+    /// In debug builds, if the storage doesn't hold `L` logic. Release builds give its data as
+    /// though it did.
+    pub fn iter_changes<L: changes::Logic>(
+        &mut self,
+        id: StorageId,
+    ) -> Result<changes::Changes<'_, L>, Error> {
+        self.ensure_loaded(id)?;
+        Ok(self.loaded_changes(id))
+    }
+
+    /// The changes to a value made of several storages of `L` logic, like an
+    /// [`Integer`](meta::VarKind::Integer) variable that spans more than one, in time order.
+    /// See [`changes::Combined`].
+    ///
+    /// # Panics
+    /// In debug builds, if any of the storages doesn't hold `L` logic.
+    pub fn iter_combined_changes<L: changes::Logic>(
+        &mut self,
+        ids: &[StorageId],
+    ) -> Result<changes::Combined<'_, L>, Error> {
+        for &id in ids {
+            self.ensure_loaded(id)?;
+        }
+        let parts = ids.iter().map(|&id| self.loaded_changes(id)).collect();
+        Ok(changes::Combined::new(parts))
+    }
+
+    fn loaded_changes<L: changes::Logic>(&self, id: StorageId) -> changes::Changes<'_, L> {
+        let storage = &self.storages[&id];
+        debug_assert!(
+            L::holds(storage.ty),
+            "storage {} holds {:?}, not {}",
+            id.0,
+            storage.ty,
+            L::NAME
+        );
+        let file = &self.files[partition_of(id, self.files.len())];
+        changes::Changes::new(&self.blocks[&id], file, storage.width)
+    }
+
+    /// Call `f` with the value in effect at the start of `range`, followed by each change within
+    /// it, in time order. Only the blocks that overlap the range are read.
+    ///
+    /// The first call is given the time the value actually started, which is usually before the
+    /// range does. It's left out if the storage hadn't changed yet by the start of the range.
+    pub fn changes_in_range_with_initial<F>(
+        &mut self,
+        id: StorageId,
+        range: Range<Timesteps>,
+        f: F,
+    ) -> Result<(), Error>
+    where
+        F: FnMut(Timesteps, &[u8]),
+    {
+        self.read_range(id, range, f, None::<fn(&summary::Summary)>)
+    }
+
+    /// Like [`Processed::changes_in_range_with_initial`], except that runs of changes that lie
+    /// entirely within the range are given to `summarized` as a [`summary::Summary`] instead of
+    /// being read, for storages that are summarized. Everything is still in time order.
+    pub fn summarized_changes_in_range<F, G>(
+        &mut self,
+        id: StorageId,
+        range: Range<Timesteps>,
+        f: F,
+        summarized: G,
+    ) -> Result<(), Error>
+    where
+        F: FnMut(Timesteps, &[u8]),
+        G: FnMut(&summary::Summary),
+    {
+        self.read_range(id, range, f, Some(summarized))
+    }
+
+    fn read_range<F, G>(
+        &mut self,
+        id: StorageId,
+        range: Range<Timesteps>,
+        mut f: F,
+        mut summarized: Option<G>,
+    ) -> Result<(), Error>
+    where
+        F: FnMut(Timesteps, &[u8]),
+        G: FnMut(&summary::Summary),
+    {
+        self.ensure_loaded(id)?;
+        let blocks = &self.blocks[&id];
+        let file = &self.files[partition_of(id, self.files.len())];
+
+        let first = blocks.block_at(range.start).unwrap_or(0);
+        let last = blocks
+            .block_offsets
+            .partition_point(|block| block.start < range.end);
+
+        let mut initial: Option<(Timesteps, Vec<u8>)> = None;
+        for index in first..last {
+            let block = &blocks.block_offsets[index];
+            // Blocks that start after the range does don't hold the value at its start.
+            match (&mut summarized, &block.summary) {
+                (Some(summarized), Some(summary))
+                    if block.start > range.start && summary.end < range.end =>
+                {
+                    if let Some((start, value)) = initial.take() {
+                        f(start, &value);
+                    }
+                    summarized(summary);
+                    continue;
+                }
+                _ => {}
+            }
+
+            blocks.read_block(file, index, |timestamp, data| {
+                if timestamp <= range.start {
+                    let (start, value) = initial.get_or_insert_with(|| (timestamp, vec![]));
+                    *start = timestamp;
+                    value.clear();
+                    value.extend_from_slice(data);
+                } else if timestamp < range.end {
+                    if let Some((start, value)) = initial.take() {
+                        f(start, &value);
+                    }
+                    f(timestamp, data);
+                }
+            })?;
+        }
+
+        // Nothing changed within the range.
+        if let Some((start, value)) = initial {
+            f(start, &value);
+        }
+
+        Ok(())
+    }
+
+    /// Pull a storage's changes from the lazy source, if it hasn't been loaded yet, and append
+    /// them to the end of the temporary file.
+    fn ensure_loaded(&mut self, id: StorageId) -> Result<(), Error> {
+        self.check_storage(id)?;
+        if let Some(warnings) = self.refused.get(&id) {
+            return Err(Error::Warnings(warnings.clone()));
+        }
+        let lazy = match &mut self.lazy {
+            Some(lazy) if !self.blocks.contains_key(&id) => lazy,
+            _ => return Ok(()),
+        };
+
+        // With warnings as errors, the changes are held back until the source has said whether
+        // it worked around anything in them, so that a storage that fails leaves nothing behind
+        // in the scratch file.
+        let staged = match self.warnings_as_errors {
+            true => {
+                let mut staged = vec![];
+                lazy.load(id, &mut |timestamp, text| {
+                    staged.push((timestamp, text.to_vec()))
+                })?;
+                let warnings = lazy.take_warnings();
+                if !warnings.is_empty() {
+                    // Sources only warn about something once, so it's remembered for next time.
+                    self.refused.insert(id, warnings.clone());
+                    return Err(Error::Warnings(warnings));
+                }
+                Some(staged)
+            }
+            false => None,
+        };
+
+        let partition = partition_of(id, self.files.len());
+        let file = &mut self.files[partition];
+        let mut writer_offset = file.end()?;
+        let mut writer = BufWriter::new(file);
+
+        let mut block = Block::new(&self.storages[&id], self.dedup, self.block_size);
+        let strings = &mut self.strings;
+        let repeats = &mut self.repeats;
+        let (scratch_dir, strict) = (&self.scratch_dir, self.strict);
+        let mut result = Ok(());
+        let mut ingest = |timestamp, text: &[u8]| {
+            if result.is_ok() {
+                if strict && !block.fits(text) {
+                    result = Err(Error::ValueTooLong(id, text.len(), block.bytes));
+                    return;
+                }
+                let index;
+                let data = match block.interned {
+                    true => {
+                        index = strings.intern(text);
+                        &index[..]
+                    }
+                    false => text,
+                };
+                if block.is_repeat(data) {
+                    if block.interned {
+                        strings.uncount(text);
+                    }
+                    *repeats += 1;
+                    return;
+                }
+                result = block
+                    .push(&mut writer, &mut writer_offset, timestamp, data)
+                    .map_err(|e| scratch::write_error(scratch_dir, e));
+            }
+        };
+        match staged {
+            Some(staged) => {
+                for (timestamp, text) in staged {
+                    ingest(timestamp, &text);
+                }
+            }
+            None => lazy.load(id, &mut ingest)?,
+        }
+        result?;
+        self.warnings.extend(lazy.take_warnings());
+
+        let committed = block
+            .commit(&mut writer, &mut writer_offset)
+            .map_err(|e| scratch::write_error(scratch_dir, e))?;
+        writer
+            .flush()
+            .map_err(|e| scratch::write_error(scratch_dir, e))?;
+        drop(writer);
+
+        self.blocks.insert(id, committed);
         Ok(())
     }
 }
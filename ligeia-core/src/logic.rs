@@ -0,0 +1,466 @@
+//! Packing logic values into the bytes of a storage, and unpacking them again.
+//!
+//! Two-logic values are packed eight to a byte and four-logic values four to a byte, with the
+//! first value in the lowest bits. Unpacked values are one byte per value: 0 and 1, plus 2 for
+//! `x` and 3 for `z`.
+//!
+//! The first value of a storage is its least significant bit, the one at a var's `lsb_index`.
+//! Formats that write vectors most significant bit first, like VCD, are packed with
+//! [`pack_four_ascii_msb_first`].
+//!
+//! Four-logic values can also be read as a number, with an [`XzPolicy`] deciding what `x` and `z`
+//! values do to it.
+//!
+//! With the `simd` feature, x86_64 uses SSE2 for the bulk of each slice, with the scalar code
+//! picking up the tail.
+
+use crate::bignum::BigUint;
+
+/// Pack two-logic values. Only the lowest bit of each value is used.
+pub fn pack_two(values: &[u8], out: &mut Vec<u8>) {
+    out.clear();
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    let values = sse2::pack_two(values, out);
+    scalar::pack_two(values, out);
+}
+
+/// Unpack `count` two-logic values.
+///
+/// # Panics
+/// If `packed` is too short to hold `count` values.
+pub fn unpack_two(packed: &[u8], count: usize, out: &mut Vec<u8>) {
+    out.clear();
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    sse2::unpack_two(packed, count, out);
+    scalar::unpack_two(packed, count, out);
+}
+
+/// Pack four-logic values. Only the lowest two bits of each value are used.
+pub fn pack_four(values: &[u8], out: &mut Vec<u8>) {
+    out.clear();
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    let values = sse2::pack_four(values, out);
+    scalar::pack_four(values, out);
+}
+
+/// Unpack `count` four-logic values.
+///
+/// # Panics
+/// If `packed` is too short to hold `count` values.
+pub fn unpack_four(packed: &[u8], count: usize, out: &mut Vec<u8>) {
+    out.clear();
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    sse2::unpack_four(packed, count, out);
+    scalar::unpack_four(packed, count, out);
+}
+
+/// Pack four-logic values written as characters, like the bits of a VCD vector change.
+///
+/// `x` and `z` may be either case. Returns `false` if there's any other character.
+pub fn pack_four_ascii(chars: &[u8], out: &mut Vec<u8>) -> bool {
+    out.clear();
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    let chars = match sse2::pack_four_ascii(chars, out) {
+        Some(rest) => rest,
+        None => return false,
+    };
+    scalar::pack_four_ascii(chars, out)
+}
+
+/// Pack four-logic values written as characters most significant first, so the last character
+/// is the first value.
+///
+/// `x` and `z` may be either case. Returns `false` if there's any other character.
+pub fn pack_four_ascii_msb_first(chars: &[u8], out: &mut Vec<u8>) -> bool {
+    out.clear();
+    scalar::pack_four_ascii_msb_first(chars, out)
+}
+
+/// What `x` and `z` values do when four-logic values are read as a number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XzPolicy {
+    /// There's no number at all.
+    Reject,
+    /// They're read as 0.
+    Zero,
+    /// The whole number is [`Number::Unknown`], which carries through evaluation the way `x`
+    /// does in Verilog.
+    Sticky,
+}
+
+/// Four-logic values read as a number.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Number<T> {
+    Known(T),
+    /// Some of the values were `x` or `z`, with [`XzPolicy::Sticky`].
+    Unknown,
+}
+
+impl<T> Number<T> {
+    pub fn map<U, F: FnOnce(T) -> U>(self, f: F) -> Number<U> {
+        match self {
+            Number::Known(value) => Number::Known(f(value)),
+            Number::Unknown => Number::Unknown,
+        }
+    }
+}
+
+/// Read `count` packed four-logic values as a number, with the first value as the least
+/// significant bit.
+///
+/// Returns `None` if there's an `x` or `z` with [`XzPolicy::Reject`], or if the number doesn't
+/// fit in a `u64`.
+///
+/// # Panics
+/// If `packed` is too short to hold `count` values.
+pub fn four_to_u64(packed: &[u8], count: usize, policy: XzPolicy) -> Option<Number<u64>> {
+    match four_to_limbs(packed, count, policy)? {
+        Number::Known(limbs) => BigUint::from_limbs(limbs).to_u64().map(Number::Known),
+        Number::Unknown => Some(Number::Unknown),
+    }
+}
+
+/// Like [`four_to_u64`], for numbers of any width.
+pub fn four_to_bigint(packed: &[u8], count: usize, policy: XzPolicy) -> Option<Number<BigUint>> {
+    four_to_limbs(packed, count, policy).map(|number| number.map(BigUint::from_limbs))
+}
+
+fn four_to_limbs(packed: &[u8], count: usize, policy: XzPolicy) -> Option<Number<Vec<u64>>> {
+    let mut limbs = vec![0; (count + 63) / 64];
+    for i in 0..count {
+        match (packed[i / 4] >> ((i % 4) * 2)) & 0b11 {
+            0 => {}
+            1 => limbs[i / 64] |= 1 << (i % 64),
+            _ => match policy {
+                XzPolicy::Reject => return None,
+                XzPolicy::Zero => {}
+                XzPolicy::Sticky => return Some(Number::Unknown),
+            },
+        }
+    }
+    Some(Number::Known(limbs))
+}
+
+/// How strongly a nine-logic value is driven.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strength {
+    Strong,
+    /// Driven by a pull or a weak driver, which any strong driver overrides.
+    Weak,
+    /// Driven, but it isn't known how strongly.
+    Unknown,
+    /// Not driven at all.
+    None,
+}
+
+/// A nine-logic value, numbered as in SVCB. Storages hold one per byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Nine {
+    StrongZero = 0,
+    StrongOne = 1,
+    WeakZero = 2,
+    WeakOne = 3,
+    StrongUnknown = 4,
+    WeakUnknown = 5,
+    UnknownZero = 6,
+    UnknownOne = 7,
+    HighImpedance = 8,
+}
+
+impl Nine {
+    /// The value a byte holds, or `None` if it isn't a nine-logic value.
+    pub fn from_u8(value: u8) -> Option<Self> {
+        Some(match value {
+            0 => Nine::StrongZero,
+            1 => Nine::StrongOne,
+            2 => Nine::WeakZero,
+            3 => Nine::WeakOne,
+            4 => Nine::StrongUnknown,
+            5 => Nine::WeakUnknown,
+            6 => Nine::UnknownZero,
+            7 => Nine::UnknownOne,
+            8 => Nine::HighImpedance,
+            _ => return None,
+        })
+    }
+
+    /// The four-logic value with the same level: 0 and 1, 2 for `x` and 3 for `z`.
+    pub fn level(self) -> u8 {
+        match self {
+            Nine::StrongZero | Nine::WeakZero | Nine::UnknownZero => 0,
+            Nine::StrongOne | Nine::WeakOne | Nine::UnknownOne => 1,
+            Nine::StrongUnknown | Nine::WeakUnknown => 2,
+            Nine::HighImpedance => 3,
+        }
+    }
+
+    pub fn strength(self) -> Strength {
+        match self {
+            Nine::StrongZero | Nine::StrongOne | Nine::StrongUnknown => Strength::Strong,
+            Nine::WeakZero | Nine::WeakOne | Nine::WeakUnknown => Strength::Weak,
+            Nine::UnknownZero | Nine::UnknownOne => Strength::Unknown,
+            Nine::HighImpedance => Strength::None,
+        }
+    }
+
+    /// The character the value is shown as, following `std_logic` where it has one: `L`, `H`
+    /// and `W` for weak values. Values of unknown strength have no `std_logic` equivalent, and
+    /// are shown as a lowercase `l` or `h`.
+    pub fn symbol(self) -> char {
+        match self {
+            Nine::StrongZero => '0',
+            Nine::StrongOne => '1',
+            Nine::WeakZero => 'L',
+            Nine::WeakOne => 'H',
+            Nine::StrongUnknown => 'X',
+            Nine::WeakUnknown => 'W',
+            Nine::UnknownZero => 'l',
+            Nine::UnknownOne => 'h',
+            Nine::HighImpedance => 'Z',
+        }
+    }
+}
+
+/// Show nine-logic values, one byte each, as a character per value, with the last value first
+/// like the most significant bit of a number. Bytes that aren't nine-logic values are shown as
+/// `?`.
+pub fn format_nine(values: &[u8]) -> String {
+    values
+        .iter()
+        .rev()
+        .map(|&value| Nine::from_u8(value).map_or('?', Nine::symbol))
+        .collect()
+}
+
+/// The reference implementations, which also handle whatever's left over from the SIMD paths.
+///
+/// Packing appends to `out`, and unpacking continues from however many values `out` already has.
+mod scalar {
+    pub fn pack_two(values: &[u8], out: &mut Vec<u8>) {
+        for chunk in values.chunks(8) {
+            let byte = chunk
+                .iter()
+                .enumerate()
+                .fold(0, |byte, (i, &value)| byte | (value & 1) << i);
+            out.push(byte);
+        }
+    }
+
+    pub fn unpack_two(packed: &[u8], count: usize, out: &mut Vec<u8>) {
+        for i in out.len()..count {
+            out.push((packed[i / 8] >> (i % 8)) & 1);
+        }
+    }
+
+    pub fn pack_four(values: &[u8], out: &mut Vec<u8>) {
+        for chunk in values.chunks(4) {
+            let byte = chunk
+                .iter()
+                .enumerate()
+                .fold(0, |byte, (i, &value)| byte | (value & 0b11) << (i * 2));
+            out.push(byte);
+        }
+    }
+
+    pub fn unpack_four(packed: &[u8], count: usize, out: &mut Vec<u8>) {
+        for i in out.len()..count {
+            out.push((packed[i / 4] >> ((i % 4) * 2)) & 0b11);
+        }
+    }
+
+    fn four_from_ascii(c: u8) -> Option<u8> {
+        match c {
+            b'0' => Some(0),
+            b'1' => Some(1),
+            b'x' | b'X' => Some(2),
+            b'z' | b'Z' => Some(3),
+            _ => None,
+        }
+    }
+
+    pub fn pack_four_ascii(chars: &[u8], out: &mut Vec<u8>) -> bool {
+        for chunk in chars.chunks(4) {
+            let mut byte = 0;
+            for (i, &c) in chunk.iter().enumerate() {
+                match four_from_ascii(c) {
+                    Some(value) => byte |= value << (i * 2),
+                    None => return false,
+                }
+            }
+            out.push(byte);
+        }
+
+        true
+    }
+
+    pub fn pack_four_ascii_msb_first(chars: &[u8], out: &mut Vec<u8>) -> bool {
+        for chunk in chars.rchunks(4) {
+            let mut byte = 0;
+            for (i, &c) in chunk.iter().rev().enumerate() {
+                match four_from_ascii(c) {
+                    Some(value) => byte |= value << (i * 2),
+                    None => return false,
+                }
+            }
+            out.push(byte);
+        }
+
+        true
+    }
+}
+
+/// SSE2 is part of the x86_64 baseline, so these don't need any runtime detection.
+///
+/// Each works sixteen values at a time, which always lines up with a whole number of bytes.
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+mod sse2 {
+    use std::arch::x86_64::*;
+
+    fn store(v: __m128i, out: &mut Vec<u8>) {
+        let mut lanes = [0u8; 16];
+        // SAFETY: `lanes` is 16 bytes, and unaligned stores are fine.
+        unsafe { _mm_storeu_si128(lanes.as_mut_ptr() as *mut __m128i, v) };
+        out.extend_from_slice(&lanes);
+    }
+
+    /// Returns the values that are left to pack.
+    pub fn pack_two<'a>(values: &'a [u8], out: &mut Vec<u8>) -> &'a [u8] {
+        let chunks = values.chunks_exact(16);
+        let rest = chunks.remainder();
+
+        for chunk in chunks {
+            // SAFETY: `chunk` is 16 bytes, and unaligned loads are fine.
+            let mask = unsafe {
+                let v = _mm_loadu_si128(chunk.as_ptr() as *const __m128i);
+                // Move the lowest bit of each byte up to the top, where movemask looks.
+                _mm_movemask_epi8(_mm_slli_epi16(v, 7))
+            };
+            out.extend_from_slice(&(mask as u16).to_le_bytes());
+        }
+
+        rest
+    }
+
+    pub fn unpack_two(packed: &[u8], count: usize, out: &mut Vec<u8>) {
+        while out.len() + 16 <= count {
+            let i = out.len() / 8;
+            let word = u16::from_le_bytes([packed[i], packed[i + 1]]);
+
+            // SAFETY: only SSE2, which is always available on x86_64.
+            let v = unsafe {
+                // Spread each byte over eight lanes, and pick out a different bit in each.
+                let x = _mm_cvtsi32_si128(word as i32);
+                let x = _mm_unpacklo_epi8(x, x);
+                let x = _mm_unpacklo_epi16(x, x);
+                let x = _mm_unpacklo_epi32(x, x);
+
+                let bits = _mm_set1_epi64x(0x8040_2010_0804_0201u64 as i64);
+                let set = _mm_cmpeq_epi8(_mm_and_si128(x, bits), bits);
+                _mm_and_si128(set, _mm_set1_epi8(1))
+            };
+            store(v, out);
+        }
+    }
+
+    /// Pack sixteen four-logic values, already in lanes, into four bytes.
+    ///
+    /// # Safety
+    /// Only SSE2, which is always available on x86_64.
+    unsafe fn pack_four_lanes(v: __m128i) -> [u8; 4] {
+        let v = _mm_and_si128(v, _mm_set1_epi8(0b11));
+
+        // Within each 32-bit lane, shift the second, third and fourth values down next to the
+        // first, and then squeeze the low byte of every lane together.
+        let v = _mm_or_si128(
+            _mm_or_si128(v, _mm_srli_epi32(v, 6)),
+            _mm_or_si128(_mm_srli_epi32(v, 12), _mm_srli_epi32(v, 18)),
+        );
+        let v = _mm_and_si128(v, _mm_set1_epi32(0xff));
+        let v = _mm_packs_epi32(v, v);
+        let v = _mm_packus_epi16(v, v);
+
+        _mm_cvtsi128_si32(v).to_le_bytes()
+    }
+
+    /// Returns the values that are left to pack.
+    pub fn pack_four<'a>(values: &'a [u8], out: &mut Vec<u8>) -> &'a [u8] {
+        let chunks = values.chunks_exact(16);
+        let rest = chunks.remainder();
+
+        for chunk in chunks {
+            // SAFETY: `chunk` is 16 bytes, and unaligned loads are fine.
+            let bytes =
+                unsafe { pack_four_lanes(_mm_loadu_si128(chunk.as_ptr() as *const __m128i)) };
+            out.extend_from_slice(&bytes);
+        }
+
+        rest
+    }
+
+    pub fn unpack_four(packed: &[u8], count: usize, out: &mut Vec<u8>) {
+        while out.len() + 16 <= count {
+            let i = out.len() / 4;
+            let word = u32::from_le_bytes(packed[i..i + 4].try_into().unwrap());
+
+            // SAFETY: only SSE2, which is always available on x86_64.
+            let v = unsafe {
+                // Spread each byte over four lanes, and pick out a different pair of bits in
+                // each.
+                let x = _mm_cvtsi32_si128(word as i32);
+                let x = _mm_unpacklo_epi8(x, x);
+                let x = _mm_unpacklo_epi16(x, x);
+
+                let low = _mm_set1_epi32(0x4010_0401);
+                let high = _mm_set1_epi32(0x8020_0802u32 as i32);
+                let low = _mm_cmpeq_epi8(_mm_and_si128(x, low), low);
+                let high = _mm_cmpeq_epi8(_mm_and_si128(x, high), high);
+                _mm_or_si128(
+                    _mm_and_si128(low, _mm_set1_epi8(1)),
+                    _mm_and_si128(high, _mm_set1_epi8(2)),
+                )
+            };
+            store(v, out);
+        }
+    }
+
+    /// Returns the characters that are left to pack, or `None` if there was an invalid one.
+    pub fn pack_four_ascii<'a>(chars: &'a [u8], out: &mut Vec<u8>) -> Option<&'a [u8]> {
+        let chunks = chars.chunks_exact(16);
+        let rest = chunks.remainder();
+
+        for chunk in chunks {
+            // SAFETY: `chunk` is 16 bytes, and unaligned loads are fine.
+            let bytes = unsafe {
+                let c = _mm_loadu_si128(chunk.as_ptr() as *const __m128i);
+
+                let is = |ch: u8| _mm_cmpeq_epi8(c, _mm_set1_epi8(ch as i8));
+                let valid = _mm_or_si128(
+                    _mm_or_si128(is(b'0'), is(b'1')),
+                    _mm_or_si128(
+                        _mm_or_si128(is(b'x'), is(b'X')),
+                        _mm_or_si128(is(b'z'), is(b'Z')),
+                    ),
+                );
+                if _mm_movemask_epi8(valid) != 0xffff {
+                    return None;
+                }
+
+                // Digits are their lowest bit. `x` and `z` differ in their second-lowest bit,
+                // whatever the case.
+                let one = _mm_set1_epi8(1);
+                let letter = _mm_cmpgt_epi8(c, _mm_set1_epi8(b'9' as i8));
+                let digit = _mm_and_si128(c, one);
+                let unknown =
+                    _mm_or_si128(_mm_set1_epi8(2), _mm_and_si128(_mm_srli_epi16(c, 1), one));
+                pack_four_lanes(_mm_or_si128(
+                    _mm_and_si128(letter, unknown),
+                    _mm_andnot_si128(letter, digit),
+                ))
+            };
+            out.extend_from_slice(&bytes);
+        }
+
+        Some(rest)
+    }
+}
@@ -1,5 +1,7 @@
 use std::ops::{Add, AddAssign};
 
+use crate::names::NameId;
+
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
 #[repr(transparent)]
 pub struct StorageId(pub u32);
@@ -33,7 +35,7 @@ impl AddAssign for Timesteps {
 
 #[derive(Debug)]
 pub struct Scope {
-    pub name: String,
+    pub name: NameId,
     pub id: ScopeId,
     pub parent: ScopeId,
 }
@@ -43,6 +45,12 @@ pub enum StorageType {
     TwoLogic,
     FourLogic,
     NineLogic,
+    /// Each change is a little-endian `u32` index into the waveform's string table. See
+    /// [`Processed::string`](crate::Processed::string).
+    Utf8,
+    /// Changes have no value, only a time: each one is an occurrence of the event. Storages of
+    /// them are zero bits wide.
+    Event,
 }
 
 #[derive(Debug)]
@@ -53,9 +61,10 @@ pub struct Storage {
     pub start: u32,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct EnumValue {
     pub name: String,
+    /// The state's bits, least significant first, like the values in storages.
     pub value: Vec<bool>,
 }
 
@@ -68,6 +77,8 @@ pub enum Signedness {
 #[derive(Debug)]
 pub enum VarKind {
     None,
+    /// The bit at `lsb_index` is the first value of the first storage, whichever way round the
+    /// range is declared. Later storages hold the more significant bits.
     Integer {
         storages: Vec<StorageId>,
         msb_index: u32,
@@ -81,11 +92,71 @@ pub enum VarKind {
     Utf8 {
         storage: StorageId,
     },
+    Event {
+        storage: StorageId,
+    },
+}
+
+impl VarKind {
+    /// How far the bit with the declared `index` is from the least significant bit of an
+    /// integer, or `None` if this isn't one or it has no such bit.
+    ///
+    /// For a var declared `[7:0]` bit 7 is 7 along, and for one declared `[0:7]` it's 0.
+    pub fn bit_offset(&self, index: u32) -> Option<u32> {
+        match *self {
+            VarKind::Integer {
+                msb_index,
+                lsb_index,
+                ..
+            } => {
+                if lsb_index <= msb_index {
+                    (lsb_index..=msb_index)
+                        .contains(&index)
+                        .then(|| index - lsb_index)
+                } else {
+                    (msb_index..=lsb_index)
+                        .contains(&index)
+                        .then(|| lsb_index - index)
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// The declared index of the bit `offset` along from the least significant bit of an
+    /// integer, or `None` if this isn't one or it has no such bit. The inverse of
+    /// [`VarKind::bit_offset`].
+    pub fn bit_index(&self, offset: u32) -> Option<u32> {
+        match *self {
+            VarKind::Integer {
+                msb_index,
+                lsb_index,
+                ..
+            } => {
+                if offset > msb_index.abs_diff(lsb_index) {
+                    None
+                } else if lsb_index <= msb_index {
+                    Some(lsb_index + offset)
+                } else {
+                    Some(lsb_index - offset)
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Where a variable is declared in the design's source code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    pub file: String,
+    pub line: u32,
 }
 
 #[derive(Debug)]
 pub struct Var {
-    pub name: String,
+    pub name: NameId,
     pub scope_id: ScopeId,
     pub kind: VarKind,
+    pub source: Option<SourceLocation>,
 }
@@ -0,0 +1,253 @@
+//! Interned scope and variable names.
+//!
+//! Big designs have millions of names, and most of them are repeats (`clk`, `rst`, `u0`, ...).
+//! Storing them end to end in one buffer, each only once, keeps that from turning into millions
+//! of small allocations.
+//!
+//! The same table also holds the text of string variables' changes, which tend to repeat a small
+//! set of messages in just the same way.
+
+use std::{
+    cmp::Ordering,
+    hash::Hasher,
+    io::{self, Read, Write},
+};
+
+use fnv::FnvHasher;
+
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(transparent)]
+pub struct NameId(pub u32);
+
+/// Marks an empty slot in the hash table.
+const EMPTY: u32 = u32::MAX;
+
+#[derive(Debug, Default)]
+pub struct NameTable {
+    text: String,
+    /// Where each name ends in `text`. Each one starts where the one before it ends.
+    ends: Vec<u32>,
+    /// An open-addressed hash table of ids, so names can be looked up without keeping a second
+    /// copy of them around as keys.
+    slots: Vec<u32>,
+}
+
+fn hash(name: &str) -> usize {
+    let mut hasher = FnvHasher::default();
+    hasher.write(name.as_bytes());
+    hasher.finish() as usize
+}
+
+impl NameTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ends.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ends.is_empty()
+    }
+
+    /// The total length of every name in the table, in bytes.
+    pub fn text_len(&self) -> usize {
+        self.text.len()
+    }
+
+    /// How much memory the table takes up, in bytes.
+    pub fn heap_bytes(&self) -> usize {
+        self.text.capacity()
+            + (self.ends.capacity() + self.slots.capacity()) * std::mem::size_of::<u32>()
+    }
+
+    /// Give back any room left over from growing the table. More names can still be added
+    /// afterwards.
+    pub fn shrink_to_fit(&mut self) {
+        self.text.shrink_to_fit();
+        self.ends.shrink_to_fit();
+    }
+
+    /// # Panics
+    /// If `id` didn't come from this table.
+    pub fn get(&self, id: NameId) -> &str {
+        let index = id.0 as usize;
+        let start = match index {
+            0 => 0,
+            _ => self.ends[index - 1] as usize,
+        };
+        &self.text[start..self.ends[index] as usize]
+    }
+
+    /// Find the slot that holds `name`, or the empty one where it would go.
+    fn slot_of(&self, name: &str) -> usize {
+        let mask = self.slots.len() - 1;
+        let mut slot = hash(name) & mask;
+        loop {
+            match self.slots[slot] {
+                EMPTY => return slot,
+                id if self.get(NameId(id)) == name => return slot,
+                _ => slot = (slot + 1) & mask,
+            }
+        }
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<NameId> {
+        if self.slots.is_empty() {
+            return None;
+        }
+
+        match self.slots[self.slot_of(name)] {
+            EMPTY => None,
+            id => Some(NameId(id)),
+        }
+    }
+
+    /// Add a name, or find the one that's already here.
+    ///
+    /// # Panics
+    /// If the table would grow past 4 GiB of text.
+    pub fn intern(&mut self, name: &str) -> NameId {
+        // Keep the table at most half full, so probe sequences stay short.
+        if self.slots.len() < (self.ends.len() + 1) * 2 {
+            self.rehash((self.slots.len() * 2).max(16));
+        }
+
+        let slot = self.slot_of(name);
+        if self.slots[slot] != EMPTY {
+            return NameId(self.slots[slot]);
+        }
+
+        let id = self.ends.len() as u32;
+        self.text.push_str(name);
+        let end = u32::try_from(self.text.len()).expect("too many names");
+        self.ends.push(end);
+        self.slots[slot] = id;
+
+        NameId(id)
+    }
+
+    fn rehash(&mut self, capacity: usize) {
+        self.slots = vec![EMPTY; capacity];
+        for id in 0..self.ends.len() as u32 {
+            let slot = self.slot_of(self.get(NameId(id)));
+            self.slots[slot] = id;
+        }
+    }
+
+    /// Write the table out, in a form that [`NameTable::read_from`] can load without
+    /// re-interning every name.
+    ///
+    /// This is the number of names, then where each one ends, then all of their text, with
+    /// integers as little-endian `u32`s.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(&(self.ends.len() as u32).to_le_bytes())?;
+        for end in &self.ends {
+            writer.write_all(&end.to_le_bytes())?;
+        }
+        writer.write_all(self.text.as_bytes())
+    }
+
+    pub fn read_from<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut word = [0; 4];
+        reader.read_exact(&mut word)?;
+        let len = u32::from_le_bytes(word) as usize;
+
+        let mut ends = Vec::with_capacity(len);
+        for _ in 0..len {
+            reader.read_exact(&mut word)?;
+            let end = u32::from_le_bytes(word);
+            if matches!(ends.last(), Some(&last) if end < last) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "name ends out of order",
+                ));
+            }
+            ends.push(end);
+        }
+
+        let mut text = vec![0; ends.last().copied().unwrap_or(0) as usize];
+        reader.read_exact(&mut text)?;
+        let text =
+            String::from_utf8(text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        let mut table = Self {
+            text,
+            ends,
+            slots: vec![],
+        };
+        if table
+            .ends
+            .iter()
+            .any(|&end| !table.text.is_char_boundary(end as usize))
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "name split inside a character",
+            ));
+        }
+
+        table.rehash((table.len() * 2).next_power_of_two().max(16));
+        Ok(table)
+    }
+}
+
+/// Compare names the way people read them, with runs of digits compared by their value, so
+/// `data[2]` comes before `data[10]`.
+///
+/// Runs with the same value put the one with fewer leading zeros first, so names only compare
+/// equal if they're the same.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i].is_ascii_digit() && b[j].is_ascii_digit() {
+            let a_run = digit_run(&a[i..]);
+            let b_run = digit_run(&b[j..]);
+            let a_value = trim_zeros(&a[i..i + a_run]);
+            let b_value = trim_zeros(&b[j..j + b_run]);
+            let ordering = a_value
+                .len()
+                .cmp(&b_value.len())
+                .then_with(|| a_value.cmp(b_value))
+                .then_with(|| a_run.cmp(&b_run));
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+            i += a_run;
+            j += b_run;
+        } else {
+            // Comparing UTF-8 bytewise orders characters by their code points.
+            match a[i].cmp(&b[j]) {
+                Ordering::Equal => {
+                    i += 1;
+                    j += 1;
+                }
+                ordering => return ordering,
+            }
+        }
+    }
+    (a.len() - i).cmp(&(b.len() - j))
+}
+
+/// Compare paths of names one name at a time, with [`natural_cmp`], so that everything within a
+/// scope sorts together.
+pub fn natural_cmp_paths<S: AsRef<str>>(a: &[S], b: &[S]) -> Ordering {
+    for (a, b) in a.iter().zip(b) {
+        match natural_cmp(a.as_ref(), b.as_ref()) {
+            Ordering::Equal => {}
+            ordering => return ordering,
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+fn digit_run(bytes: &[u8]) -> usize {
+    bytes.iter().take_while(|b| b.is_ascii_digit()).count()
+}
+
+fn trim_zeros(digits: &[u8]) -> &[u8] {
+    let zeros = digits.iter().take_while(|&&b| b == b'0').count();
+    &digits[zeros..]
+}
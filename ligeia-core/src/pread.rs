@@ -0,0 +1,54 @@
+//! Positional reads, so storages can be read from a shared file without seeking it.
+
+use std::{cell::RefCell, fs::File, io};
+
+thread_local! {
+    /// Block-sized buffers that are reused between reads on the same thread.
+    static BUFFERS: RefCell<Vec<Vec<u8>>> = const { RefCell::new(vec![]) };
+}
+
+/// Run `f` with a `len`-byte buffer from the current thread's pool.
+///
+/// The buffer holds whatever was last read into it. Calls may nest, in which case each gets its
+/// own buffer.
+pub fn with_buffer<T, F>(len: usize, f: F) -> T
+where
+    F: FnOnce(&mut [u8]) -> T,
+{
+    let mut buffer = BUFFERS
+        .with(|buffers| buffers.borrow_mut().pop())
+        .unwrap_or_default();
+    buffer.resize(len, 0);
+
+    let result = f(&mut buffer);
+    BUFFERS.with(|buffers| buffers.borrow_mut().push(buffer));
+    result
+}
+
+/// Fill `buffer` from `file`, starting at `offset`.
+#[cfg(unix)]
+pub fn read_exact_at(file: &File, buffer: &mut [u8], offset: u64) -> io::Result<()> {
+    std::os::unix::fs::FileExt::read_exact_at(file, buffer, offset)
+}
+
+/// Fill `buffer` from `file`, starting at `offset`.
+///
+/// `seek_read` does move the file cursor on Windows, but nothing else relies on it.
+#[cfg(windows)]
+pub fn read_exact_at(file: &File, mut buffer: &mut [u8], mut offset: u64) -> io::Result<()> {
+    use std::os::windows::fs::FileExt;
+
+    while !buffer.is_empty() {
+        match file.seek_read(buffer, offset) {
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => {
+                buffer = &mut buffer[n..];
+                offset += n as u64;
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,172 @@
+//! Sampling changes on a fixed grid of times, for consumers that want a value per step rather
+//! than per change, like plots and resampled exports, or on the edges of a clock, for a value per
+//! cycle the way registers see them.
+
+use std::{
+    iter::{self, Peekable},
+    mem,
+    ops::Range,
+    str::FromStr,
+};
+
+use crate::meta::Timesteps;
+
+/// Resample changes, in time order, holding each value until the next change.
+///
+/// Yields a sample at `range.start` and every `step` timesteps after it, while the time is within
+/// `range`. Each sample is the value of the last change at or before its time, or `None` if
+/// there hasn't been one yet. Changes before `range.start` only set the first sample's value.
+///
+/// # Panics
+/// If `step` is zero.
+pub fn resample<I, V>(changes: I, range: Range<Timesteps>, step: u64) -> Resample<I::IntoIter, V>
+where
+    I: IntoIterator<Item = (Timesteps, V)>,
+    V: Clone,
+{
+    assert!(step > 0, "resampling needs a step of at least one timestep");
+    Resample {
+        changes: changes.into_iter().peekable(),
+        value: None,
+        next: Some(range.start),
+        end: range.end,
+        step,
+    }
+}
+
+/// The iterator returned by [`resample`].
+#[derive(Debug, Clone)]
+pub struct Resample<I: Iterator<Item = (Timesteps, V)>, V> {
+    changes: Peekable<I>,
+    value: Option<V>,
+    /// The time of the next sample, or `None` once the samples would overflow.
+    next: Option<Timesteps>,
+    end: Timesteps,
+    step: u64,
+}
+
+impl<I, V> Iterator for Resample<I, V>
+where
+    I: Iterator<Item = (Timesteps, V)>,
+    V: Clone,
+{
+    type Item = (Timesteps, Option<V>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let time = self.next.filter(|&time| time < self.end)?;
+        while let Some((_, value)) = self.changes.next_if(|&(change, _)| change <= time) {
+            self.value = Some(value);
+        }
+        self.next = time.0.checked_add(self.step).map(Timesteps);
+        Some((time, self.value.clone()))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = match self.next {
+            Some(next) if next < self.end => {
+                ((self.end.0 - next.0 - 1) / self.step + 1).try_into().ok()
+            }
+            _ => Some(0),
+        };
+        (remaining.unwrap_or(usize::MAX), remaining)
+    }
+}
+
+/// Which edges of a clock to sample on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    Rising,
+    Falling,
+}
+
+impl FromStr for Edge {
+    type Err = String;
+
+    /// Parses `rising` or `falling`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rising" => Ok(Edge::Rising),
+            "falling" => Ok(Edge::Falling),
+            _ => Err(format!("unknown clock edge `{}`", s)),
+        }
+    }
+}
+
+/// Sample changes, in time order, on the edges of a clock.
+///
+/// `clock` is the clock's changes in time order, as levels, with `None` for `x` and `z`; see
+/// [`clocks::levels`](crate::clocks::levels). Only a change from a known low to a known high is a
+/// rising edge, and the other way around for a falling one.
+///
+/// Yields the time of each edge along with the value of the last change before it, or `None` if
+/// there hasn't been one. Like a register, a change at the same time as an edge isn't seen until
+/// the next one, since it's usually that edge's doing.
+pub fn sample_on_edges<C, I, V>(
+    clock: C,
+    edge: Edge,
+    changes: I,
+) -> SampleOnEdges<C::IntoIter, I::IntoIter, V>
+where
+    C: IntoIterator<Item = (Timesteps, Option<bool>)>,
+    I: IntoIterator<Item = (Timesteps, V)>,
+    V: Clone,
+{
+    SampleOnEdges {
+        clock: clock.into_iter(),
+        edge,
+        level: None,
+        changes: changes.into_iter().peekable(),
+        value: None,
+    }
+}
+
+/// The times of a clock's edges, from its levels like with [`sample_on_edges`].
+pub fn edges<C>(clock: C, edge: Edge) -> impl Iterator<Item = Timesteps>
+where
+    C: IntoIterator<Item = (Timesteps, Option<bool>)>,
+{
+    sample_on_edges(clock, edge, iter::empty::<(Timesteps, ())>()).map(|(time, _)| time)
+}
+
+/// The iterator returned by [`sample_on_edges`].
+#[derive(Debug, Clone)]
+pub struct SampleOnEdges<C, I: Iterator<Item = (Timesteps, V)>, V> {
+    clock: C,
+    edge: Edge,
+    /// The clock's level before its next change.
+    level: Option<bool>,
+    changes: Peekable<I>,
+    value: Option<V>,
+}
+
+impl<C, I, V> Iterator for SampleOnEdges<C, I, V>
+where
+    C: Iterator<Item = (Timesteps, Option<bool>)>,
+    I: Iterator<Item = (Timesteps, V)>,
+    V: Clone,
+{
+    type Item = (Timesteps, Option<V>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (time, level) = self.clock.next()?;
+            let edge = match (mem::replace(&mut self.level, level), level) {
+                (Some(false), Some(true)) => Edge::Rising,
+                (Some(true), Some(false)) => Edge::Falling,
+                _ => continue,
+            };
+            if edge != self.edge {
+                continue;
+            }
+
+            while let Some((_, value)) = self.changes.next_if(|&(change, _)| change < time) {
+                self.value = Some(value);
+            }
+            return Some((time, self.value.clone()));
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.clock.size_hint().1)
+    }
+}
@@ -0,0 +1,188 @@
+//! Writing switching activity as a SAIF file, for power estimation flows.
+//!
+//! Each bit of each variable is written as its own net, with how long it spent at each level and
+//! how many times it toggled over a window of time. See [`stats`](crate::stats).
+
+use std::io::Write;
+
+use crate::{
+    meta::{StorageId, StorageType, Timesteps, VarKind},
+    names,
+    stats::{self, BitActivity},
+    timescale::Timescale,
+    Error, Processed,
+};
+
+/// A variable whose activity is being written.
+struct Net {
+    scopes: Vec<String>,
+    name: String,
+    storage: StorageId,
+    /// The indices of its least and most significant bits, if it has them.
+    indices: Option<(u32, u32)>,
+}
+
+/// Escape the characters SAIF identifiers can't hold.
+fn identifier(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+    for c in name.chars() {
+        if !(c.is_ascii_alphanumeric() || c == '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn write_net<W: Write>(
+    writer: &mut W,
+    depth: usize,
+    name: &str,
+    bit: &BitActivity,
+    scale: u64,
+) -> Result<(), Error> {
+    let indent = "  ".repeat(depth);
+    writeln!(writer, "{}({}", indent, name)?;
+    writeln!(
+        writer,
+        "{}  (T0 {}) (T1 {}) (TX {}) (TZ {})",
+        indent,
+        bit.t0 * scale,
+        bit.t1 * scale,
+        bit.tx * scale,
+        bit.tz * scale
+    )?;
+    writeln!(writer, "{}  (TC {}) (IG 0)", indent, bit.toggles)?;
+    writeln!(writer, "{})", indent)?;
+    Ok(())
+}
+
+/// Write the activity of the variables whose hierarchical names pass `include`, from `start` up
+/// to `end`, as a SAIF file.
+///
+/// Variables made up of more than one storage, and strings, are left out.
+pub fn export_saif<W, F>(
+    processed: &mut Processed,
+    mut writer: W,
+    (start, end): (Timesteps, Timesteps),
+    mut include: F,
+) -> Result<(), Error>
+where
+    W: Write,
+    F: FnMut(&str) -> bool,
+{
+    let mut nets = vec![];
+    for var in processed.vars() {
+        let (storage, indices) = match &var.kind {
+            VarKind::Integer {
+                storages,
+                msb_index,
+                lsb_index,
+                ..
+            } if storages.len() == 1 => (storages[0], Some((*lsb_index, *msb_index))),
+            VarKind::Enum { storage, .. } => (*storage, None),
+            _ => continue,
+        };
+        if matches!(processed.storage(storage)?.ty, StorageType::Utf8)
+            || !include(&processed.var_path(var))
+        {
+            continue;
+        }
+
+        nets.push(Net {
+            scopes: processed
+                .scope_path(var.scope_id)
+                .into_iter()
+                .map(str::to_string)
+                .collect(),
+            name: processed.name(var.name).to_string(),
+            storage,
+            indices,
+        });
+    }
+    // Sorting by scope first writes every net of an instance before the instances within it.
+    nets.sort_by(|a, b| {
+        names::natural_cmp_paths(&a.scopes, &b.scopes)
+            .then_with(|| names::natural_cmp(&a.name, &b.name))
+    });
+
+    let (timescale, scale) =
+        match Timescale::from_femtoseconds(processed.femtoseconds_per_timestep()) {
+            Some(timescale) => (timescale.to_string(), 1),
+            // Anything else has to be written out in femtoseconds.
+            None => (
+                "1 fs".to_string(),
+                processed.femtoseconds_per_timestep() as u64,
+            ),
+        };
+    writeln!(writer, "(SAIFILE")?;
+    writeln!(writer, "(SAIFVERSION \"2.0\")")?;
+    writeln!(writer, "(DIRECTION \"backward\")")?;
+    writeln!(writer, "(PROGRAM_NAME \"ligeia\")")?;
+    writeln!(writer, "(DIVIDER / )")?;
+    writeln!(writer, "(TIMESCALE {})", timescale)?;
+    writeln!(
+        writer,
+        "(DURATION {})",
+        end.0.saturating_sub(start.0) * scale
+    )?;
+
+    let mut open: Vec<&str> = vec![];
+    let mut index = 0;
+    while index < nets.len() {
+        let scopes = &nets[index].scopes;
+        let group = nets[index..]
+            .iter()
+            .take_while(|net| &net.scopes == scopes)
+            .count();
+
+        let shared = open
+            .iter()
+            .zip(scopes)
+            .take_while(|(open, scope)| *open == scope)
+            .count();
+        for depth in (shared..open.len()).rev() {
+            writeln!(writer, "{})", "  ".repeat(depth))?;
+        }
+        open.truncate(shared);
+        for scope in &scopes[shared..] {
+            writeln!(
+                writer,
+                "{}(INSTANCE {}",
+                "  ".repeat(open.len()),
+                identifier(scope)
+            )?;
+            open.push(scope);
+        }
+
+        let depth = open.len() + 1;
+        writeln!(writer, "{}(NET", "  ".repeat(open.len()))?;
+        for net in &nets[index..index + group] {
+            let activity = stats::bit_activity(processed, net.storage, start..end)?;
+            let name = identifier(&net.name);
+            match activity.len() {
+                0 => {}
+                1 => write_net(&mut writer, depth, &name, &activity[0], scale)?,
+                _ => {
+                    let (lsb, msb) = net.indices.unwrap_or((0, activity.len() as u32 - 1));
+                    for (bit, activity) in activity.iter().enumerate() {
+                        let bit = bit as u32;
+                        let index = if msb >= lsb { lsb + bit } else { lsb - bit };
+                        let name = format!("{}\\[{}\\]", name, index);
+                        write_net(&mut writer, depth, &name, activity, scale)?;
+                    }
+                }
+            }
+        }
+        writeln!(writer, "{})", "  ".repeat(open.len()))?;
+
+        index += group;
+    }
+    for depth in (0..open.len()).rev() {
+        writeln!(writer, "{})", "  ".repeat(depth))?;
+    }
+    writeln!(writer, ")")?;
+
+    writer.flush()?;
+    Ok(())
+}
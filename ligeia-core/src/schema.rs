@@ -0,0 +1,623 @@
+//! One encoding of a waveform's metadata, for the cache, remote viewers and session files to
+//! share.
+//!
+//! This covers everything but the changes themselves: the names, scopes, variables and storages,
+//! and where each storage's blocks of changes are. Everything is written after a magic number
+//! and the version of the encoding, with integers as little-endian and lists as a `u32` count
+//! followed by their items.
+//!
+//! Each side of a connection says which versions it can read and write, and [`negotiate`] picks
+//! the newest one they share. Readers take anything from [`OLDEST_VERSION`] to [`VERSION`].
+
+use std::{
+    io::{self, Read, Write},
+    ops::RangeInclusive,
+};
+
+use crate::{
+    meta::{
+        EnumValue, Scope, ScopeId, Signedness, SourceLocation, Storage, StorageId, StorageType,
+        Timesteps, Var, VarKind,
+    },
+    names::{NameId, NameTable},
+    summary::Summary,
+};
+
+const MAGIC: &[u8; 4] = b"LGMD";
+/// The newest version of the encoding, which is what's written unless a peer asks otherwise.
+pub const VERSION: u32 = 1;
+/// The oldest version that can still be read and written.
+pub const OLDEST_VERSION: u32 = 1;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SchemaError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("not waveform metadata")]
+    Magic,
+    #[error(
+        "version {0} of the metadata encoding isn't supported, only {OLDEST_VERSION} to {VERSION}"
+    )]
+    Version(u32),
+    #[error("invalid metadata: {0}")]
+    Invalid(&'static str),
+}
+
+/// The newest version that both this and a peer supporting `theirs` can use, if there is one.
+pub fn negotiate(theirs: RangeInclusive<u32>) -> Option<u32> {
+    let newest = VERSION.min(*theirs.end());
+    (newest >= OLDEST_VERSION.max(*theirs.start())).then_some(newest)
+}
+
+/// Where one block of a storage's changes is in the scratch files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockEntry {
+    pub offset: u64,
+    pub len: u64,
+    /// The timestamp of the first change in the block.
+    pub start: Timesteps,
+    /// How many changes to the storage came before this block.
+    pub first_change: u64,
+    pub summary: Option<Summary>,
+}
+
+/// Where all of a storage's changes are.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockIndex {
+    pub storage: StorageId,
+    /// How many bytes each change's value takes up.
+    pub bytes: u32,
+    pub changes: u64,
+    pub blocks: Vec<BlockEntry>,
+}
+
+/// Everything about a waveform but its changes, as read by [`Metadata::read_from`].
+///
+/// Scopes, storages and block indexes are in order of their ids, and variables in the order
+/// they were declared.
+#[derive(Debug)]
+pub struct Metadata {
+    pub version: u32,
+    pub femtoseconds_per_timestep: u128,
+    pub time_bounds: (Timesteps, Timesteps),
+    pub names: NameTable,
+    /// The text of changes to [`Utf8`](StorageType::Utf8) storages.
+    pub strings: NameTable,
+    pub scopes: Vec<Scope>,
+    pub vars: Vec<Var>,
+    pub storages: Vec<Storage>,
+    pub blocks: Vec<BlockIndex>,
+}
+
+impl Metadata {
+    pub fn read_from<R: Read>(reader: R) -> Result<Self, SchemaError> {
+        let mut decoder = Decoder::new(reader)?;
+        let version = decoder.version;
+        let femtoseconds_per_timestep = decoder.u128()?;
+        let time_bounds = (decoder.decode()?, decoder.decode()?);
+        let names = NameTable::read_from(&mut decoder.reader)?;
+        let strings = NameTable::read_from(&mut decoder.reader)?;
+        let scopes: Vec<Scope> = decoder.decode()?;
+        let vars: Vec<Var> = decoder.decode()?;
+        let storages = decoder.decode()?;
+        let blocks = decoder.decode()?;
+
+        let name_count = names.len();
+        let names_in_range = scopes
+            .iter()
+            .map(|scope| scope.name)
+            .chain(vars.iter().map(|var| var.name))
+            .all(|name| (name.0 as usize) < name_count);
+        if !names_in_range {
+            return Err(SchemaError::Invalid("name out of range"));
+        }
+
+        Ok(Self {
+            version,
+            femtoseconds_per_timestep,
+            time_bounds,
+            names,
+            strings,
+            scopes,
+            vars,
+            storages,
+            blocks,
+        })
+    }
+}
+
+/// Writes metadata in one version of the encoding. See
+/// [`Processed::write_metadata`](crate::Processed::write_metadata).
+pub(crate) struct Encoder<W> {
+    writer: W,
+}
+
+impl<W: Write> Encoder<W> {
+    pub fn new(mut writer: W, version: u32) -> Result<Self, SchemaError> {
+        if !(OLDEST_VERSION..=VERSION).contains(&version) {
+            return Err(SchemaError::Version(version));
+        }
+        writer.write_all(MAGIC)?;
+        writer.write_all(&version.to_le_bytes())?;
+        Ok(Self { writer })
+    }
+
+    pub fn writer(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.writer.write_all(bytes)
+    }
+
+    pub fn u8(&mut self, value: u8) -> io::Result<()> {
+        self.bytes(&[value])
+    }
+
+    pub fn u32(&mut self, value: u32) -> io::Result<()> {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    pub fn u64(&mut self, value: u64) -> io::Result<()> {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    pub fn u128(&mut self, value: u128) -> io::Result<()> {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    pub fn encode<T: Encode + ?Sized>(&mut self, value: &T) -> io::Result<()> {
+        value.encode(self)
+    }
+
+    /// A list of `len` items, written one at a time with `encode`.
+    pub fn list<I, T>(&mut self, items: I) -> io::Result<()>
+    where
+        I: ExactSizeIterator<Item = T>,
+        T: Encode,
+    {
+        self.u32(items.len() as u32)?;
+        for item in items {
+            item.encode(self)?;
+        }
+        Ok(())
+    }
+}
+
+struct Decoder<R> {
+    reader: R,
+    version: u32,
+}
+
+impl<R: Read> Decoder<R> {
+    fn new(mut reader: R) -> Result<Self, SchemaError> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(SchemaError::Magic);
+        }
+        let mut decoder = Self { reader, version: 0 };
+        let version = decoder.u32()?;
+        if !(OLDEST_VERSION..=VERSION).contains(&version) {
+            return Err(SchemaError::Version(version));
+        }
+        decoder.version = version;
+        Ok(decoder)
+    }
+
+    fn array<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut bytes = [0; N];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        self.array().map(u64::from_le_bytes)
+    }
+
+    fn u128(&mut self) -> io::Result<u128> {
+        self.array().map(u128::from_le_bytes)
+    }
+
+    fn decode<T: Decode>(&mut self) -> Result<T, SchemaError> {
+        T::decode(self)
+    }
+
+    fn string(&mut self) -> Result<String, SchemaError> {
+        let len = self.u32()?;
+        let mut bytes = vec![];
+        (&mut self.reader)
+            .take(len.into())
+            .read_to_end(&mut bytes)?;
+        if bytes.len() != len as usize {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        String::from_utf8(bytes).map_err(|_| SchemaError::Invalid("string isn't UTF-8"))
+    }
+}
+
+pub(crate) trait Encode {
+    fn encode<W: Write>(&self, encoder: &mut Encoder<W>) -> io::Result<()>;
+}
+
+trait Decode: Sized {
+    fn decode<R: Read>(decoder: &mut Decoder<R>) -> Result<Self, SchemaError>;
+}
+
+impl<T: Encode + ?Sized> Encode for &T {
+    fn encode<W: Write>(&self, encoder: &mut Encoder<W>) -> io::Result<()> {
+        (**self).encode(encoder)
+    }
+}
+
+impl<T: Encode> Encode for [T] {
+    fn encode<W: Write>(&self, encoder: &mut Encoder<W>) -> io::Result<()> {
+        encoder.list(self.iter())
+    }
+}
+
+impl<T: Decode> Decode for Vec<T> {
+    fn decode<R: Read>(decoder: &mut Decoder<R>) -> Result<Self, SchemaError> {
+        let len = decoder.u32()?;
+        // The count can't be trusted to size an allocation up front.
+        let mut items = Vec::with_capacity(len.min(1024) as usize);
+        for _ in 0..len {
+            items.push(decoder.decode()?);
+        }
+        Ok(items)
+    }
+}
+
+impl Encode for str {
+    fn encode<W: Write>(&self, encoder: &mut Encoder<W>) -> io::Result<()> {
+        encoder.u32(self.len() as u32)?;
+        encoder.bytes(self.as_bytes())
+    }
+}
+
+impl Encode for u32 {
+    fn encode<W: Write>(&self, encoder: &mut Encoder<W>) -> io::Result<()> {
+        encoder.u32(*self)
+    }
+}
+
+impl Decode for u32 {
+    fn decode<R: Read>(decoder: &mut Decoder<R>) -> Result<Self, SchemaError> {
+        Ok(decoder.u32()?)
+    }
+}
+
+macro_rules! newtype {
+    ($ty:ident, $int:ident) => {
+        impl Encode for $ty {
+            fn encode<W: Write>(&self, encoder: &mut Encoder<W>) -> io::Result<()> {
+                encoder.$int(self.0)
+            }
+        }
+
+        impl Decode for $ty {
+            fn decode<R: Read>(decoder: &mut Decoder<R>) -> Result<Self, SchemaError> {
+                Ok($ty(decoder.$int()?))
+            }
+        }
+    };
+}
+
+newtype!(NameId, u32);
+newtype!(ScopeId, u32);
+newtype!(StorageId, u32);
+newtype!(Timesteps, u64);
+
+impl Encode for Scope {
+    fn encode<W: Write>(&self, encoder: &mut Encoder<W>) -> io::Result<()> {
+        encoder.encode(&self.id)?;
+        encoder.encode(&self.parent)?;
+        encoder.encode(&self.name)
+    }
+}
+
+impl Decode for Scope {
+    fn decode<R: Read>(decoder: &mut Decoder<R>) -> Result<Self, SchemaError> {
+        Ok(Self {
+            id: decoder.decode()?,
+            parent: decoder.decode()?,
+            name: decoder.decode()?,
+        })
+    }
+}
+
+impl Encode for StorageType {
+    fn encode<W: Write>(&self, encoder: &mut Encoder<W>) -> io::Result<()> {
+        encoder.u8(match self {
+            StorageType::TwoLogic => 0,
+            StorageType::FourLogic => 1,
+            StorageType::NineLogic => 2,
+            StorageType::Utf8 => 3,
+            StorageType::Event => 4,
+        })
+    }
+}
+
+impl Decode for StorageType {
+    fn decode<R: Read>(decoder: &mut Decoder<R>) -> Result<Self, SchemaError> {
+        Ok(match decoder.u8()? {
+            0 => StorageType::TwoLogic,
+            1 => StorageType::FourLogic,
+            2 => StorageType::NineLogic,
+            3 => StorageType::Utf8,
+            4 => StorageType::Event,
+            _ => return Err(SchemaError::Invalid("unknown storage type")),
+        })
+    }
+}
+
+impl Encode for Storage {
+    fn encode<W: Write>(&self, encoder: &mut Encoder<W>) -> io::Result<()> {
+        encoder.encode(&self.id)?;
+        encoder.encode(&self.ty)?;
+        encoder.u32(self.width)?;
+        encoder.u32(self.start)
+    }
+}
+
+impl Decode for Storage {
+    fn decode<R: Read>(decoder: &mut Decoder<R>) -> Result<Self, SchemaError> {
+        Ok(Self {
+            id: decoder.decode()?,
+            ty: decoder.decode()?,
+            width: decoder.u32()?,
+            start: decoder.u32()?,
+        })
+    }
+}
+
+impl Encode for Signedness {
+    fn encode<W: Write>(&self, encoder: &mut Encoder<W>) -> io::Result<()> {
+        encoder.u8(match self {
+            Signedness::Unsigned => 0,
+            Signedness::SignedTwosComplement => 1,
+        })
+    }
+}
+
+impl Decode for Signedness {
+    fn decode<R: Read>(decoder: &mut Decoder<R>) -> Result<Self, SchemaError> {
+        Ok(match decoder.u8()? {
+            0 => Signedness::Unsigned,
+            1 => Signedness::SignedTwosComplement,
+            _ => return Err(SchemaError::Invalid("unknown signedness")),
+        })
+    }
+}
+
+impl Encode for EnumValue {
+    fn encode<W: Write>(&self, encoder: &mut Encoder<W>) -> io::Result<()> {
+        encoder.encode(self.name.as_str())?;
+        encoder.u32(self.value.len() as u32)?;
+        for &bit in &self.value {
+            encoder.u8(bit as u8)?;
+        }
+        Ok(())
+    }
+}
+
+impl Decode for EnumValue {
+    fn decode<R: Read>(decoder: &mut Decoder<R>) -> Result<Self, SchemaError> {
+        let name = decoder.string()?;
+        let len = decoder.u32()?;
+        let mut value = Vec::with_capacity(len.min(1024) as usize);
+        for _ in 0..len {
+            value.push(match decoder.u8()? {
+                0 => false,
+                1 => true,
+                _ => return Err(SchemaError::Invalid("enum bit isn't 0 or 1")),
+            });
+        }
+        Ok(Self { name, value })
+    }
+}
+
+impl Encode for VarKind {
+    fn encode<W: Write>(&self, encoder: &mut Encoder<W>) -> io::Result<()> {
+        match self {
+            VarKind::None => encoder.u8(0),
+            VarKind::Integer {
+                storages,
+                msb_index,
+                lsb_index,
+                signedness,
+            } => {
+                encoder.u8(1)?;
+                encoder.encode(&storages[..])?;
+                encoder.u32(*msb_index)?;
+                encoder.u32(*lsb_index)?;
+                encoder.encode(signedness)
+            }
+            VarKind::Enum { storage, values } => {
+                encoder.u8(2)?;
+                encoder.encode(storage)?;
+                encoder.encode(&values[..])
+            }
+            VarKind::Utf8 { storage } => {
+                encoder.u8(3)?;
+                encoder.encode(storage)
+            }
+            VarKind::Event { storage } => {
+                encoder.u8(4)?;
+                encoder.encode(storage)
+            }
+        }
+    }
+}
+
+impl Decode for VarKind {
+    fn decode<R: Read>(decoder: &mut Decoder<R>) -> Result<Self, SchemaError> {
+        Ok(match decoder.u8()? {
+            0 => VarKind::None,
+            1 => VarKind::Integer {
+                storages: decoder.decode()?,
+                msb_index: decoder.u32()?,
+                lsb_index: decoder.u32()?,
+                signedness: decoder.decode()?,
+            },
+            2 => VarKind::Enum {
+                storage: decoder.decode()?,
+                values: decoder.decode()?,
+            },
+            3 => VarKind::Utf8 {
+                storage: decoder.decode()?,
+            },
+            4 => VarKind::Event {
+                storage: decoder.decode()?,
+            },
+            _ => return Err(SchemaError::Invalid("unknown variable kind")),
+        })
+    }
+}
+
+impl Encode for Var {
+    fn encode<W: Write>(&self, encoder: &mut Encoder<W>) -> io::Result<()> {
+        encoder.encode(&self.name)?;
+        encoder.encode(&self.scope_id)?;
+        encoder.encode(&self.kind)?;
+        match &self.source {
+            None => encoder.u8(0),
+            Some(source) => {
+                encoder.u8(1)?;
+                encoder.encode(source.file.as_str())?;
+                encoder.u32(source.line)
+            }
+        }
+    }
+}
+
+impl Decode for Var {
+    fn decode<R: Read>(decoder: &mut Decoder<R>) -> Result<Self, SchemaError> {
+        Ok(Self {
+            name: decoder.decode()?,
+            scope_id: decoder.decode()?,
+            kind: decoder.decode()?,
+            source: match decoder.u8()? {
+                0 => None,
+                1 => Some(SourceLocation {
+                    file: decoder.string()?,
+                    line: decoder.u32()?,
+                }),
+                _ => return Err(SchemaError::Invalid("bad source location tag")),
+            },
+        })
+    }
+}
+
+/// Options are a byte saying whether they're there, then their value if they are.
+fn encode_pair<W: Write>(encoder: &mut Encoder<W>, pair: Option<(u64, u64)>) -> io::Result<()> {
+    match pair {
+        None => encoder.u8(0),
+        Some((min, max)) => {
+            encoder.u8(1)?;
+            encoder.u64(min)?;
+            encoder.u64(max)
+        }
+    }
+}
+
+fn decode_pair<R: Read>(decoder: &mut Decoder<R>) -> Result<Option<(u64, u64)>, SchemaError> {
+    match decoder.u8()? {
+        0 => Ok(None),
+        1 => Ok(Some((decoder.u64()?, decoder.u64()?))),
+        _ => Err(SchemaError::Invalid("bad option tag")),
+    }
+}
+
+impl Encode for Summary {
+    fn encode<W: Write>(&self, encoder: &mut Encoder<W>) -> io::Result<()> {
+        encode_pair(encoder, self.unsigned)?;
+        encode_pair(
+            encoder,
+            self.signed.map(|(min, max)| (min as u64, max as u64)),
+        )?;
+        match self.last {
+            None => encoder.u8(0)?,
+            Some(last) => {
+                encoder.u8(1)?;
+                encoder.u64(last)?;
+            }
+        }
+        encoder.encode(&self.end)
+    }
+}
+
+impl Decode for Summary {
+    fn decode<R: Read>(decoder: &mut Decoder<R>) -> Result<Self, SchemaError> {
+        Ok(Self {
+            unsigned: decode_pair(decoder)?,
+            signed: decode_pair(decoder)?.map(|(min, max)| (min as i64, max as i64)),
+            last: match decoder.u8()? {
+                0 => None,
+                1 => Some(decoder.u64()?),
+                _ => return Err(SchemaError::Invalid("bad option tag")),
+            },
+            end: decoder.decode()?,
+        })
+    }
+}
+
+impl Encode for BlockEntry {
+    fn encode<W: Write>(&self, encoder: &mut Encoder<W>) -> io::Result<()> {
+        encoder.u64(self.offset)?;
+        encoder.u64(self.len)?;
+        encoder.encode(&self.start)?;
+        encoder.u64(self.first_change)?;
+        match &self.summary {
+            None => encoder.u8(0),
+            Some(summary) => {
+                encoder.u8(1)?;
+                encoder.encode(summary)
+            }
+        }
+    }
+}
+
+impl Decode for BlockEntry {
+    fn decode<R: Read>(decoder: &mut Decoder<R>) -> Result<Self, SchemaError> {
+        Ok(Self {
+            offset: decoder.u64()?,
+            len: decoder.u64()?,
+            start: decoder.decode()?,
+            first_change: decoder.u64()?,
+            summary: match decoder.u8()? {
+                0 => None,
+                1 => Some(decoder.decode()?),
+                _ => return Err(SchemaError::Invalid("bad summary tag")),
+            },
+        })
+    }
+}
+
+impl Encode for BlockIndex {
+    fn encode<W: Write>(&self, encoder: &mut Encoder<W>) -> io::Result<()> {
+        encoder.encode(&self.storage)?;
+        encoder.u32(self.bytes)?;
+        encoder.u64(self.changes)?;
+        encoder.encode(&self.blocks[..])
+    }
+}
+
+impl Decode for BlockIndex {
+    fn decode<R: Read>(decoder: &mut Decoder<R>) -> Result<Self, SchemaError> {
+        Ok(Self {
+            storage: decoder.decode()?,
+            bytes: decoder.u32()?,
+            changes: decoder.u64()?,
+            blocks: decoder.decode()?,
+        })
+    }
+}
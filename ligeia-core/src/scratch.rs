@@ -0,0 +1,158 @@
+//! Where ingested changes spill to disk.
+//!
+//! The system temp dir is often a small tmpfs, which a big dump can fill up partway through
+//! loading. Setting `LIGEIA_SCRATCH_DIR`, or [`IngestorOptions::with_scratch_dir`], puts the
+//! spill files somewhere roomier instead.
+//!
+//! Small waveforms don't need to spill at all: with [`IngestorOptions::with_memory_limit`],
+//! changes stay in memory until there are more of them than that.
+//!
+//! [`IngestorOptions::with_scratch_dir`]: crate::IngestorOptions::with_scratch_dir
+//! [`IngestorOptions::with_memory_limit`]: crate::IngestorOptions::with_memory_limit
+
+use std::{
+    env,
+    fs::File,
+    io::{self, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{pread, Error, Warning};
+
+/// The environment variable that overrides the scratch directory.
+pub const DIR_VAR: &str = "LIGEIA_SCRATCH_DIR";
+
+/// The directory that spill files are created in, unless the options say otherwise.
+pub fn dir() -> PathBuf {
+    env::var_os(DIR_VAR)
+        .map(PathBuf::from)
+        .unwrap_or_else(env::temp_dir)
+}
+
+/// Check that the scratch directory `dir` has room for about `needed` more bytes, of which the
+/// first `memory_limit` stay in memory instead.
+///
+/// `needed` is only ever an estimate, so running short is a [`Warning`] rather than an error:
+/// the changes may well fit anyway.
+pub fn check_space(dir: &Path, needed: u64, memory_limit: u64) -> Result<Option<Warning>, Error> {
+    let needed = needed.saturating_sub(memory_limit);
+    if needed == 0 {
+        return Ok(None);
+    }
+
+    let available =
+        fs2::available_space(dir).map_err(|e| Error::ScratchDir(dir.display().to_string(), e))?;
+
+    Ok((available < needed).then(|| Warning::ScratchSpace {
+        dir: dir.display().to_string(),
+        needed,
+        available,
+    }))
+}
+
+/// Create an anonymous spill file in `dir`, which is deleted once it's closed.
+fn file(dir: &Path) -> Result<File, Error> {
+    tempfile::tempfile_in(dir).map_err(|e| Error::ScratchDir(dir.display().to_string(), e))
+}
+
+/// Somewhere to append blocks of changes and read them back from, which starts out in memory and
+/// moves to a spill file once it grows past a limit.
+pub(crate) struct Scratch {
+    memory: Vec<u8>,
+    file: Option<File>,
+    limit: u64,
+    dir: PathBuf,
+}
+
+impl Scratch {
+    /// With a `limit` of 0, the spill file is created straight away, so a scratch directory that
+    /// can't be used is found out before anything is ingested.
+    pub(crate) fn new(limit: u64, dir: &Path) -> Result<Self, Error> {
+        Ok(Self {
+            memory: vec![],
+            file: match limit {
+                0 => Some(file(dir)?),
+                _ => None,
+            },
+            limit,
+            dir: dir.to_path_buf(),
+        })
+    }
+
+    /// How many bytes are in memory, which is none of them once they've spilled.
+    pub(crate) fn memory_len(&self) -> u64 {
+        self.memory.len() as u64
+    }
+
+    /// The offset that the next write goes to.
+    pub(crate) fn end(&mut self) -> io::Result<u64> {
+        match &mut self.file {
+            // Reads don't move the cursor on every platform, so it has to be put back.
+            Some(file) => file.seek(SeekFrom::End(0)),
+            None => Ok(self.memory.len() as u64),
+        }
+    }
+
+    /// Fill `buffer` from `offset`.
+    pub(crate) fn read_exact_at(&self, buffer: &mut [u8], offset: u64) -> io::Result<()> {
+        match &self.file {
+            Some(file) => pread::read_exact_at(file, buffer, offset),
+            None => {
+                let start = offset as usize;
+                let data = self
+                    .memory
+                    .get(start..start + buffer.len())
+                    .ok_or(io::ErrorKind::UnexpectedEof)?;
+                buffer.copy_from_slice(data);
+                Ok(())
+            }
+        }
+    }
+
+    fn spill(&mut self) -> io::Result<&mut File> {
+        let mut file = tempfile::tempfile_in(&self.dir)?;
+        file.write_all(&self.memory)?;
+        self.memory = vec![];
+        Ok(self.file.insert(file))
+    }
+}
+
+impl Write for Scratch {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let file = match &mut self.file {
+            Some(file) => file,
+            None if (self.memory.len() + buf.len()) as u64 <= self.limit => {
+                self.memory.extend_from_slice(buf);
+                return Ok(buf.len());
+            }
+            None => self.spill()?,
+        };
+        file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.file {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+fn is_out_of_space(e: &io::Error) -> bool {
+    // `ErrorKind::StorageFull` isn't stable, so go by the OS error codes.
+    match e.raw_os_error() {
+        #[cfg(unix)]
+        Some(28) => true, // ENOSPC
+        #[cfg(windows)]
+        Some(39) | Some(112) => true, // ERROR_HANDLE_DISK_FULL, ERROR_DISK_FULL
+        _ => false,
+    }
+}
+
+/// Name the scratch directory `dir` in errors from writing spill files, if it's run out of space.
+pub(crate) fn write_error(dir: &Path, e: io::Error) -> Error {
+    match is_out_of_space(&e) {
+        true => Error::ScratchFull(dir.display().to_string()),
+        false => Error::Io(e),
+    }
+}
@@ -0,0 +1,156 @@
+//! Finding variables by hierarchical name, with glob patterns like `top.cpu.*.valid` or
+//! `top.**.irq_[0-3]`.
+//!
+//! `*` matches anything within one level of the hierarchy, `**` matches anything at all, across
+//! levels, and `?` matches a single character other than `.`. `[abc]`, `[a-z]` and `[!abc]`
+//! match one character from, or not from, a set. A backslash matches the character after it
+//! literally, for names with brackets in them.
+
+use std::{borrow::Cow, str::Chars};
+
+#[derive(Debug, thiserror::Error, Clone, PartialEq, Eq)]
+pub enum GlobError {
+    #[error("unclosed `[` in `{0}`")]
+    UnclosedClass(String),
+    #[error("`{0}` ends with a lone `\\`")]
+    TrailingEscape(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Literal(char),
+    /// `?`
+    One,
+    /// `*`
+    Star,
+    /// `**`
+    DoubleStar,
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+impl Token {
+    /// Whether a token that matches a single character matches `c`.
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Token::Literal(literal) => *literal == c,
+            Token::One => c != '.',
+            Token::Class { negated, ranges } => {
+                ranges.iter().any(|&(low, high)| low <= c && c <= high) != *negated
+            }
+            Token::Star | Token::DoubleStar => unreachable!("stars match runs of characters"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glob {
+    tokens: Vec<Token>,
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Result<Self, GlobError> {
+        let mut tokens = vec![];
+        let mut chars = pattern.chars().peekable();
+        while let Some(c) = chars.next() {
+            tokens.push(match c {
+                '*' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    Token::DoubleStar
+                }
+                '*' => Token::Star,
+                '?' => Token::One,
+                '[' => parse_class(&mut chars)
+                    .ok_or_else(|| GlobError::UnclosedClass(pattern.to_string()))?,
+                '\\' => Token::Literal(
+                    chars
+                        .next()
+                        .ok_or_else(|| GlobError::TrailingEscape(pattern.to_string()))?,
+                ),
+                c => Token::Literal(c),
+            });
+        }
+
+        Ok(Self { tokens })
+    }
+
+    /// Whether the whole of `path` matches.
+    pub fn matches(&self, path: &str) -> bool {
+        let path: Vec<char> = path.chars().collect();
+
+        // `matched[j]` is whether the tokens so far match the first `j` characters.
+        let mut matched = vec![false; path.len() + 1];
+        matched[0] = true;
+        for token in &self.tokens {
+            let mut next = vec![false; path.len() + 1];
+            match token {
+                Token::Star | Token::DoubleStar => {
+                    for j in 0..=path.len() {
+                        next[j] = matched[j]
+                            || (j > 0
+                                && next[j - 1]
+                                && (*token == Token::DoubleStar || path[j - 1] != '.'));
+                    }
+                }
+                token => {
+                    for j in 1..=path.len() {
+                        next[j] = matched[j - 1] && token.matches(path[j - 1]);
+                    }
+                }
+            }
+            matched = next;
+        }
+
+        matched[path.len()]
+    }
+}
+
+/// Escape the characters in `literal` that mean something in a glob, so that it only matches
+/// itself.
+pub fn escape(literal: &str) -> Cow<'_, str> {
+    const SPECIAL: &[char] = &['*', '?', '[', ']', '\\'];
+    if !literal.contains(SPECIAL) {
+        return Cow::Borrowed(literal);
+    }
+    let mut escaped = String::with_capacity(literal.len() + 2);
+    for c in literal.chars() {
+        if SPECIAL.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    Cow::Owned(escaped)
+}
+
+/// Parse the rest of a `[...]` class, after the `[`.
+fn parse_class(chars: &mut std::iter::Peekable<Chars>) -> Option<Token> {
+    let negated = chars.peek() == Some(&'!');
+    if negated {
+        chars.next();
+    }
+
+    let mut ranges = vec![];
+    // A `]` straight away is part of the class, rather than closing it.
+    let mut first = true;
+    loop {
+        let low = match chars.next()? {
+            ']' if !first => return Some(Token::Class { negated, ranges }),
+            '\\' => chars.next()?,
+            c => c,
+        };
+        first = false;
+
+        let mut lookahead = chars.clone();
+        let high = match (lookahead.next(), lookahead.next()) {
+            (Some('-'), Some(high)) if high != ']' => {
+                chars.next();
+                chars.next();
+                high
+            }
+            _ => low,
+        };
+        ranges.push((low, high));
+    }
+}
@@ -0,0 +1,165 @@
+//! Views of a range of bits within a storage, like a single bit of a bus, that read the
+//! storage's own changes rather than keeping a copy of their own.
+
+use crate::{
+    logic,
+    meta::{StorageId, StorageType, Timesteps, Var, VarKind},
+    Error, Processed,
+};
+
+/// Bits `start..start + width` of a storage, counting from the first value in it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct BitSlice {
+    pub storage: StorageId,
+    pub start: u32,
+    pub width: u32,
+}
+
+impl BitSlice {
+    /// One slice per bit of the storage, in order.
+    pub fn bits(processed: &Processed, storage: StorageId) -> Result<Vec<Self>, Error> {
+        Ok((0..processed.storage(storage)?.width)
+            .map(|start| Self {
+                storage,
+                start,
+                width: 1,
+            })
+            .collect())
+    }
+
+    /// The bit of `var` with the declared `index`, like bit 3 of a var declared `[7:0]`, or
+    /// `None` if the var isn't an integer or doesn't have that bit.
+    pub fn declared_bit(
+        processed: &Processed,
+        var: &Var,
+        index: u32,
+    ) -> Result<Option<Self>, Error> {
+        match var.kind.bit_offset(index) {
+            Some(offset) => Self::of_var(processed, var, offset, 1),
+            None => Ok(None),
+        }
+    }
+
+    /// Bits `offset..offset + width` of `var`, counting from its least significant bit, or
+    /// `None` if the var isn't an integer, doesn't have those bits, or has them split between
+    /// storages.
+    pub fn of_var(
+        processed: &Processed,
+        var: &Var,
+        mut offset: u32,
+        width: u32,
+    ) -> Result<Option<Self>, Error> {
+        let storages = match &var.kind {
+            VarKind::Integer { storages, .. } => storages,
+            _ => return Ok(None),
+        };
+
+        for &storage in storages {
+            let storage_width = processed.storage(storage)?.width;
+            if offset < storage_width {
+                return Ok((offset + width <= storage_width).then_some(Self {
+                    storage,
+                    start: offset,
+                    width,
+                }));
+            }
+            offset -= storage_width;
+        }
+
+        Ok(None)
+    }
+
+    /// `var` split into bytes, least significant first, like the byte lanes of a data bus. The
+    /// last is narrower if the var's width isn't a multiple of eight.
+    ///
+    /// Returns `None` if the var isn't an integer, or if any byte is split between storages.
+    pub fn byte_lanes(processed: &Processed, var: &Var) -> Result<Option<Vec<Self>>, Error> {
+        let width = match processed.var_width(var)? {
+            Some(width) if matches!(var.kind, VarKind::Integer { .. }) => width,
+            _ => return Ok(None),
+        };
+
+        let mut lanes = vec![];
+        for offset in (0..width).step_by(8) {
+            match Self::of_var(processed, var, offset, (width - offset).min(8))? {
+                Some(lane) => lanes.push(lane),
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(lanes))
+    }
+
+    /// Pick this slice's bits out of a value of the whole storage, packed the same way.
+    ///
+    /// # Panics
+    /// If the storage holds strings, or the slice doesn't fit in it.
+    fn extract(
+        &self,
+        ty: StorageType,
+        width: u32,
+        data: &[u8],
+        values: &mut Vec<u8>,
+        out: &mut Vec<u8>,
+    ) {
+        let range = self.start as usize..(self.start + self.width) as usize;
+        match ty {
+            StorageType::TwoLogic => {
+                logic::unpack_two(data, width as usize, values);
+                logic::pack_two(&values[range], out);
+            }
+            StorageType::FourLogic => {
+                logic::unpack_four(data, width as usize, values);
+                logic::pack_four(&values[range], out);
+            }
+            StorageType::NineLogic => {
+                out.clear();
+                out.extend_from_slice(&data[range]);
+            }
+            StorageType::Utf8 => panic!("strings can't be sliced"),
+            StorageType::Event => panic!("events can't be sliced"),
+        }
+    }
+
+    /// Call `f` with every change to the slice, in time order.
+    ///
+    /// Changes to the rest of the storage that leave the slice as it was are skipped.
+    ///
+    /// # Panics
+    /// If the storage holds strings, or the slice doesn't fit in it.
+    pub fn load<F>(&self, processed: &mut Processed, mut f: F) -> Result<(), Error>
+    where
+        F: FnMut(Timesteps, &[u8]),
+    {
+        let storage = processed.storage(self.storage)?;
+        let (ty, width) = (storage.ty, storage.width);
+        assert!(self.start + self.width <= width, "slice out of range");
+
+        let mut values = vec![];
+        let mut previous: Option<Vec<u8>> = None;
+        let mut current = vec![];
+        processed.load_storage(self.storage, |time, data| {
+            self.extract(ty, width, data, &mut values, &mut current);
+            if previous.as_ref() != Some(&current) {
+                f(time, &current);
+                previous = Some(current.clone());
+            }
+        })
+    }
+
+    /// The value of the slice at `time`, or `None` if the storage hasn't changed yet.
+    pub fn value_at(
+        &self,
+        processed: &mut Processed,
+        time: Timesteps,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let storage = processed.storage(self.storage)?;
+        let (ty, width) = (storage.ty, storage.width);
+        assert!(self.start + self.width <= width, "slice out of range");
+
+        Ok(processed.value_at(self.storage, time)?.map(|value| {
+            let (mut values, mut out) = (vec![], vec![]);
+            self.extract(ty, width, &value.data, &mut values, &mut out);
+            out
+        }))
+    }
+}
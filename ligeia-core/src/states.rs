@@ -0,0 +1,104 @@
+//! Following enum variables, like the state registers of state machines, through their named
+//! states, and adding up how long they spend in each.
+//!
+//! A value is in a state when its bits match the state's exactly. Values with any bit other than
+//! a plain 0 or 1, and ones that aren't any of the states, aren't in any of them.
+
+use std::{cmp::Reverse, ops::Range};
+
+use crate::{
+    logic,
+    meta::{EnumValue, StorageId, StorageType, Timesteps},
+    Error, Processed,
+};
+
+/// A span of time an enum variable spent in one state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StateSpan {
+    pub start: Timesteps,
+    /// The time it left the state, if it did.
+    pub end: Option<Timesteps>,
+    /// An index into the enum's values, or `None` if it wasn't in any of them.
+    pub state: Option<usize>,
+}
+
+/// Which of `values` a value of a storage is, if any.
+pub fn resolve(values: &[EnumValue], ty: StorageType, width: u32, data: &[u8]) -> Option<usize> {
+    let mut bits = vec![];
+    match ty {
+        StorageType::TwoLogic => logic::unpack_two(data, width as usize, &mut bits),
+        StorageType::FourLogic => logic::unpack_four(data, width as usize, &mut bits),
+        StorageType::NineLogic => bits.extend_from_slice(&data[..width as usize]),
+        StorageType::Utf8 | StorageType::Event => return None,
+    }
+    if bits.iter().any(|&bit| bit > 1) {
+        return None;
+    }
+
+    values.iter().position(|value| {
+        (0..bits.len().max(value.value.len())).all(|i| {
+            let bit = bits.get(i) == Some(&1);
+            bit == value.value.get(i).copied().unwrap_or(false)
+        })
+    })
+}
+
+/// The states that the enum in `storage` is in over `range`, with changes that leave it in the
+/// same state merged. Only the blocks that overlap the range are read.
+///
+/// Like with [`Condition::intervals_in`](crate::condition::Condition::intervals_in), a span that
+/// started before the range starts at the start of it, and the last one has no end. There aren't
+/// any before the storage's first change.
+pub fn state_spans(
+    processed: &mut Processed,
+    storage: StorageId,
+    values: &[EnumValue],
+    range: Range<Timesteps>,
+) -> Result<Vec<StateSpan>, Error> {
+    let storage_info = processed.storage(storage)?;
+    let (ty, width) = (storage_info.ty, storage_info.width);
+
+    let mut spans: Vec<StateSpan> = vec![];
+    processed.changes_in_range_with_initial(storage, range.clone(), |time, data| {
+        let time = time.max(range.start);
+        let state = resolve(values, ty, width, data);
+        match spans.last_mut() {
+            Some(last) if last.state == state => {}
+            Some(last) => {
+                last.end = Some(time);
+                spans.push(StateSpan {
+                    start: time,
+                    end: None,
+                    state,
+                });
+            }
+            None => spans.push(StateSpan {
+                start: time,
+                end: None,
+                state,
+            }),
+        }
+    })?;
+    Ok(spans)
+}
+
+/// How long was spent in each state within `range`, from spans like [`state_spans`] finds, with
+/// the states it spent the most time in first. States it was never in are left out.
+pub fn time_in_states(spans: &[StateSpan], range: Range<Timesteps>) -> Vec<(Option<usize>, u64)> {
+    let mut totals: Vec<(Option<usize>, u64)> = vec![];
+    for span in spans {
+        let start = span.start.max(range.start);
+        let end = span.end.map_or(range.end, |end| end.min(range.end));
+        if end <= start {
+            continue;
+        }
+
+        match totals.iter_mut().find(|(state, _)| *state == span.state) {
+            Some((_, total)) => *total += end.0 - start.0,
+            None => totals.push((span.state, end.0 - start.0)),
+        }
+    }
+    // Ties keep the order the states were first visited in.
+    totals.sort_by_key(|&(_, total)| Reverse(total));
+    totals
+}
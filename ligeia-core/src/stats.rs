@@ -0,0 +1,192 @@
+//! How active each bit of a storage is over a window of time, for power estimation.
+//!
+//! For every bit, this counts how long it spent at each level and how many times it toggled
+//! between 0 and 1, which is what switching-activity formats like SAIF record. For storages read
+//! as numbers, it finds the range of values they take, for fitting an analog plot to them.
+
+use std::{cell::Cell, ops::Range};
+
+use crate::{
+    logic,
+    logic::Nine,
+    meta::{Signedness, StorageId, StorageType, Timesteps},
+    summary::Summary,
+    Error, Processed,
+};
+
+/// A bit's activity over a window of time.
+///
+/// The times add up to the length of the window. Before a storage's first change, its bits
+/// count as x.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BitActivity {
+    /// Timesteps spent at 0.
+    pub t0: u64,
+    /// Timesteps spent at 1.
+    pub t1: u64,
+    /// Timesteps spent at x, or any other level that isn't 0, 1 or z.
+    pub tx: u64,
+    /// Timesteps spent at z.
+    pub tz: u64,
+    /// Transitions from 0 to 1 or 1 to 0. Passing through x or z on the way doesn't count.
+    pub toggles: u64,
+}
+
+impl BitActivity {
+    /// The fraction of the window the bit was 1, or `None` for an empty window.
+    pub fn duty_cycle(&self) -> Option<f64> {
+        match self.t0 + self.t1 + self.tx + self.tz {
+            0 => None,
+            total => Some(self.t1 as f64 / total as f64),
+        }
+    }
+
+    fn spend(&mut self, level: u8, time: u64) {
+        match level {
+            0 => self.t0 += time,
+            1 => self.t1 += time,
+            3 => self.tz += time,
+            _ => self.tx += time,
+        }
+    }
+}
+
+/// Unpack a change to a byte per bit, as 0, 1, 2 for x and 3 for z.
+fn unpack(ty: StorageType, width: u32, data: &[u8], out: &mut Vec<u8>) {
+    out.clear();
+    match ty {
+        StorageType::TwoLogic => logic::unpack_two(data, width as usize, out),
+        StorageType::FourLogic => logic::unpack_four(data, width as usize, out),
+        // Nine-logic values other than 0 and 1 are all counted as x.
+        StorageType::NineLogic => {
+            out.extend(
+                data[..width as usize]
+                    .iter()
+                    .map(|&value| if value <= 1 { value } else { 2 }),
+            )
+        }
+        StorageType::Utf8 | StorageType::Event => unreachable!(),
+    }
+}
+
+/// The activity of each bit of a storage from `window.start` up to `window.end`, least
+/// significant bit first.
+///
+/// Strings and events don't have bits, so storages of them have no activity.
+pub fn bit_activity(
+    processed: &mut Processed,
+    id: StorageId,
+    window: Range<Timesteps>,
+) -> Result<Vec<BitActivity>, Error> {
+    let storage = processed.storage(id)?;
+    let (ty, width) = (storage.ty, storage.width);
+    if matches!(ty, StorageType::Utf8 | StorageType::Event) {
+        return Ok(vec![]);
+    }
+
+    let mut activity = vec![BitActivity::default(); width as usize];
+    let mut levels = vec![2; width as usize];
+    let mut next = vec![];
+    let mut last = window.start;
+
+    processed.changes_in_range_with_initial(id, window.clone(), |time, data| {
+        // The value in effect at the start of the window is given with when it started.
+        let time = time.max(window.start);
+        unpack(ty, width, data, &mut next);
+        for ((bit, level), &next) in activity.iter_mut().zip(&mut levels).zip(&next) {
+            bit.spend(*level, time.0 - last.0);
+            if *level <= 1 && next <= 1 && *level != next {
+                bit.toggles += 1;
+            }
+            *level = next;
+        }
+        last = time;
+    })?;
+
+    let end = window.end.max(last);
+    for (bit, &level) in activity.iter_mut().zip(&levels) {
+        bit.spend(level, end.0 - last.0);
+    }
+
+    Ok(activity)
+}
+
+/// Read unpacked values as a number, with the first as the least significant bit, or `None` if
+/// any of them isn't 0 or 1. Numbers wider than an `f64`'s mantissa lose their low bits.
+fn to_f64(values: &[u8], signedness: Signedness) -> Option<f64> {
+    let mut number = 0.0;
+    for &value in values.iter().rev() {
+        if value > 1 {
+            return None;
+        }
+        number = number * 2.0 + value as f64;
+    }
+    match (signedness, values.last()) {
+        (Signedness::SignedTwosComplement, Some(1)) => {
+            Some(number - 2f64.powi(values.len() as i32))
+        }
+        _ => Some(number),
+    }
+}
+
+/// The smallest and largest values a storage has from `window.start` up to `window.end`, read as
+/// numbers, or `None` if it has none.
+///
+/// Values with an `x` or `z` in them aren't numbers, so they're left out. Weak nine-logic values
+/// are read as the level they're pulled to. Strings and events aren't numbers either.
+///
+/// Blocks of changes that are summarized aren't read, so wide windows only read the changes at
+/// their edges.
+pub fn value_range(
+    processed: &mut Processed,
+    id: StorageId,
+    signedness: Signedness,
+    window: Range<Timesteps>,
+) -> Result<Option<(f64, f64)>, Error> {
+    let storage = processed.storage(id)?;
+    let (ty, width) = (storage.ty, storage.width);
+    if matches!(ty, StorageType::Utf8 | StorageType::Event) {
+        return Ok(None);
+    }
+
+    let range = Cell::new(None);
+    let include = |value: f64| {
+        range.set(Some(match range.get() {
+            Some((min, max)) => (f64::min(min, value), f64::max(max, value)),
+            None => (value, value),
+        }))
+    };
+    let summarized = |summary: &Summary| {
+        let extremes = match signedness {
+            Signedness::Unsigned => summary.unsigned.map(|(min, max)| (min as f64, max as f64)),
+            Signedness::SignedTwosComplement => {
+                summary.signed.map(|(min, max)| (min as f64, max as f64))
+            }
+        };
+        if let Some((min, max)) = extremes {
+            include(min);
+            include(max);
+        }
+    };
+
+    let mut values = vec![];
+    let changed = |_, data: &[u8]| {
+        match ty {
+            StorageType::NineLogic => {
+                values.clear();
+                values.extend(
+                    data[..width as usize]
+                        .iter()
+                        .map(|&value| Nine::from_u8(value).map_or(2, Nine::level)),
+                );
+            }
+            _ => unpack(ty, width, data, &mut values),
+        }
+        if let Some(value) = to_f64(&values, signedness) {
+            include(value);
+        }
+    };
+    processed.summarized_changes_in_range(id, window, changed, summarized)?;
+
+    Ok(range.get())
+}
@@ -0,0 +1,101 @@
+//! Summaries of the values in each block of a storage read as a number, so that ranges of time
+//! can be summed up without reading every change in them.
+//!
+//! Only two-logic and four-logic storages up to 64 bits wide are summarized, since their values
+//! fit in a `u64` and they're the ones plotted as numbers.
+
+use crate::{
+    logic::{self, Number, XzPolicy},
+    meta::{self, StorageType, Timesteps},
+};
+
+/// The values of a run of changes to a storage, read as numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Summary {
+    /// The smallest and largest values, read as unsigned numbers. `None` if every value had an
+    /// `x` or `z` in it.
+    pub unsigned: Option<(u64, u64)>,
+    /// The smallest and largest values, read as two's complement numbers.
+    pub signed: Option<(i64, i64)>,
+    /// The last value, or `None` if it had an `x` or `z` in it.
+    pub last: Option<u64>,
+    /// The time of the last change.
+    pub end: Timesteps,
+}
+
+/// Builds up a [`Summary`] one change at a time.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Summarizer {
+    ty: StorageType,
+    width: u32,
+    summary: Option<Summary>,
+}
+
+impl Summarizer {
+    /// A summarizer for a storage, if it's one that's summarized.
+    pub fn new(storage: &meta::Storage) -> Option<Self> {
+        match storage.ty {
+            StorageType::TwoLogic | StorageType::FourLogic if storage.width <= 64 => Some(Self {
+                ty: storage.ty,
+                width: storage.width,
+                summary: None,
+            }),
+            _ => None,
+        }
+    }
+
+    fn read(&self, data: &[u8]) -> Option<u64> {
+        match self.ty {
+            StorageType::TwoLogic => {
+                let mut bytes = [0; 8];
+                let len = data.len().min(8);
+                bytes[..len].copy_from_slice(&data[..len]);
+                let value = u64::from_le_bytes(bytes);
+                Some(match self.width {
+                    64 => value,
+                    width => value & ((1 << width) - 1),
+                })
+            }
+            _ => match logic::four_to_u64(data, self.width as usize, XzPolicy::Reject)? {
+                Number::Known(value) => Some(value),
+                Number::Unknown => None,
+            },
+        }
+    }
+
+    fn signed(&self, value: u64) -> i64 {
+        match self.width {
+            0 => 0,
+            width => ((value << (64 - width)) as i64) >> (64 - width),
+        }
+    }
+
+    pub fn push(&mut self, timestamp: Timesteps, data: &[u8]) {
+        let value = self.read(data);
+        let signed = value.map(|value| self.signed(value));
+        let summary = self.summary.get_or_insert(Summary {
+            unsigned: None,
+            signed: None,
+            last: None,
+            end: timestamp,
+        });
+
+        summary.last = value;
+        summary.end = timestamp;
+        if let (Some(value), Some(signed)) = (value, signed) {
+            summary.unsigned = Some(match summary.unsigned {
+                Some((min, max)) => (min.min(value), max.max(value)),
+                None => (value, value),
+            });
+            summary.signed = Some(match summary.signed {
+                Some((min, max)) => (min.min(signed), max.max(signed)),
+                None => (signed, signed),
+            });
+        }
+    }
+
+    /// The summary of every change pushed since the last time this was called.
+    pub fn take(&mut self) -> Option<Summary> {
+        self.summary.take()
+    }
+}
@@ -0,0 +1,82 @@
+use std::mem;
+
+use fnv::FnvHashMap;
+
+use crate::meta::{StorageId, Timesteps};
+
+/// A time-major index over the changes, recording which storages changed at each timestep.
+///
+/// This sits alongside the signal-major blocks, and answers "what was everything doing at this
+/// time" without scanning the blocks of every storage involved.
+#[derive(Default)]
+pub(crate) struct TimeIndex {
+    /// Each timestep with at least one change, and where its changes start in `changes`.
+    timesteps: Vec<(Timesteps, usize)>,
+    /// The storage that changed, and which of that storage's changes this is.
+    changes: Vec<(StorageId, u64)>,
+}
+
+impl TimeIndex {
+    /// Timesteps are expected to be recorded in order.
+    pub fn record(&mut self, timestep: Timesteps, id: StorageId, ordinal: u64) {
+        if self.timesteps.last().map(|&(last, _)| last) != Some(timestep) {
+            self.timesteps.push((timestep, self.changes.len()));
+        }
+        self.changes.push((id, ordinal));
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.timesteps.shrink_to_fit();
+        self.changes.shrink_to_fit();
+    }
+
+    pub fn heap_bytes(&self) -> usize {
+        self.timesteps.capacity() * mem::size_of::<(Timesteps, usize)>()
+            + self.changes.capacity() * mem::size_of::<(StorageId, u64)>()
+    }
+
+    fn changes_of(&self, index: usize) -> &[(StorageId, u64)] {
+        let start = self.timesteps[index].1;
+        let end = self
+            .timesteps
+            .get(index + 1)
+            .map_or(self.changes.len(), |&(_, start)| start);
+        &self.changes[start..end]
+    }
+
+    /// The storages that changed at exactly `time`.
+    pub fn changed_at(&self, time: Timesteps) -> impl Iterator<Item = StorageId> + '_ {
+        let changes = match self.timesteps.binary_search_by_key(&time, |&(t, _)| t) {
+            Ok(index) => self.changes_of(index),
+            Err(_) => &[],
+        };
+        changes.iter().map(|&(id, _)| id)
+    }
+
+    /// Find the ordinal of the last change at or before `time` for each of `ids`.
+    ///
+    /// This walks backwards from `time`, so it stops as soon as every storage has been seen.
+    pub fn last_changes(&self, ids: &[StorageId], time: Timesteps) -> Vec<Option<u64>> {
+        let mut found = vec![None; ids.len()];
+        let mut pending: FnvHashMap<StorageId, Vec<usize>> = FnvHashMap::default();
+        for (position, &id) in ids.iter().enumerate() {
+            pending.entry(id).or_default().push(position);
+        }
+
+        let end = self.timesteps.partition_point(|&(t, _)| t <= time);
+        for index in (0..end).rev() {
+            if pending.is_empty() {
+                break;
+            }
+
+            // Later changes within a timestep win.
+            for &(id, ordinal) in self.changes_of(index).iter().rev() {
+                for position in pending.remove(&id).into_iter().flatten() {
+                    found[position] = Some(ordinal);
+                }
+            }
+        }
+
+        found
+    }
+}
@@ -0,0 +1,223 @@
+//! How long each timestep of a waveform lasts.
+//!
+//! Waveforms store it as a number of femtoseconds. VCD files, and anything else following
+//! Verilog's `` `timescale ``, write it as 1, 10 or 100 of a unit, like `10 ns`.
+
+use std::{fmt, str::FromStr};
+
+use crate::meta::Timesteps;
+
+#[derive(Debug, thiserror::Error, Clone, PartialEq, Eq)]
+pub enum TimescaleError {
+    #[error("a timescale has to be 1, 10 or 100 of a unit, not {0}")]
+    Multiplier(u64),
+    #[error("unknown time unit `{0}`")]
+    Unit(String),
+    #[error("couldn't parse timescale `{0}`")]
+    Malformed(String),
+    #[error("a timestep has to last longer than zero femtoseconds")]
+    Zero,
+    #[error(
+        "{0} changes would land on the same timestep as the change before them, the first at \
+         timestep {1} before converting"
+    )]
+    Collapsed(u64, u64),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TimeUnit {
+    Femtoseconds,
+    Picoseconds,
+    Nanoseconds,
+    Microseconds,
+    Milliseconds,
+    Seconds,
+}
+
+impl TimeUnit {
+    /// Every unit, largest first.
+    pub const ALL: [TimeUnit; 6] = [
+        TimeUnit::Seconds,
+        TimeUnit::Milliseconds,
+        TimeUnit::Microseconds,
+        TimeUnit::Nanoseconds,
+        TimeUnit::Picoseconds,
+        TimeUnit::Femtoseconds,
+    ];
+
+    pub fn femtoseconds(self) -> u128 {
+        match self {
+            TimeUnit::Seconds => 1_000_000_000_000_000,  // 1e15
+            TimeUnit::Milliseconds => 1_000_000_000_000, // 1e12
+            TimeUnit::Microseconds => 1_000_000_000,     // 1e9
+            TimeUnit::Nanoseconds => 1_000_000,          // 1e6
+            TimeUnit::Picoseconds => 1_000,
+            TimeUnit::Femtoseconds => 1,
+        }
+    }
+
+    /// The unit's symbol, as written in VCD files.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            TimeUnit::Seconds => "s",
+            TimeUnit::Milliseconds => "ms",
+            TimeUnit::Microseconds => "us",
+            TimeUnit::Nanoseconds => "ns",
+            TimeUnit::Picoseconds => "ps",
+            TimeUnit::Femtoseconds => "fs",
+        }
+    }
+}
+
+impl FromStr for TimeUnit {
+    type Err = TimescaleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        TimeUnit::ALL
+            .into_iter()
+            .find(|unit| unit.symbol() == s)
+            .ok_or_else(|| TimescaleError::Unit(s.to_string()))
+    }
+}
+
+/// 1, 10 or 100 of a unit.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Timescale {
+    multiplier: u32,
+    unit: TimeUnit,
+}
+
+impl Timescale {
+    pub const MULTIPLIERS: [u32; 3] = [1, 10, 100];
+
+    pub fn new(multiplier: u64, unit: TimeUnit) -> Result<Self, TimescaleError> {
+        match Self::MULTIPLIERS
+            .into_iter()
+            .find(|&allowed| allowed as u64 == multiplier)
+        {
+            Some(multiplier) => Ok(Self { multiplier, unit }),
+            None => Err(TimescaleError::Multiplier(multiplier)),
+        }
+    }
+
+    /// The timescale that's exactly `femtoseconds` long, in the largest unit it can be written
+    /// in, if there is one.
+    pub fn from_femtoseconds(femtoseconds: u128) -> Option<Self> {
+        TimeUnit::ALL.into_iter().find_map(|unit| {
+            Self::MULTIPLIERS
+                .into_iter()
+                .find(|&multiplier| multiplier as u128 * unit.femtoseconds() == femtoseconds)
+                .map(|multiplier| Self { multiplier, unit })
+        })
+    }
+
+    pub fn multiplier(self) -> u32 {
+        self.multiplier
+    }
+
+    pub fn unit(self) -> TimeUnit {
+        self.unit
+    }
+
+    pub fn femtoseconds(self) -> u128 {
+        self.multiplier as u128 * self.unit.femtoseconds()
+    }
+}
+
+impl FromStr for Timescale {
+    type Err = TimescaleError;
+
+    /// Parses a multiplier and a unit, with or without whitespace between them, like `10 ns` or
+    /// `1ps`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let digits = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        if digits == 0 {
+            return Err(TimescaleError::Malformed(s.to_string()));
+        }
+
+        let multiplier = s[..digits]
+            .parse()
+            .map_err(|_| TimescaleError::Malformed(s.to_string()))?;
+        Self::new(multiplier, s[digits..].trim_start().parse()?)
+    }
+}
+
+impl fmt::Display for Timescale {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.multiplier, self.unit.symbol())
+    }
+}
+
+/// Check that a number of femtoseconds per timestep, from a format that stores it directly, can
+/// be used.
+pub fn validate_femtoseconds(femtoseconds: u128) -> Result<u128, TimescaleError> {
+    match femtoseconds {
+        0 => Err(TimescaleError::Zero),
+        femtoseconds => Ok(femtoseconds),
+    }
+}
+
+/// Converts the times of a storage's changes from one timescale to another, rounding down.
+///
+/// Converting to a coarser timescale can put changes that were at different times on the same
+/// timestep, and only the last of them survives anything that goes by time. Those are counted,
+/// so that they can be warned about, or refused with [`Rescale::check`].
+#[derive(Debug, Clone, Copy)]
+pub struct Rescale {
+    from: u128,
+    to: u128,
+    /// The last change's time, before and after converting.
+    last: Option<(Timesteps, Timesteps)>,
+    collapsed: u64,
+    first_collapsed: Option<Timesteps>,
+}
+
+impl Rescale {
+    /// From timesteps of `from` femtoseconds to timesteps of `to` femtoseconds.
+    pub fn new(from: u128, to: u128) -> Self {
+        Self {
+            from,
+            to,
+            last: None,
+            collapsed: 0,
+            first_collapsed: None,
+        }
+    }
+
+    /// Whether every timestep lands on a timestep of its own, so nothing can collapse.
+    pub fn is_exact(&self) -> bool {
+        self.from % self.to == 0
+    }
+
+    /// Convert the time of the next change, which is expected to be no earlier than the last.
+    pub fn convert(&mut self, time: Timesteps) -> Timesteps {
+        let converted = Timesteps((time.0 as u128 * self.from / self.to) as u64);
+        if let Some((last, last_converted)) = self.last {
+            if last != time && last_converted == converted {
+                self.collapsed += 1;
+                self.first_collapsed.get_or_insert(time);
+            }
+        }
+        self.last = Some((time, converted));
+        converted
+    }
+
+    /// Start on another storage's changes, keeping count of what's collapsed so far.
+    pub fn restart(&mut self) {
+        self.last = None;
+    }
+
+    /// How many changes landed on the same timestep as the change before them.
+    pub fn collapsed(&self) -> u64 {
+        self.collapsed
+    }
+
+    /// An error if any changes collapsed, for callers that would rather not lose any.
+    pub fn check(&self) -> Result<(), TimescaleError> {
+        match self.first_collapsed {
+            Some(first) => Err(TimescaleError::Collapsed(self.collapsed, first.0)),
+            None => Ok(()),
+        }
+    }
+}
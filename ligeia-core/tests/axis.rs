@@ -0,0 +1,125 @@
+use ligeia_core::{
+    axis::{auto_range, ticks, ticks_with},
+    format::Separators,
+    meta::{Scope, ScopeId, Signedness, Storage, StorageId, StorageType, Timesteps},
+    stats::value_range,
+    Ingestor, IngestorOptions, Value,
+};
+
+fn labels(range: (f64, f64), max_ticks: usize) -> Vec<String> {
+    ticks(range, max_ticks)
+        .into_iter()
+        .map(|tick| tick.label)
+        .collect()
+}
+
+#[test]
+fn ticks_at_round_numbers() {
+    assert_eq!(
+        labels((0.0, 100.0), 5),
+        ["0", "20", "40", "60", "80", "100"]
+    );
+    assert_eq!(labels((-3.0, 12.0), 4), ["0", "5", "10"]);
+    assert_eq!(labels((0.12, 0.5), 4), ["0.2", "0.3", "0.4", "0.5"]);
+    assert_eq!(
+        labels((-12.5, -9.5), 10),
+        ["-12.5", "-12.0", "-11.5", "-11.0", "-10.5", "-10.0", "-9.5"]
+    );
+    assert!(ticks((1.0, 1.0), 5).is_empty());
+}
+
+#[test]
+fn ticks_in_a_locale() {
+    let labels: Vec<_> = ticks_with((0.0, 3000.0), 3, Separators::from_locale("de_DE"))
+        .into_iter()
+        .map(|tick| tick.label)
+        .collect();
+    assert_eq!(labels, ["0", "1.000", "2.000", "3.000"]);
+
+    let labels: Vec<_> = ticks_with((0.12, 0.5), 4, Separators::from_locale("fr_FR"))
+        .into_iter()
+        .map(|tick| tick.label)
+        .collect();
+    assert_eq!(labels, ["0,2", "0,3", "0,4", "0,5"]);
+}
+
+#[test]
+fn auto_ranging_pads_the_values() {
+    assert_eq!(auto_range((0.0, 100.0)), (-5.0, 105.0));
+    assert_eq!(auto_range((7.0, 7.0)), (6.5, 7.5));
+}
+
+#[test]
+fn value_ranges() {
+    let mut ingestor = Ingestor::new(IngestorOptions::new()).unwrap();
+    let name = ingestor.intern("top");
+    ingestor.ingest_scope(Scope {
+        id: ScopeId(1),
+        parent: ScopeId::ROOT,
+        name,
+    });
+    ingestor.ingest_storage(Storage {
+        id: StorageId(0),
+        ty: StorageType::TwoLogic,
+        width: 4,
+        start: 0,
+    });
+    ingestor.ingest_storage(Storage {
+        id: StorageId(1),
+        ty: StorageType::FourLogic,
+        width: 2,
+        start: 0,
+    });
+    // 3, 12, 5 and 9, which is -4, -3 and -7 when signed.
+    for (time, value) in [(0, 0b0011), (10, 0b1100), (20, 0b0101), (30, 0b1001)] {
+        ingestor.ingest_timestep(Timesteps(time));
+        ingestor
+            .ingest_value(Value {
+                storage_id: StorageId(0),
+                data: &[value],
+            })
+            .unwrap();
+    }
+    // 1, then x, then 2.
+    for (time, value) in [(0, 0b00_01), (10, 0b10_00), (20, 0b01_00)] {
+        ingestor.ingest_timestep(Timesteps(time));
+        ingestor
+            .ingest_value(Value {
+                storage_id: StorageId(1),
+                data: &[value],
+            })
+            .unwrap();
+    }
+    let mut processed = ingestor.finish().unwrap();
+
+    let range = |processed: &mut _, id, signedness, (start, end)| {
+        value_range(
+            processed,
+            StorageId(id),
+            signedness,
+            Timesteps(start)..Timesteps(end),
+        )
+        .unwrap()
+    };
+    assert_eq!(
+        range(&mut processed, 0, Signedness::Unsigned, (0, 40)),
+        Some((3.0, 12.0))
+    );
+    // The value at the start of the window counts, whenever it changed to it.
+    assert_eq!(
+        range(&mut processed, 0, Signedness::Unsigned, (15, 25)),
+        Some((5.0, 12.0))
+    );
+    assert_eq!(
+        range(&mut processed, 0, Signedness::SignedTwosComplement, (0, 40)),
+        Some((-7.0, 5.0))
+    );
+    assert_eq!(
+        range(&mut processed, 1, Signedness::Unsigned, (0, 40)),
+        Some((1.0, 2.0))
+    );
+    assert_eq!(
+        range(&mut processed, 1, Signedness::Unsigned, (10, 20)),
+        None
+    );
+}
@@ -0,0 +1,151 @@
+//! Values are stored least significant bit first, and declared bit indices are found from the
+//! var's range, whichever way round it is.
+
+mod common;
+
+use ligeia_core::{
+    logic,
+    meta::{StorageId, StorageType, Timesteps, VarKind},
+    slice::BitSlice,
+    Ingestor, IngestorOptions, Processed,
+};
+
+#[test]
+fn bit_offsets_follow_the_declared_range() {
+    let down = common::integer(&[0], 7, 0);
+    assert_eq!(down.bit_offset(0), Some(0));
+    assert_eq!(down.bit_offset(7), Some(7));
+    assert_eq!(down.bit_offset(8), None);
+
+    let up = common::integer(&[0], 0, 7);
+    assert_eq!(up.bit_offset(0), Some(7));
+    assert_eq!(up.bit_offset(7), Some(0));
+
+    let shifted = common::integer(&[0], 11, 4);
+    assert_eq!(shifted.bit_offset(4), Some(0));
+    assert_eq!(shifted.bit_offset(11), Some(7));
+    assert_eq!(shifted.bit_offset(3), None);
+
+    let single = common::integer(&[0], 5, 5);
+    assert_eq!(single.bit_offset(5), Some(0));
+    assert_eq!(single.bit_offset(4), None);
+
+    for kind in [&down, &up, &shifted, &single] {
+        for offset in 0..8 {
+            let index = kind.bit_index(offset);
+            assert_eq!(
+                index.and_then(|index| kind.bit_offset(index)),
+                index.map(|_| offset)
+            );
+        }
+    }
+    assert_eq!(up.bit_index(7), Some(0));
+    assert_eq!(shifted.bit_index(8), None);
+
+    assert_eq!(
+        VarKind::Utf8 {
+            storage: StorageId(0)
+        }
+        .bit_offset(0),
+        None
+    );
+}
+
+/// `top` holds `bus[11:4]`, split over a six-bit and a two-bit storage, `rev[0:3]` and
+/// `wide[19:0]`.
+fn waveform() -> Processed {
+    let mut ingestor = Ingestor::new(IngestorOptions::new().with_timescale(1_000_000)).unwrap();
+    common::scopes(&mut ingestor, &[(1, 0, "top")]);
+    for (id, width) in [(0, 6), (1, 2), (2, 4), (3, 20)] {
+        common::storages(&mut ingestor, &[(id, StorageType::FourLogic, width)]);
+    }
+    for (name, kind) in [
+        ("bus", common::integer(&[0, 1], 11, 4)),
+        ("rev", common::integer(&[2], 0, 3)),
+        ("wide", common::integer(&[3], 19, 0)),
+    ] {
+        common::var(&mut ingestor, 1, name, kind);
+    }
+
+    // `bus` is 0b10_000110 and `rev` is 0b0x01, least significant bit first.
+    let mut packed = vec![];
+    for (id, values) in [
+        (0, &[0, 1, 1, 0, 0, 0][..]),
+        (1, &[0, 1]),
+        (2, &[1, 0, 2, 0]),
+    ] {
+        logic::pack_four(values, &mut packed);
+        common::changes(&mut ingestor, &[(0, id, &packed)]);
+    }
+    ingestor.finish().unwrap()
+}
+
+/// The value of every declared bit of the var called `name` from `indices`, with `None` for
+/// those it doesn't have.
+fn read_bits(
+    processed: &mut Processed,
+    name: &str,
+    indices: std::ops::Range<u32>,
+) -> Vec<Option<u8>> {
+    let var = processed
+        .vars()
+        .iter()
+        .position(|var| processed.name(var.name) == name)
+        .unwrap();
+
+    indices
+        .map(|index| {
+            let bit = BitSlice::declared_bit(processed, &processed.vars()[var], index).unwrap()?;
+            let value = bit.value_at(processed, Timesteps(0)).unwrap().unwrap();
+            Some(value[0])
+        })
+        .collect()
+}
+
+#[test]
+fn declared_bits_read_the_right_values() {
+    let mut processed = waveform();
+
+    assert_eq!(
+        read_bits(&mut processed, "bus", 3..13),
+        [
+            None,
+            Some(0),
+            Some(1),
+            Some(1),
+            Some(0),
+            Some(0),
+            Some(0),
+            Some(0),
+            Some(1),
+            None
+        ]
+    );
+    assert_eq!(
+        read_bits(&mut processed, "rev", 0..5),
+        [Some(0), Some(2), Some(0), Some(1), None]
+    );
+}
+
+#[test]
+fn byte_lanes() {
+    let processed = waveform();
+    let lanes = |name: &str| {
+        let var = processed
+            .vars()
+            .iter()
+            .find(|var| processed.name(var.name) == name)
+            .unwrap();
+        BitSlice::byte_lanes(&processed, var).unwrap().map(|lanes| {
+            lanes
+                .into_iter()
+                .map(|lane| (lane.storage.0, lane.start, lane.width))
+                .collect::<Vec<_>>()
+        })
+    };
+
+    assert_eq!(lanes("wide"), Some(vec![(3, 0, 8), (3, 8, 8), (3, 16, 4)]));
+    assert_eq!(lanes("rev"), Some(vec![(2, 0, 4)]));
+    // Its one byte is split between its two storages.
+    assert_eq!(lanes("bus"), None);
+}
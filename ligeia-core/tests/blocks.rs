@@ -0,0 +1,674 @@
+//! Round-trips changes through ingestion at sizes around the edges of a storage block.
+
+mod common;
+
+use ligeia_core::{
+    changes::{Four, Two},
+    logic,
+    meta::{Storage, StorageId, StorageType, Timesteps},
+    schema::{self, Metadata},
+    Error, Ingestor, IngestorOptions, Processed, Value,
+};
+
+/// Blocks aim for this many bytes, so the interesting change counts are around multiples of it.
+const BLOCK_TARGET: usize = 10 * 1024;
+
+/// How many changes fit in a block, when each is 10 timesteps after the last.
+///
+/// Blocks start with an 8-byte timestamp, and a change is only added if there's room for the
+/// largest possible one, with a 10-byte timestamp delta.
+fn per_block(bytes: usize) -> usize {
+    (BLOCK_TARGET - 8 - (10 + bytes)) / (1 + bytes) + 1
+}
+
+/// How many changes fit in a block when their values are packed in with the time since the
+/// change before, which takes a single byte when each is 10 timesteps after the last.
+fn per_packed_block() -> usize {
+    (BLOCK_TARGET - 8 - 11) + 1
+}
+
+/// `data` as a four-logic storage of `width` bits reads it back, with the bits past its width
+/// zeroed.
+fn masked(width: u32, mut data: Vec<u8>) -> Vec<u8> {
+    if let (Some(last), bits @ 1..=7) = (data.last_mut(), width * 2 % 8) {
+        *last &= (1 << bits) - 1;
+    }
+    data
+}
+
+/// A recognizable value for the `i`th change, `bytes` long.
+fn value(i: usize, bytes: usize) -> Vec<u8> {
+    (0..bytes).map(|j| (i * 31 + j) as u8).collect()
+}
+
+/// Ingest `changes` changes to a single four-logic storage of `width` bits.
+fn ingest(width: u32, changes: usize, data: impl Fn(usize) -> Vec<u8>) -> Processed {
+    ingest_with(IngestorOptions::new(), width, changes, data)
+}
+
+fn ingest_with(
+    options: IngestorOptions,
+    width: u32,
+    changes: usize,
+    data: impl Fn(usize) -> Vec<u8>,
+) -> Processed {
+    let values: Vec<_> = (0..changes).map(data).collect();
+    let changes: Vec<_> = values
+        .iter()
+        .enumerate()
+        .map(|(i, value)| (i as u64 * 10, 0, &value[..]))
+        .collect();
+    common::waveform(options, &[(0, StorageType::FourLogic, width)], &changes)
+}
+
+fn read_back(processed: &mut Processed) -> Vec<(Timesteps, Vec<u8>)> {
+    let mut changes = vec![];
+    processed
+        .load_storage(StorageId(0), |timestamp, data| {
+            changes.push((timestamp, data.to_vec()))
+        })
+        .unwrap();
+    changes
+}
+
+fn round_trip(width: u32, changes: usize) {
+    let bytes = (width as usize + 3) / 4;
+    let mut processed = ingest(width, changes, |i| value(i, bytes));
+
+    let read = read_back(&mut processed);
+    assert_eq!(read.len(), changes, "width {}, {} changes", width, changes);
+    for (i, (timestamp, data)) in read.into_iter().enumerate() {
+        assert_eq!(timestamp, Timesteps(i as u64 * 10));
+        assert_eq!(data, masked(width, value(i, bytes)));
+    }
+    assert_eq!(
+        processed.change_count(StorageId(0)).unwrap(),
+        changes as u64
+    );
+}
+
+#[test]
+fn empty_storage() {
+    round_trip(8, 0);
+}
+
+#[test]
+fn changes_around_block_boundaries() {
+    for width in [1, 4, 5, 32, 33, 64, 4 * 1017] {
+        let bytes = (width as usize + 3) / 4;
+        let per_block = match width {
+            1 => per_packed_block(),
+            _ => per_block(bytes),
+        };
+
+        for changes in [
+            1,
+            per_block - 1,
+            per_block,
+            per_block + 1,
+            2 * per_block,
+            2 * per_block + 1,
+        ] {
+            round_trip(width, changes);
+        }
+    }
+}
+
+#[test]
+fn changes_larger_than_a_block() {
+    // Each change is bigger than the block target, so every block holds exactly one.
+    let width = 4 * BLOCK_TARGET as u32 + 4;
+    for changes in [1, 2, 5] {
+        round_trip(width, changes);
+    }
+}
+
+#[test]
+fn short_values_are_zero_extended() {
+    let mut processed = ingest(32, 3, |i| vec![i as u8 + 1]);
+
+    let read = read_back(&mut processed);
+    assert_eq!(
+        read,
+        (0..3)
+            .map(|i| (Timesteps(i * 10), vec![i as u8 + 1, 0, 0, 0, 0, 0, 0, 0]))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn long_values_are_truncated() {
+    let mut processed = ingest(4, 2, |i| vec![i as u8 + 1, 0xff, 0xff]);
+
+    let read = read_back(&mut processed);
+    assert_eq!(
+        read,
+        vec![(Timesteps(0), vec![1]), (Timesteps(10), vec![2])]
+    );
+}
+
+#[test]
+fn value_at_across_blocks() {
+    let bytes = 2;
+    let per_block = per_block(bytes);
+    let mut processed = ingest(8, per_block + 1, |i| value(i, bytes));
+
+    let last = processed
+        .value_at(StorageId(0), Timesteps(per_block as u64 * 10 + 5))
+        .unwrap()
+        .unwrap();
+    assert_eq!(last.start, Timesteps(per_block as u64 * 10));
+    assert_eq!(last.end, None);
+    assert_eq!(last.data, value(per_block, bytes));
+
+    let straddling = processed
+        .value_at(StorageId(0), Timesteps((per_block as u64 - 1) * 10))
+        .unwrap()
+        .unwrap();
+    assert_eq!(straddling.end, Some(Timesteps(per_block as u64 * 10)));
+    assert_eq!(straddling.data, value(per_block - 1, bytes));
+}
+
+#[test]
+fn extreme_timestamps() {
+    // Deltas of the full 64 bits take the longest varints, and going backwards wraps.
+    let timestamps = [0, u64::MAX, 1, u64::MAX - 1, u64::MAX - 1, 1 << 63];
+
+    let mut ingestor = Ingestor::new(IngestorOptions::new()).unwrap();
+    ingestor.ingest_storage(Storage {
+        id: StorageId(0),
+        ty: StorageType::FourLogic,
+        width: 4,
+        start: 0,
+    });
+    for (i, &timestamp) in timestamps.iter().enumerate() {
+        ingestor.ingest_timestep(Timesteps(timestamp));
+        ingestor
+            .ingest_value(Value {
+                storage_id: StorageId(0),
+                data: &[i as u8],
+            })
+            .unwrap();
+    }
+    let mut processed = ingestor.finish().unwrap();
+
+    let read = read_back(&mut processed);
+    assert_eq!(
+        read,
+        timestamps
+            .iter()
+            .enumerate()
+            .map(|(i, &timestamp)| (Timesteps(timestamp), vec![i as u8]))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(processed.time_bounds(), (Timesteps(0), Timesteps(u64::MAX)));
+}
+
+#[test]
+fn timestamps_past_32_bits() {
+    let boundary = 1u64 << 32;
+    let mut ingestor = Ingestor::new(IngestorOptions::new().with_time_index()).unwrap();
+    ingestor.ingest_storage(Storage {
+        id: StorageId(0),
+        ty: StorageType::FourLogic,
+        width: 8,
+        start: 0,
+    });
+    // Enough changes either side of the boundary to span several blocks.
+    let count = per_block(2) * 2;
+    let times: Vec<u64> = (0..count as u64)
+        .map(|i| boundary - count as u64 / 2 * 10 + i * 10)
+        .collect();
+    for (i, &time) in times.iter().enumerate() {
+        ingestor.ingest_timestep(Timesteps(time));
+        ingestor
+            .ingest_value(Value {
+                storage_id: StorageId(0),
+                data: &value(i, 2),
+            })
+            .unwrap();
+    }
+    let mut processed = ingestor.finish().unwrap();
+
+    let read = read_back(&mut processed);
+    assert!(read
+        .iter()
+        .zip(&times)
+        .all(|((read, _), &time)| *read == Timesteps(time)));
+
+    // The first change past the boundary, found without wrapping back to the start.
+    let at = processed
+        .value_at(StorageId(0), Timesteps(boundary + 5))
+        .unwrap()
+        .unwrap();
+    assert_eq!(at.start, Timesteps(boundary));
+    assert_eq!(at.end, Some(Timesteps(boundary + 10)));
+    assert_eq!(at.data, value(count / 2, 2));
+    assert_eq!(
+        processed.changed_at(Timesteps(boundary)),
+        Some(vec![StorageId(0)])
+    );
+    assert_eq!(processed.changed_at(Timesteps(0)), Some(vec![]));
+}
+
+#[test]
+fn blocks_within_a_range_are_summarized() {
+    use ligeia_core::{logic, summary::Summary};
+
+    // An 8-bit counter that's sometimes x, over four blocks.
+    let count = per_block(2) * 4;
+    let mut processed = ingest(8, count, |i| {
+        let value = (i * 7 % 251) as u8;
+        let values: Vec<u8> = (0..8)
+            .map(|bit| match i % 13 {
+                0 => 2,
+                _ => value >> bit & 1,
+            })
+            .collect();
+        let mut packed = vec![];
+        logic::pack_four(&values, &mut packed);
+        packed
+    });
+
+    let range = Timesteps(55)..Timesteps(count as u64 * 10 - 55);
+    let mut changes = vec![];
+    processed
+        .changes_in_range_with_initial(StorageId(0), range.clone(), |time, data| {
+            changes.push((time, data.to_vec()))
+        })
+        .unwrap();
+
+    let mut read = vec![];
+    let mut summaries: Vec<Summary> = vec![];
+    processed
+        .summarized_changes_in_range(
+            StorageId(0),
+            range,
+            |time, data| read.push((time, data.to_vec())),
+            |summary| summaries.push(*summary),
+        )
+        .unwrap();
+    // Only the blocks at either end are read.
+    assert_eq!(summaries.len(), 2);
+    assert!(read.len() < changes.len() / 2);
+
+    let known = |data: &[u8]| match logic::four_to_u64(data, 8, logic::XzPolicy::Reject) {
+        Some(logic::Number::Known(value)) => Some(value),
+        _ => None,
+    };
+    let skipped: Vec<_> = changes
+        .iter()
+        .filter(|change| !read.contains(change))
+        .collect();
+    let expected = (
+        skipped.iter().filter_map(|(_, data)| known(data)).min(),
+        skipped.iter().filter_map(|(_, data)| known(data)).max(),
+    );
+    let unsigned = summaries.iter().filter_map(|summary| summary.unsigned);
+    let actual = (
+        unsigned.clone().map(|(min, _)| min).min(),
+        unsigned.map(|(_, max)| max).max(),
+    );
+    assert_eq!(actual, expected);
+    assert_eq!(summaries[1].end, skipped.last().unwrap().0);
+    assert_eq!(summaries[1].last, known(&skipped.last().unwrap().1));
+}
+
+#[test]
+fn memory_usage_after_loading() {
+    let bytes = 2;
+    let count = per_block(bytes) * 3 + 1;
+    let processed = ingest(8, count, |i| value(i, bytes));
+
+    let usage = processed.memory_usage();
+    // Every block is written out in full, with its own header.
+    assert_eq!(usage.scratch, (count * (1 + bytes) + 4 * 8) as u64);
+    // Four blocks' worth of index, with nothing left over from growing it.
+    let one_block = ingest(8, 1, |i| value(i, bytes)).memory_usage();
+    assert!(one_block.block_index > 0);
+    assert_eq!(usage.block_index, one_block.block_index * 4);
+    assert_eq!(usage.time_index, 0);
+}
+
+#[test]
+fn small_waveforms_stay_in_memory() {
+    let bytes = 2;
+    let count = per_block(bytes) * 10;
+    let expected: Vec<_> = (0..count)
+        .map(|i| (Timesteps(i as u64 * 10), value(i, bytes)))
+        .collect();
+
+    let options = IngestorOptions::new().with_memory_limit(1 << 30);
+    let mut processed = ingest_with(options, 8, count, |i| value(i, bytes));
+    let usage = processed.memory_usage();
+    assert_eq!(usage.scratch_in_memory, usage.scratch);
+    assert_eq!(read_back(&mut processed), expected);
+
+    // Past the limit, everything moves to a file partway through.
+    let options = IngestorOptions::new().with_memory_limit(64 * 1024);
+    let mut processed = ingest_with(options, 8, count, |i| value(i, bytes));
+    assert_eq!(processed.memory_usage().scratch_in_memory, 0);
+    assert_eq!(read_back(&mut processed), expected);
+
+    let processed = ingest(8, count, |i| value(i, bytes));
+    assert_eq!(processed.memory_usage().scratch_in_memory, 0);
+}
+
+#[test]
+fn block_size_is_configurable() {
+    let bytes = 2;
+    let count = 1000;
+    let options = IngestorOptions::new().with_block_size(1024);
+    let mut processed = ingest_with(options, 8, count, |i| value(i, bytes));
+
+    let per_block = (1024 - 8 - (10 + bytes)) / (1 + bytes) + 1;
+    let blocks = (count + per_block - 1) / per_block;
+    let usage = processed.memory_usage();
+    assert_eq!(usage.scratch, (count * (1 + bytes) + blocks * 8) as u64);
+    assert_eq!(
+        read_back(&mut processed),
+        (0..count)
+            .map(|i| (Timesteps(i as u64 * 10), value(i, bytes)))
+            .collect::<Vec<_>>()
+    );
+
+    // Changes are evenly spaced, so counting them by block is close to exact.
+    let density = processed.change_density(StorageId(0), 10).unwrap();
+    assert_eq!(density.iter().sum::<u64>(), count as u64);
+    assert!(
+        density.iter().all(|&changes| changes.abs_diff(100) <= 1),
+        "{:?}",
+        density
+    );
+}
+
+/// Storages 0 to 63, each with one change except for storage 40, which has ten, committed with
+/// storage 5 first if `prioritize` is set.
+///
+/// Every storage fits in a single block that's only written when it's committed, so the one that
+/// went first in its partition's file is the one at offset 0.
+fn commit_offsets(prioritize: bool) -> Vec<u64> {
+    let mut ingestor = Ingestor::new(IngestorOptions::new()).unwrap();
+    for id in 0..64 {
+        ingestor.ingest_storage(Storage {
+            id: StorageId(id),
+            ty: StorageType::TwoLogic,
+            width: 1,
+            start: 0,
+        });
+    }
+    if prioritize {
+        ingestor.prioritize([StorageId(5)]);
+    }
+    for id in 0..64 {
+        for t in 0..if id == 40 { 10 } else { 1 } {
+            ingestor.ingest_timestep(Timesteps(t));
+            ingestor
+                .ingest_value(Value {
+                    storage_id: StorageId(id),
+                    data: &[t as u8 & 1],
+                })
+                .unwrap();
+        }
+    }
+    let processed = ingestor.finish().unwrap();
+    assert_eq!(processed.hot_storages()[0], StorageId(40));
+
+    let mut bytes = vec![];
+    processed
+        .write_metadata(&mut bytes, schema::VERSION)
+        .unwrap();
+    Metadata::read_from(&bytes[..])
+        .unwrap()
+        .blocks
+        .iter()
+        .map(|index| index.blocks[0].offset)
+        .collect()
+}
+
+#[test]
+fn busy_and_prioritized_storages_are_committed_first() {
+    assert_eq!(commit_offsets(false)[40], 0);
+    assert_eq!(commit_offsets(true)[5], 0);
+}
+
+#[test]
+fn repeated_values_are_dropped() {
+    let mut ingestor = Ingestor::new(IngestorOptions::new().with_dedup()).unwrap();
+    for (id, ty, width) in [
+        (0, StorageType::FourLogic, 8),
+        (1, StorageType::Utf8, 0),
+        (2, StorageType::Event, 0),
+    ] {
+        ingestor.ingest_storage(Storage {
+            id: StorageId(id),
+            ty,
+            width,
+            start: 0,
+        });
+    }
+
+    let changes: [(u64, u32, &[u8]); 9] = [
+        (0, 0, &[0x12, 0]),
+        // Shorter data is zero-extended before it's compared.
+        (1, 0, &[0x12]),
+        (2, 0, &[0x12, 0x34]),
+        (3, 0, &[0x12, 0x34, 0xff]),
+        (0, 1, b"idle"),
+        (1, 1, b"idle"),
+        (2, 1, b"busy"),
+        (0, 2, &[]),
+        (1, 2, &[]),
+    ];
+    for (time, id, data) in changes {
+        ingestor.ingest_timestep(Timesteps(time));
+        ingestor
+            .ingest_value(Value {
+                storage_id: StorageId(id),
+                data,
+            })
+            .unwrap();
+    }
+    let mut processed = ingestor.finish().unwrap();
+
+    assert_eq!(processed.repeats_dropped(), 3);
+    let mut counts = vec![];
+    for id in 0..3 {
+        counts.push(processed.change_count(StorageId(id)).unwrap());
+    }
+    assert_eq!(counts, [2, 2, 2]);
+    assert_eq!(
+        read_back(&mut processed),
+        [
+            (Timesteps(0), vec![0x12, 0]),
+            (Timesteps(2), vec![0x12, 0x34])
+        ]
+    );
+    assert_eq!(processed.string_stats().changes, 2);
+}
+
+#[test]
+fn narrow_values_share_a_byte_with_their_time() {
+    let count = 1000;
+    let mut processed = ingest(1, count, |i| vec![(i % 3) as u8]);
+    // A header, and then a byte for each change.
+    assert_eq!(processed.memory_usage().scratch, (8 + count) as u64);
+    let read = read_back(&mut processed);
+    assert!(read
+        .iter()
+        .enumerate()
+        .all(|(i, (time, data))| *time == Timesteps(i as u64 * 10) && data == &[(i % 3) as u8]));
+
+    // Times that don't fit in the first byte carry on after it.
+    let processed = ingest(1, 3, |i| vec![i as u8 + 1]);
+    assert_eq!(processed.memory_usage().scratch, 8 + 3);
+    let mut ingestor = Ingestor::new(IngestorOptions::new()).unwrap();
+    ingestor.ingest_storage(Storage {
+        id: StorageId(0),
+        ty: StorageType::TwoLogic,
+        width: 3,
+        start: 0,
+    });
+    for (time, value) in [(0, 5), (1 << 40, 2), (u64::MAX, 7)] {
+        ingestor.ingest_timestep(Timesteps(time));
+        ingestor
+            .ingest_value(Value {
+                storage_id: StorageId(0),
+                data: &[value],
+            })
+            .unwrap();
+    }
+    let mut processed = ingestor.finish().unwrap();
+    assert_eq!(
+        read_back(&mut processed),
+        [
+            (Timesteps(0), vec![5]),
+            (Timesteps(1 << 40), vec![2]),
+            (Timesteps(u64::MAX), vec![7])
+        ]
+    );
+}
+
+#[test]
+fn last_values_while_ingesting() {
+    let mut ingestor = Ingestor::new(IngestorOptions::new()).unwrap();
+    for (id, ty, width) in [
+        (0, StorageType::FourLogic, 8),
+        (1, StorageType::TwoLogic, 1),
+        (2, StorageType::Utf8, 0),
+    ] {
+        ingestor.ingest_storage(Storage {
+            id: StorageId(id),
+            ty,
+            width,
+            start: 0,
+        });
+    }
+    assert_eq!(ingestor.last_value(StorageId(0)).unwrap(), None);
+    assert!(matches!(
+        ingestor.last_value(StorageId(3)),
+        Err(Error::UnknownStorage(StorageId(3)))
+    ));
+
+    // Enough to fill a few blocks, so some changes are the last before a flush.
+    for i in 0..per_block(2) * 3 {
+        let time = Timesteps(i as u64 * 10);
+        ingestor.ingest_timestep(time);
+        for (id, data) in [(0, value(i, 2)), (1, vec![i as u8 | 0xfe])] {
+            ingestor
+                .ingest_value(Value {
+                    storage_id: StorageId(id),
+                    data: &data,
+                })
+                .unwrap();
+        }
+        assert_eq!(
+            ingestor.last_value(StorageId(0)).unwrap(),
+            Some((time, &value(i, 2)[..]))
+        );
+        // Only the bit within the storage's width is kept.
+        assert_eq!(
+            ingestor.last_value(StorageId(1)).unwrap(),
+            Some((time, &[i as u8 & 1][..]))
+        );
+    }
+
+    ingestor
+        .ingest_value(Value {
+            storage_id: StorageId(2),
+            data: b"busy",
+        })
+        .unwrap();
+    assert_eq!(
+        ingestor.last_value(StorageId(2)).unwrap().unwrap().1,
+        b"busy"
+    );
+}
+
+#[test]
+fn typed_iteration_matches_loading() {
+    let bytes = 3;
+    let count = per_block(bytes) * 2 + 5;
+    let mut processed = ingest(12, count, |i| value(i, bytes));
+    let expected = read_back(&mut processed);
+
+    let changes: Vec<_> = processed
+        .iter_changes::<Four>(StorageId(0))
+        .unwrap()
+        .map(|change| change.unwrap())
+        .collect();
+    assert_eq!(changes.len(), expected.len());
+    for ((time, slice), (expected_time, data)) in changes.iter().zip(&expected) {
+        assert_eq!(time, expected_time);
+        assert_eq!(slice.packed(), &data[..]);
+        assert_eq!(slice.width(), 12);
+        let bits = slice.unpack();
+        assert_eq!(bits.len(), 12);
+        for (i, &bit) in bits.iter().enumerate() {
+            assert_eq!(slice.bit(i as u32), bit);
+            assert_eq!(bit, (data[i / 4] >> (i % 4 * 2)) & 0b11);
+        }
+    }
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "not two-logic")]
+fn typed_iteration_checks_the_type() {
+    let mut processed = ingest(8, 1, |i| value(i, 2));
+    let _ = processed.iter_changes::<Two>(StorageId(0));
+}
+
+#[test]
+fn combined_storages_change_together() {
+    let mut ingestor = Ingestor::new(IngestorOptions::new()).unwrap();
+    for (id, width) in [(0, 3), (1, 2)] {
+        ingestor.ingest_storage(Storage {
+            id: StorageId(id),
+            ty: StorageType::FourLogic,
+            width,
+            start: 0,
+        });
+    }
+    // Values are given one per bit, first bit first.
+    let changes: &[(u64, u32, &[u8])] = &[
+        (0, 0, &[1, 0, 0]),
+        (5, 1, &[2, 2]),
+        (10, 0, &[0, 1, 0]),
+        (10, 1, &[1, 1]),
+        (10, 1, &[0, 1]),
+        (20, 0, &[3, 3, 3]),
+        (30, 1, &[1, 0]),
+    ];
+    let mut packed = vec![];
+    for &(time, storage, values) in changes {
+        ingestor.ingest_timestep(Timesteps(time));
+        logic::pack_four(values, &mut packed);
+        ingestor
+            .ingest_value(Value {
+                storage_id: StorageId(storage),
+                data: &packed,
+            })
+            .unwrap();
+    }
+    let mut processed = ingestor.finish().unwrap();
+
+    let combined: Vec<_> = processed
+        .iter_combined_changes::<Four>(&[StorageId(0), StorageId(1)])
+        .unwrap()
+        .map(|change| {
+            let (time, slice) = change.unwrap();
+            assert_eq!(slice.width(), 5);
+            (time.0, slice.unpack())
+        })
+        .collect();
+    assert_eq!(
+        combined,
+        [
+            (5, vec![1, 0, 0, 2, 2]),
+            (10, vec![0, 1, 0, 0, 1]),
+            (20, vec![3, 3, 3, 0, 1]),
+            (30, vec![3, 3, 3, 1, 0]),
+        ]
+    );
+}
@@ -0,0 +1,79 @@
+//! Builds the small waveforms that tests read back, so each test only has to say what's in them.
+
+// Each test file uses some of these, and is compiled on its own.
+#![allow(dead_code)]
+
+use ligeia_core::{
+    meta::{Scope, ScopeId, Signedness, Storage, StorageId, StorageType, Timesteps, Var, VarKind},
+    Ingestor, IngestorOptions, Processed, Value,
+};
+
+/// Ingest scopes as `(id, parent, name)`.
+pub fn scopes(ingestor: &mut Ingestor, scopes: &[(u32, u32, &str)]) {
+    for &(id, parent, name) in scopes {
+        let name = ingestor.intern(name);
+        ingestor.ingest_scope(Scope {
+            id: ScopeId(id),
+            parent: ScopeId(parent),
+            name,
+        });
+    }
+}
+
+/// Ingest storages as `(id, type, width)`.
+pub fn storages(ingestor: &mut Ingestor, storages: &[(u32, StorageType, u32)]) {
+    for &(id, ty, width) in storages {
+        ingestor.ingest_storage(Storage {
+            id: StorageId(id),
+            ty,
+            width,
+            start: 0,
+        });
+    }
+}
+
+/// Ingest a variable called `name` in the scope with id `scope`.
+pub fn var(ingestor: &mut Ingestor, scope: u32, name: &str, kind: VarKind) {
+    let name = ingestor.intern(name);
+    ingestor.ingest_var(Var {
+        name,
+        scope_id: ScopeId(scope),
+        kind,
+        source: None,
+    });
+}
+
+/// An unsigned integer over `storages`, declared as `[msb_index:lsb_index]`.
+pub fn integer(storages: &[u32], msb_index: u32, lsb_index: u32) -> VarKind {
+    VarKind::Integer {
+        storages: storages.iter().map(|&id| StorageId(id)).collect(),
+        msb_index,
+        lsb_index,
+        signedness: Signedness::Unsigned,
+    }
+}
+
+/// Ingest changes as `(time, storage, value)`, in order of time.
+pub fn changes(ingestor: &mut Ingestor, changes: &[(u64, u32, &[u8])]) {
+    for &(time, storage, data) in changes {
+        ingestor.ingest_timestep(Timesteps(time));
+        ingestor
+            .ingest_value(Value {
+                storage_id: StorageId(storage),
+                data,
+            })
+            .unwrap();
+    }
+}
+
+/// A waveform of `storages` and nothing else, changing at `(time, storage, value)`.
+pub fn waveform(
+    options: IngestorOptions,
+    storages: &[(u32, StorageType, u32)],
+    changes: &[(u64, u32, &[u8])],
+) -> Processed {
+    let mut ingestor = Ingestor::new(options).unwrap();
+    self::storages(&mut ingestor, storages);
+    self::changes(&mut ingestor, changes);
+    ingestor.finish().unwrap()
+}
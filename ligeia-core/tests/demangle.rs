@@ -0,0 +1,106 @@
+//! Tidies up escaped identifiers and generate loop names for display.
+
+use ligeia_core::{
+    demangle::{split_path, Demangling},
+    meta::{Scope, ScopeId, Var, VarKind},
+    search::Glob,
+    Ingestor, IngestorOptions,
+};
+
+#[test]
+fn rules() {
+    let all = Demangling::default();
+    assert_eq!(all.apply(r"\foo.bar "), "foo.bar");
+    assert_eq!(all.apply(r"\genblk1[3] "), "genblk1[3]");
+    assert_eq!(all.apply("genblk1(3)"), "genblk1[3]");
+    assert_eq!(all.apply("mem(2)(15)"), "mem[2][15]");
+    assert_eq!(all.apply("clk"), "clk");
+    // Only numbers are generate indices.
+    assert_eq!(all.apply("f(x)"), "f(x)");
+    assert_eq!(all.apply("(3)"), "(3)");
+
+    let escaped = Demangling {
+        generate_indices: false,
+        ..Demangling::default()
+    };
+    assert_eq!(escaped.apply(r"\genblk1(3) "), "genblk1(3)");
+    assert_eq!(Demangling::NONE.apply(r"\foo.bar"), r"\foo.bar");
+}
+
+#[test]
+fn paths() {
+    assert_eq!(split_path(r"top.foo\.bar.q"), ["top", "foo.bar", "q"]);
+    assert_eq!(split_path(r"top.a\\b"), ["top", r"a\b"]);
+    assert_eq!(split_path("top"), ["top"]);
+}
+
+#[test]
+fn display_names() {
+    let mut ingestor = Ingestor::new(IngestorOptions::new()).unwrap();
+    for (id, parent, name) in [(1, 0, "top"), (2, 1, "genblk1(3)"), (3, 1, r"\foo.bar")] {
+        let name = ingestor.intern(name);
+        ingestor.ingest_scope(Scope {
+            id: ScopeId(id),
+            parent: ScopeId(parent),
+            name,
+        });
+    }
+    for (scope, name) in [(2, "q"), (3, r"\d+ ")] {
+        let name = ingestor.intern(name);
+        ingestor.ingest_var(Var {
+            name,
+            scope_id: ScopeId(scope),
+            kind: VarKind::None,
+            source: None,
+        });
+    }
+    let mut processed = ingestor.finish().unwrap();
+
+    let paths = |processed: &ligeia_core::Processed| -> Vec<(String, String)> {
+        processed
+            .vars()
+            .iter()
+            .map(|var| (processed.var_path(var), processed.display_path(var)))
+            .collect()
+    };
+    assert_eq!(
+        paths(&processed),
+        [
+            (
+                "top.genblk1(3).q".to_string(),
+                "top.genblk1[3].q".to_string()
+            ),
+            (
+                r"top.\foo.bar.\d+ ".to_string(),
+                r"top.foo\.bar.d+".to_string()
+            ),
+        ]
+    );
+    assert_eq!(
+        split_path(&paths(&processed)[1].1),
+        ["top", "foo.bar", "d+"]
+    );
+
+    // Searches still go by the raw names.
+    let glob = Glob::new(r"top.genblk1(3).*").unwrap();
+    assert_eq!(processed.find_vars(&glob).len(), 1);
+
+    processed.set_demangling(Demangling::NONE);
+    let var = &processed.vars()[0];
+    assert_eq!(processed.display_name(var.name), "q");
+    assert_eq!(processed.display_path(var), "top.genblk1(3).q");
+}
+
+#[test]
+fn demangling_option() {
+    let options = IngestorOptions::new().with_demangling(Demangling::NONE);
+    let mut ingestor = Ingestor::new(options).unwrap();
+    let name = ingestor.intern("genblk1(3)");
+    ingestor.ingest_scope(Scope {
+        id: ScopeId(1),
+        parent: ScopeId(0),
+        name,
+    });
+    let processed = ingestor.finish().unwrap();
+    assert_eq!(processed.display_name(name), "genblk1(3)");
+}
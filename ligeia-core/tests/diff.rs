@@ -0,0 +1,199 @@
+mod common;
+
+use ligeia_core::{
+    diff::{diff_storages, diff_within, Mismatch},
+    meta::{StorageId, StorageType, Timesteps},
+    Error, IngestorOptions, Processed,
+};
+
+/// A waveform with a storage for each of `storages`, changing at `(time, storage, value)`.
+fn waveform(
+    femtoseconds_per_timestep: u128,
+    storages: &[(StorageType, u32)],
+    changes: &[(u64, u32, &[u8])],
+) -> Processed {
+    let storages: Vec<_> = storages
+        .iter()
+        .enumerate()
+        .map(|(id, &(ty, width))| (id as u32, ty, width))
+        .collect();
+    let options = IngestorOptions::new().with_timescale(femtoseconds_per_timestep);
+    common::waveform(options, &storages, changes)
+}
+
+fn bit(changes: &[(u64, u8)]) -> Processed {
+    let changes: Vec<_> = changes
+        .iter()
+        .map(|(time, value)| (*time, 0, std::slice::from_ref(value)))
+        .collect();
+    waveform(1, &[(StorageType::FourLogic, 1)], &changes)
+}
+
+fn mismatch(start: u64, end: Option<u64>) -> Mismatch {
+    Mismatch {
+        start: Timesteps(start),
+        end: end.map(Timesteps),
+    }
+}
+
+#[test]
+fn identical_storages_match() {
+    let mut a = bit(&[(0, 0), (10, 1), (20, 0)]);
+    let mut b = bit(&[(0, 0), (10, 1), (20, 0)]);
+
+    let report = diff_storages(&mut a, StorageId(0), &mut b, StorageId(0)).unwrap();
+    assert!(report.is_match());
+    assert_eq!(report.first_divergence(), None);
+}
+
+#[test]
+fn repeated_values_still_match() {
+    // `b` writes the same value again, and twice at once, but never has a different one.
+    let mut a = bit(&[(0, 0), (10, 1)]);
+    let mut b = bit(&[(0, 0), (5, 0), (10, 0), (10, 1), (15, 1)]);
+
+    let report = diff_storages(&mut a, StorageId(0), &mut b, StorageId(0)).unwrap();
+    assert!(report.is_match(), "{:?}", report);
+}
+
+#[test]
+fn glitches_and_late_changes_mismatch() {
+    let mut a = bit(&[(0, 0), (10, 1), (40, 0)]);
+    let mut b = bit(&[(0, 0), (10, 1), (20, 0), (25, 1), (50, 0)]);
+
+    let report = diff_storages(&mut a, StorageId(0), &mut b, StorageId(0)).unwrap();
+    assert_eq!(
+        report.mismatches,
+        vec![mismatch(20, Some(25)), mismatch(40, Some(50))]
+    );
+    assert_eq!(report.first_divergence(), Some(Timesteps(20)));
+}
+
+#[test]
+fn missing_values_mismatch_until_the_end() {
+    let mut a = bit(&[(0, 0), (10, 1)]);
+    let mut b = bit(&[(5, 0), (10, 0)]);
+
+    let report = diff_storages(&mut a, StorageId(0), &mut b, StorageId(0)).unwrap();
+    assert_eq!(
+        report.mismatches,
+        vec![mismatch(0, Some(5)), mismatch(10, None)]
+    );
+}
+
+#[test]
+fn two_and_four_logic_compare_by_value() {
+    // 0b1010 either way.
+    let mut a = waveform(1, &[(StorageType::TwoLogic, 4)], &[(0, 0, &[0b1010])]);
+    let mut b = waveform(
+        1,
+        &[(StorageType::FourLogic, 4)],
+        &[(0, 0, &[0b01_00_01_00])],
+    );
+
+    let report = diff_storages(&mut a, StorageId(0), &mut b, StorageId(0)).unwrap();
+    assert!(report.is_match(), "{:?}", report);
+}
+
+#[test]
+fn timescales_are_converted_to_the_first() {
+    // Nanoseconds and picoseconds.
+    let mut a = waveform(
+        1_000_000,
+        &[(StorageType::FourLogic, 1)],
+        &[(0, 0, &[0]), (10, 0, &[1])],
+    );
+    let mut b = waveform(
+        1_000,
+        &[(StorageType::FourLogic, 1)],
+        &[(0, 0, &[0]), (10_000, 0, &[1]), (12_500, 0, &[0])],
+    );
+
+    let report = diff_storages(&mut a, StorageId(0), &mut b, StorageId(0)).unwrap();
+    assert_eq!(report.mismatches, vec![mismatch(12, None)]);
+}
+
+#[test]
+fn changes_collapsing_onto_a_timestep_are_counted() {
+    let mut a = waveform(
+        1_000_000,
+        &[(StorageType::FourLogic, 1)],
+        &[(0, 0, &[0]), (10, 0, &[1])],
+    );
+    // A glitch within a nanosecond, which is gone in nanoseconds.
+    let mut b = waveform(
+        1_000,
+        &[(StorageType::FourLogic, 1)],
+        &[
+            (0, 0, &[0]),
+            (10_000, 0, &[1]),
+            (10_200, 0, &[0]),
+            (10_400, 0, &[1]),
+        ],
+    );
+
+    let report = diff_storages(&mut a, StorageId(0), &mut b, StorageId(0)).unwrap();
+    assert!(report.is_match(), "{:?}", report);
+    assert_eq!(report.collapsed, 2);
+
+    let report = diff_storages(&mut b, StorageId(0), &mut a, StorageId(0)).unwrap();
+    assert_eq!(report.collapsed, 0);
+}
+
+#[test]
+fn strings_compare_by_text() {
+    // The strings are interned in a different order, so their indices differ.
+    let mut a = waveform(
+        1,
+        &[(StorageType::Utf8, 0)],
+        &[(0, 0, b"idle"), (10, 0, b"busy")],
+    );
+    let mut b = waveform(
+        1,
+        &[(StorageType::Utf8, 0), (StorageType::Utf8, 0)],
+        &[(0, 1, b"busy"), (0, 0, b"idle"), (10, 0, b"busy")],
+    );
+
+    let report = diff_storages(&mut a, StorageId(0), &mut b, StorageId(0)).unwrap();
+    assert!(report.is_match(), "{:?}", report);
+}
+
+#[test]
+fn storages_in_one_waveform() {
+    let mut processed = waveform(
+        1,
+        &[(StorageType::FourLogic, 1), (StorageType::TwoLogic, 1)],
+        &[(0, 0, &[0]), (0, 1, &[0]), (10, 0, &[1]), (12, 1, &[1])],
+    );
+
+    let report = diff_within(&mut processed, StorageId(0), StorageId(1)).unwrap();
+    assert_eq!(report.mismatches, vec![mismatch(10, Some(12))]);
+}
+
+#[test]
+fn different_widths_are_incomparable() {
+    let mut a = waveform(1, &[(StorageType::FourLogic, 1)], &[]);
+    let mut b = waveform(1, &[(StorageType::FourLogic, 2)], &[]);
+
+    assert!(matches!(
+        diff_storages(&mut a, StorageId(0), &mut b, StorageId(0)),
+        Err(Error::Incomparable(StorageId(0), StorageId(0)))
+    ));
+}
+
+#[test]
+fn events_compare_by_occurrence() {
+    let changes: &[(u64, u32, &[u8])] = &[(5, 0, &[]), (10, 0, &[]), (11, 0, &[]), (20, 0, &[])];
+    let mut a = waveform(1, &[(StorageType::Event, 0)], changes);
+    let mut b = waveform(
+        1,
+        &[(StorageType::Event, 0)],
+        &[(5, 0, &[]), (5, 0, &[]), (20, 0, &[]), (30, 0, &[])],
+    );
+
+    let report = diff_storages(&mut a, StorageId(0), &mut b, StorageId(0)).unwrap();
+    assert_eq!(
+        report.mismatches,
+        vec![mismatch(10, Some(12)), mismatch(30, Some(31))]
+    );
+}
@@ -0,0 +1,129 @@
+//! Unknown ids from a malformed file or a buggy frontend are errors, not panics, and warnings
+//! are errors when asked.
+
+mod common;
+
+use ligeia_core::{
+    meta::{ScopeId, StorageId, StorageType, Timesteps},
+    Error, Ingestor, IngestorOptions, Processed, Value, Warning,
+};
+
+fn ingest() -> Processed {
+    let mut ingestor = Ingestor::new(IngestorOptions::new()).unwrap();
+    common::scopes(&mut ingestor, &[(1, 0, "top")]);
+    common::storages(&mut ingestor, &[(0, StorageType::TwoLogic, 1)]);
+
+    ingestor.ingest_timestep(Timesteps(0));
+    let unknown = ingestor.ingest_value(Value {
+        storage_id: StorageId(7),
+        data: &[1],
+    });
+    assert!(matches!(unknown, Err(Error::UnknownStorage(StorageId(7)))));
+
+    ingestor.finish().unwrap()
+}
+
+#[test]
+fn unknown_storage() {
+    let mut processed = ingest();
+    let unknown = StorageId(7);
+
+    assert!(matches!(
+        processed.storage(unknown),
+        Err(Error::UnknownStorage(_))
+    ));
+    assert!(matches!(
+        processed.change_count(unknown),
+        Err(Error::UnknownStorage(_))
+    ));
+    assert!(matches!(
+        processed.load_storage(unknown, |_, _| {}),
+        Err(Error::UnknownStorage(_))
+    ));
+    assert!(matches!(
+        processed.value_at(unknown, Timesteps(0)),
+        Err(Error::UnknownStorage(_))
+    ));
+    assert!(matches!(
+        processed.snapshot(&[StorageId(0), unknown], Timesteps(0)),
+        Err(Error::UnknownStorage(_))
+    ));
+
+    assert_eq!(processed.change_count(StorageId(0)).unwrap(), 0);
+}
+
+#[test]
+fn unknown_scope() {
+    let processed = ingest();
+
+    assert!(processed.within_scope(ScopeId::ROOT).is_ok());
+    assert_eq!(processed.within_scope(ScopeId(1)).unwrap().0.len(), 0);
+    assert!(matches!(
+        processed.within_scope(ScopeId(2)),
+        Err(Error::UnknownScope(ScopeId(2)))
+    ));
+}
+
+#[test]
+fn strict_ingestion_rejects_long_values() {
+    let ingest = |options: IngestorOptions| {
+        let mut ingestor = Ingestor::new(options).unwrap();
+        common::storages(
+            &mut ingestor,
+            &[
+                (0, StorageType::FourLogic, 8),
+                (1, StorageType::Utf8, 0),
+                (2, StorageType::Event, 0),
+            ],
+        );
+        ingestor.ingest_timestep(Timesteps(0));
+        for (id, data) in [(0, &b"ab"[..]), (1, b"long text"), (2, b"1"), (0, b"abc")] {
+            ingestor.ingest_value(Value {
+                storage_id: StorageId(id),
+                data,
+            })?;
+        }
+        Ok::<_, Error>(())
+    };
+
+    ingest(IngestorOptions::new()).unwrap();
+    assert!(matches!(
+        ingest(IngestorOptions::new().with_strict()),
+        Err(Error::ValueTooLong(StorageId(0), 3, 2))
+    ));
+}
+
+#[test]
+fn warnings_as_errors() {
+    let ingest = |options: IngestorOptions| {
+        let mut ingestor = Ingestor::new(options.with_dedup()).unwrap();
+        common::storages(&mut ingestor, &[(0, StorageType::TwoLogic, 1)]);
+        ingestor.warn(Warning::Unsupported {
+            path: "top.r".to_string(),
+            kind: "real".to_string(),
+        });
+        common::changes(&mut ingestor, &[(0, 0, &[1]), (1, 0, &[1]), (2, 0, &[1])]);
+        ingestor.finish()
+    };
+
+    let processed = ingest(IngestorOptions::new()).unwrap();
+    assert_eq!(
+        processed.warnings(),
+        [
+            Warning::Unsupported {
+                path: "top.r".to_string(),
+                kind: "real".to_string(),
+            },
+            Warning::RepeatsDropped(2),
+        ]
+    );
+
+    match ingest(IngestorOptions::new().with_warnings_as_errors()) {
+        Err(e @ Error::Warnings(_)) => assert_eq!(
+            e.to_string(),
+            "warnings are errors, and `top.r` is a real, which isn't supported, so it was left \
+             out (and 1 more)"
+        ),
+        _ => panic!("warnings weren't errors"),
+    }
+}
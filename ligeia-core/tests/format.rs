@@ -0,0 +1,160 @@
+//! Formats values wider than any machine integer.
+
+use ligeia_core::{
+    bignum::BigUint,
+    format::{
+        format_decimal, format_time, format_values, format_values_with, FormatOptions, Radix,
+        Separators,
+    },
+};
+
+/// The bits of a number, least significant first, `width` long.
+fn bits(mut value: u128, width: usize) -> Vec<u8> {
+    (0..width)
+        .map(|_| {
+            let bit = (value & 1) as u8;
+            value >>= 1;
+            bit
+        })
+        .collect()
+}
+
+#[test]
+fn radixes() {
+    let values = bits(0x2a, 8);
+    assert_eq!(format_values(&values, Radix::Binary), "00101010");
+    assert_eq!(format_values(&values, Radix::Octal), "052");
+    assert_eq!(format_values(&values, Radix::Decimal), "42");
+    assert_eq!(format_values(&values, Radix::Hexadecimal), "2a");
+}
+
+#[test]
+fn unknowns() {
+    let mut values = bits(0x2a, 8);
+    values[0] = 2;
+    values[4..].copy_from_slice(&[3, 3, 3, 3]);
+    assert_eq!(format_values(&values, Radix::Hexadecimal), "zx");
+    assert_eq!(format_values(&values, Radix::Decimal), "x");
+    assert_eq!(format_values(&[3, 3, 3], Radix::Decimal), "z");
+}
+
+#[test]
+fn options() {
+    let values = bits(0x00de_adbe, 32);
+    let options = FormatOptions {
+        group: Some(4),
+        prefix: true,
+        trim_leading_zeros: false,
+        separator: None,
+    };
+    assert_eq!(
+        format_values_with(&values, Radix::Hexadecimal, options),
+        "0x00de_adbe"
+    );
+
+    let trimmed = FormatOptions {
+        trim_leading_zeros: true,
+        ..options
+    };
+    assert_eq!(
+        format_values_with(&values, Radix::Hexadecimal, trimmed),
+        "0xde_adbe"
+    );
+    assert_eq!(
+        format_values_with(&bits(5, 8), Radix::Binary, trimmed),
+        "0b101"
+    );
+    assert_eq!(
+        format_values_with(&bits(0, 8), Radix::Binary, trimmed),
+        "0b0"
+    );
+
+    // Decimal numbers are grouped too, but have no prefix.
+    let thousands = FormatOptions {
+        group: Some(3),
+        ..trimmed
+    };
+    assert_eq!(
+        format_values_with(&bits(1_234_567, 32), Radix::Decimal, thousands),
+        "1_234_567"
+    );
+
+    let spaced = FormatOptions {
+        separator: Some(' '),
+        ..thousands
+    };
+    assert_eq!(
+        format_values_with(&bits(1_234_567, 32), Radix::Decimal, spaced),
+        "1 234 567"
+    );
+
+    // Only zeros are trimmed, not unknown digits.
+    let mut values = bits(0x5, 12);
+    values[8..].copy_from_slice(&[3, 3, 3, 3]);
+    assert_eq!(
+        format_values_with(&values, Radix::Hexadecimal, trimmed),
+        "0xz05"
+    );
+}
+
+#[test]
+fn wide() {
+    // 2^200 + 12345
+    let mut values = bits(12345, 201);
+    values[200] = 1;
+
+    let hex = format_values(&values, Radix::Hexadecimal);
+    assert_eq!(hex.len(), 51);
+    assert_eq!(&hex[..1], "1");
+    assert!(hex.ends_with("3039"));
+
+    let decimal = "1606938044258990275541962092341162602522202993782792835313721";
+    assert_eq!(format_values(&values, Radix::Decimal), decimal);
+
+    let number = BigUint::from_str_radix(decimal, 10).unwrap();
+    assert_eq!(number.bits(), 201);
+    assert_eq!(number.to_str_radix(16), hex.trim_start_matches('0'));
+    assert_eq!(number.to_string(), decimal);
+    assert!(number > BigUint::from(u64::MAX));
+    assert_eq!(BigUint::from_str_radix("0", 10).unwrap().to_string(), "0");
+}
+
+#[test]
+fn locales() {
+    let english = Separators::from_locale("en_US.UTF-8");
+    assert_eq!(english.decimal, '.');
+    assert_eq!(english.grouping, Some(','));
+    let german = Separators::from_locale("de_DE.UTF-8");
+    assert_eq!((german.decimal, german.grouping), (',', Some('.')));
+    let swiss = Separators::from_locale("de_CH");
+    assert_eq!((swiss.decimal, swiss.grouping), ('.', Some('\'')));
+    assert_eq!(Separators::from_locale("fr").grouping, Some('\u{a0}'));
+    assert_eq!(Separators::from_locale("C"), Separators::default());
+    assert_eq!(Separators::from_locale("tlh_QO"), Separators::default());
+}
+
+#[test]
+fn decimals() {
+    let plain = Separators::default();
+    let german = Separators::from_locale("de_DE");
+    assert_eq!(format_decimal(1234567.891, 2, plain), "1234567.89");
+    assert_eq!(format_decimal(1234567.891, 2, german), "1.234.567,89");
+    assert_eq!(format_decimal(-1234.0, 0, german), "-1.234");
+    assert_eq!(format_decimal(999.0, 1, german), "999,0");
+    // Nothing left to be negative after rounding.
+    assert_eq!(format_decimal(-0.001, 1, plain), "0.0");
+}
+
+#[test]
+fn times() {
+    let plain = Separators::default();
+    assert_eq!(format_time(0, plain), "0 fs");
+    assert_eq!(format_time(999, plain), "999 fs");
+    assert_eq!(format_time(1_500_000, plain), "1.5 ns");
+    assert_eq!(format_time(20_000_000_000, plain), "20 us");
+    assert_eq!(format_time(1_000_001, plain), "1.000001 ns");
+
+    let german = Separators::from_locale("de_DE");
+    assert_eq!(format_time(1_500_000, german), "1,5 ns");
+    assert_eq!(format_time(1_234_500_000_000_000_000, german), "1.234,5 s");
+}
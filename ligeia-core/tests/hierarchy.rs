@@ -0,0 +1,399 @@
+//! Pages through the scopes and variables within a scope.
+
+mod common;
+
+use ligeia_core::{
+    hierarchy::{Child, ChildCounts, VarOrder, VarQuery, WidthFilter},
+    meta::{ScopeId, StorageId, StorageType, Timesteps, VarKind},
+    names::{natural_cmp, natural_cmp_paths},
+    Error, Ingestor, IngestorOptions, Processed,
+};
+
+/// `top` holds scopes `c`, `a` and `b`, declared in that order but with ascending ids in
+/// alphabetical order, and then `vars` variables. `a` holds a single variable.
+fn ingest(vars: usize) -> Processed {
+    let mut ingestor = Ingestor::new(IngestorOptions::new()).unwrap();
+    common::scopes(
+        &mut ingestor,
+        &[(1, 0, "top"), (4, 1, "c"), (2, 1, "a"), (3, 1, "b")],
+    );
+    for i in 0..vars {
+        common::var(&mut ingestor, 1, &format!("v{}", i), VarKind::None);
+    }
+    common::var(&mut ingestor, 2, "inner", VarKind::None);
+
+    ingestor.finish().unwrap()
+}
+
+fn name(processed: &Processed, child: Child) -> String {
+    match child {
+        Child::Scope(scope) => format!("scope {}", processed.name(scope.name)),
+        Child::Array(array) => format!("array {}", array.name()),
+        Child::Var(var) => processed.name(var.name).to_string(),
+    }
+}
+
+#[test]
+fn counts() {
+    let processed = ingest(5);
+    assert_eq!(
+        processed.child_counts(ScopeId::ROOT).unwrap(),
+        ChildCounts { scopes: 1, vars: 0 }
+    );
+    assert_eq!(processed.child_counts(ScopeId(1)).unwrap().total(), 8);
+    assert_eq!(
+        processed.child_counts(ScopeId(2)).unwrap(),
+        ChildCounts { scopes: 0, vars: 1 }
+    );
+    assert_eq!(processed.child_counts(ScopeId(3)).unwrap().total(), 0);
+}
+
+#[test]
+fn pages_cover_everything_once() {
+    let processed = ingest(20);
+    let (scopes, vars) = processed.within_scope(ScopeId(1)).unwrap();
+    let expected: Vec<String> = scopes
+        .into_iter()
+        .map(|scope| name(&processed, Child::Scope(scope)))
+        .chain(
+            vars.into_iter()
+                .map(|var| name(&processed, Child::Var(var))),
+        )
+        .collect();
+    assert_eq!(expected[..4], ["scope a", "scope b", "scope c", "v0"]);
+
+    for limit in 1..=expected.len() + 1 {
+        let mut paged = vec![];
+        let mut offset = 0;
+        loop {
+            let page = processed.children_of(ScopeId(1), offset, limit).unwrap();
+            assert!(page.len() <= limit);
+            if page.is_empty() {
+                break;
+            }
+            offset += page.len();
+            paged.extend(page.into_iter().map(|child| name(&processed, child)));
+        }
+        assert_eq!(paged, expected, "pages of {}", limit);
+    }
+}
+
+#[test]
+fn out_of_range_pages() {
+    let processed = ingest(3);
+    assert!(processed
+        .children_of(ScopeId(1), 100, 10)
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        processed
+            .children_of(ScopeId(1), 2, usize::MAX)
+            .unwrap()
+            .len(),
+        4
+    );
+    assert!(processed.children_of(ScopeId(3), 0, 10).unwrap().is_empty());
+    assert!(matches!(
+        processed.children_of(ScopeId(9), 0, 10),
+        Err(Error::UnknownScope(ScopeId(9)))
+    ));
+    assert!(matches!(
+        processed.child_counts(ScopeId(9)),
+        Err(Error::UnknownScope(_))
+    ));
+}
+
+/// `top` holds, in declaration order:
+///
+/// - `clk`, a bit that changes at 0, 5, 10 and 15
+/// - `data`, 8 bits that change at 0
+/// - `addr`, 4 bits that change at 0 and 20
+/// - `msg`, a string that changes at 12
+fn ingest_signals() -> Processed {
+    let mut ingestor = Ingestor::new(IngestorOptions::new()).unwrap();
+    common::scopes(&mut ingestor, &[(1, 0, "top")]);
+
+    let signals = [
+        ("clk", StorageType::TwoLogic, 1),
+        ("data", StorageType::TwoLogic, 8),
+        ("addr", StorageType::TwoLogic, 4),
+        ("msg", StorageType::Utf8, 0),
+    ];
+    for (id, (name, ty, width)) in signals.into_iter().enumerate() {
+        let id = id as u32;
+        common::storages(&mut ingestor, &[(id, ty, width)]);
+        let kind = match ty {
+            StorageType::Utf8 => VarKind::Utf8 {
+                storage: StorageId(id),
+            },
+            _ => common::integer(&[id], width - 1, 0),
+        };
+        common::var(&mut ingestor, 1, name, kind);
+    }
+
+    let changes: &[(u64, u32, &[u8])] = &[
+        (0, 0, &[0]),
+        (0, 1, &[0]),
+        (0, 2, &[0]),
+        (5, 0, &[1]),
+        (10, 0, &[0]),
+        (12, 3, b"hello"),
+        (15, 0, &[1]),
+        (20, 2, &[3]),
+    ];
+    common::changes(&mut ingestor, changes);
+
+    ingestor.finish().unwrap()
+}
+
+fn query(processed: &mut Processed, query: VarQuery) -> Vec<String> {
+    let vars = processed.vars_within(ScopeId(1), &query).unwrap();
+    let names: Vec<_> = vars.iter().map(|var| var.name).collect();
+    names
+        .into_iter()
+        .map(|name| processed.name(name).to_string())
+        .collect()
+}
+
+#[test]
+fn sorts() {
+    let mut processed = ingest_signals();
+    let mut sorted = |order| {
+        query(
+            &mut processed,
+            VarQuery {
+                order,
+                ..VarQuery::default()
+            },
+        )
+    };
+
+    assert_eq!(
+        sorted(VarOrder::Declaration),
+        ["clk", "data", "addr", "msg"]
+    );
+    assert_eq!(sorted(VarOrder::Name), ["addr", "clk", "data", "msg"]);
+    // Strings have no width, so they go last.
+    assert_eq!(sorted(VarOrder::Width), ["data", "addr", "clk", "msg"]);
+    // `data` and `msg` tie, so they stay in declaration order.
+    assert_eq!(sorted(VarOrder::Activity), ["clk", "addr", "data", "msg"]);
+}
+
+#[test]
+fn filters() {
+    let mut processed = ingest_signals();
+    let mut filtered = |width, changed_in| {
+        query(
+            &mut processed,
+            VarQuery {
+                width,
+                changed_in,
+                ..VarQuery::default()
+            },
+        )
+    };
+
+    assert_eq!(filtered(WidthFilter::Bits, None), ["clk"]);
+    assert_eq!(filtered(WidthFilter::Buses, None), ["data", "addr"]);
+    assert_eq!(
+        filtered(WidthFilter::Any, Some(Timesteps(11)..Timesteps(15))),
+        ["msg"]
+    );
+    // Changes right at the start of the range count, and ones right at the end don't.
+    assert_eq!(
+        filtered(WidthFilter::Any, Some(Timesteps(15)..Timesteps(20))),
+        ["clk"]
+    );
+    assert_eq!(
+        filtered(WidthFilter::Buses, Some(Timesteps(1)..Timesteps(100))),
+        ["addr"]
+    );
+    assert!(filtered(WidthFilter::Any, Some(Timesteps(30)..Timesteps(40))).is_empty());
+}
+
+#[test]
+fn natural_order() {
+    use std::cmp::Ordering;
+
+    assert_eq!(natural_cmp("data[2]", "data[10]"), Ordering::Less);
+    assert_eq!(natural_cmp("lane10", "lane9"), Ordering::Greater);
+    assert_eq!(natural_cmp("a1b2", "a1b10"), Ordering::Less);
+    assert_eq!(natural_cmp("x01", "x1"), Ordering::Greater);
+    assert_eq!(natural_cmp("x1", "x1"), Ordering::Equal);
+    assert_eq!(natural_cmp("abc", "abd"), Ordering::Less);
+    assert_eq!(natural_cmp("q", "q0"), Ordering::Less);
+    assert_eq!(
+        natural_cmp_paths(&["top", "u2", "q"], &["top", "u10"]),
+        Ordering::Less
+    );
+
+    let mut ingestor = Ingestor::new(IngestorOptions::new()).unwrap();
+    common::scopes(
+        &mut ingestor,
+        &[
+            (1, 0, "top"),
+            (2, 1, "lane10"),
+            (3, 1, "lane2"),
+            (4, 1, "lane1"),
+        ],
+    );
+    for name in ["data[10]", "data[2]", "data[1]"] {
+        common::var(&mut ingestor, 1, name, VarKind::None);
+    }
+    let mut processed = ingestor.finish().unwrap();
+
+    let children: Vec<_> = processed
+        .children_of(ScopeId(1), 0, 3)
+        .unwrap()
+        .into_iter()
+        .map(|child| name(&processed, child))
+        .collect();
+    assert_eq!(children, ["scope lane1", "scope lane2", "scope lane10"]);
+
+    let query = VarQuery {
+        order: VarOrder::Name,
+        ..VarQuery::default()
+    };
+    let vars = processed.vars_within(ScopeId(1), &query).unwrap();
+    let names: Vec<_> = vars.iter().map(|var| var.name).collect();
+    let names: Vec<_> = names.into_iter().map(|name| processed.name(name)).collect();
+    assert_eq!(names, ["data[1]", "data[2]", "data[10]"]);
+}
+
+#[test]
+fn across_instances() {
+    let mut ingestor = Ingestor::new(IngestorOptions::new()).unwrap();
+    common::scopes(
+        &mut ingestor,
+        &[
+            (1, 0, "top"),
+            (2, 1, "core0"),
+            (3, 2, "alu"),
+            (4, 2, "fpu"),
+            (5, 1, "core1"),
+            (6, 5, "alu"),
+            (7, 1, "core[2]"),
+            (8, 1, "dma"),
+            (9, 8, "alu"),
+        ],
+    );
+    for scope in [3, 4, 6, 9] {
+        common::var(&mut ingestor, scope, "result", VarKind::None);
+    }
+    let processed = ingestor.finish().unwrap();
+
+    let var = &processed.vars()[0];
+    let across = |instance| {
+        processed
+            .across_instances(var, instance)
+            .map(|(index, vars)| {
+                let paths: Vec<_> = vars.iter().map(|var| processed.var_path(var)).collect();
+                (index, paths)
+            })
+    };
+
+    // `fpu` isn't an instance like `alu`, and `core[2]` doesn't have an `alu`.
+    assert_eq!(
+        across(None),
+        Some((
+            1,
+            vec![
+                "top.core0.alu.result".to_string(),
+                "top.core1.alu.result".to_string()
+            ]
+        ))
+    );
+    assert_eq!(
+        across(Some(2)),
+        Some((2, vec!["top.core0.alu.result".to_string()]))
+    );
+    assert_eq!(across(Some(3)), None);
+}
+
+#[test]
+fn arrays() {
+    let mut ingestor = Ingestor::new(IngestorOptions::new()).unwrap();
+    common::scopes(
+        &mut ingestor,
+        &[
+            (1, 0, "top"),
+            (2, 1, "genblk(1)"),
+            (3, 1, "u0"),
+            (4, 1, "genblk(0)"),
+        ],
+    );
+    for name in ["mem[2]", "clk", "mem[0]", "mem[10]", "x[3]", "mem[1]"] {
+        common::var(&mut ingestor, 1, name, VarKind::None);
+    }
+    let processed = ingestor.finish().unwrap();
+
+    let children: Vec<_> = processed
+        .children_of(ScopeId(1), 0, usize::MAX)
+        .unwrap()
+        .into_iter()
+        .map(|child| name(&processed, child))
+        .collect();
+    assert_eq!(
+        children,
+        [
+            "scope u0",
+            "array genblk(0:1)",
+            "array mem[0:10]",
+            "clk",
+            "x[3]"
+        ]
+    );
+    assert_eq!(
+        processed.child_counts(ScopeId(1)).unwrap(),
+        ChildCounts { scopes: 3, vars: 2 }
+    );
+    // Paging a child at a time finds the same ones.
+    let paged: Vec<_> = (0..6)
+        .flat_map(|offset| processed.children_of(ScopeId(1), offset, 1).unwrap())
+        .map(|child| name(&processed, child))
+        .collect();
+    assert_eq!(paged, children);
+
+    // Everything is still there without grouping.
+    let (scopes, vars) = processed.within_scope(ScopeId(1)).unwrap();
+    assert_eq!((scopes.len(), vars.len()), (3, 6));
+
+    let mem = match processed.children_of(ScopeId(1), 2, 1).unwrap()[0] {
+        Child::Array(array) => array.clone(),
+        _ => panic!("expected an array"),
+    };
+    assert_eq!(mem.stem, "mem");
+    assert!(!mem.scopes);
+    assert_eq!((mem.range(), mem.elements()), ((0, 10), 4));
+    assert_eq!(processed.array(mem.id), Some(&mem));
+    assert_eq!(processed.array(ScopeId(1)), None);
+
+    let elements: Vec<_> = processed
+        .children_of(mem.id, 0, usize::MAX)
+        .unwrap()
+        .into_iter()
+        .map(|child| name(&processed, child))
+        .collect();
+    assert_eq!(elements, ["mem[0]", "mem[1]", "mem[2]", "mem[10]"]);
+
+    let element = processed.array_element(mem.id, 10).unwrap();
+    assert_eq!(
+        element.map(|child| name(&processed, child)).as_deref(),
+        Some("mem[10]")
+    );
+    assert!(processed.array_element(mem.id, 5).unwrap().is_none());
+    assert!(matches!(
+        processed.array_element(ScopeId(1), 0),
+        Err(Error::UnknownScope(ScopeId(1)))
+    ));
+
+    let genblk = match processed.children_of(ScopeId(1), 1, 1).unwrap()[0] {
+        Child::Array(array) => array.id,
+        _ => panic!("expected an array"),
+    };
+    let element = processed.array_element(genblk, 1).unwrap();
+    assert_eq!(
+        element.map(|child| name(&processed, child)).as_deref(),
+        Some("scope genblk(1)")
+    );
+}
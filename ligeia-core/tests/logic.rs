@@ -0,0 +1,184 @@
+//! Checks packing and unpacking against straightforward per-value implementations, at lengths
+//! on both sides of the vectorized chunk size.
+
+use ligeia_core::logic;
+
+fn random_values(len: usize, seed: u64, max: u8) -> Vec<u8> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) as u8 % (max + 1)
+        })
+        .collect()
+}
+
+fn reference_pack(values: &[u8], bits: usize) -> Vec<u8> {
+    let per_byte = 8 / bits;
+    let mut packed = vec![0; (values.len() + per_byte - 1) / per_byte];
+    for (i, &value) in values.iter().enumerate() {
+        packed[i / per_byte] |= value << ((i % per_byte) * bits);
+    }
+    packed
+}
+
+const LENGTHS: &[usize] = &[
+    0, 1, 3, 4, 5, 7, 8, 9, 15, 16, 17, 31, 32, 33, 64, 100, 1000,
+];
+
+#[test]
+fn two_logic() {
+    let mut packed = vec![];
+    let mut unpacked = vec![];
+
+    for &len in LENGTHS {
+        let values = random_values(len, len as u64, 1);
+
+        logic::pack_two(&values, &mut packed);
+        assert_eq!(packed, reference_pack(&values, 1), "len {}", len);
+
+        logic::unpack_two(&packed, len, &mut unpacked);
+        assert_eq!(unpacked, values, "len {}", len);
+    }
+}
+
+#[test]
+fn four_logic() {
+    let mut packed = vec![];
+    let mut unpacked = vec![];
+
+    for &len in LENGTHS {
+        let values = random_values(len, len as u64, 3);
+
+        logic::pack_four(&values, &mut packed);
+        assert_eq!(packed, reference_pack(&values, 2), "len {}", len);
+
+        logic::unpack_four(&packed, len, &mut unpacked);
+        assert_eq!(unpacked, values, "len {}", len);
+    }
+}
+
+#[test]
+fn unpack_fewer_than_packed() {
+    let values = random_values(40, 1, 3);
+    let mut packed = vec![];
+    let mut unpacked = vec![];
+
+    logic::pack_four(&values, &mut packed);
+    logic::unpack_four(&packed, 21, &mut unpacked);
+    assert_eq!(unpacked, values[..21]);
+}
+
+#[test]
+fn four_logic_ascii() {
+    let mut packed = vec![];
+
+    for &len in LENGTHS {
+        let values = random_values(len, len as u64 + 7, 3);
+        let chars: Vec<u8> = values
+            .iter()
+            .enumerate()
+            .map(|(i, &value)| match (value, i % 2 == 0) {
+                (0, _) => b'0',
+                (1, _) => b'1',
+                (2, true) => b'x',
+                (2, false) => b'X',
+                (_, true) => b'z',
+                (_, false) => b'Z',
+            })
+            .collect();
+
+        assert!(logic::pack_four_ascii(&chars, &mut packed), "len {}", len);
+        assert_eq!(packed, reference_pack(&values, 2), "len {}", len);
+
+        let reversed: Vec<u8> = chars.iter().rev().copied().collect();
+        assert!(
+            logic::pack_four_ascii_msb_first(&reversed, &mut packed),
+            "len {}",
+            len
+        );
+        assert_eq!(packed, reference_pack(&values, 2), "len {}", len);
+
+        for bad in [b'2', b'u', b'-', b' ', 0xff] {
+            for position in [0, len / 2, len.saturating_sub(1)] {
+                if len == 0 {
+                    continue;
+                }
+                let mut chars = chars.clone();
+                chars[position] = bad;
+                assert!(!logic::pack_four_ascii(&chars, &mut packed), "len {}", len);
+                assert!(
+                    !logic::pack_four_ascii_msb_first(&chars, &mut packed),
+                    "len {}",
+                    len
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn four_logic_numbers() {
+    use ligeia_core::{
+        bignum::BigUint,
+        logic::{Number, XzPolicy},
+    };
+
+    let mut packed = vec![];
+
+    // 0b1101, first value least significant.
+    logic::pack_four(&[1, 0, 1, 1], &mut packed);
+    for policy in [XzPolicy::Reject, XzPolicy::Zero, XzPolicy::Sticky] {
+        assert_eq!(
+            logic::four_to_u64(&packed, 4, policy),
+            Some(Number::Known(13))
+        );
+    }
+
+    logic::pack_four(&[1, 2, 1, 3], &mut packed);
+    assert_eq!(logic::four_to_u64(&packed, 4, XzPolicy::Reject), None);
+    assert_eq!(
+        logic::four_to_u64(&packed, 4, XzPolicy::Zero),
+        Some(Number::Known(5))
+    );
+    assert_eq!(
+        logic::four_to_u64(&packed, 4, XzPolicy::Sticky),
+        Some(Number::Unknown)
+    );
+
+    // Bit 64 only fits in a bigint, but leading zeros don't matter.
+    let mut values = vec![0; 100];
+    values[0] = 1;
+    logic::pack_four(&values, &mut packed);
+    assert_eq!(
+        logic::four_to_u64(&packed, 100, XzPolicy::Reject),
+        Some(Number::Known(1))
+    );
+    values[64] = 1;
+    logic::pack_four(&values, &mut packed);
+    assert_eq!(logic::four_to_u64(&packed, 100, XzPolicy::Reject), None);
+    assert_eq!(
+        logic::four_to_bigint(&packed, 100, XzPolicy::Reject),
+        Some(Number::Known(BigUint::from_limbs(vec![1, 1])))
+    );
+}
+
+#[test]
+fn nine_logic_strengths() {
+    use ligeia_core::logic::{Nine, Strength};
+
+    let values: Vec<u8> = (0..=9).collect();
+    assert_eq!(logic::format_nine(&values), "?ZhlWXHL10");
+
+    let levels: Vec<u8> = (0..=8)
+        .map(|value| Nine::from_u8(value).unwrap().level())
+        .collect();
+    assert_eq!(levels, [0, 1, 0, 1, 2, 2, 0, 1, 3]);
+
+    assert_eq!(Nine::WeakOne.strength(), Strength::Weak);
+    assert_eq!(Nine::UnknownZero.strength(), Strength::Unknown);
+    assert_eq!(Nine::HighImpedance.strength(), Strength::None);
+    assert_eq!(Nine::from_u8(9), None);
+}
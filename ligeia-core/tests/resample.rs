@@ -0,0 +1,162 @@
+use ligeia_core::{
+    clocks,
+    meta::{Storage, StorageId, StorageType, Timesteps},
+    resample::{edges, resample, sample_on_edges, Edge},
+    Error, Ingestor, IngestorOptions, Value,
+};
+
+fn changes(changes: &[(u64, char)]) -> Vec<(Timesteps, char)> {
+    changes
+        .iter()
+        .map(|&(time, value)| (Timesteps(time), value))
+        .collect()
+}
+
+fn samples(changes: Vec<(Timesteps, char)>, (start, end): (u64, u64), step: u64) -> String {
+    resample(changes, Timesteps(start)..Timesteps(end), step)
+        .map(|(_, value)| value.unwrap_or('-'))
+        .collect()
+}
+
+#[test]
+fn sample_and_hold() {
+    let changes = changes(&[(5, 'a'), (10, 'b'), (12, 'c'), (13, 'd'), (30, 'e')]);
+
+    let samples: Vec<_> = resample(changes.clone(), Timesteps(0)..Timesteps(31), 10).collect();
+    assert_eq!(
+        samples,
+        vec![
+            (Timesteps(0), None),
+            (Timesteps(10), Some('b')),
+            (Timesteps(20), Some('d')),
+            (Timesteps(30), Some('e')),
+        ]
+    );
+
+    // Changes between samples are skipped, and the last value is held past the end.
+    assert_eq!(self::samples(changes, (0, 50), 5), "-abdddeeee");
+}
+
+#[test]
+fn changes_before_the_start() {
+    let changes = changes(&[(0, 'a'), (3, 'b'), (7, 'c')]);
+    assert_eq!(samples(changes.clone(), (5, 10), 1), "bbccc");
+    assert_eq!(samples(changes, (5, 5), 1), "");
+}
+
+#[test]
+fn size_hint() {
+    let resampled = resample(changes(&[]), Timesteps(3)..Timesteps(24), 5);
+    assert_eq!(resampled.size_hint(), (5, Some(5)));
+    assert_eq!(resampled.count(), 5);
+
+    // Stops rather than overflowing.
+    let resampled = resample(
+        changes(&[]),
+        Timesteps(u64::MAX - 1)..Timesteps(u64::MAX),
+        2,
+    );
+    assert_eq!(resampled.count(), 1);
+}
+
+fn levels(levels: &[(u64, Option<bool>)]) -> Vec<(Timesteps, Option<bool>)> {
+    levels
+        .iter()
+        .map(|&(time, level)| (Timesteps(time), level))
+        .collect()
+}
+
+#[test]
+fn sample_on_clock_edges() {
+    let clock = levels(&[
+        (0, Some(false)),
+        (10, Some(true)),
+        (20, Some(false)),
+        (30, Some(true)),
+        (40, Some(false)),
+        (50, Some(true)),
+    ]);
+    let changes = changes(&[(3, 'a'), (10, 'b'), (25, 'c'), (26, 'd')]);
+
+    // A change at the same time as an edge isn't seen until the next one.
+    let samples: Vec<_> = sample_on_edges(clock.clone(), Edge::Rising, changes.clone()).collect();
+    assert_eq!(
+        samples,
+        vec![
+            (Timesteps(10), Some('a')),
+            (Timesteps(30), Some('d')),
+            (Timesteps(50), Some('d')),
+        ]
+    );
+
+    let times: Vec<_> = edges(clock.clone(), Edge::Falling).collect();
+    assert_eq!(times, vec![Timesteps(20), Timesteps(40)]);
+    let samples: Vec<_> = sample_on_edges(clock, Edge::Falling, changes).collect();
+    assert_eq!(
+        samples,
+        vec![(Timesteps(20), Some('b')), (Timesteps(40), Some('d'))]
+    );
+}
+
+#[test]
+fn unknown_clock_levels_are_not_edges() {
+    let clock = levels(&[
+        (0, None),
+        (10, Some(true)),
+        (20, Some(false)),
+        (30, None),
+        (40, Some(true)),
+        (50, Some(false)),
+        (60, Some(true)),
+    ]);
+    let samples: Vec<_> = sample_on_edges(clock, Edge::Rising, changes(&[(15, 'a')])).collect();
+    assert_eq!(samples, vec![(Timesteps(60), Some('a'))]);
+
+    let samples: Vec<_> =
+        sample_on_edges(levels(&[]), Edge::Rising, changes(&[(0, 'a')])).collect();
+    assert_eq!(samples, vec![]);
+}
+
+#[test]
+fn clock_levels() {
+    let mut ingestor = Ingestor::new(IngestorOptions::new()).unwrap();
+    for (id, width) in [(0, 1), (1, 4)] {
+        ingestor.ingest_storage(Storage {
+            id: StorageId(id),
+            ty: StorageType::FourLogic,
+            width,
+            start: 0,
+        });
+    }
+    // Four-state values are 0, 1, x, then z.
+    for (time, data) in [(0, 2), (5, 0), (10, 1), (15, 3), (20, 1)] {
+        ingestor.ingest_timestep(Timesteps(time));
+        ingestor
+            .ingest_value(Value {
+                storage_id: StorageId(0),
+                data: &[data],
+            })
+            .unwrap();
+    }
+    let mut processed = ingestor.finish().unwrap();
+
+    assert_eq!(
+        clocks::levels(&mut processed, StorageId(0)).unwrap(),
+        levels(&[
+            (0, None),
+            (5, Some(false)),
+            (10, Some(true)),
+            (15, None),
+            (20, Some(true)),
+        ])
+    );
+    assert_eq!(
+        clocks::levels_in_range(&mut processed, StorageId(0), Timesteps(12)..Timesteps(18))
+            .unwrap(),
+        levels(&[(10, Some(true)), (15, None)])
+    );
+    assert!(matches!(
+        clocks::levels(&mut processed, StorageId(1)),
+        Err(Error::NotOneBit(StorageId(1)))
+    ));
+}
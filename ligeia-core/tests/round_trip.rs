@@ -0,0 +1,194 @@
+//! Property tests that push randomly generated changes through an `Ingestor` and check that
+//! `Processed::load_storage` hands back exactly what went in.
+
+use ligeia_core::{
+    meta::{Storage, StorageId, StorageType, Timesteps},
+    Ingestor, IngestorOptions, Processed, Value,
+};
+use proptest::{collection::vec, prelude::*};
+
+/// Blocks aim for this many bytes, so the interesting change counts are around multiples of it.
+const BLOCK_TARGET: usize = 10 * 1024;
+
+/// Storage types whose changes are stored as given. Strings are interned, so what's read back is
+/// an index rather than the bytes that were ingested.
+fn storage_type() -> impl Strategy<Value = StorageType> {
+    prop_oneof![
+        Just(StorageType::TwoLogic),
+        Just(StorageType::FourLogic),
+        Just(StorageType::NineLogic),
+        Just(StorageType::Event),
+    ]
+}
+
+fn width() -> impl Strategy<Value = u32> {
+    prop_oneof![1..=64u32, 65..=2048u32]
+}
+
+fn bytes(ty: StorageType, width: u32) -> usize {
+    let width = width as usize;
+    match ty {
+        StorageType::TwoLogic => (width + 7) / 8,
+        StorageType::FourLogic => (width + 3) / 4,
+        StorageType::NineLogic => width,
+        StorageType::Event => 0,
+        StorageType::Utf8 => unreachable!(),
+    }
+}
+
+/// How many bits of a value are within the storage's width, if it's two-logic or four-logic.
+fn bits(ty: StorageType, width: u32) -> Option<u32> {
+    match ty {
+        StorageType::TwoLogic => Some(width),
+        StorageType::FourLogic => Some(width * 2),
+        _ => None,
+    }
+}
+
+/// Bits past the end of a storage read back as zero.
+fn mask(ty: StorageType, width: u32, data: &mut [u8]) {
+    if let (Some(last), Some(bits @ 1..=7)) = (data.last_mut(), bits(ty, width).map(|b| b % 8)) {
+        *last &= (1 << bits) - 1;
+    }
+}
+
+/// Mostly small steps forward, sometimes none at all, and sometimes anything, which can wrap
+/// around to earlier timestamps.
+fn delta() -> impl Strategy<Value = u64> {
+    prop_oneof![
+        3 => Just(0u64),
+        6 => 1..1000u64,
+        1 => any::<u64>(),
+    ]
+}
+
+/// A value for a storage `bytes` long. Usually it's exactly that long, but shorter values are
+/// zero-extended and longer ones truncated, so those are tried too.
+fn data(bytes: usize) -> impl Strategy<Value = Vec<u8>> {
+    prop_oneof![
+        4 => vec(any::<u8>(), bytes),
+        1 => vec(any::<u8>(), 0..=bytes + 2),
+    ]
+}
+
+/// A change to the storage at `storage`, `delta` timesteps after the one before it.
+#[derive(Debug, Clone)]
+struct Change {
+    storage: usize,
+    delta: u64,
+    data: Vec<u8>,
+}
+
+fn changes(bytes: Vec<usize>, max: usize) -> impl Strategy<Value = Vec<Change>> {
+    let change = (0..bytes.len()).prop_flat_map(move |storage| {
+        (delta(), data(bytes[storage])).prop_map(move |(delta, data)| Change {
+            storage,
+            delta,
+            data,
+        })
+    });
+    vec(change, 0..max)
+}
+
+/// Some storages and changes to them, interleaved.
+fn waveform() -> impl Strategy<Value = (Vec<(StorageType, u32)>, Vec<Change>)> {
+    vec((storage_type(), width()), 1..6).prop_flat_map(|storages| {
+        let bytes = storages
+            .iter()
+            .map(|&(ty, width)| bytes(ty, width))
+            .collect();
+        (Just(storages), changes(bytes, 400))
+    })
+}
+
+/// What a storage's changes should read back as, in order.
+type Expected = Vec<(Timesteps, Vec<u8>)>;
+
+/// Ingest `changes`, returning the result along with what each storage should read back as.
+fn ingest(storages: &[(StorageType, u32)], changes: &[Change]) -> (Processed, Vec<Expected>) {
+    let mut ingestor = Ingestor::new(IngestorOptions::new()).unwrap();
+    for (i, &(ty, width)) in storages.iter().enumerate() {
+        ingestor.ingest_storage(Storage {
+            id: StorageId(i as u32),
+            ty,
+            width,
+            start: 0,
+        });
+    }
+
+    let mut expected = vec![vec![]; storages.len()];
+    let mut timestamp = 0u64;
+    for change in changes {
+        timestamp = timestamp.wrapping_add(change.delta);
+        ingestor.ingest_timestep(Timesteps(timestamp));
+        ingestor
+            .ingest_value(Value {
+                storage_id: StorageId(change.storage as u32),
+                data: &change.data,
+            })
+            .unwrap();
+
+        let (ty, width) = storages[change.storage];
+        let mut data = change.data.clone();
+        data.resize(bytes(ty, width), 0);
+        mask(ty, width, &mut data);
+        expected[change.storage].push((Timesteps(timestamp), data));
+    }
+
+    (ingestor.finish().unwrap(), expected)
+}
+
+fn check(processed: &mut Processed, expected: &[Expected]) -> Result<(), TestCaseError> {
+    for (i, expected) in expected.iter().enumerate() {
+        let id = StorageId(i as u32);
+        let mut read = vec![];
+        processed
+            .load_storage(id, |timestamp, data| read.push((timestamp, data.to_vec())))
+            .unwrap();
+
+        prop_assert_eq!(&read, expected, "storage {}", i);
+        prop_assert_eq!(processed.change_count(id).unwrap(), expected.len() as u64);
+    }
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn interleaved_storages_round_trip((storages, changes) in waveform()) {
+        let (mut processed, expected) = ingest(&storages, &changes);
+        check(&mut processed, &expected)?;
+    }
+
+    #[test]
+    fn changes_around_block_boundaries(
+        (ty, width) in prop_oneof![
+            4 => (storage_type(), width()),
+            // Wider than a block, so each block holds one change.
+            1 => storage_type().prop_map(|ty| (ty, 8 * BLOCK_TARGET as u32 + 8)),
+        ],
+        blocks in 0..3usize,
+        offset in -2..=2isize,
+        seed in any::<u8>(),
+    ) {
+        // With every change 10 timesteps after the last, each timestamp delta is a single byte.
+        // A block starts with an 8-byte timestamp, and a change is only added if there's room for
+        // the largest possible one, with a 10-byte delta. Values narrower than a byte share the
+        // delta's byte.
+        let bytes = bytes(ty, width);
+        let per_block = match bits(ty, width) {
+            Some(1..=7) => BLOCK_TARGET - (8 + 11) + 1,
+            _ => BLOCK_TARGET.saturating_sub(8 + 10 + bytes) / (1 + bytes) + 1,
+        };
+        let count = (blocks * per_block) as isize + offset;
+
+        let changes: Vec<_> = (0..count.max(0) as usize)
+            .map(|i| Change {
+                storage: 0,
+                delta: if i == 0 { 0 } else { 10 },
+                data: (0..bytes).map(|j| (i * 31 + j) as u8 ^ seed).collect(),
+            })
+            .collect();
+        let (mut processed, expected) = ingest(&[(ty, width)], &changes);
+        check(&mut processed, &expected)?;
+    }
+}
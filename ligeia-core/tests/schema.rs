@@ -0,0 +1,163 @@
+//! Writes a waveform's metadata out and reads it back.
+
+mod common;
+
+use ligeia_core::{
+    meta::{
+        EnumValue, ScopeId, Signedness, SourceLocation, StorageId, StorageType, Timesteps, Var,
+        VarKind,
+    },
+    schema::{negotiate, Metadata, SchemaError, OLDEST_VERSION, VERSION},
+    Ingestor, IngestorOptions, Processed,
+};
+
+fn ingest() -> Processed {
+    let mut ingestor = Ingestor::new(IngestorOptions::new().with_timescale(1000)).unwrap();
+    common::scopes(&mut ingestor, &[(2, 1, "sub"), (1, 0, "top")]);
+    common::storages(
+        &mut ingestor,
+        &[(0, StorageType::FourLogic, 8), (1, StorageType::Utf8, 0)],
+    );
+
+    let vars = [
+        (
+            "count",
+            1,
+            VarKind::Integer {
+                storages: vec![StorageId(0)],
+                msb_index: 7,
+                lsb_index: 0,
+                signedness: Signedness::SignedTwosComplement,
+            },
+        ),
+        (
+            "state",
+            2,
+            VarKind::Enum {
+                storage: StorageId(0),
+                values: vec![EnumValue {
+                    name: "IDLE".to_string(),
+                    value: vec![false, true],
+                }],
+            },
+        ),
+        (
+            "message",
+            2,
+            VarKind::Utf8 {
+                storage: StorageId(1),
+            },
+        ),
+    ];
+    for (name, scope, kind) in vars {
+        let name = ingestor.intern(name);
+        ingestor.ingest_var(Var {
+            name,
+            scope_id: ScopeId(scope),
+            kind,
+            source: (scope == 1).then(|| SourceLocation {
+                file: "top.sv".to_string(),
+                line: 12,
+            }),
+        });
+    }
+
+    for t in 0..5000u64 {
+        common::changes(&mut ingestor, &[(t, 0, &[t as u8, 0])]);
+        if t % 2000 == 0 {
+            common::changes(&mut ingestor, &[(t, 1, format!("at {}", t).as_bytes())]);
+        }
+    }
+    ingestor.finish().unwrap()
+}
+
+fn written(processed: &Processed) -> Vec<u8> {
+    let mut bytes = vec![];
+    processed.write_metadata(&mut bytes, VERSION).unwrap();
+    bytes
+}
+
+#[test]
+fn round_trip() {
+    let processed = ingest();
+    let metadata = Metadata::read_from(&written(&processed)[..]).unwrap();
+
+    assert_eq!(metadata.version, VERSION);
+    assert_eq!(metadata.femtoseconds_per_timestep, 1000);
+    assert_eq!(metadata.time_bounds, processed.time_bounds());
+    assert_eq!(metadata.names.len(), processed.names().len());
+    assert_eq!(metadata.strings.len(), 3);
+
+    let scopes: Vec<_> = metadata
+        .scopes
+        .iter()
+        .map(|scope| (scope.id, scope.parent, metadata.names.get(scope.name)))
+        .collect();
+    assert_eq!(
+        scopes,
+        [
+            (ScopeId(1), ScopeId(0), "top"),
+            (ScopeId(2), ScopeId(1), "sub")
+        ]
+    );
+
+    // Nothing but `Debug` compares the rest, and it's the same for the same values.
+    assert_eq!(
+        format!("{:?}", metadata.vars),
+        format!("{:?}", processed.vars())
+    );
+    let storages: Vec<_> = [0, 1]
+        .into_iter()
+        .map(|id| processed.storage(StorageId(id)).unwrap())
+        .collect();
+    assert_eq!(
+        format!("{:?}", metadata.storages),
+        format!("{:?}", storages)
+    );
+
+    assert_eq!(metadata.blocks.len(), 2);
+    let counts = &metadata.blocks[0];
+    assert_eq!(counts.storage, StorageId(0));
+    assert_eq!(counts.bytes, 2);
+    assert_eq!(counts.changes, 5000);
+    assert!(counts.blocks.len() > 1);
+    assert_eq!(counts.blocks[0].start, Timesteps(0));
+    assert!(counts.blocks.iter().all(|block| block.summary.is_some()));
+    assert_eq!(metadata.blocks[1].changes, 3);
+}
+
+#[test]
+fn versions() {
+    assert_eq!(negotiate(OLDEST_VERSION..=VERSION + 5), Some(VERSION));
+    assert_eq!(negotiate(0..=OLDEST_VERSION), Some(OLDEST_VERSION));
+    assert_eq!(negotiate(VERSION + 1..=VERSION + 2), None);
+    assert_eq!(negotiate(0..=OLDEST_VERSION - 1), None);
+
+    let processed = ingest();
+    assert!(matches!(
+        processed.write_metadata(vec![], VERSION + 1),
+        Err(SchemaError::Version(_))
+    ));
+
+    let mut bytes = written(&processed);
+    bytes[4..8].copy_from_slice(&(VERSION + 1).to_le_bytes());
+    assert!(matches!(
+        Metadata::read_from(&bytes[..]),
+        Err(SchemaError::Version(v)) if v == VERSION + 1
+    ));
+}
+
+#[test]
+fn invalid() {
+    let bytes = written(&ingest());
+    assert!(matches!(
+        Metadata::read_from(&b"VCD0\x01\0\0\0"[..]),
+        Err(SchemaError::Magic)
+    ));
+    for len in [6, 40, bytes.len() - 1] {
+        assert!(matches!(
+            Metadata::read_from(&bytes[..len]),
+            Err(SchemaError::Io(_))
+        ));
+    }
+}
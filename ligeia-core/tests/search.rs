@@ -0,0 +1,178 @@
+//! Matches hierarchical names against glob patterns.
+
+mod common;
+
+use ligeia_core::{
+    meta::{StorageId, StorageType, Timesteps, VarKind},
+    search::{self, Glob, GlobError},
+    CrowdedScope, Ingestor, IngestorOptions, Value,
+};
+
+fn matches(pattern: &str, path: &str) -> bool {
+    Glob::new(pattern).unwrap().matches(path)
+}
+
+#[test]
+fn stars() {
+    assert!(matches("top.cpu.*", "top.cpu.pc"));
+    assert!(!matches("top.cpu.*", "top.cpu.alu.carry"));
+    assert!(matches("top.**", "top.cpu.alu.carry"));
+    assert!(matches("top.**.carry", "top.cpu.alu.carry"));
+    assert!(matches("**valid", "top.fetch.valid"));
+    assert!(matches("*", "top"));
+    assert!(!matches("*", "top.clk"));
+    assert!(matches("top.*_en", "top.wr_en"));
+    assert!(matches("top.*.*", "top.a.b"));
+    assert!(!matches("top.*.*", "top.a"));
+    assert!(matches("top.cpu*", "top.cpu"));
+}
+
+#[test]
+fn single_characters() {
+    assert!(matches("top.irq?", "top.irq3"));
+    assert!(!matches("top.irq?", "top.irq"));
+    assert!(!matches("top?clk", "top.clk"));
+    assert!(matches("top.irq_[0-3]", "top.irq_2"));
+    assert!(!matches("top.irq_[0-3]", "top.irq_4"));
+    assert!(matches("top.irq_[!0-3]", "top.irq_4"));
+    assert!(matches("top.[abc]", "top.b"));
+    assert!(matches("top.[]x]", "top.]"));
+    assert!(matches("top.[a-]", "top.-"));
+}
+
+#[test]
+fn escapes() {
+    assert!(matches(r"top.data\[7:0\]", "top.data[7:0]"));
+    assert!(!matches("top.data[7:0]", "top.data[7:0]"));
+    assert!(matches(r"top.\*", "top.*"));
+    assert!(!matches(r"top.\*", "top.a"));
+}
+
+#[test]
+fn malformed() {
+    assert_eq!(
+        Glob::new("top.[ab"),
+        Err(GlobError::UnclosedClass("top.[ab".to_string()))
+    );
+    assert_eq!(
+        Glob::new("top\\"),
+        Err(GlobError::TrailingEscape("top\\".to_string()))
+    );
+}
+
+#[test]
+fn empty() {
+    assert!(matches("", ""));
+    assert!(!matches("", "top"));
+    assert!(matches("**", ""));
+}
+
+#[test]
+fn find_vars() {
+    let mut ingestor = Ingestor::new(IngestorOptions::new()).unwrap();
+    common::scopes(
+        &mut ingestor,
+        &[(1, 0, "top"), (2, 1, "cpu"), (3, 1, "dma")],
+    );
+    for (scope, name) in [(2, "valid"), (1, "clk"), (3, "valid"), (3, "ready")] {
+        common::var(&mut ingestor, scope, name, VarKind::None);
+    }
+    let processed = ingestor.finish().unwrap();
+
+    let found = |pattern| -> Vec<String> {
+        processed
+            .find_vars(&Glob::new(pattern).unwrap())
+            .into_iter()
+            .map(|var| processed.var_path(var))
+            .collect()
+    };
+    assert_eq!(found("top.*.valid"), ["top.cpu.valid", "top.dma.valid"]);
+    assert_eq!(found("top.*"), ["top.clk"]);
+    assert_eq!(
+        found("**"),
+        ["top.cpu.valid", "top.clk", "top.dma.valid", "top.dma.ready"]
+    );
+    assert!(found("top.gpu.*").is_empty());
+}
+
+#[test]
+fn escaped_literals_match_themselves() {
+    for literal in ["mem[3]", "a*b?", "back\\slash", "plain"] {
+        let pattern = search::escape(literal);
+        assert!(matches(&pattern, literal), "{}", pattern);
+    }
+    assert!(!matches(&search::escape("mem[3]"), "mem3"));
+}
+
+/// `top` holds `clk` and a `ram` scope with `words` words of memory, each with its own event
+/// storage. `top.alias` shares the storage of the first word.
+fn ingest_memory(options: IngestorOptions, words: u32) -> Ingestor {
+    let mut ingestor = Ingestor::new(options).unwrap();
+    common::scopes(&mut ingestor, &[(1, 0, "top"), (2, 1, "ram")]);
+
+    let mut var = |scope, name: &str, storage| {
+        let kind = VarKind::Event {
+            storage: StorageId(storage),
+        };
+        common::var(&mut ingestor, scope, name, kind);
+    };
+    var(1, "clk", 0);
+    for word in 1..=words {
+        var(2, &format!("mem[{}]", word), word);
+    }
+    var(1, "alias", 1);
+
+    for id in 0..=words {
+        common::storages(&mut ingestor, &[(id, StorageType::Event, 0)]);
+    }
+    ingestor
+}
+
+#[test]
+fn ignored_vars_are_skipped() {
+    let options = IngestorOptions::new().with_ignored(Glob::new("top.ram.*").unwrap());
+    let mut ingestor = ingest_memory(options, 3);
+    ingestor.ingest_timestep(Timesteps(5));
+    for id in 0..=3 {
+        ingestor
+            .ingest_value(Value {
+                storage_id: StorageId(id),
+                data: &[],
+            })
+            .unwrap();
+    }
+    let processed = ingestor.finish().unwrap();
+
+    let paths: Vec<_> = processed
+        .vars()
+        .iter()
+        .map(|var| processed.var_path(var))
+        .collect();
+    assert_eq!(paths, ["top.clk", "top.alias"]);
+    assert_eq!(processed.ignored_vars(), 3);
+
+    // The first word's storage is kept for the alias that shares it.
+    assert_eq!(processed.storage_ids(), [StorageId(0), StorageId(1)]);
+    assert_eq!(processed.change_count(StorageId(1)).unwrap(), 1);
+    assert!(processed.storage(StorageId(2)).is_err());
+}
+
+#[test]
+fn crowded_scopes() {
+    let options = IngestorOptions::new().with_crowded_scope_vars(2);
+    let crowded = ingest_memory(options.clone(), 3).crowded_scopes();
+    assert_eq!(
+        crowded,
+        [CrowdedScope {
+            path: "top.ram".to_string(),
+            vars: 3,
+        }]
+    );
+    assert_eq!(crowded[0].ignore_pattern(), "top.ram.*");
+
+    assert!(ingest_memory(options.clone(), 2)
+        .crowded_scopes()
+        .is_empty());
+    let ignored = options.with_ignored(Glob::new("top.ram.*").unwrap());
+    assert!(ingest_memory(ignored, 3).crowded_scopes().is_empty());
+}
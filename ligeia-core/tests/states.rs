@@ -0,0 +1,165 @@
+//! Enum variables are followed through their named states, with unknown and unnamed values in
+//! none of them.
+
+mod common;
+
+use ligeia_core::{
+    logic,
+    meta::{EnumValue, StorageId, StorageType, Timesteps},
+    states::{self, StateSpan},
+    IngestorOptions, Processed,
+};
+
+fn values() -> Vec<EnumValue> {
+    [
+        ("IDLE", [false, false]),
+        ("BUSY", [true, false]),
+        ("DONE", [false, true]),
+    ]
+    .into_iter()
+    .map(|(name, value)| EnumValue {
+        name: name.to_string(),
+        value: value.to_vec(),
+    })
+    .collect()
+}
+
+/// A two-bit storage, with its values written most significant bit first.
+fn waveform(changes: &[(u64, &str)]) -> Processed {
+    let packed: Vec<_> = changes
+        .iter()
+        .map(|(_, value)| {
+            let mut packed = vec![];
+            assert!(logic::pack_four_ascii_msb_first(
+                value.as_bytes(),
+                &mut packed
+            ));
+            packed
+        })
+        .collect();
+    let changes: Vec<_> = changes
+        .iter()
+        .zip(&packed)
+        .map(|(&(time, _), packed)| (time, 0, &packed[..]))
+        .collect();
+    common::waveform(
+        IngestorOptions::new(),
+        &[(0, StorageType::FourLogic, 2)],
+        &changes,
+    )
+}
+
+fn span(start: u64, end: Option<u64>, state: Option<usize>) -> StateSpan {
+    StateSpan {
+        start: Timesteps(start),
+        end: end.map(Timesteps),
+        state,
+    }
+}
+
+#[test]
+fn resolve_states() {
+    let values = values();
+    let resolve = |value: &str| {
+        let mut packed = vec![];
+        assert!(logic::pack_four_ascii_msb_first(
+            value.as_bytes(),
+            &mut packed
+        ));
+        states::resolve(&values, StorageType::FourLogic, 2, &packed)
+    };
+
+    assert_eq!(resolve("00"), Some(0));
+    assert_eq!(resolve("01"), Some(1));
+    assert_eq!(resolve("10"), Some(2));
+    assert_eq!(resolve("11"), None);
+    assert_eq!(resolve("x0"), None);
+    assert_eq!(
+        states::resolve(&values, StorageType::NineLogic, 2, &[1, 0]),
+        Some(1)
+    );
+    assert_eq!(
+        states::resolve(&values, StorageType::NineLogic, 2, &[3, 0]),
+        None
+    );
+}
+
+#[test]
+fn spans_and_time_in_states() {
+    let mut processed = waveform(&[
+        (5, "00"),
+        (10, "01"),
+        (20, "x1"),
+        (25, "10"),
+        (30, "11"),
+        (32, "00"),
+        (40, "01"),
+    ]);
+    let values = values();
+
+    let spans = states::state_spans(
+        &mut processed,
+        StorageId(0),
+        &values,
+        Timesteps(0)..Timesteps(50),
+    )
+    .unwrap();
+    assert_eq!(
+        spans,
+        vec![
+            span(5, Some(10), Some(0)),
+            span(10, Some(20), Some(1)),
+            // `x1` and `11` are both in no state, and `10` is between them.
+            span(20, Some(25), None),
+            span(25, Some(30), Some(2)),
+            span(30, Some(32), None),
+            span(32, Some(40), Some(0)),
+            span(40, None, Some(1)),
+        ]
+    );
+    assert_eq!(
+        states::time_in_states(&spans, Timesteps(0)..Timesteps(50)),
+        vec![(Some(1), 20), (Some(0), 13), (None, 7), (Some(2), 5)]
+    );
+
+    // Only looking at part of it starts the first span at the start of the range.
+    let spans = states::state_spans(
+        &mut processed,
+        StorageId(0),
+        &values,
+        Timesteps(15)..Timesteps(28),
+    )
+    .unwrap();
+    assert_eq!(
+        spans,
+        vec![
+            span(15, Some(20), Some(1)),
+            span(20, Some(25), None),
+            span(25, None, Some(2)),
+        ]
+    );
+    assert_eq!(
+        states::time_in_states(&spans, Timesteps(15)..Timesteps(28)),
+        vec![(Some(1), 5), (None, 5), (Some(2), 3)]
+    );
+}
+
+#[test]
+fn repeated_states_are_merged() {
+    let mut processed = waveform(&[(0, "01"), (10, "0x"), (12, "01"), (20, "01")]);
+    let spans = states::state_spans(
+        &mut processed,
+        StorageId(0),
+        &values(),
+        Timesteps(0)..Timesteps(30),
+    )
+    .unwrap();
+    assert_eq!(
+        spans,
+        vec![
+            span(0, Some(10), Some(1)),
+            span(10, Some(12), None),
+            span(12, None, Some(1)),
+        ]
+    );
+}
@@ -0,0 +1,155 @@
+mod common;
+
+use ligeia_core::{
+    meta::{StorageId, StorageType, Timesteps},
+    saif::export_saif,
+    stats::{bit_activity, BitActivity},
+    Ingestor, IngestorOptions, Processed,
+};
+
+/// `top` holds a one-bit `clk` and a two-bit `data[1:0]`, changing at `(time, storage, value)`.
+fn waveform(changes: &[(u64, u32, u8)]) -> Processed {
+    let mut ingestor = Ingestor::new(IngestorOptions::new().with_timescale(1_000_000)).unwrap();
+    common::scopes(&mut ingestor, &[(1, 0, "top")]);
+    common::storages(
+        &mut ingestor,
+        &[
+            (0, StorageType::FourLogic, 1),
+            (1, StorageType::FourLogic, 2),
+        ],
+    );
+    common::var(&mut ingestor, 1, "clk", common::integer(&[0], 0, 0));
+    common::var(&mut ingestor, 1, "data", common::integer(&[1], 1, 0));
+    let changes: Vec<_> = changes
+        .iter()
+        .map(|(time, storage, value)| (*time, *storage, std::slice::from_ref(value)))
+        .collect();
+    common::changes(&mut ingestor, &changes);
+    ingestor.finish().unwrap()
+}
+
+fn activity(t0: u64, t1: u64, tx: u64, tz: u64, toggles: u64) -> BitActivity {
+    BitActivity {
+        t0,
+        t1,
+        tx,
+        tz,
+        toggles,
+    }
+}
+
+fn clock() -> Processed {
+    waveform(&[(0, 0, 0), (10, 0, 1), (20, 0, 0), (30, 0, 1), (40, 0, 0)])
+}
+
+#[test]
+fn toggles_and_time_at_each_level() {
+    let mut processed = clock();
+
+    let clk = bit_activity(&mut processed, StorageId(0), Timesteps(0)..Timesteps(50)).unwrap();
+    assert_eq!(clk, vec![activity(30, 20, 0, 0, 4)]);
+    assert_eq!(clk[0].duty_cycle(), Some(0.4));
+}
+
+#[test]
+fn windows_start_with_the_value_in_effect() {
+    let mut processed = clock();
+
+    // 1 from 15 until 20, then 0 until 25.
+    let clk = bit_activity(&mut processed, StorageId(0), Timesteps(15)..Timesteps(25)).unwrap();
+    assert_eq!(clk, vec![activity(5, 5, 0, 0, 1)]);
+}
+
+#[test]
+fn unknown_levels_dont_toggle() {
+    // Bit 0 goes 0, x, 1, z, 1; bit 1 goes 0, 1, 0.
+    let mut processed = waveform(&[
+        (10, 1, 0b00_00),
+        (20, 1, 0b00_10),
+        (30, 1, 0b01_01),
+        (40, 1, 0b00_11),
+        (50, 1, 0b00_01),
+    ]);
+    // Before its first change, storage 0 is x the whole time.
+    assert_eq!(
+        bit_activity(&mut processed, StorageId(0), Timesteps(0)..Timesteps(60)).unwrap(),
+        vec![activity(0, 0, 60, 0, 0)]
+    );
+
+    let data = bit_activity(&mut processed, StorageId(1), Timesteps(0)..Timesteps(60)).unwrap();
+    assert_eq!(
+        data,
+        vec![activity(10, 20, 20, 10, 0), activity(40, 10, 10, 0, 2)]
+    );
+    assert_eq!(
+        bit_activity(&mut processed, StorageId(1), Timesteps(5)..Timesteps(5)).unwrap()[0]
+            .duty_cycle(),
+        None
+    );
+}
+
+#[test]
+fn saif() {
+    let mut processed = waveform(&[(0, 0, 0), (0, 1, 0b01_00), (10, 0, 1), (20, 1, 0b00_01)]);
+
+    let mut out = vec![];
+    export_saif(
+        &mut processed,
+        &mut out,
+        (Timesteps(0), Timesteps(30)),
+        |_| true,
+    )
+    .unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "(SAIFILE
+(SAIFVERSION \"2.0\")
+(DIRECTION \"backward\")
+(PROGRAM_NAME \"ligeia\")
+(DIVIDER / )
+(TIMESCALE 1 ns)
+(DURATION 30)
+(INSTANCE top
+  (NET
+    (clk
+      (T0 10) (T1 20) (TX 0) (TZ 0)
+      (TC 1) (IG 0)
+    )
+    (data\\[0\\]
+      (T0 20) (T1 10) (TX 0) (TZ 0)
+      (TC 1) (IG 0)
+    )
+    (data\\[1\\]
+      (T0 10) (T1 20) (TX 0) (TZ 0)
+      (TC 1) (IG 0)
+    )
+  )
+)
+)
+"
+    );
+}
+
+#[test]
+fn change_density() {
+    let mut processed = waveform(&[
+        (0, 0, 0),
+        (0, 1, 0),
+        (1, 1, 1),
+        (2, 1, 2),
+        (10, 0, 1),
+        (20, 0, 0),
+        (30, 0, 1),
+        (40, 0, 0),
+    ]);
+    // Every bucket covers a quarter of the time from 0 to 40.
+    assert_eq!(
+        processed.change_density(StorageId(0), 4).unwrap(),
+        [1, 1, 1, 2]
+    );
+    assert_eq!(
+        processed.change_density(StorageId(1), 4).unwrap(),
+        [3, 0, 0, 0]
+    );
+    assert_eq!(processed.change_density(StorageId(0), 1).unwrap(), [5]);
+}
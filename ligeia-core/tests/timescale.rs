@@ -0,0 +1,115 @@
+//! Parses, validates and converts timescales.
+
+use ligeia_core::{
+    meta::Timesteps,
+    timescale::{Rescale, TimeUnit, Timescale, TimescaleError},
+    Error, Ingestor, IngestorOptions,
+};
+
+#[test]
+fn parses_with_and_without_spaces() {
+    for (s, femtoseconds) in [
+        ("1 fs", 1),
+        ("10ns", 10_000_000),
+        ("100 ps", 100_000),
+        ("  1   s ", 1_000_000_000_000_000),
+        ("10 us", 10_000_000_000),
+        ("100ms", 100_000_000_000_000),
+    ] {
+        let timescale: Timescale = s.parse().unwrap();
+        assert_eq!(timescale.femtoseconds(), femtoseconds, "{}", s);
+    }
+}
+
+#[test]
+fn same_length_different_units() {
+    let ten_ns: Timescale = "10 ns".parse().unwrap();
+    let hundred_ps: Timescale = "100 ps".parse().unwrap();
+    assert_ne!(ten_ns, hundred_ps);
+    assert_eq!(ten_ns.femtoseconds(), 100 * hundred_ps.femtoseconds());
+
+    let one_ns: Timescale = "1 ns".parse().unwrap();
+    let thousand_ps = 1_000 * TimeUnit::Picoseconds.femtoseconds();
+    assert_eq!(one_ns.femtoseconds(), thousand_ps);
+}
+
+#[test]
+fn rejects_nonstandard_timescales() {
+    assert_eq!(
+        "5 ns".parse::<Timescale>(),
+        Err(TimescaleError::Multiplier(5))
+    );
+    assert_eq!(
+        "1000 ps".parse::<Timescale>(),
+        Err(TimescaleError::Multiplier(1000))
+    );
+    assert_eq!(
+        "0 ns".parse::<Timescale>(),
+        Err(TimescaleError::Multiplier(0))
+    );
+    assert_eq!(
+        "10 ks".parse::<Timescale>(),
+        Err(TimescaleError::Unit("ks".to_string()))
+    );
+    assert!(matches!(
+        "ns".parse::<Timescale>(),
+        Err(TimescaleError::Malformed(_))
+    ));
+    assert!(matches!(
+        "99999999999999999999999 ns".parse::<Timescale>(),
+        Err(TimescaleError::Malformed(_))
+    ));
+}
+
+#[test]
+fn round_trips_through_femtoseconds() {
+    for unit in TimeUnit::ALL {
+        for multiplier in Timescale::MULTIPLIERS {
+            let timescale = Timescale::new(multiplier as u64, unit).unwrap();
+            let back = Timescale::from_femtoseconds(timescale.femtoseconds()).unwrap();
+            assert_eq!(back.femtoseconds(), timescale.femtoseconds());
+            assert_eq!(back.to_string().parse::<Timescale>(), Ok(back));
+        }
+    }
+
+    // The largest unit wins.
+    assert_eq!(
+        Timescale::from_femtoseconds(1_000_000).unwrap().to_string(),
+        "1 ns"
+    );
+    assert_eq!(Timescale::from_femtoseconds(0), None);
+    assert_eq!(Timescale::from_femtoseconds(5_000), None);
+}
+
+#[test]
+fn ingestor_rejects_zero() {
+    assert!(matches!(
+        Ingestor::new(IngestorOptions::new().with_timescale(0)),
+        Err(Error::Timescale(TimescaleError::Zero))
+    ));
+}
+
+#[test]
+fn rescaling_counts_collapsed_changes() {
+    // Picoseconds to 10 ns.
+    let mut rescale = Rescale::new(1_000, 10_000_000);
+    assert!(!rescale.is_exact());
+    let converted: Vec<_> = [0, 5_000, 9_999, 9_999, 10_000, 25_000]
+        .into_iter()
+        .map(|time| rescale.convert(Timesteps(time)).0)
+        .collect();
+    assert_eq!(converted, [0, 0, 0, 0, 1, 2]);
+    // Changes at the same time to begin with don't count.
+    assert_eq!(rescale.collapsed(), 2);
+    assert_eq!(rescale.check(), Err(TimescaleError::Collapsed(2, 5_000)));
+
+    // Each storage's changes are counted separately.
+    rescale.restart();
+    rescale.convert(Timesteps(1));
+    assert_eq!(rescale.collapsed(), 2);
+
+    let mut exact = Rescale::new(10_000_000, 1_000);
+    assert!(exact.is_exact());
+    assert_eq!(exact.convert(Timesteps(3)), Timesteps(30_000));
+    assert_eq!(exact.check(), Ok(()));
+}
@@ -0,0 +1,326 @@
+//! Waveform file formats, and picking the right one for a file.
+//!
+//! Format crates implement [`WaveformLoader`] and add themselves to a [`LoaderRegistry`], so the
+//! viewer doesn't need to know about any format in particular.
+
+use std::{
+    error::Error as StdError,
+    fs::{self, File},
+    io::{self, BufReader, Cursor, Read},
+    path::Path,
+};
+
+use ligeia_core::{IngestorOptions, Processed};
+
+pub mod position;
+
+/// How many bytes from the start of a file are given to [`WaveformLoader::sniff`].
+pub const SNIFF_LEN: usize = 16;
+
+/// Signatures of formats that can't be loaded yet, so they get a more helpful error.
+const KNOWN_SIGNATURES: &[(&[u8], &str)] = &[
+    (b"GHDLwave", "a GHDL waveform (GHW) file"),
+    // A header block, which is always 329 bytes long.
+    (&[0, 0, 0, 0, 0, 0, 0, 0x01, 0x49], "an FST file"),
+    (&[0x13, 0x80], "an LXT2 file"),
+];
+
+/// Compression that's looked through transparently, if the matching feature is enabled.
+#[derive(Debug, Copy, Clone)]
+enum Compression {
+    Gzip,
+    Zstd,
+    Xz,
+}
+
+impl Compression {
+    fn detect(header: &[u8]) -> Option<Self> {
+        if header.starts_with(&[0x1f, 0x8b]) {
+            Some(Self::Gzip)
+        } else if header.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Self::Zstd)
+        } else if header.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0]) {
+            Some(Self::Xz)
+        } else {
+            None
+        }
+    }
+
+    fn feature(self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Zstd => "zstd",
+            Self::Xz => "xz",
+        }
+    }
+
+    fn decode<'a, R>(self, name: &str, reader: R) -> Result<Box<dyn Read + 'a>, Error>
+    where
+        R: Read + 'a,
+    {
+        match self {
+            #[cfg(feature = "gzip")]
+            // Concatenated members are common when dumps are compressed as they're written.
+            Self::Gzip => Ok(Box::new(flate2::read::MultiGzDecoder::new(reader))),
+            #[cfg(feature = "zstd")]
+            Self::Zstd => Ok(Box::new(zstd::stream::read::Decoder::new(reader)?)),
+            #[cfg(feature = "xz")]
+            Self::Xz => Ok(Box::new(xz2::read::XzDecoder::new_multi_decoder(reader))),
+            #[allow(unreachable_patterns)]
+            _ => {
+                drop(reader);
+                Err(Error::Compressed(name.to_string(), self.feature()))
+            }
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("an i/o error occured")]
+    Io(#[from] io::Error),
+    #[error("couldn't tell what format `{0}` is in")]
+    UnknownFormat(String),
+    #[error("`{0}` looks like {1}, which can't be loaded yet")]
+    Unsupported(String, &'static str),
+    #[error("there's no loader for the `{0}` format")]
+    NoLoader(String),
+    #[error("`{0}` is compressed, but ligeia was built without the `{1}` feature")]
+    Compressed(String, &'static str),
+    #[error("{0}")]
+    Load(Box<dyn StdError>),
+    #[error("{0}")]
+    Core(#[from] ligeia_core::Error),
+}
+
+pub trait WaveformLoader: Send + Sync {
+    /// A description of the loader, like "the Value Change Dump (VCD) loader".
+    fn description(&self) -> String;
+
+    /// Whether files with this extension (without the leading `.`) are in this format.
+    fn supports_file_extension(&self, s: &str) -> bool;
+
+    /// Whether a file starting with `header` looks like it's in this format.
+    ///
+    /// Files shorter than [`SNIFF_LEN`] are padded with zeros.
+    fn sniff(&self, header: &[u8; SNIFF_LEN]) -> bool;
+
+    /// Load a waveform with `options`, replacing their timescale with the stream's own.
+    fn load_stream(
+        &self,
+        reader: &mut dyn Read,
+        options: &IngestorOptions,
+    ) -> Result<Processed, Box<dyn StdError>>;
+
+    /// Loaders that can do better with random access, like loading lazily, should override this.
+    fn load_file(
+        &self,
+        path: &Path,
+        options: &IngestorOptions,
+    ) -> Result<Processed, Box<dyn StdError>> {
+        let mut reader = BufReader::new(File::open(path)?);
+        self.load_stream(&mut reader, options)
+    }
+
+    /// Whether `load_file` loads storages lazily, as they're needed, rather than spilling every
+    /// change to scratch files up front.
+    fn loads_lazily(&self) -> bool {
+        false
+    }
+}
+
+/// The set of formats that can be opened, and the options they're loaded with.
+#[derive(Default)]
+pub struct LoaderRegistry {
+    loaders: Vec<Box<dyn WaveformLoader>>,
+    options: IngestorOptions,
+}
+
+impl LoaderRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Earlier loaders take priority when more than one recognizes a file.
+    pub fn register<L>(&mut self, loader: L)
+    where
+        L: WaveformLoader + 'static,
+    {
+        self.loaders.push(Box::new(loader));
+    }
+
+    /// Load everything from now on with `options`.
+    pub fn set_options(&mut self, options: IngestorOptions) {
+        self.options = options;
+    }
+
+    pub fn options(&self) -> &IngestorOptions {
+        &self.options
+    }
+
+    pub fn loaders(&self) -> impl Iterator<Item = &dyn WaveformLoader> {
+        self.loaders.iter().map(|loader| &**loader)
+    }
+
+    pub fn by_extension(&self, path: &Path) -> Option<&dyn WaveformLoader> {
+        let extension = path.extension()?.to_str()?;
+        self.loaders()
+            .find(|loader| loader.supports_file_extension(extension))
+    }
+
+    /// Find a loader by the name of its format, as given by `--format`. This is the same as
+    /// the format's usual file extension.
+    pub fn by_format(&self, format: &str) -> Option<&dyn WaveformLoader> {
+        self.loaders()
+            .find(|loader| loader.supports_file_extension(format))
+    }
+
+    pub fn sniff(&self, header: &[u8; SNIFF_LEN]) -> Option<&dyn WaveformLoader> {
+        self.loaders().find(|loader| loader.sniff(header))
+    }
+
+    /// Pick a loader by looking at the start of the input, falling back to `format` if nothing
+    /// recognizes it.
+    ///
+    /// `name` is only used for errors.
+    fn identify(
+        &self,
+        name: &str,
+        header: &[u8; SNIFF_LEN],
+        format: Option<&str>,
+    ) -> Result<&dyn WaveformLoader, Error> {
+        if let Some(loader) = self.sniff(header) {
+            return Ok(loader);
+        }
+
+        if let Some(format) = format {
+            return self
+                .by_format(format)
+                .ok_or_else(|| Error::NoLoader(format.to_string()));
+        }
+
+        match KNOWN_SIGNATURES
+            .iter()
+            .find(|(signature, _)| header.starts_with(signature))
+        {
+            Some(&(_, description)) => Err(Error::Unsupported(name.to_string(), description)),
+            None => Err(Error::UnknownFormat(name.to_string())),
+        }
+    }
+
+    /// Read the start of a stream, decompressing it if need be.
+    fn peel<'a>(&self, name: &str, mut reader: Box<dyn Read + 'a>) -> Result<Peeled<'a>, Error> {
+        let mut compressed = false;
+        loop {
+            let mut header = [0; SNIFF_LEN];
+            let len = read_up_to(&mut reader, &mut header)?;
+            let rest = Box::new(Cursor::new(header).take(len as u64).chain(reader));
+
+            match Compression::detect(&header[..len]) {
+                Some(compression) => {
+                    reader = compression.decode(name, rest)?;
+                    compressed = true;
+                }
+                None => {
+                    return Ok(Peeled {
+                        reader: rest,
+                        header,
+                        compressed,
+                    })
+                }
+            }
+        }
+    }
+
+    /// Load a file, picking a loader by its extension, and falling back to looking at its
+    /// contents and then to `format`.
+    ///
+    /// Compressed files are decompressed as they're loaded, using the extension underneath
+    /// the compression one (like `vcd` in `dump.vcd.gz`) in place of `format` if there isn't one.
+    pub fn load_file(&self, path: &Path, format: Option<&str>) -> Result<Processed, Error> {
+        let name = path.display().to_string();
+        let file = BufReader::new(File::open(path)?);
+        let Peeled {
+            mut reader,
+            header,
+            compressed,
+        } = self.peel(&name, Box::new(file))?;
+
+        if !compressed {
+            // Loaders can do better with the file itself than with a stream.
+            drop(reader);
+            let loader = match self.by_extension(path) {
+                Some(loader) => loader,
+                None => self.identify(&name, &header, format)?,
+            };
+
+            // Spilled changes take no more room than an uncompressed dump of them, so the file's
+            // size is a fair estimate of the scratch space needed. Compressed files can't be
+            // estimated without decompressing them, and lazy loads only spill what's looked at.
+            let shortfall = if loader.loads_lazily() {
+                None
+            } else {
+                ligeia_core::scratch::check_space(
+                    &self.options.scratch_dir(),
+                    fs::metadata(path)?.len(),
+                    self.options.memory_limit(),
+                )?
+            };
+
+            let mut processed = loader.load_file(path, &self.options).map_err(Error::Load)?;
+            if let Some(warning) = shortfall {
+                processed.warn(warning)?;
+            }
+            return Ok(processed);
+        }
+
+        let format = format.or_else(|| {
+            Path::new(path.file_stem()?)
+                .extension()
+                .and_then(|extension| extension.to_str())
+        });
+        self.identify(&name, &header, format)?
+            .load_stream(&mut reader, &self.options)
+            .map_err(Error::Load)
+    }
+
+    /// Load from a stream that has no name to go by, like stdin.
+    ///
+    /// The first few bytes are read to work out the format, decompressing the stream if need
+    /// be, and then handed to the loader along with the rest of it.
+    pub fn load_stream<R: Read>(
+        &self,
+        name: &str,
+        reader: R,
+        format: Option<&str>,
+    ) -> Result<Processed, Error> {
+        let Peeled {
+            mut reader, header, ..
+        } = self.peel(name, Box::new(reader))?;
+        self.identify(name, &header, format)?
+            .load_stream(&mut reader, &self.options)
+            .map_err(Error::Load)
+    }
+}
+
+/// The start of a stream, after looking through any compression.
+struct Peeled<'a> {
+    /// The whole (decompressed) stream, from the beginning.
+    reader: Box<dyn Read + 'a>,
+    header: [u8; SNIFF_LEN],
+    compressed: bool,
+}
+
+/// Fill as much of `buffer` as possible, stopping early at the end of the stream.
+fn read_up_to<R: Read>(reader: &mut R, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
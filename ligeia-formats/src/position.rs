@@ -0,0 +1,180 @@
+//! Where in a file a loader gave up, so that its errors can point there.
+
+use std::{
+    cell::RefCell,
+    error::Error as StdError,
+    fmt,
+    io::{self, Read},
+};
+
+/// How many of the bytes read last are kept around to show where an error is.
+const RECENT_LEN: usize = 32;
+
+/// How many bytes of a binary file are shown in an excerpt.
+const HEX_LEN: usize = 8;
+
+/// A place in a file, with a little of what's there.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Position {
+    /// Bytes from the start of the file.
+    pub offset: u64,
+    /// Counting from 1, for text formats.
+    pub line: Option<u64>,
+    /// What's at `offset`, as text or as hex.
+    pub excerpt: String,
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "byte {}", self.offset)?;
+        if let Some(line) = self.line {
+            write!(f, ", line {}", line)?;
+        }
+        if !self.excerpt.is_empty() {
+            write!(f, ", near `{}`", self.excerpt)?;
+        }
+        Ok(())
+    }
+}
+
+/// An error from a loader, along with where in the file it was found.
+#[derive(Debug)]
+pub struct ParseError {
+    pub position: Position,
+    pub source: Box<dyn StdError + Send + Sync>,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "at {}: {}", self.position, self.source)
+    }
+}
+
+impl StdError for ParseError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(&*self.source)
+    }
+}
+
+impl ParseError {
+    /// An `InvalidData` I/O error, for parsers whose errors are I/O errors.
+    pub fn into_io(self) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, self)
+    }
+}
+
+/// A reader that keeps track of how far into a stream it's got, in bytes and in lines, and of
+/// the last few bytes it read.
+pub struct Tracking<R> {
+    inner: R,
+    /// Where tracking started, since nothing before it has been seen.
+    start: u64,
+    offset: u64,
+    line: u64,
+    /// The last bytes read, as a ring that `offset` is the end of.
+    recent: [u8; RECENT_LEN],
+}
+
+impl<R> Tracking<R> {
+    pub fn new(inner: R) -> Self {
+        Self::starting_at(inner, 0, 1)
+    }
+
+    /// Track a reader that's partway into a file already.
+    pub fn starting_at(inner: R, offset: u64, line: u64) -> Self {
+        Self {
+            inner,
+            start: offset,
+            offset,
+            line,
+            recent: [0; RECENT_LEN],
+        }
+    }
+
+    /// How many bytes have been read, including any before this started tracking.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub fn line(&self) -> u64 {
+        self.line
+    }
+
+    /// The bytes read from `from` on, as far back as they're kept.
+    fn recent_from(&self, from: u64) -> Vec<u8> {
+        let oldest = self
+            .offset
+            .saturating_sub(RECENT_LEN as u64)
+            .max(self.start);
+        let from = from.clamp(oldest, self.offset);
+        (from..self.offset)
+            .map(|offset| self.recent[(offset % RECENT_LEN as u64) as usize])
+            .collect()
+    }
+
+    /// Where a text format's reader is, with the end of the line it's partway through.
+    ///
+    /// Parsers tend to read the whitespace after a token before they find out it's wrong, so
+    /// that's skipped back over.
+    pub fn text_position(&self) -> Position {
+        let recent = self.recent_from(0);
+        let end = recent
+            .iter()
+            .rposition(|b| !b.is_ascii_whitespace())
+            .map_or(0, |i| i + 1);
+        let newlines = recent[end..].iter().filter(|&&b| b == b'\n').count() as u64;
+        let line_start = recent[..end]
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |i| i + 1);
+        let excerpt = String::from_utf8_lossy(&recent[line_start..end])
+            .trim()
+            .escape_debug()
+            .to_string();
+        Position {
+            offset: self.offset - (recent.len() - end) as u64,
+            line: Some(self.line - newlines),
+            excerpt,
+        }
+    }
+
+    /// The position of `offset` in a binary format, which is at most as far as has been read,
+    /// with the bytes from there that have been read so far in hex.
+    pub fn binary_position(&self, offset: u64) -> Position {
+        let excerpt: Vec<String> = self
+            .recent_from(offset)
+            .iter()
+            .take(HEX_LEN)
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        Position {
+            offset,
+            line: None,
+            excerpt: excerpt.join(" "),
+        }
+    }
+}
+
+impl<R: Read> Read for Tracking<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        for &byte in &buf[..len] {
+            self.recent[(self.offset % RECENT_LEN as u64) as usize] = byte;
+            self.offset += 1;
+            if byte == b'\n' {
+                self.line += 1;
+            }
+        }
+        Ok(len)
+    }
+}
+
+/// Reads through a [`Tracking`] that's still looked at while something else owns the reader,
+/// like a parser that takes its reader by value.
+pub struct Shared<'a, R>(pub &'a RefCell<Tracking<R>>);
+
+impl<R: Read> Read for Shared<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.borrow_mut().read(buf)
+    }
+}
@@ -0,0 +1,77 @@
+/*
+ * The interface ligeia expects from an FSDB reader shim.
+ *
+ * The vendor's FSDB reader is a C++ library that can't be redistributed, so ligeia doesn't link
+ * against it. Instead, a small shim built against the reader on a machine that has it exports
+ * the functions below, and ligeia loads the shim at runtime when it opens an `.fsdb` file. It's
+ * looked for as `libligeia_fsdb.so` (or the platform's equivalent) on the library search path,
+ * or at the path in the `LIGEIA_FSDB_SHIM` environment variable.
+ *
+ * Every function that can fail returns 0 on success, and otherwise writes a nul-terminated
+ * message, truncated to fit, into `error`.
+ */
+
+#ifndef LIGEIA_FSDB_H
+#define LIGEIA_FSDB_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* The version of this interface. Shims return it from `ligeia_fsdb_abi_version`. */
+#define LIGEIA_FSDB_ABI_VERSION 1
+
+/* The parent of top-level scopes. Other scope ids must not be 0. */
+#define LIGEIA_FSDB_ROOT_SCOPE 0
+
+enum ligeia_fsdb_var_kind {
+    /* Changes are `width` characters, most significant bit first, each one of `0`, `1`, `x`
+       or `z`. */
+    LIGEIA_FSDB_LOGIC = 0,
+    /* Changes are UTF-8 text, and `width` is ignored. */
+    LIGEIA_FSDB_STRING = 1,
+    /* Changes only mark when the event happened, and their value is ignored. */
+    LIGEIA_FSDB_EVENT = 2,
+};
+
+/*
+ * Called by `ligeia_fsdb_read` with everything in the file. Returning anything but 0 from a
+ * callback stops reading, and `ligeia_fsdb_read` should return that value.
+ */
+struct ligeia_fsdb_callbacks {
+    /* Passed to every callback. */
+    void *context;
+    /* A scope, given before anything inside it. */
+    int (*scope)(void *context, uint32_t id, uint32_t parent, const char *name);
+    /* A variable in `scope`, whose changes are those of `storage`. Variables that alias each
+       other share a storage, and give the same kind and width. */
+    int (*var)(void *context, uint32_t storage, uint32_t scope, const char *name, uint32_t kind,
+               uint32_t width);
+    /* Changes after this are at `timestep`. Timesteps never go backwards. */
+    int (*time)(void *context, uint64_t timestep);
+    /* A change to `storage`, in the format given by its kind. */
+    int (*change)(void *context, uint32_t storage, const uint8_t *value, size_t len);
+};
+
+uint32_t ligeia_fsdb_abi_version(void);
+
+/* Open the file at `path`, setting `reader` to a handle for the other functions. */
+int ligeia_fsdb_open(const char *path, void **reader, char *error, size_t error_len);
+
+/* How long each timestep in the file is. */
+uint64_t ligeia_fsdb_femtoseconds_per_timestep(void *reader);
+
+/* Report every scope and variable, and then every change in time order. */
+int ligeia_fsdb_read(void *reader, const struct ligeia_fsdb_callbacks *callbacks, char *error,
+                     size_t error_len);
+
+void ligeia_fsdb_close(void *reader);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,292 @@
+//! Loading FSDB files through the vendor's reader library.
+//!
+//! The reader can't be redistributed, so this goes through a shim that's built against it
+//! separately and loaded at runtime. See `shim/ligeia_fsdb.h` for what the shim has to provide.
+//! Without the shim, FSDB files fail to load with an error saying where it was looked for.
+
+use std::{
+    error::Error as StdError,
+    ffi::CStr,
+    io::Read,
+    os::raw::{c_char, c_int, c_void},
+    path::Path,
+    slice,
+};
+
+use fnv::FnvHashMap;
+use ligeia_core::{
+    logic,
+    meta::{self, ScopeId, StorageId, Timesteps},
+    Ingestor, IngestorOptions, Processed, Warning,
+};
+use ligeia_formats::{LoaderRegistry, WaveformLoader, SNIFF_LEN};
+
+pub use crate::shim::SHIM_VAR;
+use crate::shim::{Callbacks, Shim};
+
+mod shim;
+
+const LOGIC: u32 = 0;
+const STRING: u32 = 1;
+const EVENT: u32 = 2;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("couldn't load the FSDB reader shim `{0}` (set {SHIM_VAR} to where it is): {1}")]
+    NoShim(String, libloading::Error),
+    #[error("the FSDB reader shim `{0}` is for version {1} of the interface, not 1")]
+    ShimVersion(String, u32),
+    #[error("the FSDB reader shim is missing a function: {0}")]
+    MissingSymbol(libloading::Error),
+    #[error("the FSDB reader failed: {0}")]
+    Shim(String),
+    #[error("variable `{0}` has an unknown kind {1}")]
+    UnknownKind(String, u32),
+    #[error("storage {0} changed before it was declared")]
+    UnknownStorage(u32),
+    #[error("invalid value for storage {0}")]
+    InvalidValue(u32),
+    #[error("{0}")]
+    Core(#[from] ligeia_core::Error),
+}
+
+pub fn load_fsdb(path: &Path, options: IngestorOptions) -> Result<Processed, Error> {
+    let shim = Shim::load()?;
+    let reader = shim.open(path)?;
+
+    let mut state = State {
+        ingestor: Ingestor::new(
+            options.with_timescale(reader.femtoseconds_per_timestep()?.into()),
+        )?,
+        storages: FnvHashMap::default(),
+        buffer: vec![],
+        declared: false,
+        error: None,
+    };
+    let callbacks = Callbacks {
+        context: &mut state as *mut State as *mut c_void,
+        scope,
+        var,
+        time,
+        change,
+    };
+
+    // The callbacks are only called with `context`, which outlives the read.
+    let code = unsafe { reader.read(&callbacks)? };
+    drop(reader);
+    match state.error {
+        Some(e) => Err(e),
+        None if code != 0 => Err(Error::Shim(format!("reading stopped with code {}", code))),
+        None => Ok(state.ingestor.finish()?),
+    }
+}
+
+/// Everything the callbacks need, passed to them as their context.
+struct State {
+    ingestor: Ingestor,
+    /// The type of each storage that's been declared, so aliases don't declare them again.
+    storages: FnvHashMap<u32, meta::StorageType>,
+    buffer: Vec<u8>,
+    /// Whether every scope and variable has been declared, which they have been by the first
+    /// timestep.
+    declared: bool,
+    /// What stopped reading, if anything did.
+    error: Option<Error>,
+}
+
+impl State {
+    fn scope(&mut self, id: u32, parent: u32, name: &str) {
+        let name = self.ingestor.intern(name);
+        self.ingestor.ingest_scope(meta::Scope {
+            id: ScopeId(id),
+            parent: ScopeId(parent),
+            name,
+        });
+    }
+
+    fn var(
+        &mut self,
+        storage: u32,
+        scope: u32,
+        name: &str,
+        kind: u32,
+        width: u32,
+    ) -> Result<(), Error> {
+        let id = StorageId(storage);
+        let (kind, ty, width) = match kind {
+            LOGIC => (
+                meta::VarKind::Integer {
+                    storages: vec![id],
+                    msb_index: width.saturating_sub(1),
+                    lsb_index: 0,
+                    signedness: meta::Signedness::Unsigned,
+                },
+                meta::StorageType::FourLogic,
+                width,
+            ),
+            STRING => (
+                meta::VarKind::Utf8 { storage: id },
+                meta::StorageType::Utf8,
+                width,
+            ),
+            EVENT => (
+                meta::VarKind::Event { storage: id },
+                meta::StorageType::Event,
+                0,
+            ),
+            kind => return Err(Error::UnknownKind(name.to_string(), kind)),
+        };
+
+        if self.storages.insert(storage, ty).is_none() {
+            self.ingestor.ingest_storage(meta::Storage {
+                id,
+                ty,
+                start: 0,
+                width,
+            });
+        }
+
+        let name = self.ingestor.intern(name);
+        self.ingestor.ingest_var(meta::Var {
+            kind,
+            name,
+            scope_id: ScopeId(scope),
+            source: None,
+        });
+        Ok(())
+    }
+
+    fn time(&mut self, timestep: u64) {
+        if !self.declared {
+            self.declared = true;
+            for scope in self.ingestor.crowded_scopes() {
+                self.ingestor.warn(Warning::Crowded(scope));
+            }
+        }
+        self.ingestor.ingest_timestep(Timesteps(timestep));
+    }
+
+    fn change(&mut self, storage: u32, value: &[u8]) -> Result<(), Error> {
+        let ty = self
+            .storages
+            .get(&storage)
+            .ok_or(Error::UnknownStorage(storage))?;
+        let data = match ty {
+            meta::StorageType::FourLogic => {
+                if !logic::pack_four_ascii_msb_first(value, &mut self.buffer) {
+                    return Err(Error::InvalidValue(storage));
+                }
+                &self.buffer[..]
+            }
+            meta::StorageType::Event => &[],
+            _ => value,
+        };
+        self.ingestor.ingest_value(ligeia_core::Value {
+            storage_id: StorageId(storage),
+            data,
+        })?;
+        Ok(())
+    }
+}
+
+/// Run a callback against the state, stopping the read if it fails.
+unsafe fn with_state<F>(context: *mut c_void, f: F) -> c_int
+where
+    F: FnOnce(&mut State) -> Result<(), Error>,
+{
+    let state = &mut *(context as *mut State);
+    match f(state) {
+        Ok(()) => 0,
+        Err(e) => {
+            state.error = Some(e);
+            1
+        }
+    }
+}
+
+unsafe fn name<'a>(name: *const c_char) -> std::borrow::Cow<'a, str> {
+    CStr::from_ptr(name).to_string_lossy()
+}
+
+unsafe extern "C" fn scope(
+    context: *mut c_void,
+    id: u32,
+    parent: u32,
+    name: *const c_char,
+) -> c_int {
+    with_state(context, |state| {
+        state.scope(id, parent, &self::name(name));
+        Ok(())
+    })
+}
+
+unsafe extern "C" fn var(
+    context: *mut c_void,
+    storage: u32,
+    scope: u32,
+    name: *const c_char,
+    kind: u32,
+    width: u32,
+) -> c_int {
+    with_state(context, |state| {
+        state.var(storage, scope, &self::name(name), kind, width)
+    })
+}
+
+unsafe extern "C" fn time(context: *mut c_void, timestep: u64) -> c_int {
+    with_state(context, |state| {
+        state.time(timestep);
+        Ok(())
+    })
+}
+
+unsafe extern "C" fn change(
+    context: *mut c_void,
+    storage: u32,
+    value: *const u8,
+    len: usize,
+) -> c_int {
+    let value = match len {
+        0 => &[][..],
+        len => slice::from_raw_parts(value, len),
+    };
+    with_state(context, |state| state.change(storage, value))
+}
+
+pub struct FsdbLoader;
+
+impl WaveformLoader for FsdbLoader {
+    fn description(&self) -> String {
+        "the Fast Signal Database (FSDB) loader".to_string()
+    }
+
+    fn supports_file_extension(&self, s: &str) -> bool {
+        s.eq_ignore_ascii_case("fsdb")
+    }
+
+    /// FSDB's layout isn't public, so files are only recognized by their extension.
+    fn sniff(&self, _header: &[u8; SNIFF_LEN]) -> bool {
+        false
+    }
+
+    fn load_stream(
+        &self,
+        _reader: &mut dyn Read,
+        _options: &IngestorOptions,
+    ) -> Result<Processed, Box<dyn StdError>> {
+        Err("FSDB files can only be loaded from a file, since the reader opens them itself".into())
+    }
+
+    fn load_file(
+        &self,
+        path: &Path,
+        options: &IngestorOptions,
+    ) -> Result<Processed, Box<dyn StdError>> {
+        Ok(load_fsdb(path, options.clone())?)
+    }
+}
+
+/// Add the FSDB loader to `registry`.
+pub fn register(registry: &mut LoaderRegistry) {
+    registry.register(FsdbLoader);
+}
@@ -0,0 +1,131 @@
+//! Loading the FSDB reader shim, and calling into it. The interface is described in
+//! `shim/ligeia_fsdb.h`.
+
+use std::{
+    env,
+    ffi::{CStr, CString},
+    os::raw::{c_char, c_int, c_void},
+    path::Path,
+    ptr,
+};
+
+use libloading::{Library, Symbol};
+
+use crate::Error;
+
+/// The version of `ligeia_fsdb.h` this was written against.
+const ABI_VERSION: u32 = 1;
+
+/// Where to find the shim, if it isn't on the library search path.
+pub const SHIM_VAR: &str = "LIGEIA_FSDB_SHIM";
+
+const ERROR_LEN: usize = 1024;
+
+#[repr(C)]
+pub struct Callbacks {
+    pub context: *mut c_void,
+    pub scope: unsafe extern "C" fn(*mut c_void, u32, u32, *const c_char) -> c_int,
+    pub var: unsafe extern "C" fn(*mut c_void, u32, u32, *const c_char, u32, u32) -> c_int,
+    pub time: unsafe extern "C" fn(*mut c_void, u64) -> c_int,
+    pub change: unsafe extern "C" fn(*mut c_void, u32, *const u8, usize) -> c_int,
+}
+
+type AbiVersion = unsafe extern "C" fn() -> u32;
+type Open = unsafe extern "C" fn(*const c_char, *mut *mut c_void, *mut c_char, usize) -> c_int;
+type FemtosecondsPerTimestep = unsafe extern "C" fn(*mut c_void) -> u64;
+type Read = unsafe extern "C" fn(*mut c_void, *const Callbacks, *mut c_char, usize) -> c_int;
+type Close = unsafe extern "C" fn(*mut c_void);
+
+pub struct Shim {
+    library: Library,
+}
+
+impl Shim {
+    pub fn load() -> Result<Self, Error> {
+        let path =
+            env::var_os(SHIM_VAR).unwrap_or_else(|| libloading::library_filename("ligeia_fsdb"));
+        let name = Path::new(&path).display().to_string();
+
+        // Loading the shim runs its initializers, and the vendor's reader with them. There's
+        // nothing to be done about that other than trusting whoever built it.
+        let library = unsafe { Library::new(&path) }.map_err(|e| Error::NoShim(name.clone(), e))?;
+        let shim = Self { library };
+
+        let version = unsafe { shim.symbol::<AbiVersion>(b"ligeia_fsdb_abi_version\0")?() };
+        if version != ABI_VERSION {
+            return Err(Error::ShimVersion(name, version));
+        }
+        Ok(shim)
+    }
+
+    unsafe fn symbol<T>(&self, name: &[u8]) -> Result<Symbol<'_, T>, Error> {
+        self.library.get(name).map_err(Error::MissingSymbol)
+    }
+
+    pub fn open(&self, path: &Path) -> Result<Reader<'_>, Error> {
+        let c_path = CString::new(path.to_string_lossy().into_owned())
+            .map_err(|_| Error::Shim("the path has a nul byte in it".to_string()))?;
+        let mut reader = ptr::null_mut();
+        let mut error = [0; ERROR_LEN];
+        unsafe {
+            let open = self.symbol::<Open>(b"ligeia_fsdb_open\0")?;
+            check(
+                open(c_path.as_ptr(), &mut reader, error.as_mut_ptr(), ERROR_LEN),
+                &error,
+            )?;
+        }
+        Ok(Reader { shim: self, reader })
+    }
+}
+
+/// An open FSDB file.
+pub struct Reader<'a> {
+    shim: &'a Shim,
+    reader: *mut c_void,
+}
+
+impl Reader<'_> {
+    pub fn femtoseconds_per_timestep(&self) -> Result<u64, Error> {
+        unsafe {
+            let femtoseconds = self
+                .shim
+                .symbol::<FemtosecondsPerTimestep>(b"ligeia_fsdb_femtoseconds_per_timestep\0")?;
+            Ok(femtoseconds(self.reader))
+        }
+    }
+
+    /// Returns the first callback's return value that wasn't 0, if reading was stopped by one.
+    ///
+    /// # Safety
+    /// The callbacks must be safe to call with `callbacks.context`.
+    pub unsafe fn read(&self, callbacks: &Callbacks) -> Result<c_int, Error> {
+        let read = self.shim.symbol::<Read>(b"ligeia_fsdb_read\0")?;
+        let mut error = [0; ERROR_LEN];
+        match read(self.reader, callbacks, error.as_mut_ptr(), ERROR_LEN) {
+            0 => Ok(0),
+            // Callbacks report their own errors.
+            code if error[0] == 0 => Ok(code),
+            code => check(code, &error).map(|()| 0),
+        }
+    }
+}
+
+impl Drop for Reader<'_> {
+    fn drop(&mut self) {
+        // If the shim has no `close`, leaking the reader is all that's left to do.
+        if let Ok(close) = unsafe { self.shim.symbol::<Close>(b"ligeia_fsdb_close\0") } {
+            unsafe { close(self.reader) };
+        }
+    }
+}
+
+fn check(code: c_int, error: &[c_char; ERROR_LEN]) -> Result<(), Error> {
+    if code == 0 {
+        return Ok(());
+    }
+    // The shim may not have terminated a message that filled the buffer.
+    let mut error = *error;
+    error[ERROR_LEN - 1] = 0;
+    let message = unsafe { CStr::from_ptr(error.as_ptr()) };
+    Err(Error::Shim(message.to_string_lossy().into_owned()))
+}
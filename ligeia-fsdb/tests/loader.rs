@@ -0,0 +1,24 @@
+use std::{env, path::Path};
+
+use ligeia_core::IngestorOptions;
+use ligeia_formats::{Error, LoaderRegistry};
+
+#[test]
+fn fsdb_files_need_the_shim() {
+    let mut loaders = LoaderRegistry::new();
+    ligeia_fsdb::register(&mut loaders);
+    assert!(loaders.by_extension(Path::new("dump.fsdb")).is_some());
+
+    env::set_var(ligeia_fsdb::SHIM_VAR, "/nonexistent/libligeia_fsdb.so");
+    match ligeia_fsdb::load_fsdb(Path::new("dump.fsdb"), IngestorOptions::new()) {
+        Err(ligeia_fsdb::Error::NoShim(name, _)) => {
+            assert_eq!(name, "/nonexistent/libligeia_fsdb.so")
+        }
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("loaded without a shim"),
+    }
+
+    // The shim opens files itself, so there's nothing it can do with a stream.
+    let result = loaders.load_stream("stdin", &b""[..], Some("fsdb"));
+    assert!(matches!(result, Err(Error::Load(_))));
+}
@@ -0,0 +1,83 @@
+/*
+ * Streaming waveforms from a simulator plugin to ligeia through a ring buffer in shared memory.
+ *
+ * The plugin creates the ring, usually somewhere like `/dev/shm/sim.ring`, declares its scopes,
+ * storages and variables, and then writes changes as the simulation runs. Opening the same path
+ * in ligeia reads them out as they're written, without a syscall per change like a pipe would
+ * need. Link against `libligeia_shm` (static or shared) from the `ligeia-shm` crate.
+ *
+ * Declarations have to come before anything that refers to them: a scope before its children
+ * and the variables in it, and a storage before its variables and changes.
+ *
+ * Every function that can fail returns 0 on success and -1 otherwise, after which
+ * `ligeia_shm_last_error` says what went wrong. Writes wait while the ring is full, and fail
+ * once ligeia has gone away.
+ */
+
+#ifndef LIGEIA_SHM_H
+#define LIGEIA_SHM_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* The parent of top-level scopes. Other scope ids must not be 0. */
+#define LIGEIA_SHM_ROOT_SCOPE 0
+
+enum ligeia_shm_storage_type {
+    /* Changes are eight values to a byte, least significant bit first. */
+    LIGEIA_SHM_TWO_LOGIC = 0,
+    /* Changes are four values to a byte, least significant first: 0, 1, 2 for x and 3 for z. */
+    LIGEIA_SHM_FOUR_LOGIC = 1,
+    /* Changes are two values to a byte, least significant first, numbered as in SVCB. */
+    LIGEIA_SHM_NINE_LOGIC = 2,
+};
+
+struct ligeia_shm;
+
+/*
+ * Create a ring buffer at `path` with room for `capacity` bytes, replacing any file that's
+ * there, for a simulation in steps of `femtoseconds_per_timestep`. Returns NULL on failure,
+ * after writing a nul-terminated message, truncated to fit, into `error`.
+ */
+struct ligeia_shm *ligeia_shm_create(const char *path, uint64_t capacity,
+                                     uint64_t femtoseconds_per_timestep, char *error,
+                                     size_t error_len);
+
+/* What went wrong with the last call that failed, valid until the next one. */
+const char *ligeia_shm_last_error(const struct ligeia_shm *shm);
+
+int ligeia_shm_scope(struct ligeia_shm *shm, uint32_t id, uint32_t parent, const char *name);
+
+/* A storage of one of `ligeia_shm_storage_type`, holding `width` values. */
+int ligeia_shm_storage(struct ligeia_shm *shm, uint32_t id, uint32_t type, uint32_t width);
+
+/* An integer variable in `scope`, held in `storage`, declared `[msb:lsb]`. */
+int ligeia_shm_integer(struct ligeia_shm *shm, uint32_t scope, const char *name,
+                       uint32_t storage, uint32_t msb, uint32_t lsb, int is_signed);
+
+/* A string variable in `scope`, held in a two-logic `storage` as UTF-8 padded with zeros. */
+int ligeia_shm_string(struct ligeia_shm *shm, uint32_t scope, const char *name,
+                      uint32_t storage);
+
+/* Changes after this are at `time`, in timesteps. Time never goes backwards. */
+int ligeia_shm_time(struct ligeia_shm *shm, uint64_t time);
+
+/* A change to `storage`, packed as its type says, exactly as many bytes as it needs. */
+int ligeia_shm_change(struct ligeia_shm *shm, uint32_t storage, const uint8_t *value,
+                      size_t len);
+
+/* Hand over changes gathered so far, rather than waiting for the next time or declaration. */
+int ligeia_shm_flush(struct ligeia_shm *shm);
+
+/* Flush, end the stream, and free `shm`, whether or not that succeeds. */
+int ligeia_shm_close(struct ligeia_shm *shm);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,169 @@
+//! The C interface in `include/ligeia_shm.h`.
+
+use std::{
+    ffi::{CStr, CString},
+    io,
+    os::raw::{c_char, c_int},
+    ptr, slice,
+};
+
+use crate::{RingWriter, StorageType, SvcbWriter, VarKind};
+
+pub struct Shm {
+    writer: SvcbWriter<RingWriter>,
+    error: CString,
+}
+
+impl Shm {
+    fn check(&mut self, result: io::Result<()>) -> c_int {
+        match result {
+            Ok(()) => 0,
+            Err(e) => {
+                self.error = message(&e);
+                -1
+            }
+        }
+    }
+}
+
+fn message(e: &io::Error) -> CString {
+    CString::new(e.to_string().replace('\0', "")).unwrap()
+}
+
+unsafe fn str_arg<'a>(s: *const c_char) -> io::Result<&'a str> {
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "name isn't valid UTF-8"))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ligeia_shm_create(
+    path: *const c_char,
+    capacity: u64,
+    femtoseconds_per_timestep: u64,
+    error: *mut c_char,
+    error_len: usize,
+) -> *mut Shm {
+    let writer = str_arg(path)
+        .and_then(|path| RingWriter::create(path, capacity))
+        .and_then(|ring| SvcbWriter::new(ring, femtoseconds_per_timestep.into()));
+    match writer {
+        Ok(writer) => Box::into_raw(Box::new(Shm {
+            writer,
+            error: CString::default(),
+        })),
+        Err(e) => {
+            if error_len > 0 {
+                let message = message(&e);
+                let bytes = message.as_bytes();
+                let len = bytes.len().min(error_len - 1);
+                ptr::copy_nonoverlapping(bytes.as_ptr() as *const c_char, error, len);
+                *error.add(len) = 0;
+            }
+            ptr::null_mut()
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ligeia_shm_last_error(shm: *const Shm) -> *const c_char {
+    (*shm).error.as_ptr()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ligeia_shm_scope(
+    shm: *mut Shm,
+    id: u32,
+    parent: u32,
+    name: *const c_char,
+) -> c_int {
+    let shm = &mut *shm;
+    let result = str_arg(name).and_then(|name| shm.writer.scope(id, parent, name));
+    shm.check(result)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ligeia_shm_storage(shm: *mut Shm, id: u32, ty: u32, width: u32) -> c_int {
+    let shm = &mut *shm;
+    let result = match ty {
+        0 => shm.writer.storage(id, StorageType::TwoLogic, width),
+        1 => shm.writer.storage(id, StorageType::FourLogic, width),
+        2 => shm.writer.storage(id, StorageType::NineLogic, width),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "unknown storage type",
+        )),
+    };
+    shm.check(result)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ligeia_shm_integer(
+    shm: *mut Shm,
+    scope: u32,
+    name: *const c_char,
+    storage: u32,
+    msb: u32,
+    lsb: u32,
+    is_signed: c_int,
+) -> c_int {
+    let shm = &mut *shm;
+    let kind = VarKind::Integer {
+        storages: &[storage],
+        msb,
+        lsb,
+        signed: is_signed != 0,
+    };
+    let result = str_arg(name).and_then(|name| shm.writer.var(scope, name, kind));
+    shm.check(result)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ligeia_shm_string(
+    shm: *mut Shm,
+    scope: u32,
+    name: *const c_char,
+    storage: u32,
+) -> c_int {
+    let shm = &mut *shm;
+    let kind = VarKind::Utf8 { storage };
+    let result = str_arg(name).and_then(|name| shm.writer.var(scope, name, kind));
+    shm.check(result)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ligeia_shm_time(shm: *mut Shm, time: u64) -> c_int {
+    let shm = &mut *shm;
+    let result = shm.writer.time(time);
+    shm.check(result)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ligeia_shm_change(
+    shm: *mut Shm,
+    storage: u32,
+    value: *const u8,
+    len: usize,
+) -> c_int {
+    let shm = &mut *shm;
+    let result = shm
+        .writer
+        .change(storage, slice::from_raw_parts(value, len));
+    shm.check(result)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ligeia_shm_flush(shm: *mut Shm) -> c_int {
+    let shm = &mut *shm;
+    let result = shm.writer.flush();
+    shm.check(result)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ligeia_shm_close(shm: *mut Shm) -> c_int {
+    // Dropping the ring once the stream is finished is what tells the reader it has ended.
+    match Box::from_raw(shm).writer.finish() {
+        Ok(_) => 0,
+        Err(_) => -1,
+    }
+}
@@ -0,0 +1,176 @@
+//! Streaming SVCB from a simulator to the viewer through a ring buffer in shared memory, for
+//! change rates where writing to a pipe spends more time in syscalls than simulating.
+//!
+//! The simulator side creates the ring with [`RingWriter::create`], usually on a tmpfs like
+//! `/dev/shm` so that it never touches a disk, and writes an SVCB stream into it with
+//! [`SvcbWriter`]. The viewer opens the same file, which it recognizes by [`RING_MAGIC`], and
+//! reads the stream out with a [`RingReader`] until the writer closes it. A memfd shared with a
+//! co-process works just as well, through [`RingWriter::from_file`] and
+//! [`RingReader::from_file`].
+//!
+//! There is one writer and one reader. Each only moves its own position forward, so nothing is
+//! locked: whichever side gets ahead spins briefly, then sleeps, until the other catches up.
+//!
+//! Plugins written in C or C++ use the same thing through `include/ligeia_shm.h`.
+
+use std::{
+    fs::File,
+    io,
+    sync::atomic::{fence, AtomicU32, AtomicU64, Ordering},
+    thread,
+    time::Duration,
+};
+
+use mapr::MmapMut;
+
+pub use crate::{
+    reader::RingReader,
+    svcb::{StorageType, SvcbWriter, VarKind},
+    writer::RingWriter,
+};
+
+mod ffi;
+mod reader;
+mod svcb;
+mod writer;
+
+/// The first bytes of a ring buffer's file.
+pub const RING_MAGIC: &[u8; 8] = b"svcbring";
+
+/// The version of the layout below.
+const VERSION: u32 = 1;
+
+// The header, with each side's position on its own cache line so that they don't contend:
+//
+//   0: magic, 8 bytes
+//   8: version, u32
+//  16: capacity of the data in bytes, u64
+//  64: bytes written so far, u64
+//  72: whether the writer is done, u32
+// 128: bytes read so far, u64
+// 136: whether the reader has gone away, u32
+// 192: the data itself
+const VERSION_OFFSET: usize = 8;
+const CAPACITY_OFFSET: usize = 16;
+const WRITTEN_OFFSET: usize = 64;
+const CLOSED_OFFSET: usize = 72;
+const READ_OFFSET: usize = 128;
+const DETACHED_OFFSET: usize = 136;
+const HEADER_LEN: usize = 192;
+
+/// A mapped ring buffer, shared by the writer and the reader.
+struct Ring {
+    map: MmapMut,
+    capacity: u64,
+}
+
+impl Ring {
+    fn data(&self) -> *mut u8 {
+        // The data is only ever accessed through raw pointers, since the other side of the ring
+        // is writing to parts of it at the same time.
+        unsafe { self.map.as_ptr().add(HEADER_LEN) as *mut u8 }
+    }
+
+    fn u64_at(&self, offset: usize) -> &AtomicU64 {
+        // The mapping is page-aligned, and every offset is a multiple of 8.
+        unsafe { &*(self.map.as_ptr().add(offset) as *const AtomicU64) }
+    }
+
+    fn u32_at(&self, offset: usize) -> &AtomicU32 {
+        unsafe { &*(self.map.as_ptr().add(offset) as *const AtomicU32) }
+    }
+
+    fn written(&self) -> &AtomicU64 {
+        self.u64_at(WRITTEN_OFFSET)
+    }
+
+    fn read(&self) -> &AtomicU64 {
+        self.u64_at(READ_OFFSET)
+    }
+
+    fn closed(&self) -> &AtomicU32 {
+        self.u32_at(CLOSED_OFFSET)
+    }
+
+    fn detached(&self) -> &AtomicU32 {
+        self.u32_at(DETACHED_OFFSET)
+    }
+
+    /// Lay out a new ring in `file`, which is truncated.
+    fn create(file: &File, capacity: u64) -> io::Result<Self> {
+        if capacity == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "a ring buffer needs room for at least one byte",
+            ));
+        }
+        file.set_len(0)?;
+        file.set_len(HEADER_LEN as u64 + capacity)?;
+        let mut map = unsafe { MmapMut::map_mut(file)? };
+        map[VERSION_OFFSET..VERSION_OFFSET + 4].copy_from_slice(&VERSION.to_ne_bytes());
+        map[CAPACITY_OFFSET..CAPACITY_OFFSET + 8].copy_from_slice(&capacity.to_ne_bytes());
+        // The magic goes in last, so a reader never sees a ring that's only partly set up.
+        fence(Ordering::Release);
+        map[..RING_MAGIC.len()].copy_from_slice(RING_MAGIC);
+        Ok(Self { map, capacity })
+    }
+
+    /// Map a ring that a writer has already laid out in `file`.
+    fn open(file: &File) -> io::Result<Self> {
+        let invalid = |message| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        if file.metadata()?.len() < HEADER_LEN as u64 {
+            return Err(invalid("too short to be a ring buffer"));
+        }
+        let map = unsafe { MmapMut::map_mut(file)? };
+        if &map[..RING_MAGIC.len()] != RING_MAGIC {
+            return Err(invalid("not a ring buffer"));
+        }
+        let version =
+            u32::from_ne_bytes(map[VERSION_OFFSET..VERSION_OFFSET + 4].try_into().unwrap());
+        if version != VERSION {
+            return Err(invalid("ring buffer is of an unknown version"));
+        }
+        let capacity = u64::from_ne_bytes(
+            map[CAPACITY_OFFSET..CAPACITY_OFFSET + 8]
+                .try_into()
+                .unwrap(),
+        );
+        if capacity == 0 || (map.len() as u64) < HEADER_LEN as u64 + capacity {
+            return Err(invalid("ring buffer is shorter than its capacity"));
+        }
+        Ok(Self { map, capacity })
+    }
+}
+
+/// Waiting for the other side of a ring: spinning at first, since it's usually only a moment,
+/// then yielding, then sleeping so that a stalled simulator doesn't burn a core.
+struct Backoff {
+    step: u32,
+}
+
+impl Backoff {
+    const SPINS: u32 = 64;
+    const YIELDS: u32 = 64;
+    const SLEEP: Duration = Duration::from_micros(100);
+
+    fn new() -> Self {
+        Self { step: 0 }
+    }
+
+    fn wait(&mut self) {
+        if self.step < Self::SPINS {
+            std::hint::spin_loop();
+        } else if self.step < Self::SPINS + Self::YIELDS {
+            thread::yield_now();
+        } else {
+            thread::sleep(Self::SLEEP);
+        }
+        self.step = self.step.saturating_add(1);
+    }
+}
+
+/// Whether `flag` has been set by the other side.
+fn is_set(flag: &AtomicU32) -> bool {
+    flag.load(Ordering::Acquire) != 0
+}
@@ -0,0 +1,85 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read},
+    path::Path,
+    ptr,
+    sync::atomic::Ordering,
+};
+
+use crate::{is_set, Backoff, Ring};
+
+/// The viewer's side of a ring buffer.
+///
+/// Reads block until the writer has written something, and reach the end of the stream once
+/// it's closed the ring and everything it wrote has been read. Dropping the reader makes the
+/// writer's next write fail, rather than waiting forever for room.
+pub struct RingReader {
+    ring: Ring,
+    read: u64,
+}
+
+impl RingReader {
+    /// Open the ring buffer a writer created at `path`.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        Self::from_file(&file)
+    }
+
+    /// Open a ring buffer in a file that's already open, like a memfd shared by the writer.
+    pub fn from_file(file: &File) -> io::Result<Self> {
+        let ring = Ring::open(file)?;
+        // Pick up where any earlier reader left off.
+        let read = ring.read().load(Ordering::Acquire);
+        Ok(Self { ring, read })
+    }
+}
+
+impl Read for RingReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let mut backoff = Backoff::new();
+        let available = loop {
+            let written = self.ring.written().load(Ordering::Acquire);
+            // The positions are in memory the writer can scribble over, so they're only trusted
+            // as far as they stay within the ring.
+            if written < self.read || written - self.read > self.ring.capacity {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "ring buffer's writer is behind the reader, or further ahead than the ring",
+                ));
+            }
+            if written > self.read {
+                break written - self.read;
+            }
+            // The writer stores its last position before closing, so once it's closed, what's
+            // written is all there'll ever be.
+            if is_set(self.ring.closed()) && self.ring.written().load(Ordering::Acquire) == written
+            {
+                return Ok(0);
+            }
+            backoff.wait();
+        };
+
+        let len = buf.len().min(available.min(self.ring.capacity) as usize);
+        let start = (self.read % self.ring.capacity) as usize;
+        let first = len.min(self.ring.capacity as usize - start);
+        unsafe {
+            let data = self.ring.data();
+            ptr::copy_nonoverlapping(data.add(start), buf.as_mut_ptr(), first);
+            ptr::copy_nonoverlapping(data, buf.as_mut_ptr().add(first), len - first);
+        }
+
+        self.read += len as u64;
+        self.ring.read().store(self.read, Ordering::Release);
+        Ok(len)
+    }
+}
+
+impl Drop for RingReader {
+    fn drop(&mut self) {
+        self.ring.detached().store(1, Ordering::Release);
+    }
+}
@@ -0,0 +1,263 @@
+//! Writing rev 1 SVCB streams, as laid out in `svcb.txt` at the root of the repository.
+
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+};
+
+const SCOPE: u8 = 0;
+const VARIABLE: u8 = 1;
+const STORAGE: u8 = 2;
+const VALUE_CHANGE: u8 = 3;
+const TIMESTEP: u8 = 4;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StorageType {
+    /// Eight values to a byte: 0 and 1.
+    TwoLogic,
+    /// Four values to a byte: 0, 1, 2 for `x` and 3 for `z`.
+    FourLogic,
+    /// Two values to a byte, numbered like `ligeia_core::logic::Nine`.
+    NineLogic,
+}
+
+impl StorageType {
+    fn code(self) -> u32 {
+        match self {
+            Self::TwoLogic => 0,
+            Self::FourLogic => 1,
+            Self::NineLogic => 2,
+        }
+    }
+
+    /// How many bytes a value `width` wide takes up.
+    pub fn bytes(self, width: u32) -> usize {
+        let per_byte = match self {
+            Self::TwoLogic => 8,
+            Self::FourLogic => 4,
+            Self::NineLogic => 2,
+        };
+        ((width + per_byte - 1) / per_byte) as usize
+    }
+}
+
+/// How a variable's storages are to be read.
+#[derive(Debug, Clone, Copy)]
+pub enum VarKind<'a> {
+    /// Just the storage, with no meaning given to it.
+    None { storage: u32 },
+    /// An integer made of `storages`, the first of which holds its least significant bits.
+    Integer {
+        storages: &'a [u32],
+        msb: u32,
+        lsb: u32,
+        signed: bool,
+    },
+    /// A two-logic storage whose values are named, with each value given as a number.
+    Enum {
+        storage: u32,
+        values: &'a [(&'a str, u64)],
+    },
+    /// A two-logic storage that holds UTF-8 text, padded out with zero bytes.
+    Utf8 { storage: u32 },
+}
+
+/// Writes an SVCB stream, gathering changes at the same time into one block.
+///
+/// Declarations have to come before anything that refers to them: a scope before its children
+/// and the variables in it, and a storage before its variables and changes. Scope 0 is the top
+/// level, and doesn't need declaring.
+pub struct SvcbWriter<W: Write> {
+    inner: W,
+    /// The type and width of each storage declared so far.
+    storages: HashMap<u32, (StorageType, u32)>,
+    time: u64,
+    changes: Vec<u8>,
+    change_count: u32,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> SvcbWriter<W> {
+    /// Start a stream whose times are in steps of `femtoseconds_per_timestep`.
+    pub fn new(mut inner: W, femtoseconds_per_timestep: u128) -> io::Result<Self> {
+        inner.write_all(b"svcb")?;
+        inner.write_all(&1u32.to_le_bytes())?;
+        inner.write_all(&femtoseconds_per_timestep.to_le_bytes())?;
+
+        Ok(Self {
+            inner,
+            storages: HashMap::new(),
+            time: 0,
+            changes: vec![],
+            change_count: 0,
+            buffer: vec![],
+        })
+    }
+
+    pub fn scope(&mut self, id: u32, parent: u32, name: &str) -> io::Result<()> {
+        if id == 0 {
+            return Err(invalid("scope 0 is the top level, and can't be declared"));
+        }
+
+        self.start(SCOPE)?;
+        put_u32(&mut self.buffer, parent);
+        put_u32(&mut self.buffer, id);
+        put_string(&mut self.buffer, name);
+        self.finish_block()
+    }
+
+    pub fn storage(&mut self, id: u32, ty: StorageType, width: u32) -> io::Result<()> {
+        if self.storages.insert(id, (ty, width)).is_some() {
+            return Err(invalid("storage declared twice"));
+        }
+
+        self.start(STORAGE)?;
+        put_u32(&mut self.buffer, id);
+        put_u32(&mut self.buffer, ty.code());
+        put_u32(&mut self.buffer, width);
+        // Where the storage starts within its variable. Storages always hold whole variables.
+        put_u32(&mut self.buffer, 0);
+        self.finish_block()
+    }
+
+    pub fn var(&mut self, scope: u32, name: &str, kind: VarKind) -> io::Result<()> {
+        self.start(VARIABLE)?;
+        put_u32(&mut self.buffer, scope);
+        put_string(&mut self.buffer, name);
+        match kind {
+            VarKind::None { storage } => {
+                put_u32(&mut self.buffer, 0);
+                put_u32(&mut self.buffer, storage);
+            }
+            VarKind::Integer {
+                storages,
+                msb,
+                lsb,
+                signed,
+            } => {
+                put_u32(&mut self.buffer, 1);
+                put_u32(&mut self.buffer, storages.len() as u32);
+                for &storage in storages {
+                    put_u32(&mut self.buffer, storage);
+                }
+                put_u32(&mut self.buffer, msb);
+                put_u32(&mut self.buffer, lsb);
+                put_u32(&mut self.buffer, if signed { 0 } else { 1 });
+            }
+            VarKind::Enum { storage, values } => {
+                let width = match self.storages.get(&storage) {
+                    Some(&(StorageType::TwoLogic, width)) => width,
+                    _ => return Err(invalid("enums need a declared two-logic storage")),
+                };
+                put_u32(&mut self.buffer, 2);
+                put_u32(&mut self.buffer, storage);
+                put_u32(&mut self.buffer, values.len() as u32);
+                for &(name, value) in values {
+                    put_string(&mut self.buffer, name);
+                    let bytes = StorageType::TwoLogic.bytes(width);
+                    let mut packed = value.to_le_bytes().to_vec();
+                    packed.resize(bytes, 0);
+                    self.buffer.extend_from_slice(&packed[..bytes]);
+                }
+            }
+            VarKind::Utf8 { storage } => {
+                put_u32(&mut self.buffer, 3);
+                put_u32(&mut self.buffer, storage);
+            }
+        }
+        self.finish_block()
+    }
+
+    /// Move on to `time`, which can't be before the last one. Changes from now on happen then.
+    pub fn time(&mut self, time: u64) -> io::Result<()> {
+        if time < self.time {
+            return Err(invalid("time went backwards"));
+        }
+        if time == self.time {
+            return Ok(());
+        }
+
+        self.flush_changes()?;
+        self.buffer.clear();
+        self.buffer.push(TIMESTEP);
+        put_leb(&mut self.buffer, time - self.time);
+        self.time = time;
+        self.inner.write_all(&self.buffer)
+    }
+
+    /// Change a storage's value, which is packed like [`StorageType`] describes and is exactly
+    /// as many bytes as the storage needs.
+    pub fn change(&mut self, storage: u32, value: &[u8]) -> io::Result<()> {
+        match self.storages.get(&storage) {
+            Some(&(ty, width)) if ty.bytes(width) == value.len() => {}
+            Some(_) => return Err(invalid("change is the wrong size for its storage")),
+            None => return Err(invalid("change to a storage that hasn't been declared")),
+        }
+
+        put_leb(&mut self.changes, storage as u64);
+        self.changes.extend_from_slice(value);
+        self.change_count += 1;
+        Ok(())
+    }
+
+    /// Write out any changes that are still being gathered, and flush the stream.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.flush_changes()?;
+        self.inner.flush()
+    }
+
+    /// Finish the stream, and get back what it was written to.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.flush()?;
+        Ok(self.inner)
+    }
+
+    fn flush_changes(&mut self) -> io::Result<()> {
+        if self.change_count == 0 {
+            return Ok(());
+        }
+
+        self.buffer.clear();
+        self.buffer.push(VALUE_CHANGE);
+        put_leb(&mut self.buffer, self.change_count as u64);
+        self.inner.write_all(&self.buffer)?;
+        self.inner.write_all(&self.changes)?;
+        self.changes.clear();
+        self.change_count = 0;
+        Ok(())
+    }
+
+    /// Start a declaration, which ends the block of changes before it.
+    fn start(&mut self, block: u8) -> io::Result<()> {
+        self.flush_changes()?;
+        self.buffer.clear();
+        self.buffer.push(block);
+        Ok(())
+    }
+
+    fn finish_block(&mut self) -> io::Result<()> {
+        self.inner.write_all(&self.buffer)
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_string(out: &mut Vec<u8>, s: &str) {
+    put_u32(out, s.len() as u32);
+    out.extend_from_slice(s.as_bytes());
+}
+
+/// Unsigned LEB128.
+fn put_leb(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
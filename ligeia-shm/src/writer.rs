@@ -0,0 +1,90 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    ptr,
+    sync::atomic::Ordering,
+};
+
+use crate::{is_set, Backoff, Ring};
+
+/// The simulator's side of a ring buffer.
+///
+/// Writes block while the ring is full, and fail with [`io::ErrorKind::BrokenPipe`] once the
+/// reader has gone away, like writing to a pipe would. Dropping the writer tells the reader that
+/// the stream has ended.
+pub struct RingWriter {
+    ring: Ring,
+    written: u64,
+}
+
+impl RingWriter {
+    /// Create a ring buffer at `path` with room for `capacity` bytes, replacing any file that's
+    /// there.
+    pub fn create(path: impl AsRef<Path>, capacity: u64) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        Self::from_file(&file, capacity)
+    }
+
+    /// Lay out a ring buffer in a file that's already open, like a memfd. Whatever was in it is
+    /// lost.
+    pub fn from_file(file: &File, capacity: u64) -> io::Result<Self> {
+        Ok(Self {
+            ring: Ring::create(file, capacity)?,
+            written: 0,
+        })
+    }
+}
+
+impl Write for RingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let mut backoff = Backoff::new();
+        let free = loop {
+            if is_set(self.ring.detached()) {
+                return Err(io::Error::new(
+                    io::ErrorKind::BrokenPipe,
+                    "the reader of the ring buffer has gone away",
+                ));
+            }
+            let read = self.ring.read().load(Ordering::Acquire);
+            let free = self.ring.capacity - (self.written - read);
+            if free > 0 {
+                break free;
+            }
+            backoff.wait();
+        };
+
+        let len = buf.len().min(free as usize);
+        let start = (self.written % self.ring.capacity) as usize;
+        // The free space may wrap around the end of the ring.
+        let first = len.min(self.ring.capacity as usize - start);
+        unsafe {
+            let data = self.ring.data();
+            ptr::copy_nonoverlapping(buf.as_ptr(), data.add(start), first);
+            ptr::copy_nonoverlapping(buf.as_ptr().add(first), data, len - first);
+        }
+
+        self.written += len as u64;
+        self.ring.written().store(self.written, Ordering::Release);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for RingWriter {
+    fn drop(&mut self) {
+        self.ring.closed().store(1, Ordering::Release);
+    }
+}
@@ -0,0 +1,116 @@
+//! Bytes make it through a ring buffer in order, however the reads and writes line up with its
+//! end, and each side finds out when the other has gone.
+
+use std::{
+    io::{ErrorKind, Read, Write},
+    thread,
+};
+
+use ligeia_shm::{RingReader, RingWriter, StorageType, SvcbWriter, VarKind, RING_MAGIC};
+
+#[test]
+fn bytes_wrap_around_in_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("wrap.ring");
+    // Much smaller than what's sent, and not a divisor of the writes, so they straddle the end.
+    let mut writer = RingWriter::create(&path, 61).unwrap();
+    let mut reader = RingReader::open(&path).unwrap();
+
+    let sent: Vec<u8> = (0..100_000u32).map(|i| (i * 7 % 251) as u8).collect();
+    let expected = sent.clone();
+    let writing = thread::spawn(move || {
+        for chunk in sent.chunks(17) {
+            writer.write_all(chunk).unwrap();
+        }
+    });
+
+    let mut received = vec![];
+    reader.read_to_end(&mut received).unwrap();
+    writing.join().unwrap();
+    assert_eq!(received, expected);
+}
+
+#[test]
+fn reader_going_away_breaks_the_writer() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("broken.ring");
+    let mut writer = RingWriter::create(&path, 8).unwrap();
+    drop(RingReader::open(&path).unwrap());
+
+    let error = writer.write_all(&[0; 16]).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::BrokenPipe);
+}
+
+#[test]
+fn only_rings_are_opened() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("not.ring");
+    std::fs::write(&path, vec![0; 1024]).unwrap();
+    let error = RingReader::open(&path).err().unwrap();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+
+    RingWriter::create(&path, 64).unwrap();
+    let header = std::fs::read(&path).unwrap();
+    assert!(header.starts_with(RING_MAGIC));
+}
+
+#[test]
+fn broken_positions_are_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("lying.ring");
+    let mut writer = RingWriter::create(&path, 64).unwrap();
+    writer.write_all(&[1; 8]).unwrap();
+    drop(writer);
+
+    // Claim more has been written than the ring holds, as a broken writer could. Bytes written
+    // so far are at 64 in the header.
+    let mut header = std::fs::read(&path).unwrap();
+    header[64..72].copy_from_slice(&1000u64.to_ne_bytes());
+    std::fs::write(&path, header).unwrap();
+
+    let mut reader = RingReader::open(&path).unwrap();
+    let error = reader.read(&mut [0; 4096]).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+}
+
+#[test]
+fn svcb_blocks() {
+    let mut writer = SvcbWriter::new(vec![], 1000).unwrap();
+    writer.scope(1, 0, "top").unwrap();
+    writer.storage(0, StorageType::FourLogic, 3).unwrap();
+    writer
+        .var(
+            1,
+            "bus",
+            VarKind::Integer {
+                storages: &[0],
+                msb: 2,
+                lsb: 0,
+                signed: false,
+            },
+        )
+        .unwrap();
+    writer.change(0, &[0b10_01_00]).unwrap();
+    writer.time(200).unwrap();
+    writer.change(0, &[0b11_11_11]).unwrap();
+    writer.change(0, &[0]).unwrap();
+
+    assert!(writer.change(0, &[0, 0]).is_err());
+    assert!(writer.change(1, &[0]).is_err());
+    assert!(writer.time(100).is_err());
+
+    let stream = writer.finish().unwrap();
+    let mut expected = b"svcb".to_vec();
+    expected.extend_from_slice(&1u32.to_le_bytes());
+    expected.extend_from_slice(&1000u128.to_le_bytes());
+    // The scope, storage and variable.
+    expected.extend_from_slice(&[0, 0, 0, 0, 0, 1, 0, 0, 0, 3, 0, 0, 0, b't', b'o', b'p']);
+    expected.extend_from_slice(&[2, 0, 0, 0, 0, 1, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0]);
+    expected.extend_from_slice(&[1, 1, 0, 0, 0, 3, 0, 0, 0, b'b', b'u', b's', 1, 0, 0, 0]);
+    expected.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0]);
+    // One change, then 200 timesteps later in LEB128, then the two changes then.
+    expected.extend_from_slice(&[3, 1, 0, 0b10_01_00]);
+    expected.extend_from_slice(&[4, 0xc8, 0x01]);
+    expected.extend_from_slice(&[3, 2, 0, 0b11_11_11, 0, 0]);
+    assert_eq!(stream, expected);
+}
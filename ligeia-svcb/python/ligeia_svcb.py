@@ -0,0 +1,208 @@
+"""Writing rev 1 SVCB streams, as laid out in `svcb.txt` at the root of the ligeia repository.
+
+This is the Python twin of `SvcbWriter` in `ligeia-shm`, and writes byte for byte the same
+stream given the same calls, which `ligeia-svcb/tests/python.rs` checks. It has no dependencies,
+so a cocotb testbench can trace straight to SVCB by declaring what it wants to see up front and
+handing over values as they change:
+
+    with open("dut.svcb", "wb") as f, SvcbWriter(f, femtoseconds_per_timestep=1000) as svcb:
+        svcb.scope(1, 0, "dut")
+        svcb.storage(0, FOUR_LOGIC, 8)
+        svcb.var_integer(1, "data", [0], 7, 0)
+        ...
+        svcb.time(get_sim_time("ns"))
+        svcb.change(0, pack(FOUR_LOGIC, 8, str(dut.data.value)))
+
+Files can be checked with `ligeia-svcb-validate`.
+"""
+
+import struct
+
+__all__ = ["SvcbWriter", "pack", "storage_bytes", "TWO_LOGIC", "FOUR_LOGIC", "NINE_LOGIC"]
+
+_SCOPE = 0
+_VARIABLE = 1
+_STORAGE = 2
+_VALUE_CHANGE = 3
+_TIMESTEP = 4
+
+# Eight values to a byte: 0 and 1.
+TWO_LOGIC = 0
+# Four values to a byte: 0, 1, 2 for `x` and 3 for `z`.
+FOUR_LOGIC = 1
+# Two values to a byte, numbered like `ligeia_core::logic::Nine`.
+NINE_LOGIC = 2
+
+_PER_BYTE = {TWO_LOGIC: 8, FOUR_LOGIC: 4, NINE_LOGIC: 2}
+
+# The characters each logic's values are written as, as `ligeia` shows them. `u`, `-` and `U`
+# from `std_logic` are taken as unknown.
+_SYMBOLS = {
+    TWO_LOGIC: {"0": 0, "1": 1},
+    FOUR_LOGIC: {"0": 0, "1": 1, "x": 2, "u": 2, "-": 2, "z": 3},
+    NINE_LOGIC: {
+        "0": 0, "1": 1, "L": 2, "H": 3, "X": 4, "U": 4, "-": 4, "W": 5, "l": 6, "h": 7,
+        "Z": 8, "x": 4, "u": 4, "w": 5, "z": 8,
+    },
+}
+
+
+def storage_bytes(ty, width):
+    """How many bytes a value `width` wide takes up."""
+    per_byte = _PER_BYTE[ty]
+    return (width + per_byte - 1) // per_byte
+
+
+def pack(ty, width, value):
+    """Pack a value for a storage, from a non-negative integer or from a string of characters
+    most significant first, like `str(handle.value)` in cocotb.
+
+    Strings shorter than the storage are zero-extended.
+    """
+    if isinstance(value, int):
+        if value < 0:
+            raise ValueError("values are packed from non-negative integers")
+        value = format(value, "b")
+    if len(value) > width:
+        raise ValueError("`%s` is wider than %d bits" % (value, width))
+
+    symbols = _SYMBOLS[ty]
+    if ty != NINE_LOGIC:
+        value = value.lower()
+    bits = 8 // _PER_BYTE[ty]
+    out = bytearray(storage_bytes(ty, width))
+    for i, char in enumerate(reversed(value)):
+        try:
+            level = symbols[char]
+        except KeyError:
+            raise ValueError("`%s` isn't a value of this logic" % char) from None
+        out[i * bits // 8] |= level << (i * bits % 8)
+    return bytes(out)
+
+
+class SvcbWriter:
+    """Writes an SVCB stream to a binary file, gathering changes at the same time into one block.
+
+    Declarations have to come before anything that refers to them: a scope before its children
+    and the variables in it, and a storage before its variables and changes. Scope 0 is the top
+    level, and doesn't need declaring.
+    """
+
+    def __init__(self, out, femtoseconds_per_timestep):
+        self._out = out
+        # The type and width of each storage declared so far.
+        self._storages = {}
+        self._time = 0
+        self._changes = bytearray()
+        self._change_count = 0
+
+        out.write(b"svcb")
+        out.write(struct.pack("<I", 1))
+        out.write(femtoseconds_per_timestep.to_bytes(16, "little"))
+
+    def __enter__(self):
+        return self
+
+    def __exit__(self, *exc):
+        self.flush()
+
+    def scope(self, id, parent, name):
+        if id == 0:
+            raise ValueError("scope 0 is the top level, and can't be declared")
+        self._block(_SCOPE, _u32(parent) + _u32(id) + _string(name))
+
+    def storage(self, id, ty, width):
+        if ty not in _PER_BYTE:
+            raise ValueError("unknown storage type %r" % ty)
+        if id in self._storages:
+            raise ValueError("storage declared twice")
+        self._storages[id] = (ty, width)
+        # Where the storage starts within its variable. Storages always hold whole variables.
+        self._block(_STORAGE, _u32(id) + _u32(ty) + _u32(width) + _u32(0))
+
+    def var_none(self, scope, name, storage):
+        """Just the storage, with no meaning given to it."""
+        self._block(_VARIABLE, _u32(scope) + _string(name) + _u32(0) + _u32(storage))
+
+    def var_integer(self, scope, name, storages, msb, lsb, signed=False):
+        """An integer made of `storages`, the first of which holds its least significant bits."""
+        block = _u32(scope) + _string(name) + _u32(1) + _u32(len(storages))
+        for storage in storages:
+            block += _u32(storage)
+        block += _u32(msb) + _u32(lsb) + _u32(0 if signed else 1)
+        self._block(_VARIABLE, block)
+
+    def var_enum(self, scope, name, storage, values):
+        """A two-logic storage whose values are named, from a list of names and numbers."""
+        ty, width = self._storages.get(storage, (None, 0))
+        if ty != TWO_LOGIC:
+            raise ValueError("enums need a declared two-logic storage")
+        size = storage_bytes(TWO_LOGIC, width)
+        block = _u32(scope) + _string(name) + _u32(2) + _u32(storage) + _u32(len(values))
+        for value_name, value in values:
+            block += _string(value_name)
+            block += value.to_bytes(8, "little").ljust(size, b"\0")[:size]
+        self._block(_VARIABLE, block)
+
+    def var_utf8(self, scope, name, storage):
+        """A two-logic storage that holds UTF-8 text, padded out with zero bytes."""
+        self._block(_VARIABLE, _u32(scope) + _string(name) + _u32(3) + _u32(storage))
+
+    def time(self, time):
+        """Move on to `time`, which can't be before the last one. Changes from now on happen
+        then."""
+        if time < self._time:
+            raise ValueError("time went backwards")
+        if time == self._time:
+            return
+        self._flush_changes()
+        self._out.write(bytes([_TIMESTEP]) + _leb(time - self._time))
+        self._time = time
+
+    def change(self, storage, value):
+        """Change a storage's value, which is packed like `pack` does it and is exactly as many
+        bytes as the storage needs."""
+        if storage not in self._storages:
+            raise ValueError("change to a storage that hasn't been declared")
+        if storage_bytes(*self._storages[storage]) != len(value):
+            raise ValueError("change is the wrong size for its storage")
+        self._changes += _leb(storage)
+        self._changes += value
+        self._change_count += 1
+
+    def flush(self):
+        """Write out any changes that are still being gathered, and flush the file."""
+        self._flush_changes()
+        self._out.flush()
+
+    def _flush_changes(self):
+        if self._change_count == 0:
+            return
+        self._out.write(bytes([_VALUE_CHANGE]) + _leb(self._change_count))
+        self._out.write(bytes(self._changes))
+        self._changes.clear()
+        self._change_count = 0
+
+    def _block(self, ty, block):
+        # A declaration ends the block of changes before it.
+        self._flush_changes()
+        self._out.write(bytes([ty]) + block)
+
+
+def _u32(value):
+    return struct.pack("<I", value)
+
+
+def _string(s):
+    encoded = s.encode("utf-8")
+    return _u32(len(encoded)) + encoded
+
+
+def _leb(value):
+    """Unsigned LEB128."""
+    out = bytearray()
+    while value >= 0x80:
+        out.append(value & 0x7F | 0x80)
+        value >>= 7
+    out.append(value)
+    return bytes(out)
@@ -0,0 +1,16 @@
+//! Check that SVCB files load, for anyone writing them from somewhere other than ligeia, like
+//! the Python writer in `ligeia-svcb/python`. `ligeia svcb-validate` does the same.
+
+use std::{env, process};
+
+fn main() {
+    let paths: Vec<_> = env::args_os().skip(1).collect();
+    if paths.is_empty() {
+        eprintln!("usage: ligeia-svcb-validate <file>...");
+        process::exit(2);
+    }
+
+    if !ligeia_svcb::validate_all(&paths) {
+        process::exit(1);
+    }
+}
@@ -1 +1,599 @@
-// mod svcb;
+//! Loading Streamed Value Change Blocks (SVCB), the format laid out in `svcb.txt` at the root of
+//! the repository.
+//!
+//! SVCB is written by simulators as they run, so it's read a block at a time from any stream:
+//! a file, a pipe, or a ring buffer in shared memory written through `ligeia-shm`, which is
+//! opened like any other file and read until the simulator closes it.
+
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufReader, Read},
+    path::Path,
+};
+
+use fnv::FnvHashMap;
+use ligeia_core::{
+    logic,
+    meta::{self, ScopeId, StorageId, Timesteps},
+    Ingestor, IngestorOptions, Processed, Warning,
+};
+use ligeia_formats::position::{Position, Tracking};
+use ligeia_shm::RingReader;
+
+pub use crate::loader::{register, SvcbLoader};
+
+mod loader;
+
+const SCOPE: u8 = 0;
+const VARIABLE: u8 = 1;
+const STORAGE: u8 = 2;
+const VALUE_CHANGE: u8 = 3;
+const TIMESTEP: u8 = 4;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("an i/o error occured")]
+    Io(#[from] io::Error),
+    #[error("at {position}: {reason}")]
+    Invalid { position: Position, reason: Reason },
+    #[error("{0}")]
+    Core(#[from] ligeia_core::Error),
+}
+
+impl Error {
+    /// Why the stream isn't valid, if that's what went wrong.
+    pub fn reason(&self) -> Option<&Reason> {
+        match self {
+            Self::Invalid { reason, .. } => Some(reason),
+            _ => None,
+        }
+    }
+
+    /// The byte the stream stopped being valid at, if that's what went wrong.
+    pub fn offset(&self) -> Option<u64> {
+        match self {
+            Self::Invalid { position, .. } => Some(position.offset),
+            _ => None,
+        }
+    }
+}
+
+/// Why a stream isn't valid SVCB.
+#[derive(Debug, thiserror::Error)]
+pub enum Reason {
+    #[error("not an SVCB stream")]
+    NotSvcb,
+    #[error("this is version {0} of SVCB, but only version 1 is supported")]
+    Version(u32),
+    #[error("the stream ended partway through a block")]
+    Truncated,
+    #[error("unknown block type {0}")]
+    UnknownBlock(u8),
+    #[error("scope `{0}` has id 0, which belongs to the top level")]
+    RootScope(String),
+    #[error("storage {0} has an unknown type {1}")]
+    UnknownStorageType(u32, u32),
+    #[error("storage {0} starts at {1}, but only storages that start at 0 are supported")]
+    StorageStart(u32, u32),
+    #[error("storage {0} is declared twice")]
+    DuplicateStorage(u32),
+    #[error("storage {0} is used before it's declared")]
+    UnknownStorage(u32),
+    #[error("variable `{0}` has an unknown interpretation {1}")]
+    UnknownInterpretation(String, u32),
+    #[error("variable `{0}` has an unknown signedness {1}")]
+    UnknownSignedness(String, u32),
+    #[error("text variable `{0}` needs its storage to be two-logic, and declared before changes")]
+    TextStorage(String),
+    #[error("a name isn't valid UTF-8")]
+    InvalidUtf8,
+    #[error("a number is too big for its field")]
+    Overflow,
+}
+
+/// The excerpt is filled in by [`load_svcb`], which has the bytes.
+fn invalid(offset: u64, reason: Reason) -> Error {
+    Error::Invalid {
+        position: Position {
+            offset,
+            line: None,
+            excerpt: String::new(),
+        },
+        reason,
+    }
+}
+
+/// The types of storage, by how many values of each logic there are to a byte.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Logic {
+    Two,
+    Four,
+    Nine,
+}
+
+impl Logic {
+    fn bytes(self, width: u32) -> usize {
+        let per_byte = match self {
+            Self::Two => 8,
+            Self::Four => 4,
+            Self::Nine => 2,
+        };
+        ((width as u64 + per_byte - 1) / per_byte) as usize
+    }
+}
+
+struct Storage {
+    logic: Logic,
+    width: u32,
+    /// Whether a text variable is held in it, so that it's ingested as UTF-8.
+    text: bool,
+    /// Storages are only handed to the ingestor once changes start, so that text variables
+    /// declared after their storages can still change how they're stored.
+    ingested: bool,
+}
+
+/// Load an SVCB stream with `options`, replacing their timescale with the stream's own.
+pub fn load_svcb<R: Read>(reader: R, options: IngestorOptions) -> Result<Processed, Error> {
+    let mut input = Input {
+        reader: Tracking::new(BufReader::new(reader)),
+    };
+    read(&mut input, options).map_err(|e| match e {
+        Error::Invalid { position, reason } => Error::Invalid {
+            position: input.reader.binary_position(position.offset),
+            reason,
+        },
+        e => e,
+    })
+}
+
+fn read<R: Read>(input: &mut Input<R>, options: IngestorOptions) -> Result<Processed, Error> {
+    let mut magic = [0; 4];
+    input.bytes(&mut magic)?;
+    if &magic != b"svcb" {
+        return Err(invalid(0, Reason::NotSvcb));
+    }
+    let version = input.u32()?;
+    if version != 1 {
+        return Err(invalid(4, Reason::Version(version)));
+    }
+    let femtoseconds_per_timestep = input.u128()?;
+
+    let mut state = State {
+        ingestor: Ingestor::new(options.with_timescale(femtoseconds_per_timestep))?,
+        storages: FnvHashMap::default(),
+        changing: false,
+        time: 0,
+        value: vec![],
+        buffer: vec![],
+    };
+    loop {
+        let at = input.offset();
+        let block = match input.block_type()? {
+            Some(block) => block,
+            None => break,
+        };
+        match block {
+            SCOPE => state.scope(input)?,
+            VARIABLE => state.var(input)?,
+            STORAGE => state.storage(input)?,
+            VALUE_CHANGE => {
+                state.start_changes();
+                let count = input.leb(32)?;
+                for _ in 0..count {
+                    state.change(input)?;
+                }
+            }
+            TIMESTEP => {
+                state.start_changes();
+                let delta = input.leb(64)?;
+                state.time = state
+                    .time
+                    .checked_add(delta)
+                    .ok_or_else(|| invalid(at + 1, Reason::Overflow))?;
+                state.ingestor.ingest_timestep(Timesteps(state.time));
+            }
+            _ => return Err(invalid(at, Reason::UnknownBlock(block))),
+        }
+    }
+
+    state.start_changes();
+    Ok(state.ingestor.finish()?)
+}
+
+/// Load the SVCB stream a simulator is writing into the ring buffer at `path`, which finishes
+/// once the simulator closes it.
+pub fn load_ring(path: &Path, options: IngestorOptions) -> Result<Processed, Error> {
+    load_svcb(RingReader::open(path)?, options)
+}
+
+/// What's in a valid SVCB file, from [`validate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Summary {
+    pub storages: usize,
+    pub changes: u64,
+    pub first: Timesteps,
+    pub last: Timesteps,
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} storages, {} changes, timesteps {} to {}",
+            self.storages, self.changes, self.first.0, self.last.0
+        )
+    }
+}
+
+/// Check that the SVCB file or ring buffer at `path` loads, for anyone writing SVCB from
+/// somewhere other than ligeia.
+pub fn validate(path: &Path) -> Result<Summary, Error> {
+    let processed = if is_ring(path)? {
+        load_ring(path, IngestorOptions::new())?
+    } else {
+        load_svcb(File::open(path)?, IngestorOptions::new())?
+    };
+
+    let storage_ids = processed.storage_ids();
+    let changes = storage_ids
+        .iter()
+        .map(|&id| processed.change_count(id))
+        .sum::<Result<u64, _>>()?;
+    let (first, last) = processed.time_bounds();
+    Ok(Summary {
+        storages: storage_ids.len(),
+        changes,
+        first,
+        last,
+    })
+}
+
+/// [`validate`] each of `paths`, printing a line about each to stdout, and say whether they all
+/// loaded. This is the whole of `ligeia svcb-validate` and `ligeia-svcb-validate`.
+pub fn validate_all(paths: &[impl AsRef<Path>]) -> bool {
+    let mut valid = true;
+    for path in paths {
+        let path = path.as_ref();
+        match validate(path) {
+            Ok(summary) => println!("{}: ok, {}", path.display(), summary),
+            Err(e) => {
+                println!("{}: {}", path.display(), e);
+                valid = false;
+            }
+        }
+    }
+    valid
+}
+
+/// Whether the file at `path` is a ring buffer from `ligeia-shm`, rather than SVCB itself.
+pub fn is_ring(path: &Path) -> Result<bool, Error> {
+    let mut magic = [0; ligeia_shm::RING_MAGIC.len()];
+    match File::open(path)?.read_exact(&mut magic) {
+        Ok(()) => Ok(&magic == ligeia_shm::RING_MAGIC),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+struct Input<R> {
+    /// Errors are reported relative to how far this has read.
+    reader: Tracking<BufReader<R>>,
+}
+
+impl<R: Read> Input<R> {
+    fn offset(&self) -> u64 {
+        self.reader.offset()
+    }
+
+    fn bytes(&mut self, buffer: &mut [u8]) -> Result<(), Error> {
+        let at = self.offset();
+        self.reader.read_exact(buffer).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => invalid(at, Reason::Truncated),
+            _ => e.into(),
+        })
+    }
+
+    /// The type of the next block, or `None` if the stream ended cleanly before it.
+    fn block_type(&mut self) -> Result<Option<u8>, Error> {
+        let mut byte = [0];
+        loop {
+            match self.reader.read(&mut byte) {
+                Ok(0) => return Ok(None),
+                Ok(_) => return Ok(Some(byte[0])),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        let mut bytes = [0; 4];
+        self.bytes(&mut bytes)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn u128(&mut self) -> Result<u128, Error> {
+        let mut bytes = [0; 16];
+        self.bytes(&mut bytes)?;
+        Ok(u128::from_le_bytes(bytes))
+    }
+
+    /// An unsigned LEB128 number that fits in `bits`.
+    fn leb(&mut self, bits: u32) -> Result<u64, Error> {
+        let at = self.offset();
+        let mut value = 0u64;
+        let mut shift = 0;
+        loop {
+            let mut byte = [0];
+            self.bytes(&mut byte)?;
+            let low = (byte[0] & 0x7f) as u64;
+            if shift >= 64 || (low << shift) >> shift != low {
+                return Err(invalid(at, Reason::Overflow));
+            }
+            value |= low << shift;
+            if byte[0] & 0x80 == 0 {
+                break;
+            }
+            shift += 7;
+        }
+        if bits < 64 && value >> bits != 0 {
+            return Err(invalid(at, Reason::Overflow));
+        }
+        Ok(value)
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        let len = self.u32()?;
+        let at = self.offset();
+        // The length isn't trusted until there's that much to read, so the string only grows as
+        // it's read rather than being allocated up front.
+        let mut bytes = vec![];
+        (&mut self.reader)
+            .take(len as u64)
+            .read_to_end(&mut bytes)?;
+        if bytes.len() != len as usize {
+            return Err(invalid(at, Reason::Truncated));
+        }
+        String::from_utf8(bytes).map_err(|_| invalid(at, Reason::InvalidUtf8))
+    }
+}
+
+struct State {
+    ingestor: Ingestor,
+    storages: FnvHashMap<u32, Storage>,
+    /// Whether any changes or timesteps have been read yet.
+    changing: bool,
+    time: u64,
+    value: Vec<u8>,
+    buffer: Vec<u8>,
+}
+
+impl State {
+    fn scope<R: Read>(&mut self, input: &mut Input<R>) -> Result<(), Error> {
+        let parent = input.u32()?;
+        let at = input.offset();
+        let id = input.u32()?;
+        let name = input.string()?;
+        if id == 0 {
+            return Err(invalid(at, Reason::RootScope(name)));
+        }
+
+        let name = self.ingestor.intern(&name);
+        self.ingestor.ingest_scope(meta::Scope {
+            name,
+            id: ScopeId(id),
+            parent: ScopeId(parent),
+        });
+        Ok(())
+    }
+
+    fn storage<R: Read>(&mut self, input: &mut Input<R>) -> Result<(), Error> {
+        let at = input.offset();
+        let id = input.u32()?;
+        let logic = match input.u32()? {
+            0 => Logic::Two,
+            1 => Logic::Four,
+            2 => Logic::Nine,
+            ty => return Err(invalid(at + 4, Reason::UnknownStorageType(id, ty))),
+        };
+        let width = input.u32()?;
+        let start = input.u32()?;
+        if start != 0 {
+            return Err(invalid(at + 12, Reason::StorageStart(id, start)));
+        }
+        if self.storages.contains_key(&id) {
+            return Err(invalid(at, Reason::DuplicateStorage(id)));
+        }
+
+        self.storages.insert(
+            id,
+            Storage {
+                logic,
+                width,
+                text: false,
+                ingested: false,
+            },
+        );
+        if self.changing {
+            self.ingest_storage(id);
+        }
+        Ok(())
+    }
+
+    fn var<R: Read>(&mut self, input: &mut Input<R>) -> Result<(), Error> {
+        let scope_id = ScopeId(input.u32()?);
+        let name = input.string()?;
+
+        let at = input.offset();
+        let kind = match input.u32()? {
+            // Storages without any interpretation are shown as the bits they hold.
+            0 => {
+                let storage = input.u32()?;
+                let width = self.declared(storage, at + 4)?.width;
+                meta::VarKind::Integer {
+                    storages: vec![StorageId(storage)],
+                    msb_index: width.saturating_sub(1),
+                    lsb_index: 0,
+                    signedness: meta::Signedness::Unsigned,
+                }
+            }
+            1 => {
+                let count = input.u32()?;
+                let storages = (0..count)
+                    .map(|_| Ok(StorageId(input.u32()?)))
+                    .collect::<Result<_, Error>>()?;
+                let msb_index = input.u32()?;
+                let lsb_index = input.u32()?;
+                let signedness_at = input.offset();
+                let signedness = match input.u32()? {
+                    0 => meta::Signedness::SignedTwosComplement,
+                    1 => meta::Signedness::Unsigned,
+                    signedness => {
+                        return Err(invalid(
+                            signedness_at,
+                            Reason::UnknownSignedness(name, signedness),
+                        ))
+                    }
+                };
+                meta::VarKind::Integer {
+                    storages,
+                    msb_index,
+                    lsb_index,
+                    signedness,
+                }
+            }
+            2 => {
+                let storage = input.u32()?;
+                let width = self.declared(storage, at + 4)?.width;
+                let count = input.u32()?;
+                // Grown as values are read, since the count could be anything.
+                let mut values = vec![];
+                for _ in 0..count {
+                    let name = input.string()?;
+                    self.value.resize(Logic::Two.bytes(width), 0);
+                    input.bytes(&mut self.value)?;
+                    logic::unpack_two(&self.value, width as usize, &mut self.buffer);
+                    values.push(meta::EnumValue {
+                        name,
+                        value: self.buffer.iter().map(|&bit| bit == 1).collect(),
+                    });
+                }
+                meta::VarKind::Enum {
+                    storage: StorageId(storage),
+                    values,
+                }
+            }
+            3 => {
+                let storage = input.u32()?;
+                match self.storages.get_mut(&storage) {
+                    Some(declared) if declared.logic == Logic::Two && !declared.ingested => {
+                        declared.text = true
+                    }
+                    Some(_) => return Err(invalid(at + 4, Reason::TextStorage(name))),
+                    None => return Err(invalid(at + 4, Reason::UnknownStorage(storage))),
+                }
+                meta::VarKind::Utf8 {
+                    storage: StorageId(storage),
+                }
+            }
+            interpretation => {
+                return Err(invalid(
+                    at,
+                    Reason::UnknownInterpretation(name, interpretation),
+                ))
+            }
+        };
+
+        let name = self.ingestor.intern(&name);
+        self.ingestor.ingest_var(meta::Var {
+            name,
+            scope_id,
+            kind,
+            source: None,
+        });
+        Ok(())
+    }
+
+    /// The storage `storage`, whose id was read at `at`.
+    fn declared(&self, storage: u32, at: u64) -> Result<&Storage, Error> {
+        self.storages
+            .get(&storage)
+            .ok_or_else(|| invalid(at, Reason::UnknownStorage(storage)))
+    }
+
+    /// Hand the storages declared so far to the ingestor, the first time changes are read.
+    fn start_changes(&mut self) {
+        if self.changing {
+            return;
+        }
+        self.changing = true;
+        // Streams start at time 0, so changes before the first timestep happen then.
+        self.ingestor.ingest_timestep(Timesteps(0));
+
+        let mut ids: Vec<u32> = self.storages.keys().copied().collect();
+        ids.sort_unstable();
+        for id in ids {
+            self.ingest_storage(id);
+        }
+        for scope in self.ingestor.crowded_scopes() {
+            self.ingestor.warn(Warning::Crowded(scope));
+        }
+    }
+
+    fn ingest_storage(&mut self, id: u32) {
+        let storage = self.storages.get_mut(&id).unwrap();
+        storage.ingested = true;
+        let ty = match (storage.logic, storage.text) {
+            (_, true) => meta::StorageType::Utf8,
+            (Logic::Two, false) => meta::StorageType::TwoLogic,
+            (Logic::Four, false) => meta::StorageType::FourLogic,
+            (Logic::Nine, false) => meta::StorageType::NineLogic,
+        };
+        self.ingestor.ingest_storage(meta::Storage {
+            id: StorageId(id),
+            ty,
+            width: storage.width,
+            start: 0,
+        });
+    }
+
+    fn change<R: Read>(&mut self, input: &mut Input<R>) -> Result<(), Error> {
+        let at = input.offset();
+        let id = input.leb(32)? as u32;
+        let storage = self.declared(id, at)?;
+        let (logic, width, text) = (storage.logic, storage.width, storage.text);
+
+        self.value.resize(logic.bytes(width), 0);
+        input.bytes(&mut self.value)?;
+        let data = match logic {
+            // Text is padded out to the width of its storage with zeros.
+            _ if text => {
+                let len = self
+                    .value
+                    .iter()
+                    .position(|&byte| byte == 0)
+                    .unwrap_or(self.value.len());
+                &self.value[..len]
+            }
+            Logic::Two | Logic::Four => &self.value[..],
+            // Storages hold one nine-logic value per byte rather than two.
+            Logic::Nine => {
+                self.buffer.clear();
+                self.buffer.extend(
+                    self.value
+                        .iter()
+                        .flat_map(|&byte| [byte & 0xf, byte >> 4])
+                        .take(width as usize),
+                );
+                &self.buffer[..]
+            }
+        };
+
+        self.ingestor.ingest_value(ligeia_core::Value {
+            storage_id: StorageId(id),
+            data,
+        })?;
+        Ok(())
+    }
+}
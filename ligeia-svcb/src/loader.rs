@@ -0,0 +1,54 @@
+use std::{error::Error, fs::File, io::Read, path::Path};
+
+use ligeia_core::{IngestorOptions, Processed};
+use ligeia_formats::{LoaderRegistry, WaveformLoader, SNIFF_LEN};
+use ligeia_shm::RING_MAGIC;
+
+use crate::{is_ring, load_ring, load_svcb};
+
+pub struct SvcbLoader;
+
+impl WaveformLoader for SvcbLoader {
+    fn description(&self) -> String {
+        "the Streamed Value Change Blocks (SVCB) loader".to_string()
+    }
+
+    fn supports_file_extension(&self, s: &str) -> bool {
+        s.eq_ignore_ascii_case("svcb")
+    }
+
+    /// Ring buffers start with `svcb` too, so they're recognized along with the streams in them.
+    fn sniff(&self, header: &[u8; SNIFF_LEN]) -> bool {
+        header.starts_with(b"svcb")
+    }
+
+    fn load_stream(
+        &self,
+        reader: &mut dyn Read,
+        options: &IngestorOptions,
+    ) -> Result<Processed, Box<dyn Error>> {
+        let mut magic = [0; RING_MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if &magic == RING_MAGIC {
+            return Err("ring buffers can only be loaded from a file, since they're shared".into());
+        }
+        Ok(load_svcb(magic.chain(reader), options.clone())?)
+    }
+
+    fn load_file(
+        &self,
+        path: &Path,
+        options: &IngestorOptions,
+    ) -> Result<Processed, Box<dyn Error>> {
+        if is_ring(path)? {
+            Ok(load_ring(path, options.clone())?)
+        } else {
+            Ok(load_svcb(File::open(path)?, options.clone())?)
+        }
+    }
+}
+
+/// Add the SVCB loader to `registry`.
+pub fn register(registry: &mut LoaderRegistry) {
+    registry.register(SvcbLoader);
+}
@@ -0,0 +1,60 @@
+//! The conformance corpus in `tests/corpus`, which `generate.py` there writes: every valid stream
+//! loads, and every invalid one is refused for the reason, and at the byte, it's expected to be.
+
+use std::{collections::HashSet, fs, mem, path::Path};
+
+use ligeia_svcb::validate;
+
+fn corpus(directory: &str) -> Vec<std::path::PathBuf> {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/corpus")
+        .join(directory);
+    let mut paths: Vec<_> = fs::read_dir(directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().map_or(false, |ext| ext == "svcb"))
+        .collect();
+    paths.sort();
+    paths
+}
+
+#[test]
+fn valid_streams_load() {
+    let paths = corpus("valid");
+    assert!(!paths.is_empty());
+    for path in paths {
+        if let Err(e) = validate(&path) {
+            panic!("{} didn't load: {}", path.display(), e);
+        }
+    }
+}
+
+#[test]
+fn invalid_streams_are_refused() {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus/invalid");
+    let expected = fs::read_to_string(directory.join("expected.txt")).unwrap();
+
+    let mut reasons = HashSet::new();
+    let mut checked = 0;
+    for line in expected.lines() {
+        let (name, message) = line.split_once(": ").unwrap();
+        let error = validate(&directory.join(name)).err().unwrap();
+        let (offset, reason) = (error.offset().unwrap(), error.reason().unwrap());
+        assert_eq!(
+            format!("at byte {}: {}", offset, reason),
+            message,
+            "for {}",
+            name
+        );
+        reasons.insert(mem::discriminant(reason));
+        checked += 1;
+    }
+
+    assert_eq!(
+        checked,
+        corpus("invalid").len(),
+        "not every stream is in expected.txt"
+    );
+    // One for each variant of `Reason`.
+    assert_eq!(reasons.len(), 14);
+}
@@ -0,0 +1,199 @@
+"""Write the SVCB conformance corpus: small streams in `valid/` that every reader should load,
+and in `invalid/` that every reader should refuse, with why and where in `invalid/expected.txt`.
+
+Run it from anywhere to regenerate the corpus, after changing the format or adding a case:
+
+    python3 ligeia-svcb/tests/corpus/generate.py
+"""
+
+import os
+import struct
+import sys
+
+HERE = os.path.dirname(os.path.abspath(__file__))
+sys.path.insert(0, os.path.join(HERE, "..", "..", "python"))
+
+from ligeia_svcb import FOUR_LOGIC, NINE_LOGIC, TWO_LOGIC, SvcbWriter, pack  # noqa: E402
+
+
+class Buffer:
+    """A file-like sink for `SvcbWriter`, which can have raw bytes added to it."""
+
+    def __init__(self):
+        self.data = bytearray()
+
+    def write(self, data):
+        self.data += data
+
+    def flush(self):
+        pass
+
+
+def u32(value):
+    return struct.pack("<I", value)
+
+
+def string(s):
+    return u32(len(s)) + s
+
+
+HEADER = b"svcb" + u32(1) + (1000).to_bytes(16, "little")
+
+
+def every_block():
+    """Every block type, storage type and interpretation."""
+    out = Buffer()
+    with SvcbWriter(out, 1000) as w:
+        w.scope(1, 0, "top")
+        w.scope(2, 1, "fsm")
+        w.storage(0, TWO_LOGIC, 1)
+        w.storage(1, FOUR_LOGIC, 4)
+        w.storage(2, NINE_LOGIC, 3)
+        w.storage(3, TWO_LOGIC, 2)
+        w.storage(4, TWO_LOGIC, 32)
+        w.var_integer(1, "clk", [0], 0, 0)
+        w.var_integer(1, "data", [1], 3, 0, signed=True)
+        w.var_none(1, "pad", 2)
+        w.var_enum(2, "state", 3, [("IDLE", 0), ("BUSY", 1), ("DONE", 2)])
+        w.var_utf8(2, "label", 4)
+        w.change(3, pack(TWO_LOGIC, 2, 0))
+        w.change(4, b"idle")
+        for cycle in range(4):
+            w.time(cycle * 10)
+            w.change(0, pack(TWO_LOGIC, 1, 1))
+            w.change(1, pack(FOUR_LOGIC, 4, "10xz" if cycle % 2 == 0 else "0101"))
+            w.time(cycle * 10 + 5)
+            w.change(0, pack(TWO_LOGIC, 1, 0))
+        w.change(2, pack(NINE_LOGIC, 3, "1ZX"))
+        w.change(3, pack(TWO_LOGIC, 2, 1))
+        w.change(4, b"busy")
+    return out.data
+
+
+def large_numbers():
+    """Storage ids and timesteps that take more than one byte of LEB128, and an integer made of
+    more than one storage."""
+    out = Buffer()
+    with SvcbWriter(out, 1) as w:
+        w.storage(300, TWO_LOGIC, 64)
+        w.storage(70000, TWO_LOGIC, 64)
+        w.var_integer(0, "wide", [300, 70000], 127, 0)
+        w.time(1 << 40)
+        w.change(300, pack(TWO_LOGIC, 64, (1 << 64) - 1))
+        w.change(70000, pack(TWO_LOGIC, 64, 1 << 63))
+        w.time((1 << 63) + 5)
+        w.change(70000, pack(TWO_LOGIC, 64, 0))
+    return out.data
+
+
+def storage(id, ty=TWO_LOGIC, width=1, start=0):
+    return bytes([2]) + u32(id) + u32(ty) + u32(width) + u32(start)
+
+
+def var(scope, name, interpretation, rest):
+    return bytes([1]) + u32(scope) + string(name) + u32(interpretation) + rest
+
+
+VALID = {
+    "header-only": HEADER,
+    "every-block": every_block(),
+    "large-numbers": large_numbers(),
+}
+
+# The bytes of each broken stream, along with what's wrong with it and the byte it's at.
+INVALID = {
+    "not-svcb": (b"$date today $end", 0, "not an SVCB stream"),
+    "version-2": (
+        b"svcb" + u32(2) + bytes(16),
+        4,
+        "this is version 2 of SVCB, but only version 1 is supported",
+    ),
+    "truncated-header": (HEADER[:20], 8, "the stream ended partway through a block"),
+    "truncated-block": (
+        HEADER + storage(0)[:7],
+        29,
+        "the stream ended partway through a block",
+    ),
+    "unknown-block": (HEADER + bytes([5]), 24, "unknown block type 5"),
+    "root-scope": (
+        HEADER + bytes([0]) + u32(0) + u32(0) + string(b"top"),
+        29,
+        "scope `top` has id 0, which belongs to the top level",
+    ),
+    "unknown-storage-type": (
+        HEADER + storage(0, ty=3),
+        29,
+        "storage 0 has an unknown type 3",
+    ),
+    "storage-start": (
+        HEADER + storage(0, start=7),
+        37,
+        "storage 0 starts at 7, but only storages that start at 0 are supported",
+    ),
+    "duplicate-storage": (
+        HEADER + storage(0) + storage(0),
+        42,
+        "storage 0 is declared twice",
+    ),
+    "undeclared-storage-change": (
+        HEADER + bytes([3, 1, 7, 0]),
+        26,
+        "storage 7 is used before it's declared",
+    ),
+    "undeclared-storage-var": (
+        HEADER + var(0, b"x", 0, u32(7)),
+        38,
+        "storage 7 is used before it's declared",
+    ),
+    "unknown-interpretation": (
+        HEADER + var(0, b"x", 4, u32(0)),
+        34,
+        "variable `x` has an unknown interpretation 4",
+    ),
+    "unknown-signedness": (
+        HEADER + storage(0) + var(0, b"x", 1, u32(1) + u32(0) + u32(0) + u32(0) + u32(2)),
+        71,
+        "variable `x` has an unknown signedness 2",
+    ),
+    "text-storage": (
+        HEADER + storage(0, ty=FOUR_LOGIC, width=8) + var(0, b"x", 3, u32(0)),
+        55,
+        "text variable `x` needs its storage to be two-logic, and declared before changes",
+    ),
+    "invalid-utf8": (
+        HEADER + bytes([0]) + u32(0) + u32(1) + string(b"\xff"),
+        37,
+        "a name isn't valid UTF-8",
+    ),
+    "leb-overflow": (
+        HEADER + bytes([3]) + b"\x80\x80\x80\x80\x10",
+        25,
+        "a number is too big for its field",
+    ),
+    "time-overflow": (
+        HEADER + bytes([4]) + b"\x80" * 9 + b"\x01" + bytes([4]) + b"\x80" * 9 + b"\x01",
+        36,
+        "a number is too big for its field",
+    ),
+}
+
+
+def main():
+    for directory in ["valid", "invalid"]:
+        os.makedirs(os.path.join(HERE, directory), exist_ok=True)
+
+    for name, data in VALID.items():
+        with open(os.path.join(HERE, "valid", name + ".svcb"), "wb") as f:
+            f.write(data)
+
+    expected = []
+    for name, (data, offset, reason) in INVALID.items():
+        with open(os.path.join(HERE, "invalid", name + ".svcb"), "wb") as f:
+            f.write(data)
+        expected.append("%s.svcb: at byte %d: %s\n" % (name, offset, reason))
+    with open(os.path.join(HERE, "invalid", "expected.txt"), "w") as f:
+        f.writelines(expected)
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,192 @@
+//! SVCB streams load the same whether they're read from memory or streamed through a ring
+//! buffer by a simulator, and broken ones are reported rather than half-loaded.
+
+use std::{io::Write, thread};
+
+use ligeia_core::{dump::dump, IngestorOptions, Processed};
+use ligeia_formats::LoaderRegistry;
+use ligeia_shm::{RingWriter, StorageType, SvcbWriter, VarKind};
+use ligeia_svcb::{load_svcb, Reason};
+
+fn dump_to_string(mut processed: Processed) -> String {
+    let mut out = vec![];
+    dump(&mut processed, &mut out, 3).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+/// A little of everything: each type of storage, and each way of reading one.
+fn simulate<W: Write>(out: W) -> W {
+    let mut writer = SvcbWriter::new(out, 1000).unwrap();
+    writer.scope(1, 0, "top").unwrap();
+    writer.scope(2, 1, "fsm").unwrap();
+    writer.storage(0, StorageType::TwoLogic, 1).unwrap();
+    writer.storage(1, StorageType::FourLogic, 4).unwrap();
+    writer.storage(2, StorageType::NineLogic, 3).unwrap();
+    writer.storage(3, StorageType::TwoLogic, 2).unwrap();
+    writer.storage(4, StorageType::TwoLogic, 32).unwrap();
+    writer
+        .var(
+            1,
+            "clk",
+            VarKind::Integer {
+                storages: &[0],
+                msb: 0,
+                lsb: 0,
+                signed: false,
+            },
+        )
+        .unwrap();
+    writer
+        .var(
+            1,
+            "data",
+            VarKind::Integer {
+                storages: &[1],
+                msb: 3,
+                lsb: 0,
+                signed: true,
+            },
+        )
+        .unwrap();
+    writer.var(1, "pad", VarKind::None { storage: 2 }).unwrap();
+    writer
+        .var(
+            2,
+            "state",
+            VarKind::Enum {
+                storage: 3,
+                values: &[("IDLE", 0), ("BUSY", 1), ("DONE", 2)],
+            },
+        )
+        .unwrap();
+    writer
+        .var(2, "label", VarKind::Utf8 { storage: 4 })
+        .unwrap();
+
+    writer.change(3, &[0]).unwrap();
+    writer.change(4, b"idle").unwrap();
+    for cycle in 0..100u64 {
+        writer.time(cycle * 10).unwrap();
+        writer.change(0, &[1]).unwrap();
+        // `10xz`, `0101` and so on.
+        writer
+            .change(
+                1,
+                &[if cycle % 2 == 0 {
+                    0b01_00_10_11
+                } else {
+                    0b00_01_00_01
+                }],
+            )
+            .unwrap();
+        writer.time(cycle * 10 + 5).unwrap();
+        writer.change(0, &[0]).unwrap();
+    }
+    writer.change(2, &[0x84, 0x01]).unwrap();
+    writer.change(3, &[1]).unwrap();
+    writer.change(4, b"busy").unwrap();
+    writer.finish().unwrap()
+}
+
+#[test]
+fn load_from_memory() {
+    let stream = simulate(vec![]);
+    let processed = load_svcb(&stream[..], IngestorOptions::new()).unwrap();
+    assert_eq!(processed.femtoseconds_per_timestep(), 1000);
+
+    let dump = dump_to_string(processed);
+    for expected in [
+        // The changes before the first timestep are at time 0.
+        "time bounds: 0..995",
+        "  0 10xz\n  10 0101\n",
+        // Untyped storages are shown as their bits.
+        "pad: integer [2:0] Unsigned, storages 2",
+        // Enum values least significant bit first.
+        "state: enum, storage 3, IDLE=00, BUSY=10, DONE=01",
+        // Nine-logic values unpacked from two to a byte, least significant first: strong `x`, `z`
+        // and then `1`.
+        "  995 1ZX\n",
+        "4: Utf8, 32 bits from 0, 2 changes\n  0 \"idle\"\n  995 \"busy\"\n",
+    ] {
+        assert!(dump.contains(expected), "{} isn't in:\n{}", expected, dump);
+    }
+}
+
+#[test]
+fn load_from_ring() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("sim.ring");
+    let ring = RingWriter::create(&path, 100).unwrap();
+    let simulating = thread::spawn(move || drop(simulate(ring)));
+
+    // It's recognized by what it starts with, and read until the simulator closes it.
+    let mut loaders = LoaderRegistry::new();
+    ligeia_svcb::register(&mut loaders);
+    let processed = loaders.load_file(&path, None).unwrap();
+    simulating.join().unwrap();
+
+    let expected = load_svcb(&simulate(vec![])[..], IngestorOptions::new()).unwrap();
+    assert_eq!(dump_to_string(processed), dump_to_string(expected));
+}
+
+#[test]
+fn broken_streams() {
+    let stream = simulate(vec![]);
+    let load = |stream: &[u8]| load_svcb(stream, IngestorOptions::new()).err().unwrap();
+
+    let error = load(b"vcd!");
+    assert!(matches!(error.reason(), Some(Reason::NotSvcb)));
+    assert_eq!(error.offset(), Some(0));
+    let error = load(b"svcb\x02\0\0\0");
+    assert!(matches!(error.reason(), Some(Reason::Version(2))));
+    assert_eq!(error.offset(), Some(4));
+    assert!(matches!(
+        load(&stream[..stream.len() - 1]).reason(),
+        Some(Reason::Truncated)
+    ));
+
+    // Errors say where in the stream they are, and what's there.
+    let mut unknown = stream.clone();
+    unknown.push(9);
+    let error = load(&unknown);
+    assert!(matches!(error.reason(), Some(Reason::UnknownBlock(9))));
+    assert_eq!(
+        error.to_string(),
+        format!("at byte {}, near `09`: unknown block type 9", stream.len())
+    );
+
+    // A change to storage 7, which was never declared.
+    let mut undeclared = stream.clone();
+    undeclared.extend_from_slice(&[3, 1, 7, 0]);
+    let error = load(&undeclared);
+    assert!(matches!(error.reason(), Some(Reason::UnknownStorage(7))));
+    assert_eq!(error.offset(), Some(stream.len() as u64 + 2));
+    assert!(error.to_string().contains("near `07`"), "{}", error);
+
+    // A timestep whose delta takes the time, already past zero, beyond what it can hold.
+    let mut overflowing = stream.clone();
+    overflowing.push(4);
+    overflowing.extend_from_slice(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]);
+    let error = load(&overflowing);
+    assert!(matches!(error.reason(), Some(Reason::Overflow)));
+    assert_eq!(error.offset(), Some(stream.len() as u64 + 1));
+
+    // Lengths and counts that claim far more than there is fail for being truncated, without
+    // trying to make room for them first.
+    let mut long_name = stream.clone();
+    long_name.extend_from_slice(&[0, 0, 0, 0, 0, 9, 0, 0, 0, 0xff, 0xff, 0xff, 0xff, b'a']);
+    let error = load(&long_name);
+    assert!(matches!(error.reason(), Some(Reason::Truncated)));
+    assert_eq!(error.offset(), Some(stream.len() as u64 + 13));
+
+    let mut many_values = b"svcb\x01\0\0\0".to_vec();
+    many_values.extend_from_slice(&1000u128.to_le_bytes());
+    // An eight-bit two-logic storage, then an enum of it claiming u32::MAX values.
+    many_values.extend_from_slice(&[2, 0, 0, 0, 0, 0, 0, 0, 0, 8, 0, 0, 0, 0, 0, 0, 0]);
+    many_values.extend_from_slice(&[1, 0, 0, 0, 0, 1, 0, 0, 0, b'e', 2, 0, 0, 0, 0, 0, 0, 0]);
+    many_values.extend_from_slice(&[0xff, 0xff, 0xff, 0xff]);
+    assert!(matches!(
+        load(&many_values).reason(),
+        Some(Reason::Truncated)
+    ));
+}
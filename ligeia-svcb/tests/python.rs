@@ -0,0 +1,133 @@
+//! The Python writer in `python/ligeia_svcb.py` writes the same stream as `SvcbWriter` does, so
+//! that it's held to the same format as the loader.
+
+use std::process::Command;
+
+use ligeia_core::IngestorOptions;
+use ligeia_shm::{StorageType, SvcbWriter, VarKind};
+use ligeia_svcb::load_svcb;
+
+const SIMULATE: &str = r#"
+import sys
+from ligeia_svcb import *
+
+out = sys.stdout.buffer
+with SvcbWriter(out, 1000) as writer:
+    writer.scope(1, 0, "top")
+    writer.scope(2, 1, "fsm")
+    writer.storage(0, TWO_LOGIC, 1)
+    writer.storage(1, FOUR_LOGIC, 4)
+    writer.storage(2, NINE_LOGIC, 3)
+    writer.storage(3, TWO_LOGIC, 2)
+    writer.storage(4, TWO_LOGIC, 32)
+    writer.storage(5, TWO_LOGIC, 200)
+    writer.var_integer(1, "clk", [0], 0, 0)
+    writer.var_integer(1, "data", [1], 3, 0, signed=True)
+    writer.var_none(1, "pad", 2)
+    writer.var_enum(2, "state", 3, [("IDLE", 0), ("BUSY", 1), ("DONE", 2)])
+    writer.var_utf8(2, "label", 4)
+    writer.var_integer(1, "wide", [5], 199, 0)
+
+    writer.change(3, pack(TWO_LOGIC, 2, 0))
+    writer.change(4, b"idle")
+    for cycle in range(100):
+        writer.time(cycle * 10)
+        writer.change(0, pack(TWO_LOGIC, 1, "1"))
+        writer.change(1, pack(FOUR_LOGIC, 4, "10xz" if cycle % 2 == 0 else "0101"))
+        writer.change(5, pack(TWO_LOGIC, 200, cycle << 150))
+        writer.time(cycle * 10 + 5)
+        writer.change(0, pack(TWO_LOGIC, 1, 0))
+    writer.time(100_000)
+    writer.change(2, pack(NINE_LOGIC, 3, "1ZX"))
+    writer.change(3, pack(TWO_LOGIC, 2, "01"))
+    writer.change(4, b"busy")
+"#;
+
+fn simulate() -> Vec<u8> {
+    let mut writer = SvcbWriter::new(vec![], 1000).unwrap();
+    writer.scope(1, 0, "top").unwrap();
+    writer.scope(2, 1, "fsm").unwrap();
+    writer.storage(0, StorageType::TwoLogic, 1).unwrap();
+    writer.storage(1, StorageType::FourLogic, 4).unwrap();
+    writer.storage(2, StorageType::NineLogic, 3).unwrap();
+    writer.storage(3, StorageType::TwoLogic, 2).unwrap();
+    writer.storage(4, StorageType::TwoLogic, 32).unwrap();
+    writer.storage(5, StorageType::TwoLogic, 200).unwrap();
+    let integer = |storages, msb, signed| VarKind::Integer {
+        storages,
+        msb,
+        lsb: 0,
+        signed,
+    };
+    writer.var(1, "clk", integer(&[0], 0, false)).unwrap();
+    writer.var(1, "data", integer(&[1], 3, true)).unwrap();
+    writer.var(1, "pad", VarKind::None { storage: 2 }).unwrap();
+    writer
+        .var(
+            2,
+            "state",
+            VarKind::Enum {
+                storage: 3,
+                values: &[("IDLE", 0), ("BUSY", 1), ("DONE", 2)],
+            },
+        )
+        .unwrap();
+    writer
+        .var(2, "label", VarKind::Utf8 { storage: 4 })
+        .unwrap();
+    writer.var(1, "wide", integer(&[5], 199, false)).unwrap();
+
+    writer.change(3, &[0]).unwrap();
+    writer.change(4, b"idle").unwrap();
+    for cycle in 0..100u64 {
+        writer.time(cycle * 10).unwrap();
+        writer.change(0, &[1]).unwrap();
+        writer
+            .change(
+                1,
+                &[if cycle % 2 == 0 {
+                    0b01_00_10_11
+                } else {
+                    0b00_01_00_01
+                }],
+            )
+            .unwrap();
+        // `cycle` shifted up by 150 bits, which is bit 6 of byte 18 onwards.
+        let mut wide = [0; 25];
+        wide[18] = (cycle << 6) as u8;
+        wide[19] = (cycle >> 2) as u8;
+        writer.change(5, &wide).unwrap();
+        writer.time(cycle * 10 + 5).unwrap();
+        writer.change(0, &[0]).unwrap();
+    }
+    writer.time(100_000).unwrap();
+    writer.change(2, &[0x84, 0x01]).unwrap();
+    writer.change(3, &[1]).unwrap();
+    writer.change(4, b"busy").unwrap();
+    writer.finish().unwrap()
+}
+
+#[test]
+fn python_writes_the_same_stream() {
+    let output = match Command::new("python3")
+        .arg("-c")
+        .arg(SIMULATE)
+        .env("PYTHONPATH", concat!(env!("CARGO_MANIFEST_DIR"), "/python"))
+        .output()
+    {
+        Ok(output) => output,
+        Err(e) => {
+            eprintln!("skipping, since python3 couldn't be run: {}", e);
+            return;
+        }
+    };
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let expected = simulate();
+    assert_eq!(output.stdout, expected);
+    load_svcb(&output.stdout[..], IngestorOptions::new()).unwrap();
+}
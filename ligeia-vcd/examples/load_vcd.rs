@@ -2,13 +2,17 @@ use std::{
     env, error, fs::File, io::BufReader, os::unix::prelude::MetadataExt, path::Path, time::Instant,
 };
 
-use ligeia_vcd;
+use ligeia_core::{meta::Timesteps, IngestorOptions};
 use number_prefix::NumberPrefix;
 
+const RANDOM_QUERIES: usize = 100_000;
+
 fn main() -> Result<(), Box<dyn error::Error>> {
-    let args: Vec<_> = env::args_os().skip(1).collect();
+    let mut args: Vec<_> = env::args_os().skip(1).collect();
+    let lazy = args.iter().any(|arg| arg == "--lazy");
+    args.retain(|arg| arg != "--lazy");
     if args.len() != 1 {
-        eprintln!("must have 1 argument");
+        eprintln!("usage: load_vcd [--lazy] <file>");
         return Ok(());
     }
 
@@ -18,7 +22,11 @@ fn main() -> Result<(), Box<dyn error::Error>> {
 
     let start = Instant::now();
 
-    let mut processed = ligeia_vcd::load_vcd(BufReader::new(f))?;
+    let mut processed = if lazy {
+        ligeia_vcd::load_vcd_lazy(f, IngestorOptions::new())?
+    } else {
+        ligeia_vcd::load_vcd(BufReader::new(f), IngestorOptions::new())?
+    };
 
     let elapsed = start.elapsed();
 
@@ -31,14 +39,64 @@ fn main() -> Result<(), Box<dyn error::Error>> {
 
     let storage_ids = processed.storage_ids();
 
+    let (first, last) = processed.time_bounds();
+    let changes = storage_ids
+        .iter()
+        .map(|&id| processed.change_count(id))
+        .sum::<Result<u64, _>>()?;
+    println!(
+        "{} storages, {} changes, timesteps {} to {}",
+        storage_ids.len(),
+        changes,
+        first.0,
+        last.0
+    );
+
     let start = Instant::now();
 
-    for id in storage_ids {
+    for &id in &storage_ids {
         processed.load_storage(id, |_timestamp, _value| {})?;
     }
 
     let elapsed = start.elapsed();
     println!("loaded storages in {:?}", elapsed);
 
+    let strings = processed.string_stats();
+    if strings.changes > 0 {
+        println!(
+            "{} string changes, {} distinct, {:.1}x smaller deduplicated",
+            strings.changes,
+            strings.distinct,
+            strings.dedup_ratio()
+        );
+    }
+
+    if storage_ids.is_empty() {
+        return Ok(());
+    }
+
+    // A fixed-seed LCG, so runs are comparable.
+    let mut state: u64 = 1;
+    let mut random = move || {
+        state = state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        state >> 33
+    };
+
+    let start = Instant::now();
+
+    for _ in 0..RANDOM_QUERIES {
+        let id = storage_ids[random() as usize % storage_ids.len()];
+        let time = first.0 + random() % (last.0 - first.0 + 1);
+        processed.value_at(id, Timesteps(time))?;
+    }
+
+    let elapsed = start.elapsed();
+    println!(
+        "{} random value_at queries in {:?}",
+        RANDOM_QUERIES, elapsed
+    );
+
     Ok(())
 }
@@ -0,0 +1,264 @@
+//! Writing part of a waveform back out as a VCD file, for attaching minimal repros to bug
+//! reports.
+
+use std::{
+    error::Error,
+    io::{self, Write},
+};
+
+use ligeia_core::{
+    logic,
+    meta::{StorageId, StorageType, Timesteps, VarKind},
+    names,
+    timescale::Timescale,
+    Processed,
+};
+
+/// A variable that's being exported.
+struct Exported {
+    /// The scopes it's in, outermost first, followed by its own name.
+    path: Vec<String>,
+    storage: StorageId,
+    ty: StorageType,
+    width: u32,
+}
+
+/// The shortest id code for the `index`th variable, from the printable ASCII characters.
+fn id_code(mut index: usize) -> String {
+    const FIRST: u8 = b'!';
+    const COUNT: usize = (b'~' - b'!' + 1) as usize;
+
+    let mut code = String::new();
+    loop {
+        code.push((FIRST + (index % COUNT) as u8) as char);
+        index /= COUNT;
+        if index == 0 {
+            return code;
+        }
+        index -= 1;
+    }
+}
+
+/// The timescale to write, and what to multiply timestamps by to fit it.
+fn timescale(femtoseconds_per_timestep: u128) -> Result<(String, u64), Box<dyn Error>> {
+    match Timescale::from_femtoseconds(femtoseconds_per_timestep) {
+        Some(timescale) => Ok((timescale.to_string(), 1)),
+        // Anything else has to be written out in femtoseconds.
+        None => match u64::try_from(femtoseconds_per_timestep) {
+            Ok(scale) => Ok(("1 fs".to_string(), scale)),
+            Err(_) => Err(format!(
+                "a timestep of {} fs is too long to write out",
+                femtoseconds_per_timestep
+            )
+            .into()),
+        },
+    }
+}
+
+/// `time` in the timescale that's written, or an error if it's too late to fit in it.
+fn scaled(time: Timesteps, scale: u64) -> Result<u64, Box<dyn Error>> {
+    time.0.checked_mul(scale).ok_or_else(|| {
+        format!(
+            "timestep {} is too late to write out in femtoseconds",
+            time.0
+        )
+        .into()
+    })
+}
+
+fn write_value<W: Write>(
+    writer: &mut W,
+    processed: &Processed,
+    var: &Exported,
+    code: &str,
+    data: Option<&[u8]>,
+) -> io::Result<()> {
+    let data = match (var.ty, data) {
+        (StorageType::Event, _) => return writeln!(writer, "1{}", code),
+        (StorageType::Utf8, Some(data)) => {
+            // Strings are a single token, so they can't hold whitespace.
+            let text: String = processed
+                .string(data)
+                .chars()
+                .map(|c| if c.is_whitespace() { '_' } else { c })
+                .collect();
+            return writeln!(writer, "s{} {}", text, code);
+        }
+        (_, Some(data)) => data,
+        (StorageType::Utf8, None) => return writeln!(writer, "s {}", code),
+        (_, None) => {
+            return match var.width {
+                1 => writeln!(writer, "x{}", code),
+                _ => writeln!(writer, "bx {}", code),
+            }
+        }
+    };
+
+    let mut values = vec![];
+    match var.ty {
+        StorageType::TwoLogic => logic::unpack_two(data, var.width as usize, &mut values),
+        StorageType::FourLogic => logic::unpack_four(data, var.width as usize, &mut values),
+        StorageType::NineLogic => {
+            values.extend(
+                data[..var.width as usize]
+                    .iter()
+                    .map(|&value| if value <= 1 { value } else { 2 }),
+            )
+        }
+        StorageType::Utf8 | StorageType::Event => unreachable!(),
+    }
+
+    // VCD vectors are written most significant bit first.
+    let bits: String = values
+        .iter()
+        .rev()
+        .map(|&value| ['0', '1', 'x', 'z'][value as usize])
+        .collect();
+    match var.width {
+        1 => writeln!(writer, "{}{}", bits, code),
+        _ => writeln!(writer, "b{} {}", bits, code),
+    }
+}
+
+/// Write the variables whose hierarchical names pass `include` as a VCD file, with only their
+/// changes from `start` to `end`.
+///
+/// Values at `start` are written out as the initial values, whenever they changed to them.
+/// Events only have occurrences, so only those from `start` on are written.
+/// Variables made up of more than one storage are left out.
+pub fn export_vcd<W, F>(
+    processed: &mut Processed,
+    mut writer: W,
+    (start, end): (Timesteps, Timesteps),
+    mut include: F,
+) -> Result<(), Box<dyn Error>>
+where
+    W: Write,
+    F: FnMut(&str) -> bool,
+{
+    let mut vars = vec![];
+    for var in processed.vars() {
+        let storage = match &var.kind {
+            VarKind::Integer { storages, .. } if storages.len() == 1 => storages[0],
+            VarKind::Enum { storage, .. }
+            | VarKind::Utf8 { storage }
+            | VarKind::Event { storage } => *storage,
+            _ => continue,
+        };
+
+        let path = processed.var_path(var);
+        if !include(&path) {
+            continue;
+        }
+
+        let mut path: Vec<String> = processed
+            .scope_path(var.scope_id)
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        path.push(processed.name(var.name).to_string());
+
+        let storage_meta = processed.storage(storage)?;
+        vars.push(Exported {
+            path,
+            storage,
+            ty: storage_meta.ty,
+            width: storage_meta.width,
+        });
+    }
+    // Sorting keeps the variables of each scope together.
+    vars.sort_by(|a, b| names::natural_cmp_paths(&a.path, &b.path));
+
+    let (timescale, scale) = timescale(processed.femtoseconds_per_timestep())?;
+    writeln!(writer, "$timescale {} $end", timescale)?;
+
+    let mut open: Vec<&str> = vec![];
+    for (index, var) in vars.iter().enumerate() {
+        let (name, scopes) = var.path.split_last().unwrap();
+        let shared = open
+            .iter()
+            .zip(scopes)
+            .take_while(|(open, scope)| *open == scope)
+            .count();
+        for _ in shared..open.len() {
+            writeln!(writer, "$upscope $end")?;
+        }
+        open.truncate(shared);
+        for scope in &scopes[shared..] {
+            writeln!(writer, "$scope module {} $end", scope)?;
+            open.push(scope);
+        }
+
+        let (kind, width) = match var.ty {
+            StorageType::Utf8 => ("string", 1),
+            StorageType::Event => ("event", 1),
+            _ => ("wire", var.width),
+        };
+        writeln!(
+            writer,
+            "$var {} {} {} {} $end",
+            kind,
+            width,
+            id_code(index),
+            name
+        )?;
+    }
+    for _ in &open {
+        writeln!(writer, "$upscope $end")?;
+    }
+    writeln!(writer, "$enddefinitions $end")?;
+
+    // Everything after `start`, up to and including `end`, short of the very last timestep
+    // there could be if that's where `end` is.
+    let range = start..Timesteps(end.0.saturating_add(1));
+    let mut initial = vec![None; vars.len()];
+    let mut changes = vec![];
+    for (index, var) in vars.iter().enumerate() {
+        processed.changes_in_range_with_initial(
+            var.storage,
+            range.clone(),
+            |time, data| match (time <= start, var.ty) {
+                // An event before `start` is the last one, which has already happened.
+                (true, StorageType::Event) if time < start => {}
+                (true, StorageType::Event) | (false, _) => {
+                    changes.push((time, index, data.to_vec()))
+                }
+                (true, _) => initial[index] = Some(data.to_vec()),
+            },
+        )?;
+    }
+    // Stable, so each storage's changes stay in order.
+    changes.sort_by_key(|&(time, ..)| time);
+
+    writeln!(writer, "#{}", scaled(start, scale)?)?;
+    writeln!(writer, "$dumpvars")?;
+    for (index, var) in vars.iter().enumerate() {
+        if matches!(var.ty, StorageType::Event) {
+            continue;
+        }
+        let data = initial[index].as_deref();
+        write_value(&mut writer, processed, var, &id_code(index), data)?;
+    }
+    writeln!(writer, "$end")?;
+
+    let mut current = start;
+    for (time, index, data) in changes {
+        if time != current {
+            writeln!(writer, "#{}", scaled(time, scale)?)?;
+            current = time;
+        }
+        write_value(
+            &mut writer,
+            processed,
+            &vars[index],
+            &id_code(index),
+            Some(&data),
+        )?;
+    }
+    if current != end {
+        writeln!(writer, "#{}", scaled(end, scale)?)?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
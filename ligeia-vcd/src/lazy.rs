@@ -0,0 +1,405 @@
+//! Two-pass loading for seekable VCD files.
+//!
+//! The first pass only tokenizes the body, recording which regions of the file each storage
+//! changes in. Values are decoded from just those regions the first time a storage is accessed,
+//! so opening a huge dump to look at a handful of signals doesn't parse every change into storage.
+
+use std::{
+    cell::RefCell,
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom},
+    str,
+};
+
+use fnv::FnvHashMap;
+use ligeia_core::{
+    logic,
+    meta::{StorageId, Timesteps},
+    Ingestor, IngestorOptions, LazySource, Warning,
+};
+use ligeia_formats::position::{ParseError, Position, Shared, Tracking};
+use vcd::{IdCode, Parser};
+
+use crate::{femtoseconds_per_timestep, fit_width, generate_scopes, Truncations};
+
+/// Regions are at least this many bytes long, and always start at a timestamp.
+const REGION_SIZE: u64 = 1024 * 1024;
+
+/// Splits the input on whitespace, keeping track of where each token starts.
+struct Tokenizer<R> {
+    reader: R,
+    position: u64,
+    /// The line `position` is on, counting from 1.
+    line: u64,
+    token: Vec<u8>,
+    /// The line the last token is on.
+    token_line: u64,
+}
+
+impl<R: BufRead> Tokenizer<R> {
+    fn new(reader: R, position: u64, line: u64) -> Self {
+        Self {
+            reader,
+            position,
+            line,
+            token: vec![],
+            token_line: line,
+        }
+    }
+
+    /// Read the next token into `self.token`, returning the offset it started at.
+    fn next_token(&mut self) -> io::Result<Option<u64>> {
+        self.token.clear();
+        let mut start = None;
+
+        loop {
+            let buffer = self.reader.fill_buf()?;
+            if buffer.is_empty() {
+                return Ok(start);
+            }
+
+            let mut used = 0;
+            let mut done = false;
+            for &b in buffer {
+                used += 1;
+                if b.is_ascii_whitespace() {
+                    if b == b'\n' {
+                        self.line += 1;
+                    }
+                    if start.is_some() {
+                        done = true;
+                        break;
+                    }
+                } else {
+                    if start.is_none() {
+                        start = Some(self.position + used as u64 - 1);
+                        self.token_line = self.line;
+                    }
+                    self.token.push(b);
+                }
+            }
+
+            self.reader.consume(used);
+            self.position += used as u64;
+            if done {
+                return Ok(start);
+            }
+        }
+    }
+}
+
+enum Item {
+    Timestamp(Timesteps),
+    /// A four-logic value change, with its characters left in `Body::value`.
+    Change(IdCode),
+    /// A string change, with its text left in `Body::value`.
+    String(IdCode),
+}
+
+fn parse_code(token: &[u8]) -> Option<IdCode> {
+    str::from_utf8(token).ok().and_then(|s| s.parse().ok())
+}
+
+/// An error at `offset` on `line`, where `excerpt` is.
+fn invalid_data(message: &str, offset: u64, line: u64, excerpt: &[u8]) -> io::Error {
+    ParseError {
+        position: Position {
+            offset,
+            line: Some(line),
+            excerpt: String::from_utf8_lossy(excerpt).escape_debug().to_string(),
+        },
+        source: message.into(),
+    }
+    .into_io()
+}
+
+/// A minimal parser for the body of a VCD file that can start at any timestamp.
+struct Body<R> {
+    tokens: Tokenizer<R>,
+    /// These aren't checked until they're packed, to keep the indexing pass quick.
+    value: Vec<u8>,
+    /// Where the last item started, to point errors at.
+    offset: u64,
+}
+
+impl<R: BufRead> Body<R> {
+    fn new(reader: R, position: u64, line: u64) -> Self {
+        Self {
+            tokens: Tokenizer::new(reader, position, line),
+            value: vec![],
+            offset: position,
+        }
+    }
+
+    /// An error in the last item, showing `excerpt` from it.
+    fn error(&self, message: &str, excerpt: &[u8]) -> io::Error {
+        invalid_data(message, self.offset, self.tokens.token_line, excerpt)
+    }
+
+    fn next_token(&mut self) -> io::Result<&[u8]> {
+        match self.tokens.next_token()? {
+            Some(_) => Ok(&self.tokens.token),
+            None => Err(invalid_data(
+                "unexpected end of file",
+                self.tokens.position,
+                self.tokens.line,
+                b"",
+            )),
+        }
+    }
+
+    /// The id code in the next token.
+    fn next_code(&mut self) -> io::Result<IdCode> {
+        self.next_token()?;
+        let token = &self.tokens.token;
+        parse_code(token).ok_or_else(|| self.error("invalid id code", token))
+    }
+
+    /// Returns the next item along with the offset it started at.
+    fn next(&mut self) -> io::Result<Option<(u64, Item)>> {
+        loop {
+            let offset = match self.tokens.next_token()? {
+                Some(offset) => offset,
+                None => return Ok(None),
+            };
+            self.offset = offset;
+
+            let token = &self.tokens.token;
+            let item = match token[0] {
+                b'#' => {
+                    let timestamp = str::from_utf8(&token[1..])
+                        .ok()
+                        .and_then(|s| s.parse().ok())
+                        .ok_or_else(|| self.error("invalid timestamp", token))?;
+                    Item::Timestamp(Timesteps(timestamp))
+                }
+                b'$' => {
+                    if token == b"$comment" {
+                        while self.next_token()? != b"$end" {}
+                    }
+                    // `$dumpvars`, `$end`, etc. don't affect values.
+                    continue;
+                }
+                b'b' | b'B' => {
+                    self.value.clear();
+                    self.value.extend_from_slice(&token[1..]);
+                    Item::Change(self.next_code()?)
+                }
+                b's' | b'S' => {
+                    self.value.clear();
+                    self.value.extend_from_slice(&token[1..]);
+                    Item::String(self.next_code()?)
+                }
+                // Real changes aren't stored yet.
+                b'r' | b'R' => {
+                    self.next_token()?;
+                    continue;
+                }
+                c => {
+                    self.value.clear();
+                    self.value.push(c);
+                    Item::Change(
+                        parse_code(&token[1..])
+                            .ok_or_else(|| self.error("invalid id code", token))?,
+                    )
+                }
+            };
+
+            return Ok(Some((offset, item)));
+        }
+    }
+}
+
+struct Region {
+    offset: u64,
+    len: u64,
+    /// The line `offset` is on, so that errors decoding it can say where they are.
+    line: u64,
+    start: Timesteps,
+}
+
+struct VcdSource {
+    file: File,
+    regions: Vec<Region>,
+    codes: FnvHashMap<StorageId, IdCode>,
+    widths: FnvHashMap<IdCode, usize>,
+    truncations: Truncations,
+    /// Indices into `regions` that each storage changes in.
+    storage_regions: FnvHashMap<StorageId, Vec<u32>>,
+    change_counts: FnvHashMap<StorageId, u64>,
+}
+
+impl LazySource for VcdSource {
+    fn load(
+        &mut self,
+        id: StorageId,
+        f: &mut dyn FnMut(Timesteps, &[u8]),
+    ) -> Result<(), ligeia_core::Error> {
+        let (code, regions) = match (self.codes.get(&id), self.storage_regions.get(&id)) {
+            (Some(&code), Some(regions)) => (code, regions),
+            _ => return Ok(()),
+        };
+        let width = self.widths.get(&code).copied();
+
+        let mut buffer = vec![];
+        for &index in regions {
+            let region = &self.regions[index as usize];
+            self.file.seek(SeekFrom::Start(region.offset))?;
+
+            let mut body = Body::new(
+                BufReader::new((&mut self.file).take(region.len)),
+                region.offset,
+                region.line,
+            );
+            let mut timestamp = region.start;
+
+            // Errors in the file are only found now, so they're passed on as text to keep where
+            // they are.
+            let lazy = |e: io::Error| ligeia_core::Error::Lazy(e.to_string());
+            while let Some((_, item)) = body.next().map_err(lazy)? {
+                match item {
+                    Item::Timestamp(new) => timestamp = new,
+                    Item::Change(changed) if changed == code => {
+                        if let Some(width) = width {
+                            let dropped = fit_width(&mut body.value, width, b'0', |c| {
+                                matches!(c, b'x' | b'X' | b'z' | b'Z')
+                            });
+                            self.truncations
+                                .check(code, dropped, width)
+                                .map_err(|e| lazy(body.error(&e.to_string(), &body.value)))?;
+                        }
+                        if !logic::pack_four_ascii_msb_first(&body.value, &mut buffer) {
+                            return Err(lazy(body.error("invalid value", &body.value)));
+                        }
+                        f(timestamp, &buffer);
+                    }
+                    Item::String(changed) if changed == code => f(timestamp, &body.value),
+                    Item::Change(_) | Item::String(_) => {}
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn change_count(&self, id: StorageId) -> u64 {
+        self.change_counts.get(&id).copied().unwrap_or(0)
+    }
+
+    fn take_warnings(&mut self) -> Vec<Warning> {
+        self.truncations.take_warnings()
+    }
+}
+
+/// Find the offset of the first byte after `$enddefinitions $end`, and the line it's on.
+fn find_body(file: &mut File) -> io::Result<(u64, u64)> {
+    file.seek(SeekFrom::Start(0))?;
+    let mut tokens = Tokenizer::new(BufReader::new(file), 0, 1);
+
+    while tokens.next_token()?.is_some() {
+        if tokens.token == b"$enddefinitions" {
+            while tokens.next_token()?.is_some() {
+                if tokens.token == b"$end" {
+                    return Ok((tokens.position, tokens.line));
+                }
+            }
+        }
+    }
+
+    Err(invalid_data(
+        "missing `$enddefinitions`",
+        tokens.position,
+        tokens.line,
+        b"",
+    ))
+}
+
+/// Load a VCD file, only decoding the values of a storage when it's first accessed.
+///
+/// This makes a single indexing pass over the file, which must not change while the
+/// returned database is in use. Values are only checked against `options` once they're decoded.
+pub fn load_vcd_lazy(
+    mut file: File,
+    options: IngestorOptions,
+) -> Result<ligeia_core::Processed, Box<dyn std::error::Error>> {
+    let (body_offset, body_line) = find_body(&mut file)?;
+
+    file.seek(SeekFrom::Start(0))?;
+    let tracking = RefCell::new(Tracking::new(BufReader::new((&mut file).take(body_offset))));
+    let header = Parser::new(Shared(&tracking))
+        .parse_header()
+        .map_err(|e| ParseError {
+            position: tracking.borrow().text_position(),
+            source: e.into(),
+        })?;
+    drop(tracking);
+
+    let truncations = Truncations::new(&options);
+    let mut ingestor = Ingestor::new(options.with_timescale(femtoseconds_per_timestep(&header)?))?;
+    let (storage_map, widths) = generate_scopes(&header, &mut ingestor);
+
+    let mut regions = vec![Region {
+        offset: body_offset,
+        len: 0,
+        line: body_line,
+        start: Timesteps(0),
+    }];
+    let mut storage_regions: FnvHashMap<StorageId, Vec<u32>> = FnvHashMap::default();
+    let mut change_counts: FnvHashMap<StorageId, u64> = FnvHashMap::default();
+
+    file.seek(SeekFrom::Start(body_offset))?;
+    let mut body = Body::new(BufReader::new(&mut file), body_offset, body_line);
+
+    while let Some((offset, item)) = body.next()? {
+        match item {
+            Item::Timestamp(timestamp) => {
+                ingestor.ingest_timestep(timestamp);
+
+                let current = regions.last_mut().unwrap();
+                if offset - current.offset >= REGION_SIZE {
+                    current.len = offset - current.offset;
+                    regions.push(Region {
+                        offset,
+                        len: 0,
+                        line: body.tokens.token_line,
+                        start: timestamp,
+                    });
+                }
+            }
+            Item::Change(code) | Item::String(code) => {
+                let storage_id = match storage_map.get(&code) {
+                    Some(&id) => id,
+                    None => continue,
+                };
+
+                *change_counts.entry(storage_id).or_default() += 1;
+
+                let region = regions.len() as u32 - 1;
+                let indices = storage_regions.entry(storage_id).or_default();
+                if indices.last() != Some(&region) {
+                    indices.push(region);
+                }
+            }
+        }
+    }
+
+    let end = body.tokens.position;
+    let last = regions.last_mut().unwrap();
+    last.len = end - last.offset;
+    drop(body);
+
+    let codes = storage_map
+        .into_iter()
+        .map(|(code, id)| (id, code))
+        .collect();
+
+    Ok(ingestor.finish_lazy(Box::new(VcdSource {
+        file,
+        regions,
+        codes,
+        widths,
+        truncations,
+        storage_regions,
+        change_counts,
+    }))?)
+}
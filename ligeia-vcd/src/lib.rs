@@ -1,76 +1,221 @@
-use std::{cell::Cell, io::Read, slice};
+use std::{
+    cell::{Cell, RefCell},
+    error::Error as StdError,
+    io::{self, Read},
+    mem, slice,
+};
 
-use fnv::FnvHashMap;
+use fnv::{FnvHashMap, FnvHashSet};
 use ligeia_core::{
+    logic,
     meta::{self, ScopeId, StorageId},
-    Ingestor,
+    timescale::{TimeUnit, Timescale, TimescaleError},
+    Ingestor, IngestorOptions, Warning,
+};
+use ligeia_formats::position::{ParseError, Shared, Tracking};
+use vcd::{Command, Header, IdCode, Parser, ReferenceIndex, ScopeItem, Value, VarType};
+
+pub use crate::{
+    export::export_vcd,
+    lazy::load_vcd_lazy,
+    loader::{register, VcdLoader},
 };
-use vcd::{Command, Header, IdCode, Parser, ScopeItem, Value, VarType};
 
-pub fn load_vcd<R>(reader: R) -> Result<ligeia_core::Processed, Box<dyn std::error::Error>>
+mod export;
+mod lazy;
+mod loader;
+
+/// Files without a `$timescale` are taken to be in femtoseconds.
+fn femtoseconds_per_timestep(header: &Header) -> Result<u128, TimescaleError> {
+    let (multiplier, unit) = match header.timescale {
+        Some(timescale) => timescale,
+        None => return Ok(1),
+    };
+    let unit = match unit {
+        vcd::TimescaleUnit::S => TimeUnit::Seconds,
+        vcd::TimescaleUnit::MS => TimeUnit::Milliseconds,
+        vcd::TimescaleUnit::US => TimeUnit::Microseconds,
+        vcd::TimescaleUnit::NS => TimeUnit::Nanoseconds,
+        vcd::TimescaleUnit::PS => TimeUnit::Picoseconds,
+        vcd::TimescaleUnit::FS => TimeUnit::Femtoseconds,
+    };
+    Ok(Timescale::new(multiplier as u64, unit)?.femtoseconds())
+}
+
+fn four_logic(value: Value) -> u8 {
+    match value {
+        Value::V0 => 0,
+        Value::V1 => 1,
+        Value::X => 2,
+        Value::Z => 3,
+    }
+}
+
+/// Fit a vector change, written most significant value first, to the width of its var.
+///
+/// Short vectors are extended on the left the way the VCD spec says: with `x` or `z` when
+/// that's what they start with, and with 0 otherwise. Long vectors lose their leftmost values,
+/// and the number dropped is returned.
+fn fit_width<T: Copy>(
+    values: &mut Vec<T>,
+    width: usize,
+    zero: T,
+    extends: impl Fn(T) -> bool,
+) -> usize {
+    let len = values.len();
+    if len < width {
+        let fill = match values.first() {
+            Some(&first) if extends(first) => first,
+            _ => zero,
+        };
+        values.resize(width, fill);
+        values.rotate_right(width - len);
+        0
+    } else {
+        values.drain(..len - width);
+        len - width
+    }
+}
+
+/// Vars that have been truncated, and whether that's allowed.
+struct Truncations {
+    strict: bool,
+    /// Only the first truncation of each var is warned about, since a dump that does it once
+    /// tends to do it on every change.
+    warned: FnvHashSet<IdCode>,
+    warnings: Vec<Warning>,
+}
+
+impl Truncations {
+    fn new(options: &IngestorOptions) -> Self {
+        Self {
+            strict: options.is_strict(),
+            warned: FnvHashSet::default(),
+            warnings: vec![],
+        }
+    }
+
+    /// Warn that `dropped` values were cut from a change to `code`, or fail if that's not
+    /// allowed.
+    fn check(&mut self, code: IdCode, dropped: usize, width: usize) -> io::Result<()> {
+        if dropped == 0 {
+            return Ok(());
+        }
+        if self.strict {
+            let message = format!(
+                "a value of `{}` has {} more values than its declared width of {}",
+                code, dropped, width
+            );
+            return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+        }
+        if self.warned.insert(code) {
+            self.warnings.push(Warning::Truncated {
+                code: code.to_string(),
+                dropped,
+                width,
+            });
+        }
+        Ok(())
+    }
+
+    /// The warnings since this was last called.
+    fn take_warnings(&mut self) -> Vec<Warning> {
+        mem::take(&mut self.warnings)
+    }
+}
+
+/// Load a VCD file with `options`, taking the timescale from the file.
+///
+/// Vectors wider than their vars are truncated with a
+/// [warning](ligeia_core::Processed::warnings), unless the options are
+/// [strict](IngestorOptions::with_strict). Vars of types other than wires, strings and events
+/// are left out with a warning too. Errors in the file are a [`ParseError`], saying where it is.
+pub fn load_vcd<R>(
+    reader: R,
+    options: IngestorOptions,
+) -> Result<ligeia_core::Processed, Box<dyn std::error::Error>>
 where
     R: Read,
 {
+    let tracking = RefCell::new(Tracking::new(reader));
+    let ingestor = ingest(Shared(&tracking), options).map_err(|source| ParseError {
+        position: tracking.borrow().text_position(),
+        source,
+    })?;
+    Ok(ingestor.finish()?)
+}
+
+fn ingest<R: Read>(
+    reader: R,
+    options: IngestorOptions,
+) -> Result<Ingestor, Box<dyn StdError + Send + Sync>> {
     let mut parser = Parser::new(reader);
     let header = parser.parse_header()?;
 
-    let femtoseconds_per_timestep = if let Some((timesteps, unit)) = header.timescale {
-        timesteps as u128
-            * match unit {
-                vcd::TimescaleUnit::S => 1_000_000_000_000_000, // 1e15
-                vcd::TimescaleUnit::MS => 1_000_000_000_000,    // 1e12
-                vcd::TimescaleUnit::US => 1_000_000_000,        // 1e9
-                vcd::TimescaleUnit::NS => 1_000_000,            // 1e6
-                vcd::TimescaleUnit::PS => 1_000,
-                vcd::TimescaleUnit::FS => 1,
-            }
-    } else {
-        1
-    };
-
-    let mut ingestor = Ingestor::new(femtoseconds_per_timestep)?;
+    let mut truncations = Truncations::new(&options);
+    let mut ingestor = Ingestor::new(options.with_timescale(femtoseconds_per_timestep(&header)?))?;
 
-    let storage_map = generate_scopes(&header, &mut ingestor);
+    let (storage_map, widths) = generate_scopes(&header, &mut ingestor);
+    let mut values = vec![];
     let mut buffer = vec![];
 
     loop {
         if let Some(command) = parser.next_command() {
             let command = command?;
+            // Changes to vars that were left out are skipped along with them.
+            if let Command::ChangeVector(code, _)
+            | Command::ChangeScalar(code, _)
+            | Command::ChangeString(code, _) = &command
+            {
+                if !storage_map.contains_key(code) {
+                    continue;
+                }
+            }
             match command {
                 Command::Timestamp(timestamp) => {
                     ingestor.ingest_timestep(meta::Timesteps(timestamp));
                 }
-                Command::ChangeVector(code, values) => {
-                    let bytes = values.chunks(4).map(|chunk| {
-                        let mut b = 0u8;
-                        for (i, val) in chunk.iter().enumerate() {
-                            let val = match val {
-                                Value::V0 => 0,
-                                Value::V1 => 1,
-                                Value::X => 2,
-                                Value::Z => 3,
-                            };
-                            b |= val << (i * 2);
-                        }
-                        b
-                    });
-                    buffer.clear();
-                    buffer.extend(bytes);
+                Command::ChangeVector(code, vector) => {
+                    values.clear();
+                    values.extend(vector.into_iter().map(four_logic));
+                    if let Some(&width) = widths.get(&code) {
+                        let dropped = fit_width(&mut values, width, 0, |value| value > 1);
+                        truncations.check(code, dropped, width)?;
+                    }
+                    // Vectors are written most significant bit first.
+                    values.reverse();
+                    logic::pack_four(&values, &mut buffer);
 
                     ingestor.ingest_value(ligeia_core::Value {
                         storage_id: storage_map[&code],
                         data: &buffer,
                     })?;
                 }
-                Command::ChangeScalar(code, value) => {
+                Command::ChangeScalar(code, value) => match widths.get(&code) {
+                    // A scalar change to a wider var is a one-value vector.
+                    Some(&width) if width != 1 => {
+                        values.clear();
+                        values.push(four_logic(value));
+                        fit_width(&mut values, width, 0, |value| value > 1);
+                        values.reverse();
+                        logic::pack_four(&values, &mut buffer);
+
+                        ingestor.ingest_value(ligeia_core::Value {
+                            storage_id: storage_map[&code],
+                            data: &buffer,
+                        })?;
+                    }
+                    _ => {
+                        ingestor.ingest_value(ligeia_core::Value {
+                            storage_id: storage_map[&code],
+                            data: slice::from_ref(&four_logic(value)),
+                        })?;
+                    }
+                },
+                Command::ChangeString(code, text) => {
                     ingestor.ingest_value(ligeia_core::Value {
                         storage_id: storage_map[&code],
-                        data: slice::from_ref(&match value {
-                            Value::V0 => 0,
-                            Value::V1 => 1,
-                            Value::X => 2,
-                            Value::Z => 3,
-                        }),
+                        data: text.as_bytes(),
                     })?;
                 }
                 _ => {}
@@ -80,15 +225,26 @@ where
         }
     }
 
-    Ok(ingestor.finish()?)
+    for warning in truncations.take_warnings() {
+        ingestor.warn(warning);
+    }
+    Ok(ingestor)
 }
 
-fn generate_scopes(header: &Header, ingestor: &mut Ingestor) -> FnvHashMap<IdCode, StorageId> {
+/// The widths are of four-logic vars, which are the only ones whose values get fitted to them.
+/// Vars that aren't supported are left out of both maps, and scopes with too many variables in
+/// them are warned about once they've all been declared.
+fn generate_scopes(
+    header: &Header,
+    ingestor: &mut Ingestor,
+) -> (FnvHashMap<IdCode, StorageId>, FnvHashMap<IdCode, usize>) {
     fn recurse<F1, F2>(
         ingestor: &mut Ingestor,
         items: &[ScopeItem],
         parent: meta::ScopeId,
+        path: &mut Vec<String>,
         storage_map: &mut FnvHashMap<IdCode, StorageId>,
+        widths: &mut FnvHashMap<IdCode, usize>,
         scope_gen: &F1,
         storage_gen: &F2,
     ) where
@@ -99,51 +255,94 @@ fn generate_scopes(header: &Header, ingestor: &mut Ingestor) -> FnvHashMap<IdCod
             match item {
                 ScopeItem::Scope(scope) => {
                     let id = scope_gen();
-                    ingestor.ingest_scope(meta::Scope {
-                        id,
-                        parent,
-                        name: scope.identifier.clone(),
-                    });
+                    let name = ingestor.intern(&scope.identifier);
+                    ingestor.ingest_scope(meta::Scope { id, parent, name });
 
+                    path.push(scope.identifier.clone());
                     recurse(
                         ingestor,
                         &scope.children,
                         id,
+                        path,
                         storage_map,
+                        widths,
                         scope_gen,
                         storage_gen,
                     );
+                    path.pop();
+                }
+                ScopeItem::Var(var)
+                    if !matches!(
+                        var.var_type,
+                        VarType::Wire | VarType::String | VarType::Event
+                    ) =>
+                {
+                    let mut var_path = path.clone();
+                    var_path.push(var.reference.clone());
+                    ingestor.warn(Warning::Unsupported {
+                        path: var_path.join("."),
+                        kind: var.var_type.to_string(),
+                    });
                 }
                 ScopeItem::Var(var) => {
                     let storage_id = storage_gen();
                     storage_map.insert(var.code, storage_id);
 
-                    let kind = match var.var_type {
-                        VarType::Wire => meta::VarKind::Integer {
-                            storages: vec![storage_id],
-                            msb_index: var.size - 1,
-                            lsb_index: 0,
-                            signedness: meta::Signedness::Unsigned,
-                        },
-                        VarType::String => meta::VarKind::Utf8 {
-                            storage: storage_id,
-                        },
-                        _ => unimplemented!(
-                            "only wires and strings are supported in the VCD parser for the moment"
+                    // Events and empty strings can be declared zero wide, which has no top bit
+                    // to speak of, so theirs is left at 0.
+                    let (msb_index, lsb_index) = match var.index {
+                        Some(ReferenceIndex::Range(msb, lsb)) => (msb, lsb),
+                        Some(ReferenceIndex::BitSelect(index)) => (index, index),
+                        None => (var.size.saturating_sub(1), 0),
+                    };
+
+                    let (kind, ty, width) = match var.var_type {
+                        VarType::Wire => (
+                            meta::VarKind::Integer {
+                                storages: vec![storage_id],
+                                msb_index,
+                                lsb_index,
+                                signedness: meta::Signedness::Unsigned,
+                            },
+                            meta::StorageType::FourLogic,
+                            var.size,
+                        ),
+                        VarType::String => (
+                            meta::VarKind::Utf8 {
+                                storage: storage_id,
+                            },
+                            meta::StorageType::Utf8,
+                            var.size,
+                        ),
+                        // Whatever value an event's changes have is ignored, since it only
+                        // stores when they happened.
+                        VarType::Event => (
+                            meta::VarKind::Event {
+                                storage: storage_id,
+                            },
+                            meta::StorageType::Event,
+                            0,
                         ),
+                        _ => unreachable!("unsupported vars are left out"),
                     };
 
+                    if matches!(ty, meta::StorageType::FourLogic) {
+                        widths.insert(var.code, width as usize);
+                    }
+
                     ingestor.ingest_storage(meta::Storage {
                         id: storage_id,
-                        ty: meta::StorageType::FourLogic,
+                        ty,
                         start: 0,
-                        width: var.size,
+                        width,
                     });
 
+                    let name = ingestor.intern(&var.reference);
                     ingestor.ingest_var(meta::Var {
                         kind,
-                        name: var.reference.clone(),
+                        name,
                         scope_id: parent,
+                        source: None,
                     });
                 }
             }
@@ -156,15 +355,22 @@ fn generate_scopes(header: &Header, ingestor: &mut Ingestor) -> FnvHashMap<IdCod
     let storage_gen = || StorageId(storage_counter.replace(storage_counter.get() + 1));
 
     let mut storage_map = FnvHashMap::default();
+    let mut widths = FnvHashMap::default();
 
     recurse(
         ingestor,
         &header.items,
         ScopeId::ROOT,
+        &mut vec![],
         &mut storage_map,
+        &mut widths,
         &scope_gen,
         &storage_gen,
     );
 
-    storage_map
+    for scope in ingestor.crowded_scopes() {
+        ingestor.warn(Warning::Crowded(scope));
+    }
+
+    (storage_map, widths)
 }
@@ -0,0 +1,56 @@
+use std::{error::Error, io::Read};
+
+use ligeia_core::{IngestorOptions, Processed};
+use ligeia_formats::{LoaderRegistry, WaveformLoader, SNIFF_LEN};
+
+use crate::load_vcd;
+
+/// Keywords that can start the header of a VCD file.
+const HEADER_KEYWORDS: &[&[u8]] = &[
+    b"$date",
+    b"$version",
+    b"$timescale",
+    b"$comment",
+    b"$scope",
+    b"$var",
+    b"$enddefinitions",
+];
+
+pub struct VcdLoader;
+
+impl WaveformLoader for VcdLoader {
+    fn description(&self) -> String {
+        "the Value Change Dump (VCD) loader".to_string()
+    }
+
+    fn supports_file_extension(&self, s: &str) -> bool {
+        s.eq_ignore_ascii_case("vcd")
+    }
+
+    fn sniff(&self, header: &[u8; SNIFF_LEN]) -> bool {
+        let start = header
+            .iter()
+            .position(|b| !b.is_ascii_whitespace())
+            .unwrap_or(SNIFF_LEN);
+        let rest = &header[start..];
+
+        // Long keywords may be cut off by the end of the header.
+        HEADER_KEYWORDS.iter().any(|keyword| {
+            let len = keyword.len().min(rest.len());
+            len >= 4 && rest[..len] == keyword[..len]
+        })
+    }
+
+    fn load_stream(
+        &self,
+        reader: &mut dyn Read,
+        options: &IngestorOptions,
+    ) -> Result<Processed, Box<dyn Error>> {
+        load_vcd(reader, options.clone())
+    }
+}
+
+/// Add the VCD loader to `registry`.
+pub fn register(registry: &mut LoaderRegistry) {
+    registry.register(VcdLoader);
+}
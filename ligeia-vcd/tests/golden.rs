@@ -0,0 +1,237 @@
+//! Loads the VCD files in `tests/golden`, both eagerly and lazily, and compares dumps of what
+//! was loaded against the `.dump` file next to each.
+//!
+//! After a change that's meant to alter what's loaded, run with `LIGEIA_BLESS=1` to write the
+//! dumps afresh, then review the diff.
+
+use std::{
+    env,
+    fs::{self, File},
+    io::Write,
+    path::Path,
+};
+
+use ligeia_core::{
+    condition::{Condition, Interval},
+    dump::dump,
+    meta::Timesteps,
+    search::Glob,
+    IngestorOptions, Processed, Warning,
+};
+use ligeia_vcd::{load_vcd, load_vcd_lazy};
+
+/// How many changes from each end of every storage go in the dumps.
+const CHANGES: usize = 3;
+
+fn dump_to_string(mut processed: Processed) -> String {
+    let mut out = vec![];
+    dump(&mut processed, &mut out, CHANGES).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn golden() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let bless = env::var_os("LIGEIA_BLESS").is_some();
+
+    let mut vcds: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| matches!(path.extension(), Some(ext) if ext == "vcd"))
+        .collect();
+    vcds.sort();
+    assert!(!vcds.is_empty(), "no golden files in {}", dir.display());
+
+    for vcd in vcds {
+        let options = IngestorOptions::new();
+        let eager = dump_to_string(load_vcd(File::open(&vcd).unwrap(), options.clone()).unwrap());
+        let lazy = dump_to_string(load_vcd_lazy(File::open(&vcd).unwrap(), options).unwrap());
+        assert_eq!(
+            eager,
+            lazy,
+            "{} loads differently when loaded lazily",
+            vcd.display()
+        );
+
+        let golden = vcd.with_extension("dump");
+        if bless {
+            fs::write(&golden, &eager).unwrap();
+            continue;
+        }
+        let expected = fs::read_to_string(&golden)
+            .unwrap_or_else(|e| panic!("couldn't read {}: {}", golden.display(), e));
+        assert_eq!(
+            eager,
+            expected,
+            "{} doesn't match {}; if that's intended, rerun with LIGEIA_BLESS=1",
+            vcd.display(),
+            golden.display()
+        );
+    }
+}
+
+#[test]
+fn strict_loading_rejects_overlong_vectors() {
+    let vcd = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/widths.vcd");
+    let options = IngestorOptions::new().with_strict();
+
+    assert!(load_vcd(File::open(&vcd).unwrap(), options.clone()).is_err());
+
+    // Lazily loaded values aren't decoded until they're used.
+    let mut lazy = load_vcd_lazy(File::open(&vcd).unwrap(), options).unwrap();
+    let results: Vec<_> = lazy
+        .storage_ids()
+        .into_iter()
+        .map(|id| lazy.load_storage(id, |_, _| {}).is_ok())
+        .collect();
+    // Only `long` has vectors that are too wide.
+    assert_eq!(results, [true, false, true]);
+}
+
+#[test]
+fn ignored_vars_are_not_loaded() {
+    let vcd = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/basic.vcd");
+    let options = IngestorOptions::new().with_ignored(Glob::new("top.cpu.*").unwrap());
+
+    for processed in [
+        load_vcd(File::open(&vcd).unwrap(), options.clone()).unwrap(),
+        load_vcd_lazy(File::open(&vcd).unwrap(), options.clone()).unwrap(),
+    ] {
+        let paths: Vec<_> = processed
+            .vars()
+            .iter()
+            .map(|var| processed.var_path(var))
+            .collect();
+        assert_eq!(paths, ["top.clk", "top.rst", "top.nibble"]);
+        assert_eq!(processed.ignored_vars(), 2);
+        assert_eq!(processed.storage_ids().len(), 3);
+    }
+}
+
+#[test]
+fn conditions_about_one_variable() {
+    let vcd = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/basic.vcd");
+    let mut processed = load_vcd(File::open(&vcd).unwrap(), IngestorOptions::new()).unwrap();
+    let interval = |start, end: Option<u64>| Interval {
+        start: Timesteps(start),
+        end: end.map(Timesteps),
+    };
+
+    let unknown = Condition::parse_for("unknown(value)", &processed, "top.cpu.data").unwrap();
+    assert_eq!(
+        unknown.intervals(&mut processed).unwrap(),
+        [interval(0, Some(10))]
+    );
+    // `z` counts as unknown too.
+    let unknown = Condition::parse_for("unknown(value)", &processed, "top.cpu.state").unwrap();
+    assert_eq!(
+        unknown.intervals(&mut processed).unwrap(),
+        [interval(0, None)]
+    );
+
+    // Only the part in range is looked at, so what held before it starts with it.
+    let equal = Condition::parse_for("value == 0xa5", &processed, "top.cpu.data").unwrap();
+    assert_eq!(
+        equal
+            .intervals_in(&mut processed, Timesteps(12)..Timesteps(30))
+            .unwrap(),
+        [interval(12, Some(25))]
+    );
+    let reset = Condition::parse("rst", &processed).unwrap();
+    assert_eq!(
+        reset
+            .intervals_in(&mut processed, Timesteps(5)..Timesteps(8))
+            .unwrap(),
+        [interval(5, None)]
+    );
+}
+
+#[test]
+fn errors_say_where_they_are() {
+    let mut vcd = tempfile::NamedTempFile::new().unwrap();
+    vcd.write_all(
+        b"$timescale 1 ns $end\n\
+          $scope module top $end\n\
+          $var wire 1 ! clk $end\n\
+          $upscope $end\n\
+          $enddefinitions $end\n\
+          #0\n\
+          0!\n\
+          #1x\n\
+          1!\n",
+    )
+    .unwrap();
+
+    let eager = load_vcd(File::open(vcd.path()).unwrap(), IngestorOptions::new())
+        .err()
+        .unwrap();
+    let lazy = load_vcd_lazy(File::open(vcd.path()).unwrap(), IngestorOptions::new())
+        .err()
+        .unwrap();
+    for error in [eager.to_string(), lazy.to_string()] {
+        assert!(error.contains(", line 8, near `#1x`: "), "{}", error);
+    }
+    // Lazy loading knows where the token starts.
+    assert!(lazy.to_string().starts_with("at byte 108, line 8"));
+}
+
+#[test]
+fn workarounds_are_warned_about() {
+    let truncated = Warning::Truncated {
+        code: "\"".to_string(),
+        dropped: 2,
+        width: 4,
+    };
+    let vcd = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/widths.vcd");
+    let eager = load_vcd(File::open(&vcd).unwrap(), IngestorOptions::new()).unwrap();
+    assert_eq!(eager.warnings(), [truncated.clone()]);
+
+    // Lazily loaded values aren't truncated until they're loaded.
+    let mut lazy = load_vcd_lazy(File::open(&vcd).unwrap(), IngestorOptions::new()).unwrap();
+    assert!(lazy.warnings().is_empty());
+    for id in lazy.storage_ids() {
+        lazy.load_storage(id, |_, _| {}).unwrap();
+    }
+    assert_eq!(lazy.warnings(), [truncated]);
+
+    // With warnings as errors, only the storage that was truncated fails to load, every time.
+    let options = IngestorOptions::new().with_warnings_as_errors();
+    let mut lazy = load_vcd_lazy(File::open(&vcd).unwrap(), options).unwrap();
+    for _ in 0..2 {
+        let results: Vec<_> = lazy
+            .storage_ids()
+            .into_iter()
+            .map(|id| lazy.load_storage(id, |_, _| {}).is_ok())
+            .collect();
+        assert_eq!(results, [true, false, true]);
+    }
+
+    let mut vcd = tempfile::NamedTempFile::new().unwrap();
+    vcd.write_all(
+        b"$scope module top $end\n\
+          $var wire 1 ! clk $end\n\
+          $var real 64 % r $end\n\
+          $upscope $end\n\
+          $enddefinitions $end\n\
+          #0\n\
+          0!\n\
+          r1.5 %\n",
+    )
+    .unwrap();
+    for processed in [
+        load_vcd(File::open(vcd.path()).unwrap(), IngestorOptions::new()).unwrap(),
+        load_vcd_lazy(File::open(vcd.path()).unwrap(), IngestorOptions::new()).unwrap(),
+    ] {
+        assert_eq!(processed.vars().len(), 1);
+        assert_eq!(
+            processed.warnings(),
+            [Warning::Unsupported {
+                path: "top.r".to_string(),
+                kind: "real".to_string(),
+            }]
+        );
+    }
+
+    let options = IngestorOptions::new().with_warnings_as_errors();
+    assert!(load_vcd(File::open(vcd.path()).unwrap(), options).is_err());
+}
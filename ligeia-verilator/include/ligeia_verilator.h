@@ -0,0 +1,79 @@
+/*
+ * Tracing a verilated model straight into ligeia, without writing a VCD file.
+ *
+ * Verilator traces through a subclass of `VerilatedTrace`, and these functions take the same
+ * calls it does, so that a subclass only has to forward them:
+ *
+ *   declBit, declBus, declQuad, declArray  ->  ligeia_verilator_decl
+ *   dump(time), or emitTimeChange          ->  ligeia_verilator_time
+ *   emitBit, emitCData, emitSData,
+ *   emitIData, emitQData                   ->  ligeia_verilator_change
+ *   emitWData                              ->  ligeia_verilator_change_wide
+ *
+ * Doubles and events aren't traced. Link against `libligeia_verilator` (static or shared) from
+ * the `ligeia-verilator` crate.
+ *
+ * Where the trace goes is picked when the model starts. Passing NULL as the destination to
+ * `ligeia_verilator_open` reads it from the `LIGEIA_TRACE` environment variable, so that the
+ * same binary can trace to a VCD file as usual, or to ligeia:
+ *
+ *   - `memory` ingests the trace into a waveform in memory, which a Rust testbench driving the
+ *     model takes back with `Tracer::from_raw` once the simulation is done.
+ *   - Anything else is the path of a ring buffer to create, like `/dev/shm/model.ring`, which
+ *     ligeia reads the trace out of as it's written when the same path is opened.
+ *
+ * Every function that can fail returns 0 on success and -1 otherwise, after which
+ * `ligeia_verilator_last_error` says what went wrong.
+ */
+
+#ifndef LIGEIA_VERILATOR_H
+#define LIGEIA_VERILATOR_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+struct ligeia_verilator;
+
+/*
+ * Start tracing a model whose time is in steps of `femtoseconds_per_timestep`, to `destination`
+ * or wherever `LIGEIA_TRACE` says if it's NULL.
+ *
+ * Returns NULL if tracing to ligeia is off, with `error` left empty, or if it fails, after
+ * writing a nul-terminated message, truncated to fit, into `error`.
+ */
+struct ligeia_verilator *ligeia_verilator_open(const char *destination,
+                                               uint64_t femtoseconds_per_timestep, char *error,
+                                               size_t error_len);
+
+/* What went wrong with the last call that failed, valid until the next one. */
+const char *ligeia_verilator_last_error(const struct ligeia_verilator *tracer);
+
+/*
+ * A signal, with its hierarchical name separated by spaces like Verilator gives it, or by dots.
+ * Single bits are declared `[0:0]`. Declaring a code again gives the same signal another name.
+ */
+int ligeia_verilator_decl(struct ligeia_verilator *tracer, uint32_t code, const char *name,
+                          int msb, int lsb);
+
+/* Changes after this are at `time`, in timesteps. Time never goes backwards. */
+int ligeia_verilator_time(struct ligeia_verilator *tracer, uint64_t time);
+
+/* A change to a signal of up to 64 bits. */
+int ligeia_verilator_change(struct ligeia_verilator *tracer, uint32_t code, uint64_t value);
+
+/* A change to a wider signal, as the `len` words of its `WData`, least significant first. */
+int ligeia_verilator_change_wide(struct ligeia_verilator *tracer, uint32_t code,
+                                 const uint32_t *words, size_t len);
+
+/* Finish tracing, and free `tracer`, whether or not that succeeds. */
+int ligeia_verilator_close(struct ligeia_verilator *tracer);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
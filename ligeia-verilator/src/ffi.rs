@@ -0,0 +1,120 @@
+//! The C interface in `include/ligeia_verilator.h`.
+
+use std::{
+    ffi::{CStr, CString},
+    os::raw::{c_char, c_int},
+    ptr, slice,
+};
+
+use crate::{Destination, Error, Tracer};
+
+fn message(e: &Error) -> CString {
+    CString::new(e.to_string().replace('\0', "")).unwrap()
+}
+
+fn check(tracer: &mut Tracer, result: Result<(), Error>) -> c_int {
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            tracer.error = message(&e);
+            -1
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ligeia_verilator_open(
+    destination: *const c_char,
+    femtoseconds_per_timestep: u64,
+    error: *mut c_char,
+    error_len: usize,
+) -> *mut Tracer {
+    let destination = if destination.is_null() {
+        Destination::from_env()
+    } else {
+        Some(Destination::parse(
+            &CStr::from_ptr(destination).to_string_lossy(),
+        ))
+    };
+
+    let result = match destination {
+        Some(destination) => Tracer::open(destination, femtoseconds_per_timestep.into()),
+        // Tracing is off, which isn't an error.
+        None => {
+            if error_len > 0 {
+                *error = 0;
+            }
+            return ptr::null_mut();
+        }
+    };
+    match result {
+        Ok(tracer) => tracer.into_raw(),
+        Err(e) => {
+            if error_len > 0 {
+                let message = message(&e);
+                let bytes = message.as_bytes();
+                let len = bytes.len().min(error_len - 1);
+                ptr::copy_nonoverlapping(bytes.as_ptr() as *const c_char, error, len);
+                *error.add(len) = 0;
+            }
+            ptr::null_mut()
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ligeia_verilator_last_error(tracer: *const Tracer) -> *const c_char {
+    (*tracer).error.as_ptr()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ligeia_verilator_decl(
+    tracer: *mut Tracer,
+    code: u32,
+    name: *const c_char,
+    msb: c_int,
+    lsb: c_int,
+) -> c_int {
+    let tracer = &mut *tracer;
+    let name = CStr::from_ptr(name).to_string_lossy();
+    let result = tracer.declare(code, &name, msb, lsb);
+    check(tracer, result)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ligeia_verilator_time(tracer: *mut Tracer, time: u64) -> c_int {
+    let tracer = &mut *tracer;
+    let result = tracer.time(time);
+    check(tracer, result)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ligeia_verilator_change(
+    tracer: *mut Tracer,
+    code: u32,
+    value: u64,
+) -> c_int {
+    let tracer = &mut *tracer;
+    let result = tracer.change(code, &[value as u32, (value >> 32) as u32]);
+    check(tracer, result)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ligeia_verilator_change_wide(
+    tracer: *mut Tracer,
+    code: u32,
+    words: *const u32,
+    len: usize,
+) -> c_int {
+    let tracer = &mut *tracer;
+    let result = tracer.change(code, slice::from_raw_parts(words, len));
+    check(tracer, result)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ligeia_verilator_close(tracer: *mut Tracer) -> c_int {
+    match Tracer::from_raw(tracer).finish() {
+        Ok(_) => 0,
+        Err(_) => -1,
+    }
+}
@@ -0,0 +1,280 @@
+//! Tracing a verilated model straight into ligeia, without a VCD file in between.
+//!
+//! Verilator traces through a subclass of `VerilatedTrace`, which is told about each signal once
+//! with a `decl*` call and then about every change with an `emit*` call, both by the signal's
+//! code. A [`Tracer`] takes the same calls, through `include/ligeia_verilator.h` from C++, and
+//! either ingests the changes into a waveform in memory, for a Rust testbench to look at when
+//! the simulation's done, or streams them through a ring buffer to a viewer that has it open.
+//! Which one is picked when the model starts, usually from `LIGEIA_TRACE`.
+//!
+//! Verilated signals are two-state, so every signal gets a two-logic storage. Signals that
+//! share a code are the same net under different names, and share a storage.
+
+use std::{env, ffi::CString, io, path::PathBuf};
+
+use fnv::FnvHashMap;
+use ligeia_core::{
+    meta::{self, ScopeId, StorageId, Timesteps},
+    Ingestor, IngestorOptions, Processed,
+};
+use ligeia_shm::{RingWriter, StorageType, SvcbWriter, VarKind};
+
+mod ffi;
+
+/// Where to trace to, if the caller doesn't say: `memory`, or the path of a ring buffer to create.
+/// Tracing is off when it isn't set.
+pub const TRACE_VAR: &str = "LIGEIA_TRACE";
+
+/// How big ring buffers are made.
+pub const RING_CAPACITY: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("an i/o error occured")]
+    Io(#[from] io::Error),
+    #[error("signal `{0}` has a negative bit index, which isn't supported")]
+    NegativeIndex(String),
+    #[error("signal `{0}` has no name")]
+    EmptyName(String),
+    #[error("signal code {0} changed before it was declared")]
+    UnknownCode(u32),
+    #[error("time went backwards from {0} to {1}")]
+    Backwards(u64, u64),
+    #[error("{0}")]
+    Core(#[from] ligeia_core::Error),
+}
+
+/// Where a [`Tracer`] sends what it's given.
+#[derive(Debug, Clone)]
+pub enum Destination {
+    /// Into a waveform in memory, which [`Tracer::finish`] returns.
+    Memory(IngestorOptions),
+    /// Through a ring buffer at a path, for a viewer to open while the simulation runs.
+    Ring(PathBuf),
+}
+
+impl Destination {
+    /// `memory` for [`Destination::Memory`], and anything else for the path of a ring.
+    pub fn parse(s: &str) -> Self {
+        match s {
+            "memory" => Self::Memory(IngestorOptions::new()),
+            path => Self::Ring(PathBuf::from(path)),
+        }
+    }
+
+    /// The destination given by [`TRACE_VAR`], if tracing is on.
+    pub fn from_env() -> Option<Self> {
+        match env::var(TRACE_VAR) {
+            Ok(s) if !s.is_empty() => Some(Self::parse(&s)),
+            _ => None,
+        }
+    }
+}
+
+enum Sink {
+    Memory(Ingestor),
+    Ring(SvcbWriter<RingWriter>),
+}
+
+struct Signal {
+    width: u32,
+}
+
+pub struct Tracer {
+    sink: Sink,
+    /// Scope ids by their path, joined with dots.
+    scopes: FnvHashMap<String, u32>,
+    signals: FnvHashMap<u32, Signal>,
+    time: Option<u64>,
+    value: Vec<u8>,
+    /// What went wrong last, for `ligeia_verilator_last_error`.
+    error: CString,
+}
+
+impl Tracer {
+    /// Start tracing a simulation in steps of `femtoseconds_per_timestep`.
+    pub fn open(destination: Destination, femtoseconds_per_timestep: u128) -> Result<Self, Error> {
+        let sink = match destination {
+            Destination::Memory(options) => Sink::Memory(Ingestor::new(
+                options.with_timescale(femtoseconds_per_timestep),
+            )?),
+            Destination::Ring(path) => Sink::Ring(SvcbWriter::new(
+                RingWriter::create(path, RING_CAPACITY)?,
+                femtoseconds_per_timestep,
+            )?),
+        };
+
+        Ok(Self {
+            sink,
+            scopes: FnvHashMap::default(),
+            signals: FnvHashMap::default(),
+            time: None,
+            value: vec![],
+            error: CString::default(),
+        })
+    }
+
+    /// Declare a signal by its hierarchical name, separated by spaces like Verilator does or by
+    /// dots, and the bit indices it's declared with. Declaring a code again gives the same
+    /// signal another name.
+    pub fn declare(&mut self, code: u32, name: &str, msb: i32, lsb: i32) -> Result<(), Error> {
+        if msb < 0 || lsb < 0 {
+            return Err(Error::NegativeIndex(name.to_string()));
+        }
+        let (msb, lsb) = (msb as u32, lsb as u32);
+
+        let separator = if name.contains(' ') { ' ' } else { '.' };
+        let mut path: Vec<&str> = name.split(separator).filter(|s| !s.is_empty()).collect();
+        let var_name = path
+            .pop()
+            .ok_or_else(|| Error::EmptyName(name.to_string()))?;
+        let scope = self.scope(&path)?;
+
+        if !self.signals.contains_key(&code) {
+            let width = msb.abs_diff(lsb) + 1;
+            self.signals.insert(code, Signal { width });
+            match &mut self.sink {
+                Sink::Memory(ingestor) => ingestor.ingest_storage(meta::Storage {
+                    id: StorageId(code),
+                    ty: meta::StorageType::TwoLogic,
+                    width,
+                    start: 0,
+                }),
+                Sink::Ring(writer) => writer.storage(code, StorageType::TwoLogic, width)?,
+            }
+        }
+
+        match &mut self.sink {
+            Sink::Memory(ingestor) => {
+                let name = ingestor.intern(var_name);
+                ingestor.ingest_var(meta::Var {
+                    name,
+                    scope_id: ScopeId(scope),
+                    kind: meta::VarKind::Integer {
+                        storages: vec![StorageId(code)],
+                        msb_index: msb,
+                        lsb_index: lsb,
+                        signedness: meta::Signedness::Unsigned,
+                    },
+                    source: None,
+                });
+            }
+            Sink::Ring(writer) => writer.var(
+                scope,
+                var_name,
+                VarKind::Integer {
+                    storages: &[code],
+                    msb,
+                    lsb,
+                    signed: false,
+                },
+            )?,
+        }
+        Ok(())
+    }
+
+    /// The id of the scope at `path`, declaring it and its parents if they're new.
+    fn scope(&mut self, path: &[&str]) -> Result<u32, Error> {
+        let mut parent = ScopeId::ROOT.0;
+        let mut key = String::new();
+        for name in path {
+            if !key.is_empty() {
+                key.push('.');
+            }
+            key.push_str(name);
+            if let Some(&id) = self.scopes.get(&key) {
+                parent = id;
+                continue;
+            }
+
+            let id = self.scopes.len() as u32 + 1;
+            self.scopes.insert(key.clone(), id);
+            match &mut self.sink {
+                Sink::Memory(ingestor) => {
+                    let name = ingestor.intern(name);
+                    ingestor.ingest_scope(meta::Scope {
+                        name,
+                        id: ScopeId(id),
+                        parent: ScopeId(parent),
+                    });
+                }
+                Sink::Ring(writer) => writer.scope(id, parent, name)?,
+            }
+            parent = id;
+        }
+        Ok(parent)
+    }
+
+    /// Changes from now on happen at `time`, like Verilator's `dump(time)`.
+    pub fn time(&mut self, time: u64) -> Result<(), Error> {
+        match self.time {
+            Some(last) if time < last => return Err(Error::Backwards(last, time)),
+            Some(last) if time == last => return Ok(()),
+            _ => {}
+        }
+        self.time = Some(time);
+
+        match &mut self.sink {
+            Sink::Memory(ingestor) => ingestor.ingest_timestep(Timesteps(time)),
+            Sink::Ring(writer) => writer.time(time)?,
+        }
+        Ok(())
+    }
+
+    /// A change to a signal, as the 32-bit words Verilator keeps wide values in, least
+    /// significant first. Narrower values are a single word, or two for a `QData`.
+    pub fn change(&mut self, code: u32, words: &[u32]) -> Result<(), Error> {
+        let width = self
+            .signals
+            .get(&code)
+            .ok_or(Error::UnknownCode(code))?
+            .width;
+
+        // Two-logic storages are packed least significant bit first, just like the words.
+        let bytes = ((width + 7) / 8) as usize;
+        self.value.clear();
+        self.value
+            .extend(words.iter().flat_map(|word| word.to_le_bytes()));
+        self.value.resize(bytes, 0);
+        // Verilator doesn't promise that the bits past the top of a signal are clear.
+        if width % 8 != 0 {
+            self.value[bytes - 1] &= (1 << (width % 8)) - 1;
+        }
+
+        match &mut self.sink {
+            Sink::Memory(ingestor) => ingestor.ingest_value(ligeia_core::Value {
+                storage_id: StorageId(code),
+                data: &self.value,
+            })?,
+            Sink::Ring(writer) => writer.change(code, &self.value)?,
+        }
+        Ok(())
+    }
+
+    /// Finish tracing, returning the waveform if it was traced into memory.
+    pub fn finish(self) -> Result<Option<Processed>, Error> {
+        match self.sink {
+            Sink::Memory(ingestor) => Ok(Some(ingestor.finish()?)),
+            // Dropping the ring once the stream is finished tells the viewer it's ended.
+            Sink::Ring(writer) => {
+                writer.finish()?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Hand the tracer to C++, as the handle the functions in `ligeia_verilator.h` take. A Rust
+    /// testbench tracing into memory gets it back with [`Tracer::from_raw`].
+    pub fn into_raw(self) -> *mut Tracer {
+        Box::into_raw(Box::new(self))
+    }
+
+    /// Take back a tracer from C++.
+    ///
+    /// # Safety
+    /// `tracer` must have come from [`Tracer::into_raw`] or `ligeia_verilator_open`, and not
+    /// have been taken back or closed already.
+    pub unsafe fn from_raw(tracer: *mut Tracer) -> Self {
+        *Box::from_raw(tracer)
+    }
+}
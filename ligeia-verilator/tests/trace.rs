@@ -0,0 +1,106 @@
+//! A model traces the same whether it's ingested into memory or streamed through a ring buffer,
+//! and calls Verilator wouldn't make are refused.
+
+use std::thread;
+
+use ligeia_core::{dump::dump, IngestorOptions, Processed};
+use ligeia_verilator::{Destination, Error, Tracer};
+
+fn dump_to_string(mut processed: Processed) -> String {
+    let mut out = vec![];
+    dump(&mut processed, &mut out, 3).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+/// What a verilated counter would call, with a clock that's also seen from inside the counter.
+fn simulate(tracer: &mut Tracer) {
+    tracer.declare(1, "TOP clk", 0, 0).unwrap();
+    tracer.declare(1, "TOP counter clk", 0, 0).unwrap();
+    tracer.declare(2, "TOP counter value", 2, 0).unwrap();
+    tracer.declare(3, "TOP.counter.wide", 69, 0).unwrap();
+
+    for cycle in 0..50u32 {
+        tracer.time(cycle as u64 * 10).unwrap();
+        tracer.change(1, &[1, 0]).unwrap();
+        // Verilator leaves junk above the top bit now and then.
+        tracer.change(2, &[cycle | 0xffff_fff8, 0]).unwrap();
+        tracer.change(3, &[cycle, !0, !0]).unwrap();
+        tracer.time(cycle as u64 * 10 + 5).unwrap();
+        tracer.change(1, &[0, 0]).unwrap();
+    }
+}
+
+#[test]
+fn trace_into_memory() {
+    let mut tracer = Tracer::open(Destination::Memory(IngestorOptions::new()), 1000).unwrap();
+    simulate(&mut tracer);
+    let processed = tracer.finish().unwrap().unwrap();
+    assert_eq!(processed.femtoseconds_per_timestep(), 1000);
+
+    let dump = dump_to_string(processed);
+    for expected in [
+        "time bounds: 0..495",
+        // Scopes come from the spaces in Verilator's names, or dots.
+        "TOP\n  clk: integer [0:0] Unsigned, storages 1\n  counter\n",
+        "    clk: integer [0:0] Unsigned, storages 1\n",
+        "    wide: integer [69:0] Unsigned, storages 3\n",
+        // The aliased clock is one storage.
+        "1: TwoLogic, 1 bits from 0, 100 changes\n  0 1\n  5 0\n",
+        "2: TwoLogic, 3 bits from 0, 50 changes\n  0 000\n  10 001\n",
+        "3: TwoLogic, 70 bits from 0, 50 changes\n  0 111111",
+    ] {
+        assert!(dump.contains(expected), "{} isn't in:\n{}", expected, dump);
+    }
+}
+
+#[test]
+fn trace_through_ring() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("model.ring");
+    let mut tracer = Tracer::open(Destination::Ring(path.clone()), 1000).unwrap();
+    let simulating = thread::spawn(move || {
+        simulate(&mut tracer);
+        assert!(tracer.finish().unwrap().is_none());
+    });
+
+    let processed = ligeia_svcb::load_ring(&path, IngestorOptions::new()).unwrap();
+    simulating.join().unwrap();
+
+    let mut tracer = Tracer::open(Destination::Memory(IngestorOptions::new()), 1000).unwrap();
+    simulate(&mut tracer);
+    let expected = tracer.finish().unwrap().unwrap();
+    assert_eq!(dump_to_string(processed), dump_to_string(expected));
+}
+
+#[test]
+fn refused_calls() {
+    let mut tracer = Tracer::open(Destination::Memory(IngestorOptions::new()), 1000).unwrap();
+    assert!(matches!(
+        tracer.declare(1, "TOP x", -1, 0),
+        Err(Error::NegativeIndex(_))
+    ));
+    assert!(matches!(
+        tracer.declare(1, " ", 0, 0),
+        Err(Error::EmptyName(_))
+    ));
+    assert!(matches!(
+        tracer.change(7, &[0, 0]),
+        Err(Error::UnknownCode(7))
+    ));
+
+    tracer.time(10).unwrap();
+    tracer.time(10).unwrap();
+    assert!(matches!(tracer.time(9), Err(Error::Backwards(10, 9))));
+}
+
+#[test]
+fn destinations() {
+    assert!(matches!(
+        Destination::parse("memory"),
+        Destination::Memory(_)
+    ));
+    assert!(matches!(
+        Destination::parse("/dev/shm/model.ring"),
+        Destination::Ring(path) if path.ends_with("model.ring")
+    ));
+}
@@ -0,0 +1,69 @@
+//! Notes attached to points or spans of time, like "first bad packet here", so that findings
+//! from a debugging session can be marked on the waveform and shared.
+
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Annotation {
+    pub text: String,
+    pub time: f64,
+    /// Where the span the note covers ends, if it's more than a single point in time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<f64>,
+    /// The hierarchical name of the variable the note is about, if it's about one in particular.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub var: Option<String>,
+}
+
+impl Annotation {
+    /// Whether any of the note falls between `start` and `end`.
+    fn overlaps(&self, start: f64, end: f64) -> bool {
+        self.time <= end && self.end.unwrap_or(self.time) >= start
+    }
+}
+
+/// Every note in the session, kept in time order.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Annotations {
+    annotations: Vec<Annotation>,
+}
+
+impl Annotations {
+    pub fn add(&mut self, annotation: Annotation) {
+        let index = self
+            .annotations
+            .partition_point(|other| other.time <= annotation.time);
+        self.annotations.insert(index, annotation);
+    }
+
+    /// The notes that show up in a view of the time range from `start` to `end`.
+    pub fn visible(&self, start: f64, end: f64) -> impl Iterator<Item = &Annotation> {
+        self.annotations
+            .iter()
+            .filter(move |annotation| annotation.overlaps(start, end))
+    }
+
+    /// Load notes that were written out by [`Annotations::write`], adding them to these.
+    pub fn load(&mut self, path: &Path) -> io::Result<()> {
+        let loaded: Vec<Annotation> = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+        for annotation in loaded {
+            self.add(annotation);
+        }
+        Ok(())
+    }
+
+    /// Write every note out as a JSON report, in time order.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writeln!(writer)?;
+        writer.flush()
+    }
+}
@@ -0,0 +1,126 @@
+//! How the edges of lines are smoothed.
+
+use std::{fmt, str::FromStr};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Antialiasing {
+    /// Hard edges.
+    None,
+    /// The line shader fades out the edges of each line itself. This works everywhere.
+    Analytic,
+    /// Multisampling, with this many samples per pixel.
+    Msaa(u32),
+}
+
+impl Antialiasing {
+    /// wgpu only supports this many samples, besides one.
+    pub const MSAA_SAMPLES: u32 = 4;
+
+    /// The next mode along, for cycling through them from the keyboard.
+    pub fn next(self) -> Self {
+        match self {
+            Antialiasing::None => Antialiasing::Analytic,
+            Antialiasing::Analytic => Antialiasing::Msaa(Self::MSAA_SAMPLES),
+            Antialiasing::Msaa(_) => Antialiasing::None,
+        }
+    }
+
+    /// The mode to actually use, given whether the adapter can multisample the surface format.
+    ///
+    /// Multisampling that can't be done falls back to analytic antialiasing.
+    pub fn supported(self, msaa_supported: bool) -> Self {
+        match self {
+            Antialiasing::Msaa(1) => Antialiasing::None,
+            Antialiasing::Msaa(samples) if samples != Self::MSAA_SAMPLES || !msaa_supported => {
+                Antialiasing::Analytic
+            }
+            mode => mode,
+        }
+    }
+
+    pub fn sample_count(self) -> u32 {
+        match self {
+            Antialiasing::Msaa(samples) => samples,
+            _ => 1,
+        }
+    }
+
+    /// The fraction of each side of a line that the shader fades out.
+    pub fn feather_fraction(self) -> f32 {
+        match self {
+            Antialiasing::Analytic => 0.4,
+            _ => 0.0,
+        }
+    }
+}
+
+impl FromStr for Antialiasing {
+    type Err = String;
+
+    /// Parses `none`, `analytic`, `msaa`, or `msaa` followed by a sample count, like `msaa4`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Antialiasing::None),
+            "analytic" => Ok(Antialiasing::Analytic),
+            "msaa" => Ok(Antialiasing::Msaa(Self::MSAA_SAMPLES)),
+            _ => s
+                .strip_prefix("msaa")
+                .and_then(|samples| samples.parse().ok())
+                .filter(|&samples| samples >= 1)
+                .map(Antialiasing::Msaa)
+                .ok_or_else(|| format!("unknown antialiasing mode `{}`", s)),
+        }
+    }
+}
+
+impl fmt::Display for Antialiasing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Antialiasing::None => write!(f, "none"),
+            Antialiasing::Analytic => write!(f, "analytic"),
+            Antialiasing::Msaa(samples) => write!(f, "msaa{}", samples),
+        }
+    }
+}
+
+/// The antialiasing mode in use, kept to one the adapter supports.
+#[derive(Debug)]
+pub struct AntialiasingSetting {
+    mode: Antialiasing,
+    msaa_supported: bool,
+}
+
+impl AntialiasingSetting {
+    pub fn new(requested: Antialiasing, msaa_supported: bool) -> Self {
+        let mut setting = Self {
+            mode: requested,
+            msaa_supported,
+        };
+        setting.set(requested);
+        setting
+    }
+
+    pub fn mode(&self) -> Antialiasing {
+        self.mode
+    }
+
+    /// Switch modes, falling back to one the adapter supports if need be.
+    pub fn set(&mut self, requested: Antialiasing) {
+        self.mode = requested.supported(self.msaa_supported);
+        if self.mode != requested {
+            eprintln!(
+                "antialiasing mode {} isn't supported here, so using {}",
+                requested, self.mode
+            );
+        }
+    }
+
+    /// Switch to the next mode the adapter supports.
+    pub fn cycle(&mut self) {
+        let mut next = self.mode.next();
+        while next.supported(self.msaa_supported) != next {
+            next = next.next();
+        }
+        self.mode = next;
+    }
+}
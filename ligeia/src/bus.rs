@@ -0,0 +1,74 @@
+//! Draws the tracks of buses and strings from the tile pool, with `shaders/bus.wgsl`: each value
+//! as a hexagon, with transitions where they cross. Their values are labelled by the
+//! [text pass](crate::text).
+
+use std::{borrow::Cow, sync::Arc};
+
+use crate::{
+    render_graph::Pass,
+    scene::Scene,
+    tile_draws::{self, TileDraws},
+    uploads::Uploader,
+};
+
+pub struct BusPass {
+    shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    /// Built by `configure`, since it depends on the sample count.
+    pipeline: Option<wgpu::RenderPipeline>,
+    tiles: TileDraws,
+}
+
+impl BusPass {
+    /// A pass that reads the tiles it draws from `pool`, the [`TilePool`]'s buffer.
+    ///
+    /// [`TilePool`]: crate::residency::TilePool
+    pub fn new(device: &wgpu::Device, pool: Arc<wgpu::Buffer>) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("bus.wgsl"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(concat!(
+                include_str!("shaders/common.wgsl"),
+                include_str!("shaders/tiles.wgsl"),
+                include_str!("shaders/bus.wgsl"),
+            ))),
+        });
+        let tiles = TileDraws::new(device, pool);
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[tiles.bind_group_layout()],
+            push_constant_ranges: &[],
+        });
+
+        Self {
+            shader,
+            pipeline_layout,
+            pipeline: None,
+            tiles,
+        }
+    }
+}
+
+impl Pass for BusPass {
+    fn configure(&mut self, device: &wgpu::Device, format: wgpu::TextureFormat, sample_count: u32) {
+        self.pipeline = Some(tile_draws::create_pipeline(
+            device,
+            &self.pipeline_layout,
+            &self.shader,
+            ("vs_bus", "fs_bus"),
+            format,
+            sample_count,
+        ));
+    }
+
+    fn prepare(&mut self, uploader: &mut Uploader, scene: &Scene) {
+        self.tiles
+            .prepare(uploader, scene, |pane| pane.buses.as_slice());
+    }
+
+    fn draw<'a>(&'a self, rpass: &mut wgpu::RenderPass<'a>, scene: &Scene) {
+        if let Some(pipeline) = &self.pipeline {
+            // Six edges for each value.
+            self.tiles.draw(rpass, scene, pipeline, 6);
+        }
+    }
+}
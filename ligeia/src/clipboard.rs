@@ -0,0 +1,64 @@
+//! Copying paths, values and snippets of waveforms to the system clipboard, for pasting into
+//! bug reports.
+
+use std::fmt::Write;
+
+/// Put `text` on the system clipboard.
+///
+/// On X11 the clipboard belongs to whichever process set it, so the text only outlives this
+/// process if a clipboard manager is running to take it over.
+pub fn copy(text: &str) -> Result<(), String> {
+    let mut clipboard =
+        arboard::Clipboard::new().map_err(|e| format!("couldn't open the clipboard: {}", e))?;
+    clipboard
+        .set_text(text.to_string())
+        .map_err(|e| format!("couldn't copy to the clipboard: {}", e))
+}
+
+/// Quote a CSV field if it needs it.
+fn csv_field(field: &str) -> String {
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Lay out the changes to several variables as CSV, with a `time` column followed by a column
+/// for each variable, headed by its name.
+///
+/// `columns` holds each variable's changes in time order. There's a row for every time any of
+/// them changes, holding each variable's value at that time, or nothing before its first change.
+pub fn csv_snippet(names: &[String], columns: &[Vec<(u64, String)>]) -> String {
+    let mut csv = String::from("time");
+    for name in names {
+        csv.push(',');
+        csv.push_str(&csv_field(name));
+    }
+    csv.push('\n');
+
+    let mut times: Vec<u64> = columns
+        .iter()
+        .flat_map(|changes| changes.iter().map(|&(time, _)| time))
+        .collect();
+    times.sort_unstable();
+    times.dedup();
+
+    // How far through each column's changes the rows have got.
+    let mut next = vec![0; columns.len()];
+    for time in times {
+        write!(csv, "{}", time).unwrap();
+        for (changes, next) in columns.iter().zip(&mut next) {
+            while matches!(changes.get(*next), Some(&(t, _)) if t <= time) {
+                *next += 1;
+            }
+            csv.push(',');
+            if let Some(i) = next.checked_sub(1) {
+                csv.push_str(&csv_field(&changes[i].1));
+            }
+        }
+        csv.push('\n');
+    }
+
+    csv
+}
@@ -0,0 +1,250 @@
+use std::{path::PathBuf, sync::mpsc::Sender};
+
+use ligeia_core::resample::Edge;
+use serde_json::Value;
+use winit::event::{ModifiersState, VirtualKeyCode};
+
+use crate::{
+    annotations::Annotation,
+    antialiasing::Antialiasing,
+    loading::Loaded,
+    palette::{self, Palette},
+    traces::{Highlight, Reset, Sampling},
+    triggers::Trigger,
+};
+
+/// How much a single `Z`/`Shift+Z` press zooms in or out.
+const ZOOM_STEP: f64 = 0.5;
+/// Where `P` exports the view to, since a key can't say.
+const EXPORT_PATH: &str = "ligeia-export.pdf";
+
+/// Something the viewer can be asked to do, independent of where the request came from.
+#[derive(Debug, Clone)]
+pub enum Command {
+    ZoomFull,
+    /// Scale the visible time span around the cursor; less than one zooms in.
+    Zoom(f64),
+    /// Center the view on a time, keeping the current zoom level.
+    GotoTime(f64),
+    ToggleSplit,
+    ToggleTimeLock,
+    /// Export tracks to a PDF, over a time range or what the pane under the cursor shows, of
+    /// some signals or every shown one.
+    ExportPdf {
+        path: PathBuf,
+        range: Option<(f64, f64)>,
+        signals: Option<Vec<String>>,
+    },
+    /// Start loading a waveform in the background, replacing the open one once it's loaded.
+    OpenFile(PathBuf),
+    /// Start or stop reloading the open file whenever it changes.
+    ToggleWatch,
+    /// List what the open file's loader warned about, or go back to only counting it in the
+    /// title.
+    ToggleWarnings,
+    /// Check a condition whenever a file is opened, jumping to where it first holds.
+    AddTrigger(Trigger),
+    RemoveTrigger(String),
+    /// Jump to where the first trigger goes off in the open file.
+    JumpToTrigger,
+    AddSignal(String),
+    /// Add every variable whose hierarchical name matches a glob, as a group named after it.
+    AddMatching(String),
+    /// Add a signal from each instance like one of the scopes it's in, as a group, such as
+    /// `top.core1.alu.result` and on for `top.core0.alu.result`. `instance` is the path of that
+    /// scope, like `top.core0`; without it, the innermost one with other instances is used.
+    AddAcrossInstances {
+        path: String,
+        instance: Option<String>,
+    },
+    /// Show a bus split into bytes, grouped under it.
+    SplitByteLanes(String),
+    /// Show which state an enum variable is in, in a track grouped under it.
+    ShowStates(String),
+    /// Report how long an enum variable spent in each state, over the time the pane shows.
+    SummarizeStates(String),
+    /// Keep a shown signal's track at the top of the pane while the others scroll.
+    Pin(String),
+    Unpin(String),
+    /// Scroll the tracks under the cursor down by some pixels, or up if negative.
+    ScrollTracks(f64),
+    /// Tint a shown signal's track wherever a condition about it holds.
+    Highlight(Highlight),
+    ClearHighlights(String),
+    /// Shade every track while a reset signal is asserted.
+    SetReset(Reset),
+    ClearReset,
+    /// Show tracks a clock cycle at a time, as sampled on the clock's edges.
+    SetSampling(Sampling),
+    ClearSampling,
+    PlaceMarker(f64),
+    Annotate(Annotation),
+    LoadAnnotations(PathBuf),
+    ExportAnnotations(PathBuf),
+    SetAntialiasing(Antialiasing),
+    CycleAntialiasing,
+    SetPalette(Palette),
+    CyclePalette,
+    /// Hatch unknown regions, or stop hatching them.
+    SetHatching(bool),
+    ToggleHatching,
+    /// Copy the hierarchical name of the signal under the cursor.
+    CopyPath,
+    /// Copy the value of the signal under the cursor, at the cursor.
+    CopyValue,
+    /// Copy the values of the visible signals over a time range, as CSV.
+    CopyRange {
+        start: f64,
+        end: f64,
+    },
+}
+
+/// A command sent from another thread, along with where to send its outcome.
+pub struct RemoteCommand {
+    pub command: Command,
+    pub reply: Sender<Result<(), String>>,
+}
+
+/// Something sent to the event loop from another thread.
+pub enum UserEvent {
+    Remote(RemoteCommand),
+    Loaded(Box<Loaded>),
+    /// A watched file has changed.
+    Changed(PathBuf),
+}
+
+impl Command {
+    pub fn from_key(keycode: VirtualKeyCode, modifiers: ModifiersState) -> Option<Self> {
+        Some(match keycode {
+            VirtualKeyCode::F => Command::ZoomFull,
+            VirtualKeyCode::Z if modifiers.shift() => Command::Zoom(1.0 / ZOOM_STEP),
+            VirtualKeyCode::Z => Command::Zoom(ZOOM_STEP),
+            VirtualKeyCode::S => Command::ToggleSplit,
+            VirtualKeyCode::L => Command::ToggleTimeLock,
+            VirtualKeyCode::P => Command::ExportPdf {
+                path: EXPORT_PATH.into(),
+                range: None,
+                signals: None,
+            },
+            VirtualKeyCode::A => Command::CycleAntialiasing,
+            VirtualKeyCode::K => Command::CyclePalette,
+            VirtualKeyCode::H => Command::ToggleHatching,
+            VirtualKeyCode::W if modifiers.shift() => Command::ToggleWarnings,
+            VirtualKeyCode::W => Command::ToggleWatch,
+            VirtualKeyCode::T => Command::JumpToTrigger,
+            VirtualKeyCode::C if modifiers.ctrl() && modifiers.shift() => Command::CopyPath,
+            VirtualKeyCode::C if modifiers.ctrl() => Command::CopyValue,
+            _ => return None,
+        })
+    }
+
+    /// Look up a command by its remote method name, e.g. `goto_time` with `{ "time": 100 }`.
+    pub fn from_method(method: &str, params: &Value) -> Result<Self, String> {
+        let f64_param = |name: &str| {
+            params[name]
+                .as_f64()
+                .ok_or_else(|| format!("expected a number `{}` parameter", name))
+        };
+        let str_param = |name: &str| {
+            params[name]
+                .as_str()
+                .ok_or_else(|| format!("expected a string `{}` parameter", name))
+        };
+
+        Ok(match method {
+            "zoom_full" => Command::ZoomFull,
+            "zoom" => Command::Zoom(f64_param("factor")?),
+            "goto_time" => Command::GotoTime(f64_param("time")?),
+            "toggle_split" => Command::ToggleSplit,
+            "toggle_time_lock" => Command::ToggleTimeLock,
+            "export_pdf" => Command::ExportPdf {
+                path: str_param("path")?.into(),
+                range: match (params["start"].as_f64(), params["end"].as_f64()) {
+                    (Some(start), Some(end)) => Some((start, end)),
+                    (None, None) => None,
+                    _ => return Err("expected both `start` and `end`, or neither".to_string()),
+                },
+                signals: match &params["signals"] {
+                    Value::Null => None,
+                    Value::Array(signals) => Some(
+                        signals
+                            .iter()
+                            .map(|signal| signal.as_str().map(str::to_string))
+                            .collect::<Option<_>>()
+                            .ok_or_else(|| "expected `signals` to be strings".to_string())?,
+                    ),
+                    _ => return Err("expected an array of `signals`".to_string()),
+                },
+            },
+            "open_file" => Command::OpenFile(str_param("path")?.into()),
+            "toggle_watch" => Command::ToggleWatch,
+            "toggle_warnings" => Command::ToggleWarnings,
+            "add_trigger" => Command::AddTrigger(Trigger {
+                name: str_param("name")?.to_string(),
+                condition: str_param("condition")?.to_string(),
+            }),
+            "remove_trigger" => Command::RemoveTrigger(str_param("name")?.to_string()),
+            "jump_to_trigger" => Command::JumpToTrigger,
+            "add_signal" => Command::AddSignal(str_param("path")?.to_string()),
+            "add_matching" => Command::AddMatching(str_param("pattern")?.to_string()),
+            "add_across_instances" => Command::AddAcrossInstances {
+                path: str_param("path")?.to_string(),
+                instance: params["instance"].as_str().map(str::to_string),
+            },
+            "split_byte_lanes" => Command::SplitByteLanes(str_param("path")?.to_string()),
+            "show_states" => Command::ShowStates(str_param("path")?.to_string()),
+            "summarize_states" => Command::SummarizeStates(str_param("path")?.to_string()),
+            "pin" => Command::Pin(str_param("path")?.to_string()),
+            "unpin" => Command::Unpin(str_param("path")?.to_string()),
+            "scroll_tracks" => Command::ScrollTracks(f64_param("pixels")?),
+            "highlight" => Command::Highlight(Highlight {
+                signal: str_param("path")?.to_string(),
+                condition: str_param("condition")?.to_string(),
+                color: params["color"]
+                    .as_str()
+                    .map(palette::parse_color)
+                    .transpose()?,
+            }),
+            "clear_highlights" => Command::ClearHighlights(str_param("path")?.to_string()),
+            "set_reset" => Command::SetReset(Reset {
+                signal: str_param("path")?.to_string(),
+                active_low: params["active_low"].as_bool().unwrap_or(false),
+            }),
+            "clear_reset" => Command::ClearReset,
+            "set_sampling" => Command::SetSampling(Sampling {
+                clock: str_param("clock")?.to_string(),
+                edge: match params["edge"].as_str() {
+                    Some(edge) => edge.parse()?,
+                    None => Edge::Rising,
+                },
+            }),
+            "clear_sampling" => Command::ClearSampling,
+            "place_marker" => Command::PlaceMarker(f64_param("time")?),
+            "annotate" => Command::Annotate(Annotation {
+                text: str_param("text")?.to_string(),
+                time: f64_param("time")?,
+                end: params["end"].as_f64(),
+                var: params["var"].as_str().map(str::to_string),
+            }),
+            "load_annotations" => Command::LoadAnnotations(str_param("path")?.into()),
+            "export_annotations" => Command::ExportAnnotations(str_param("path")?.into()),
+            "set_antialiasing" => Command::SetAntialiasing(str_param("mode")?.parse()?),
+            "cycle_antialiasing" => Command::CycleAntialiasing,
+            "set_palette" => Command::SetPalette(str_param("palette")?.parse()?),
+            "cycle_palette" => Command::CyclePalette,
+            "set_hatching" => Command::SetHatching(
+                params["on"]
+                    .as_bool()
+                    .ok_or("expected a boolean `on` parameter".to_string())?,
+            ),
+            "toggle_hatching" => Command::ToggleHatching,
+            "copy_path" => Command::CopyPath,
+            "copy_value" => Command::CopyValue,
+            "copy_range" => Command::CopyRange {
+                start: f64_param("start")?,
+                end: f64_param("end")?,
+            },
+            _ => return Err(format!("unknown method `{}`", method)),
+        })
+    }
+}
@@ -0,0 +1,182 @@
+//! `ligeia diff`, for checking in CI that two waveforms are equivalent.
+
+use std::path::Path;
+
+use ligeia_core::{
+    diff::{diff_storages, Mismatch},
+    meta::StorageId,
+    search::Glob,
+    timescale::Timescale,
+    Error, IngestorOptions, Processed,
+};
+
+use crate::loading;
+
+/// How a signal in the first waveform compares with the same one in the second.
+enum Outcome {
+    Match,
+    Mismatch {
+        first: Mismatch,
+        intervals: usize,
+    },
+    /// The signals can't be compared, like when one's missing.
+    Unmatched(String),
+}
+
+fn load(path: &Path, options: &IngestorOptions) -> Result<Processed, String> {
+    let mut loaders = loading::loaders();
+    loaders.set_options(options.clone());
+    let processed = loaders
+        .load_file(path, None)
+        .map_err(|e| format!("failed to open {}: {}", path.display(), e))?;
+    for warning in processed.warnings() {
+        eprintln!("warning: {}: {}", path.display(), warning);
+    }
+    Ok(processed)
+}
+
+/// Compare every signal of `a` whose path matches one of `patterns`, or every signal if there
+/// are none, with the signal of the same path in `b`.
+///
+/// Prints a line for each signal that doesn't match, and a summary. Returns whether everything
+/// matched.
+///
+/// If `b` has a finer timescale, changes that land on the same timestep of `a` are warned about,
+/// or with `strict`, are an error. So are what either file's loader warns about, with
+/// `warnings_as_errors`.
+pub fn run(
+    a_path: &Path,
+    b_path: &Path,
+    patterns: &[String],
+    strict: bool,
+    warnings_as_errors: bool,
+) -> Result<bool, String> {
+    let globs = patterns
+        .iter()
+        .map(|pattern| Glob::new(pattern).map_err(|e| format!("bad pattern `{}`: {}", pattern, e)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut options = IngestorOptions::new();
+    if warnings_as_errors {
+        options = options.with_warnings_as_errors();
+    }
+    let mut a = load(a_path, &options)?;
+    let mut b = load(b_path, &options)?;
+
+    let signals: Vec<(String, Vec<_>)> = a
+        .vars()
+        .iter()
+        .filter(|var| globs.is_empty() || globs.iter().any(|glob| glob.matches(&a.var_path(var))))
+        .map(|var| (a.var_path(var), Processed::var_storages(var).to_vec()))
+        .collect();
+    if signals.is_empty() {
+        return Err(format!("no signals in {} match", a_path.display()));
+    }
+
+    let mut differing = 0;
+    for (path, storages_a) in &signals {
+        let mut collapsed = 0;
+        let outcome = compare(&mut a, storages_a, &mut b, path, &mut collapsed)?;
+        if collapsed != 0 {
+            let message = format!(
+                "{}: {} change{} landed on the same timestep as the one before when converted \
+                 to the first waveform's timescale, so only the last was compared",
+                path,
+                collapsed,
+                if collapsed == 1 { "" } else { "s" }
+            );
+            match strict {
+                true => return Err(message),
+                false => eprintln!("warning: {}", message),
+            }
+        }
+        let line = match outcome {
+            Outcome::Match => continue,
+            Outcome::Mismatch { first, intervals } => {
+                let end = match first.end {
+                    Some(end) => format!(" until {}", end.0),
+                    None => String::new(),
+                };
+                format!(
+                    "differs from {}{}, in {} interval{}",
+                    first.start.0,
+                    end,
+                    intervals,
+                    if intervals == 1 { "" } else { "s" }
+                )
+            }
+            Outcome::Unmatched(reason) => reason,
+        };
+        differing += 1;
+        println!("{}: {}", path, line);
+    }
+
+    let timescale = match Timescale::from_femtoseconds(a.femtoseconds_per_timestep()) {
+        Some(timescale) => timescale.to_string(),
+        None => format!("{} fs", a.femtoseconds_per_timestep()),
+    };
+    println!(
+        "{} of {} signals differ (times in steps of {}, as in {})",
+        differing,
+        signals.len(),
+        timescale,
+        a_path.display()
+    );
+
+    Ok(differing == 0)
+}
+
+fn compare(
+    a: &mut Processed,
+    storages_a: &[StorageId],
+    b: &mut Processed,
+    path: &str,
+    collapsed: &mut u64,
+) -> Result<Outcome, String> {
+    let storages_b = match b.vars().iter().find(|var| b.var_path(var) == path) {
+        Some(var) => Processed::var_storages(var).to_vec(),
+        None => {
+            return Ok(Outcome::Unmatched(
+                "missing from the second waveform".to_string(),
+            ))
+        }
+    };
+    if storages_a.len() != storages_b.len() {
+        return Ok(Outcome::Unmatched(format!(
+            "stored as {} storages in the first waveform but {} in the second",
+            storages_a.len(),
+            storages_b.len()
+        )));
+    }
+
+    // A signal made of several storages differs wherever any of them do, so report the
+    // earliest mismatch of any.
+    let mut outcome = Outcome::Match;
+    for (&id_a, &id_b) in storages_a.iter().zip(&storages_b) {
+        let report = match diff_storages(a, id_a, b, id_b) {
+            Ok(report) => report,
+            Err(Error::Incomparable(..)) => {
+                return Ok(Outcome::Unmatched(
+                    "has a different width or kind of value in each waveform".to_string(),
+                ))
+            }
+            Err(e) => return Err(format!("failed to compare {}: {}", path, e)),
+        };
+        *collapsed += report.collapsed;
+        let first = match report.mismatches.first() {
+            Some(&first) => first,
+            None => continue,
+        };
+        let earlier = match &outcome {
+            Outcome::Mismatch { first: current, .. } => first.start < current.start,
+            _ => true,
+        };
+        if earlier {
+            outcome = Outcome::Mismatch {
+                first,
+                intervals: report.mismatches.len(),
+            };
+        }
+    }
+
+    Ok(outcome)
+}
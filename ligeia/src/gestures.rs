@@ -0,0 +1,103 @@
+//! Turning touchpad scrolling and touchscreen gestures into pans and zooms of the time axis.
+
+use std::collections::HashMap;
+
+use crate::viewport::Viewport;
+
+/// Scrolling this many pixels with Ctrl held zooms by a factor of two.
+const PIXELS_PER_DOUBLING: f64 = 200.0;
+/// Fingers closer together than this, in pixels, are too close to pinch with.
+const MIN_PINCH_DISTANCE: f64 = 10.0;
+
+/// A change to the time axis, in terms of what's on screen.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Gesture {
+    /// Where the gesture is, in pixels from the top left of the canvas. Zooming keeps the time
+    /// under this point where it is.
+    pub position: (f64, f64),
+    /// How far to move the view to the right, in pixels.
+    pub pan: f64,
+    /// How much to scale the visible span by; less than one zooms in.
+    pub zoom: f64,
+}
+
+impl Gesture {
+    /// A gesture for scrolling by `delta` pixels at `position`, which zooms instead of panning
+    /// if `zoom` is set, as it is when Ctrl is held.
+    ///
+    /// Scrolling up zooms in, and scrolling sideways pans. Without `zoom`, scrolling up and down
+    /// does nothing.
+    pub fn scroll(position: (f64, f64), (dx, dy): (f64, f64), zoom: bool) -> Self {
+        match zoom {
+            true => Self {
+                position,
+                pan: 0.0,
+                zoom: 2f64.powf(-dy / PIXELS_PER_DOUBLING),
+            },
+            // The content follows the fingers, so the view moves the other way.
+            false => Self {
+                position,
+                pan: -dx,
+                zoom: 1.0,
+            },
+        }
+    }
+
+    pub fn apply(&self, viewport: &mut Viewport) {
+        viewport.pan_by(self.pan);
+        let time = viewport.time_at(self.position.0);
+        viewport.zoom_around(time, self.zoom);
+    }
+}
+
+/// The fingers on a touchscreen. One finger drags the time axis around, and two pinch to zoom.
+#[derive(Debug, Default)]
+pub struct Touches {
+    positions: HashMap<u64, (f64, f64)>,
+}
+
+impl Touches {
+    pub fn start(&mut self, id: u64, position: (f64, f64)) {
+        self.positions.insert(id, position);
+    }
+
+    pub fn end(&mut self, id: u64) {
+        self.positions.remove(&id);
+    }
+
+    /// Move a finger, returning the gesture that makes, if any.
+    pub fn moved(&mut self, id: u64, position: (f64, f64)) -> Option<Gesture> {
+        let before = self.centroid_and_spread()?;
+        *self.positions.get_mut(&id)? = position;
+        let after = self.centroid_and_spread()?;
+
+        let zoom = match (before.1, after.1) {
+            (Some(before), Some(after)) => before / after,
+            _ => 1.0,
+        };
+        Some(Gesture {
+            position: after.0,
+            pan: before.0 .0 - after.0 .0,
+            zoom,
+        })
+    }
+
+    /// The point between the fingers, and how far apart they are if there are two of them, far
+    /// enough apart to pinch with.
+    ///
+    /// Returns `None` with no fingers, or with more than two.
+    fn centroid_and_spread(&self) -> Option<((f64, f64), Option<f64>)> {
+        let mut positions = self.positions.values();
+        match (positions.next(), positions.next(), positions.next()) {
+            (Some(&only), None, None) => Some((only, None)),
+            (Some(&(ax, ay)), Some(&(bx, by)), None) => {
+                let spread = (ax - bx).hypot(ay - by);
+                Some((
+                    ((ax + bx) / 2.0, (ay + by) / 2.0),
+                    Some(spread).filter(|&spread| spread >= MIN_PINCH_DISTANCE),
+                ))
+            }
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,256 @@
+//! Draws the tints of highlighting rules and other shading under the traces, with
+//! `shaders/highlights.wgsl`.
+
+use std::mem;
+
+use wgpu::util::DeviceExt;
+
+use crate::{
+    panes::Panes,
+    render_graph::Pass,
+    scene::{PaneScene, Scene, Tint},
+    uploads::Uploader,
+};
+
+#[derive(Copy, Clone, bytemuck::NoUninit)]
+#[repr(C)]
+struct Uniforms {
+    scale: [f32; 2],
+}
+
+/// A tint, as the vertex shader takes it.
+#[derive(Copy, Clone, bytemuck::NoUninit)]
+#[repr(C)]
+struct Instance {
+    span: [f32; 2],
+    extent: [f32; 2],
+    color: [f32; 4],
+}
+
+fn create_render_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("highlights"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &[
+                wgpu::VertexBufferLayout {
+                    array_stride: mem::size_of::<[f32; 2]>() as _,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x2],
+                },
+                wgpu::VertexBufferLayout {
+                    array_stride: mem::size_of::<Instance>() as _,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &wgpu::vertex_attr_array![
+                        1 => Float32x2,
+                        2 => Float32x2,
+                        3 => Float32x4,
+                    ],
+                },
+            ],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: "fs_main",
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::all(),
+            })],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            ..Default::default()
+        },
+        multiview: None,
+    })
+}
+
+/// The buffers for drawing one pane's tints.
+struct PaneResources {
+    uniform_buffer: wgpu::Buffer,
+    instances_buffer: wgpu::Buffer,
+    /// How many tints fit in `instances_buffer`.
+    capacity: usize,
+    bind_group: wgpu::BindGroup,
+    /// How many tints were uploaded for this frame.
+    len: usize,
+}
+
+pub struct HighlightsPass {
+    shader: wgpu::ShaderModule,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    /// Built by `configure`, since it depends on the sample count.
+    pipeline: Option<wgpu::RenderPipeline>,
+    vertices_buffer: wgpu::Buffer,
+    panes: Vec<PaneResources>,
+    /// Reused from frame to frame to lay out each pane's tints.
+    instances: Vec<Instance>,
+}
+
+impl HighlightsPass {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/highlights.wgsl"));
+
+        let vertices: &[[f32; 2]] = &[
+            [0.0, 0.0],
+            [1.0, 0.0],
+            [1.0, 1.0],
+            [0.0, 0.0],
+            [1.0, 1.0],
+            [0.0, 1.0],
+        ];
+        let vertices_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::cast_slice(vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let mut pass = Self {
+            shader,
+            bind_group_layout,
+            pipeline_layout,
+            pipeline: None,
+            vertices_buffer,
+            panes: vec![],
+            instances: vec![],
+        };
+        pass.panes = (0..Panes::MAX)
+            .map(|_| pass.create_pane_resources(device, 0))
+            .collect();
+        pass
+    }
+
+    /// Buffers for a pane with room for at least `tints` tints.
+    fn create_pane_resources(&self, device: &wgpu::Device, tints: usize) -> PaneResources {
+        let capacity = tints.next_power_of_two().max(16);
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: mem::size_of::<Uniforms>() as _,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let instances_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: (capacity * mem::size_of::<Instance>()) as _,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+
+        PaneResources {
+            uniform_buffer,
+            instances_buffer,
+            capacity,
+            bind_group,
+            len: 0,
+        }
+    }
+}
+
+impl Pass for HighlightsPass {
+    fn configure(&mut self, device: &wgpu::Device, format: wgpu::TextureFormat, sample_count: u32) {
+        self.pipeline = Some(create_render_pipeline(
+            device,
+            &self.pipeline_layout,
+            &self.shader,
+            format,
+            sample_count,
+        ));
+    }
+
+    fn prepare(&mut self, uploader: &mut Uploader, scene: &Scene) {
+        for i in 0..scene.panes.len() {
+            let PaneScene { height, tints, .. } = &scene.panes[i];
+            if tints.len() > self.panes[i].capacity {
+                self.panes[i] = self.create_pane_resources(uploader.device(), tints.len());
+            }
+
+            self.instances.clear();
+            self.instances.extend(tints.iter().map(
+                |&Tint {
+                     span,
+                     extent,
+                     color,
+                 }| Instance {
+                    span,
+                    extent,
+                    color,
+                },
+            ));
+            let resources = &mut self.panes[i];
+            if !self.instances.is_empty() {
+                uploader.write(
+                    &resources.instances_buffer,
+                    0,
+                    bytemuck::cast_slice(&self.instances),
+                );
+            }
+            uploader.write(
+                &resources.uniform_buffer,
+                0,
+                bytemuck::bytes_of(&Uniforms {
+                    scale: [2.0 / scene.width as f32, 2.0 / height],
+                }),
+            );
+            resources.len = tints.len();
+        }
+    }
+
+    fn draw<'a>(&'a self, rpass: &mut wgpu::RenderPass<'a>, scene: &Scene) {
+        let pipeline = match &self.pipeline {
+            Some(pipeline) => pipeline,
+            None => return,
+        };
+
+        rpass.set_pipeline(pipeline);
+        rpass.set_vertex_buffer(0, self.vertices_buffer.slice(..));
+        for (pane, resources) in scene.panes.iter().zip(&self.panes) {
+            if resources.len == 0 {
+                continue;
+            }
+            rpass.set_viewport(0.0, pane.top, scene.width as f32, pane.height, 0.0, 1.0);
+            rpass.set_bind_group(0, &resources.bind_group, &[]);
+            rpass.set_vertex_buffer(1, resources.instances_buffer.slice(..));
+            rpass.draw(0..6, 0..resources.len as u32);
+        }
+    }
+}
@@ -0,0 +1,138 @@
+//! Where each signal's track goes, top to bottom within a pane.
+//!
+//! Tracks can be pinned, like a clock and reset, to a region at the top of the pane that stays
+//! put while the rest scroll beneath it. Each region has a scroll offset of its own; the pinned
+//! one only scrolls once it has more tracks than fit in the room it's given.
+
+use crate::traces::Traces;
+
+/// How tall each track is, in pixels.
+pub const TRACK_HEIGHT: f64 = 40.0;
+/// The most of a pane that the pinned region takes up.
+const MAX_PINNED_FRACTION: f64 = 0.5;
+
+/// A track that can be seen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Row<'a> {
+    pub signal: &'a str,
+    /// Offset from the top of the pane, in pixels. Tracks partly scrolled out of their region are
+    /// cut down to the part inside it.
+    pub top: f64,
+    pub height: f64,
+    /// Offset of the whole track from the top of the pane, which is above `top` while it's
+    /// scrolled partly out of the top of its region.
+    pub track_top: f64,
+    pub pinned: bool,
+}
+
+/// Every track in the scrolling region of a pane, whether it can be seen or not.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Overview<'a> {
+    pub signals: Vec<&'a str>,
+    /// Offset of the region from the top of the pane, and its height, in pixels.
+    pub top: f64,
+    pub height: f64,
+    /// How far the region is scrolled down, in pixels.
+    pub scroll: f64,
+}
+
+/// A stack of tracks that scrolls together.
+struct Region<'a> {
+    signals: Vec<&'a str>,
+    top: f64,
+    height: f64,
+}
+
+impl Region<'_> {
+    /// The furthest the region can be scrolled down.
+    fn max_scroll(&self) -> f64 {
+        (self.signals.len() as f64 * TRACK_HEIGHT - self.height).max(0.0)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct TrackLayout {
+    /// How far each region is scrolled down, in pixels.
+    pinned_scroll: f64,
+    scroll: f64,
+}
+
+impl TrackLayout {
+    /// The pinned region and the scrolling region below it, in a pane `height` tall.
+    fn regions<'a>(traces: &'a Traces, height: f64) -> (Region<'a>, Region<'a>) {
+        let (pinned, rest): (Vec<_>, Vec<_>) = traces
+            .signals()
+            .partition(|signal| traces.is_pinned(signal));
+        let pinned_height = (pinned.len() as f64 * TRACK_HEIGHT).min(height * MAX_PINNED_FRACTION);
+        (
+            Region {
+                signals: pinned,
+                top: 0.0,
+                height: pinned_height,
+            },
+            Region {
+                signals: rest,
+                top: pinned_height,
+                height: height - pinned_height,
+            },
+        )
+    }
+
+    /// Scroll the region under `y`, in a pane `height` tall, down by `delta` pixels, or up if
+    /// it's negative, as far as it goes.
+    pub fn scroll(&mut self, traces: &Traces, y: f64, height: f64, delta: f64) {
+        let (pinned, rest) = Self::regions(traces, height);
+        let (scroll, region) = match y < rest.top {
+            true => (&mut self.pinned_scroll, pinned),
+            false => (&mut self.scroll, rest),
+        };
+        *scroll = (scroll.min(region.max_scroll()) + delta).clamp(0.0, region.max_scroll());
+    }
+
+    /// Scroll the scrolling region of a pane `height` tall so that the point `tracks` tracks
+    /// down it is in the middle, as near as it goes.
+    pub fn center_on(&mut self, traces: &Traces, height: f64, tracks: f64) {
+        let (_, rest) = Self::regions(traces, height);
+        self.scroll = (tracks * TRACK_HEIGHT - rest.height / 2.0).clamp(0.0, rest.max_scroll());
+    }
+
+    /// The scrolling region of a pane `height` tall.
+    pub fn overview<'a>(&self, traces: &'a Traces, height: f64) -> Overview<'a> {
+        let (_, rest) = Self::regions(traces, height);
+        Overview {
+            scroll: self.scroll.min(rest.max_scroll()),
+            signals: rest.signals,
+            top: rest.top,
+            height: rest.height,
+        }
+    }
+
+    /// The tracks that can be seen in a pane `height` tall, pinned ones first.
+    pub fn rows<'a>(&self, traces: &'a Traces, height: f64) -> Vec<Row<'a>> {
+        let (pinned, rest) = Self::regions(traces, height);
+        let mut rows = vec![];
+        for (region, scroll, is_pinned) in [
+            (pinned, self.pinned_scroll, true),
+            (rest, self.scroll, false),
+        ] {
+            // Tracks can go away while scrolled past, leaving the offset further than it goes.
+            let scroll = scroll.min(region.max_scroll());
+            let bottom = region.top + region.height;
+            for (i, signal) in region.signals.into_iter().enumerate() {
+                let track_top = region.top + i as f64 * TRACK_HEIGHT - scroll;
+                let top = track_top.max(region.top);
+                let end = (track_top + TRACK_HEIGHT).min(bottom);
+                if end > top {
+                    rows.push(Row {
+                        signal,
+                        top,
+                        height: end - top,
+                        track_top,
+                        pinned: is_pinned,
+                    });
+                }
+            }
+        }
+        rows
+    }
+}
@@ -0,0 +1,303 @@
+//! Draws each pane's trace as a thick line, with `shaders/lines.wgsl`, and draws it with the
+//! pane's [trace id](picking::trace_id) for picking.
+
+use std::{borrow::Cow, mem};
+
+use wgpu::util::DeviceExt;
+
+use crate::{
+    panes::Panes,
+    picking,
+    render_graph::Pass,
+    scene::{PaneScene, Scene},
+    uploads::Uploader,
+    LINE_WIDTH,
+};
+
+#[derive(Copy, Clone, bytemuck::NoUninit)]
+#[repr(C)]
+struct Uniforms {
+    scale: [f32; 2],
+    feather_fraction: f32,
+    line_width: f32,
+    pick_id: u32,
+    /// `color` is aligned to 16 bytes.
+    _padding: [u32; 3],
+    color: [f32; 4],
+}
+
+fn create_render_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: None,
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &[wgpu::VertexBufferLayout {
+                array_stride: mem::size_of::<[f32; 2]>() as _,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: &wgpu::vertex_attr_array![0 => Float32x2],
+            }],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: "fs_main",
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::all(),
+            })],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            ..Default::default()
+        },
+        multiview: None,
+    })
+}
+
+/// Draws the same lines as the render pipeline, but with their ids, and without blending or
+/// multisampling them.
+fn create_pick_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("lines picking"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &[wgpu::VertexBufferLayout {
+                array_stride: mem::size_of::<[f32; 2]>() as _,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: &wgpu::vertex_attr_array![0 => Float32x2],
+            }],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: "fs_pick",
+            targets: &[Some(wgpu::ColorTargetState {
+                format: picking::FORMAT,
+                blend: None,
+                write_mask: wgpu::ColorWrites::all(),
+            })],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    })
+}
+
+/// The buffers for drawing one pane's trace.
+struct PaneResources {
+    uniform_buffer: wgpu::Buffer,
+    points_buffer: wgpu::Buffer,
+    /// How many points fit in `points_buffer`.
+    capacity: usize,
+    bind_group: wgpu::BindGroup,
+    /// How many points were uploaded for this frame.
+    len: usize,
+}
+
+pub struct LinesPass {
+    shader: wgpu::ShaderModule,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    /// Built by `configure`, since it depends on the sample count.
+    pipeline: Option<wgpu::RenderPipeline>,
+    pick_pipeline: wgpu::RenderPipeline,
+    vertices_buffer: wgpu::Buffer,
+    /// Each pane can look at a different time range, so each needs its own copy of the points.
+    panes: Vec<PaneResources>,
+}
+
+impl LinesPass {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("lines.wgsl"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(concat!(
+                include_str!("shaders/common.wgsl"),
+                include_str!("shaders/lines.wgsl"),
+            ))),
+        });
+
+        let vertices: &[[f32; 2]] = &[
+            [0.0, -0.5],
+            [1.0, -0.5],
+            [1.0, 0.5],
+            [0.0, -0.5],
+            [1.0, 0.5],
+            [0.0, 0.5],
+        ];
+        let vertices_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::cast_slice(vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        // Spelled out rather than derived from the shader, so that pipelines rebuilt for another
+        // sample count can share the bind groups.
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pick_pipeline = create_pick_pipeline(device, &pipeline_layout, &shader);
+
+        let mut pass = Self {
+            shader,
+            bind_group_layout,
+            pipeline_layout,
+            pipeline: None,
+            pick_pipeline,
+            vertices_buffer,
+            panes: vec![],
+        };
+        pass.panes = (0..Panes::MAX)
+            .map(|_| pass.create_pane_resources(device, 0))
+            .collect();
+        pass
+    }
+
+    /// Buffers for a pane with room for at least `points` points.
+    fn create_pane_resources(&self, device: &wgpu::Device, points: usize) -> PaneResources {
+        // Storage buffers can't be empty, and growing a little at a time would mean a new buffer
+        // every frame while a trace is growing.
+        let capacity = points.next_power_of_two().max(64);
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: mem::size_of::<Uniforms>() as _,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let points_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: (capacity * mem::size_of::<[f32; 2]>()) as _,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: points_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        PaneResources {
+            uniform_buffer,
+            points_buffer,
+            capacity,
+            bind_group,
+            len: 0,
+        }
+    }
+}
+
+impl Pass for LinesPass {
+    fn configure(&mut self, device: &wgpu::Device, format: wgpu::TextureFormat, sample_count: u32) {
+        self.pipeline = Some(create_render_pipeline(
+            device,
+            &self.pipeline_layout,
+            &self.shader,
+            format,
+            sample_count,
+        ));
+    }
+
+    fn prepare(&mut self, uploader: &mut Uploader, scene: &Scene) {
+        for i in 0..scene.panes.len() {
+            let PaneScene { height, points, .. } = &scene.panes[i];
+            if points.len() > self.panes[i].capacity {
+                self.panes[i] = self.create_pane_resources(uploader.device(), points.len());
+            }
+
+            let resources = &mut self.panes[i];
+            uploader.write(&resources.points_buffer, 0, bytemuck::cast_slice(points));
+            uploader.write(
+                &resources.uniform_buffer,
+                0,
+                bytemuck::bytes_of(&Uniforms {
+                    scale: [2.0 / scene.width as f32, 2.0 / height],
+                    feather_fraction: scene.feather_fraction,
+                    line_width: LINE_WIDTH,
+                    pick_id: picking::trace_id(i),
+                    _padding: [0; 3],
+                    color: scene.colors.line,
+                }),
+            );
+            resources.len = points.len();
+        }
+    }
+
+    fn draw<'a>(&'a self, rpass: &mut wgpu::RenderPass<'a>, scene: &Scene) {
+        let pipeline = match &self.pipeline {
+            Some(pipeline) => pipeline,
+            None => return,
+        };
+
+        rpass.set_pipeline(pipeline);
+        rpass.set_vertex_buffer(0, self.vertices_buffer.slice(..));
+        for (pane, resources) in scene.panes.iter().zip(&self.panes) {
+            rpass.set_viewport(0.0, pane.top, scene.width as f32, pane.height, 0.0, 1.0);
+            rpass.set_bind_group(0, &resources.bind_group, &[]);
+            // One instance for each segment between consecutive points.
+            rpass.draw(0..6, 0..resources.len.saturating_sub(1) as u32);
+        }
+    }
+
+    fn pick<'a>(&'a self, rpass: &mut wgpu::RenderPass<'a>, scene: &Scene) {
+        rpass.set_pipeline(&self.pick_pipeline);
+        rpass.set_vertex_buffer(0, self.vertices_buffer.slice(..));
+        for (pane, resources) in scene.panes.iter().zip(&self.panes) {
+            rpass.set_viewport(0.0, pane.top, scene.width as f32, pane.height, 0.0, 1.0);
+            rpass.set_bind_group(0, &resources.bind_group, &[]);
+            rpass.draw(0..6, 0..resources.len.saturating_sub(1) as u32);
+        }
+    }
+}
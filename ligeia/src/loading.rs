@@ -0,0 +1,40 @@
+//! Opening waveform files on a background thread, so the window keeps responding while they
+//! load.
+
+use std::{path::PathBuf, thread};
+
+use ligeia_core::{IngestorOptions, Processed};
+use ligeia_formats::LoaderRegistry;
+use winit::event_loop::EventLoopProxy;
+
+use crate::commands::UserEvent;
+
+/// A file that's finished loading, one way or another.
+pub struct Loaded {
+    pub path: PathBuf,
+    pub result: Result<Processed, String>,
+}
+
+/// Every format the viewer can open.
+pub fn loaders() -> LoaderRegistry {
+    let mut loaders = LoaderRegistry::new();
+    ligeia_vcd::register(&mut loaders);
+    ligeia_svcb::register(&mut loaders);
+    #[cfg(feature = "fsdb")]
+    ligeia_fsdb::register(&mut loaders);
+    loaders
+}
+
+/// Start loading `path` with `options`, and send it to the event loop once it's done.
+///
+/// Each load gets its own thread and its own loaders, so nothing is shared with the event loop
+/// until the finished waveform is handed over.
+pub fn spawn(path: PathBuf, options: IngestorOptions, proxy: EventLoopProxy<UserEvent>) {
+    thread::spawn(move || {
+        let mut loaders = loaders();
+        loaders.set_options(options);
+        let result = loaders.load_file(&path, None).map_err(|e| e.to_string());
+        // If the window has closed in the meantime, there's nobody left to tell.
+        let _ = proxy.send_event(UserEvent::Loaded(Box::new(Loaded { path, result })));
+    });
+}
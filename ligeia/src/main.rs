@@ -1,256 +1,1939 @@
-use std::mem;
+use std::{env, ffi::OsString, fs::File, io::BufWriter, mem, path::PathBuf, process, sync::Arc};
 
-use wgpu::{util::DeviceExt, Instance};
+use ligeia_core::{
+    clocks,
+    condition::Condition,
+    format::{self, Separators},
+    meta::{EnumValue, StorageId, StorageType, Timesteps, Var, VarKind},
+    resample,
+    search::Glob,
+    slice::BitSlice,
+    states, Error, IngestorOptions, Processed,
+};
+use wgpu::Instance;
 use winit::{
-    event::{Event, WindowEvent},
-    event_loop::{ControlFlow, EventLoop},
-    window::Window,
+    event::{
+        ElementState, Event, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta, Touch,
+        TouchPhase, WindowEvent,
+    },
+    event_loop::{ControlFlow, EventLoop, EventLoopProxy},
+    window::{CursorIcon, Window},
+};
+
+use crate::{
+    annotations::Annotations,
+    antialiasing::{Antialiasing, AntialiasingSetting},
+    bus::BusPass,
+    commands::{Command, RemoteCommand, UserEvent},
+    gestures::{Gesture, Touches},
+    highlights::HighlightsPass,
+    layout::{Overview, Row, TrackLayout, TRACK_HEIGHT},
+    lines::LinesPass,
+    loading::Loaded,
+    minimap::{self, Minimap},
+    one_bit::OneBitPass,
+    palette::{Theme, STATE_COLORS},
+    panes::{Pane, Panes},
+    pdf::PdfPage,
+    picking::Picks,
+    render_graph::RenderGraph,
+    residency::{Residency, TileKey, TilePool, TILE_BUCKETS},
+    scene::{Label, Scene, SceneExchange, TileDraw, TileUpload, Tint},
+    text::TextPass,
+    traces::Traces,
+    triggers::{Trigger, Triggers},
+    values::Source,
+    viewport::Viewport,
+    watch::Watch,
 };
 
-#[derive(Copy, Clone, bytemuck::NoUninit)]
-#[repr(C)]
-struct Uniforms {
-    scale: [f32; 2],
-    feather_fraction: f32,
-    line_width: f32,
-}
-
-fn create_msaa_frambuffer(
-    device: &wgpu::Device,
-    config: &wgpu::SurfaceConfiguration,
-    sample_count: u32,
-) -> wgpu::TextureView {
-    let multisampled_texture_extent = wgpu::Extent3d {
-        width: config.width,
-        height: config.height,
-        depth_or_array_layers: 1,
+mod annotations;
+mod antialiasing;
+mod bus;
+mod clipboard;
+mod commands;
+mod diff;
+mod gestures;
+mod highlights;
+mod layout;
+mod lines;
+mod loading;
+mod minimap;
+mod one_bit;
+mod outline;
+mod palette;
+mod panes;
+mod pdf;
+mod picking;
+mod render;
+mod render_graph;
+mod residency;
+mod rpc;
+mod scene;
+#[cfg(feature = "script")]
+mod script;
+mod text;
+mod tile_draws;
+mod traces;
+mod triggers;
+mod uploads;
+mod values;
+mod viewport;
+mod watch;
+
+/// How far the values written on buses keep from the transitions at either end, in pixels.
+const LABEL_MARGIN: f64 = 8.0;
+/// How far tooltips are from the cursor, across and down, in pixels.
+const TOOLTIP_OFFSET: f64 = 16.0;
+/// How opaque the backgrounds of tooltips are.
+const TOOLTIP_ALPHA: f32 = 0.9;
+/// How opaque the shading of the minimap is, and of the mark over the tracks that can be seen.
+const MINIMAP_ALPHA: f32 = 0.08;
+const MINIMAP_WINDOW_ALPHA: f32 = 0.25;
+
+/// Drags shorter than this, in pixels, are treated as clicks rather than selections.
+const MIN_DRAG_DISTANCE: f64 = 4.0;
+/// How many pixels a line of mouse wheel scrolling counts as.
+const PIXELS_PER_LINE: f64 = 40.0;
+/// How opaque the tints of highlighting rules are.
+const TINT_ALPHA: f32 = 0.3;
+/// How opaque the lines between clock cycles are while sampling on a clock.
+const CYCLE_DIVIDER_ALPHA: f32 = 0.5;
+/// Clock cycles narrower than this, in pixels, are too crowded to mark.
+const MIN_CYCLE_WIDTH: f64 = 4.0;
+/// How opaque the segments of state timelines are.
+const STATE_ALPHA: f32 = 0.8;
+/// Added to the path of an enum variable to name the track that shows which state it's in.
+const STATES_SUFFIX: &str = " (states)";
+/// The size of annotation text, and of the flags that mark them, in points.
+const FLAG_SIZE: f64 = 10.0;
+
+/// Whether a storage holds logic values, which are drawn as levels, rather than strings or events.
+fn is_logic(ty: StorageType) -> bool {
+    matches!(
+        ty,
+        StorageType::TwoLogic | StorageType::FourLogic | StorageType::NineLogic
+    )
+}
+
+/// The storage that a signal's track is drawn from, with its type and width, or `None` if the
+/// track isn't drawn from tiles.
+///
+/// Only whole variables of logic values or strings are, so far. Slices of variables aren't, and
+/// nor are variables split between storages or events. Enums' states are tints.
+fn tile_storage(processed: &Processed, signal: &str) -> Option<(StorageId, StorageType, u32)> {
+    let (storage, bits) = match signal_source(processed, signal) {
+        Ok(Source::Bits { storage, bits }) => (storage, bits),
+        _ => return None,
     };
-    let multisampled_frame_descriptor = &wgpu::TextureDescriptor {
-        size: multisampled_texture_extent,
-        mip_level_count: 1,
-        sample_count,
-        dimension: wgpu::TextureDimension::D2,
-        format: config.format,
-        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-        label: None,
+    let info = processed.storage(storage).ok()?;
+    if bits != (0..info.width) || !(is_logic(info.ty) || info.ty == StorageType::Utf8) {
+        return None;
+    }
+    Some((storage, info.ty, info.width))
+}
+
+/// Whether a track drawn from tiles is drawn as levels, rather than as a bus of values.
+fn is_one_bit(ty: StorageType, width: u32) -> bool {
+    is_logic(ty) && width == 1
+}
+
+/// A track to draw from tiles, and where it goes in its pane.
+struct TileTrack {
+    storage: StorageId,
+    ty: StorageType,
+    /// The time axis it's drawn along, which starts `left` pixels from the left of the pane.
+    viewport: Viewport,
+    left: f64,
+    /// See [`TileDraw`].
+    extent: [f64; 2],
+    clip: [f64; 4],
+}
+
+/// Where a tile goes along its track, worked out from the bucket at the left of the axis, or as
+/// near to it as the tile goes, so that positions stay precise however far in it's zoomed.
+#[derive(Debug, Clone, Copy)]
+struct TilePosition {
+    /// That bucket of the tile, and where it starts in pixels from the left of the pane.
+    origin: [f64; 2],
+    bucket_width: f64,
+    /// Where the last change stops being drawn, at the end of the tile or of the waveform.
+    end: f64,
+}
+
+/// Call `f` with each of the tiles covering the time `track` shows, and where it goes.
+fn tiles_along<F>(processed: &mut Processed, track: &TileTrack, mut f: F) -> Result<(), Error>
+where
+    F: FnMut(&mut Processed, TileKey, TilePosition) -> Result<(), Error>,
+{
+    let TileTrack { viewport, left, .. } = *track;
+    let last = processed.time_bounds().1 .0 as f64;
+    let lod = residency::lod_for(viewport.span() / viewport.width);
+    let bucket = (1u64 << lod) as f64;
+    let range = Timesteps(viewport.start.max(0.0) as u64)..Timesteps(viewport.end.ceil() as u64);
+
+    for key in residency::tiles_covering(track.storage, lod, range) {
+        let tile = key.range();
+        let start = tile.start.0 as f64;
+        let origin = ((viewport.start - start) / bucket)
+            .floor()
+            .clamp(0.0, TILE_BUCKETS as f64);
+        let position = TilePosition {
+            origin: [origin, left + viewport.x_at(start + origin * bucket)],
+            bucket_width: bucket / viewport.span() * viewport.width,
+            end: left + viewport.x_at((tile.end.0 as f64).min(last)),
+        };
+        f(processed, key, position)?;
+    }
+    Ok(())
+}
+
+/// Draw `track` from the tiles covering the time it shows, adding uploads for any that aren't in
+/// the tile pool yet.
+fn draw_tiles(
+    residency: &mut Residency,
+    processed: &mut Processed,
+    uploads: &mut Vec<TileUpload>,
+    track: &TileTrack,
+    draws: &mut Vec<TileDraw>,
+) -> Result<(), Error> {
+    tiles_along(processed, track, |processed, key, position| {
+        let placement = match residency.require(processed, key, uploads)? {
+            Some(placement) if placement.changes > 0 => placement,
+            // The pool is full, or the signal hadn't changed yet.
+            _ => return Ok(()),
+        };
+        let len = placement.range.end - placement.range.start;
+        draws.push(TileDraw {
+            offset: placement.range.start,
+            changes: placement.changes,
+            stride: (len / placement.changes as u64) as u32,
+            ty: track.ty,
+            origin: position.origin.map(|x| x as f32),
+            bucket_width: position.bucket_width as f32,
+            end: position.end as f32,
+            extent: track.extent.map(|y| y as f32),
+            clip: track.clip.map(|edge| edge as f32),
+        });
+        Ok(())
+    })
+}
+
+/// Make sure the tiles of every signal that the panes draw are in the tile pool, adding uploads
+/// to `scene` for any that aren't, and draw them on their tracks and in the minimap. Tracks
+/// scrolled out of sight don't need theirs, except for the minimap's far coarser ones.
+///
+/// See [`tile_storage`] for which signals are drawn this way.
+fn require_tiles(residency: &mut Residency, state: &mut ViewState, scene: &mut Scene) {
+    let processed = match &mut state.waveform {
+        Some((_, processed)) => processed,
+        None => return,
     };
+    let (first, last) = processed.time_bounds();
 
-    device
-        .create_texture(multisampled_frame_descriptor)
-        .create_view(&wgpu::TextureViewDescriptor::default())
+    // The minimap shows the whole waveform, along the strip to the right of the time axis.
+    let whole = Viewport::new(first.0 as f64, last.0 as f64, minimap::WIDTH);
+
+    residency.begin_frame();
+    for (pane, pane_scene) in state.panes.iter().zip(&mut scene.panes) {
+        let viewport = pane.viewport;
+        let rows = state.tracks.rows(&state.traces, pane.height);
+        let tracks = rows.iter().map(|row| {
+            let track = [row.track_top, row.track_top + TRACK_HEIGHT];
+            let clip = [0.0, row.top, viewport.width, row.top + row.height];
+            (row.signal, viewport, 0.0, track, clip)
+        });
+        let minimap = Minimap::new(&state.tracks, &state.traces, pane.height);
+        let thumbnails = minimap
+            .overview
+            .signals
+            .iter()
+            .enumerate()
+            .map(|(i, &signal)| {
+                let thumbnail = minimap.thumbnail(i);
+                let left = viewport.width;
+                let clip = [left, thumbnail[0], left + minimap::WIDTH, thumbnail[1]];
+                (signal, whole, left, thumbnail, clip)
+            });
+
+        for (signal, viewport, left, extent, clip) in tracks.chain(thumbnails) {
+            // Missing signals are reported when the file is opened.
+            let (storage, ty, width) = match tile_storage(processed, signal) {
+                Some(storage) => storage,
+                None => continue,
+            };
+            let draws = match is_one_bit(ty, width) {
+                true => &mut pane_scene.bits,
+                false => &mut pane_scene.buses,
+            };
+            let track = TileTrack {
+                storage,
+                ty,
+                viewport,
+                left,
+                extent,
+                clip,
+            };
+            if let Err(e) = draw_tiles(residency, processed, &mut scene.uploads, &track, draws) {
+                eprintln!("failed to load signal data: {}", e);
+                return;
+            }
+        }
+    }
 }
 
-async fn run(event_loop: EventLoop<()>, window: Window) {
-    let size = window.inner_size();
-    let instance = Instance::new(wgpu::Backends::all());
-    let surface = unsafe { instance.create_surface(&window) };
+/// The values of buses and strings to write on `rows`, over the time `viewport` shows, centered
+/// in each stretch where the value holds that's wide enough to fit it. Where values change too
+/// often to be read, none are written.
+fn value_labels(
+    processed: &mut Processed,
+    viewport: &Viewport,
+    rows: &[Row],
+    color: [f32; 4],
+) -> Result<Vec<Label>, Error> {
+    let last = processed.time_bounds().1 .0 as f64;
+    // The least room a value could be written in.
+    let min_width = text::GLYPH_WIDTH as f64 + LABEL_MARGIN * 2.0;
+    let mut labels = vec![];
 
-    let adapter = instance
-        .request_adapter(&wgpu::RequestAdapterOptions {
-            power_preference: wgpu::PowerPreference::default(),
-            force_fallback_adapter: false,
-            // Request an adapter which can render to our surface
-            compatible_surface: Some(&surface),
-        })
-        .await
-        .expect("Failed to find an appropriate adapter");
-
-    // Create the logical device and command queue
-    let (device, queue) = adapter
-        .request_device(
-            &wgpu::DeviceDescriptor {
-                label: None,
-                features: wgpu::Features::empty(),
-                // Make sure we use the texture resolution limits from the adapter, so we can support images the size of the swapchain.
-                limits: wgpu::Limits::default().using_resolution(adapter.limits()),
-            },
-            None,
-        )
-        .await
-        .expect("Failed to create device");
+    for row in rows {
+        match tile_storage(processed, row.signal) {
+            Some((_, ty, width)) if !is_one_bit(ty, width) => {}
+            _ => continue,
+        }
+        // Text cut off by the edge of the track's region can't be read either.
+        let y = row.track_top + (TRACK_HEIGHT - text::GLYPH_HEIGHT as f64) / 2.0;
+        if y < row.top || y + text::GLYPH_HEIGHT as f64 > row.top + row.height {
+            continue;
+        }
+        let source = match signal_source(processed, row.signal) {
+            Ok(source) => source,
+            Err(_) => continue,
+        };
 
-    let swapchain_format = surface.get_supported_formats(&adapter)[0];
+        // Each value found covers a stretch of the pane, and the next one is looked for past it,
+        // or far enough along to be written if it's too narrow. That way there are only ever as
+        // many to look up as could fit.
+        let mut time = viewport.start.max(0.0) as u64;
+        while (time as f64) < viewport.end {
+            let skip = viewport
+                .time_at(viewport.x_at(time as f64) + min_width)
+                .ceil()
+                .max(time as f64 + 1.0) as u64;
+            let shown = match source.value_at(processed, Timesteps(time))? {
+                Some(shown) => shown,
+                None => {
+                    time = skip;
+                    continue;
+                }
+            };
 
-    let mut config = wgpu::SurfaceConfiguration {
-        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-        format: swapchain_format,
-        width: size.width,
-        height: size.height,
-        present_mode: wgpu::PresentMode::Fifo,
+            let end = shown.end.map_or(last, |end| end.0 as f64);
+            let left = viewport.x_at(shown.start.0 as f64).max(0.0) + LABEL_MARGIN;
+            let right = viewport.x_at(end).min(viewport.width) - LABEL_MARGIN;
+            let width = text::text_width(&shown.text) as f64;
+            if right - left >= width {
+                labels.push(Label {
+                    position: [((left + right - width) / 2.0) as f32, y as f32],
+                    text: shown.text,
+                    color,
+                    background: None,
+                });
+            }
+
+            time = match shown.end {
+                Some(end) => end.0.max(skip),
+                None => break,
+            };
+        }
+    }
+    Ok(labels)
+}
+
+/// Write the values of buses and strings on their tracks in each pane. See [`value_labels`].
+fn describe_values(state: &mut ViewState, scene: &mut Scene) {
+    let processed = match &mut state.waveform {
+        Some((_, processed)) => processed,
+        None => return,
     };
+    for (pane, pane_scene) in state.panes.iter().zip(&mut scene.panes) {
+        let rows = state.tracks.rows(&state.traces, pane.height);
+        match value_labels(processed, &pane.viewport, &rows, scene.colors.line) {
+            Ok(labels) => pane_scene.labels.extend(labels),
+            Err(e) => {
+                eprintln!("failed to load signal data: {}", e);
+                return;
+            }
+        }
+    }
+}
 
-    let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/lines.wgsl"));
+/// Show the value of the signal under `cursor` beside it, along with when the signal took that
+/// value and how long it holds it.
+fn describe_tooltip(state: &mut ViewState, scene: &mut Scene, cursor: (f64, f64)) {
+    let index = state.panes.pane_at(cursor.1);
+    let pane = match state.panes.iter().nth(index) {
+        Some(pane) => *pane,
+        None => return,
+    };
+    let signal = match state.signal_at(cursor) {
+        Some(signal) => signal.to_owned(),
+        None => return,
+    };
+    let processed = match &mut state.waveform {
+        Some((_, processed)) => processed,
+        None => return,
+    };
+    let time = pane.viewport.time_at(cursor.0);
+    if time < 0.0 {
+        return;
+    }
+    // Signals that can't be read are reported when the file is opened.
+    let source = match signal_source(processed, &signal) {
+        Ok(source) => source,
+        Err(_) => return,
+    };
+    let shown = match source.value_at(processed, Timesteps(time as u64)) {
+        Ok(Some(shown)) => shown,
+        Ok(None) => return,
+        Err(e) => {
+            eprintln!("failed to load signal data: {}", e);
+            return;
+        }
+    };
 
-    let vertices: &[[f32; 2]] = &[
-        [0.0, -0.5],
-        [1.0, -0.5],
-        [1.0, 0.5],
-        [0.0, -0.5],
-        [1.0, 0.5],
-        [0.0, 0.5],
+    let femtoseconds_per_timestep = processed.femtoseconds_per_timestep();
+    let format_time = |timesteps: u64| {
+        format::format_time(
+            timesteps as u128 * femtoseconds_per_timestep,
+            Separators::from_env(),
+        )
+    };
+    let lines = [
+        format!("{} = {}", signal, shown.text),
+        format!("since {}", format_time(shown.start.0)),
+        match shown.end {
+            Some(end) => format!("for {}", format_time(end.0 - shown.start.0)),
+            None => "until the end".to_string(),
+        },
     ];
-    let points: &[[f32; 2]] = &[[10., 100.], [300., 10.], [300., 500.]];
 
-    let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-        label: None,
-        size: mem::size_of::<Uniforms>() as _,
-        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        mapped_at_creation: false,
-    });
-    let vertices_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-        label: None,
-        contents: bytemuck::cast_slice(vertices),
-        usage: wgpu::BufferUsages::VERTEX,
-    });
-    let points_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-        label: None,
-        contents: bytemuck::cast_slice(points),
-        usage: wgpu::BufferUsages::STORAGE,
-    });
+    // Lines are padded to the same length, so their backgrounds make one box. It goes below and
+    // to the right of the cursor, unless that would run off the pane.
+    let len = lines
+        .iter()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0);
+    let width = text::text_width(&" ".repeat(len)) as f64;
+    let line_height = (text::GLYPH_HEIGHT + text::BACKGROUND_PADDING * 2.0) as f64;
+    let height = line_height * lines.len() as f64;
+    let (x, y) = (cursor.0, cursor.1 - pane.top);
+    let x = match x + TOOLTIP_OFFSET + width > pane.viewport.width {
+        true => x - TOOLTIP_OFFSET - width,
+        false => x + TOOLTIP_OFFSET,
+    };
+    let y = match y + TOOLTIP_OFFSET + height > pane.height {
+        true => y - TOOLTIP_OFFSET - height,
+        false => y + TOOLTIP_OFFSET,
+    };
 
-    let sample_count = 1;
-
-    let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-        label: None,
-        layout: None,
-        vertex: wgpu::VertexState {
-            module: &shader,
-            entry_point: "vs_main",
-            buffers: &[wgpu::VertexBufferLayout {
-                array_stride: mem::size_of::<[f32; 2]>() as _,
-                step_mode: wgpu::VertexStepMode::Vertex,
-                attributes: &wgpu::vertex_attr_array![0 => Float32x2],
-            }],
-        },
-        fragment: Some(wgpu::FragmentState {
-            module: &shader,
-            entry_point: "fs_main",
-            targets: &[Some(wgpu::ColorTargetState {
-                format: swapchain_format,
-                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-                write_mask: wgpu::ColorWrites::all(),
-            })],
-        }),
-        primitive: wgpu::PrimitiveState::default(),
-        depth_stencil: None,
-        multisample: wgpu::MultisampleState {
-            count: sample_count,
-            ..Default::default()
-        },
-        multiview: None,
-    });
+    let [r, g, b, _] = scene.colors.background;
+    scene.panes[index]
+        .labels
+        .extend(lines.into_iter().enumerate().map(|(i, line)| Label {
+            text: format!("{:1$}", line, len),
+            position: [x as f32, (y + line_height * i as f64) as f32],
+            color: scene.colors.line,
+            background: Some([r, g, b, TOOLTIP_ALPHA]),
+        }));
+}
 
-    let bind_group_layout = render_pipeline.get_bind_group_layout(0);
-    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-        label: None,
-        layout: &bind_group_layout,
-        entries: &[
-            wgpu::BindGroupEntry {
-                binding: 0,
-                resource: uniform_buffer.as_entire_binding(),
-            },
-            wgpu::BindGroupEntry {
-                binding: 1,
-                resource: points_buffer.as_entire_binding(),
-            },
-        ],
+/// Shade the minimap of each pane, and mark the tracks in it that can be seen. The thumbnails
+/// themselves are drawn from tiles, by [`require_tiles`].
+fn describe_minimap(state: &ViewState, scene: &mut Scene) {
+    let [r, g, b, _] = scene.colors.line;
+    for (pane, pane_scene) in state.panes.iter().zip(&mut scene.panes) {
+        let minimap = Minimap::new(&state.tracks, &state.traces, pane.height);
+        if minimap.overview.signals.is_empty() {
+            continue;
+        }
+        let left = pane.viewport.width as f32;
+        let span = [left, left + minimap::WIDTH as f32];
+        let Overview { top, height, .. } = minimap.overview;
+        pane_scene.tints.push(Tint {
+            span,
+            extent: [top as f32, (top + height) as f32],
+            color: [r, g, b, MINIMAP_ALPHA],
+        });
+        pane_scene.tints.push(Tint {
+            span,
+            extent: minimap.window().map(|y| y as f32),
+            color: [r, g, b, MINIMAP_WINDOW_ALPHA],
+        });
+    }
+}
+
+/// Say in the middle of the canvas how to open a waveform while there isn't one, or which one is
+/// opening.
+fn describe_hint(state: &ViewState, scene: &mut Scene) {
+    if state.waveform.is_some() {
+        return;
+    }
+    let text = match &state.loading {
+        Some(path) => format!("opening {}...", path.display()),
+        None => "drop a waveform here to open it".to_string(),
+    };
+    let color = scene.colors.line;
+    if let Some((pane, pane_scene)) = state.panes.iter().zip(&mut scene.panes).next() {
+        let x = (pane.viewport.width as f32 - text::text_width(&text)) / 2.0;
+        let y = (pane.height as f32 - text::GLYPH_HEIGHT) / 2.0;
+        pane_scene.labels.push(Label {
+            text,
+            position: [x.max(0.0), y.max(0.0)],
+            color,
+            background: None,
+        });
+    }
+}
+
+/// Everything about the view that commands can change.
+struct ViewState {
+    panes: Panes,
+    annotations: Annotations,
+    antialiasing: AntialiasingSetting,
+    theme: Theme,
+    traces: Traces,
+    tracks: TrackLayout,
+    triggers: Triggers,
+    /// The open waveform, and where it came from.
+    waveform: Option<(PathBuf, Processed)>,
+    /// A file being loaded in the background, which will replace `waveform` once it's loaded.
+    loading: Option<PathBuf>,
+    /// The file to reload whenever it changes.
+    watch: Option<Watch>,
+    /// Whether the watched file changed again while it was being reloaded.
+    reload_again: bool,
+    /// Whether the open file's warnings are listed, rather than only counted.
+    warnings_expanded: bool,
+    /// What every file is loaded with, from the command line.
+    options: IngestorOptions,
+    /// The first and last timesteps of the open waveform, which zooming to fit shows.
+    time_bounds: (f64, f64),
+    /// How the last command went, for those that say, like exporting. The title shows it until
+    /// the next command.
+    notice: Option<String>,
+}
+
+impl ViewState {
+    /// The window title, which says what's open, or how to open something like the
+    /// [canvas](describe_hint) does.
+    fn title(&self) -> String {
+        let title = match (&self.loading, &self.waveform) {
+            (Some(path), _) => format!("ligeia - opening {}...", path.display()),
+            (None, Some((path, _))) => format!("ligeia - {}", path.display()),
+            (None, None) => "ligeia - drop a waveform here to open it".to_string(),
+        };
+        let title = match self.watch {
+            Some(_) => format!("{} (watching)", title),
+            None => title,
+        };
+        let title = match &self.notice {
+            Some(notice) => format!("{} - {}", title, notice),
+            None => title,
+        };
+        match &self.waveform {
+            Some((_, processed)) if self.loading.is_none() && !processed.warnings().is_empty() => {
+                let count = processed.warnings().len();
+                format!(
+                    "{} ({} warning{})",
+                    title,
+                    count,
+                    if count == 1 { "" } else { "s" }
+                )
+            }
+            _ => title,
+        }
+    }
+
+    /// List the open file's warnings if they're expanded, or say how many there are if not.
+    fn report_warnings(&self) {
+        let (path, warnings) = match &self.waveform {
+            Some((path, processed)) if !processed.warnings().is_empty() => {
+                (path, processed.warnings())
+            }
+            _ => return,
+        };
+        if !self.warnings_expanded {
+            eprintln!(
+                "{} warning{} opening {}; shift+W lists them",
+                warnings.len(),
+                if warnings.len() == 1 { "" } else { "s" },
+                path.display()
+            );
+            return;
+        }
+        eprintln!("warnings opening {}:", path.display());
+        for warning in warnings {
+            eprintln!("  {}", warning);
+        }
+    }
+
+    /// Start loading `path` in the background. The open waveform, and the view of it, stay as
+    /// they are until it's loaded.
+    fn open(&mut self, path: PathBuf, proxy: &EventLoopProxy<UserEvent>) -> Result<(), String> {
+        if let Some(loading) = &self.loading {
+            return Err(format!("already opening {}", loading.display()));
+        }
+        loading::spawn(path.clone(), self.options.clone(), proxy.clone());
+        self.loading = Some(path);
+        Ok(())
+    }
+
+    fn processed(&self) -> Result<&Processed, String> {
+        match &self.waveform {
+            Some((_, processed)) => Ok(processed),
+            None => Err("there's no waveform open".to_string()),
+        }
+    }
+
+    /// Scroll the tracks to the one under `cursor` in the minimap, centering it, if the cursor is
+    /// on the minimap. Returns whether it is.
+    fn navigate_minimap(&mut self, cursor: (f64, f64)) -> bool {
+        let pane = match self.panes.iter().nth(self.panes.pane_at(cursor.1)) {
+            Some(pane) => *pane,
+            None => return false,
+        };
+        if cursor.0 < pane.viewport.width {
+            return false;
+        }
+        let minimap = Minimap::new(&self.tracks, &self.traces, pane.height);
+        if let Some(tracks) = minimap.tracks_at(cursor.1 - pane.top) {
+            self.tracks.center_on(&self.traces, pane.height, tracks);
+        }
+        true
+    }
+
+    fn processed_mut(&mut self) -> Result<&mut Processed, String> {
+        match &mut self.waveform {
+            Some((_, processed)) => Ok(processed),
+            None => Err("there's no waveform open".to_string()),
+        }
+    }
+
+    /// Center every pane on where the first trigger goes off in the open file, keeping their
+    /// zoom levels. Returns whether any went off.
+    fn jump_to_trigger(&mut self) -> Result<bool, String> {
+        let processed = match &mut self.waveform {
+            Some((_, processed)) => processed,
+            None => return Err("there's no waveform open".to_string()),
+        };
+        let hit = self.triggers.check(processed, |trigger, e| {
+            eprintln!("not checking trigger {}: {}", trigger.name, e)
+        });
+        let hit = match hit {
+            Some(hit) => hit,
+            None => return Ok(false),
+        };
+
+        let femtoseconds = hit.time.0 as u128 * processed.femtoseconds_per_timestep();
+        eprintln!(
+            "{} went off at {}",
+            hit.names.join(", "),
+            format::format_time(femtoseconds, Separators::from_env())
+        );
+        for index in 0..self.panes.iter().count() {
+            self.panes
+                .update_viewport(index, |viewport| viewport.center_on(hit.time.0 as f64));
+        }
+        Ok(true)
+    }
+
+    /// The signal whose track is under `cursor`, if there is one.
+    fn signal_at(&self, cursor: (f64, f64)) -> Option<&str> {
+        let pane = self.panes.iter().nth(self.panes.pane_at(cursor.1))?;
+        // Past the time axis is the minimap.
+        if cursor.0 >= pane.viewport.width {
+            return None;
+        }
+        let y = cursor.1 - pane.top;
+        self.tracks
+            .rows(&self.traces, pane.height)
+            .into_iter()
+            .find(|row| (row.top..row.top + row.height).contains(&y))
+            .map(|row| row.signal)
+    }
+
+    fn set_pinned(&mut self, signal: &str, pinned: bool) -> Result<(), String> {
+        if !self.traces.signals().any(|shown| shown == signal) {
+            return Err(format!("`{}` isn't shown", signal));
+        }
+        self.traces.set_pinned(signal, pinned);
+        Ok(())
+    }
+
+    /// Start watching the open file, or the one being opened.
+    fn start_watching(&mut self, proxy: &EventLoopProxy<UserEvent>) -> Result<(), String> {
+        let path = match (&self.loading, &self.waveform) {
+            (Some(path), _) | (None, Some((path, _))) => path.clone(),
+            (None, None) => return Err("there's no file open to watch".to_string()),
+        };
+        self.watch = Some(Watch::new(path, proxy.clone())?);
+        Ok(())
+    }
+}
+
+/// Tint each pane wherever the highlighting rules hold, and shade it while the reset is asserted,
+/// over the time it shows.
+fn describe_highlights(state: &mut ViewState, scene: &mut Scene) {
+    let processed = match &mut state.waveform {
+        Some((_, processed)) => processed,
+        None => return,
+    };
+
+    // The reset's shading goes first, so rules tint over it.
+    let reset = state
+        .traces
+        .reset()
+        .map(|reset| (reset.condition(), reset.signal.as_str(), scene.colors.reset));
+    let rules = state.traces.highlights().iter().map(|highlight| {
+        let [r, g, b, _] = scene.colors.unknown;
+        let [r, g, b] = highlight.color.unwrap_or([r, g, b]);
+        (
+            highlight.condition.as_str(),
+            highlight.signal.as_str(),
+            [r, g, b, TINT_ALPHA],
+        )
     });
+    let tints: Vec<_> = reset.into_iter().chain(rules).collect();
 
-    let mut msaa_framebuffer = create_msaa_frambuffer(&device, &config, sample_count);
+    for (pane, pane_scene) in state.panes.iter().zip(&mut scene.panes) {
+        let viewport = &pane.viewport;
+        let range =
+            Timesteps(viewport.start.max(0.0) as u64)..Timesteps(viewport.end.ceil() as u64);
+        for &(condition, signal, color) in &tints {
+            // Rules that don't make sense for the open file are reported when it's opened.
+            let condition = match Condition::parse_for(condition, processed, signal) {
+                Ok(condition) => condition,
+                Err(_) => continue,
+            };
+            let intervals = match condition.intervals_in(processed, range.clone()) {
+                Ok(intervals) => intervals,
+                Err(e) => {
+                    eprintln!("failed to load signal data: {}", e);
+                    return;
+                }
+            };
 
-    surface.configure(&device, &config);
+            pane_scene
+                .tints
+                .extend(intervals.into_iter().map(|interval| {
+                    // Tints stop at the end of the time axis, short of the minimap.
+                    let end = interval
+                        .end
+                        .map_or(viewport.end, |end| (end.0 as f64).min(viewport.end));
+                    Tint {
+                        span: [
+                            viewport.x_at(interval.start.0 as f64) as f32,
+                            viewport.x_at(end) as f32,
+                        ],
+                        extent: [0.0, pane.height as f32],
+                        color,
+                    }
+                }));
+        }
+    }
+}
 
-    event_loop.run(move |event, _, control_flow| {
-        // Have the closure take ownership of the resources.
-        // `event_loop.run` never returns, therefore we must do this to ensure
-        // the resources are properly cleaned up.
-        let _ = (&instance, &adapter, &shader);
+/// The name of the bits `offset..offset + width` of `var`, with their declared indices, like
+/// `top.bus[15:8]`.
+fn slice_name(processed: &Processed, var: &Var, offset: u32, width: u32) -> String {
+    let index = |offset| var.kind.bit_index(offset).unwrap();
+    format!(
+        "{}[{}:{}]",
+        processed.var_path(var),
+        index(offset + width - 1),
+        index(offset)
+    )
+}
+
+/// The storages a signal reads, whether it's a variable, a slice of one named like with
+/// [`slice_name`], or the states of an enum, or `None` if there isn't one by that name.
+fn signal_storages(processed: &Processed, signal: &str) -> Option<Vec<StorageId>> {
+    let find = |path: &str| {
+        processed
+            .vars()
+            .iter()
+            .find(|var| processed.var_path(var) == path)
+    };
+    if let Some(var) = find(signal) {
+        return Some(Processed::var_storages(var).to_vec());
+    }
+    if let Some(path) = signal.strip_suffix(STATES_SUFFIX) {
+        return match &find(path)?.kind {
+            VarKind::Enum { storage, .. } => Some(vec![*storage]),
+            _ => None,
+        };
+    }
+    Some(vec![signal_slice(processed, signal)?.storage])
+}
+
+/// The bits of a variable that a signal named like with [`slice_name`] reads, or `None` if it
+/// isn't named like one.
+fn signal_slice(processed: &Processed, signal: &str) -> Option<BitSlice> {
+    let (path, range) = signal.strip_suffix(']')?.rsplit_once('[')?;
+    let (high, low) = range.split_once(':')?;
+    let var = processed
+        .vars()
+        .iter()
+        .find(|var| processed.var_path(var) == path)?;
+    let high = var.kind.bit_offset(high.parse().ok()?)?;
+    let low = var.kind.bit_offset(low.parse().ok()?)?;
+    BitSlice::of_var(processed, var, low.min(high), low.abs_diff(high) + 1).ok()?
+}
 
+/// Where the values of a signal come from, to show them as text.
+fn signal_source(processed: &Processed, signal: &str) -> Result<Source, String> {
+    let var = processed
+        .vars()
+        .iter()
+        .find(|var| processed.var_path(var) == signal);
+    if let Some(var) = var {
+        let storage = match Processed::var_storages(var) {
+            &[storage] => storage,
+            _ => return Err(format!("`{}` isn't held in one storage", signal)),
+        };
+        let width = processed.storage(storage).map_err(|e| e.to_string())?.width;
+        return Ok(Source::Bits {
+            storage,
+            bits: 0..width,
+        });
+    }
+    if let Some(path) = signal.strip_suffix(STATES_SUFFIX) {
+        let (storage, values) = enum_states(processed, path)?;
+        return Ok(Source::States { storage, values });
+    }
+
+    let slice = signal_slice(processed, signal)
+        .ok_or_else(|| format!("there's no signal named `{}`", signal))?;
+    Ok(Source::Bits {
+        storage: slice.storage,
+        bits: slice.start..slice.start + slice.width,
+    })
+}
+
+/// The storage of an enum variable and its states.
+fn enum_states(processed: &Processed, path: &str) -> Result<(StorageId, Vec<EnumValue>), String> {
+    let var = processed
+        .vars()
+        .iter()
+        .find(|var| processed.var_path(var) == path)
+        .ok_or_else(|| format!("there's no variable named `{}`", path))?;
+    match &var.kind {
+        VarKind::Enum { storage, values } => Ok((*storage, values.clone())),
+        _ => Err(format!("`{}` isn't an enum", path)),
+    }
+}
+
+/// Color the tracks that show which state an enum variable is in, over the time each pane shows,
+/// with a color for each state.
+fn describe_states(state: &mut ViewState, scene: &mut Scene) {
+    let processed = match &mut state.waveform {
+        Some((_, processed)) => processed,
+        None => return,
+    };
+
+    for (pane, pane_scene) in state.panes.iter().zip(&mut scene.panes) {
+        let viewport = &pane.viewport;
+        let range =
+            Timesteps(viewport.start.max(0.0) as u64)..Timesteps(viewport.end.ceil() as u64);
+        for row in state.tracks.rows(&state.traces, pane.height) {
+            let path = match row.signal.strip_suffix(STATES_SUFFIX) {
+                Some(path) => path,
+                None => continue,
+            };
+            // Tracks that aren't in the open file are reported when it's opened.
+            let (storage, values) = match enum_states(processed, path) {
+                Ok(states) => states,
+                Err(_) => continue,
+            };
+            let spans = match states::state_spans(processed, storage, &values, range.clone()) {
+                Ok(spans) => spans,
+                Err(e) => {
+                    eprintln!("failed to load signal data: {}", e);
+                    return;
+                }
+            };
+
+            let extent = [row.top as f32, (row.top + row.height) as f32];
+            pane_scene.tints.extend(spans.into_iter().map(|span| {
+                let end = span
+                    .end
+                    .map_or(viewport.end, |end| (end.0 as f64).min(viewport.end));
+                let [r, g, b] = match span.state {
+                    Some(state) => STATE_COLORS[state % STATE_COLORS.len()],
+                    None => {
+                        let [r, g, b, _] = scene.colors.unknown;
+                        [r, g, b]
+                    }
+                };
+                Tint {
+                    span: [
+                        viewport.x_at(span.start.0 as f64) as f32,
+                        viewport.x_at(end) as f32,
+                    ],
+                    extent,
+                    color: [r, g, b, STATE_ALPHA],
+                }
+            }));
+        }
+    }
+}
+
+/// The storage of a one-bit variable, to sample on its edges.
+fn clock_storage(processed: &mut Processed, path: &str) -> Result<StorageId, String> {
+    let var = processed
+        .vars()
+        .iter()
+        .find(|var| processed.var_path(var) == path)
+        .ok_or_else(|| format!("there's no variable named `{}`", path))?;
+    let id = match Processed::var_storages(var) {
+        &[id] => id,
+        _ => return Err(format!("`{}` isn't a single bit", path)),
+    };
+    // An empty range checks that it's one bit without loading any of it.
+    clocks::levels_in_range(processed, id, Timesteps(0)..Timesteps(0))
+        .map_err(|e| e.to_string())?;
+    Ok(id)
+}
+
+/// Mark where each clock cycle starts in each pane, over the time it shows, while tracks are
+/// sampled on a clock's edges.
+fn describe_cycles(state: &mut ViewState, scene: &mut Scene) {
+    let (processed, sampling) = match (&mut state.waveform, state.traces.sampling()) {
+        (Some((_, processed)), Some(sampling)) => (processed, sampling),
+        _ => return,
+    };
+    // Clocks that aren't in the open file are reported when it's opened.
+    let clock = match clock_storage(processed, &sampling.clock) {
+        Ok(clock) => clock,
+        Err(_) => return,
+    };
+
+    let [r, g, b, _] = scene.colors.line;
+    for (pane, pane_scene) in state.panes.iter().zip(&mut scene.panes) {
+        let viewport = &pane.viewport;
+        let range =
+            Timesteps(viewport.start.max(0.0) as u64)..Timesteps(viewport.end.ceil() as u64);
+        let levels = match clocks::levels_in_range(processed, clock, range) {
+            Ok(levels) => levels,
+            Err(e) => {
+                eprintln!("failed to load signal data: {}", e);
+                return;
+            }
+        };
+
+        let edges: Vec<_> = resample::edges(levels, sampling.edge).collect();
+        if edges.len() as f64 * MIN_CYCLE_WIDTH > viewport.width {
+            continue;
+        }
+        pane_scene.tints.extend(edges.into_iter().map(|time| {
+            let x = viewport.x_at(time.0 as f64) as f32;
+            Tint {
+                span: [x - 0.5, x + 0.5],
+                extent: [0.0, pane.height as f32],
+                color: [r, g, b, CYCLE_DIVIDER_ALPHA],
+            }
+        }));
+    }
+}
+
+/// Export the tracks of `signals`, or of every shown signal if there aren't any, to a PDF at
+/// `path`, along with the notes over them. They're drawn from the same tiles as the canvas, in
+/// outline, over `range`, or the time `pane` shows if there isn't one, and named down the left.
+///
+/// Returns how many tracks there are.
+fn export_pdf(
+    state: &mut ViewState,
+    pane: &Pane,
+    path: &std::path::Path,
+    range: Option<(f64, f64)>,
+    signals: Option<Vec<String>>,
+) -> Result<usize, String> {
+    let mut viewport = pane.viewport;
+    if let Some((start, end)) = range {
+        viewport.zoom_to(start, end);
+    }
+    // Every track is shown whole, pinned ones first as on the canvas.
+    let signals = match signals {
+        Some(signals) => signals,
+        None => {
+            let mut signals: Vec<_> = state.traces.signals().map(str::to_owned).collect();
+            signals.sort_by_key(|signal| !state.traces.is_pinned(signal));
+            signals
+        }
+    };
+    if signals.is_empty() {
+        return Err("there aren't any signals to export".to_string());
+    }
+
+    let processed = match &mut state.waveform {
+        Some((_, processed)) => processed,
+        None => return Err("there's no waveform open".to_string()),
+    };
+    let mut traces = Traces::default();
+    for signal in signals {
+        signal_source(processed, &signal)?;
+        traces.add_signal(signal);
+    }
+    let height = traces.signals().count() as f64 * TRACK_HEIGHT;
+    let rows = TrackLayout::default().rows(&traces, height);
+
+    // Names go down the left, so the time axis starts past the longest. Helvetica averages about
+    // half a point across per point of its size.
+    let longest = rows.iter().map(|row| row.signal.len()).max().unwrap_or(0);
+    let left = longest as f64 * FLAG_SIZE * 0.6 + LABEL_MARGIN * 2.0;
+    let span = [left, left + viewport.width];
+    let mut page = PdfPage::new(span[1], height);
+
+    let to_error = |e: Error| format!("failed to load signal data: {}", e);
+    for row in &rows {
+        let name_y = row.track_top + (TRACK_HEIGHT + FLAG_SIZE) / 2.0;
+        page.text(LABEL_MARGIN, name_y, FLAG_SIZE, row.signal);
+
+        let (storage, ty, width) = match tile_storage(processed, row.signal) {
+            Some(storage) => storage,
+            None => continue,
+        };
+        let extent = [row.track_top, row.track_top + TRACK_HEIGHT];
+        let track = TileTrack {
+            storage,
+            ty,
+            viewport,
+            left,
+            extent,
+            clip: [span[0], row.top, span[1], row.top + row.height],
+        };
+        tiles_along(processed, &track, |processed, key, position| {
+            let (packed, changes) = residency::pack_tile(processed, key)?;
+            let tile = outline::Tile {
+                packed: &packed,
+                changes,
+                ty,
+                position,
+            };
+            outline::draw(&mut page, &tile, is_one_bit(ty, width), extent, span);
+            Ok(())
+        })
+        .map_err(to_error)?;
+    }
+    let labels = value_labels(processed, &viewport, &rows, [0.0; 4]).map_err(to_error)?;
+    for label in labels {
+        let [x, y] = label.position.map(|position| position as f64);
+        let baseline = y + text::GLYPH_HEIGHT as f64;
+        page.text(left + x, baseline, text::GLYPH_HEIGHT as f64, &label.text);
+    }
+
+    // Each note is a flag on a pole at its time, with a bar along the top for the span it covers,
+    // if it covers one.
+    for annotation in state.annotations.visible(viewport.start, viewport.end) {
+        let x = left + viewport.x_at(annotation.time);
+        let (top, bottom) = (FLAG_SIZE, height);
+        page.polyline(&[[x, bottom], [x, top]], 1.0);
+        page.polyline(
+            &[
+                [x, top],
+                [x + FLAG_SIZE, top + FLAG_SIZE / 2.0],
+                [x, top + FLAG_SIZE],
+            ],
+            1.0,
+        );
+        if let Some(end) = annotation.end {
+            let end_x = left + viewport.x_at(end);
+            page.polyline(&[[x, top], [end_x, top]], 1.0);
+        }
+
+        let label = match &annotation.var {
+            Some(var) => format!("{}: {}", var, annotation.text),
+            None => annotation.text.clone(),
+        };
+        page.text(x + FLAG_SIZE * 1.5, top + FLAG_SIZE, FLAG_SIZE, &label);
+    }
+
+    File::create(path)
+        .and_then(|file| page.write(BufWriter::new(file)))
+        .map_err(|e| format!("failed to export view to {}: {}", path.display(), e))?;
+    Ok(rows.len())
+}
+
+/// Carry out a command against the pane under the cursor.
+///
+/// Returns whether the canvas needs to be redrawn.
+fn execute(
+    command: Command,
+    state: &mut ViewState,
+    cursor: (f64, f64),
+    proxy: &EventLoopProxy<UserEvent>,
+) -> Result<bool, String> {
+    // Notices are only about the last command.
+    state.notice = None;
+    let ViewState {
+        panes,
+        annotations,
+        antialiasing,
+        theme,
+        time_bounds,
+        ..
+    } = state;
+    let index = panes.pane_at(cursor.1);
+    let cursor_time = panes.viewport(index).time_at(cursor.0);
+
+    match command {
+        Command::ZoomFull => {
+            panes.update_viewport(index, |viewport| viewport.zoom_full(*time_bounds))
+        }
+        Command::Zoom(factor) => {
+            panes.update_viewport(index, |viewport| viewport.zoom_around(cursor_time, factor))
+        }
+        Command::GotoTime(time) => {
+            panes.update_viewport(index, |viewport| viewport.center_on(time))
+        }
+        Command::ToggleSplit => panes.toggle_split(),
+        Command::ToggleTimeLock => panes.time_locked = !panes.time_locked,
+        Command::ExportPdf {
+            path,
+            range,
+            signals,
+        } => {
+            let pane = *panes.iter().nth(index).unwrap();
+            let outcome = export_pdf(state, &pane, &path, range, signals);
+            state.notice = Some(match &outcome {
+                Ok(tracks) => format!(
+                    "exported {} track{} to {}",
+                    tracks,
+                    if *tracks == 1 { "" } else { "s" },
+                    path.display()
+                ),
+                Err(e) => e.clone(),
+            });
+            outcome?;
+            return Ok(false);
+        }
+        Command::Annotate(annotation) => annotations.add(annotation),
+        Command::LoadAnnotations(path) => annotations
+            .load(&path)
+            .map_err(|e| format!("failed to load annotations from {}: {}", path.display(), e))?,
+        Command::ExportAnnotations(path) => {
+            annotations.write(&path).map_err(|e| {
+                format!("failed to export annotations to {}: {}", path.display(), e)
+            })?;
+            return Ok(false);
+        }
+        Command::SetAntialiasing(mode) => antialiasing.set(mode),
+        Command::CycleAntialiasing => {
+            antialiasing.cycle();
+            eprintln!("antialiasing: {}", antialiasing.mode());
+        }
+        Command::SetPalette(palette) => theme.palette = palette,
+        Command::CyclePalette => {
+            theme.palette = theme.palette.next();
+            eprintln!("palette: {}", theme.palette);
+        }
+        Command::SetHatching(hatching) => theme.hatching = hatching,
+        Command::ToggleHatching => theme.hatching = !theme.hatching,
+        Command::OpenFile(path) => {
+            state.open(path, proxy)?;
+            // With nothing open yet, the canvas says what's opening.
+            return Ok(state.waveform.is_none());
+        }
+        Command::ToggleWatch => {
+            if state.watch.take().is_none() {
+                state.start_watching(proxy)?;
+            }
+            return Ok(false);
+        }
+        Command::ToggleWarnings => {
+            state.warnings_expanded = !state.warnings_expanded;
+            state.report_warnings();
+            return Ok(false);
+        }
+        Command::AddTrigger(trigger) => {
+            // It can't be checked until there's a file to check it against.
+            if let Ok(processed) = state.processed() {
+                Condition::parse(&trigger.condition, processed).map_err(|e| e.to_string())?;
+            }
+            state.triggers.add(trigger);
+            return Ok(false);
+        }
+        Command::RemoveTrigger(name) => {
+            if !state.triggers.remove(&name) {
+                return Err(format!("there's no trigger named `{}`", name));
+            }
+            return Ok(false);
+        }
+        Command::JumpToTrigger => {
+            if state.triggers.iter().next().is_none() {
+                return Err("there aren't any triggers".to_string());
+            }
+            if !state.jump_to_trigger()? {
+                return Err("none of the triggers go off".to_string());
+            }
+        }
+        Command::AddSignal(path) => {
+            let processed = state.processed()?;
+            if !processed
+                .vars()
+                .iter()
+                .any(|var| processed.var_path(var) == path)
+            {
+                return Err(format!("there's no variable named `{}`", path));
+            }
+            state.traces.add_signal(path);
+        }
+        Command::AddMatching(pattern) => {
+            let glob = Glob::new(&pattern).map_err(|e| e.to_string())?;
+            let processed = state.processed()?;
+            let paths: Vec<_> = processed
+                .find_vars(&glob)
+                .into_iter()
+                .map(|var| processed.var_path(var))
+                .collect();
+            if paths.is_empty() {
+                return Err(format!("no variables match `{}`", pattern));
+            }
+            eprintln!("added {} signals matching `{}`", paths.len(), pattern);
+            state.traces.add_group(pattern, paths);
+        }
+        Command::AddAcrossInstances { path, instance } => {
+            let processed = state.processed()?;
+            let var = processed
+                .vars()
+                .iter()
+                .find(|var| processed.var_path(var) == path)
+                .ok_or_else(|| format!("there's no variable named `{}`", path))?;
+            let scopes = processed.scope_path(var.scope_id);
+            let index = match &instance {
+                Some(instance) => {
+                    let prefix: Vec<_> = instance.split('.').collect();
+                    if !scopes.starts_with(&prefix) {
+                        return Err(format!("`{}` isn't a scope `{}` is in", instance, path));
+                    }
+                    Some(prefix.len() - 1)
+                }
+                None => None,
+            };
+
+            let (index, vars) = processed
+                .across_instances(var, index)
+                .ok_or_else(|| format!("`{}` isn't in any other instances", path))?;
+            let paths: Vec<_> = vars.iter().map(|var| processed.var_path(var)).collect();
+            // Named like a pattern for the signals, with the instance as a wildcard.
+            let mut name: Vec<_> = scopes;
+            name[index] = "*";
+            name.push(processed.name(var.name));
+            let name = name.join(".");
+            eprintln!("added {} signals as `{}`", paths.len(), name);
+            state.traces.add_group(name, paths);
+        }
+        Command::SplitByteLanes(path) => {
+            let processed = state.processed()?;
+            let var = processed
+                .vars()
+                .iter()
+                .find(|var| processed.var_path(var) == path)
+                .ok_or_else(|| format!("there's no variable named `{}`", path))?;
+            let lanes = BitSlice::byte_lanes(processed, var)
+                .map_err(|e| e.to_string())?
+                .ok_or_else(|| format!("`{}` can't be split into bytes", path))?;
+            if lanes.len() < 2 {
+                return Err(format!("`{}` is only a byte wide", path));
+            }
+            // Most significant first, the way the bus's value reads.
+            let names: Vec<_> = lanes
+                .iter()
+                .enumerate()
+                .rev()
+                .map(|(i, lane)| slice_name(processed, var, i as u32 * 8, lane.width))
+                .collect();
+            eprintln!("split `{}` into {} byte lanes", path, names.len());
+            state.traces.group_under(&path, names);
+        }
+        Command::ShowStates(path) => {
+            enum_states(state.processed()?, &path)?;
+            state
+                .traces
+                .group_under(&path, vec![format!("{}{}", path, STATES_SUFFIX)]);
+        }
+        Command::SummarizeStates(path) => {
+            let viewport = &panes.iter().nth(index).unwrap().viewport;
+            let range =
+                Timesteps(viewport.start.max(0.0) as u64)..Timesteps(viewport.end.ceil() as u64);
+            let processed = state.processed_mut()?;
+            let (storage, values) = enum_states(processed, &path)?;
+            let spans = states::state_spans(processed, storage, &values, range.clone())
+                .map_err(|e| e.to_string())?;
+
+            let separators = Separators::from_env();
+            let femtoseconds_per_timestep = processed.femtoseconds_per_timestep();
+            let span = (range.end.0 - range.start.0).max(1);
+            eprintln!("time `{}` spent in each state:", path);
+            for (state, time) in states::time_in_states(&spans, range) {
+                let name = state.map_or("(none)", |state| values[state].name.as_str());
+                eprintln!(
+                    "  {}: {}%, {}",
+                    name,
+                    format::format_decimal(time as f64 * 100.0 / span as f64, 1, separators),
+                    format::format_time(time as u128 * femtoseconds_per_timestep, separators)
+                );
+            }
+        }
+        Command::Pin(signal) => state.set_pinned(&signal, true)?,
+        Command::Unpin(signal) => state.set_pinned(&signal, false)?,
+        Command::ScrollTracks(pixels) => {
+            let pane = panes.iter().nth(index).unwrap();
+            let y = cursor.1 - pane.top;
+            state.tracks.scroll(&state.traces, y, pane.height, pixels);
+        }
+        Command::Highlight(highlight) => {
+            if !state
+                .traces
+                .signals()
+                .any(|signal| signal == highlight.signal)
+            {
+                return Err(format!("`{}` isn't shown", highlight.signal));
+            }
+            let processed = state.processed()?;
+            Condition::parse_for(&highlight.condition, processed, &highlight.signal)
+                .map_err(|e| e.to_string())?;
+            state.traces.add_highlight(highlight);
+        }
+        Command::ClearHighlights(signal) => {
+            if state.traces.clear_highlights(&signal) == 0 {
+                return Err(format!("`{}` isn't highlighted", signal));
+            }
+        }
+        Command::SetReset(reset) => {
+            // Unlike highlighted signals, the reset needn't be shown itself.
+            let processed = state.processed()?;
+            Condition::parse_for(reset.condition(), processed, &reset.signal)
+                .map_err(|e| e.to_string())?;
+            state.traces.set_reset(Some(reset));
+        }
+        Command::ClearReset => {
+            if state.traces.set_reset(None).is_none() {
+                return Err("there's no reset chosen".to_string());
+            }
+        }
+        Command::SetSampling(sampling) => {
+            clock_storage(state.processed_mut()?, &sampling.clock)?;
+            state.traces.set_sampling(Some(sampling));
+        }
+        Command::ClearSampling => {
+            if state.traces.set_sampling(None).is_none() {
+                return Err("tracks aren't being sampled on a clock".to_string());
+            }
+        }
+        Command::CopyPath => {
+            let signal = state
+                .signal_at(cursor)
+                .ok_or("there's no signal under the cursor")?;
+            // The states of an enum are copied as the enum's path.
+            let path = signal.strip_suffix(STATES_SUFFIX).unwrap_or(signal);
+            clipboard::copy(path)?;
+            eprintln!("copied {}", path);
+            return Ok(false);
+        }
+        Command::CopyValue => {
+            let signal = state
+                .signal_at(cursor)
+                .ok_or("there's no signal under the cursor")?
+                .to_owned();
+            let processed = state.processed_mut()?;
+            let source = signal_source(processed, &signal)?;
+            let shown = source
+                .value_at(processed, Timesteps(cursor_time.max(0.0) as u64))
+                .map_err(|e| e.to_string())?
+                .ok_or_else(|| format!("`{}` isn't set yet at the cursor", signal))?;
+            clipboard::copy(&shown.text)?;
+            eprintln!("copied {} = {}", signal, shown.text);
+            return Ok(false);
+        }
+        Command::CopyRange { start, end } => {
+            let pane = panes.iter().nth(index).unwrap();
+            let signals: Vec<_> = state
+                .tracks
+                .rows(&state.traces, pane.height)
+                .into_iter()
+                .map(|row| row.signal.to_owned())
+                .collect();
+            if signals.is_empty() {
+                return Err("there aren't any signals shown".to_string());
+            }
+
+            let range = Timesteps(start.min(end).max(0.0) as u64)
+                ..Timesteps(start.max(end).max(0.0).ceil() as u64);
+            let processed = state.processed_mut()?;
+            let mut names = vec![];
+            let mut columns = vec![];
+            for signal in signals {
+                let changes = signal_source(processed, &signal).and_then(|source| {
+                    source
+                        .changes_in_range(processed, range.clone())
+                        .map_err(|e| e.to_string())
+                });
+                let changes = match changes {
+                    Ok(changes) => changes,
+                    Err(e) => {
+                        eprintln!("not copying {}: {}", signal, e);
+                        continue;
+                    }
+                };
+                // Values already in effect at the start show up as of the start.
+                columns.push(
+                    changes
+                        .into_iter()
+                        .map(|(time, text)| (time.0.max(range.start.0), text))
+                        .collect(),
+                );
+                names.push(signal);
+            }
+
+            clipboard::copy(&clipboard::csv_snippet(&names, &columns))?;
+            eprintln!(
+                "copied {} signals from {} to {} as CSV",
+                names.len(),
+                range.start.0,
+                range.end.0
+            );
+            return Ok(false);
+        }
+        Command::PlaceMarker(_) => {
+            return Err("not supported yet: the viewer doesn't draw markers".to_string())
+        }
+    }
+
+    Ok(true)
+}
+
+/// A way of getting an adapter, from the list in [`ADAPTER_ATTEMPTS`].
+struct AdapterAttempt {
+    name: &'static str,
+    backends: wgpu::Backends,
+    force_fallback_adapter: bool,
+    /// Ask Mesa for its software rasterizer, llvmpipe, rather than any driver for the hardware.
+    software: bool,
+}
+
+/// The ways of getting an adapter that are tried, most preferred first.
+const ADAPTER_ATTEMPTS: &[AdapterAttempt] = &[
+    AdapterAttempt {
+        name: "hardware",
+        backends: wgpu::Backends::all(),
+        force_fallback_adapter: false,
+        software: false,
+    },
+    AdapterAttempt {
+        name: "fallback",
+        backends: wgpu::Backends::all(),
+        force_fallback_adapter: true,
+        software: false,
+    },
+    AdapterAttempt {
+        name: "software OpenGL",
+        backends: wgpu::Backends::GL,
+        force_fallback_adapter: false,
+        software: true,
+    },
+];
+
+struct Gpu {
+    /// Never used again, but kept for as long as the surface made from it.
+    _instance: Instance,
+    surface: wgpu::Surface,
+    adapter: wgpu::Adapter,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+}
+
+/// Find an adapter that can draw to the window, and create a device on it.
+///
+/// Returns why each attempt failed, if they all did.
+async fn request_gpu(window: &Window) -> Result<Gpu, String> {
+    let mut failures = vec![];
+    for attempt in ADAPTER_ATTEMPTS {
+        if attempt.software {
+            // Mesa reads this when the OpenGL instance below is created.
+            env::set_var("LIBGL_ALWAYS_SOFTWARE", "1");
+        }
+
+        let instance = Instance::new(attempt.backends);
+        let surface = unsafe { instance.create_surface(window) };
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                force_fallback_adapter: attempt.force_fallback_adapter,
+                // Request an adapter which can render to our surface
+                compatible_surface: Some(&surface),
+            })
+            .await;
+        let adapter = match adapter {
+            Some(adapter) => adapter,
+            None => {
+                failures.push(format!("  {}: no adapter was found", attempt.name));
+                continue;
+            }
+        };
+
+        // Create the logical device and command queue
+        let device = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: None,
+                    features: wgpu::Features::empty(),
+                    // Make sure we use the texture resolution limits from the adapter, so we can support images the size of the swapchain.
+                    limits: wgpu::Limits::default().using_resolution(adapter.limits()),
+                },
+                None,
+            )
+            .await;
+        match device {
+            Ok((device, queue)) => {
+                if !failures.is_empty() {
+                    eprintln!(
+                        "drawing with the {} adapter, {}",
+                        attempt.name,
+                        adapter.get_info().name
+                    );
+                }
+                return Ok(Gpu {
+                    _instance: instance,
+                    surface,
+                    adapter,
+                    device,
+                    queue,
+                });
+            }
+            Err(e) => failures.push(format!("  {}: {}", attempt.name, e)),
+        }
+    }
+
+    Err(failures.join("\n"))
+}
+
+/// What the viewer was started with, from the command line.
+struct Args {
+    antialiasing: Antialiasing,
+    theme: Theme,
+    file: Option<PathBuf>,
+    watch: bool,
+    triggers: Triggers,
+    options: IngestorOptions,
+}
+
+async fn run(event_loop: EventLoop<UserEvent>, window: Window, args: Args) {
+    let Args {
+        antialiasing,
+        theme,
+        file,
+        watch,
+        triggers,
+        options,
+    } = args;
+    let mut size = window.inner_size();
+    let gpu = match request_gpu(&window).await {
+        Ok(gpu) => gpu,
+        Err(e) => {
+            eprintln!("couldn't find a graphics adapter to draw with:\n{}", e);
+            eprintln!(
+                "waveforms can still be processed without a window, with `ligeia script <file.lua>`"
+            );
+            process::exit(1);
+        }
+    };
+
+    let swapchain_format = gpu.surface.get_supported_formats(&gpu.adapter)[0];
+
+    // The surface format has to support multisampling and resolving for MSAA to work.
+    let msaa_flags = wgpu::TextureFormatFeatureFlags::MULTISAMPLE
+        | wgpu::TextureFormatFeatureFlags::MULTISAMPLE_RESOLVE;
+    let msaa_supported = gpu
+        .adapter
+        .get_texture_format_features(swapchain_format)
+        .flags
+        .contains(msaa_flags);
+
+    // (time, y) pairs
+    let points: &[[f32; 2]] = &[[10., 100.], [300., 10.], [300., 500.]];
+    // Until a waveform is opened, the demo line is all there is to fit.
+    let time_bounds = points
+        .iter()
+        .fold((f64::MAX, f64::MIN), |(first, last), &[t, _]| {
+            (first.min(t as f64), last.max(t as f64))
+        });
+
+    let mut state = ViewState {
+        panes: Panes::new(
+            Viewport::new(
+                time_bounds.0,
+                time_bounds.1,
+                minimap::axis_width(size.width),
+            ),
+            size.height as f64,
+        ),
+        annotations: Annotations::default(),
+        antialiasing: AntialiasingSetting::new(antialiasing, msaa_supported),
+        theme,
+        traces: Traces::default(),
+        tracks: TrackLayout::default(),
+        triggers,
+        waveform: None,
+        loading: None,
+        watch: None,
+        reload_again: false,
+        warnings_expanded: false,
+        options,
+        time_bounds,
+        notice: None,
+    };
+    let proxy = event_loop.create_proxy();
+    if let Some(file) = file {
+        state.open(file, &proxy).unwrap();
+        if watch {
+            if let Err(e) = state.start_watching(&proxy) {
+                eprintln!("{}", e);
+            }
+        }
+    }
+    window.set_title(&state.title());
+    let mut modifiers = ModifiersState::empty();
+    let mut cursor = (0.0, 0.0);
+    // (pane index, x)
+    let mut drag_start = None;
+    // The pane whose trace is under the cursor.
+    let mut hovered = None;
+    // Whether the cursor is over a track, and so has a tooltip beside it.
+    let mut hovered_track = false;
+    let mut touches = Touches::default();
+
+    // The passes are drawn in the order they're added.
+    let mut graph = RenderGraph::new();
+    let tile_pool = TilePool::new(&gpu.device);
+    let one_bit = OneBitPass::new(&gpu.device, tile_pool.buffer());
+    let buses = BusPass::new(&gpu.device, tile_pool.buffer());
+    graph.add("tile pool", tile_pool);
+    graph.add("highlights", HighlightsPass::new(&gpu.device));
+    graph.add("one-bit tracks", one_bit);
+    graph.add("buses", buses);
+    graph.add("lines", LinesPass::new(&gpu.device));
+    graph.add("text", TextPass::new(&gpu.device));
+    let scenes = Arc::new(SceneExchange::new());
+    let picks = Arc::new(Picks::default());
+    let mut render_thread = Some(render::spawn(
+        gpu,
+        swapchain_format,
+        graph,
+        scenes.clone(),
+        picks.clone(),
+    ));
+    // Described afresh for each frame, then swapped for an old one when it's published.
+    let mut scene = Scene::default();
+    let mut residency = Residency::new();
+
+    event_loop.run(move |event, _, control_flow| {
         *control_flow = ControlFlow::Wait;
         match event {
             Event::WindowEvent {
-                event: WindowEvent::Resized(size),
+                event: WindowEvent::Resized(new_size),
                 ..
             } => {
-                config.width = size.width;
-                config.height = size.height;
-                msaa_framebuffer = create_msaa_frambuffer(&device, &config, sample_count);
-                surface.configure(&device, &config);
-                // On macos the window needs to be redrawn manually after resizing
+                size = new_size;
+                state
+                    .panes
+                    .resize(minimap::axis_width(size.width), size.height as f64);
+                // On macos the window needs to be redrawn manually after resizing, and the render
+                // thread only resizes the surface when it's given a scene to draw.
                 window.request_redraw();
             }
-            Event::RedrawRequested(_) => {
-                let frame = surface
-                    .get_current_texture()
-                    .expect("failed to acquire next swap chain texture");
-                let view = frame
-                    .texture
-                    .create_view(&wgpu::TextureViewDescriptor::default());
-
-                queue.write_buffer(
-                    &uniform_buffer,
-                    0,
-                    bytemuck::bytes_of(&Uniforms {
-                        scale: [2.0 / config.width as f32, 2.0 / config.height as f32],
-                        feather_fraction: 0.4,
-                        line_width: 7.0,
+            Event::WindowEvent {
+                event: WindowEvent::ModifiersChanged(new),
+                ..
+            } => {
+                modifiers = new;
+            }
+            Event::WindowEvent {
+                event: WindowEvent::CursorMoved { position, .. },
+                ..
+            } => {
+                cursor = (position.x, position.y);
+                let trace = picks.trace_at(cursor);
+                if trace != hovered {
+                    hovered = trace;
+                    window.set_cursor_icon(match hovered {
+                        Some(_) => CursorIcon::Hand,
+                        None => CursorIcon::Default,
+                    });
+                }
+                // Tooltips follow the cursor, and go away once it's off the tracks.
+                let over_track = state.signal_at(cursor).is_some();
+                if over_track || hovered_track {
+                    window.request_redraw();
+                }
+                hovered_track = over_track;
+            }
+            Event::WindowEvent {
+                event: WindowEvent::CursorLeft { .. },
+                ..
+            } => {
+                if mem::take(&mut hovered_track) {
+                    window.request_redraw();
+                }
+            }
+            Event::WindowEvent {
+                event:
+                    WindowEvent::MouseInput {
+                        state: button_state,
+                        button: MouseButton::Left,
+                        ..
+                    },
+                ..
+            } => match button_state {
+                ElementState::Pressed => {
+                    // Clicking the minimap goes to the track clicked on, rather than zooming.
+                    if state.navigate_minimap(cursor) {
+                        window.request_redraw();
+                    } else {
+                        drag_start = Some((state.panes.pane_at(cursor.1), cursor.0));
+                    }
+                }
+                ElementState::Released => {
+                    // Zoom to the dragged-over time range.
+                    if let Some((index, start_x)) = drag_start.take() {
+                        if (cursor.0 - start_x).abs() >= MIN_DRAG_DISTANCE {
+                            state.panes.update_viewport(index, |viewport| {
+                                viewport
+                                    .zoom_to(viewport.time_at(start_x), viewport.time_at(cursor.0))
+                            });
+                            window.request_redraw();
+                        }
+                    }
+                }
+            },
+            Event::WindowEvent {
+                event: WindowEvent::MouseWheel { delta, .. },
+                ..
+            } => {
+                // Touchpads scroll by pixels, and mouse wheels by lines. Pinching on a touchpad
+                // comes through as scrolling with Ctrl held on most platforms.
+                let delta = match delta {
+                    MouseScrollDelta::LineDelta(x, y) => {
+                        (x as f64 * PIXELS_PER_LINE, y as f64 * PIXELS_PER_LINE)
+                    }
+                    MouseScrollDelta::PixelDelta(position) => (position.x, position.y),
+                };
+                let gesture = Gesture::scroll(cursor, delta, modifiers.ctrl());
+                // Scrolling up and down without zooming scrolls the tracks instead. The content
+                // follows the fingers, so the tracks move the other way.
+                if !modifiers.ctrl() && delta.1 != 0.0 {
+                    let command = Command::ScrollTracks(-delta.1);
+                    let outcome = execute(command, &mut state, cursor, &proxy);
+                    if let Ok(true) = outcome {
+                        window.request_redraw();
+                    }
+                    window.set_title(&state.title());
+                }
+                if gesture.pan != 0.0 || gesture.zoom != 1.0 {
+                    let index = state.panes.pane_at(cursor.1);
+                    state
+                        .panes
+                        .update_viewport(index, |viewport| gesture.apply(viewport));
+                    window.request_redraw();
+                }
+            }
+            Event::WindowEvent {
+                event:
+                    WindowEvent::Touch(Touch {
+                        id,
+                        phase,
+                        location,
+                        ..
                     }),
-                );
-
-                let mut encoder =
-                    device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
-                {
-                    let color_attachment = if sample_count == 1 {
-                        wgpu::RenderPassColorAttachment {
-                            view: &view,
-                            resolve_target: None,
-                            ops: wgpu::Operations {
-                                load: wgpu::LoadOp::Clear(wgpu::Color {
-                                    r: 0.15,
-                                    g: 0.15,
-                                    b: 0.25,
-                                    a: 1.0,
-                                }),
-                                store: true,
+                ..
+            } => match phase {
+                TouchPhase::Started => touches.start(id, (location.x, location.y)),
+                TouchPhase::Moved => {
+                    if let Some(gesture) = touches.moved(id, (location.x, location.y)) {
+                        let index = state.panes.pane_at(gesture.position.1);
+                        state
+                            .panes
+                            .update_viewport(index, |viewport| gesture.apply(viewport));
+                        window.request_redraw();
+                    }
+                }
+                TouchPhase::Ended | TouchPhase::Cancelled => touches.end(id),
+            },
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(keycode),
+                                ..
                             },
+                        ..
+                    },
+                ..
+            } => {
+                let command = match Command::from_key(keycode, modifiers) {
+                    Some(command) => command,
+                    None => return,
+                };
+
+                match execute(command, &mut state, cursor, &proxy) {
+                    Ok(true) => window.request_redraw(),
+                    Ok(false) => {}
+                    Err(e) => eprintln!("{}", e),
+                }
+                window.set_title(&state.title());
+            }
+            Event::WindowEvent {
+                event: WindowEvent::HoveredFile(_),
+                ..
+            } => window.set_title("ligeia - drop to open"),
+            Event::WindowEvent {
+                event: WindowEvent::HoveredFileCancelled,
+                ..
+            } => window.set_title(&state.title()),
+            Event::WindowEvent {
+                event: WindowEvent::DroppedFile(path),
+                ..
+            } => {
+                let command = Command::OpenFile(path);
+                match execute(command, &mut state, cursor, &proxy) {
+                    Ok(true) => window.request_redraw(),
+                    Ok(false) => {}
+                    Err(e) => eprintln!("{}", e),
+                }
+                window.set_title(&state.title());
+            }
+            Event::UserEvent(UserEvent::Remote(RemoteCommand { command, reply })) => {
+                let outcome = execute(command, &mut state, cursor, &proxy);
+                if let Ok(true) = outcome {
+                    window.request_redraw();
+                }
+                let _ = reply.send(outcome.map(|_| ()));
+                window.set_title(&state.title());
+            }
+            Event::UserEvent(UserEvent::Loaded(loaded)) => {
+                let Loaded { path, result } = *loaded;
+                state.loading = None;
+                match result {
+                    Ok(mut processed) => {
+                        let (first, last) = processed.time_bounds();
+                        eprintln!(
+                            "opened {}: {} storages, from {} to {}",
+                            path.display(),
+                            processed.storage_ids().len(),
+                            first.0,
+                            last.0
+                        );
+                        // Signals are kept by name, so they can go missing from a reloaded file.
+                        let missing = state
+                            .traces
+                            .signals()
+                            .filter(|signal| signal_storages(&processed, signal).is_none());
+                        for signal in missing {
+                            eprintln!("{} isn't in {}", signal, path.display());
                         }
-                    } else {
-                        wgpu::RenderPassColorAttachment {
-                            view: &msaa_framebuffer,
-                            resolve_target: Some(&view),
-                            ops: wgpu::Operations {
-                                load: wgpu::LoadOp::Clear(wgpu::Color {
-                                    r: 0.9453125,
-                                    g: 0.9453125,
-                                    b: 0.9453125,
-                                    a: 1.0,
-                                }),
-                                // Storing pre-resolve MSAA data is unnecessary if it isn't used later.
-                                // On tile-based GPU, avoid store can reduce your app's memory footprint.
-                                store: false,
-                            },
+                        if let Some(sampling) = state.traces.sampling() {
+                            if let Err(e) = clock_storage(&mut processed, &sampling.clock) {
+                                eprintln!("not sampling on {}: {}", sampling.clock, e);
+                            }
                         }
-                    };
-
-                    let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                        label: None,
-                        color_attachments: &[Some(color_attachment)],
-                        depth_stencil_attachment: None,
-                    });
-
-                    rpass.set_pipeline(&render_pipeline);
-                    rpass.set_vertex_buffer(0, vertices_buffer.slice(..));
-                    rpass.set_bind_group(0, &bind_group, &[]);
-                    rpass.draw(0..6, 0..6);
+                        if let Some(reset) = state.traces.reset() {
+                            let parsed =
+                                Condition::parse_for(reset.condition(), &processed, &reset.signal);
+                            if let Err(e) = parsed {
+                                eprintln!("not shading during reset {}: {}", reset.signal, e);
+                            }
+                        }
+                        for highlight in state.traces.highlights() {
+                            let parsed = Condition::parse_for(
+                                &highlight.condition,
+                                &processed,
+                                &highlight.signal,
+                            );
+                            if let Err(e) = parsed {
+                                eprintln!(
+                                    "not highlighting {} where `{}`: {}",
+                                    highlight.signal, highlight.condition, e
+                                );
+                            }
+                        }
+                        // A new file is shown whole, but reloads keep their place.
+                        let bounds = (first.0 as f64, last.0 as f64);
+                        let reloaded = matches!(&state.waveform, Some((open, _)) if *open == path);
+                        if !reloaded {
+                            state.panes.zoom_full(bounds);
+                        }
+                        state.time_bounds = bounds;
+                        state.waveform = Some((path, processed));
+                        state.report_warnings();
+                        residency.clear();
+                        let _ = state.jump_to_trigger();
+                    }
+                    Err(e) => eprintln!("failed to open {}: {}", path.display(), e),
                 }
-
-                queue.submit([encoder.finish()]);
-                frame.present();
+                // Either there's a waveform to draw, or the canvas stops saying it's opening.
+                window.request_redraw();
+                if mem::take(&mut state.reload_again) {
+                    if let Some(path) = state.watch.as_ref().map(|watch| watch.path().to_owned()) {
+                        state.open(path, &proxy).unwrap();
+                    }
+                }
+                window.set_title(&state.title());
+            }
+            Event::UserEvent(UserEvent::Changed(path)) => {
+                // Changes to a file that's no longer watched can still be on their way.
+                if state.watch.as_ref().map(Watch::path) != Some(path.as_path()) {
+                    return;
+                }
+                if state.loading.is_some() {
+                    state.reload_again = true;
+                } else {
+                    state.open(path, &proxy).unwrap();
+                    window.set_title(&state.title());
+                }
+            }
+            Event::RedrawRequested(_) => {
+                scene.describe(
+                    (size.width, size.height),
+                    state.antialiasing.mode(),
+                    state.theme,
+                    &state.panes,
+                    points,
+                );
+                require_tiles(&mut residency, &mut state, &mut scene);
+                describe_values(&mut state, &mut scene);
+                describe_highlights(&mut state, &mut scene);
+                describe_states(&mut state, &mut scene);
+                describe_cycles(&mut state, &mut scene);
+                describe_minimap(&state, &mut scene);
+                describe_hint(&state, &mut scene);
+                if hovered_track {
+                    describe_tooltip(&mut state, &mut scene, cursor);
+                }
+                scenes.publish(&mut scene);
             }
             Event::WindowEvent {
                 event: WindowEvent::CloseRequested,
                 ..
             } => {
+                // The surface has to go before the window does.
+                scenes.close();
+                if let Some(thread) = render_thread.take() {
+                    let _ = thread.join();
+                }
                 *control_flow = ControlFlow::Exit;
             }
             _ => {}
@@ -258,8 +1941,203 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
     })
 }
 
+#[cfg(feature = "script")]
+fn run_script(args: &[OsString]) {
+    let path = match args {
+        [path] => std::path::Path::new(path),
+        _ => {
+            eprintln!("usage: ligeia [--scratch-dir <dir>] script <file.lua>");
+            process::exit(2);
+        }
+    };
+
+    if let Err(e) = script::run(path) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+#[cfg(not(feature = "script"))]
+fn run_script(_args: &[OsString]) {
+    eprintln!("ligeia was built without the `script` feature");
+    process::exit(2);
+}
+
+fn run_diff(mut args: &[OsString]) {
+    let (mut strict, mut warnings_as_errors) = (false, false);
+    loop {
+        match args.first() {
+            Some(arg) if arg == "--strict" => strict = true,
+            Some(arg) if arg == "--warnings-as-errors" => warnings_as_errors = true,
+            _ => break,
+        }
+        args = &args[1..];
+    }
+    let (a, b, patterns) = match args {
+        [a, b, patterns @ ..] => (a, b, patterns),
+        _ => {
+            eprintln!(
+                "usage: ligeia [--scratch-dir <dir>] diff [--strict] [--warnings-as-errors] \
+                 <a> <b> [<pattern>...]"
+            );
+            process::exit(2);
+        }
+    };
+    let patterns: Vec<_> = patterns
+        .iter()
+        .map(|pattern| pattern.to_string_lossy().into_owned())
+        .collect();
+
+    // Like diff(1): 1 if they differ, and 2 if they couldn't be compared at all.
+    let (a, b) = (std::path::Path::new(a), std::path::Path::new(b));
+    match diff::run(a, b, &patterns, strict, warnings_as_errors) {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            process::exit(2);
+        }
+    }
+}
+
+fn run_svcb_validate(paths: &[OsString]) {
+    if paths.is_empty() {
+        eprintln!("usage: ligeia [--scratch-dir <dir>] svcb-validate <file>...");
+        process::exit(2);
+    }
+
+    if !ligeia_svcb::validate_all(paths) {
+        process::exit(1);
+    }
+}
+
+/// The directory given to `--scratch-dir`, which has to exist.
+fn scratch_dir(dir: Option<&OsString>) -> PathBuf {
+    match dir {
+        Some(dir) if std::path::Path::new(dir).is_dir() => PathBuf::from(dir),
+        _ => {
+            eprintln!("--scratch-dir needs a directory that exists");
+            process::exit(2);
+        }
+    }
+}
+
 fn main() {
-    let event_loop = EventLoop::new();
+    let mut args: Vec<OsString> = env::args_os().skip(1).collect();
+
+    // Subcommands load with options of their own, so before one of them this applies through
+    // the environment instead of to `options` below.
+    if args.first().map_or(false, |arg| arg == "--scratch-dir") {
+        let dir = scratch_dir(args.get(1));
+        // Nothing else is running yet, so this can't race with anything reading it.
+        env::set_var(ligeia_core::scratch::DIR_VAR, dir);
+        args.drain(..2);
+    }
+
+    if args.first().map_or(false, |arg| arg == "script") {
+        run_script(&args[1..]);
+        return;
+    }
+    if matches!(args.first(), Some(arg) if arg == "diff") {
+        run_diff(&args[1..]);
+        return;
+    }
+    if matches!(args.first(), Some(arg) if arg == "svcb-validate") {
+        run_svcb_validate(&args[1..]);
+        return;
+    }
+
+    let usage = "usage: ligeia [--scratch-dir <dir>] [--rpc <address>] \
+                 [--antialiasing none|analytic|msaa] \
+                 [--palette standard|deuteranopia|protanopia] [--hatching] [--watch] \
+                 [--trigger <name>=<condition>]... [--ignore <pattern>]... \
+                 [--warnings-as-errors] [<file>]";
+    let mut rpc_addr = None;
+    let mut file = None;
+    let mut watch = false;
+    let mut triggers = Triggers::default();
+    let mut antialiasing = Antialiasing::Analytic;
+    let mut theme = Theme::default();
+    let mut options = IngestorOptions::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--rpc" {
+            rpc_addr = args.next();
+        } else if arg == "--antialiasing" {
+            let mode = args.next().unwrap_or_default();
+            antialiasing = match mode.to_string_lossy().parse() {
+                Ok(mode) => mode,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    eprintln!("{}", usage);
+                    process::exit(2);
+                }
+            };
+        } else if arg == "--palette" {
+            let palette = args.next().unwrap_or_default();
+            theme.palette = match palette.to_string_lossy().parse() {
+                Ok(palette) => palette,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    eprintln!("{}", usage);
+                    process::exit(2);
+                }
+            };
+        } else if arg == "--hatching" {
+            theme.hatching = true;
+        } else if arg == "--watch" {
+            watch = true;
+        } else if arg == "--trigger" {
+            let trigger = args.next().unwrap_or_default();
+            match Trigger::parse(&trigger.to_string_lossy()) {
+                Ok(trigger) => triggers.add(trigger),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    eprintln!("{}", usage);
+                    process::exit(2);
+                }
+            }
+        } else if arg == "--ignore" {
+            // Variables matching the pattern, like the words of a huge memory, aren't loaded.
+            let pattern = args.next().unwrap_or_default();
+            match Glob::new(&pattern.to_string_lossy()) {
+                Ok(glob) => options = options.with_ignored(glob),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    eprintln!("{}", usage);
+                    process::exit(2);
+                }
+            }
+        } else if arg == "--warnings-as-errors" {
+            options = options.with_warnings_as_errors();
+        } else if arg == "--scratch-dir" {
+            options = options.with_scratch_dir(scratch_dir(args.next().as_ref()));
+        } else if file.is_none() && !arg.to_string_lossy().starts_with("--") {
+            file = Some(PathBuf::from(arg));
+        } else {
+            eprintln!("unexpected argument {:?}", arg);
+            eprintln!("{}", usage);
+            process::exit(2);
+        }
+    }
+
+    let event_loop = EventLoop::with_user_event();
     let window = Window::new(&event_loop).unwrap();
-    pollster::block_on(run(event_loop, window));
+
+    if let Some(addr) = rpc_addr {
+        let addr = addr.to_string_lossy().into_owned();
+        if let Err(e) = rpc::serve(addr.as_str(), event_loop.create_proxy()) {
+            eprintln!("failed to start rpc server on {}: {}", addr, e);
+            process::exit(1);
+        }
+    }
+    let args = Args {
+        antialiasing,
+        theme,
+        file,
+        watch,
+        triggers,
+        options,
+    };
+    pollster::block_on(run(event_loop, window, args));
 }
@@ -0,0 +1,68 @@
+//! A strip down the right of each pane with every track in its scrolling region squeezed into it,
+//! and the ones that can be seen marked, for getting around lots of tracks quickly.
+//!
+//! Each track's thumbnail is drawn from the same tiles as the track itself, at a level of detail
+//! that fits the whole waveform into the strip, so only a tile or two of each is ever needed.
+
+use crate::{
+    layout::{Overview, TrackLayout, TRACK_HEIGHT},
+    traces::Traces,
+};
+
+/// How wide the strip is, in pixels.
+pub const WIDTH: f64 = 80.0;
+/// The tallest that thumbnails get, in pixels, however few tracks there are.
+const MAX_TRACK_HEIGHT: f64 = 6.0;
+
+/// How wide the time axis is on a canvas `width` wide, which stops where the strip starts.
+pub fn axis_width(width: u32) -> f64 {
+    (width as f64 - WIDTH).max(1.0)
+}
+
+/// The strip of a pane.
+pub struct Minimap<'a> {
+    pub overview: Overview<'a>,
+    /// How tall each thumbnail is, in pixels.
+    pub track_height: f64,
+}
+
+impl<'a> Minimap<'a> {
+    /// The strip of a pane `height` tall.
+    pub fn new(tracks: &TrackLayout, traces: &'a Traces, height: f64) -> Self {
+        let overview = tracks.overview(traces, height);
+        let track_height = match overview.signals.len() {
+            0 => MAX_TRACK_HEIGHT,
+            len => (overview.height / len as f64).min(MAX_TRACK_HEIGHT),
+        };
+        Self {
+            overview,
+            track_height,
+        }
+    }
+
+    /// The top and bottom of the thumbnail of the `i`th track, in pixels from the top of the
+    /// pane.
+    pub fn thumbnail(&self, i: usize) -> [f64; 2] {
+        let top = self.overview.top + i as f64 * self.track_height;
+        [top, top + self.track_height]
+    }
+
+    /// The top and bottom of the mark over the tracks that can be seen, in pixels from the top
+    /// of the pane.
+    pub fn window(&self) -> [f64; 2] {
+        let Overview { top, height, .. } = self.overview;
+        let scale = self.track_height / TRACK_HEIGHT;
+        let bottom = top + self.overview.signals.len() as f64 * self.track_height;
+        let start = top + self.overview.scroll * scale;
+        [start, (start + height * scale).min(bottom)]
+    }
+
+    /// How many tracks down the scrolling region the thumbnails are at `y`, in pixels from the
+    /// top of the pane, or `None` if there isn't one there.
+    pub fn tracks_at(&self, y: f64) -> Option<f64> {
+        let tracks = (y - self.overview.top) / self.track_height;
+        (0.0..self.overview.signals.len() as f64)
+            .contains(&tracks)
+            .then_some(tracks)
+    }
+}
@@ -0,0 +1,93 @@
+//! Draws the tracks of one-bit signals from the tile pool, with `shaders/one_bit.wgsl`: the
+//! fills of unknown regions first, then the levels the signal holds, then the edges between
+//! them.
+
+use std::{borrow::Cow, sync::Arc};
+
+use crate::{
+    render_graph::Pass,
+    scene::Scene,
+    tile_draws::{self, TileDraws},
+    uploads::Uploader,
+};
+
+struct Pipelines {
+    unknown_fill: wgpu::RenderPipeline,
+    horizontal_lines: wgpu::RenderPipeline,
+    vertical_lines: wgpu::RenderPipeline,
+}
+
+pub struct OneBitPass {
+    shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    /// Built by `configure`, since they depend on the sample count.
+    pipelines: Option<Pipelines>,
+    tiles: TileDraws,
+}
+
+impl OneBitPass {
+    /// A pass that reads the tiles it draws from `pool`, the [`TilePool`]'s buffer.
+    ///
+    /// [`TilePool`]: crate::residency::TilePool
+    pub fn new(device: &wgpu::Device, pool: Arc<wgpu::Buffer>) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("one_bit.wgsl"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(concat!(
+                include_str!("shaders/common.wgsl"),
+                include_str!("shaders/tiles.wgsl"),
+                include_str!("shaders/one_bit.wgsl"),
+            ))),
+        });
+        let tiles = TileDraws::new(device, pool);
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[tiles.bind_group_layout()],
+            push_constant_ranges: &[],
+        });
+
+        Self {
+            shader,
+            pipeline_layout,
+            pipelines: None,
+            tiles,
+        }
+    }
+}
+
+impl Pass for OneBitPass {
+    fn configure(&mut self, device: &wgpu::Device, format: wgpu::TextureFormat, sample_count: u32) {
+        let pipeline = |entry_points| {
+            tile_draws::create_pipeline(
+                device,
+                &self.pipeline_layout,
+                &self.shader,
+                entry_points,
+                format,
+                sample_count,
+            )
+        };
+        self.pipelines = Some(Pipelines {
+            unknown_fill: pipeline(("vs_unknown_fill", "fs_unknown_fill")),
+            horizontal_lines: pipeline(("vs_horizontal_lines", "fs_shared")),
+            vertical_lines: pipeline(("vs_vertical_lines", "fs_shared")),
+        });
+    }
+
+    fn prepare(&mut self, uploader: &mut Uploader, scene: &Scene) {
+        self.tiles
+            .prepare(uploader, scene, |pane| pane.bits.as_slice());
+    }
+
+    fn draw<'a>(&'a self, rpass: &mut wgpu::RenderPass<'a>, scene: &Scene) {
+        let pipelines = match &self.pipelines {
+            Some(pipelines) => pipelines,
+            None => return,
+        };
+
+        // One instance for each change.
+        self.tiles.draw(rpass, scene, &pipelines.unknown_fill, 1);
+        self.tiles
+            .draw(rpass, scene, &pipelines.horizontal_lines, 1);
+        self.tiles.draw(rpass, scene, &pipelines.vertical_lines, 1);
+    }
+}
@@ -0,0 +1,124 @@
+//! Draws tiles of signals' changes in outline on a PDF page, the way `shaders/one_bit.wgsl` and
+//! `shaders/bus.wgsl` draw them on the canvas, from the same packed changes. There's no color or
+//! hatching on paper, so unknown values are only told apart by where they're drawn.
+
+use ligeia_core::{logic::Nine, meta::StorageType};
+
+use crate::{
+    pdf::PdfPage,
+    residency::{self, GLITCH, INITIAL},
+    tile_draws::LINE_WIDTH,
+    TilePosition,
+};
+
+/// Like `PADDING` in `shaders/tiles.wgsl`.
+const PADDING: f64 = 4.0;
+/// Like `TRANSITION_WIDTH` in `shaders/bus.wgsl`.
+const TRANSITION_WIDTH: f64 = 4.0;
+
+/// A tile of a track, as packed by [`pack_tile`](residency::pack_tile).
+pub struct Tile<'a> {
+    pub packed: &'a [u8],
+    pub changes: u32,
+    pub ty: StorageType,
+    pub position: TilePosition,
+}
+
+/// Draw `tile` on a track that's `extent` from the top of the page, between the left and right
+/// of its time axis in `span`. One-bit tracks are drawn as levels, and anything else as a bus.
+pub fn draw(page: &mut PdfPage, tile: &Tile, one_bit: bool, extent: [f64; 2], span: [f64; 2]) {
+    let changes: Vec<_> = residency::unpack_tile(tile.packed, tile.changes).collect();
+    let position = tile.position;
+    let clamp = |x: f64| x.clamp(span[0], span[1]);
+    let starts: Vec<_> = changes
+        .iter()
+        .map(|&(word, _)| {
+            let bucket = (word & (INITIAL - 1)) as f64;
+            let x = position.origin[1] + (bucket - position.origin[0]) * position.bucket_width;
+            clamp(x.min(position.end))
+        })
+        .collect();
+    let end_of = |i: usize| {
+        starts
+            .get(i + 1)
+            .copied()
+            .unwrap_or_else(|| clamp(position.end))
+    };
+
+    let padding = PADDING.min((extent[1] - extent[0]) * 0.25);
+    let (top, bottom) = (extent[0] + padding, extent[1] - padding);
+    let middle = (top + bottom) / 2.0;
+    let width = LINE_WIDTH as f64;
+
+    if one_bit {
+        // Ones along the top, zeros along the bottom, and anything else through the middle.
+        let y = |value: &[u8]| match level(tile.ty, value) {
+            0 => bottom,
+            1 => top,
+            _ => middle,
+        };
+        for (i, &(word, value)) in changes.iter().enumerate() {
+            let (start, at) = (starts[i], y(value));
+            page.polyline(&[[start, at], [end_of(i), at]], width);
+
+            // The first change in a tile doesn't have the value before it, and a bucket that had
+            // changes left out could have gone anywhere, so their edges go all the way across.
+            if word & INITIAL == 0 {
+                let (from, to) = if i == 0 || word & GLITCH != 0 {
+                    (bottom, top)
+                } else {
+                    (y(changes[i - 1].1), at)
+                };
+                page.polyline(&[[start, from], [start, to]], width);
+            }
+        }
+        return;
+    }
+
+    // Each value is an elongated hexagon, with the ends left open where the value didn't change:
+    // at the start of the tile, and at the end of the last value in it.
+    for (i, &(word, _)) in changes.iter().enumerate() {
+        let (start, end) = (starts[i], end_of(i));
+        let slant = TRANSITION_WIDTH.min((end - start) / 2.0);
+        let start_slant = match word & INITIAL {
+            0 => slant,
+            _ => 0.0,
+        };
+        let end_slant = match i + 1 < changes.len() {
+            true => slant,
+            false => 0.0,
+        };
+
+        page.polyline(&[[start + start_slant, top], [end - end_slant, top]], width);
+        page.polyline(
+            &[[start + start_slant, bottom], [end - end_slant, bottom]],
+            width,
+        );
+        if start_slant > 0.0 {
+            let slanted = [
+                [start + start_slant, top],
+                [start, middle],
+                [start + start_slant, bottom],
+            ];
+            page.polyline(&slanted, width);
+        }
+        if end_slant > 0.0 {
+            let slanted = [
+                [end - end_slant, top],
+                [end, middle],
+                [end - end_slant, bottom],
+            ];
+            page.polyline(&slanted, width);
+        }
+    }
+}
+
+/// The level of a one-bit value: 0 or 1, 2 for unknown and 3 for high impedance. Weak values
+/// are drawn at the level they pull to, as on the canvas.
+fn level(ty: StorageType, value: &[u8]) -> u8 {
+    match ty {
+        StorageType::TwoLogic => value[0] & 1,
+        StorageType::FourLogic => value[0] & 3,
+        _ => Nine::from_u8(value[0]).map_or(2, Nine::level),
+    }
+}
@@ -0,0 +1,136 @@
+//! The colors traces are drawn in, including palettes that stay distinguishable with the common
+//! kinds of color blindness, and whether unknown values are also hatched so that they don't rely
+//! on color at all.
+
+use std::{fmt, str::FromStr};
+
+/// The colors of the states of state machines, in RGB, which go with any of the palettes. They're
+/// the Okabe-Ito palette, less black, so they stay apart with color blindness too.
+pub const STATE_COLORS: [[f32; 3]; 7] = [
+    [0.9, 0.6, 0.0],
+    [0.34, 0.71, 0.91],
+    [0.0, 0.62, 0.45],
+    [0.94, 0.89, 0.26],
+    [0.0, 0.45, 0.7],
+    [0.84, 0.37, 0.0],
+    [0.8, 0.47, 0.65],
+];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Palette {
+    Standard,
+    /// For red-green color blindness from missing green cones. Unknown and high impedance values
+    /// are orange and blue, from the Okabe-Ito palette.
+    Deuteranopia,
+    /// For red-green color blindness from missing red cones, which also makes reds look dark.
+    /// Unknown values are blue rather than red, and high impedance ones are yellow.
+    Protanopia,
+}
+
+/// The colors of a palette, in RGBA.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Colors {
+    pub background: [f32; 4],
+    /// Lines for known values.
+    pub line: [f32; 4],
+    pub unknown: [f32; 4],
+    pub high_impedance: [f32; 4],
+    /// Shades tracks while the reset is asserted.
+    pub reset: [f32; 4],
+}
+
+impl Default for Colors {
+    fn default() -> Self {
+        Palette::Standard.colors()
+    }
+}
+
+impl Palette {
+    /// The next palette along, for cycling through them from the keyboard.
+    pub fn next(self) -> Self {
+        match self {
+            Palette::Standard => Palette::Deuteranopia,
+            Palette::Deuteranopia => Palette::Protanopia,
+            Palette::Protanopia => Palette::Standard,
+        }
+    }
+
+    pub fn colors(self) -> Colors {
+        match self {
+            Palette::Standard => Colors {
+                background: [0.15, 0.15, 0.25, 1.0],
+                line: [0.0, 0.0, 0.0, 1.0],
+                unknown: [0.9, 0.1, 0.1, 1.0],
+                high_impedance: [0.9, 0.75, 0.1, 1.0],
+                reset: [0.0, 0.0, 0.0, 0.35],
+            },
+            // Light backgrounds keep the darker colors apart from it as well.
+            Palette::Deuteranopia => Colors {
+                background: [0.95, 0.95, 0.95, 1.0],
+                line: [0.0, 0.0, 0.0, 1.0],
+                unknown: [0.9, 0.6, 0.0, 1.0],
+                high_impedance: [0.34, 0.71, 0.91, 1.0],
+                reset: [0.0, 0.0, 0.0, 0.15],
+            },
+            Palette::Protanopia => Colors {
+                background: [0.95, 0.95, 0.95, 1.0],
+                line: [0.0, 0.0, 0.0, 1.0],
+                unknown: [0.0, 0.45, 0.7, 1.0],
+                high_impedance: [0.94, 0.89, 0.26, 1.0],
+                reset: [0.0, 0.0, 0.0, 0.15],
+            },
+        }
+    }
+}
+
+impl FromStr for Palette {
+    type Err = String;
+
+    /// Parses `standard`, `deuteranopia` or `protanopia`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "standard" => Ok(Palette::Standard),
+            "deuteranopia" => Ok(Palette::Deuteranopia),
+            "protanopia" => Ok(Palette::Protanopia),
+            _ => Err(format!("unknown palette `{}`", s)),
+        }
+    }
+}
+
+impl fmt::Display for Palette {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Palette::Standard => write!(f, "standard"),
+            Palette::Deuteranopia => write!(f, "deuteranopia"),
+            Palette::Protanopia => write!(f, "protanopia"),
+        }
+    }
+}
+
+/// Parse a color written like `#ff8000`.
+pub fn parse_color(s: &str) -> Result<[f32; 3], String> {
+    let digits = s
+        .strip_prefix('#')
+        .filter(|digits| digits.len() == 6 && digits.bytes().all(|b| b.is_ascii_hexdigit()))
+        .ok_or_else(|| format!("expected a color like `#ff8000`, not `{}`", s))?;
+    let channel =
+        |i: usize| u8::from_str_radix(&digits[i * 2..i * 2 + 2], 16).unwrap() as f32 / 255.0;
+    Ok([channel(0), channel(1), channel(2)])
+}
+
+/// How traces are colored and patterned.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Theme {
+    pub palette: Palette,
+    /// Whether unknown regions are hatched, on top of being colored.
+    pub hatching: bool,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            palette: Palette::Standard,
+            hatching: false,
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use crate::viewport::Viewport;
+
+/// A horizontal strip of the canvas with its own view of the time axis.
+#[derive(Debug, Copy, Clone)]
+pub struct Pane {
+    pub viewport: Viewport,
+    /// Offset of the top of the pane from the top of the canvas, in pixels.
+    pub top: f64,
+    pub height: f64,
+}
+
+/// The canvas, divided into vertically stacked panes.
+pub struct Panes {
+    panes: Vec<Pane>,
+    /// When set, changing the time axis of one pane changes it for all of them.
+    pub time_locked: bool,
+    height: f64,
+}
+
+impl Panes {
+    pub const MAX: usize = 2;
+
+    pub fn new(viewport: Viewport, height: f64) -> Self {
+        Self {
+            panes: vec![Pane {
+                viewport,
+                top: 0.0,
+                height,
+            }],
+            time_locked: true,
+            height,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Pane> {
+        self.panes.iter()
+    }
+
+    pub fn is_split(&self) -> bool {
+        self.panes.len() > 1
+    }
+
+    /// Split the canvas into two panes showing the same time range, or merge them back into one.
+    pub fn toggle_split(&mut self) {
+        if self.is_split() {
+            self.panes.truncate(1);
+        } else {
+            let first = self.panes[0];
+            self.panes.push(first);
+        }
+        self.layout();
+    }
+
+    pub fn resize(&mut self, width: f64, height: f64) {
+        self.height = height;
+        for pane in &mut self.panes {
+            pane.viewport.width = width;
+        }
+        self.layout();
+    }
+
+    /// Fit every pane to the whole simulation, as given by its first and last timestamps.
+    pub fn zoom_full(&mut self, bounds: (f64, f64)) {
+        for pane in &mut self.panes {
+            pane.viewport.zoom_full(bounds);
+        }
+    }
+
+    /// The index of the pane under the vertical position `y`.
+    pub fn pane_at(&self, y: f64) -> usize {
+        self.panes
+            .iter()
+            .rposition(|pane| y >= pane.top)
+            .unwrap_or(0)
+    }
+
+    pub fn viewport(&self, index: usize) -> &Viewport {
+        &self.panes[index].viewport
+    }
+
+    /// Modify the time axis of a pane, keeping the other panes in sync if they're locked together.
+    pub fn update_viewport<F>(&mut self, index: usize, f: F)
+    where
+        F: FnOnce(&mut Viewport),
+    {
+        f(&mut self.panes[index].viewport);
+
+        if self.time_locked {
+            let Viewport { start, end, .. } = self.panes[index].viewport;
+            for pane in &mut self.panes {
+                pane.viewport.start = start;
+                pane.viewport.end = end;
+            }
+        }
+    }
+
+    fn layout(&mut self) {
+        let height = self.height / self.panes.len() as f64;
+        for (i, pane) in self.panes.iter_mut().enumerate() {
+            pane.top = height * i as f64;
+            pane.height = height;
+        }
+    }
+}
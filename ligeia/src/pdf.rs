@@ -0,0 +1,105 @@
+use std::{
+    fmt::Write as _,
+    io::{self, Write},
+};
+
+/// A single-page vector PDF, built up from stroked paths and text.
+///
+/// Coordinates are in points with the origin at the top-left of the page, to match the canvas.
+pub struct PdfPage {
+    width: f64,
+    height: f64,
+    content: String,
+}
+
+impl PdfPage {
+    pub fn new(width: f64, height: f64) -> Self {
+        Self {
+            width,
+            height,
+            // Round caps and joins, to match the feathered lines on screen.
+            content: String::from("1 J 1 j\n"),
+        }
+    }
+
+    pub fn polyline(&mut self, points: &[[f64; 2]], line_width: f64) {
+        let (&[x, y], rest) = match points.split_first() {
+            Some(split) => split,
+            None => return,
+        };
+
+        // PDF puts the origin at the bottom-left.
+        let _ = writeln!(self.content, "{} w", line_width);
+        let _ = writeln!(self.content, "{:.3} {:.3} m", x, self.height - y);
+        for &[x, y] in rest {
+            let _ = writeln!(self.content, "{:.3} {:.3} l", x, self.height - y);
+        }
+        self.content.push_str("S\n");
+    }
+
+    /// Write a line of text in Helvetica, with the left end of its baseline at `x`, `y`.
+    ///
+    /// Anything outside of printable ASCII is shown as `?`, since that's all the standard fonts
+    /// are sure to have.
+    pub fn text(&mut self, x: f64, y: f64, size: f64, text: &str) {
+        let mut escaped = String::with_capacity(text.len());
+        for c in text.chars() {
+            match c {
+                '(' | ')' | '\\' => {
+                    escaped.push('\\');
+                    escaped.push(c);
+                }
+                ' '..='~' => escaped.push(c),
+                _ => escaped.push('?'),
+            }
+        }
+
+        let _ = writeln!(
+            self.content,
+            "BT /F1 {} Tf {:.3} {:.3} Td ({}) Tj ET",
+            size,
+            x,
+            self.height - y,
+            escaped
+        );
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let objects = [
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.3} {:.3}] /Contents 4 0 R \
+                 /Resources << /Font << /F1 5 0 R >> >> >>",
+                self.width, self.height
+            ),
+            format!(
+                "<< /Length {} >>\nstream\n{}endstream",
+                self.content.len(),
+                self.content
+            ),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>".to_string(),
+        ];
+
+        let mut out = String::from("%PDF-1.4\n");
+        let mut offsets = Vec::with_capacity(objects.len());
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(out.len());
+            let _ = write!(out, "{} 0 obj\n{}\nendobj\n", i + 1, object);
+        }
+
+        let xref_offset = out.len();
+        let _ = write!(out, "xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+        for offset in offsets {
+            let _ = writeln!(out, "{:010} 00000 n ", offset);
+        }
+        let _ = write!(
+            out,
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref_offset
+        );
+
+        writer.write_all(out.as_bytes())
+    }
+}
@@ -0,0 +1,237 @@
+//! Finding which trace is under the cursor, by drawing the id of every trace into an offscreen
+//! `R32Uint` target and reading back the id under a pixel, rather than working out on the CPU
+//! which thick, slanted line a pixel falls in.
+//!
+//! The render thread only redraws the ids every so often, after a frame, and reads them back
+//! without waiting on the GPU. Lookups use the last ids that were read back, so they can lag a
+//! little behind what's on screen.
+
+use std::{
+    num::NonZeroU32,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{render_graph::RenderGraph, scene::Scene, Gpu};
+
+pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+
+/// The ids are redrawn at most this often.
+const INTERVAL: Duration = Duration::from_millis(100);
+/// How often to check whether ids being read back have arrived, when nothing else is drawn.
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// The id a pane's trace is drawn with. Zero is left for where there's no trace.
+pub fn trace_id(pane: usize) -> u32 {
+    pane as u32 + 1
+}
+
+#[derive(Debug, Default)]
+struct Ids {
+    width: u32,
+    height: u32,
+    ids: Vec<u32>,
+}
+
+/// The ids last read back, shared between the render thread and whatever looks things up.
+#[derive(Debug, Default)]
+pub struct Picks {
+    ids: Mutex<Ids>,
+}
+
+impl Picks {
+    /// The pane whose trace is under `(x, y)`, in pixels from the top left of the canvas.
+    pub fn trace_at(&self, (x, y): (f64, f64)) -> Option<usize> {
+        let ids = self.ids.lock().unwrap();
+        if x < 0.0 || y < 0.0 || x >= ids.width as f64 || y >= ids.height as f64 {
+            return None;
+        }
+        let id = ids.ids[y as usize * ids.width as usize + x as usize];
+        id.checked_sub(1).map(|pane| pane as usize)
+    }
+}
+
+/// The target ids are drawn to, and the buffer they're copied into to be read back.
+struct Target {
+    width: u32,
+    height: u32,
+    view: wgpu::TextureView,
+    texture: wgpu::Texture,
+    readback: wgpu::Buffer,
+    /// Bytes per row of `readback`, which are padded out to what copies need.
+    padded_row: u32,
+}
+
+impl Target {
+    fn new(device: &wgpu::Device, width: u32, height: u32) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("picking"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let row = width * 4;
+        let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_row = row + (alignment - row % alignment) % alignment;
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("picking readback"),
+            size: padded_row as u64 * height as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            width,
+            height,
+            view,
+            texture,
+            readback,
+            padded_row,
+        }
+    }
+}
+
+/// Draws and reads back the ids on the render thread.
+pub struct Picker {
+    picks: Arc<Picks>,
+    target: Option<Target>,
+    /// Whether a frame has been drawn since the ids were last drawn.
+    stale: bool,
+    last_drawn: Option<Instant>,
+    /// Whether the ids being read back were mapped, once they have been, or `None` when none
+    /// are being read back.
+    mapped: Option<Arc<Mutex<Option<bool>>>>,
+}
+
+impl Picker {
+    pub fn new(picks: Arc<Picks>) -> Self {
+        Self {
+            picks,
+            target: None,
+            stale: false,
+            last_drawn: None,
+            mapped: None,
+        }
+    }
+
+    /// Note that a frame was drawn, so the ids need drawing again.
+    pub fn frame_drawn(&mut self) {
+        self.stale = true;
+    }
+
+    /// How long the render thread can wait for another scene before there's picking to do, or
+    /// `None` if there's none to do until there's another scene.
+    pub fn next_due(&self) -> Option<Duration> {
+        if self.mapped.is_some() {
+            return Some(POLL_INTERVAL);
+        }
+        if !self.stale {
+            return None;
+        }
+        Some(match self.last_drawn {
+            Some(last) => INTERVAL.saturating_sub(last.elapsed()),
+            None => Duration::ZERO,
+        })
+    }
+
+    /// Collect ids that have been read back, and draw them again for `scene` if it's time to.
+    ///
+    /// `scene` has to be the last one `graph` rendered, since the passes draw ids from what was
+    /// uploaded for it.
+    pub fn update(&mut self, gpu: &Gpu, graph: &RenderGraph, scene: &Scene) {
+        if self.mapped.is_some() {
+            gpu.device.poll(wgpu::Maintain::Poll);
+            self.collect();
+        }
+        if self.mapped.is_some() || self.next_due() != Some(Duration::ZERO) {
+            return;
+        }
+        if scene.width == 0 || scene.height == 0 {
+            return;
+        }
+
+        let resized = match &self.target {
+            Some(target) => (target.width, target.height) != (scene.width, scene.height),
+            None => true,
+        };
+        if resized {
+            self.target = Some(Target::new(&gpu.device, scene.width, scene.height));
+        }
+        let target = self.target.as_ref().unwrap();
+
+        let mut encoder = graph.pick(&gpu.device, &target.view, scene);
+        encoder.copy_texture_to_buffer(
+            target.texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &target.readback,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(target.padded_row),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: target.width,
+                height: target.height,
+                depth_or_array_layers: 1,
+            },
+        );
+        gpu.queue.submit([encoder.finish()]);
+
+        let mapped = Arc::new(Mutex::new(None));
+        let done = mapped.clone();
+        target
+            .readback
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                *done.lock().unwrap() = Some(result.is_ok());
+            });
+        self.mapped = Some(mapped);
+        self.stale = false;
+        self.last_drawn = Some(Instant::now());
+    }
+
+    /// Copy the ids out of the readback buffer, if they've arrived.
+    fn collect(&mut self) {
+        let mapped = match &self.mapped {
+            Some(mapped) => *mapped.lock().unwrap(),
+            None => return,
+        };
+        let target = match (mapped, &self.target) {
+            (Some(true), Some(target)) => target,
+            (None, _) => return,
+            // A failed mapping leaves the old ids in place, to be drawn again next time.
+            _ => {
+                self.mapped = None;
+                self.stale = true;
+                return;
+            }
+        };
+
+        {
+            let data = target.readback.slice(..).get_mapped_range();
+            let mut ids = self.picks.ids.lock().unwrap();
+            ids.width = target.width;
+            ids.height = target.height;
+            ids.ids.clear();
+            for row in data.chunks_exact(target.padded_row as usize) {
+                let row = &row[..target.width as usize * 4];
+                ids.ids.extend(
+                    row.chunks_exact(4)
+                        .map(|id| u32::from_le_bytes(id.try_into().unwrap())),
+                );
+            }
+        }
+        target.readback.unmap();
+        self.mapped = None;
+    }
+}
@@ -0,0 +1,172 @@
+//! The render thread, which draws the scenes the event loop publishes.
+//!
+//! Uploads and drawing happen here rather than on the event loop, so however long a frame
+//! takes, input is still handled in the meantime.
+
+use std::{
+    sync::Arc,
+    thread::{self, JoinHandle},
+};
+
+use crate::{
+    picking::{Picker, Picks},
+    render_graph::{Canvas, RenderGraph},
+    scene::{Scene, SceneExchange, Taken},
+    uploads::Uploads,
+    Gpu,
+};
+
+fn create_msaa_frambuffer(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    sample_count: u32,
+) -> wgpu::TextureView {
+    let multisampled_texture_extent = wgpu::Extent3d {
+        width: config.width,
+        height: config.height,
+        depth_or_array_layers: 1,
+    };
+    let multisampled_frame_descriptor = &wgpu::TextureDescriptor {
+        size: multisampled_texture_extent,
+        mip_level_count: 1,
+        sample_count,
+        dimension: wgpu::TextureDimension::D2,
+        format: config.format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        label: None,
+    };
+
+    device
+        .create_texture(multisampled_frame_descriptor)
+        .create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+struct Renderer {
+    gpu: Gpu,
+    config: wgpu::SurfaceConfiguration,
+    graph: RenderGraph,
+    uploads: Uploads,
+    sample_count: u32,
+    /// Only there when multisampling.
+    msaa_framebuffer: Option<wgpu::TextureView>,
+    picker: Picker,
+}
+
+impl Renderer {
+    /// Bring the surface and passes up to date with how `scene` is to be drawn.
+    fn reconfigure(&mut self, scene: &Scene) {
+        let resized = (scene.width, scene.height) != (self.config.width, self.config.height);
+        if resized {
+            self.config.width = scene.width;
+            self.config.height = scene.height;
+            self.gpu.surface.configure(&self.gpu.device, &self.config);
+        }
+
+        let resampled = scene.sample_count != self.sample_count;
+        if resampled {
+            self.sample_count = scene.sample_count;
+            self.graph
+                .configure(&self.gpu.device, self.config.format, self.sample_count);
+        }
+
+        if resized || resampled {
+            self.msaa_framebuffer = match self.sample_count {
+                1 => None,
+                samples => Some(create_msaa_frambuffer(
+                    &self.gpu.device,
+                    &self.config,
+                    samples,
+                )),
+            };
+        }
+    }
+
+    fn draw(&mut self, scene: &Scene) {
+        // Minimized windows have nothing to draw to.
+        if scene.width == 0 || scene.height == 0 {
+            return;
+        }
+        self.reconfigure(scene);
+
+        let frame = match self.gpu.surface.get_current_texture() {
+            Ok(frame) => frame,
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                // The next scene will be drawn to it once it's configured again.
+                self.gpu.surface.configure(&self.gpu.device, &self.config);
+                return;
+            }
+            Err(e) => {
+                eprintln!("failed to get a frame to draw to: {}", e);
+                return;
+            }
+        };
+        let view = frame
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        let commands = self.graph.render(
+            &self.gpu.device,
+            &mut self.uploads,
+            Canvas {
+                view: &view,
+                multisampled: self.msaa_framebuffer.as_ref(),
+            },
+            scene,
+        );
+        self.uploads
+            .submit(&self.gpu.device, &self.gpu.queue, commands);
+        frame.present();
+        self.picker.frame_drawn();
+    }
+
+    /// Bring the ids for picking up to date with `scene`, the last one drawn, if it's time to.
+    fn pick(&mut self, scene: &Scene) {
+        self.picker.update(&self.gpu, &self.graph, scene);
+    }
+}
+
+/// Start drawing scenes from `scenes` onto the surface, until the exchange is closed, and
+/// keeping `picks` up to date with what was drawn.
+///
+/// The surface isn't configured until the first scene says how big it is.
+pub fn spawn(
+    gpu: Gpu,
+    format: wgpu::TextureFormat,
+    graph: RenderGraph,
+    scenes: Arc<SceneExchange>,
+    picks: Arc<Picks>,
+) -> JoinHandle<()> {
+    let config = wgpu::SurfaceConfiguration {
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        format,
+        width: 0,
+        height: 0,
+        present_mode: wgpu::PresentMode::Fifo,
+    };
+    let mut renderer = Renderer {
+        gpu,
+        config,
+        graph,
+        uploads: Uploads::new(),
+        // Nothing is configured for any sample count yet.
+        sample_count: 0,
+        msaa_framebuffer: None,
+        picker: Picker::new(picks),
+    };
+
+    thread::Builder::new()
+        .name("render".to_string())
+        .spawn(move || {
+            let mut scene = Scene::default();
+            loop {
+                // Ids for picking are due a while after a frame, even if no more scenes come.
+                match scenes.take(&mut scene, renderer.picker.next_due()) {
+                    Taken::Scene => renderer.draw(&scene),
+                    Taken::TimedOut => {}
+                    Taken::Closed => break,
+                }
+                renderer.pick(&scene);
+            }
+        })
+        .expect("failed to start the render thread")
+}
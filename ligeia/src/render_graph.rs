@@ -0,0 +1,155 @@
+//! The passes that make up a frame, and the order they're drawn in.
+//!
+//! Every pass draws onto the canvas in turn, over whatever the passes before it drew. The graph
+//! takes care of the attachments: the first pass clears the canvas to the scene's background,
+//! and with multisampling, the last one resolves it to the frame.
+//!
+//! Passes can also draw ids for [picking](crate::picking), into a target of their own.
+
+use crate::{
+    scene::Scene,
+    uploads::{Uploader, Uploads},
+};
+
+/// Something drawn onto the canvas each frame, like the traces, text, or overlays.
+pub trait Pass: Send {
+    /// Rebuild anything that depends on how the canvas is drawn, like pipelines.
+    fn configure(&mut self, device: &wgpu::Device, format: wgpu::TextureFormat, sample_count: u32);
+
+    /// Upload whatever the pass needs from the scene, before any pass is recorded.
+    fn prepare(&mut self, uploader: &mut Uploader, scene: &Scene);
+
+    fn draw<'a>(&'a self, rpass: &mut wgpu::RenderPass<'a>, scene: &Scene);
+
+    /// Draw the ids of whatever can be picked, in [`picking::FORMAT`](crate::picking::FORMAT),
+    /// from what was uploaded for the last frame. Most passes have nothing to pick.
+    fn pick<'a>(&'a self, _rpass: &mut wgpu::RenderPass<'a>, _scene: &Scene) {}
+}
+
+/// Where a frame is drawn.
+pub struct Canvas<'a> {
+    /// The frame being drawn.
+    pub view: &'a wgpu::TextureView,
+    /// Drawn to instead of the frame when multisampling, and resolved to it at the end.
+    pub multisampled: Option<&'a wgpu::TextureView>,
+}
+
+pub struct RenderGraph {
+    passes: Vec<(&'static str, Box<dyn Pass>)>,
+}
+
+impl RenderGraph {
+    pub fn new() -> Self {
+        Self { passes: vec![] }
+    }
+
+    /// Add a pass, to be drawn over the ones already added.
+    pub fn add(&mut self, name: &'static str, pass: impl Pass + 'static) {
+        self.passes.push((name, Box::new(pass)));
+    }
+
+    pub fn configure(
+        &mut self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) {
+        for (_, pass) in &mut self.passes {
+            pass.configure(device, format, sample_count);
+        }
+    }
+
+    /// Prepare every pass, and record them all, in order.
+    ///
+    /// The uploads are copied at the start of the returned commands, so they have to be
+    /// submitted with [`Uploads::submit`].
+    pub fn render(
+        &mut self,
+        device: &wgpu::Device,
+        uploads: &mut Uploads,
+        canvas: Canvas,
+        scene: &Scene,
+    ) -> wgpu::CommandBuffer {
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+        let mut uploader = uploads.uploader(device, &mut encoder);
+        for (_, pass) in &mut self.passes {
+            pass.prepare(&mut uploader, scene);
+        }
+
+        let [r, g, b, a] = scene.colors.background;
+        let background = wgpu::Color {
+            r: r as f64,
+            g: g as f64,
+            b: b as f64,
+            a: a as f64,
+        };
+        // Even with nothing to draw, the canvas still has to be cleared.
+        let count = self.passes.len().max(1);
+        for i in 0..count {
+            let last = i + 1 == count;
+            let load = match i {
+                0 => wgpu::LoadOp::Clear(background),
+                _ => wgpu::LoadOp::Load,
+            };
+            let color_attachment = match canvas.multisampled {
+                None => wgpu::RenderPassColorAttachment {
+                    view: canvas.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations { load, store: true },
+                },
+                Some(multisampled) => wgpu::RenderPassColorAttachment {
+                    view: multisampled,
+                    resolve_target: last.then_some(canvas.view),
+                    // Once it's resolved, the multisampled canvas isn't needed again, and not
+                    // storing it saves memory bandwidth on tile-based GPUs.
+                    ops: wgpu::Operations { load, store: !last },
+                },
+            };
+
+            let pass = self.passes.get(i);
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: pass.map(|&(name, _)| name),
+                color_attachments: &[Some(color_attachment)],
+                depth_stencil_attachment: None,
+            });
+            if let Some((_, pass)) = pass {
+                pass.draw(&mut rpass, scene);
+            }
+        }
+
+        encoder.finish()
+    }
+
+    /// Record every pass drawing ids onto `target`, which is cleared to zero first, for the
+    /// scene that was last rendered. The returned encoder can have more recorded onto it, like
+    /// reading the ids back.
+    pub fn pick(
+        &self,
+        device: &wgpu::Device,
+        target: &wgpu::TextureView,
+        scene: &Scene,
+    ) -> wgpu::CommandEncoder {
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        {
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("picking"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: target,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            for (_, pass) in &self.passes {
+                pass.pick(&mut rpass, scene);
+            }
+        }
+        encoder
+    }
+}
@@ -0,0 +1,362 @@
+//! Which signal data is on the GPU, for generating vertices there.
+//!
+//! Signals are cut into tiles: spans of time at a level of detail, where level `lod` divides
+//! time into buckets of `2^lod` timesteps and a tile covers [`TILE_BUCKETS`] of them. The
+//! changes in a tile are packed and uploaded into one big pool buffer, where they stay until
+//! the pool is full and they've gone the longest without being drawn. Zooming and panning only
+//! uploads tiles that weren't already there.
+//!
+//! The bookkeeping happens alongside the waveform, on the event loop, and the uploads it decides
+//! on go to the render thread in the scene.
+
+use std::{collections::HashMap, ops::Range, sync::Arc};
+
+use ligeia_core::{
+    logic::Nine,
+    meta::{StorageId, StorageType, Timesteps},
+    Error, Processed,
+};
+
+use crate::{
+    render_graph::Pass,
+    scene::{Scene, TileUpload},
+    uploads::Uploader,
+};
+
+/// How many buckets of time a tile covers, whatever its level of detail.
+pub const TILE_BUCKETS: u64 = 1024;
+/// How big the pool of resident tiles is, in bytes.
+pub const POOL_SIZE: u64 = 64 << 20;
+/// Set on a packed change's bucket when the bucket had more changes than the one kept.
+pub const GLITCH: u32 = 1 << 31;
+/// Set on a packed change's bucket when any bit of the value kept is unknown or high impedance.
+pub const UNKNOWN: u32 = 1 << 30;
+/// Set on a packed change's bucket when a change that was left out of it had unknown or high
+/// impedance bits, so that they still show when zoomed out.
+pub const DROPPED_UNKNOWN: u32 = 1 << 29;
+/// Set on the first change in a tile when it's only the value the storage already had.
+pub const INITIAL: u32 = 1 << 28;
+
+/// A span of a signal's changes at a level of detail.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct TileKey {
+    pub storage: StorageId,
+    pub lod: u32,
+    pub index: u64,
+}
+
+impl TileKey {
+    /// The times the tile covers.
+    pub fn range(&self) -> Range<Timesteps> {
+        let span = TILE_BUCKETS << self.lod;
+        let start = self.index.saturating_mul(span);
+        Timesteps(start)..Timesteps(start.saturating_add(span))
+    }
+}
+
+/// The level of detail to draw at, when a pixel covers `timesteps_per_pixel` timesteps: the
+/// finest one whose buckets are at least a pixel wide.
+pub fn lod_for(timesteps_per_pixel: f64) -> u32 {
+    if timesteps_per_pixel <= 1.0 {
+        return 0;
+    }
+    // Tiles can't span more than every timestep there is.
+    (timesteps_per_pixel.log2().ceil() as u32).min(63 - TILE_BUCKETS.trailing_zeros())
+}
+
+/// The tiles of a storage that cover `range` at a level of detail.
+pub fn tiles_covering(
+    storage: StorageId,
+    lod: u32,
+    range: Range<Timesteps>,
+) -> impl Iterator<Item = TileKey> {
+    let span = TILE_BUCKETS << lod;
+    let first = range.start.0 / span;
+    let last = match range.end.0 {
+        0 => 0,
+        end => (end - 1) / span + 1,
+    };
+    (first..last.max(first + 1)).map(move |index| TileKey {
+        storage,
+        lod,
+        index,
+    })
+}
+
+/// Whether any bit of a value is unknown or high impedance.
+fn has_unknown(ty: StorageType, width: u32, data: &[u8]) -> bool {
+    match ty {
+        // The high bit of each two-bit value is set for `x` and `z`.
+        StorageType::FourLogic => {
+            (0..width as usize).any(|i| (data[i / 4] >> (i % 4 * 2)) & 2 != 0)
+        }
+        StorageType::NineLogic => data[..width as usize]
+            .iter()
+            .any(|&value| Nine::from_u8(value).map_or(true, |value| value.level() > 1)),
+        StorageType::TwoLogic | StorageType::Utf8 | StorageType::Event => false,
+    }
+}
+
+/// Pack the changes in a tile for the GPU, returning them and how many there are.
+///
+/// Each change is a little-endian `u32` bucket within the tile, with flags in its top bits,
+/// followed by the value padded to a multiple of four bytes. Only the last change in each bucket
+/// is kept, so a tile never holds more than [`TILE_BUCKETS`] of them. [`GLITCH`] is set if the
+/// bucket had other changes that were left out, and [`DROPPED_UNKNOWN`] if any of those were
+/// unknown, so that unknown values win out over known ones when zoomed out.
+///
+/// The value the storage had when the tile starts is in bucket zero, with [`INITIAL`] set,
+/// unless a change in that bucket replaced it.
+pub fn pack_tile(processed: &mut Processed, key: TileKey) -> Result<(Vec<u8>, u32), Error> {
+    let storage = processed.storage(key.storage)?;
+    let (ty, width) = (storage.ty, storage.width);
+    let range = key.range();
+    let mut packed: Vec<u8> = vec![];
+    let mut changes = 0;
+    // The bucket of the last change packed, and whether it was within the tile.
+    let mut last: Option<(u32, bool)> = None;
+
+    processed.changes_in_range_with_initial(key.storage, range.clone(), |timestamp, data| {
+        let within = timestamp >= range.start;
+        let bucket = ((timestamp.0.max(range.start.0) - range.start.0) >> key.lod) as u32;
+        // Every change to a storage is the same length.
+        let stride = 4 + ((data.len() + 3) & !3);
+
+        let mut flags = match within {
+            true => 0,
+            false => INITIAL,
+        };
+        if has_unknown(ty, width, data) {
+            flags |= UNKNOWN;
+        }
+        if let Some((last_bucket, last_within)) = last {
+            if last_bucket == bucket {
+                // Only the last change in a bucket is kept. The value before the tile only
+                // counts if it lasted into it.
+                let start = packed.len() - stride;
+                let dropped = u32::from_le_bytes(packed[start..start + 4].try_into().unwrap());
+                packed.truncate(start);
+                changes -= 1;
+                if last_within {
+                    flags |= GLITCH;
+                }
+                if dropped & (UNKNOWN | DROPPED_UNKNOWN) != 0 && timestamp > range.start {
+                    flags |= DROPPED_UNKNOWN;
+                }
+            }
+        }
+        last = Some((bucket, within));
+
+        packed.extend_from_slice(&(bucket | flags).to_le_bytes());
+        let start = packed.len();
+        packed.extend_from_slice(data);
+        packed.resize(start + stride - 4, 0);
+        changes += 1;
+    })?;
+
+    Ok((packed, changes))
+}
+
+/// The changes in a tile packed by [`pack_tile`], as their buckets, with flags, and values.
+pub fn unpack_tile(packed: &[u8], changes: u32) -> impl Iterator<Item = (u32, &[u8])> {
+    let stride = match changes {
+        0 => 1,
+        changes => packed.len() / changes as usize,
+    };
+    packed
+        .chunks_exact(stride)
+        .take(changes as usize)
+        .map(|change| {
+            let (word, value) = change.split_at(4);
+            (u32::from_le_bytes(word.try_into().unwrap()), value)
+        })
+}
+
+/// First-fit allocation of ranges of the pool.
+struct Allocator {
+    /// Unallocated ranges, in order and never touching.
+    free: Vec<Range<u64>>,
+}
+
+impl Allocator {
+    fn new(size: u64) -> Self {
+        Self {
+            free: vec![Range {
+                start: 0,
+                end: size,
+            }],
+        }
+    }
+
+    fn allocate(&mut self, len: u64) -> Option<Range<u64>> {
+        let index = self
+            .free
+            .iter()
+            .position(|range| range.end - range.start >= len)?;
+        let range = &mut self.free[index];
+        let allocated = range.start..range.start + len;
+        range.start += len;
+        if range.start == range.end {
+            self.free.remove(index);
+        }
+        Some(allocated)
+    }
+
+    fn free(&mut self, range: Range<u64>) {
+        if range.start == range.end {
+            return;
+        }
+        let index = self.free.partition_point(|free| free.start < range.start);
+        let joins_before = index > 0 && self.free[index - 1].end == range.start;
+        let joins_after = index < self.free.len() && self.free[index].start == range.end;
+        match (joins_before, joins_after) {
+            (true, true) => {
+                self.free[index - 1].end = self.free[index].end;
+                self.free.remove(index);
+            }
+            (true, false) => self.free[index - 1].end = range.end,
+            (false, true) => self.free[index].start = range.start,
+            (false, false) => self.free.insert(index, range),
+        }
+    }
+}
+
+/// Where a tile is in the pool.
+#[derive(Debug, Clone)]
+pub struct Placement {
+    /// In bytes.
+    pub range: Range<u64>,
+    /// How many changes are packed into it, each taking the same share of the range.
+    pub changes: u32,
+}
+
+struct Resident {
+    placement: Placement,
+    /// The last frame the tile was needed for.
+    last_used: u64,
+}
+
+/// Keeps track of which tiles are in the pool, and decides which to upload and evict.
+pub struct Residency {
+    allocator: Allocator,
+    tiles: HashMap<TileKey, Resident>,
+    frame: u64,
+}
+
+impl Residency {
+    pub fn new() -> Self {
+        Self {
+            allocator: Allocator::new(POOL_SIZE),
+            tiles: HashMap::new(),
+            frame: 0,
+        }
+    }
+
+    /// Forget every tile, like when the waveform they came from is replaced.
+    ///
+    /// The pool isn't cleared, but whatever's left in it is overwritten before it's used again.
+    pub fn clear(&mut self) {
+        self.allocator = Allocator::new(POOL_SIZE);
+        self.tiles.clear();
+    }
+
+    /// Start working out the tiles needed for another frame. Tiles needed for this one won't be
+    /// evicted until the next.
+    pub fn begin_frame(&mut self) {
+        self.frame += 1;
+    }
+
+    /// Where a tile is in the pool, packing it and adding an upload to `uploads` if it isn't
+    /// there already.
+    ///
+    /// Returns `None` if there's no room for it, even after evicting every tile that isn't
+    /// needed for this frame.
+    pub fn require(
+        &mut self,
+        processed: &mut Processed,
+        key: TileKey,
+        uploads: &mut Vec<TileUpload>,
+    ) -> Result<Option<Placement>, Error> {
+        if let Some(resident) = self.tiles.get_mut(&key) {
+            resident.last_used = self.frame;
+            return Ok(Some(resident.placement.clone()));
+        }
+
+        let (data, changes) = pack_tile(processed, key)?;
+        let range = match self.allocate(data.len() as u64) {
+            Some(range) => range,
+            None => return Ok(None),
+        };
+        uploads.push(TileUpload {
+            offset: range.start,
+            data,
+        });
+        let placement = Placement { range, changes };
+        self.tiles.insert(
+            key,
+            Resident {
+                placement: placement.clone(),
+                last_used: self.frame,
+            },
+        );
+        Ok(Some(placement))
+    }
+
+    /// Allocate room in the pool, evicting the tiles that were drawn longest ago until there is.
+    fn allocate(&mut self, len: u64) -> Option<Range<u64>> {
+        if len == 0 {
+            return Some(0..0);
+        }
+        loop {
+            if let Some(range) = self.allocator.allocate(len) {
+                return Some(range);
+            }
+
+            let (&key, _) = self
+                .tiles
+                .iter()
+                .filter(|(_, resident)| resident.last_used < self.frame)
+                .min_by_key(|(_, resident)| resident.last_used)?;
+            let evicted = self.tiles.remove(&key).unwrap();
+            self.allocator.free(evicted.placement.range);
+        }
+    }
+}
+
+/// Owns the pool on the GPU, and writes the scene's tile uploads into it.
+///
+/// It doesn't draw anything itself; passes that generate vertices from signal data read from
+/// the pool, which they're given with [`TilePool::buffer`].
+pub struct TilePool {
+    pool: Arc<wgpu::Buffer>,
+}
+
+impl TilePool {
+    pub fn new(device: &wgpu::Device) -> Self {
+        Self {
+            pool: Arc::new(device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("tile pool"),
+                size: POOL_SIZE,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })),
+        }
+    }
+
+    /// The pool, for passes to bind.
+    pub fn buffer(&self) -> Arc<wgpu::Buffer> {
+        self.pool.clone()
+    }
+}
+
+impl Pass for TilePool {
+    fn configure(&mut self, _: &wgpu::Device, _: wgpu::TextureFormat, _: u32) {}
+
+    fn prepare(&mut self, uploader: &mut Uploader, scene: &Scene) {
+        for upload in &scene.uploads {
+            uploader.write(&self.pool, upload.offset, &upload.data);
+        }
+    }
+
+    fn draw<'a>(&'a self, _: &mut wgpu::RenderPass<'a>, _: &Scene) {}
+}
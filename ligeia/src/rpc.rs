@@ -0,0 +1,100 @@
+//! A line-delimited JSON-RPC 2.0 server, so that editors and simulators can drive the viewer.
+//!
+//! Each line sent to the server is a request such as
+//! `{"jsonrpc": "2.0", "id": 1, "method": "goto_time", "params": {"time": 1000}}`,
+//! and each request is answered with a single line.
+
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::mpsc,
+    thread,
+};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use winit::event_loop::EventLoopProxy;
+
+use crate::commands::{Command, RemoteCommand, UserEvent};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const SERVER_ERROR: i64 = -32000;
+
+#[derive(Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+/// Start accepting connections on a background thread.
+pub fn serve<A: ToSocketAddrs>(addr: A, proxy: EventLoopProxy<UserEvent>) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    eprintln!("listening for rpc on {}", listener.local_addr()?);
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let proxy = proxy.clone();
+            thread::spawn(move || {
+                if let Err(e) = handle(stream, proxy) {
+                    eprintln!("rpc connection failed: {}", e);
+                }
+            });
+        }
+    });
+
+    Ok(())
+}
+
+fn handle(stream: TcpStream, proxy: EventLoopProxy<UserEvent>) -> io::Result<()> {
+    let reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => {
+                let result = Command::from_method(&request.method, &request.params)
+                    .map_err(|e| (INVALID_REQUEST, e))
+                    .and_then(|command| call(&proxy, command).map_err(|e| (SERVER_ERROR, e)));
+
+                match result {
+                    Ok(()) => json!({ "jsonrpc": "2.0", "id": request.id, "result": null }),
+                    Err((code, message)) => error(request.id, code, message),
+                }
+            }
+            Err(e) => error(Value::Null, PARSE_ERROR, e.to_string()),
+        };
+
+        writeln!(writer, "{}", response)?;
+    }
+
+    Ok(())
+}
+
+/// Run a command on the event loop thread and wait for its outcome.
+fn call(proxy: &EventLoopProxy<UserEvent>, command: Command) -> Result<(), String> {
+    let (reply, outcome) = mpsc::channel();
+    proxy
+        .send_event(UserEvent::Remote(RemoteCommand { command, reply }))
+        .map_err(|_| "the viewer is shutting down".to_string())?;
+
+    outcome
+        .recv()
+        .map_err(|_| "the viewer is shutting down".to_string())?
+}
+
+fn error(id: Value, code: i64, message: String) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
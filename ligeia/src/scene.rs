@@ -0,0 +1,232 @@
+//! What the event loop wants drawn, handed over to the render thread.
+
+use std::{
+    mem,
+    sync::{Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+use ligeia_core::meta::StorageType;
+
+use crate::{
+    antialiasing::Antialiasing,
+    palette::{Colors, Theme},
+    panes::Panes,
+};
+
+/// A pane, as it should be drawn.
+#[derive(Debug, Default)]
+pub struct PaneScene {
+    /// Offset of the top of the pane from the top of the canvas, in pixels.
+    pub top: f32,
+    pub height: f32,
+    /// The trace, in pixels from the middle of the pane.
+    pub points: Vec<[f32; 2]>,
+    /// Where highlighting rules hold, and other shading, drawn under the trace in order.
+    pub tints: Vec<Tint>,
+    /// The tracks of one-bit signals, a tile at a time.
+    pub bits: Vec<TileDraw>,
+    /// The tracks of buses and strings, a tile at a time.
+    pub buses: Vec<TileDraw>,
+    /// Text drawn over everything else, in order.
+    pub labels: Vec<Label>,
+}
+
+/// A span of a pane tinted by a [highlighting rule](crate::traces::Highlight), or some other
+/// shading of the time within it.
+#[derive(Debug, Clone, Copy)]
+pub struct Tint {
+    /// From and to, in pixels from the left of the pane.
+    pub span: [f32; 2],
+    /// Top and bottom, in pixels from the top of the pane, for tints that only cover a track.
+    pub extent: [f32; 2],
+    /// RGBA.
+    pub color: [f32; 4],
+}
+
+/// Text on the canvas, like a value written on its track.
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub text: String,
+    /// The top left corner of the text, in pixels from the top left of the pane.
+    pub position: [f32; 2],
+    /// RGBA.
+    pub color: [f32; 4],
+    /// The color of a box behind the text, if it has one.
+    pub background: Option<[f32; 4]>,
+}
+
+/// A tile of a signal's changes, drawn on its track straight out of the tile pool. See
+/// [`pack_tile`](crate::residency::pack_tile) for how it's packed.
+#[derive(Debug, Clone, Copy)]
+pub struct TileDraw {
+    /// Where the tile starts in the pool, in bytes.
+    pub offset: u64,
+    pub changes: u32,
+    /// How far apart its changes are, in bytes.
+    pub stride: u32,
+    pub ty: StorageType,
+    /// A bucket of the tile near the pane, and where it starts, in pixels from the left of the
+    /// pane.
+    pub origin: [f32; 2],
+    /// How wide each bucket is, in pixels.
+    pub bucket_width: f32,
+    /// Where the last change stops being drawn, at the end of the tile or of the waveform.
+    pub end: f32,
+    /// The top and bottom of the track, in pixels from the top of the pane.
+    pub extent: [f32; 2],
+    /// The left, top, right and bottom of the part of the track that can be seen, in pixels from
+    /// the top left of the pane. It's less than all of the track while it's scrolled partly out
+    /// of its region, and stops short of the minimap.
+    pub clip: [f32; 4],
+}
+
+/// Signal data to write into the tile pool. See [`Residency`](crate::residency::Residency).
+#[derive(Debug)]
+pub struct TileUpload {
+    /// Where in the pool it goes.
+    pub offset: u64,
+    pub data: Vec<u8>,
+}
+
+/// Everything the render thread needs to draw a frame, so that it never has to look at the view
+/// state itself.
+#[derive(Debug, Default)]
+pub struct Scene {
+    pub width: u32,
+    pub height: u32,
+    /// How many samples per pixel to draw with.
+    pub sample_count: u32,
+    /// The fraction of each side of a line that the line shader fades out.
+    pub feather_fraction: f32,
+    pub colors: Colors,
+    /// Whether unknown regions are hatched as well as colored.
+    pub hatching: bool,
+    pub panes: Vec<PaneScene>,
+    /// Written to the tile pool before anything is drawn, in order.
+    pub uploads: Vec<TileUpload>,
+}
+
+impl Scene {
+    /// Describe the view, reusing the allocations of whatever scene this was before.
+    pub fn describe(
+        &mut self,
+        (width, height): (u32, u32),
+        antialiasing: Antialiasing,
+        theme: Theme,
+        panes: &Panes,
+        points: &[[f32; 2]],
+    ) {
+        self.width = width;
+        self.height = height;
+        self.sample_count = antialiasing.sample_count();
+        self.feather_fraction = antialiasing.feather_fraction();
+        self.colors = theme.palette.colors();
+        self.hatching = theme.hatching;
+        self.uploads.clear();
+
+        let count = panes.iter().count();
+        self.panes.truncate(count);
+        self.panes.resize_with(count, PaneScene::default);
+        for (pane, scene) in panes.iter().zip(&mut self.panes) {
+            scene.top = pane.top as f32;
+            scene.height = pane.height as f32;
+            scene.points.clear();
+            scene.tints.clear();
+            scene.bits.clear();
+            scene.buses.clear();
+            scene.labels.clear();
+            scene.points.extend(
+                points
+                    .iter()
+                    .map(|&[t, y]| [pane.viewport.x_at(t as f64) as f32, y]),
+            );
+        }
+    }
+}
+
+struct Slot {
+    scene: Scene,
+    /// Whether `scene` was published since the render thread last took one.
+    fresh: bool,
+    closed: bool,
+}
+
+/// What happened while waiting for a scene.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Taken {
+    Scene,
+    TimedOut,
+    Closed,
+}
+
+/// Passes scenes from the event loop to the render thread.
+///
+/// The event loop fills in one scene while the render thread draws another. Publishing swaps the
+/// finished one into a slot, replacing any the render thread hasn't gotten to, so a slow frame
+/// means skipping scenes rather than falling behind on them. Scenes are swapped rather than moved,
+/// so their allocations go around and around.
+pub struct SceneExchange {
+    slot: Mutex<Slot>,
+    published: Condvar,
+}
+
+impl SceneExchange {
+    pub fn new() -> Self {
+        Self {
+            slot: Mutex::new(Slot {
+                scene: Scene::default(),
+                fresh: false,
+                closed: false,
+            }),
+            published: Condvar::new(),
+        }
+    }
+
+    /// Hand `scene` over to be drawn, leaving an old one in its place to be described anew.
+    pub fn publish(&self, scene: &mut Scene) {
+        let mut slot = self.slot.lock().unwrap();
+        if slot.fresh {
+            // The scene being replaced was never drawn, but the residency bookkeeping assumes its
+            // uploads happened, so they go first in this one.
+            let skipped = mem::take(&mut slot.scene.uploads);
+            scene.uploads.splice(0..0, skipped);
+        }
+        mem::swap(&mut slot.scene, scene);
+        slot.fresh = true;
+        self.published.notify_one();
+    }
+
+    /// Wait for a scene to be published and swap it into `scene`, for up to `timeout` if
+    /// there is one.
+    ///
+    /// `scene` is left alone unless one was taken.
+    pub fn take(&self, scene: &mut Scene, timeout: Option<Duration>) -> Taken {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut slot = self.slot.lock().unwrap();
+        while !slot.fresh && !slot.closed {
+            slot = match deadline {
+                None => self.published.wait(slot).unwrap(),
+                Some(deadline) => {
+                    let left = deadline.saturating_duration_since(Instant::now());
+                    if left.is_zero() {
+                        return Taken::TimedOut;
+                    }
+                    self.published.wait_timeout(slot, left).unwrap().0
+                }
+            };
+        }
+        if slot.closed {
+            return Taken::Closed;
+        }
+        mem::swap(&mut slot.scene, scene);
+        slot.fresh = false;
+        Taken::Scene
+    }
+
+    /// Stop the render thread once it's done with the frame it's on.
+    pub fn close(&self) {
+        self.slot.lock().unwrap().closed = true;
+        self.published.notify_one();
+    }
+}
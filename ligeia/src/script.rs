@@ -0,0 +1,866 @@
+//! Lua bindings over the waveform database, for automating waveform triage.
+//!
+//! Scripts get a global `ligeia` table:
+//!
+//! ```lua
+//! local wave = ligeia.open("dump.vcd")
+//! local first, last = wave:time_bounds()
+//! for _, id in ipairs(wave:storages()) do
+//!     print(id, wave:change_count(id), wave:value_at(id, last))
+//! end
+//! print(wave:value_at(0, last, "hex"))
+//! print(wave:value_at(0, last, { radix = "hex", group = 4, prefix = true, trim = true }))
+//! print(wave:value_at(0, last, { radix = "dec", group = 3, separator = "," }))
+//!
+//! -- Like `1.5 ns`, with the separators of the locale in `LC_NUMERIC`, or of the one given.
+//! print(wave:format_time(last))
+//! print(wave:format_time(last, "de_DE"))
+//! wave:export_csv(0, "storage0.csv")
+//! wave:export_csv(0, "storage0-every-10.csv", "hex", 10)
+//! wave:export_saif("activity.saif", first, last)
+//!
+//! -- Put `top.cpu.pc`, say, on the clipboard, then the values of two storages as CSV.
+//! wave:copy_path(0)
+//! wave:copy_range({ 0, 1 }, first, last)
+//!
+//! -- The format is worked out from the contents, or given explicitly for stdin.
+//! local piped = ligeia.open("-", "vcd")
+//!
+//! -- { "top.cpu.pc": { "file": "cpu.sv", "line": 42 }, ... }
+//! wave:attach_sources("sources.json")
+//! local file, line = wave:source_of("top.cpu.pc")
+//!
+//! -- How busy each variable at the top is, over the whole waveform.
+//! for _, child in ipairs(wave:children_of(nil, 0, 100)) do
+//!     if child.kind == "var" then
+//!         print(wave:sparkline(child.storages[1], 40), child.name)
+//!     end
+//! end
+//!
+//! -- A row per rising edge of storage 0, with storages 1 and 2 as registers clocked by it see them.
+//! for _, cycle in ipairs(wave:sample_on_edges(0, { 1, 2 }, "rising", "hex")) do
+//!     print(cycle.time, cycle.values[1], cycle.values[2])
+//! end
+//!
+//! -- How long the state machine whose state register is storage 3 spent in each state.
+//! for _, entry in ipairs(wave:time_in_states(3, first, last)) do
+//!     print(entry.state or "(none)", wave:format_time(entry.time))
+//! end
+//! ```
+
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    ops::Range,
+    path::Path,
+};
+
+use ligeia_core::{
+    clocks,
+    condition::Condition,
+    format::{self, FormatOptions, Radix, Separators},
+    hierarchy::{Child, VarQuery},
+    logic::{self, Nine},
+    meta::{
+        EnumValue, ScopeId, Signedness, SourceLocation, StorageId, StorageType, Timesteps, VarKind,
+    },
+    resample::{edges, resample, sample_on_edges, Edge},
+    saif,
+    search::Glob,
+    slice::BitSlice,
+    states, stats, Processed,
+};
+use mlua::{Lua, Table, UserData, UserDataMethods};
+
+use crate::{clipboard, loading};
+
+struct Waveform(Processed);
+
+/// Bars from lowest to highest, for sparklines.
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Draw counts as a line of bars as tall as they are, relative to the largest, with a space for
+/// each count of zero so that quiet stretches stand out.
+fn sparkline(counts: &[u64]) -> String {
+    let max = counts.iter().copied().max().unwrap_or(0);
+    counts
+        .iter()
+        .map(|&count| match count {
+            0 => ' ',
+            count => SPARKS[((count - 1) * SPARKS.len() as u64 / max) as usize],
+        })
+        .collect()
+}
+
+/// Render a packed value as one character per bit, in storage order.
+///
+/// String storages are looked up in the waveform's string table instead, and events are just
+/// `event`.
+fn format_value(ty: StorageType, width: u32, data: &[u8]) -> String {
+    let mut values = vec![];
+    match ty {
+        StorageType::TwoLogic => logic::unpack_two(data, width as usize, &mut values),
+        StorageType::FourLogic => logic::unpack_four(data, width as usize, &mut values),
+        StorageType::NineLogic => values.extend_from_slice(&data[..width as usize]),
+        StorageType::Utf8 | StorageType::Event => unreachable!("not packed values"),
+    }
+
+    values
+        .into_iter()
+        .map(|value| match ty {
+            StorageType::TwoLogic | StorageType::FourLogic => ['0', '1', 'x', 'z'][value as usize],
+            StorageType::NineLogic => Nine::from_u8(value).map_or('?', Nine::symbol),
+            StorageType::Utf8 | StorageType::Event => unreachable!(),
+        })
+        .collect()
+}
+
+fn external<E: ToString>(e: E) -> mlua::Error {
+    mlua::Error::RuntimeError(e.to_string())
+}
+
+/// How a script asked for values to be written as numbers: a radix (`"bin"`, `"oct"`, `"dec"` or
+/// `"hex"`), or a table with a `radix` and any of a `group` size, `prefix`, `trim` and the
+/// `separator` between groups, like `{ radix = "hex", group = 4, prefix = true }`. Returns `None`
+/// for nil.
+fn number_format(format: mlua::Value) -> mlua::Result<Option<(Radix, FormatOptions)>> {
+    let (radix, options) = match format {
+        mlua::Value::Nil => return Ok(None),
+        mlua::Value::String(radix) => (radix.to_str()?.to_owned(), FormatOptions::default()),
+        mlua::Value::Table(table) => (
+            table.get::<_, String>("radix")?,
+            FormatOptions {
+                group: table.get("group")?,
+                prefix: table.get::<_, Option<bool>>("prefix")?.unwrap_or(false),
+                trim_leading_zeros: table.get::<_, Option<bool>>("trim")?.unwrap_or(false),
+                separator: table
+                    .get::<_, Option<String>>("separator")?
+                    .and_then(|separator| separator.chars().next()),
+            },
+        ),
+        _ => return Err(external("a format is a radix or a table")),
+    };
+    Ok(Some((radix.parse().map_err(external)?, options)))
+}
+
+impl Waveform {
+    /// The table `children_of` lists a child as.
+    fn child_entry<'lua>(&self, lua: &'lua Lua, child: Child) -> mlua::Result<Table<'lua>> {
+        let entry = lua.create_table()?;
+        match child {
+            Child::Scope(scope) => {
+                let counts = self.0.child_counts(scope.id).map_err(external)?;
+                entry.set("kind", "scope")?;
+                entry.set("id", scope.id.0)?;
+                entry.set("name", self.0.name(scope.name))?;
+                entry.set("display", self.0.display_name(scope.name))?;
+                entry.set("children", counts.total())?;
+            }
+            Child::Array(array) => {
+                let (first, last) = array.range();
+                entry.set("kind", "array")?;
+                entry.set("id", array.id.0)?;
+                entry.set("name", array.name())?;
+                entry.set("display", array.name())?;
+                entry.set("first", first)?;
+                entry.set("last", last)?;
+                entry.set("children", array.elements())?;
+            }
+            Child::Var(var) => {
+                entry.set("kind", "var")?;
+                entry.set("name", self.0.name(var.name))?;
+                entry.set("display", self.0.display_name(var.name))?;
+                let storages = Processed::var_storages(var).iter().map(|id| id.0);
+                entry.set("storages", lua.create_sequence_from(storages)?)?;
+            }
+        }
+        Ok(entry)
+    }
+
+    fn format(&self, id: StorageId, data: &[u8]) -> mlua::Result<String> {
+        let storage = self.0.storage(id).map_err(external)?;
+        Ok(match storage.ty {
+            StorageType::Utf8 => self.0.string(data).to_owned(),
+            StorageType::Event => "event".to_owned(),
+            ty => format_value(ty, storage.width, data),
+        })
+    }
+
+    fn format_number(
+        &self,
+        id: StorageId,
+        data: &[u8],
+        (radix, options): (Radix, FormatOptions),
+    ) -> mlua::Result<String> {
+        let storage = self.0.storage(id).map_err(external)?;
+        let width = storage.width as usize;
+        let mut values = vec![];
+        match storage.ty {
+            StorageType::TwoLogic => logic::unpack_two(data, width, &mut values),
+            StorageType::FourLogic => logic::unpack_four(data, width, &mut values),
+            // Weak values are read as the level they're pulled to, like `H` and `L` in VHDL.
+            StorageType::NineLogic => values.extend(
+                data[..width]
+                    .iter()
+                    .map(|&value| Nine::from_u8(value).map_or(2, Nine::level)),
+            ),
+            StorageType::Utf8 => return Ok(self.0.string(data).to_owned()),
+            StorageType::Event => return Ok("event".to_owned()),
+        }
+        Ok(format::format_values_with(&values, radix, options))
+    }
+
+    fn changes_in_range(
+        &mut self,
+        id: StorageId,
+        range: Range<Timesteps>,
+    ) -> mlua::Result<Vec<(u64, String)>> {
+        let mut changes = vec![];
+        self.0
+            .changes_in_range_with_initial(id, range, |timestamp, data| {
+                changes.push((timestamp.0, data.to_vec()))
+            })
+            .map_err(external)?;
+
+        changes
+            .into_iter()
+            .map(|(timestamp, data)| Ok((timestamp, self.format(id, &data)?)))
+            .collect()
+    }
+
+    /// The states of the enum variable held in a storage.
+    fn enum_values(&self, id: StorageId) -> mlua::Result<Vec<EnumValue>> {
+        self.0
+            .vars()
+            .iter()
+            .find_map(|var| match &var.kind {
+                VarKind::Enum { storage, values } if *storage == id => Some(values.clone()),
+                _ => None,
+            })
+            .ok_or_else(|| external("that storage doesn't hold an enum"))
+    }
+
+    /// The hierarchical name of the variable held in a storage, or a stand-in if there isn't
+    /// one.
+    fn path_of(&self, id: StorageId) -> String {
+        self.0
+            .vars()
+            .iter()
+            .find(|var| match &var.kind {
+                VarKind::Integer { storages, .. } => storages.contains(&id),
+                VarKind::Enum { storage, .. }
+                | VarKind::Utf8 { storage }
+                | VarKind::Event { storage } => *storage == id,
+                VarKind::None => false,
+            })
+            .map_or_else(|| format!("storage {}", id.0), |var| self.0.var_path(var))
+    }
+
+    /// Every change to a storage, formatted as a number if there's a `format`.
+    fn changes(
+        &mut self,
+        id: StorageId,
+        format: Option<(Radix, FormatOptions)>,
+    ) -> mlua::Result<Vec<(u64, String)>> {
+        let mut changes = vec![];
+        self.0
+            .load_storage(id, |timestamp, data| {
+                changes.push((timestamp.0, data.to_vec()))
+            })
+            .map_err(external)?;
+
+        changes
+            .into_iter()
+            .map(|(timestamp, data)| {
+                let value = match format {
+                    Some(format) => self.format_number(id, &data, format)?,
+                    None => self.format(id, &data)?,
+                };
+                Ok((timestamp, value))
+            })
+            .collect()
+    }
+}
+
+impl UserData for Waveform {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("time_bounds", |_, this, ()| {
+            let (first, last) = this.0.time_bounds();
+            Ok((first.0, last.0))
+        });
+
+        methods.add_method("femtoseconds_per_timestep", |_, this, ()| {
+            Ok(this.0.femtoseconds_per_timestep() as f64)
+        });
+
+        methods.add_method(
+            "format_time",
+            |_, this, (time, locale): (u64, Option<String>)| {
+                let separators = match locale {
+                    Some(locale) => Separators::from_locale(&locale),
+                    None => Separators::from_env(),
+                };
+                let femtoseconds = time as u128 * this.0.femtoseconds_per_timestep();
+                Ok(format::format_time(femtoseconds, separators))
+            },
+        );
+
+        methods.add_method("storages", |_, this, ()| {
+            Ok(this
+                .0
+                .storage_ids()
+                .into_iter()
+                .map(|id| id.0)
+                .collect::<Vec<_>>())
+        });
+
+        methods.add_method("change_count", |_, this, id: u32| {
+            this.0.change_count(StorageId(id)).map_err(external)
+        });
+
+        // Returns how many times a storage changes in each of `buckets` equal spans of the
+        // waveform, roughly. It's cheap enough to call for every variable being browsed.
+        methods.add_method_mut("activity", |_, this, (id, buckets): (u32, usize)| {
+            this.0
+                .change_density(StorageId(id), buckets)
+                .map_err(external)
+        });
+
+        // Returns `activity` drawn as a line of `width` bars, like `▁▁█▃   `, to tell dead
+        // signals from busy ones before adding them.
+        methods.add_method_mut("sparkline", |_, this, (id, width): (u32, usize)| {
+            let density = this
+                .0
+                .change_density(StorageId(id), width)
+                .map_err(external)?;
+            Ok(sparkline(&density))
+        });
+
+        // Returns the number of string changes, how many were distinct, and how many times
+        // smaller they are for being deduplicated.
+        methods.add_method("string_stats", |_, this, ()| {
+            let stats = this.0.string_stats();
+            Ok((stats.changes, stats.distinct, stats.dedup_ratio()))
+        });
+
+        // Returns a table of child scope names and a table of variable names.
+        methods.add_method("within_scope", |lua, this, id: Option<u32>| {
+            let (scopes, vars) = this
+                .0
+                .within_scope(id.map_or(ScopeId::ROOT, ScopeId))
+                .map_err(external)?;
+            let scopes =
+                lua.create_sequence_from(scopes.into_iter().map(|s| this.0.name(s.name)))?;
+            let vars = lua.create_sequence_from(vars.into_iter().map(|v| this.0.name(v.name)))?;
+            Ok((scopes, vars))
+        });
+
+        // Returns a sequence of up to `limit` `{ kind = "scope", id = ..., name = ..., children =
+        // ... }` and `{ kind = "var", name = ..., storages = { ... } }` tables for what's directly
+        // within a scope, skipping the first `offset`, along with how many there are in total.
+        // `children` counts what's within each child scope, without listing it. Each one also has
+        // a `display` name, demangled for showing to people.
+        //
+        // Families like `mem[0]`, `mem[1]`, ... come as one `{ kind = "array", id = ..., name =
+        // "mem[0:1023]", first = 0, last = 1023, children = ... }` instead, whose id pages through
+        // its elements like a scope's.
+        methods.add_method(
+            "children_of",
+            |lua, this, (id, offset, limit): (Option<u32>, usize, usize)| {
+                let id = id.map_or(ScopeId::ROOT, ScopeId);
+                let total = this.0.child_counts(id).map_err(external)?.total();
+                let children = this.0.children_of(id, offset, limit).map_err(external)?;
+
+                let table = lua.create_table_with_capacity(children.len() as _, 0)?;
+                for (i, child) in children.into_iter().enumerate() {
+                    let entry = this.child_entry(lua, child)?;
+                    table.set(i + 1, entry)?;
+                }
+                Ok((table, total))
+            },
+        );
+
+        // Returns the entry `children_of` would for the element of an array with `index`, or nil
+        // if there isn't one.
+        methods.add_method(
+            "array_element",
+            |lua, this, (id, index): (u32, i64)| match this
+                .0
+                .array_element(ScopeId(id), index)
+                .map_err(external)?
+            {
+                Some(child) => Ok(Some(this.child_entry(lua, child)?)),
+                None => Ok(None),
+            },
+        );
+
+        // Returns the names of the variables directly within a scope. `options` can have an
+        // `order` (`"declaration"`, `"name"`, `"width"` or `"activity"`), a `width` filter
+        // (`"any"`, `"bits"` or `"buses"`), and `from` and `to` times to only keep variables that
+        // change between them.
+        methods.add_method_mut(
+            "vars_within",
+            |lua, this, (id, options): (Option<u32>, Option<Table>)| {
+                let mut query = VarQuery::default();
+                if let Some(options) = options {
+                    if let Some(order) = options.get::<_, Option<String>>("order")? {
+                        query.order = order.parse().map_err(external)?;
+                    }
+                    if let Some(width) = options.get::<_, Option<String>>("width")? {
+                        query.width = width.parse().map_err(external)?;
+                    }
+                    let from = options.get::<_, Option<u64>>("from")?;
+                    let to = options.get::<_, Option<u64>>("to")?;
+                    if from.is_some() || to.is_some() {
+                        let (first, last) = this.0.time_bounds();
+                        query.changed_in = Some(
+                            Timesteps(from.unwrap_or(first.0))
+                                ..Timesteps(to.unwrap_or_else(|| last.0.saturating_add(1))),
+                        );
+                    }
+                }
+
+                let vars = this
+                    .0
+                    .vars_within(id.map_or(ScopeId::ROOT, ScopeId), &query)
+                    .map_err(external)?;
+                let names: Vec<_> = vars.iter().map(|var| var.name).collect();
+                lua.create_sequence_from(names.into_iter().map(|name| this.0.name(name)))
+            },
+        );
+
+        // Returns the value, the time it changed to that value, and the time of the next change.
+        // With a format, like `"hex"` or `{ radix = "hex", group = 4 }`, the value is formatted
+        // as a number.
+        methods.add_method_mut(
+            "value_at",
+            |_, this, (id, time, format): (u32, u64, mlua::Value)| {
+                let id = StorageId(id);
+                let format = number_format(format)?;
+                match this.0.value_at(id, Timesteps(time)).map_err(external)? {
+                    Some(value) => Ok((
+                        Some(match format {
+                            Some(format) => this.format_number(id, &value.data, format)?,
+                            None => this.format(id, &value.data)?,
+                        }),
+                        Some(value.start.0),
+                        value.end.map(|end| end.0),
+                    )),
+                    None => Ok((None, None, None)),
+                }
+            },
+        );
+
+        // Returns the smallest and largest values a storage has between `from` and `to`, read as
+        // unsigned numbers or, with `signed`, two's complement ones. Values with an `x` or `z` in
+        // them are left out, and with none left, returns nil.
+        methods.add_method_mut(
+            "value_range",
+            |_, this, (id, from, to, signed): (u32, u64, u64, Option<bool>)| {
+                let signedness = match signed {
+                    Some(true) => Signedness::SignedTwosComplement,
+                    _ => Signedness::Unsigned,
+                };
+                let range = stats::value_range(
+                    &mut this.0,
+                    StorageId(id),
+                    signedness,
+                    Timesteps(from)..Timesteps(to),
+                )
+                .map_err(external)?;
+                Ok(range.map_or((None, None), |(min, max)| (Some(min), Some(max))))
+            },
+        );
+
+        // Returns the hierarchical names of every variable matching a glob like `top.*.valid`.
+        methods.add_method("find_vars", |_, this, pattern: String| {
+            let glob = Glob::new(&pattern).map_err(external)?;
+            Ok(this
+                .0
+                .find_vars(&glob)
+                .into_iter()
+                .map(|var| this.0.var_path(var))
+                .collect::<Vec<_>>())
+        });
+
+        methods.add_method(
+            "path_of",
+            |_, this, id: u32| Ok(this.path_of(StorageId(id))),
+        );
+
+        // The `copy_*` methods put text on the clipboard for pasting into bug reports, and return
+        // it too.
+        methods.add_method("copy_path", |_, this, id: u32| {
+            let path = this.path_of(StorageId(id));
+            clipboard::copy(&path).map_err(external)?;
+            Ok(path)
+        });
+
+        // Copies the value in effect at `time`, formatted like `value_at`, or nothing if it
+        // hadn't been set yet.
+        methods.add_method_mut(
+            "copy_value",
+            |_, this, (id, time, format): (u32, u64, mlua::Value)| {
+                let id = StorageId(id);
+                let format = number_format(format)?;
+                let value = match this.0.value_at(id, Timesteps(time)).map_err(external)? {
+                    Some(value) => value,
+                    None => return Ok(None),
+                };
+                let text = match format {
+                    Some(format) => this.format_number(id, &value.data, format)?,
+                    None => this.format(id, &value.data)?,
+                };
+                clipboard::copy(&text).map_err(external)?;
+                Ok(Some(text))
+            },
+        );
+
+        // Copies the values of a sequence of storages from `start` up to `finish` as CSV, with a
+        // column for each storage and a row for each time any of them changes.
+        methods.add_method_mut(
+            "copy_range",
+            |_, this, (ids, start, finish): (Vec<u32>, u64, u64)| {
+                let mut names = vec![];
+                let mut columns = vec![];
+                for id in ids.into_iter().map(StorageId) {
+                    names.push(this.path_of(id));
+                    // Values already in effect at `start` show up as of `start`.
+                    let changes = this.changes_in_range(id, Timesteps(start)..Timesteps(finish))?;
+                    columns.push(
+                        changes
+                            .into_iter()
+                            .map(|(time, value)| (time.max(start), value))
+                            .collect::<Vec<_>>(),
+                    );
+                }
+                let csv = clipboard::csv_snippet(&names, &columns);
+                clipboard::copy(&csv).map_err(external)?;
+                Ok(csv)
+            },
+        );
+
+        // Returns a sequence of `{ time = ..., value = ... }` tables, with values formatted like
+        // with `value_at`.
+        methods.add_method_mut("changes", |lua, this, (id, format): (u32, mlua::Value)| {
+            let changes = this.changes(StorageId(id), number_format(format)?)?;
+            let table = lua.create_table_with_capacity(changes.len() as _, 0)?;
+            for (i, (time, value)) in changes.into_iter().enumerate() {
+                let change = lua.create_table()?;
+                change.set("time", time)?;
+                change.set("value", value)?;
+                table.set(i + 1, change)?;
+            }
+            Ok(table)
+        });
+
+        // Like `changes`, but only from `start` up to `finish`, along with the value that was
+        // already in effect at `start`.
+        methods.add_method_mut(
+            "changes_in_range",
+            |lua, this, (id, start, finish): (u32, u64, u64)| {
+                let changes =
+                    this.changes_in_range(StorageId(id), Timesteps(start)..Timesteps(finish))?;
+                let table = lua.create_table_with_capacity(changes.len() as _, 0)?;
+                for (i, (time, value)) in changes.into_iter().enumerate() {
+                    let change = lua.create_table()?;
+                    change.set("time", time)?;
+                    change.set("value", value)?;
+                    table.set(i + 1, change)?;
+                }
+                Ok(table)
+            },
+        );
+
+        // Returns a sequence of `{ storage = ..., period = ... }` tables for the clocks that were
+        // found, and a table mapping each storage to the storage of its clock, for those that
+        // have one.
+        methods.add_method_mut("clock_domains", |lua, this, ()| {
+            let detected = clocks::detect_clocks(&mut this.0).map_err(external)?;
+            let ids = this.0.storage_ids();
+            let domains = clocks::clock_domains(&mut this.0, &detected, &ids).map_err(external)?;
+
+            let clocks = lua.create_table_with_capacity(detected.len() as _, 0)?;
+            for (i, clock) in detected.iter().enumerate() {
+                let entry = lua.create_table()?;
+                entry.set("storage", clock.storage.0)?;
+                entry.set("period", clock.period.0)?;
+                clocks.set(i + 1, entry)?;
+            }
+
+            let table = lua.create_table()?;
+            for (id, domain) in ids.into_iter().zip(domains) {
+                if let Some(domain) = domain {
+                    table.set(id.0, detected[domain.clock].storage.0)?;
+                }
+            }
+            Ok((clocks, table))
+        });
+
+        // Returns a sequence of `{ time = ..., values = { ... } }` tables, one for each `edge`
+        // (`rising` or `falling`, rising if it's nil) of the one-bit storage `clock`. `values`
+        // has the value of each of `ids` before that edge, formatted like with `value_at`, or nil
+        // before its first change.
+        methods.add_method_mut(
+            "sample_on_edges",
+            |lua,
+             this,
+             (clock, ids, edge, format): (u32, Vec<u32>, Option<String>, mlua::Value)| {
+                let edge: Edge = edge
+                    .as_deref()
+                    .unwrap_or("rising")
+                    .parse()
+                    .map_err(external)?;
+                let format = number_format(format)?;
+                let levels = clocks::levels(&mut this.0, StorageId(clock)).map_err(external)?;
+
+                let mut columns = Vec::with_capacity(ids.len());
+                for id in ids {
+                    let changes = this
+                        .changes(StorageId(id), format)?
+                        .into_iter()
+                        .map(|(time, value)| (Timesteps(time), value));
+                    columns.push(sample_on_edges(levels.iter().copied(), edge, changes));
+                }
+
+                let table = lua.create_table()?;
+                for (i, time) in edges(levels.iter().copied(), edge).enumerate() {
+                    let values = lua.create_table_with_capacity(columns.len() as _, 0)?;
+                    for (j, column) in columns.iter_mut().enumerate() {
+                        if let Some((_, Some(value))) = column.next() {
+                            values.set(j + 1, value)?;
+                        }
+                    }
+                    let cycle = lua.create_table()?;
+                    cycle.set("time", time.0)?;
+                    cycle.set("values", values)?;
+                    table.set(i + 1, cycle)?;
+                }
+                Ok(table)
+            },
+        );
+
+        // Returns a sequence of `{ start = ..., finish = ..., state = ... }` tables for the states
+        // that the enum in a storage is in between `start` and `finish`, in order. `state` is the
+        // state's name, or nil where the value isn't any of them, and the last `finish` is nil.
+        methods.add_method_mut(
+            "states",
+            |lua, this, (id, start, finish): (u32, u64, u64)| {
+                let values = this.enum_values(StorageId(id))?;
+                let spans = states::state_spans(
+                    &mut this.0,
+                    StorageId(id),
+                    &values,
+                    Timesteps(start)..Timesteps(finish),
+                )
+                .map_err(external)?;
+
+                let table = lua.create_table_with_capacity(spans.len() as _, 0)?;
+                for (i, span) in spans.into_iter().enumerate() {
+                    let entry = lua.create_table()?;
+                    entry.set("start", span.start.0)?;
+                    entry.set("finish", span.end.map(|end| end.0))?;
+                    entry.set("state", span.state.map(|state| values[state].name.as_str()))?;
+                    table.set(i + 1, entry)?;
+                }
+                Ok(table)
+            },
+        );
+
+        // Returns a sequence of `{ state = ..., time = ... }` tables for how long the enum in a
+        // storage spent in each state between `start` and `finish`, most time first, with `state`
+        // nil for values that aren't any of them.
+        methods.add_method_mut(
+            "time_in_states",
+            |lua, this, (id, start, finish): (u32, u64, u64)| {
+                let values = this.enum_values(StorageId(id))?;
+                let range = Timesteps(start)..Timesteps(finish);
+                let spans = states::state_spans(&mut this.0, StorageId(id), &values, range.clone())
+                    .map_err(external)?;
+
+                let totals = states::time_in_states(&spans, range);
+                let table = lua.create_table_with_capacity(totals.len() as _, 0)?;
+                for (i, (state, time)) in totals.into_iter().enumerate() {
+                    let entry = lua.create_table()?;
+                    entry.set("state", state.map(|state| values[state].name.as_str()))?;
+                    entry.set("time", time)?;
+                    table.set(i + 1, entry)?;
+                }
+                Ok(table)
+            },
+        );
+
+        // Returns a sequence of `{ start = ..., finish = ... }` tables for the spans of time over
+        // which a condition like `req && !ack` holds. `finish` is nil if it holds to the end.
+        methods.add_method_mut("search", |lua, this, condition: String| {
+            let condition = Condition::parse(&condition, &this.0).map_err(external)?;
+            let intervals = condition.intervals(&mut this.0).map_err(external)?;
+
+            let table = lua.create_table_with_capacity(intervals.len() as _, 0)?;
+            for (i, interval) in intervals.into_iter().enumerate() {
+                let entry = lua.create_table()?;
+                entry.set("start", interval.start.0)?;
+                entry.set("finish", interval.end.map(|end| end.0))?;
+                table.set(i + 1, entry)?;
+            }
+            Ok(table)
+        });
+
+        // Like `changes`, but for a single bit of a storage.
+        methods.add_method_mut("bit_changes", |lua, this, (id, bit): (u32, u32)| {
+            let storage = this.0.storage(StorageId(id)).map_err(external)?;
+            let ty = storage.ty;
+            if matches!(ty, StorageType::Utf8) || bit >= storage.width {
+                return Err(external("no such bit"));
+            }
+
+            let slice = BitSlice {
+                storage: StorageId(id),
+                start: bit,
+                width: 1,
+            };
+            let mut changes = vec![];
+            slice
+                .load(&mut this.0, |time, data| {
+                    changes.push((time.0, format_value(ty, 1, data)))
+                })
+                .map_err(external)?;
+
+            let table = lua.create_table_with_capacity(changes.len() as _, 0)?;
+            for (i, (time, value)) in changes.into_iter().enumerate() {
+                let change = lua.create_table()?;
+                change.set("time", time)?;
+                change.set("value", value)?;
+                table.set(i + 1, change)?;
+            }
+            Ok(table)
+        });
+
+        // Load a JSON sidecar mapping hierarchical variable names to `{ "file": ..., "line": ... }`.
+        methods.add_method_mut("attach_sources", |_, this, path: String| {
+            let sidecar = fs::read_to_string(&path).map_err(external)?;
+            let mut sources: HashMap<String, serde_json::Value> =
+                serde_json::from_str(&sidecar).map_err(external)?;
+
+            this.0.attach_sources(|var_path| {
+                let source = sources.remove(var_path)?;
+                Some(SourceLocation {
+                    file: source["file"].as_str()?.to_string(),
+                    line: source["line"].as_u64()? as u32,
+                })
+            });
+            Ok(())
+        });
+
+        methods.add_method("source_of", |_, this, path: String| {
+            Ok(match this.0.source_of(&path) {
+                Some(source) => (Some(source.file.clone()), Some(source.line)),
+                None => (None, None),
+            })
+        });
+
+        // Write the changes between `start` and `finish` to a new VCD file, along with the values
+        // at `start`. `vars` is a sequence of hierarchical names to include, or nil for all of
+        // them.
+        methods.add_method_mut(
+            "export_vcd",
+            |_, this, (path, start, finish, vars): (String, u64, u64, Option<Vec<String>>)| {
+                let writer = BufWriter::new(File::create(&path).map_err(external)?);
+                ligeia_vcd::export_vcd(
+                    &mut this.0,
+                    writer,
+                    (Timesteps(start), Timesteps(finish)),
+                    |var| {
+                        vars.as_ref()
+                            .map_or(true, |vars| vars.iter().any(|v| v == var))
+                    },
+                )
+                .map_err(external)
+            },
+        );
+
+        // Write the toggle counts and time at each level of every bit between `start` and
+        // `finish` as a SAIF file, for power estimation. `vars` is like with `export_vcd`.
+        methods.add_method_mut(
+            "export_saif",
+            |_, this, (path, start, finish, vars): (String, u64, u64, Option<Vec<String>>)| {
+                let writer = BufWriter::new(File::create(&path).map_err(external)?);
+                saif::export_saif(
+                    &mut this.0,
+                    writer,
+                    (Timesteps(start), Timesteps(finish)),
+                    |var| {
+                        vars.as_ref()
+                            .map_or(true, |vars| vars.iter().any(|v| v == var))
+                    },
+                )
+                .map_err(external)
+            },
+        );
+
+        // Write every change to a storage to a CSV file, with values formatted like with
+        // `value_at`. With a `step`, the value is written every `step` timesteps across the whole
+        // waveform instead, empty before the storage's first change.
+        methods.add_method_mut(
+            "export_csv",
+            |_, this, (id, path, format, step): (u32, String, mlua::Value, Option<u64>)| {
+                let changes = this.changes(StorageId(id), number_format(format)?)?;
+
+                let mut writer = BufWriter::new(File::create(&path).map_err(external)?);
+                writeln!(writer, "time,value").map_err(external)?;
+                match step {
+                    Some(0) => return Err(external("the step has to be at least one timestep")),
+                    Some(step) => {
+                        let (first, last) = this.0.time_bounds();
+                        let changes = changes
+                            .into_iter()
+                            .map(|(time, value)| (Timesteps(time), value));
+                        let range = first..Timesteps(last.0.saturating_add(1));
+                        for (time, value) in resample(changes, range, step) {
+                            let value = value.unwrap_or_default();
+                            writeln!(writer, "{},{}", time.0, value).map_err(external)?;
+                        }
+                    }
+                    None => {
+                        for (time, value) in changes {
+                            writeln!(writer, "{},{}", time, value).map_err(external)?;
+                        }
+                    }
+                }
+                writer.flush().map_err(external)
+            },
+        );
+    }
+}
+
+fn register(lua: &Lua) -> mlua::Result<()> {
+    let ligeia: Table = lua.create_table()?;
+
+    ligeia.set(
+        "open",
+        lua.create_function(|_, (path, format): (String, Option<String>)| {
+            let loaders = loading::loaders();
+            let processed = if path == "-" {
+                loaders.load_stream("<stdin>", io::stdin().lock(), format.as_deref())
+            } else {
+                loaders.load_file(Path::new(&path), format.as_deref())
+            }
+            .map_err(external)?;
+            Ok(Waveform(processed))
+        })?,
+    )?;
+
+    lua.globals().set("ligeia", ligeia)
+}
+
+/// Run a Lua script headlessly, without opening a window.
+pub fn run(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let source = fs::read_to_string(path)?;
+
+    let lua = Lua::new();
+    register(&lua)?;
+    lua.load(&source).exec()?;
+
+    Ok(())
+}
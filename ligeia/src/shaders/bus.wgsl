@@ -0,0 +1,94 @@
+// Draws the tracks of buses and strings, after `common.wgsl` and `tiles.wgsl`. Each value is an
+// elongated hexagon, whose slanted ends cross the neighbouring values' to form the classic
+// transition. The values themselves are written inside by the text pass, where they fit.
+
+// Horizontal size of the slanted edge at each transition, in pixels.
+let TRANSITION_WIDTH: f32 = 4.0;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) offset: f32,
+    @location(1) @interpolate(flat) unknown: u32,
+}
+
+// Draws the outline of each value as six instances: the top and bottom edges, and the two
+// slanted edges on either end. Narrow values degrade into a diamond rather than overlapping
+// their neighbours. The value a tile starts with didn't change there, and nor does the last one
+// at the end of the tile, so those ends are left open.
+@vertex
+fn vs_bus(
+    @builtin(instance_index) instance_index: u32,
+    @location(0) vertex: vec2<f32>,
+) -> VertexOutput {
+    let change: u32 = instance_index / 6u;
+    let edge: u32 = instance_index % 6u;
+    let word: u32 = change_word(change);
+
+    let start: f32 = change_start(change);
+    let end: f32 = change_end(change);
+    let slant: f32 = min(TRANSITION_WIDTH, (end - start) * 0.5);
+    let start_slant: f32 = select(slant, 0.0, (word & INITIAL) != 0u);
+    let end_slant: f32 = select(0.0, slant, change + 1u < uniforms.changes);
+
+    let top: f32 = uniforms.extent.x + track_padding();
+    let bottom: f32 = uniforms.extent.y - track_padding();
+    let middle: f32 = (top + bottom) * 0.5;
+
+    var point_a: vec2<f32>;
+    var point_b: vec2<f32>;
+    switch edge {
+        case 0u: {
+            point_a = vec2<f32>(start + start_slant, top);
+            point_b = vec2<f32>(end - end_slant, top);
+        }
+        case 1u: {
+            point_a = vec2<f32>(start + start_slant, bottom);
+            point_b = vec2<f32>(end - end_slant, bottom);
+        }
+        case 2u: {
+            point_a = vec2<f32>(start, middle);
+            point_b = vec2<f32>(start + start_slant, top);
+        }
+        case 3u: {
+            point_a = vec2<f32>(start, middle);
+            point_b = vec2<f32>(start + start_slant, bottom);
+        }
+        case 4u: {
+            point_a = vec2<f32>(end - end_slant, top);
+            point_b = vec2<f32>(end, middle);
+        }
+        default: {
+            point_a = vec2<f32>(end - end_slant, bottom);
+            point_b = vec2<f32>(end, middle);
+        }
+    }
+    // Open ends have no slanted edges.
+    let open_start: bool = (edge == 2u || edge == 3u) && start_slant == 0.0;
+    let open_end: bool = edge >= 4u && end_slant == 0.0;
+    if open_start || open_end {
+        point_b = point_a;
+    }
+
+    var result: VertexOutput;
+    result.position = to_clip(generate_line(vertex, point_a, point_b, uniforms.line_width));
+    result.offset = vertex.y * 2f;
+    result.unknown = word & UNKNOWN;
+    return result;
+}
+
+@fragment
+fn fs_bus(
+    input: VertexOutput,
+) -> @location(0) vec4<f32> {
+    let alpha: f32 = feathered_alpha(input.offset, uniforms.feather_fraction);
+
+    if input.unknown != 0u {
+        // Dashed when hatching, so that unknown values don't rely on color alone.
+        let diagonal: f32 = input.position.x + input.position.y;
+        if uniforms.hatching != 0u && fract(diagonal / (uniforms.dash_length * 2.0)) > 0.5 {
+            discard;
+        }
+        return vec4<f32>(uniforms.unknown_color.rgb, uniforms.unknown_color.a * alpha);
+    }
+    return vec4<f32>(uniforms.line_color.rgb, uniforms.line_color.a * alpha);
+}
@@ -0,0 +1,30 @@
+// Shared by the shaders that draw thick lines, and put in front of each of them.
+
+// The corner `vertex` of the quad that draws the line from `point_a` to `point_b`,
+// `line_width` thick. The quad's x goes from 0 to 1 along the line, and its y from -0.5 to 0.5
+// across it. A line with no length has no quad.
+fn generate_line(
+    vertex: vec2<f32>,
+    point_a: vec2<f32>,
+    point_b: vec2<f32>,
+    line_width: f32,
+) -> vec2<f32> {
+    // the vector parallel to the line
+    let x_basis: vec2<f32> = point_b - point_a;
+    if length(x_basis) == 0.0 {
+        return point_a;
+    }
+    // a unit vector normal to the line
+    let y_basis: vec2<f32> = normalize(vec2<f32>(-x_basis.y, x_basis.x));
+    return point_a + x_basis * vertex.x + y_basis * line_width * vertex.y;
+}
+
+// How opaque a line is `offset` across it, from -1 on one edge to 1 on the other, fading out
+// over `feather_fraction` of each side.
+fn feathered_alpha(offset: f32, feather_fraction: f32) -> f32 {
+    let dist: f32 = abs(offset);
+    if dist > 1f - feather_fraction {
+        return (1f - dist) / feather_fraction;
+    }
+    return 1f;
+}
@@ -0,0 +1,38 @@
+
+struct Uniforms {
+    scale: vec2<f32>,
+}
+
+@group(0)
+@binding(0)
+var<uniform> uniforms: Uniforms;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+}
+
+// Each instance is a tint, stretched between its start and end, which are measured from the left
+// of the pane, and its top and bottom, which are measured down from the top.
+@vertex
+fn vs_main(
+    @location(0) vertex: vec2<f32>,
+    @location(1) span: vec2<f32>,
+    @location(2) extent: vec2<f32>,
+    @location(3) color: vec4<f32>,
+) -> VertexOutput {
+    let x: f32 = mix(span.x, span.y, vertex.x);
+    let y: f32 = mix(extent.x, extent.y, vertex.y);
+
+    var result: VertexOutput;
+    result.position = vec4<f32>(x * uniforms.scale.x - 1.0, 1.0 - y * uniforms.scale.y, 0.0, 1.0);
+    result.color = color;
+    return result;
+}
+
+@fragment
+fn fs_main(
+    input: VertexOutput,
+) -> @location(0) vec4<f32> {
+    return input.color;
+}
@@ -1,8 +1,10 @@
-
 struct Uniforms {
     scale: vec2<f32>,
     feather_fraction: f32,
     line_width: f32,
+    // What the line is drawn with when picking.
+    pick_id: u32,
+    color: vec4<f32>,
 }
 
 @group(0)
@@ -25,12 +27,7 @@ fn vs_main(
 ) -> VertexOutput {
     let point_a: vec2<f32> = points[instance_index];
     let point_b: vec2<f32> = points[instance_index + 1u];
-
-    // the vector parallel to the line
-    let x_basis: vec2<f32> = point_b - point_a;
-    // a unit vector normal to the line
-    let y_basis: vec2<f32> = normalize(vec2<f32>(-x_basis.y, x_basis.x));
-    let the_point: vec2<f32> = point_a + x_basis * vertex.x + y_basis * uniforms.line_width * vertex.y;
+    let the_point: vec2<f32> = generate_line(vertex, point_a, point_b, uniforms.line_width);
 
     var result: VertexOutput;
     result.position = vec4<f32>(the_point * uniforms.scale, 0.0, 1.0);
@@ -42,14 +39,11 @@ fn vs_main(
 fn fs_main(
     input: VertexOutput,
 ) -> @location(0) vec4<f32> {
-    let dist: f32 = abs(input.offset);
-
-    var alpha: f32;
-    if dist > 1f - uniforms.feather_fraction {
-        alpha = (1f - dist) / uniforms.feather_fraction;
-    } else {
-        alpha = 1f;
-    }
+    let alpha: f32 = feathered_alpha(input.offset, uniforms.feather_fraction);
+    return vec4<f32>(uniforms.color.rgb, uniforms.color.a * alpha);
+}
 
-    return vec4<f32>(0.0, 0.0, 0.0, alpha);
+@fragment
+fn fs_pick() -> @location(0) u32 {
+    return uniforms.pick_id;
 }
@@ -1,67 +1,126 @@
+// Draws the tracks of one-bit signals, after `common.wgsl` and `tiles.wgsl`. Unknown values are
+// filled in and drawn through the middle of the track in their own color, and high impedance
+// ones are dashed there, so that neither relies on color alone. When a bucket's changes were
+// left out to zoom out, any unknown values among them still show, over the known ones.
 
-struct Uniforms {
-    scale: vec2<f32>,
-    feather_fraction: f32,
-    line_width: f32,
-    window_start: f32,
-    height: f32,
-}
-
-@group(0)
-@binding(0)
-var<uniform> uniforms: Uniforms;
+// Spacing of the hatching's diagonal stripes, in pixels.
+let HATCH_SPACING: f32 = 8.0;
 
-@group(0)
-@binding(1)
-var<storage, read> timestamps: array<f32>;
-
-@group(0)
-@binding(2)
-var<storage, read> level_bits: array<u32>;
+let STATE_ZERO: u32 = 0u;
+let STATE_ONE: u32 = 1u;
+let STATE_X: u32 = 2u;
+let STATE_Z: u32 = 3u;
 
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
     @location(0) offset: f32,
+    @location(1) @interpolate(flat) state: u32,
+    // Distance along the line in pixels, used to dash high impedance regions.
+    @location(2) dash_distance: f32,
 }
 
-fn generate_line(vertex: vec2<f32>, from: vec2<f32> to: vec2<f32>) -> vec4<f32> {
-    // the vector parallel to the line
-    let x_basis: vec2<f32> = point_b - point_a;
-    // a unit vector normal to the line
-    let y_basis: vec2<f32> = normalize(vec2<f32>(-x_basis.y, x_basis.x));
-    let the_point: vec2<f32> = point_a + x_basis * vertex.x + y_basis * uniforms.line_width * vertex.y;
+// The state of a change's value: 0, 1, 2 (unknown) or 3 (high impedance).
+fn state_at(change: u32) -> u32 {
+    let word: u32 = value_word(change, 0u);
+    if uniforms.ty == TWO_LOGIC {
+        return word & 1u;
+    }
+    if uniforms.ty == FOUR_LOGIC {
+        return word & 3u;
+    }
+
+    // Nine-logic values are a byte each, and weak ones are drawn at the level they pull to.
+    var levels: array<u32, 9> = array<u32, 9>(0u, 1u, 0u, 1u, 2u, 2u, 0u, 1u, 3u);
+    let value: u32 = word & 255u;
+    if value > 8u {
+        return STATE_X;
+    }
+    return levels[value];
+}
 
-    return vec4<f32>(the_point * uniforms.scale, 0.0, 1.0);
+// Ones are drawn along the top of the track, zeros along the bottom, and anything else through
+// the middle.
+fn state_y(state: u32) -> f32 {
+    let top: f32 = uniforms.extent.x + track_padding();
+    let bottom: f32 = uniforms.extent.y - track_padding();
+    return select(select((top + bottom) * 0.5, top, state == STATE_ONE), bottom, state == STATE_ZERO);
 }
 
+// Each instance is the line through a change's value, until the next change.
 @vertex
-fn vs_vertical_lines(
+fn vs_horizontal_lines(
     @builtin(instance_index) instance_index: u32,
     @location(0) vertex: vec2<f32>,
 ) -> VertexOutput {
-    let point_a: vec2<f32> = vec2<f32>(timestamps[uniforms.window_start + instance_index]);
-    let point_b: vec2<f32> = vec2<f32>(point_a.x, point_a.y + uniforms.height);
+    let state: u32 = state_at(instance_index);
+    let y: f32 = state_y(state);
+    let point_a: vec2<f32> = vec2<f32>(change_start(instance_index), y);
+    let point_b: vec2<f32> = vec2<f32>(change_end(instance_index), y);
 
     var result: VertexOutput;
-    result.position = generate_line(vertex, point_a, point_b);
+    result.position = to_clip(generate_line(vertex, point_a, point_b, uniforms.line_width));
     result.offset = vertex.y * 2f;
+    result.state = state;
+    result.dash_distance = mix(point_a.x, point_b.x, vertex.x);
     return result;
 }
 
+// Each instance is the edge at a change, from the value before it. The first change in a tile
+// doesn't have the value before it, and a bucket that had changes left out could have gone
+// anywhere, so their edges go all the way across the track.
 @vertex
-fn vs_horizontal_lines(
+fn vs_vertical_lines(
     @builtin(instance_index) instance_index: u32,
     @location(0) vertex: vec2<f32>,
 ) -> VertexOutput {
-    let is_high: bool = extractBits(level_bits[instance_index / 32], instance_index % 32, 1) != 0u;
-    let offset: f32 = select(0.0, uniforms.height, level);
+    let word: u32 = change_word(instance_index);
+    let x: f32 = change_start(instance_index);
 
-    let point_a: vec2<f32> = vec2<f32>(timestamps[instance_index], offset);
-    let point_b: vec2<f32> = vec2<f32>(timestamps[instance_index + 1u], offset);
+    var point_a: vec2<f32> = vec2<f32>(x, state_y(state_at(instance_index)));
+    var point_b: vec2<f32> = point_a;
+    if (word & INITIAL) == 0u {
+        if instance_index == 0u || (word & GLITCH) != 0u {
+            point_a.y = state_y(STATE_ZERO);
+            point_b.y = state_y(STATE_ONE);
+        } else {
+            point_b.y = state_y(state_at(instance_index - 1u));
+        }
+    }
 
     var result: VertexOutput;
-    result.position = generate_line(vertex, point_a, point_b);
+    result.position = to_clip(generate_line(vertex, point_a, point_b, uniforms.line_width));
     result.offset = vertex.y * 2f;
+    result.state = STATE_ONE;
+    result.dash_distance = 0.0;
+    return result;
+}
+
+// Fills the whole height of the track wherever the value is unknown, so that unknown regions
+// stand out even when zoomed far out. Unknown values that were left out of a bucket fill just
+// the bucket.
+@vertex
+fn vs_unknown_fill(
+    @builtin(instance_index) instance_index: u32,
+    @location(0) vertex: vec2<f32>,
+) -> VertexOutput {
+    let state: u32 = state_at(instance_index);
+    let word: u32 = change_word(instance_index);
+    let start: f32 = change_start(instance_index);
+    var end: f32 = change_end(instance_index);
+    if state != STATE_X {
+        end = min(end, start + uniforms.bucket_width);
+    }
+
+    // Collapse the quad for anything that isn't unknown.
+    let filled: bool = state == STATE_X || (word & DROPPED_UNKNOWN) != 0u;
+    let top: f32 = select(uniforms.extent.y, uniforms.extent.x, filled);
+    let the_point: vec2<f32> = vec2<f32>(mix(start, end, vertex.x), mix(uniforms.extent.y, top, vertex.y + 0.5));
+
+    var result: VertexOutput;
+    result.position = to_clip(the_point);
+    result.offset = 0.0;
+    result.state = STATE_X;
+    result.dash_distance = 0.0;
     return result;
 }
 
@@ -69,14 +128,36 @@ fn vs_horizontal_lines(
 fn fs_shared(
     input: VertexOutput,
 ) -> @location(0) vec4<f32> {
-    let dist: f32 = abs(input.offset);
+    let alpha: f32 = feathered_alpha(input.offset, uniforms.feather_fraction);
 
-    var alpha: f32;
-    if dist > 1f - uniforms.feather_fraction {
-        alpha = (1f - dist) / uniforms.feather_fraction;
-    } else {
-        alpha = 1f;
+    switch input.state {
+        case 2u: {
+            return vec4<f32>(uniforms.unknown_color.rgb, uniforms.unknown_color.a * alpha);
+        }
+        case 3u: {
+            // Dashed, so that high impedance is distinguishable without relying on color.
+            if fract(input.dash_distance / (uniforms.dash_length * 2.0)) > 0.5 {
+                discard;
+            }
+            return vec4<f32>(uniforms.high_impedance_color.rgb, uniforms.high_impedance_color.a * alpha);
+        }
+        default: {
+            return vec4<f32>(uniforms.line_color.rgb, uniforms.line_color.a * alpha);
+        }
     }
+}
 
-    return vec4<f32>(0.0, 0.0, 0.0, alpha);
+@fragment
+fn fs_unknown_fill(
+    input: VertexOutput,
+) -> @location(0) vec4<f32> {
+    if uniforms.hatching != 0u {
+        let diagonal: f32 = input.position.x + input.position.y;
+        if fract(diagonal / HATCH_SPACING) > 0.5 {
+            discard;
+        }
+        // Stripes leave gaps, so they're drawn more strongly than a plain fill.
+        return vec4<f32>(uniforms.unknown_color.rgb, 0.5);
+    }
+    return vec4<f32>(uniforms.unknown_color.rgb, 0.25);
 }
@@ -0,0 +1,54 @@
+struct Uniforms {
+    scale: vec2<f32>,
+}
+
+@group(0)
+@binding(0)
+var<uniform> uniforms: Uniforms;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    // Where in the glyph's 5 by 7 cell the fragment is.
+    @location(0) cell: vec2<f32>,
+    @location(1) @interpolate(flat) glyph: vec2<u32>,
+    @location(2) color: vec4<f32>,
+}
+
+// Each instance is a character, or a solid box behind some, stretched over its rectangle. The
+// rectangle's corner is measured from the top left of the pane.
+@vertex
+fn vs_main(
+    @location(0) vertex: vec2<f32>,
+    @location(1) rect: vec4<f32>,
+    @location(2) glyph: vec2<u32>,
+    @location(3) color: vec4<f32>,
+) -> VertexOutput {
+    let position: vec2<f32> = rect.xy + vertex * rect.zw;
+
+    var result: VertexOutput;
+    result.position = vec4<f32>(
+        position.x * uniforms.scale.x - 1.0,
+        1.0 - position.y * uniforms.scale.y,
+        0.0,
+        1.0
+    );
+    result.cell = vertex * vec2<f32>(5.0, 7.0);
+    result.glyph = glyph;
+    result.color = color;
+    return result;
+}
+
+// Glyphs are five columns of seven bits, the top row in the lowest bit, with the first four
+// columns in the bytes of one word and the last in the next.
+@fragment
+fn fs_main(
+    input: VertexOutput,
+) -> @location(0) vec4<f32> {
+    let column: u32 = min(u32(input.cell.x), 4u);
+    let row: u32 = min(u32(input.cell.y), 6u);
+    let bits: u32 = select(input.glyph.x >> (column * 8u), input.glyph.y, column == 4u);
+    if ((bits >> row) & 1u) == 0u {
+        discard;
+    }
+    return input.color;
+}
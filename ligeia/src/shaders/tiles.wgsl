@@ -0,0 +1,104 @@
+// Shared by the shaders that draw a track from a tile of its signal's changes, straight out of
+// the tile pool, and put in front of each of them. Tiles are packed by `pack_tile`, in
+// `residency.rs`. Each draw is of one tile, with uniforms of its own.
+
+struct Uniforms {
+    // Of the pane, in pixels.
+    size: vec2<f32>,
+    feather_fraction: f32,
+    line_width: f32,
+    // A bucket of the tile, and where it starts in pixels from the left of the pane. Positions
+    // are worked out from one near the pane so that they stay precise however far in it's zoomed.
+    origin: vec2<f32>,
+    bucket_width: f32,
+    // Where the last change stops being drawn, at the end of the tile or of the waveform.
+    end: f32,
+    // The top and bottom of the track, in pixels from the top of the pane.
+    extent: vec2<f32>,
+    // Where the tile starts in the pool, and how far apart its changes are, in words.
+    offset: u32,
+    stride: u32,
+    changes: u32,
+    // How values are packed: two-logic, four-logic or nine-logic, numbered as below.
+    ty: u32,
+    // Length of the dashes of high impedance lines, in pixels.
+    dash_length: f32,
+    // Nonzero to hatch unknown regions, so that they don't rely on color alone.
+    hatching: u32,
+    line_color: vec4<f32>,
+    unknown_color: vec4<f32>,
+    high_impedance_color: vec4<f32>,
+}
+
+@group(0)
+@binding(0)
+var<uniform> uniforms: Uniforms;
+
+@group(0)
+@binding(1)
+var<storage, read> pool: array<u32>;
+
+// Flags in the top bits of each change's bucket: 1 << 31 if other changes in the bucket were
+// left out, 1 << 30 if the value has unknown bits, 1 << 29 if a change that was left out did,
+// and 1 << 28 if the change is only the value the tile starts with.
+let GLITCH: u32 = 2147483648u;
+let UNKNOWN: u32 = 1073741824u;
+let DROPPED_UNKNOWN: u32 = 536870912u;
+let INITIAL: u32 = 268435456u;
+let BUCKET_MASK: u32 = 268435455u;
+
+let TWO_LOGIC: u32 = 0u;
+let FOUR_LOGIC: u32 = 1u;
+let NINE_LOGIC: u32 = 2u;
+
+// How far in from the top and bottom of tracks values are drawn, in pixels, when there's room.
+let PADDING: f32 = 4.0;
+
+// How far past the edges of the pane anything is drawn, in pixels. Points further out are
+// pulled in to it, so that lines to them still cross the pane without losing precision.
+let OFFSCREEN: f32 = 64.0;
+
+// The bucket word of a change, with its flags.
+fn change_word(change: u32) -> u32 {
+    return pool[uniforms.offset + change * uniforms.stride];
+}
+
+// A word of a change's value, which follows its bucket.
+fn value_word(change: u32, index: u32) -> u32 {
+    return pool[uniforms.offset + change * uniforms.stride + 1u + index];
+}
+
+fn clamp_x(x: f32) -> f32 {
+    return clamp(x, -OFFSCREEN, uniforms.size.x + OFFSCREEN);
+}
+
+// Where a change starts, in pixels from the left of the pane.
+fn change_start(change: u32) -> f32 {
+    let bucket: f32 = f32(change_word(change) & BUCKET_MASK);
+    let x: f32 = uniforms.origin.y + (bucket - uniforms.origin.x) * uniforms.bucket_width;
+    return clamp_x(min(x, uniforms.end));
+}
+
+// Where a change ends, at the next one or the end of the tile.
+fn change_end(change: u32) -> f32 {
+    if change + 1u < uniforms.changes {
+        return change_start(change + 1u);
+    }
+    return clamp_x(uniforms.end);
+}
+
+// From pixels from the top left of the pane.
+fn to_clip(point: vec2<f32>) -> vec4<f32> {
+    return vec4<f32>(
+        point.x * 2.0 / uniforms.size.x - 1.0,
+        1.0 - point.y * 2.0 / uniforms.size.y,
+        0.0,
+        1.0
+    );
+}
+
+// How far in from the top and bottom of the track values are drawn, in pixels. Thin tracks, like
+// the thumbnails in the minimap, can't spare all of `PADDING`.
+fn track_padding() -> f32 {
+    return min(PADDING, (uniforms.extent.y - uniforms.extent.x) * 0.25);
+}
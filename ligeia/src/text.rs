@@ -0,0 +1,405 @@
+//! Draws the labels of a scene, like the values of buses, with `shaders/text.wgsl` and a built-in
+//! bitmap font.
+//!
+//! Each character is an instance of its own, with its glyph's bits given to the shader as they
+//! are, so there's no font texture to build or sample.
+
+use std::mem;
+
+use wgpu::util::DeviceExt;
+
+use crate::{
+    panes::Panes,
+    render_graph::Pass,
+    scene::{Label, PaneScene, Scene},
+    uploads::Uploader,
+};
+
+/// How many pixels each dot of the font takes up, across and down.
+const SCALE: f32 = 2.0;
+/// How big each character is, in pixels.
+pub const GLYPH_WIDTH: f32 = 5.0 * SCALE;
+pub const GLYPH_HEIGHT: f32 = 7.0 * SCALE;
+/// How far apart characters are, in pixels.
+const ADVANCE: f32 = GLYPH_WIDTH + SCALE;
+/// How far the backgrounds of labels reach past their text, in pixels.
+pub const BACKGROUND_PADDING: f32 = 3.0;
+/// A glyph with every dot set, for the backgrounds of labels.
+const SOLID: [u32; 2] = [u32::MAX, u32::MAX];
+
+/// The printable ASCII characters, from space to `~`, as columns of seven dots left to right,
+/// with the top dot in the lowest bit.
+const GLYPHS: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // space
+    [0x00, 0x00, 0x5f, 0x00, 0x00], // !
+    [0x00, 0x07, 0x00, 0x07, 0x00], // "
+    [0x14, 0x7f, 0x14, 0x7f, 0x14], // #
+    [0x24, 0x2a, 0x7f, 0x2a, 0x12], // $
+    [0x23, 0x13, 0x08, 0x64, 0x62], // %
+    [0x36, 0x49, 0x55, 0x22, 0x50], // &
+    [0x00, 0x05, 0x03, 0x00, 0x00], // '
+    [0x00, 0x1c, 0x22, 0x41, 0x00], // (
+    [0x00, 0x41, 0x22, 0x1c, 0x00], // )
+    [0x08, 0x2a, 0x1c, 0x2a, 0x08], // *
+    [0x08, 0x08, 0x3e, 0x08, 0x08], // +
+    [0x00, 0x50, 0x30, 0x00, 0x00], // ,
+    [0x08, 0x08, 0x08, 0x08, 0x08], // -
+    [0x00, 0x60, 0x60, 0x00, 0x00], // .
+    [0x20, 0x10, 0x08, 0x04, 0x02], // /
+    [0x3e, 0x51, 0x49, 0x45, 0x3e], // 0
+    [0x00, 0x42, 0x7f, 0x40, 0x00], // 1
+    [0x42, 0x61, 0x51, 0x49, 0x46], // 2
+    [0x21, 0x41, 0x45, 0x4b, 0x31], // 3
+    [0x18, 0x14, 0x12, 0x7f, 0x10], // 4
+    [0x27, 0x45, 0x45, 0x45, 0x39], // 5
+    [0x3c, 0x4a, 0x49, 0x49, 0x30], // 6
+    [0x01, 0x71, 0x09, 0x05, 0x03], // 7
+    [0x36, 0x49, 0x49, 0x49, 0x36], // 8
+    [0x06, 0x49, 0x49, 0x29, 0x1e], // 9
+    [0x00, 0x36, 0x36, 0x00, 0x00], // :
+    [0x00, 0x56, 0x36, 0x00, 0x00], // ;
+    [0x08, 0x14, 0x22, 0x41, 0x00], // <
+    [0x14, 0x14, 0x14, 0x14, 0x14], // =
+    [0x00, 0x41, 0x22, 0x14, 0x08], // >
+    [0x02, 0x01, 0x51, 0x09, 0x06], // ?
+    [0x32, 0x49, 0x79, 0x41, 0x3e], // @
+    [0x7e, 0x11, 0x11, 0x11, 0x7e], // A
+    [0x7f, 0x49, 0x49, 0x49, 0x36], // B
+    [0x3e, 0x41, 0x41, 0x41, 0x22], // C
+    [0x7f, 0x41, 0x41, 0x22, 0x1c], // D
+    [0x7f, 0x49, 0x49, 0x49, 0x41], // E
+    [0x7f, 0x09, 0x09, 0x09, 0x01], // F
+    [0x3e, 0x41, 0x49, 0x49, 0x7a], // G
+    [0x7f, 0x08, 0x08, 0x08, 0x7f], // H
+    [0x00, 0x41, 0x7f, 0x41, 0x00], // I
+    [0x20, 0x40, 0x41, 0x3f, 0x01], // J
+    [0x7f, 0x08, 0x14, 0x22, 0x41], // K
+    [0x7f, 0x40, 0x40, 0x40, 0x40], // L
+    [0x7f, 0x02, 0x0c, 0x02, 0x7f], // M
+    [0x7f, 0x04, 0x08, 0x10, 0x7f], // N
+    [0x3e, 0x41, 0x41, 0x41, 0x3e], // O
+    [0x7f, 0x09, 0x09, 0x09, 0x06], // P
+    [0x3e, 0x41, 0x51, 0x21, 0x5e], // Q
+    [0x7f, 0x09, 0x19, 0x29, 0x46], // R
+    [0x46, 0x49, 0x49, 0x49, 0x31], // S
+    [0x01, 0x01, 0x7f, 0x01, 0x01], // T
+    [0x3f, 0x40, 0x40, 0x40, 0x3f], // U
+    [0x1f, 0x20, 0x40, 0x20, 0x1f], // V
+    [0x3f, 0x40, 0x38, 0x40, 0x3f], // W
+    [0x63, 0x14, 0x08, 0x14, 0x63], // X
+    [0x07, 0x08, 0x70, 0x08, 0x07], // Y
+    [0x61, 0x51, 0x49, 0x45, 0x43], // Z
+    [0x00, 0x7f, 0x41, 0x41, 0x00], // [
+    [0x02, 0x04, 0x08, 0x10, 0x20], // backslash
+    [0x00, 0x41, 0x41, 0x7f, 0x00], // ]
+    [0x04, 0x02, 0x01, 0x02, 0x04], // ^
+    [0x40, 0x40, 0x40, 0x40, 0x40], // _
+    [0x00, 0x01, 0x02, 0x04, 0x00], // `
+    [0x20, 0x54, 0x54, 0x54, 0x78], // a
+    [0x7f, 0x48, 0x44, 0x44, 0x38], // b
+    [0x38, 0x44, 0x44, 0x44, 0x20], // c
+    [0x38, 0x44, 0x44, 0x48, 0x7f], // d
+    [0x38, 0x54, 0x54, 0x54, 0x18], // e
+    [0x08, 0x7e, 0x09, 0x01, 0x02], // f
+    [0x0c, 0x52, 0x52, 0x52, 0x3e], // g
+    [0x7f, 0x08, 0x04, 0x04, 0x78], // h
+    [0x00, 0x44, 0x7d, 0x40, 0x00], // i
+    [0x20, 0x40, 0x44, 0x3d, 0x00], // j
+    [0x7f, 0x10, 0x28, 0x44, 0x00], // k
+    [0x00, 0x41, 0x7f, 0x40, 0x00], // l
+    [0x7c, 0x04, 0x18, 0x04, 0x78], // m
+    [0x7c, 0x08, 0x04, 0x04, 0x78], // n
+    [0x38, 0x44, 0x44, 0x44, 0x38], // o
+    [0x7c, 0x14, 0x14, 0x14, 0x08], // p
+    [0x08, 0x14, 0x14, 0x18, 0x7c], // q
+    [0x7c, 0x08, 0x04, 0x04, 0x08], // r
+    [0x48, 0x54, 0x54, 0x54, 0x20], // s
+    [0x04, 0x3f, 0x44, 0x40, 0x20], // t
+    [0x3c, 0x40, 0x40, 0x20, 0x7c], // u
+    [0x1c, 0x20, 0x40, 0x20, 0x1c], // v
+    [0x3c, 0x40, 0x30, 0x40, 0x3c], // w
+    [0x44, 0x28, 0x10, 0x28, 0x44], // x
+    [0x0c, 0x50, 0x50, 0x50, 0x3c], // y
+    [0x44, 0x64, 0x54, 0x4c, 0x44], // z
+    [0x00, 0x08, 0x36, 0x41, 0x00], // {
+    [0x00, 0x00, 0x7f, 0x00, 0x00], // |
+    [0x00, 0x41, 0x36, 0x08, 0x00], // }
+    [0x08, 0x04, 0x08, 0x10, 0x08], // ~
+];
+
+/// The glyph of `c`, packed the way the shader reads it. Anything outside of printable ASCII is
+/// shown as `?`.
+fn glyph(c: char) -> [u32; 2] {
+    let index = match c {
+        ' '..='~' => c as usize - ' ' as usize,
+        _ => '?' as usize - ' ' as usize,
+    };
+    let [a, b, c, d, e] = GLYPHS[index];
+    [u32::from_le_bytes([a, b, c, d]), e as u32]
+}
+
+/// How wide `text` is when it's drawn, in pixels.
+pub fn text_width(text: &str) -> f32 {
+    match text.chars().count() {
+        0 => 0.0,
+        len => len as f32 * ADVANCE - SCALE,
+    }
+}
+
+#[derive(Copy, Clone, bytemuck::NoUninit)]
+#[repr(C)]
+struct Uniforms {
+    scale: [f32; 2],
+}
+
+/// A character, or the background of a label, as the vertex shader takes it.
+#[derive(Copy, Clone, bytemuck::NoUninit)]
+#[repr(C)]
+struct Instance {
+    /// Left, top, width and height, in pixels.
+    rect: [f32; 4],
+    glyph: [u32; 2],
+    color: [f32; 4],
+}
+
+fn create_render_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("text"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &[
+                wgpu::VertexBufferLayout {
+                    array_stride: mem::size_of::<[f32; 2]>() as _,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x2],
+                },
+                wgpu::VertexBufferLayout {
+                    array_stride: mem::size_of::<Instance>() as _,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &wgpu::vertex_attr_array![
+                        1 => Float32x4,
+                        2 => Uint32x2,
+                        3 => Float32x4,
+                    ],
+                },
+            ],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: "fs_main",
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::all(),
+            })],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            ..Default::default()
+        },
+        multiview: None,
+    })
+}
+
+/// The buffers for drawing one pane's labels.
+struct PaneResources {
+    uniform_buffer: wgpu::Buffer,
+    instances_buffer: wgpu::Buffer,
+    /// How many instances fit in `instances_buffer`.
+    capacity: usize,
+    bind_group: wgpu::BindGroup,
+    /// How many instances were uploaded for this frame.
+    len: usize,
+}
+
+pub struct TextPass {
+    shader: wgpu::ShaderModule,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    /// Built by `configure`, since it depends on the sample count.
+    pipeline: Option<wgpu::RenderPipeline>,
+    vertices_buffer: wgpu::Buffer,
+    panes: Vec<PaneResources>,
+    /// Reused from frame to frame to lay out each pane's characters.
+    instances: Vec<Instance>,
+}
+
+impl TextPass {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/text.wgsl"));
+
+        let vertices: &[[f32; 2]] = &[
+            [0.0, 0.0],
+            [1.0, 0.0],
+            [1.0, 1.0],
+            [0.0, 0.0],
+            [1.0, 1.0],
+            [0.0, 1.0],
+        ];
+        let vertices_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::cast_slice(vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let mut pass = Self {
+            shader,
+            bind_group_layout,
+            pipeline_layout,
+            pipeline: None,
+            vertices_buffer,
+            panes: vec![],
+            instances: vec![],
+        };
+        pass.panes = (0..Panes::MAX)
+            .map(|_| pass.create_pane_resources(device, 0))
+            .collect();
+        pass
+    }
+
+    /// Buffers for a pane with room for at least `instances` instances.
+    fn create_pane_resources(&self, device: &wgpu::Device, instances: usize) -> PaneResources {
+        let capacity = instances.next_power_of_two().max(64);
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: mem::size_of::<Uniforms>() as _,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let instances_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: (capacity * mem::size_of::<Instance>()) as _,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+
+        PaneResources {
+            uniform_buffer,
+            instances_buffer,
+            capacity,
+            bind_group,
+            len: 0,
+        }
+    }
+}
+
+impl Pass for TextPass {
+    fn configure(&mut self, device: &wgpu::Device, format: wgpu::TextureFormat, sample_count: u32) {
+        self.pipeline = Some(create_render_pipeline(
+            device,
+            &self.pipeline_layout,
+            &self.shader,
+            format,
+            sample_count,
+        ));
+    }
+
+    fn prepare(&mut self, uploader: &mut Uploader, scene: &Scene) {
+        for i in 0..scene.panes.len() {
+            let PaneScene { height, labels, .. } = &scene.panes[i];
+
+            self.instances.clear();
+            for Label {
+                text,
+                position: [x, y],
+                color,
+                background,
+            } in labels
+            {
+                if let Some(background) = background {
+                    self.instances.push(Instance {
+                        rect: [
+                            x - BACKGROUND_PADDING,
+                            y - BACKGROUND_PADDING,
+                            text_width(text) + BACKGROUND_PADDING * 2.0,
+                            GLYPH_HEIGHT + BACKGROUND_PADDING * 2.0,
+                        ],
+                        glyph: SOLID,
+                        color: *background,
+                    });
+                }
+                self.instances
+                    .extend(text.chars().enumerate().map(|(i, c)| Instance {
+                        rect: [x + i as f32 * ADVANCE, *y, GLYPH_WIDTH, GLYPH_HEIGHT],
+                        glyph: glyph(c),
+                        color: *color,
+                    }));
+            }
+
+            if self.instances.len() > self.panes[i].capacity {
+                self.panes[i] = self.create_pane_resources(uploader.device(), self.instances.len());
+            }
+            let resources = &mut self.panes[i];
+            uploader.write(
+                &resources.instances_buffer,
+                0,
+                bytemuck::cast_slice(&self.instances),
+            );
+            uploader.write(
+                &resources.uniform_buffer,
+                0,
+                bytemuck::bytes_of(&Uniforms {
+                    scale: [2.0 / scene.width as f32, 2.0 / height],
+                }),
+            );
+            resources.len = self.instances.len();
+        }
+    }
+
+    fn draw<'a>(&'a self, rpass: &mut wgpu::RenderPass<'a>, scene: &Scene) {
+        let pipeline = match &self.pipeline {
+            Some(pipeline) => pipeline,
+            None => return,
+        };
+
+        rpass.set_pipeline(pipeline);
+        rpass.set_vertex_buffer(0, self.vertices_buffer.slice(..));
+        for (pane, resources) in scene.panes.iter().zip(&self.panes) {
+            if resources.len == 0 {
+                continue;
+            }
+            rpass.set_viewport(0.0, pane.top, scene.width as f32, pane.height, 0.0, 1.0);
+            rpass.set_bind_group(0, &resources.bind_group, &[]);
+            rpass.set_vertex_buffer(1, resources.instances_buffer.slice(..));
+            rpass.draw(0..6, 0..resources.len as u32);
+        }
+    }
+}
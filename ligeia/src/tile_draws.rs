@@ -0,0 +1,299 @@
+//! Drawing tracks a tile at a time, straight out of the [tile pool](crate::residency), for the
+//! passes that generate a track's vertices from its signal's changes. Their shaders go after
+//! `shaders/common.wgsl` and `shaders/tiles.wgsl`.
+//!
+//! Every tile drawn has uniforms of its own, in one buffer that's bound at a different offset for
+//! each, along with the whole pool.
+
+use std::{mem, num::NonZeroU64, sync::Arc};
+
+use ligeia_core::meta::StorageType;
+use wgpu::util::DeviceExt;
+
+use crate::{
+    scene::{PaneScene, Scene, TileDraw},
+    uploads::Uploader,
+};
+
+/// How thick the lines of tracks are, in pixels.
+pub const LINE_WIDTH: f32 = 2.0;
+/// How long the dashes of high impedance lines are, in pixels.
+const DASH_LENGTH: f32 = 4.0;
+/// How far apart each draw's uniforms are in their buffer, in bytes. Offsets into uniform
+/// buffers have to be aligned to this on some devices.
+const UNIFORMS_STRIDE: usize = 256;
+
+/// Laid out like `Uniforms` in `shaders/tiles.wgsl`.
+#[derive(Copy, Clone, bytemuck::NoUninit)]
+#[repr(C)]
+struct Uniforms {
+    size: [f32; 2],
+    feather_fraction: f32,
+    line_width: f32,
+    origin: [f32; 2],
+    bucket_width: f32,
+    end: f32,
+    extent: [f32; 2],
+    offset: u32,
+    stride: u32,
+    changes: u32,
+    ty: u32,
+    dash_length: f32,
+    hatching: u32,
+    line_color: [f32; 4],
+    unknown_color: [f32; 4],
+    high_impedance_color: [f32; 4],
+}
+
+const VERTEX_ATTRIBUTES: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![0 => Float32x2];
+
+/// A pipeline that draws tiles, with the entry points `vertex` and `fragment` of `shader`, and
+/// each instance given a corner of a unit quad.
+pub fn create_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    (vertex, fragment): (&str, &str),
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(vertex),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: vertex,
+            buffers: &[wgpu::VertexBufferLayout {
+                array_stride: mem::size_of::<[f32; 2]>() as _,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: &VERTEX_ATTRIBUTES,
+            }],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: fragment,
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::all(),
+            })],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            ..Default::default()
+        },
+        multiview: None,
+    })
+}
+
+/// A tile that's ready to draw.
+struct Prepared {
+    pane: usize,
+    /// The part of the canvas that the track can be seen in: x, y, width and height.
+    scissor: [u32; 4],
+    changes: u32,
+}
+
+/// The tiles a pass draws for a frame, and the buffers they're drawn with.
+pub struct TileDraws {
+    pool: Arc<wgpu::Buffer>,
+    bind_group_layout: wgpu::BindGroupLayout,
+    vertices_buffer: wgpu::Buffer,
+    uniform_buffer: wgpu::Buffer,
+    /// How many draws' uniforms fit in `uniform_buffer`.
+    capacity: usize,
+    bind_group: wgpu::BindGroup,
+    /// Reused from frame to frame to lay out the uniforms.
+    uniforms: Vec<u8>,
+    prepared: Vec<Prepared>,
+}
+
+impl TileDraws {
+    pub fn new(device: &wgpu::Device, pool: Arc<wgpu::Buffer>) -> Self {
+        let vertices: &[[f32; 2]] = &[
+            [0.0, -0.5],
+            [1.0, -0.5],
+            [1.0, 0.5],
+            [0.0, -0.5],
+            [1.0, 0.5],
+            [0.0, 0.5],
+        ];
+        let vertices_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::cast_slice(vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: NonZeroU64::new(mem::size_of::<Uniforms>() as _),
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let (uniform_buffer, capacity, bind_group) =
+            Self::create_uniforms(device, &bind_group_layout, &pool, 0);
+        Self {
+            pool,
+            bind_group_layout,
+            vertices_buffer,
+            uniform_buffer,
+            capacity,
+            bind_group,
+            uniforms: vec![],
+            prepared: vec![],
+        }
+    }
+
+    /// For the layouts of pipelines that draw tiles.
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    /// A uniform buffer with room for at least `draws` draws, and a bind group with it and the
+    /// pool.
+    fn create_uniforms(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        pool: &wgpu::Buffer,
+        draws: usize,
+    ) -> (wgpu::Buffer, usize, wgpu::BindGroup) {
+        let capacity = draws.next_power_of_two().max(16);
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: (capacity * UNIFORMS_STRIDE) as _,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: &uniform_buffer,
+                        offset: 0,
+                        size: NonZeroU64::new(mem::size_of::<Uniforms>() as _),
+                    }),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: pool.as_entire_binding(),
+                },
+            ],
+        });
+        (uniform_buffer, capacity, bind_group)
+    }
+
+    /// Upload the uniforms of the tiles that `tiles` picks out of each pane.
+    pub fn prepare(
+        &mut self,
+        uploader: &mut Uploader,
+        scene: &Scene,
+        tiles: fn(&PaneScene) -> &[TileDraw],
+    ) {
+        self.prepared.clear();
+        self.uniforms.clear();
+        for (i, pane) in scene.panes.iter().enumerate() {
+            for draw in tiles(pane) {
+                // Tracks can be cut off by the edges of the canvas as well as their region.
+                let [left, top, right, bottom] = draw.clip;
+                let left = left.max(0.0) as u32;
+                let right = (right.ceil() as u32).min(scene.width);
+                let top = (pane.top + top).max(0.0) as u32;
+                let bottom = ((pane.top + bottom).ceil() as u32).min(scene.height);
+                if right <= left || bottom <= top || draw.changes == 0 {
+                    continue;
+                }
+                self.prepared.push(Prepared {
+                    pane: i,
+                    scissor: [left, top, right - left, bottom - top],
+                    changes: draw.changes,
+                });
+
+                let uniforms = Uniforms {
+                    size: [scene.width as f32, pane.height],
+                    feather_fraction: scene.feather_fraction,
+                    line_width: LINE_WIDTH,
+                    origin: draw.origin,
+                    bucket_width: draw.bucket_width,
+                    end: draw.end,
+                    extent: draw.extent,
+                    offset: (draw.offset / 4) as u32,
+                    stride: draw.stride / 4,
+                    changes: draw.changes,
+                    ty: match draw.ty {
+                        StorageType::TwoLogic => 0,
+                        StorageType::FourLogic => 1,
+                        StorageType::NineLogic => 2,
+                        // Their values aren't read on the GPU.
+                        StorageType::Utf8 | StorageType::Event => 0,
+                    },
+                    dash_length: DASH_LENGTH,
+                    hatching: scene.hatching as u32,
+                    line_color: scene.colors.line,
+                    unknown_color: scene.colors.unknown,
+                    high_impedance_color: scene.colors.high_impedance,
+                };
+                let start = self.uniforms.len();
+                self.uniforms
+                    .extend_from_slice(bytemuck::bytes_of(&uniforms));
+                self.uniforms.resize(start + UNIFORMS_STRIDE, 0);
+            }
+        }
+
+        if self.prepared.len() > self.capacity {
+            let device = uploader.device();
+            (self.uniform_buffer, self.capacity, self.bind_group) = Self::create_uniforms(
+                device,
+                &self.bind_group_layout,
+                &self.pool,
+                self.prepared.len(),
+            );
+        }
+        uploader.write(&self.uniform_buffer, 0, &self.uniforms);
+    }
+
+    /// Draw every tile prepared for this frame, with `instances` instances for each change.
+    pub fn draw<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        scene: &Scene,
+        pipeline: &'a wgpu::RenderPipeline,
+        instances: u32,
+    ) {
+        rpass.set_pipeline(pipeline);
+        rpass.set_vertex_buffer(0, self.vertices_buffer.slice(..));
+        for (i, prepared) in self.prepared.iter().enumerate() {
+            let pane = &scene.panes[prepared.pane];
+            rpass.set_viewport(0.0, pane.top, scene.width as f32, pane.height, 0.0, 1.0);
+            let [x, y, width, height] = prepared.scissor;
+            rpass.set_scissor_rect(x, y, width, height);
+            let offset = (i * UNIFORMS_STRIDE) as u32;
+            rpass.set_bind_group(0, &self.bind_group, &[offset]);
+            rpass.draw(0..6, 0..prepared.changes * instances);
+        }
+    }
+}
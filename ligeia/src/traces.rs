@@ -0,0 +1,172 @@
+//! The signals chosen for display, by hierarchical name, some of them gathered into named
+//! groups.
+//!
+//! Signals are kept by name rather than by id so that they survive the file being reloaded.
+//! Highlighting rules on them are kept as the text of their conditions for the same reason, and
+//! so are the reset that shades every track and the clock they're sampled on.
+
+use std::collections::HashSet;
+
+use ligeia_core::resample::Edge;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Trace {
+    Signal(String),
+    Group { name: String, signals: Vec<String> },
+}
+
+/// Tints the track of a signal wherever a condition holds, like `value == 0xdead`, with
+/// `value` standing for the signal. See [`Condition::parse_for`].
+///
+/// [`Condition::parse_for`]: ligeia_core::condition::Condition::parse_for
+#[derive(Debug, Clone, PartialEq)]
+pub struct Highlight {
+    pub signal: String,
+    pub condition: String,
+    /// RGB, or the palette's color for unknown values if `None`.
+    pub color: Option<[f32; 3]>,
+}
+
+/// Shades the background of every track while a reset signal is asserted, so behavior after
+/// reset stands apart from what happens during it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reset {
+    pub signal: String,
+    /// Whether the reset is asserted when low, rather than when high.
+    pub active_low: bool,
+}
+
+impl Reset {
+    /// The condition that holds while the reset is asserted, for
+    /// [`Condition::parse_for`](ligeia_core::condition::Condition::parse_for).
+    pub fn condition(&self) -> &'static str {
+        match self.active_low {
+            true => "!value",
+            false => "value",
+        }
+    }
+}
+
+/// Shows each track's value only as it is at every edge of a clock, a cycle at a time, the way
+/// registers clocked by it see them. See [`sample_on_edges`].
+///
+/// [`sample_on_edges`]: ligeia_core::resample::sample_on_edges
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sampling {
+    pub clock: String,
+    pub edge: Edge,
+}
+
+#[derive(Debug, Default)]
+pub struct Traces {
+    traces: Vec<Trace>,
+    highlights: Vec<Highlight>,
+    /// Signals whose tracks stay at the top. See [`TrackLayout`](crate::layout::TrackLayout).
+    pinned: HashSet<String>,
+    reset: Option<Reset>,
+    sampling: Option<Sampling>,
+}
+
+impl Traces {
+    /// Every signal, whether on its own or in a group.
+    pub fn signals(&self) -> impl Iterator<Item = &str> {
+        self.traces
+            .iter()
+            .flat_map(|trace| match trace {
+                Trace::Signal(path) => std::slice::from_ref(path),
+                Trace::Group { signals, .. } => signals.as_slice(),
+            })
+            .map(String::as_str)
+    }
+
+    pub fn add_signal(&mut self, path: String) {
+        self.traces.push(Trace::Signal(path));
+    }
+
+    /// Add several signals at once as a group, like every signal matching a pattern.
+    ///
+    /// Adding a group with the same name as an existing one replaces its signals, so running the
+    /// same search again refreshes the group rather than duplicating it.
+    pub fn add_group(&mut self, name: String, signals: Vec<String>) {
+        let existing = self.traces.iter_mut().find_map(|trace| match trace {
+            Trace::Group {
+                name: existing,
+                signals,
+            } if *existing == name => Some(signals),
+            _ => None,
+        });
+        match existing {
+            Some(existing) => *existing = signals,
+            None => self.traces.push(Trace::Group { name, signals }),
+        }
+    }
+
+    /// Group signals derived from `parent`, like its byte lanes, under it. The group is named
+    /// after `parent` and takes its place if it's shown on its own, so doing it again refreshes
+    /// the group rather than adding another.
+    pub fn group_under(&mut self, parent: &str, derived: Vec<String>) {
+        let signals = std::iter::once(parent.to_string()).chain(derived).collect();
+        let alone = self
+            .traces
+            .iter()
+            .position(|trace| matches!(trace, Trace::Signal(path) if path == parent));
+        match alone {
+            Some(i) => {
+                self.traces[i] = Trace::Group {
+                    name: parent.to_string(),
+                    signals,
+                }
+            }
+            None => self.add_group(parent.to_string(), signals),
+        }
+    }
+
+    pub fn is_pinned(&self, signal: &str) -> bool {
+        self.pinned.contains(signal)
+    }
+
+    /// Pin a signal's track to the top, or unpin it. Returns whether that changed anything.
+    pub fn set_pinned(&mut self, signal: &str, pinned: bool) -> bool {
+        match pinned {
+            true => self.pinned.insert(signal.to_string()),
+            false => self.pinned.remove(signal),
+        }
+    }
+
+    /// Every highlighting rule, in the order they were added, which is the order they're drawn.
+    pub fn highlights(&self) -> &[Highlight] {
+        &self.highlights
+    }
+
+    pub fn add_highlight(&mut self, highlight: Highlight) {
+        self.highlights.push(highlight);
+    }
+
+    /// Remove the highlighting rules on a signal, returning how many there were.
+    pub fn clear_highlights(&mut self, signal: &str) -> usize {
+        let before = self.highlights.len();
+        self.highlights
+            .retain(|highlight| highlight.signal != signal);
+        before - self.highlights.len()
+    }
+
+    pub fn reset(&self) -> Option<&Reset> {
+        self.reset.as_ref()
+    }
+
+    /// Shade tracks while `reset` is asserted, or stop shading them if it's `None`, returning the
+    /// reset that was used before.
+    pub fn set_reset(&mut self, reset: Option<Reset>) -> Option<Reset> {
+        std::mem::replace(&mut self.reset, reset)
+    }
+
+    pub fn sampling(&self) -> Option<&Sampling> {
+        self.sampling.as_ref()
+    }
+
+    /// Sample tracks on a clock's edges, or go back to showing every change if `sampling` is
+    /// `None`, returning how they were sampled before.
+    pub fn set_sampling(&mut self, sampling: Option<Sampling>) -> Option<Sampling> {
+        std::mem::replace(&mut self.sampling, sampling)
+    }
+}
@@ -0,0 +1,112 @@
+//! Named conditions, like `overflow` for `count == 0xff && valid`, that are checked whenever a
+//! file is opened. Together with watching the file, re-running a simulation jumps the view
+//! straight to where the first of them goes off.
+//!
+//! Like highlighting rules, triggers are kept as the text of their conditions so that they can be
+//! checked against each new file.
+
+use ligeia_core::{
+    condition::{Condition, ConditionError},
+    meta::Timesteps,
+    Processed,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trigger {
+    pub name: String,
+    /// A condition over any variables, like [`Condition::parse`] takes.
+    pub condition: String,
+}
+
+impl Trigger {
+    /// Parse `name=condition`, like on the command line.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s.split_once('=') {
+            Some((name, condition)) if !name.is_empty() => Ok(Self {
+                name: name.trim().to_string(),
+                condition: condition.trim().to_string(),
+            }),
+            _ => Err(format!(
+                "expected a trigger like `name=condition`, not `{}`",
+                s
+            )),
+        }
+    }
+
+    /// When the condition first holds, if it ever does.
+    pub fn first_hit(
+        &self,
+        processed: &mut Processed,
+    ) -> Result<Option<Timesteps>, ConditionError> {
+        let condition = Condition::parse(&self.condition, processed)?;
+        Ok(condition
+            .intervals(processed)?
+            .first()
+            .map(|interval| interval.start))
+    }
+}
+
+/// The earliest that any trigger goes off.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hit {
+    pub time: Timesteps,
+    /// Every trigger that goes off then.
+    pub names: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct Triggers {
+    triggers: Vec<Trigger>,
+}
+
+impl Triggers {
+    /// Every trigger, in the order they were added.
+    pub fn iter(&self) -> impl Iterator<Item = &Trigger> {
+        self.triggers.iter()
+    }
+
+    /// Add a trigger, replacing any with the same name so that it can be edited.
+    pub fn add(&mut self, trigger: Trigger) {
+        match self.triggers.iter_mut().find(|t| t.name == trigger.name) {
+            Some(existing) => *existing = trigger,
+            None => self.triggers.push(trigger),
+        }
+    }
+
+    /// Remove the trigger with this name, returning whether there was one.
+    pub fn remove(&mut self, name: &str) -> bool {
+        let before = self.triggers.len();
+        self.triggers.retain(|trigger| trigger.name != name);
+        self.triggers.len() != before
+    }
+
+    /// Check every trigger against a file, returning the first hit. Triggers that don't make
+    /// sense for the file, like ones about variables it doesn't have, are passed to `skipped`.
+    pub fn check<F>(&self, processed: &mut Processed, mut skipped: F) -> Option<Hit>
+    where
+        F: FnMut(&Trigger, ConditionError),
+    {
+        let mut first: Option<Hit> = None;
+        for trigger in &self.triggers {
+            let time = match trigger.first_hit(processed) {
+                Ok(Some(time)) => time,
+                Ok(None) => continue,
+                Err(e) => {
+                    skipped(trigger, e);
+                    continue;
+                }
+            };
+            match &mut first {
+                Some(hit) if hit.time == time => hit.names.push(trigger.name.clone()),
+                Some(hit) if hit.time < time => {}
+                _ => {
+                    first = Some(Hit {
+                        time,
+                        names: vec![trigger.name.clone()],
+                    })
+                }
+            }
+        }
+        first
+    }
+}
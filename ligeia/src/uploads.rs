@@ -0,0 +1,87 @@
+//! Streaming per-frame data, like points and text vertices, to the GPU.
+//!
+//! `Queue::write_buffer` copies into a fresh staging allocation every time, and large writes
+//! every frame end up waiting on them. Uploads go through a staging belt instead: a set of
+//! mapped chunks that data is written straight into, copied out of by the frame's command
+//! buffer, and reused once the GPU is done with them.
+
+use std::num::NonZeroU64;
+
+use wgpu::util::StagingBelt;
+
+/// How big each staging chunk is. Bigger uploads get a chunk of their own.
+const CHUNK_SIZE: wgpu::BufferAddress = 1 << 20;
+
+/// The staging chunks, which go around from frame to frame.
+///
+/// A frame's uploads go into an [`Uploader`]. [`Uploads::submit`] then submits the frame, and
+/// only once it's submitted are the chunks it wrote to taken back, to be reused as soon as the
+/// GPU has copied out of them.
+pub struct Uploads {
+    belt: StagingBelt,
+}
+
+impl Uploads {
+    pub fn new() -> Self {
+        Self {
+            belt: StagingBelt::new(CHUNK_SIZE),
+        }
+    }
+
+    /// Start uploading data for a frame, copying it as part of the commands in `encoder`.
+    pub fn uploader<'a>(
+        &'a mut self,
+        device: &'a wgpu::Device,
+        encoder: &'a mut wgpu::CommandEncoder,
+    ) -> Uploader<'a> {
+        Uploader {
+            device,
+            encoder,
+            belt: &mut self.belt,
+        }
+    }
+
+    /// Submit a frame whose uploads were written with [`Uploads::uploader`], and take back the
+    /// chunks they were written to.
+    pub fn submit(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        commands: wgpu::CommandBuffer,
+    ) {
+        // The chunks are unmapped for the copies, and mapped again once they're done.
+        self.belt.finish();
+        queue.submit([commands]);
+        self.belt.recall();
+        // Chunks come back as their copies finish, which is noticed when the device is polled.
+        device.poll(wgpu::Maintain::Poll);
+    }
+}
+
+/// Writes a frame's uploads.
+pub struct Uploader<'a> {
+    device: &'a wgpu::Device,
+    encoder: &'a mut wgpu::CommandEncoder,
+    belt: &'a mut StagingBelt,
+}
+
+impl Uploader<'_> {
+    /// The device, for passes that need to make buffers to upload into.
+    pub fn device(&self) -> &wgpu::Device {
+        self.device
+    }
+
+    /// Copy `data` into `target` at `offset`, before anything in the frame is drawn.
+    ///
+    /// Like with `Queue::write_buffer`, the length and offset have to be multiples of
+    /// `wgpu::COPY_BUFFER_ALIGNMENT`.
+    pub fn write(&mut self, target: &wgpu::Buffer, offset: wgpu::BufferAddress, data: &[u8]) {
+        let size = match NonZeroU64::new(data.len() as u64) {
+            Some(size) => size,
+            None => return,
+        };
+        self.belt
+            .write_buffer(self.encoder, target, offset, size, self.device)
+            .copy_from_slice(data);
+    }
+}
@@ -0,0 +1,132 @@
+//! Signal values written out as text, the way the viewer labels, shows and copies them.
+
+use std::ops::Range;
+
+use ligeia_core::{
+    format::{self, Radix},
+    logic::{self, Nine},
+    meta::{EnumValue, StorageId, StorageType, Timesteps},
+    states, Error, Processed,
+};
+
+/// Where a signal's values come from.
+pub enum Source {
+    /// Values `bits` of a storage: all of them for a variable, or some for a slice of one.
+    Bits {
+        storage: StorageId,
+        bits: Range<u32>,
+    },
+    /// The state that the enum in a storage is in, by name.
+    States {
+        storage: StorageId,
+        values: Vec<EnumValue>,
+    },
+}
+
+/// A signal's value at some time, as text, and how long it stays that way.
+pub struct Shown {
+    pub text: String,
+    /// When the signal took the value.
+    pub start: Timesteps,
+    /// When it next changes, if it does.
+    pub end: Option<Timesteps>,
+}
+
+impl Source {
+    fn storage(&self) -> StorageId {
+        match *self {
+            Source::Bits { storage, .. } | Source::States { storage, .. } => storage,
+        }
+    }
+
+    /// A value of the source's storage as text: numbers in hexadecimal with every digit, strings
+    /// as they are, events as `event`, and states by name.
+    pub fn format(&self, processed: &Processed, data: &[u8]) -> Result<String, Error> {
+        let storage = processed.storage(self.storage())?;
+        let (ty, width) = (storage.ty, storage.width);
+        let bits = match self {
+            Source::Bits { bits, .. } => bits.start as usize..bits.end as usize,
+            Source::States { values, .. } => {
+                let state = states::resolve(values, ty, width, data);
+                return Ok(state
+                    .map_or("(none)", |state| values[state].name.as_str())
+                    .to_owned());
+            }
+        };
+
+        let mut values = vec![];
+        match ty {
+            StorageType::TwoLogic => logic::unpack_two(data, width as usize, &mut values),
+            StorageType::FourLogic => logic::unpack_four(data, width as usize, &mut values),
+            // Weak values are read as the level they're pulled to, like `H` and `L` in VHDL.
+            StorageType::NineLogic => values.extend(
+                data[..width as usize]
+                    .iter()
+                    .map(|&value| Nine::from_u8(value).map_or(2, Nine::level)),
+            ),
+            StorageType::Utf8 => return Ok(processed.string(data).to_owned()),
+            StorageType::Event => return Ok("event".to_owned()),
+        }
+        Ok(format::format_values(&values[bits], Radix::Hexadecimal))
+    }
+
+    /// The value at `time`, or `None` if the storage hadn't changed yet.
+    ///
+    /// Changes to the storage that leave the text as it was, like changes to bits outside a
+    /// slice, are part of the same stable stretch.
+    pub fn value_at(
+        &self,
+        processed: &mut Processed,
+        time: Timesteps,
+    ) -> Result<Option<Shown>, Error> {
+        let storage = self.storage();
+        let value = match processed.value_at(storage, time)? {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        let text = self.format(processed, &value.data)?;
+
+        let mut start = value.start;
+        while start.0 > 0 {
+            match processed.value_at(storage, Timesteps(start.0 - 1))? {
+                Some(before) if self.format(processed, &before.data)? == text => {
+                    start = before.start
+                }
+                _ => break,
+            }
+        }
+        let mut end = value.end;
+        while let Some(next) = end {
+            match processed.value_at(storage, next)? {
+                Some(after) if self.format(processed, &after.data)? == text => end = after.end,
+                _ => break,
+            }
+        }
+
+        Ok(Some(Shown { text, start, end }))
+    }
+
+    /// Every change over `range` as text, with changes that leave the text as it was left out.
+    ///
+    /// Like with [`Processed::changes_in_range_with_initial`], the value already in effect at the
+    /// start of the range comes first, with the time it started.
+    pub fn changes_in_range(
+        &self,
+        processed: &mut Processed,
+        range: Range<Timesteps>,
+    ) -> Result<Vec<(Timesteps, String)>, Error> {
+        let mut changes = vec![];
+        processed.changes_in_range_with_initial(self.storage(), range, |time, data| {
+            changes.push((time, data.to_vec()))
+        })?;
+
+        let mut shown: Vec<(Timesteps, String)> = vec![];
+        for (time, data) in changes {
+            let text = self.format(processed, &data)?;
+            if shown.last().map(|(_, last)| last) != Some(&text) {
+                shown.push((time, text));
+            }
+        }
+        Ok(shown)
+    }
+}
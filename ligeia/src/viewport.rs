@@ -0,0 +1,74 @@
+/// The visible window onto the time axis.
+#[derive(Debug, Copy, Clone)]
+pub struct Viewport {
+    /// Time at the left edge of the canvas.
+    pub start: f64,
+    /// Time at the right edge of the canvas.
+    pub end: f64,
+    /// Width of the canvas in pixels.
+    pub width: f64,
+}
+
+impl Viewport {
+    /// Don't let the visible span collapse to nothing.
+    const MIN_SPAN: f64 = 1e-3;
+
+    pub fn new(start: f64, end: f64, width: f64) -> Self {
+        let mut viewport = Self { start, end, width };
+        viewport.zoom_to(start, end);
+        viewport
+    }
+
+    pub fn span(&self) -> f64 {
+        self.end - self.start
+    }
+
+    pub fn time_at(&self, x: f64) -> f64 {
+        self.start + x / self.width * self.span()
+    }
+
+    pub fn x_at(&self, time: f64) -> f64 {
+        (time - self.start) / self.span() * self.width
+    }
+
+    pub fn zoom_to(&mut self, start: f64, end: f64) {
+        let (start, end) = if start <= end {
+            (start, end)
+        } else {
+            (end, start)
+        };
+
+        let middle = (start + end) / 2.0;
+        let half_span = ((end - start) / 2.0).max(Self::MIN_SPAN / 2.0);
+        self.start = middle - half_span;
+        self.end = middle + half_span;
+    }
+
+    /// Move the visible window so `time` is in the middle, without changing the zoom level.
+    pub fn center_on(&mut self, time: f64) {
+        let half_span = self.span() / 2.0;
+        self.zoom_to(time - half_span, time + half_span);
+    }
+
+    /// Move the visible window by `pixels` to the right, or left if it's negative, without
+    /// changing the zoom level.
+    pub fn pan_by(&mut self, pixels: f64) {
+        let offset = pixels / self.width * self.span();
+        self.zoom_to(self.start + offset, self.end + offset);
+    }
+
+    /// Fit the whole simulation, as given by its first and last timestamps.
+    pub fn zoom_full(&mut self, (first, last): (f64, f64)) {
+        self.zoom_to(first, last);
+    }
+
+    /// Scale the visible span by `factor` while keeping `time` at the same position on screen.
+    ///
+    /// A `factor` less than one zooms in.
+    pub fn zoom_around(&mut self, time: f64, factor: f64) {
+        self.zoom_to(
+            time - (time - self.start) * factor,
+            time + (self.end - time) * factor,
+        );
+    }
+}
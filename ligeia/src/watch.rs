@@ -0,0 +1,89 @@
+//! Watching the open file, so a re-run simulation shows up without reopening it.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use winit::event_loop::EventLoopProxy;
+
+use crate::commands::UserEvent;
+
+/// How long a file has to go without changing before it's reloaded, so that a simulator still
+/// writing it doesn't cause a reload for every write.
+const SETTLE_TIME: Duration = Duration::from_millis(500);
+
+/// A file being watched. Dropping it stops watching.
+pub struct Watch {
+    path: PathBuf,
+    _watcher: RecommendedWatcher,
+}
+
+impl Watch {
+    /// Start watching `path`, sending [`UserEvent::Changed`] with it once it's changed and
+    /// settled down.
+    ///
+    /// The directory the file is in is what's actually watched, since simulators often replace
+    /// the file rather than writing to it in place.
+    pub fn new(path: PathBuf, proxy: EventLoopProxy<UserEvent>) -> Result<Self, String> {
+        let failed = |e: &dyn std::fmt::Display| format!("can't watch {}: {}", path.display(), e);
+
+        let canonical = path.canonicalize().map_err(|e| failed(&e))?;
+        let dir = canonical
+            .parent()
+            .ok_or_else(|| failed(&io::Error::from(io::ErrorKind::NotFound)))?
+            .to_path_buf();
+
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender).map_err(|e| failed(&e))?;
+        watcher
+            .watch(&dir, RecursiveMode::NonRecursive)
+            .map_err(|e| failed(&e))?;
+
+        let changed = path.clone();
+        thread::spawn(move || loop {
+            // The watcher hangs up when it's dropped.
+            let event = match events.recv() {
+                Ok(Ok(event)) => event,
+                Ok(Err(_)) => continue,
+                Err(_) => return,
+            };
+            if !touches(&event, &canonical) {
+                continue;
+            }
+
+            loop {
+                match events.recv_timeout(SETTLE_TIME) {
+                    Ok(_) => {}
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+            }
+            if proxy
+                .send_event(UserEvent::Changed(changed.clone()))
+                .is_err()
+            {
+                return;
+            }
+        });
+
+        Ok(Self {
+            path,
+            _watcher: watcher,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Whether an event means the file's contents have changed.
+fn touches(event: &notify::Event, path: &Path) -> bool {
+    matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+        && event.paths.iter().any(|changed| changed == path)
+}